@@ -0,0 +1,64 @@
+//! Criterion benchmarks for the derivation hot path
+//!
+//! Run with `cargo bench --bench fp_code`; add `--features rayon` to include the
+//! parallel batch.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flowerpassword::{fp_code, fp_code_batch};
+use flowerpassword_core::bench::hmac_md5;
+
+fn bench_fp_code(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fp_code");
+    for length in [2, 16, 32] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(length),
+            &length,
+            |b, &length| {
+                b.iter(|| {
+                    fp_code(
+                        black_box("master password"),
+                        black_box("github.com"),
+                        length,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_hmac_md5(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hmac_md5");
+    let base_hash = hmac_md5(b"master password", b"github.com");
+    group.bench_function("password", |b| {
+        b.iter(|| hmac_md5(black_box(b"master password"), black_box(b"github.com")))
+    });
+    group.bench_function("salt", |b| {
+        b.iter(|| hmac_md5(black_box(&base_hash), black_box(b"kise")))
+    });
+    group.bench_function("long_key", |b| {
+        b.iter(|| hmac_md5(black_box(b"master password"), black_box(&[b'k'; 100])))
+    });
+    group.finish();
+}
+
+fn bench_batch(c: &mut Criterion) {
+    let keys: Vec<String> = (0..10_000).map(|i| format!("site{}.example", i)).collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(keys.len() as u64));
+    group.bench_function("sequential", |b| {
+        b.iter(|| fp_code_batch(black_box("master password"), black_box(&keys), 16))
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("rayon", |b| {
+        b.iter(|| {
+            flowerpassword::fp_code_batch_par(black_box("master password"), black_box(&keys), 16)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_fp_code, bench_hmac_md5, bench_batch);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+package com.flowerpassword;
+
+/**
+ * JNI bindings for the Rust flowerpassword library, built with the {@code jni} feature.
+ *
+ * <p>Strings are passed to Rust as UTF-16, so characters outside the Basic Multilingual
+ * Plane (surrogate pairs) derive the same passwords as on every other platform.
+ */
+public final class Native {
+    static {
+        System.loadLibrary("flowerpassword");
+    }
+
+    private Native() {}
+
+    /**
+     * Generates a Flower Password.
+     *
+     * @throws IllegalArgumentException if {@code length} is not between 2 and 32, or an
+     *     argument contains an unpaired surrogate
+     * @throws NullPointerException if {@code password} or {@code key} is null
+     */
+    public static native String fpCode(String password, String key, int length);
+
+    /**
+     * Checks whether {@code candidate} is the Flower Password for {@code password} and
+     * {@code key}, at the candidate's own length.
+     *
+     * @throws IllegalArgumentException if an argument contains an unpaired surrogate
+     * @throws NullPointerException if an argument is null
+     */
+    public static native boolean fpVerify(String password, String key, String candidate);
+}
@@ -0,0 +1,53 @@
+package com.flowerpassword;
+
+/**
+ * Integration checks for {@link Native}, run by the Rust test suite when
+ * {@code FP_JNI_TEST=1} is set and a JDK is installed.
+ */
+public final class NativeTest {
+    private static void check(boolean condition, String message) {
+        if (!condition) {
+            throw new AssertionError(message);
+        }
+    }
+
+    private static void checkEquals(String expected, String actual) {
+        check(expected.equals(actual), "expected " + expected + ", got " + actual);
+    }
+
+    private static void checkThrows(Class<? extends Throwable> type, String message, Runnable call) {
+        try {
+            call.run();
+        } catch (Throwable thrown) {
+            check(type.isInstance(thrown), "expected " + type.getName() + ", got " + thrown);
+            check(message.equals(thrown.getMessage()), "unexpected message: " + thrown.getMessage());
+            return;
+        }
+        throw new AssertionError("expected " + type.getName());
+    }
+
+    public static void main(String[] args) {
+        checkEquals("K3A2a66Bf88b628c", Native.fpCode("password", "key", 16));
+        checkEquals("D04175F7A9c7Ab4a", Native.fpCode("test", "github.com", 16));
+        checkEquals("KFF7FEa7928bAAAa", Native.fpCode("密码", "网站.com", 16));
+        // U+1F338 is a surrogate pair in UTF-16 and a 6-byte sequence in modified UTF-8
+        checkEquals("Kb305852652B6264", Native.fpCode("test", "🌸.example", 16));
+        // Modified UTF-8 would encode the NUL as C0 80
+        checkEquals("K8071E9AE472F0d5", Native.fpCode("test", "a\u0000b", 16));
+
+        check(Native.fpVerify("test", "github.com", "D04175F7A9c7Ab4a"), "verify");
+        check(!Native.fpVerify("test", "gitlab.com", "D04175F7A9c7Ab4a"), "verify mismatch");
+
+        checkThrows(IllegalArgumentException.class, "Length must be between 2 and 32, got: 33",
+                () -> Native.fpCode("password", "key", 33));
+        checkThrows(IllegalArgumentException.class, "Length must be between 2 and 32, got: -1",
+                () -> Native.fpCode("password", "key", -1));
+        checkThrows(IllegalArgumentException.class,
+                "Argument `key` contains an unpaired surrogate at index 0",
+                () -> Native.fpCode("password", "\uD83C", 16));
+        checkThrows(NullPointerException.class, "Argument `password` is null",
+                () -> Native.fpCode(null, "key", 16));
+
+        System.out.println("ok");
+    }
+}
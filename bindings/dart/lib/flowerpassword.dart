@@ -0,0 +1,95 @@
+/// Flower Password for Dart and Flutter, backed by the Rust implementation.
+///
+/// ```dart
+/// final fp = FlowerPassword.open();
+/// fp.code('test', 'github.com'); // 'D04175F7A9c7Ab4a'
+/// ```
+library flowerpassword;
+
+import 'dart:ffi';
+import 'dart:io';
+
+import 'package:ffi/ffi.dart';
+
+import 'src/flowerpassword_bindings.dart';
+
+/// Shortest password the library generates.
+const int minLength = 2;
+
+/// Longest password the library generates.
+const int maxLength = 32;
+
+/// Password length used when none is given.
+const int defaultLength = 16;
+
+/// Generates Flower Passwords through the library's C ABI.
+class FlowerPassword {
+  final FlowerPasswordBindings _bindings;
+
+  /// Uses the already opened flowerpassword [library].
+  FlowerPassword(DynamicLibrary library)
+      : _bindings = FlowerPasswordBindings(library);
+
+  /// Opens the library at [path], or the platform's default library name.
+  ///
+  /// Built with `cargo build --release -p flowerpassword-ffi`, the library is
+  /// `libflowerpassword_ffi.so`, `libflowerpassword_ffi.dylib` or `flowerpassword_ffi.dll`.
+  factory FlowerPassword.open([String? path]) =>
+      FlowerPassword(DynamicLibrary.open(path ?? _defaultLibraryName()));
+
+  static String _defaultLibraryName() {
+    if (Platform.isWindows) return 'flowerpassword_ffi.dll';
+    if (Platform.isMacOS || Platform.isIOS) return 'libflowerpassword_ffi.dylib';
+    return 'libflowerpassword_ffi.so';
+  }
+
+  /// Generates the password for [password] and [key] at [length].
+  ///
+  /// Throws an [ArgumentError] if [length] is not between [minLength] and
+  /// [maxLength], or if [password] or [key] contains a NUL character, which
+  /// cannot be passed through a C string.
+  String code(String password, String key, [int length = defaultLength]) {
+    _checkNoNul(password, 'password');
+    _checkNoNul(key, 'key');
+    if (length < 0) {
+      // size_t cannot carry a negative length, so reject it before the call
+      throw ArgumentError.value(length, 'length', _lengthMessage(length));
+    }
+
+    return using((arena) {
+      // Strings are encoded as UTF-8; the buffer always fits the longest password
+      final passwordPtr = password.toNativeUtf8(allocator: arena).cast<Char>();
+      final keyPtr = key.toNativeUtf8(allocator: arena).cast<Char>();
+      const capacity = maxLength + 1;
+      final out = arena<Char>(capacity);
+
+      final result = _bindings.fp_code_c(passwordPtr, keyPtr, length, out, capacity);
+      switch (result) {
+        case FP_OK:
+          return out.cast<Utf8>().toDartString();
+        case FP_ERROR_INVALID_LENGTH:
+          throw ArgumentError.value(length, 'length', _lastErrorMessage());
+        case FP_ERROR_INVALID_UTF8:
+          // Dart encodes unpaired surrogates as U+FFFD, so this cannot happen
+          throw ArgumentError(_lastErrorMessage());
+        default:
+          throw StateError('fp_code_c failed with $result: ${_lastErrorMessage()}');
+      }
+    });
+  }
+
+  String _lastErrorMessage() {
+    final message = _bindings.fp_last_error_message();
+    return message == nullptr ? '' : message.cast<Utf8>().toDartString();
+  }
+
+  /// Leaves [value] out of the error, since it may be the master password.
+  static void _checkNoNul(String value, String name) {
+    if (value.contains('\u0000')) {
+      throw ArgumentError('Must not contain NUL characters', name);
+    }
+  }
+
+  static String _lengthMessage(int length) =>
+      'Length must be between $minLength and $maxLength, got: $length';
+}
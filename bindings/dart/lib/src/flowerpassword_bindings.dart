@@ -0,0 +1,84 @@
+// ignore_for_file: type=lint
+
+// AUTO GENERATED FILE, DO NOT EDIT.
+//
+// Generated by `package:ffigen`.
+import 'dart:ffi' as ffi;
+
+/// Bindings for the flowerpassword C ABI (include/flowerpassword.h)
+class FlowerPasswordBindings {
+  /// Holds the symbol lookup function.
+  final ffi.Pointer<T> Function<T extends ffi.NativeType>(String symbolName)
+      _lookup;
+
+  /// The symbols are looked up in [dynamicLibrary].
+  FlowerPasswordBindings(ffi.DynamicLibrary dynamicLibrary)
+      : _lookup = dynamicLibrary.lookup;
+
+  /// The symbols are looked up with [lookup].
+  FlowerPasswordBindings.fromLookup(
+      ffi.Pointer<T> Function<T extends ffi.NativeType>(String symbolName)
+          lookup)
+      : _lookup = lookup;
+
+  /// Generates a Flower Password into a caller-provided buffer
+  ///
+  /// On success the password and a NUL terminator are written to `out`, which must
+  /// have room for at least `length + 1` bytes. On failure `out` is left untouched
+  /// and `fp_last_error_message` describes the error.
+  ///
+  /// Returns `FP_OK` or one of the negative `FP_ERROR_*` codes.
+  ///
+  /// # Safety
+  ///
+  /// `password` and `key` must each be null or point to a NUL-terminated string,
+  /// and `out` must be null or point to at least `out_cap` writable bytes.
+  int fp_code_c(
+    ffi.Pointer<ffi.Char> password,
+    ffi.Pointer<ffi.Char> key,
+    int length,
+    ffi.Pointer<ffi.Char> out,
+    int out_cap,
+  ) {
+    return _fp_code_c(
+      password,
+      key,
+      length,
+      out,
+      out_cap,
+    );
+  }
+
+  late final _fp_code_cPtr = _lookup<
+      ffi.NativeFunction<
+          ffi.Int32 Function(ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Char>,
+              ffi.Size, ffi.Pointer<ffi.Char>, ffi.Size)>>('fp_code_c');
+  late final _fp_code_c = _fp_code_cPtr.asFunction<
+      int Function(ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Char>, int,
+          ffi.Pointer<ffi.Char>, int)>();
+
+  /// Returns the message of the calling thread's last failed call, or null
+  ///
+  /// The string is owned by the library and stays valid until the next call to
+  /// `fp_code_c` on the same thread; it must not be freed. A successful call
+  /// clears the message.
+  ffi.Pointer<ffi.Char> fp_last_error_message() {
+    return _fp_last_error_message();
+  }
+
+  late final _fp_last_error_messagePtr =
+      _lookup<ffi.NativeFunction<ffi.Pointer<ffi.Char> Function()>>(
+          'fp_last_error_message');
+  late final _fp_last_error_message = _fp_last_error_messagePtr
+      .asFunction<ffi.Pointer<ffi.Char> Function()>();
+}
+
+const int FP_OK = 0;
+
+const int FP_ERROR_INVALID_LENGTH = -1;
+
+const int FP_ERROR_INVALID_UTF8 = -2;
+
+const int FP_ERROR_BUFFER_TOO_SMALL = -3;
+
+const int FP_ERROR_NULL_POINTER = -4;
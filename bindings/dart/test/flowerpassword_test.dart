@@ -0,0 +1,90 @@
+// Run against a locally built library:
+//
+//   cargo build -p flowerpassword-ffi
+//   FLOWERPASSWORD_LIB=$PWD/../../target/debug/libflowerpassword_ffi.so dart test
+import 'dart:io';
+
+import 'package:flowerpassword/flowerpassword.dart';
+import 'package:test/test.dart';
+
+// A subset of the known-answer vectors in src/vectors.rs
+const vectors = [
+  ('password', 'key', 16, 'K3A2a66Bf88b628c'),
+  ('password', 'key', 2, 'K3'),
+  ('password', 'key', 32, 'K3A2a66Bf88b628c2Cd7cDA9958f6b26'),
+  ('test', 'github.com', 16, 'D04175F7A9c7Ab4a'),
+  ('mypassword', 'example.com', 12, 'K0CA12CecFFB'),
+  ('password', '', 16, 'eB3b1cA3D6B54c00'),
+  ('', '', 16, 'K930B0264e62DDFC'),
+];
+
+void main() {
+  final path = Platform.environment['FLOWERPASSWORD_LIB'];
+  final skip = path == null ? 'Set FLOWERPASSWORD_LIB to the built library' : null;
+  late final FlowerPassword fp;
+
+  setUpAll(() {
+    if (path != null) fp = FlowerPassword.open(path);
+  });
+
+  group('code', () {
+    test('matches the known vectors', () {
+      for (final (password, key, length, expected) in vectors) {
+        expect(fp.code(password, key, length), expected);
+      }
+    });
+
+    test('defaults to length 16', () {
+      expect(fp.code('test', 'github.com'), 'D04175F7A9c7Ab4a');
+    });
+
+    test('encodes strings as UTF-8', () {
+      expect(fp.code('密码', '网站.com', 16), 'KFF7FEa7928bAAAa');
+      expect(fp.code('test', '🌸.example', 16), 'Kb305852652B6264');
+    });
+  }, skip: skip);
+
+  group('errors', () {
+    ArgumentError invalidLength(int length) => ArgumentError.value(length,
+        'length', 'Length must be between 2 and 32, got: $length');
+
+    Matcher throwsArgument(ArgumentError expected) => throwsA(
+        isA<ArgumentError>()
+            .having((e) => e.name, 'name', expected.name)
+            .having((e) => e.invalidValue, 'invalidValue', expected.invalidValue)
+            .having((e) => e.message, 'message', expected.message));
+
+    test('invalid lengths throw ArgumentError', () {
+      for (final length in [0, 1, 33, 100]) {
+        expect(() => fp.code('password', 'key', length),
+            throwsArgument(invalidLength(length)));
+      }
+    });
+
+    test('negative lengths throw ArgumentError', () {
+      expect(() => fp.code('password', 'key', -1),
+          throwsArgument(invalidLength(-1)));
+    });
+
+    test('NUL characters throw ArgumentError', () {
+      expect(() => fp.code('password', 'a\u0000b', 16),
+          throwsA(isA<ArgumentError>().having((e) => e.name, 'name', 'key')));
+    });
+
+    test('NUL errors leave the input out', () {
+      const master = 'hunter2-master\u0000';
+      expect(
+          () => fp.code(master, 'github.com', 16),
+          throwsA(isA<ArgumentError>()
+              .having((e) => e.name, 'name', 'password')
+              .having((e) => e.invalidValue, 'invalidValue', isNull)
+              .having((e) => e.toString(), 'toString()',
+                  isNot(contains('hunter2')))));
+    });
+
+    test('a failed call does not affect the next one', () {
+      expect(() => fp.code('password', 'key', 40), throwsArgumentError);
+      expect(fp.code('password', 'key', 16), 'K3A2a66Bf88b628c');
+    });
+  }, skip: skip);
+}
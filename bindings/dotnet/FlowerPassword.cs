@@ -0,0 +1,112 @@
+// P/Invoke wrapper for the flowerpassword C ABI (src/ffi.rs, include/flowerpassword.h).
+//
+// Strings are marshalled by hand as NUL-terminated UTF-8 byte arrays, so this works
+// on .NET Framework as well as .NET, where UnmanagedType.LPUTF8Str is unavailable or
+// the default string marshalling would use the ANSI code page.
+
+using System;
+using System.Collections.Generic;
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace Flowerpassword
+{
+    /// <summary>Generates Flower Passwords with the Rust implementation.</summary>
+    public static class FlowerPassword
+    {
+        /// <summary>Shortest password the library generates.</summary>
+        public const int MinLength = 2;
+
+        /// <summary>Longest password the library generates.</summary>
+        public const int MaxLength = 32;
+
+        private const string Library = "flowerpassword_ffi";
+
+        // Error codes of fp_code_c
+        private const int Ok = 0;
+        private const int ErrorInvalidLength = -1;
+        private const int ErrorInvalidUtf8 = -2;
+        private const int ErrorBufferTooSmall = -3;
+        private const int ErrorNullPointer = -4;
+
+        [DllImport(Library, EntryPoint = "fp_code_c", CallingConvention = CallingConvention.Cdecl)]
+        private static extern int FpCode(
+            byte[] password, byte[] key, UIntPtr length, byte[] output, UIntPtr outputCapacity);
+
+        [DllImport(Library, EntryPoint = "fp_last_error_message", CallingConvention = CallingConvention.Cdecl)]
+        private static extern IntPtr FpLastErrorMessage();
+
+        /// <summary>Generates the password for <paramref name="password"/> and <paramref name="key"/>.</summary>
+        /// <exception cref="ArgumentNullException"><paramref name="password"/> or <paramref name="key"/> is null.</exception>
+        /// <exception cref="ArgumentOutOfRangeException"><paramref name="length"/> is not between 2 and 32.</exception>
+        /// <exception cref="ArgumentException"><paramref name="password"/> or <paramref name="key"/> contains a NUL character.</exception>
+        public static string Code(string password, string key, int length = 16)
+        {
+            byte[] passwordBytes = ToCString(password, nameof(password));
+            byte[] keyBytes = ToCString(key, nameof(key));
+            if (length < 0)
+            {
+                // size_t cannot carry a negative length, so reject it before the call
+                throw new ArgumentOutOfRangeException(nameof(length), length,
+                    $"Length must be between {MinLength} and {MaxLength}, got: {length}");
+            }
+
+            // The buffer always fits the longest password and its NUL terminator
+            byte[] output = new byte[MaxLength + 1];
+            int result = FpCode(passwordBytes, keyBytes, (UIntPtr)length, output, (UIntPtr)output.Length);
+            switch (result)
+            {
+                case Ok:
+                    return Encoding.ASCII.GetString(output, 0, Array.IndexOf(output, (byte)0));
+                case ErrorInvalidLength:
+                    throw new ArgumentOutOfRangeException(nameof(length), length, LastErrorMessage());
+                case ErrorInvalidUtf8:
+                    // Encoding.UTF8 replaces unpaired surrogates with U+FFFD, so this cannot happen
+                    throw new ArgumentException(LastErrorMessage());
+                case ErrorBufferTooSmall:
+                case ErrorNullPointer:
+                default:
+                    throw new InvalidOperationException($"fp_code_c failed with {result}: {LastErrorMessage()}");
+            }
+        }
+
+        /// <summary>Encodes <paramref name="value"/> as a NUL-terminated UTF-8 string.</summary>
+        private static byte[] ToCString(string value, string name)
+        {
+            if (value == null)
+            {
+                throw new ArgumentNullException(name);
+            }
+            if (value.IndexOf('\0') >= 0)
+            {
+                throw new ArgumentException("Must not contain NUL characters", name);
+            }
+
+            byte[] bytes = new byte[Encoding.UTF8.GetByteCount(value) + 1];
+            Encoding.UTF8.GetBytes(value, 0, value.Length, bytes, 0);
+            return bytes;
+        }
+
+        /// <summary>Reads the library's last error message for this thread.</summary>
+        private static string LastErrorMessage()
+        {
+            IntPtr message = FpLastErrorMessage();
+            if (message == IntPtr.Zero)
+            {
+                return string.Empty;
+            }
+
+            var bytes = new List<byte>();
+            for (int offset = 0; ; offset++)
+            {
+                byte b = Marshal.ReadByte(message, offset);
+                if (b == 0)
+                {
+                    break;
+                }
+                bytes.Add(b);
+            }
+            return Encoding.UTF8.GetString(bytes.ToArray());
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Build script for flowerpassword
+//!
+//! With the `ffi` feature enabled, generates the C header for `src/ffi.rs` into
+//! `include/flowerpassword.h`.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    use std::path::PathBuf;
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml is valid");
+    // Only src/ffi.rs is parsed, so public constants elsewhere stay out of the header
+    cbindgen::Builder::new()
+        .with_src(crate_dir.join("src").join("ffi.rs"))
+        .with_config(config)
+        .generate()
+        .expect("src/ffi.rs can be translated to C")
+        .write_to_file(crate_dir.join("include").join("flowerpassword.h"));
+}
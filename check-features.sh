@@ -0,0 +1,71 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - feature combination builds
+# Cargo unifies features across the packages of one build, so a workspace-wide
+# build never shows whether a package compiles with only the features it asks for.
+# Each combination is therefore built for a single package with `-p`.
+# Usage: ./check-features.sh
+
+check() {
+  echo "cargo check $*"
+  cargo check --quiet --all-targets "$@"
+}
+
+echo "Checking flowerpassword-core..."
+check -p flowerpassword-core --no-default-features --features scheme-v1-md5
+check -p flowerpassword-core --no-default-features --features heapless
+check -p flowerpassword-core --no-default-features --features legacy-md5
+check -p flowerpassword-core --no-default-features --features simd
+check -p flowerpassword-core --no-default-features --features tiny
+check -p flowerpassword-core --no-default-features --features tiny,legacy-md5
+check -p flowerpassword-core --no-default-features --features rustcrypto
+check -p flowerpassword-core --no-default-features --features vendored-md5
+check -p flowerpassword-core --no-default-features --features simd,vendored-md5
+check -p flowerpassword-core --features vendored-md5
+check -p flowerpassword-core
+
+echo "Checking the flowerpassword facade..."
+check -p flowerpassword --no-default-features
+check -p flowerpassword --no-default-features --features heapless
+check -p flowerpassword --no-default-features --features tiny
+check -p flowerpassword --no-default-features --features hardening
+check -p flowerpassword --no-default-features --features std,vendored-md5
+check -p flowerpassword --features scheme-v2-sha256
+check -p flowerpassword --features default-scheme-v2
+check -p flowerpassword --features vendored-md5
+check -p flowerpassword
+check -p flowerpassword --features config-encryption
+check -p flowerpassword --features rayon
+check -p flowerpassword --features simd,rayon
+check -p flowerpassword --features agent,rpc,native-host,binary
+check -p flowerpassword --features memlock
+check -p flowerpassword --no-default-features --features memlock
+check -p flowerpassword --features agent,memlock
+check -p flowerpassword --features python,memlock
+check -p flowerpassword --features strength
+check -p flowerpassword --features python,strength
+check -p flowerpassword --features hibp
+check -p flowerpassword --features blocklist
+check -p flowerpassword --features clipboard
+check -p flowerpassword --features strength,blocklist
+check -p flowerpassword --features pinentry,ssh-agent,yubikey
+check -p flowerpassword --features fido2
+check -p flowerpassword --features secrecy
+check -p flowerpassword --features secrecy,memlock
+check -p flowerpassword --no-default-features --features secrecy
+check -p flowerpassword --all-features
+
+echo "Checking the command line..."
+check -p flowerpassword-cli
+check -p flowerpassword-cli --features tui
+check -p flowerpassword-cli --features autotype
+check -p flowerpassword-cli --features http
+check -p flowerpassword-cli --features grpc
+check -p flowerpassword-cli --features dbus
+
+echo "Testing the facade without default features..."
+cargo test --quiet -p flowerpassword --no-default-features --features heapless,tiny
+
+echo "Testing the in-tree MD5 against the RustCrypto crates..."
+cargo test --quiet -p flowerpassword-core --features vendored-md5
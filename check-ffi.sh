@@ -0,0 +1,36 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - C library check
+# Builds the shared and static C libraries from the flowerpassword-ffi crate, checks
+# that both export the C ABI, and links and runs tests/smoke.c against each.
+# Usage: ./check-ffi.sh   (needs a C compiler as $CC, default cc, and nm as $NM)
+
+CC=${CC:-cc}
+NM=${NM:-nm}
+BIN=target/release
+OUT=$(mktemp -d)
+trap 'rm -rf "$OUT"' EXIT
+
+echo "Building flowerpassword-ffi..."
+cargo build --quiet --release -p flowerpassword-ffi
+
+exports() {
+  if ! "$NM" "$@" --defined-only 2>/dev/null | grep -qw "T $symbol"; then
+    echo "error: ${*: -1} does not export $symbol" >&2
+    exit 1
+  fi
+}
+for symbol in fp_code_c fp_last_error_message; do
+  exports -D "$BIN/libflowerpassword_ffi.so"
+  exports "$BIN/libflowerpassword_ffi.a"
+done
+
+echo "Linking the smoke test against the shared library..."
+"$CC" flowerpassword-ffi/tests/smoke.c -Iinclude -L"$BIN" -lflowerpassword_ffi -o "$OUT/smoke-shared"
+LD_LIBRARY_PATH=$BIN "$OUT/smoke-shared"
+
+echo "Linking the smoke test against the static library..."
+"$CC" flowerpassword-ffi/tests/smoke.c -Iinclude "$BIN/libflowerpassword_ffi.a" \
+  -lpthread -ldl -lm -o "$OUT/smoke-static"
+"$OUT/smoke-static"
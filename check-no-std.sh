@@ -0,0 +1,26 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - no_std check
+# Builds the core without the `std` feature for a bare-metal target, then runs the
+# no_std harness tests on the host.
+# Usage: ./check-no-std.sh [target]
+
+TARGET=${1:-thumbv7em-none-eabihf}
+
+rustup target add "$TARGET"
+
+echo "Checking flowerpassword-core and flowerpassword without std for $TARGET..."
+cargo check -p flowerpassword-core --no-default-features --features scheme-v1-md5 --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features scheme-v2-sha256 --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features rustcrypto --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features heapless --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features legacy-md5 --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features simd --target "$TARGET"
+cargo check -p flowerpassword-core --no-default-features --features tiny --target "$TARGET"
+cargo check -p flowerpassword --no-default-features --target "$TARGET"
+cargo check -p flowerpassword --no-default-features --features heapless,secrecy --target "$TARGET"
+cargo check -p flowerpassword-no-std --target "$TARGET"
+
+echo "Running no_std harness tests..."
+cargo test -p flowerpassword-no-std
@@ -0,0 +1,39 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - derivation scheme combinations
+# Builds and tests flowerpassword-core with each combination of `scheme-v1-md5`
+# and `scheme-v2-sha256`: the build without either must fail with the
+# `compile_error!` naming them, and a build without v1 must not depend on MD5.
+# Usage: ./check-schemes.sh
+
+run() {
+  echo "cargo test -p flowerpassword-core --no-default-features --features $1"
+  cargo test --quiet -p flowerpassword-core --no-default-features --features "$1"
+}
+
+echo "Testing each scheme alone and both together..."
+run std,scheme-v1-md5
+run std,scheme-v2-sha256
+run std,scheme-v1-md5,scheme-v2-sha256
+run std,scheme-v1-md5,default-scheme-v2
+
+echo "Checking that a build without v1 does not depend on MD5..."
+if cargo tree --quiet -p flowerpassword-core --no-default-features --features std,scheme-v2-sha256 \
+  --edges normal --prefix none | grep -E '^md-?5 '; then
+  echo "error: the v2-only build depends on MD5" >&2
+  exit 1
+fi
+
+echo "Checking that a build without any scheme fails clearly..."
+if output=$(cargo check --quiet -p flowerpassword-core --no-default-features 2>&1); then
+  echo "error: the build without a scheme succeeded" >&2
+  exit 1
+fi
+if ! grep -q 'flowerpassword-core needs a derivation scheme' <<<"$output"; then
+  echo "$output" >&2
+  echo "error: the build without a scheme failed for another reason" >&2
+  exit 1
+fi
+
+echo "All scheme combinations OK"
@@ -0,0 +1,38 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - binary size check
+# Builds the bare-metal probes in tests/size with an opt-level "z", LTO, panic=abort
+# release profile: one calling `fp_code` and rendering its error with `Display`, the
+# other calling `fp_code_tiny`. Prints both sizes and fails if the `tiny` build is not
+# smaller or links any `core::fmt` formatting.
+# Usage: ./check-size.sh [target]   (needs llvm-size and llvm-nm, or set SIZE and NM)
+
+TARGET=${1:-thumbv7em-none-eabihf}
+SIZE=${SIZE:-llvm-size}
+NM=${NM:-llvm-nm}
+
+rustup target add "$TARGET"
+
+echo "Building the size probes for $TARGET..."
+cargo build --quiet --release --manifest-path tests/size/Cargo.toml --target "$TARGET"
+
+BIN=tests/size/target/$TARGET/release
+"$SIZE" "$BIN/default" "$BIN/tiny"
+
+text() {
+  "$SIZE" "$1" | awk 'NR == 2 { print $1 }'
+}
+
+DEFAULT=$(text "$BIN/default")
+TINY=$(text "$BIN/tiny")
+echo "tiny saves $((DEFAULT - TINY)) of $DEFAULT bytes of code"
+
+if [ "$TINY" -ge "$DEFAULT" ]; then
+  echo "error: the tiny build is not smaller than the default one" >&2
+  exit 1
+fi
+if "$NM" --demangle "$BIN/tiny" | grep -E 'core::fmt::(write|Formatter)|as core::fmt::'; then
+  echo "error: the tiny build links formatting code" >&2
+  exit 1
+fi
@@ -0,0 +1,48 @@
+#!/bin/bash
+set -e
+
+# Flower Password Rust - WASI build of the CLI
+# Builds `fp` for wasm32-wasip1 with the features WASI can have, then, if
+# wasmtime is on PATH, runs batch, JSON and quiet derivations under it.
+# Usage: ./check-wasi.sh
+
+TARGET=wasm32-wasip1
+
+rustup target add "$TARGET"
+
+echo "Building fp for $TARGET..."
+cargo build -p flowerpassword-cli --target "$TARGET"
+cargo build -p flowerpassword-cli --target "$TARGET" --features qr
+cargo clippy -p flowerpassword-cli --target "$TARGET" --all-targets -- -D warnings
+
+if ! command -v wasmtime > /dev/null; then
+  echo "wasmtime is not installed, skipping the runs under it"
+  exit 0
+fi
+
+FP="target/$TARGET/debug/fp.wasm"
+DIR=$(mktemp -d)
+trap 'rm -rf "$DIR"' EXIT
+printf '[sites.github]\nkey = "github.com"\nlength = 16\n' > "$DIR/config.toml"
+
+expect() {
+  local expected=$1 actual=$2
+  if [ "$actual" != "$expected" ]; then
+    echo "expected: $expected"
+    echo "got:      $actual"
+    exit 1
+  fi
+}
+
+fp() {
+  wasmtime run --dir "$DIR::/work" "$FP" --config /work/config.toml "$@"
+}
+
+echo "Running fp under wasmtime..."
+expect "$(printf 'D04175F7A9c7Ab4a\nKAC5fEf00146FD68')" \
+  "$(printf 'test\ngithub\nkey\n' | fp --batch)"
+expect "$(printf 'D04175F7A9c7Ab4a\nKAC5fEf00146FD68')" \
+  "$(printf 'test\ngithub\nkey\n' | fp --batch --jobs 4 --quiet)"
+expect 'github.com D04175F7A9c7Ab4a' \
+  "$(printf 'test\n' | fp --format '{key} {password}' github)"
+echo "fp runs under wasmtime"
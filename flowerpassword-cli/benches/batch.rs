@@ -0,0 +1,62 @@
+//! Throughput of the streaming `--batch --jobs` pipeline
+//!
+//! Run with `cargo bench -p flowerpassword-cli --bench batch`. Each iteration
+//! streams [`KEYS`] generated keys through `jobs::stream_ordered`, as
+//! `fp --batch --jobs N` does, and writes the passwords to a sink.
+//!
+//! The target, in a release build, is 500,000 keys per second with one job, so
+//! that 10 million hostnames take under 20 seconds, and 1,500,000 with four jobs
+//! on four cores. Four jobs sharing one core are slower than one, from handing
+//! keys between threads.
+
+#![cfg_attr(target_os = "wasi", allow(unused))]
+
+use flowerpassword::FpGenerator;
+use std::io::Write;
+
+// Only the pipeline is used; without the harness its unit tests are left out
+// but their imports are not
+#[path = "../src/jobs.rs"]
+#[allow(dead_code, unused_imports)]
+mod jobs;
+
+/// Keys per iteration
+const KEYS: usize = 100_000;
+
+#[cfg(not(target_os = "wasi"))]
+fn bench_stream(c: &mut criterion::Criterion) {
+    let generator = FpGenerator::new("master password".to_string(), 16).unwrap();
+    let mut group = c.benchmark_group("stream_ordered");
+    group.throughput(criterion::Throughput::Elements(KEYS as u64));
+    group.sample_size(20);
+    for jobs in [1, 4] {
+        group.bench_function(criterion::BenchmarkId::from_parameter(jobs), |b| {
+            b.iter(|| {
+                let mut next = 0;
+                let mut out = std::io::sink();
+                jobs::stream_ordered(
+                    || {
+                        next += 1;
+                        Ok::<_, std::io::Error>(
+                            (next <= KEYS).then(|| format!("site{}.example", next)),
+                        )
+                    },
+                    jobs,
+                    |key| generator.generate(key),
+                    |_, password| writeln!(out, "{}", password),
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(target_os = "wasi"))]
+criterion::criterion_group!(benches, bench_stream);
+#[cfg(not(target_os = "wasi"))]
+criterion::criterion_main!(benches);
+
+/// Criterion does not build for WASI, which has no threads to measure anyway
+#[cfg(target_os = "wasi")]
+fn main() {}
@@ -0,0 +1,21 @@
+//! Build script for `fp`
+//!
+//! With the `grpc` feature enabled, generates the service of `fp serve --grpc`
+//! from `proto/flowerpassword.proto`.
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    generate_service();
+}
+
+#[cfg(feature = "grpc")]
+fn generate_service() {
+    println!("cargo:rerun-if-changed=proto/flowerpassword.proto");
+
+    // The vendored protoc, so that building needs none installed
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc is vendored for this host");
+    std::env::set_var("PROTOC", protoc);
+    tonic_build::configure()
+        .compile_protos(&["proto/flowerpassword.proto"], &["proto"])
+        .expect("proto/flowerpassword.proto can be compiled");
+}
@@ -0,0 +1,169 @@
+//! `fp agent start`, `fp agent stop` and `fp --agent`, a process holding the
+//! master password for clients that should not prompt
+//!
+//! `fp agent start` asks for the master password, listens on the endpoint of
+//! the user, `$XDG_RUNTIME_DIR/flowerpassword/agent.sock` on Unix and the named
+//! pipe `\\.\pipe\flowerpassword-agent-<SID>` on Windows, or the one given
+//! with `--agent-endpoint`. On Unix it then leaves the terminal: the agent goes
+//! on in a session of its own, and the command returns once it answers. With
+//! `--foreground`, and always on Windows, it stays, as a service manager
+//! expects. `fp agent stop` has it wipe the master password and exit.
+//!
+//! `fp --agent KEY` asks the agent for the password instead of reading the
+//! master password. If no agent answers, or it is locked, that is reported and
+//! the master password is read as usual.
+
+use crate::cli::Generate;
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::agent::{
+    self, AgentClient, AgentListener, AgentSession, Duplex, Endpoint, Request, Response,
+    SystemTransport,
+};
+use flowerpassword::Scheme;
+use std::cell::RefCell;
+use std::io;
+use zeroize::Zeroizing;
+
+/// A running agent holding the master password, for `--agent`
+pub(crate) struct Agent {
+    client: RefCell<AgentClient<Box<dyn Duplex>>>,
+}
+
+impl Agent {
+    /// Connects to the agent at `endpoint`, or returns why it cannot serve
+    fn connect(endpoint: &Endpoint) -> Result<Self, String> {
+        let mut client = AgentClient::connect(&mut SystemTransport, endpoint)
+            .map_err(|error| Msg::AgentUnreachable.fill(&[endpoint, &error]))?;
+        match client.request(&Request::Status) {
+            Ok(Response::Status { locked: false }) => Ok(Agent {
+                client: RefCell::new(client),
+            }),
+            Ok(_) => Err(Msg::AgentLocked.fill(&[endpoint])),
+            Err(error) => Err(Msg::AgentUnreachable.fill(&[endpoint, &error])),
+        }
+    }
+}
+
+impl Generate for Agent {
+    fn password(&self, scheme: Scheme, key: &str, length: usize) -> Result<String, CliError> {
+        let request = Request::Derive {
+            key: key.to_string(),
+            length,
+            scheme: Some(scheme),
+        };
+        match self.client.borrow_mut().request(&request)? {
+            Response::Password { password } => Ok(password),
+            Response::Error { message } => Err(CliError::AgentRefused(message)),
+            response => Err(agent::AgentError::Malformed(format!(
+                "Unexpected response to derive: {:?}",
+                response
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Returns the agent at `endpoint` if it answers unlocked, and otherwise tells
+/// `on_notice` why not, so that the master password is read instead
+pub(crate) fn connect(endpoint: Option<&Endpoint>, on_notice: impl FnOnce(&str)) -> Option<Agent> {
+    let Some(endpoint) = endpoint else {
+        on_notice(Msg::AgentNoEndpoint.text());
+        return None;
+    };
+    Agent::connect(endpoint)
+        .map_err(|notice| on_notice(&notice))
+        .ok()
+}
+
+/// Serves `master` on `listener` until `fp agent stop`, in the background
+/// unless `foreground`
+///
+/// `on_started` is called once the agent answers, in the process that returns
+/// to the terminal.
+pub(crate) fn run(
+    listener: AgentListener,
+    mut master: Zeroizing<String>,
+    foreground: bool,
+    on_started: impl FnOnce(&Endpoint),
+) -> Result<(), CliError> {
+    #[cfg(unix)]
+    if !foreground {
+        if !detach()? {
+            // The socket is the agent's to remove now
+            on_started(listener.endpoint());
+            std::mem::forget(listener);
+            return Ok(());
+        }
+        return serve(listener, std::mem::take(&mut *master));
+    }
+    #[cfg(not(unix))]
+    let _ = foreground;
+    on_started(listener.endpoint());
+    serve(listener, std::mem::take(&mut *master))
+}
+
+/// Answers clients with a session holding `master` until one stops the agent
+fn serve(listener: AgentListener, master: String) -> Result<(), CliError> {
+    let mut session = AgentSession::new(master);
+    Ok(agent::serve(listener, &mut session)?)
+}
+
+/// Forks, returning `false` in the parent and `true` in the child, which gets a
+/// session of its own without a terminal and `/dev/null` for standard streams
+///
+/// `fp` runs on a single thread until then, so the child may go on as usual.
+#[cfg(unix)]
+fn detach() -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: no other thread runs, so the child gets a consistent copy
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        _ => return Ok(false),
+    }
+    // SAFETY: setsid only affects this process, which leads no group yet
+    if unsafe { libc::setsid() } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let null = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: both descriptors are open; dup2 replaces the standard one
+        if unsafe { libc::dup2(null.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(true)
+}
+
+/// Stops the agent at `endpoint`, returning `false` if none answers there
+pub(crate) fn stop(endpoint: &Endpoint) -> Result<bool, CliError> {
+    let mut client = match AgentClient::connect(&mut SystemTransport, endpoint) {
+        Ok(client) => client,
+        Err(agent::AgentError::Io(error))
+            if matches!(
+                error.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(error) => return Err(error.into()),
+    };
+    match client.request(&Request::Stop)? {
+        Response::Stopped => {
+            client.wait_closed()?;
+            Ok(true)
+        }
+        Response::Error { message } => Err(CliError::AgentRefused(message)),
+        response => Err(agent::AgentError::Malformed(format!(
+            "Unexpected response to stop: {:?}",
+            response
+        ))
+        .into()),
+    }
+}
@@ -0,0 +1,491 @@
+//! `--autotype`, the password typed into the focused window instead of printed
+//!
+//! After `--delay`, the password is sent as keystrokes, so it never passes
+//! through the clipboard or its history. With `--tab-user` the configured
+//! username and Tab come first and Enter follows, filling a typical login form.
+//!
+//! On Windows and macOS the keystrokes go through enigo and the native input
+//! APIs, which type text in any layout. Elsewhere `fp` runs xdotool under X11 and
+//! ydotool under Wayland, feeding both on standard input so that the password
+//! never shows in a process list. xdotool gets X keysyms, and finds the key and
+//! the Shift or AltGr it needs in the active layout, so ASCII types correctly on
+//! non-US layouts too. ydotool works below layouts, with the key positions of a
+//! US keyboard, holding Shift for shifted characters.
+
+use crate::i18n::{self, Msg};
+use std::error::Error;
+#[cfg(not(any(windows, target_os = "macos")))]
+use std::ffi::OsStr;
+use std::fmt;
+#[cfg(not(any(windows, target_os = "macos")))]
+use std::io::{self, Write};
+#[cfg(not(any(windows, target_os = "macos")))]
+use std::path::{Path, PathBuf};
+#[cfg(not(any(windows, target_os = "macos")))]
+use std::process::{Command, Stdio};
+#[cfg(not(any(windows, target_os = "macos")))]
+use zeroize::Zeroizing;
+
+/// One keystroke of the typed sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stroke {
+    /// The key, with any modifiers, that types this character
+    Char(char),
+    /// The Tab key
+    Tab,
+    /// The Enter key
+    Enter,
+}
+
+/// Error type for typing into the focused window
+#[derive(Debug)]
+pub(crate) enum AutotypeError {
+    /// There is no X11 or Wayland display to type into
+    #[cfg(not(any(windows, target_os = "macos")))]
+    NoDisplay,
+    /// The typing program is not on `PATH`
+    #[cfg(not(any(windows, target_os = "macos")))]
+    NotInstalled(&'static str),
+    /// The backend cannot type this character
+    Unsupported { backend: &'static str, c: char },
+    /// The backend ran and failed, with its message
+    Failed {
+        backend: &'static str,
+        message: String,
+    },
+    /// The typing program could not be started or fed
+    #[cfg(not(any(windows, target_os = "macos")))]
+    Io(io::Error),
+}
+
+impl fmt::Display for AutotypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(not(any(windows, target_os = "macos")))]
+            AutotypeError::NoDisplay => f.write_str(Msg::NoDisplay.text()),
+            #[cfg(not(any(windows, target_os = "macos")))]
+            AutotypeError::NotInstalled(program) => i18n::write(f, Msg::NotInstalled, &[program]),
+            AutotypeError::Unsupported { backend, c } => {
+                i18n::write(f, Msg::CannotType, &[backend, &format_args!("{:?}", c)])
+            }
+            AutotypeError::Failed { backend, message } => {
+                i18n::write(f, Msg::TypingFailed, &[backend, message])
+            }
+            #[cfg(not(any(windows, target_os = "macos")))]
+            AutotypeError::Io(error) => i18n::write(f, Msg::TypingIo, &[error]),
+        }
+    }
+}
+
+impl Error for AutotypeError {}
+
+/// A way to send keystrokes to the focused window
+pub(crate) trait Typist {
+    /// Types `strokes`, in order
+    fn type_strokes(&mut self, strokes: &[Stroke]) -> Result<(), AutotypeError>;
+}
+
+/// Returns the keystrokes for `password`, with `--tab-user` if `tab_user`
+///
+/// `--tab-user` types `username` and Tab before the password, when there is a
+/// username, and Enter after it.
+pub(crate) fn strokes(username: Option<&str>, password: &str, tab_user: bool) -> Vec<Stroke> {
+    let mut strokes = Vec::new();
+    if let (true, Some(username)) = (tab_user, username) {
+        strokes.extend(username.chars().map(Stroke::Char));
+        strokes.push(Stroke::Tab);
+    }
+    strokes.extend(password.chars().map(Stroke::Char));
+    if tab_user {
+        strokes.push(Stroke::Enter);
+    }
+    strokes
+}
+
+/// Returns the typist for this platform and session
+pub(crate) fn system_typist() -> Result<Box<dyn Typist>, AutotypeError> {
+    #[cfg(any(windows, target_os = "macos"))]
+    return Ok(Box::new(Native::new()?));
+    #[cfg(not(any(windows, target_os = "macos")))]
+    match Backend::for_session(
+        std::env::var_os("WAYLAND_DISPLAY").as_deref(),
+        std::env::var_os("DISPLAY").as_deref(),
+    ) {
+        Some(Backend::Xdotool) => Ok(Box::new(Xdotool::default())),
+        Some(Backend::Ydotool) => Ok(Box::new(Ydotool::default())),
+        None => Err(AutotypeError::NoDisplay),
+    }
+}
+
+/// The program that types outside Windows and macOS
+#[cfg(not(any(windows, target_os = "macos")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    /// xdotool, for X11
+    Xdotool,
+    /// ydotool, for Wayland, where X11 clients cannot type into other windows
+    Ydotool,
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl Backend {
+    /// Picks the backend for a session with `$WAYLAND_DISPLAY` set to `wayland`
+    /// and `$DISPLAY` set to `x11`
+    pub(crate) fn for_session(wayland: Option<&OsStr>, x11: Option<&OsStr>) -> Option<Backend> {
+        let set = |value: Option<&OsStr>| value.is_some_and(|value| !value.is_empty());
+        if set(wayland) {
+            Some(Backend::Ydotool)
+        } else if set(x11) {
+            Some(Backend::Xdotool)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the X keysym name for `c`, or `None` for control characters
+#[cfg(not(any(windows, target_os = "macos")))]
+pub(crate) fn keysym(c: char) -> Option<String> {
+    let name = match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' => return Some(c.to_string()),
+        ' ' => "space",
+        '!' => "exclam",
+        '"' => "quotedbl",
+        '#' => "numbersign",
+        '$' => "dollar",
+        '%' => "percent",
+        '&' => "ampersand",
+        '\'' => "apostrophe",
+        '(' => "parenleft",
+        ')' => "parenright",
+        '*' => "asterisk",
+        '+' => "plus",
+        ',' => "comma",
+        '-' => "minus",
+        '.' => "period",
+        '/' => "slash",
+        ':' => "colon",
+        ';' => "semicolon",
+        '<' => "less",
+        '=' => "equal",
+        '>' => "greater",
+        '?' => "question",
+        '@' => "at",
+        '[' => "bracketleft",
+        '\\' => "backslash",
+        ']' => "bracketright",
+        '^' => "asciicircum",
+        '_' => "underscore",
+        '`' => "grave",
+        '{' => "braceleft",
+        '|' => "bar",
+        '}' => "braceright",
+        '~' => "asciitilde",
+        c if c.is_control() => return None,
+        // X maps every other Unicode character to a keysym of this form
+        c => return Some(format!("U{:04X}", u32::from(c))),
+    };
+    Some(name.to_string())
+}
+
+/// Returns the xdotool script that types `strokes`
+#[cfg(not(any(windows, target_os = "macos")))]
+pub(crate) fn xdotool_script(strokes: &[Stroke]) -> Result<Zeroizing<String>, AutotypeError> {
+    let mut script = Zeroizing::new(String::from("key --clearmodifiers"));
+    for stroke in strokes {
+        script.push(' ');
+        match stroke {
+            Stroke::Char(c) => {
+                let name = keysym(*c).ok_or(AutotypeError::Unsupported {
+                    backend: "xdotool",
+                    c: *c,
+                })?;
+                script.push_str(&name);
+            }
+            Stroke::Tab => script.push_str("Tab"),
+            Stroke::Enter => script.push_str("Return"),
+        }
+    }
+    script.push('\n');
+    Ok(script)
+}
+
+/// Returns the text ydotool types for `strokes`, Tab and Enter included
+#[cfg(not(any(windows, target_os = "macos")))]
+pub(crate) fn ydotool_text(strokes: &[Stroke]) -> Result<Zeroizing<String>, AutotypeError> {
+    let mut text = Zeroizing::new(String::new());
+    for stroke in strokes {
+        match stroke {
+            // ydotool only knows the characters of a US keyboard
+            Stroke::Char(c) if c.is_ascii() && !c.is_ascii_control() => text.push(*c),
+            Stroke::Char(c) => {
+                return Err(AutotypeError::Unsupported {
+                    backend: "ydotool",
+                    c: *c,
+                })
+            }
+            Stroke::Tab => text.push('\t'),
+            Stroke::Enter => text.push('\n'),
+        }
+    }
+    Ok(text)
+}
+
+/// A [`Typist`] running `xdotool -`, which reads its commands from standard input
+#[cfg(not(any(windows, target_os = "macos")))]
+#[derive(Debug, Clone)]
+pub(crate) struct Xdotool {
+    program: PathBuf,
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl Default for Xdotool {
+    fn default() -> Self {
+        Xdotool {
+            program: PathBuf::from("xdotool"),
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl Typist for Xdotool {
+    fn type_strokes(&mut self, strokes: &[Stroke]) -> Result<(), AutotypeError> {
+        let script = xdotool_script(strokes)?;
+        feed(&self.program, "xdotool", &["-"], script.as_bytes())
+    }
+}
+
+/// A [`Typist`] running `ydotool type --file -`, which types standard input
+#[cfg(not(any(windows, target_os = "macos")))]
+#[derive(Debug, Clone)]
+pub(crate) struct Ydotool {
+    program: PathBuf,
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl Default for Ydotool {
+    fn default() -> Self {
+        Ydotool {
+            program: PathBuf::from("ydotool"),
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+impl Typist for Ydotool {
+    fn type_strokes(&mut self, strokes: &[Stroke]) -> Result<(), AutotypeError> {
+        let text = ydotool_text(strokes)?;
+        feed(
+            &self.program,
+            "ydotool",
+            &["type", "--file", "-"],
+            text.as_bytes(),
+        )
+    }
+}
+
+/// Runs `program` with `args` and `input` on its standard input
+#[cfg(not(any(windows, target_os = "macos")))]
+fn feed(
+    program: &Path,
+    backend: &'static str,
+    args: &[&str],
+    input: &[u8],
+) -> Result<(), AutotypeError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => AutotypeError::NotInstalled(backend),
+            _ => AutotypeError::Io(error),
+        })?;
+    // A password is far below a pipe buffer, so this cannot block on stderr;
+    // the pipe is closed at the end of the statement, ending the input
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input)
+        .map_err(AutotypeError::Io)?;
+    let output = child.wait_with_output().map_err(AutotypeError::Io)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AutotypeError::Failed {
+            backend,
+            message: match stderr.trim() {
+                "" => output.status.to_string(),
+                message => message.to_string(),
+            },
+        });
+    }
+    Ok(())
+}
+
+/// A [`Typist`] sending keystrokes through the native input APIs
+#[cfg(any(windows, target_os = "macos"))]
+pub(crate) struct Native(enigo::Enigo);
+
+#[cfg(any(windows, target_os = "macos"))]
+impl Native {
+    fn new() -> Result<Self, AutotypeError> {
+        enigo::Enigo::new(&enigo::Settings::default())
+            .map(Native)
+            .map_err(|error| AutotypeError::Failed {
+                backend: "enigo",
+                message: error.to_string(),
+            })
+    }
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+impl Typist for Native {
+    fn type_strokes(&mut self, strokes: &[Stroke]) -> Result<(), AutotypeError> {
+        use enigo::{Direction, Key, Keyboard};
+
+        for stroke in strokes {
+            let typed = match stroke {
+                // enigo would press Enter for a newline in a username
+                Stroke::Char(c) if c.is_control() => {
+                    return Err(AutotypeError::Unsupported {
+                        backend: "enigo",
+                        c: *c,
+                    })
+                }
+                Stroke::Char(c) => self.0.text(c.encode_utf8(&mut [0; 4])),
+                Stroke::Tab => self.0.key(Key::Tab, Direction::Click),
+                Stroke::Enter => self.0.key(Key::Return, Direction::Click),
+            };
+            typed.map_err(|error| AutotypeError::Failed {
+                backend: "enigo",
+                message: error.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A [`Typist`] remembering what it was asked to type
+    #[derive(Default)]
+    struct Recorder(Vec<Stroke>);
+
+    impl Typist for Recorder {
+        fn type_strokes(&mut self, strokes: &[Stroke]) -> Result<(), AutotypeError> {
+            self.0.extend_from_slice(strokes);
+            Ok(())
+        }
+    }
+
+    fn chars(text: &str) -> Vec<Stroke> {
+        text.chars().map(Stroke::Char).collect()
+    }
+
+    #[test]
+    fn test_password_only() {
+        let mut typist = Recorder::default();
+        let strokes = strokes(Some("octocat"), "D04175F7A9c7Ab4a", false);
+        typist.type_strokes(&strokes).unwrap();
+        assert_eq!(typist.0, chars("D04175F7A9c7Ab4a"));
+    }
+
+    #[test]
+    fn test_tab_user_choreography() {
+        let mut typist = Recorder::default();
+        typist
+            .type_strokes(&strokes(Some("octocat"), "D04175F7A9c7Ab4a", true))
+            .unwrap();
+        let mut expected = chars("octocat");
+        expected.push(Stroke::Tab);
+        expected.extend(chars("D04175F7A9c7Ab4a"));
+        expected.push(Stroke::Enter);
+        assert_eq!(typist.0, expected);
+
+        // Without a username there is nothing to tab from
+        let mut expected = chars("KAC5fEf0");
+        expected.push(Stroke::Enter);
+        assert_eq!(strokes(None, "KAC5fEf0", true), expected);
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn test_backend_for_session() {
+        let set = Some(OsStr::new(":0"));
+        let empty = Some(OsStr::new(""));
+        assert_eq!(
+            Backend::for_session(Some(OsStr::new("wayland-0")), set),
+            Some(Backend::Ydotool)
+        );
+        assert_eq!(Backend::for_session(None, set), Some(Backend::Xdotool));
+        assert_eq!(Backend::for_session(empty, set), Some(Backend::Xdotool));
+        assert_eq!(Backend::for_session(None, empty), None);
+        assert_eq!(Backend::for_session(None, None), None);
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn test_keysyms_cover_ascii() {
+        for c in ' '..='~' {
+            let name = keysym(c).unwrap();
+            assert!(
+                name.chars().all(|c| c.is_ascii_alphanumeric()),
+                "{:?} -> {:?}",
+                c,
+                name
+            );
+        }
+        assert_eq!(keysym('K').as_deref(), Some("K"));
+        assert_eq!(keysym('@').as_deref(), Some("at"));
+        assert_eq!(keysym('~').as_deref(), Some("asciitilde"));
+        assert_eq!(keysym('é').as_deref(), Some("U00E9"));
+        assert_eq!(keysym('\t'), None);
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn test_xdotool_script() {
+        let script = xdotool_script(&strokes(Some("me@x.org"), "K3A2", true)).unwrap();
+        assert_eq!(
+            *script,
+            "key --clearmodifiers m e at x period o r g Tab K 3 A 2 Return\n"
+        );
+        assert!(matches!(
+            xdotool_script(&[Stroke::Char('\n')]),
+            Err(AutotypeError::Unsupported {
+                backend: "xdotool",
+                c: '\n'
+            })
+        ));
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn test_ydotool_text() {
+        let text = ydotool_text(&strokes(Some("me@x.org"), "K3A2", true)).unwrap();
+        assert_eq!(*text, "me@x.org\tK3A2\n");
+        assert!(matches!(
+            ydotool_text(&strokes(Some("José"), "K3A2", true)),
+            Err(AutotypeError::Unsupported {
+                backend: "ydotool",
+                c: 'é'
+            })
+        ));
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn test_missing_program() {
+        let mut typist = Xdotool {
+            program: PathBuf::from("/nonexistent/xdotool"),
+        };
+        assert!(matches!(
+            typist.type_strokes(&chars("K3A2")),
+            Err(AutotypeError::NotInstalled("xdotool"))
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+//! `--check-blocklist`, a warning for master and derived passwords that are
+//! common passwords
+//!
+//! The check is offline: the list bundled with `flowerpassword::blocklist`,
+//! merged with the `--blocklist` files of one entry per line. Entries and
+//! passwords are compared trimmed and lowercased. As with `--check-pwned`, a
+//! listed password is still printed, with a warning.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::blocklist::Blocklist;
+use std::path::PathBuf;
+
+/// Returns the built-in blocklist merged with the lists in `files`
+pub(crate) fn load(files: &[PathBuf]) -> Result<Blocklist, CliError> {
+    let mut blocklist = Blocklist::builtin();
+    for path in files {
+        let list = Blocklist::from_file(path).map_err(|source| CliError::Blocklist {
+            path: path.clone(),
+            source,
+        })?;
+        blocklist.merge(list);
+    }
+    Ok(blocklist)
+}
+
+/// Returns the warning for the password of `name` if `blocklist` lists it
+pub(crate) fn check(blocklist: &Blocklist, name: &str, password: &str) -> Option<String> {
+    blocklist
+        .contains(password)
+        .then(|| Msg::Blocklisted.fill(&[&name]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "Company2024  \r\n\r\nD04175F7A9c7Ab4a\r\n").unwrap();
+
+        let blocklist = load(&[path]).unwrap();
+        assert!(blocklist.contains("letmein"));
+        assert!(blocklist.contains("company2024"));
+        assert_eq!(
+            check(&blocklist, "github", "D04175F7A9c7Ab4a").as_deref(),
+            Some("The password of github is on the blocklist of common passwords")
+        );
+        assert_eq!(
+            check(&load(&[]).unwrap(), "github", "D04175F7A9c7Ab4a"),
+            None
+        );
+
+        let missing = dir.path().join("missing.txt");
+        let error = load(&[missing]).unwrap_err();
+        assert!(matches!(error, CliError::Blocklist { .. }));
+        assert!(error.to_string().starts_with("Cannot read the blocklist "));
+    }
+}
@@ -0,0 +1,2391 @@
+//! Command-line arguments and the derivation loop
+
+use crate::agent::{self, Agent};
+#[cfg(feature = "autotype")]
+use crate::autotype;
+use crate::blocklist;
+use crate::color::{self, ColorChoice};
+use crate::completions;
+use crate::config::{self, Lint};
+use crate::copy;
+use crate::doctor;
+use crate::duration;
+use crate::entropy;
+use crate::erase::{self, Display};
+use crate::error::CliError;
+use crate::export::{self, ExportFormat};
+use crate::fido2::{self, Fido2Tools};
+use crate::find;
+use crate::fingerprint;
+use crate::gpg::{self, Gpg};
+use crate::history::{self, Record};
+use crate::i18n::{Lang, Msg};
+use crate::jobs::{self, Stats};
+#[cfg(feature = "keyring")]
+use crate::keyring::{self, Keyring, OsKeyring};
+use crate::launcher;
+use crate::list::{self, ListFormat, SortKey};
+use crate::man;
+#[cfg(unix)]
+use crate::mask::{self, MaskStyle, RawGuard, Tty};
+#[cfg(unix)]
+use crate::master::Prompter;
+use crate::master::{self, Confirm, Terminal};
+use crate::menu::{self, Rofi};
+use crate::native_host::{self, BrowserArg};
+use crate::output::{self, Records};
+use crate::pass::{self, Pass, PassError, Runner};
+use crate::pwned;
+#[cfg(feature = "qr")]
+use crate::qr::{self, QrFormat};
+use crate::repl::{self, Session};
+use crate::selftest;
+#[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+use crate::serve;
+use crate::sites;
+use crate::stdio;
+use crate::template::Template;
+#[cfg(feature = "tui")]
+use crate::tui;
+#[cfg(unix)]
+use crate::unlock::{self, Cache};
+use crate::uri;
+use crate::yubikey::{self, Ykchalresp};
+use clap::builder::{NonEmptyStringValueParser, RangedU64ValueParser};
+use clap::{Parser, Subcommand};
+use directories::BaseDirs;
+use flowerpassword::agent::{AgentListener, Endpoint};
+use flowerpassword::blocklist::Blocklist;
+use flowerpassword::clipboard::{copy_with_ttl, CommandClipboard, DEFAULT_TTL};
+use flowerpassword::config::{
+    config_paths, decrypt_file, encrypt_file, is_encrypted, load_layered_with_passphrase,
+    user_config_path, Config, ConfigError, Diagnostic, SiteConfig,
+};
+use flowerpassword::hibp::Curl;
+use flowerpassword::policy::{shortest_length_satisfying, Policy};
+#[cfg(unix)]
+use flowerpassword::ssh_agent::{self, AgentClient};
+use flowerpassword::uri::parse_fp_uri;
+use flowerpassword::{fp_fingerprint, FpGenerator, Scheme};
+use flowerpassword_core::{MAX_LENGTH, MIN_LENGTH};
+use std::io::{self, IsTerminal, Write};
+#[cfg(any(feature = "grpc", feature = "http"))]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use zeroize::Zeroizing;
+
+/// Derive Flower Passwords from a master password and site keys
+#[derive(Debug, Parser)]
+#[command(
+    name = "fp",
+    version,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
+#[cfg_attr(unix, command(group = clap::ArgGroup::new("masked_display").multiple(true)))]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
+    /// Site names from the configuration, or literal keys
+    #[arg(required_unless_present_any = ["batch", "interactive"])]
+    pub(crate) keys: Vec<String>,
+
+    /// Password length, instead of the configured one
+    #[arg(short, long, value_parser = length_parser())]
+    pub(crate) length: Option<usize>,
+
+    /// Fail unless the password meets these comma-separated requirements:
+    /// require-digit, require-upper, require-lower
+    #[arg(long, value_name = "REQUIREMENTS", conflicts_with = "interactive")]
+    pub(crate) policy: Option<Policy>,
+
+    /// Derive at length 32 and output the shortest prefix, of at least --min
+    /// characters, that meets the --policy; the length chosen is reported
+    #[arg(long, requires = "policy", conflicts_with = "length")]
+    pub(crate) find_length: bool,
+
+    /// Shortest length --find-length tries, instead of the configured one
+    #[arg(long, value_name = "N", value_parser = length_parser(), requires = "find_length")]
+    pub(crate) min: Option<usize>,
+
+    /// Configuration file to read instead of the user's
+    #[arg(long, value_name = "PATH", global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Read site names or keys from standard input, one per line, after the
+    /// master password when it is not a terminal
+    #[arg(long, conflicts_with = "keys")]
+    pub(crate) batch: bool,
+
+    /// Derive the --batch keys on N threads, streaming them in their order,
+    /// with a progress bar on a terminal
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        requires = "batch",
+        conflicts_with_all = ["keys", "entropy", "show_timeout"]
+    )]
+    pub(crate) jobs: Option<usize>,
+
+    /// Print how many --batch keys were derived per second, at the end
+    #[arg(long, requires = "batch", conflicts_with_all = ["keys", "interactive"])]
+    pub(crate) stats: bool,
+
+    /// Read the master password once, then keys and :commands line by line
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["keys", "batch", "format", "null", "newline", "no_newline", "raw"]
+    )]
+    pub(crate) interactive: bool,
+
+    /// Ask for the master password twice; the default on a terminal without
+    /// --batch
+    #[arg(long, overrides_with = "no_confirm", conflicts_with = "master_source")]
+    pub(crate) confirm: bool,
+
+    /// Ask for the master password once
+    #[arg(long)]
+    pub(crate) no_confirm: bool,
+
+    /// Read the master password from the first line of a file
+    #[arg(long, value_name = "PATH", group = "master_source")]
+    pub(crate) password_file: Option<PathBuf>,
+
+    /// Accept a --password-file that other users may read
+    #[arg(long, requires = "password_file")]
+    pub(crate) insecure_perms: bool,
+
+    /// Read the master password from the first line of an inherited file
+    /// descriptor
+    #[cfg(unix)]
+    #[arg(long, value_name = "N", group = "master_source")]
+    pub(crate) password_fd: Option<i32>,
+
+    /// Read the master password from the first line of a gpg-encrypted file
+    #[arg(long, value_name = "PATH", group = "master_source")]
+    pub(crate) password_gpg: Option<PathBuf>,
+
+    /// Ask a pinentry program for the master password: the first pinentry on
+    /// PATH, or PROGRAM with --pinentry=PROGRAM
+    #[arg(
+        long,
+        value_name = "PROGRAM",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto",
+        group = "master_source"
+    )]
+    pub(crate) pinentry: Option<String>,
+
+    /// Derive the master password from a device: fido2, or fido2:LABEL for
+    /// another one from the same security key; yubikey, or yubikey:SLOT for
+    /// slot 1 rather than 2; ssh-agent, or ssh-agent:FINGERPRINT among several
+    /// Ed25519 keys
+    #[arg(long, value_name = "SOURCE", group = "master_source")]
+    pub(crate) master: Option<String>,
+
+    /// Label of the challenge sent by --master yubikey, for another master
+    /// password from the same slot [default: default]
+    #[arg(long, value_name = "LABEL", requires = "master")]
+    pub(crate) yubikey_label: Option<String>,
+
+    /// Ask the agent of `fp agent start` for the passwords, reading the master
+    /// password as usual if no agent answers unlocked
+    #[arg(long, conflicts_with_all = ["interactive", "jobs"])]
+    pub(crate) agent: bool,
+
+    /// Where `fp agent`, --agent and `fp doctor` find the agent: a socket path,
+    /// or a named pipe \\.\pipe\NAME on Windows, instead of the user's default
+    #[arg(long, value_name = "ENDPOINT", global = true)]
+    pub(crate) agent_endpoint: Option<Endpoint>,
+
+    /// Read the master password from the OS keyring, prompting if it cannot
+    #[cfg(feature = "keyring")]
+    #[arg(long, group = "master_source")]
+    pub(crate) use_keyring: bool,
+
+    /// Take no chances: the master password only from --password-fd or
+    /// --pinentry, confirmed, and shown --masked without copying; no --log, and
+    /// no standard output redirected to a file
+    #[cfg(unix)]
+    #[arg(long, group = "masked_display")]
+    pub(crate) paranoid: bool,
+
+    /// Output template with {key}, {password}, {length}, {scheme} and
+    /// {username}; {{ and }} are literal braces
+    #[arg(long, value_name = "TEMPLATE", value_parser = Template::parse)]
+    pub(crate) format: Option<Template>,
+
+    /// End every output record with NUL instead of a newline
+    #[arg(short = '0', long)]
+    pub(crate) null: bool,
+
+    /// Terminate the last record, even when piped
+    #[arg(long, overrides_with = "no_newline")]
+    pub(crate) newline: bool,
+
+    /// Leave the last record unterminated, even on a terminal
+    #[arg(long, overrides_with = "newline")]
+    pub(crate) no_newline: bool,
+
+    /// Print the passwords and nothing else: no trailing newline unless
+    /// --newline, and no decoration even on a terminal
+    #[arg(long, conflicts_with_all = ["entropy", "show_timeout"])]
+    pub(crate) raw: bool,
+
+    /// Print nothing but the passwords and errors, leaving out warnings and
+    /// notices
+    #[arg(short, long, global = true)]
+    pub(crate) quiet: bool,
+
+    /// Color digits, uppercase and lowercase letters and symbols apart
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub(crate) color: ColorChoice,
+
+    /// Language of the prompts and messages, instead of the one of $LANG
+    #[arg(long, value_enum, global = true)]
+    pub(crate) lang: Option<Lang>,
+
+    /// Erase the passwords from the terminal after this long, such as 10s
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = duration::parse_duration,
+        conflicts_with_all = ["batch", "interactive", "null", "no_newline"]
+    )]
+    pub(crate) show_timeout: Option<Duration>,
+
+    /// Show the password masked on the terminal, with keys to reveal or copy it
+    #[cfg(unix)]
+    #[arg(
+        long,
+        group = "masked_display",
+        conflicts_with_all = [
+            "batch",
+            "interactive",
+            "format",
+            "null",
+            "show_timeout",
+            "raw",
+            "show_user",
+            "copy",
+            "copy_user_then_pass"
+        ]
+    )]
+    pub(crate) masked: bool,
+
+    /// Characters --masked leaves visible at each end, as FIRST+LAST or none
+    #[cfg(unix)]
+    #[arg(
+        long,
+        value_name = "STYLE",
+        default_value_t = MaskStyle::default(),
+        value_parser = mask::parse_mask_style,
+        requires = "masked_display"
+    )]
+    pub(crate) mask_style: MaskStyle,
+
+    /// Type the password into the focused window instead of printing it
+    #[cfg(feature = "autotype")]
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "batch",
+            "interactive",
+            "format",
+            "null",
+            "show_timeout",
+            "show_user",
+            "copy",
+            "copy_user_then_pass"
+        ]
+    )]
+    pub(crate) autotype: bool,
+
+    /// How long --autotype waits before typing, to focus the window, such as 5s
+    #[cfg(feature = "autotype")]
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "3s",
+        value_parser = duration::parse_duration,
+        requires = "autotype"
+    )]
+    pub(crate) delay: Duration,
+
+    /// Have --autotype type the configured username and Tab first, and Enter last
+    #[cfg(feature = "autotype")]
+    #[arg(long, requires = "autotype")]
+    pub(crate) tab_user: bool,
+
+    /// Print the username configured for the site and a Tab before the password
+    #[arg(long, conflicts_with_all = ["format", "interactive"])]
+    pub(crate) show_user: bool,
+
+    /// Copy the password instead of printing it, and restore the clipboard after
+    /// 30s
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "batch",
+            "interactive",
+            "format",
+            "null",
+            "show_timeout",
+            "show_user",
+            "copy_user_then_pass"
+        ]
+    )]
+    pub(crate) copy: bool,
+
+    /// Copy the username configured for the site, then the password once Enter
+    /// is pressed or after 5s, instead of printing the password
+    #[arg(
+        long,
+        conflicts_with_all = ["batch", "interactive", "format", "null", "show_timeout", "show_user"]
+    )]
+    pub(crate) copy_user_then_pass: bool,
+
+    /// Append the site, length and time of each derived password to the history,
+    /// never the password
+    #[arg(long, conflicts_with = "interactive")]
+    pub(crate) log: bool,
+
+    /// Print an estimate of each password's strength to standard error
+    #[arg(long, conflicts_with = "interactive")]
+    pub(crate) entropy: bool,
+
+    /// Warn about passwords found in Pwned Passwords, sending only the start of
+    /// their SHA-1 hash over curl
+    #[arg(long, conflicts_with = "interactive")]
+    pub(crate) check_pwned: bool,
+
+    /// Warn about master and derived passwords on the built-in blocklist of
+    /// common passwords
+    #[arg(long, conflicts_with = "interactive")]
+    pub(crate) check_blocklist: bool,
+
+    /// Add the passwords listed in FILE, one per line, to the blocklist of
+    /// --check-blocklist
+    #[arg(long, value_name = "FILE", requires = "check_blocklist")]
+    pub(crate) blocklist: Vec<PathBuf>,
+
+    /// Show the password as a QR code instead of printing it
+    #[cfg(feature = "qr")]
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "batch",
+            "interactive",
+            "format",
+            "null",
+            "show_timeout",
+            "show_user",
+            "copy",
+            "copy_user_then_pass"
+        ]
+    )]
+    pub(crate) qr: bool,
+
+    /// How to render --qr
+    #[cfg(feature = "qr")]
+    #[arg(long, value_enum, default_value_t = QrFormat::Ansi, requires = "qr")]
+    pub(crate) qr_format: QrFormat,
+
+    /// File to write the SVG of --qr-format svg to
+    #[cfg(feature = "qr")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "qr",
+        required_if_eq("qr_format", "svg")
+    )]
+    pub(crate) qr_out: Option<PathBuf>,
+
+    /// Encode the flowerpassword://derive link for the key instead of the password
+    #[cfg(feature = "qr")]
+    #[arg(long, requires = "qr")]
+    pub(crate) qr_uri: bool,
+}
+
+/// Commands other than deriving passwords
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Print the completion script for a shell to standard output
+    Completions {
+        /// Shell to complete for
+        shell: clap_complete::Shell,
+    },
+    /// List the configured sites with their keys and lengths, never passwords
+    List {
+        /// How to print the sites
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        output: ListFormat,
+        /// Show only sites whose name or key contains this, ignoring case
+        #[arg(long, value_name = "SUBSTR")]
+        filter: Option<String>,
+        /// Order of the sites
+        #[arg(long, value_enum, default_value_t = SortKey::Name)]
+        sort: SortKey,
+        /// Also show the notes of each site
+        #[arg(short, long)]
+        long: bool,
+    },
+    /// Show everything about a site or alias except its password
+    Show {
+        /// Name of the site, or an alias of it
+        name: String,
+    },
+    /// Set the notes of a site in the configuration file
+    Note {
+        /// Name of the site
+        name: String,
+        /// Notes, stored as typed
+        text: String,
+        /// Add the text as a new line instead of replacing the notes
+        #[arg(long)]
+        append: bool,
+    },
+    /// Fuzzy-search the configured sites by name, key, alias and notes
+    Find {
+        /// Characters to look for, in order
+        query: String,
+        /// Derive the password of the best match, asking which one if several
+        /// match about as well
+        #[arg(long)]
+        select: bool,
+    },
+    /// Script filter for Raycast and Alfred: list matching sites as JSON, or
+    /// copy the password of the picked one
+    #[command(group = clap::ArgGroup::new("mode").required(true))]
+    Launcher {
+        /// Print the sites fuzzy-matching this as script filter items
+        #[arg(long, value_name = "TEXT", group = "mode")]
+        query: Option<String>,
+        /// Copy the password for the site of this item's arg
+        #[arg(long, value_name = "ARG", group = "mode")]
+        action: Option<String>,
+    },
+    /// Site names for rofi or dmenu, one per line, or copy the password of
+    /// the picked one
+    Menu {
+        /// Copy the password of this site, as picked from the list
+        #[arg(long, value_name = "NAME", conflicts_with = "rofi")]
+        selected: Option<String>,
+        /// Run rofi on the list and copy the password of the picked site
+        #[arg(long)]
+        rofi: bool,
+    },
+    /// Add a site to the configuration file
+    Add {
+        /// Name to type for the site
+        name: String,
+        /// Key to derive with, instead of the name
+        #[arg(long, value_parser = NonEmptyStringValueParser::new())]
+        key: Option<String>,
+        /// Password length, instead of the default
+        #[arg(long, value_parser = length_parser())]
+        length: Option<usize>,
+        /// Derivation scheme, v1 or v2, instead of the default
+        #[arg(long, value_parser = scheme_parser)]
+        scheme: Option<Scheme>,
+        /// Replace the site if the file configures it already
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove a site from the configuration file, after asking
+    Rm {
+        /// Name of the site
+        name: String,
+        /// Do not ask
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Rename a site of the configuration file, keeping its key and so its
+    /// password, and the aliases pointing at it
+    Rename {
+        /// Current name of the site
+        old: String,
+        /// New name of the site
+        new: String,
+        /// Derive with the new name as the key too, which changes the password
+        #[arg(long)]
+        also_key: bool,
+        /// Replace the site configured under the new name, if any
+        #[arg(long)]
+        force: bool,
+        /// Do not ask before --also-key changes the password
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Manage short names for sites in the configuration file
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    /// Derive every configured site and report those sharing a password,
+    /// never printing the passwords
+    Audit {
+        /// Report sites that derive the same password; the only check for now,
+        /// and the one run without any
+        #[arg(long)]
+        check_reuse: bool,
+    },
+    /// Write every configured site with its password, for importing into
+    /// another password manager
+    #[command(group = clap::ArgGroup::new("destination").required(true))]
+    Export {
+        /// What to write
+        #[arg(long, value_enum, default_value_t = ExportFormat::BrowserCsv)]
+        format: ExportFormat,
+        /// File to write, created readable by its owner only
+        #[arg(long, value_name = "PATH", group = "destination")]
+        out: Option<PathBuf>,
+        /// Write to standard output instead, even a terminal
+        #[arg(long, group = "destination")]
+        stdout: bool,
+        /// Group to put the entries of the KeePass formats in
+        #[arg(long, value_name = "NAME", default_value = export::DEFAULT_GROUP)]
+        group: String,
+        /// Do not ask before writing the passwords to --out
+        #[arg(short, long, conflicts_with = "stdout")]
+        yes: bool,
+    },
+    /// Add a site for every login of a browser's password CSV, keyed by its
+    /// domain; the passwords in it are ignored
+    Import {
+        /// CSV exported by the browser, with a `url` column
+        path: PathBuf,
+        /// Import other accounts on a configured domain as NAME-2 and so on
+        #[arg(long)]
+        allow_duplicates: bool,
+    },
+    /// Check the configuration, clipboard, keyring, agent and terminal
+    Doctor,
+    /// Check that this build reproduces the known-answer vectors
+    SelfTest,
+    /// Inspect and maintain the configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the short fingerprint of the master password
+    Fingerprint {
+        /// Also keep it in the configuration, for verify-master and --batch
+        #[arg(long)]
+        save: bool,
+    },
+    /// Check a master password against the saved fingerprint
+    VerifyMaster,
+    /// Show when passwords were derived with --log, oldest first
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+        /// Only this site or key
+        site: Option<String>,
+        /// Only what is newer than this, such as 30d or 12h
+        #[arg(long, value_name = "DURATION", value_parser = duration::parse_duration)]
+        since: Option<Duration>,
+    },
+    /// Write the man pages of fp and its subcommands
+    #[command(hide = true)]
+    Man {
+        /// Directory to write fp.1 and the other pages to
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+    },
+    /// Keep the derived passwords in the pass store, under flowerpassword/
+    Pass {
+        #[command(subcommand)]
+        action: PassAction,
+    },
+    /// Cache the master password for a while, for runs that would prompt
+    #[command(hide = !UNLOCK_SUPPORTED)]
+    Unlock {
+        /// How long to keep it, such as 90s, 15m or 1h30m
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "15m",
+            value_parser = duration::parse_duration
+        )]
+        timeout: Duration,
+    },
+    /// Wipe the cached master password
+    #[command(hide = !UNLOCK_SUPPORTED)]
+    Lock,
+    /// Tell whether the master password is cached, and for how long
+    #[command(hide = !UNLOCK_SUPPORTED)]
+    Status,
+    /// Pick sites from a full-screen list and copy their passwords
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Derive passwords over standard input and output, HTTP, gRPC or the
+    /// session D-Bus
+    #[command(group = clap::ArgGroup::new("listen").required(true).multiple(true))]
+    #[command(group = clap::ArgGroup::new("network").multiple(true))]
+    Serve {
+        /// Answer JSON-RPC 2.0 on standard input and output, one message per
+        /// line or with Content-Length headers, until standard input closes
+        #[arg(long, group = "listen", conflicts_with_all = ["network", "binary"])]
+        stdio: bool,
+        /// Answer the length-prefixed binary protocol on standard input and
+        /// output, the master password coming in its handshake frame
+        #[arg(long, group = "listen", conflicts_with = "network")]
+        binary: bool,
+        /// Accept setMaster more than once with --stdio
+        #[arg(long, requires = "stdio")]
+        allow_rekey: bool,
+        /// Serve the HTTP API here, such as 127.0.0.1:0 for any free port
+        #[cfg(feature = "http")]
+        #[arg(long, value_name = "ADDR", groups = ["listen", "network"])]
+        http: Option<SocketAddr>,
+        /// Serve the gRPC API here, such as 127.0.0.1:0 for any free port
+        #[cfg(feature = "grpc")]
+        #[arg(long, value_name = "ADDR", groups = ["listen", "network"])]
+        grpc: Option<SocketAddr>,
+        /// Serve org.flowerpassword.Derive1 on the session bus, with the
+        /// master password read now
+        #[cfg(all(unix, feature = "dbus"))]
+        #[arg(long, groups = ["listen", "network"])]
+        dbus: bool,
+        /// Listen on an address other machines can reach too
+        #[cfg(any(feature = "grpc", feature = "http"))]
+        #[arg(long)]
+        i_know_what_im_doing: bool,
+    },
+    /// Answer a browser extension over native messaging, as the browser runs
+    /// it, with the master password of the agent, `fp unlock` or the keyring
+    NativeHost {
+        /// What the browser passes: the origin of the extension, or the
+        /// manifest path and add-on ID
+        #[arg(hide = true, allow_hyphen_values = true)]
+        caller: Vec<String>,
+    },
+    /// Let a browser extension run `fp native-host`, writing its host manifest
+    /// and a script running this fp
+    InstallManifest {
+        /// Browser to install the host for
+        #[arg(long, value_enum)]
+        browser: BrowserArg,
+        /// ID of an extension allowed to connect, repeatable
+        #[arg(long = "extension-id", value_name = "ID", required = true)]
+        extension_ids: Vec<String>,
+        /// fp to run instead of this one
+        #[arg(long, value_name = "PATH")]
+        binary: Option<PathBuf>,
+    },
+    /// Derive the password a flowerpassword:// link asks for, as the desktop
+    /// runs it, with the master password of the agent, `fp unlock` or the keyring
+    HandleUri {
+        /// flowerpassword://derive?key=KEY[&len=N][&copy=1]
+        uri: String,
+    },
+    /// Have the desktop open flowerpassword:// links with `fp handle-uri`
+    RegisterUriHandler {
+        /// fp to run instead of this one
+        #[arg(long, value_name = "PATH")]
+        binary: Option<PathBuf>,
+    },
+    /// Use a security key for --master fido2
+    Fido2 {
+        #[command(subcommand)]
+        action: Fido2Action,
+    },
+    /// Keep the master password in a background agent for --agent runs
+    Agent {
+        #[command(subcommand)]
+        action: AgentAction,
+    },
+    /// Manage the master password stored in the OS keyring
+    #[cfg(feature = "keyring")]
+    Keyring {
+        #[command(subcommand)]
+        action: KeyringAction,
+    },
+}
+
+/// What `fp history` does besides showing the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub(crate) enum HistoryAction {
+    /// Remove the whole history
+    Clear,
+}
+
+/// What `fp config` does
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub(crate) enum ConfigAction {
+    /// Show the file of each configuration layer, in order, and which exist
+    Path,
+    /// Check every configuration file for typos, wrong types and bad lengths,
+    /// failing on errors but not on warnings
+    Lint,
+    /// Upgrade the configuration file to the current schema version, keeping
+    /// a backup
+    Migrate {
+        /// Only tell what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Encrypt the configuration file with a passphrase asked for on the
+    /// terminal
+    Encrypt,
+    /// Decrypt the configuration file back to plain TOML
+    Decrypt,
+}
+
+/// What `fp alias` does
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub(crate) enum AliasAction {
+    /// Point ALIAS at a site or another alias, replacing what it pointed to
+    Add {
+        /// Short name to type
+        alias: String,
+        /// Site or alias it stands for
+        target: String,
+    },
+    /// Remove an alias
+    Rm {
+        /// Alias to remove
+        alias: String,
+    },
+    /// List the aliases with what they point to
+    List,
+}
+
+/// What `fp pass` does
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub(crate) enum PassAction {
+    /// Store the password of every configured site as flowerpassword/<name>
+    Sync,
+    /// Remove the entries of these sites, or all of flowerpassword/
+    Rm {
+        /// Site names whose entries to remove
+        sites: Vec<String>,
+    },
+}
+
+/// What `fp agent` does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub(crate) enum AgentAction {
+    /// Read the master password and serve it until `fp agent stop`
+    Start {
+        /// Stay in the foreground, as a service manager expects; always so on
+        /// Windows
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Have the agent wipe the master password and exit
+    Stop,
+}
+
+/// What `fp fido2` does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub(crate) enum Fido2Action {
+    /// Create a credential on the security key and keep its ID in the
+    /// configuration
+    Enroll {
+        /// Replace the credential enrolled already, which changes every
+        /// password
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// What `fp keyring` does
+#[cfg(feature = "keyring")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub(crate) enum KeyringAction {
+    /// Prompt for the master password and store it
+    Set,
+    /// Remove the stored master password
+    Clear,
+    /// Tell whether a master password is stored
+    Status,
+}
+
+impl Cli {
+    /// Reports a problem that does not stop the run, unless `--quiet`
+    pub(crate) fn warn(&self, warning: impl std::fmt::Display) {
+        if !self.quiet {
+            eprintln!("fp: {}: {}", Msg::Warning.text(), warning);
+        }
+    }
+
+    /// Reports what a command did or waits for, unless `--quiet`
+    pub(crate) fn notice(&self, notice: impl std::fmt::Display) {
+        if !self.quiet {
+            eprintln!("fp: {}", notice);
+        }
+    }
+
+    /// Reports a configuration diagnostic, which names its own severity, unless
+    /// `--quiet`
+    pub(crate) fn report(&self, diagnostic: &Diagnostic) {
+        if !self.quiet {
+            eprintln!("fp: {}", diagnostic);
+        }
+    }
+
+    /// Returns `Some(true)` for `--confirm` and `Some(false)` for `--no-confirm`,
+    /// whichever came last
+    pub(crate) fn confirm_flag(&self) -> Option<bool> {
+        match (self.confirm, self.no_confirm) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(true)` for `--newline` and `Some(false)` for `--no-newline`,
+    /// whichever came last
+    pub(crate) fn newline_flag(&self) -> Option<bool> {
+        match (self.newline, self.no_newline) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Checks combinations of arguments that clap cannot express
+    pub(crate) fn check(&self) -> Result<(), clap::Error> {
+        #[cfg(all(unix, feature = "qr"))]
+        if self.masked && self.qr {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--masked and --qr cannot be used together",
+            ));
+        }
+        #[cfg(unix)]
+        if self.masked && self.keys.len() != 1 {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--masked shows one password at a time",
+            ));
+        }
+        #[cfg(all(unix, feature = "autotype"))]
+        if self.autotype && self.masked {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--autotype and --masked cannot be used together",
+            ));
+        }
+        #[cfg(all(feature = "autotype", feature = "qr"))]
+        if self.autotype && self.qr {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--autotype and --qr cannot be used together",
+            ));
+        }
+        #[cfg(feature = "autotype")]
+        if self.autotype && self.keys.len() != 1 {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--autotype types one password at a time",
+            ));
+        }
+        #[cfg(feature = "qr")]
+        if self.qr && self.keys.len() != 1 {
+            return Err(<Cli as clap::CommandFactory>::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--qr shows one password at a time",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `fp unlock`, `fp lock` and `fp status` work here: they keep the key
+/// of their cache in the Linux kernel keyring, and are hidden elsewhere
+const UNLOCK_SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Accepts the lengths `fp_code` accepts
+fn length_parser() -> RangedU64ValueParser<usize> {
+    RangedU64ValueParser::new().range(MIN_LENGTH as u64..=MAX_LENGTH as u64)
+}
+
+/// Accepts the names of the schemes `Scheme::from_name` knows
+fn scheme_parser(name: &str) -> Result<Scheme, String> {
+    Scheme::from_name(name).ok_or_else(|| format!("expected v1 or v2, got `{}`", name))
+}
+
+/// Runs the command, or derives passwords if there is none
+pub(crate) fn run(cli: &Cli) -> Result<(), CliError> {
+    match &cli.command {
+        None => run_derive(cli),
+        Some(Command::Completions { shell }) => {
+            let mut out = io::stdout().lock();
+            completions::write(*shell, &mut out)?;
+            Ok(out.flush()?)
+        }
+        Some(Command::List {
+            output,
+            filter,
+            sort,
+            long,
+        }) => {
+            let config = load_config(cli)?;
+            let entries = list::entries(&config, filter.as_deref(), *sort);
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            list::write(&mut out, &entries, *output, *long)?;
+            Ok(out.flush()?)
+        }
+        Some(Command::Show { name }) => {
+            let config = load_config(cli)?;
+            let mut out = io::stdout().lock();
+            list::show(&mut out, &config, name)?;
+            Ok(out.flush()?)
+        }
+        Some(Command::Note { name, text, append }) => {
+            sites::note(&config_file(cli)?, name, text, *append)?;
+            let msg = match append {
+                true => Msg::NoteAppended,
+                false => Msg::NoteSet,
+            };
+            cli.notice(msg.fill(&[name]));
+            Ok(())
+        }
+        Some(Command::Find { query, select }) => {
+            let config = load_config(cli)?;
+            let matches = config.find(query);
+            if !select {
+                if matches.is_empty() {
+                    return Err(CliError::NoMatch(query.clone()));
+                }
+                let mut out = io::stdout().lock();
+                find::write(&mut out, &matches)?;
+                return Ok(out.flush()?);
+            }
+            let mut input = io::stdin().lock();
+            let site = find::select(query, &matches, &mut input, &mut io::stderr())?;
+            let master = read_master(cli, &mut input)?;
+            let generator = into_generator(master)?;
+            let derived = derive(&generator, &config, site, None)?;
+            // Piped, the password goes without a newline, as when derived by name
+            let trailing = output::trailing(io::stdout().is_terminal(), None, false, false, false);
+            let mut out = Records::new(io::stdout().lock(), false, trailing);
+            out.write(&derived.password)?;
+            Ok(out.finish()?)
+        }
+        Some(Command::Launcher { query, action }) => {
+            let config = load_config(cli)?;
+            if let Some(query) = query {
+                let mut out = io::stdout().lock();
+                launcher::write(&mut out, &launcher::items(&config, query))?;
+                return Ok(out.flush()?);
+            }
+            let arg = action.as_deref().unwrap_or_default();
+            let master = launcher_master(cli, &mut io::stdin().lock())?;
+            let generator = into_generator(master)?;
+            let clipboard = CommandClipboard::detect()?;
+            let guard = launcher::action(&generator, &config, arg, clipboard, DEFAULT_TTL)?;
+            // Launchers run the action without a terminal; stay until the
+            // clipboard is restored
+            std::thread::sleep(DEFAULT_TTL);
+            guard.restore()?;
+            Ok(())
+        }
+        Some(Command::Menu { selected, rofi }) => {
+            let config = load_config(cli)?;
+            let picked = match rofi {
+                true => menu::pick(&mut Rofi::default(), &config)?,
+                false => selected.clone(),
+            };
+            let Some(name) = picked else {
+                if *rofi {
+                    return Ok(());
+                }
+                let mut out = io::stdout().lock();
+                menu::write(&mut out, &config)?;
+                return Ok(out.flush()?);
+            };
+            menu::check(&config, &name)?;
+            let clipboard = CommandClipboard::detect()?;
+            let master = launcher_master(cli, &mut io::stdin().lock())?;
+            let generator = into_generator(master)?;
+            let guard = menu::selected(&generator, &config, &name, clipboard, DEFAULT_TTL)?;
+            // Menus run without a terminal; stay until the clipboard is restored
+            std::thread::sleep(DEFAULT_TTL);
+            guard.restore()?;
+            Ok(())
+        }
+        Some(Command::Add {
+            name,
+            key,
+            length,
+            scheme,
+            force,
+        }) => {
+            let config = load_config(cli)?;
+            let site = SiteConfig {
+                key: key.clone(),
+                length: *length,
+                scheme: *scheme,
+                ..Default::default()
+            };
+            let replaced = sites::add(&config_file(cli)?, name, &site, *force)?;
+            let (key, length, scheme) = sites::describe(&config, name, &site);
+            let msg = match replaced {
+                Some(_) => Msg::SiteReplaced,
+                None => Msg::SiteAdded,
+            };
+            cli.notice(msg.fill(&[name, &key, &length, &scheme.name()]));
+            Ok(())
+        }
+        Some(Command::Rm { name, yes }) => {
+            let config = load_config(cli)?;
+            let question = Msg::SiteRemoveConfirm.fill(&[name]);
+            if !yes && !sites::confirm(&mut io::stdin().lock(), &mut io::stderr(), &question)? {
+                cli.notice(Msg::SiteKept.fill(&[name]));
+                return Ok(());
+            }
+            let (site, dangling) = sites::remove_site_from(&config_file(cli)?, name)?;
+            let (key, length, scheme) = sites::describe(&config, name, &site);
+            cli.notice(Msg::SiteRemoved.fill(&[name, &key, &length, &scheme.name()]));
+            if !dangling.is_empty() {
+                cli.warn(Msg::AliasesDangling.fill(&[name, &dangling.join(", ")]));
+            }
+            Ok(())
+        }
+        Some(Command::Rename {
+            old,
+            new,
+            also_key,
+            force,
+            yes,
+        }) => run_rename(cli, old, new, *also_key, *force, *yes),
+        Some(Command::Alias { action }) => run_alias(cli, action),
+        Some(Command::Audit { check_reuse: _ }) => {
+            let config = load_config(cli)?;
+            let master = read_master(cli, &mut io::stdin().lock())?;
+            if fingerprint::verify(config.fingerprint.as_deref(), &master) == Some(false) {
+                return Err(CliError::FingerprintMismatch);
+            }
+            let groups = config.find_reused(&master)?;
+            let mut out = io::stdout().lock();
+            for group in &groups {
+                writeln!(out, "{}", Msg::AuditReused.fill(&[&group.sites.join(", ")]))?;
+            }
+            if groups.is_empty() {
+                writeln!(out, "{}", Msg::AuditClean.fill(&[&config.sites.len()]))?;
+            }
+            out.flush()?;
+            match groups.len() {
+                0 => Ok(()),
+                groups => Err(CliError::Reused { groups }),
+            }
+        }
+        Some(Command::Export {
+            format,
+            out,
+            stdout: _,
+            group,
+            yes,
+        }) => {
+            let config = load_config(cli)?;
+            if let Some(path) = out {
+                cli.warn(Msg::ExportPlaintext.fill(&[&path.display()]));
+                let question = Msg::ExportConfirm.fill(&[&config.sites.len(), &path.display()]);
+                if !yes && !sites::confirm(&mut io::stdin().lock(), &mut io::stderr(), &question)? {
+                    cli.notice(Msg::ExportCancelled.text());
+                    return Ok(());
+                }
+            }
+            let master = read_master(cli, &mut io::stdin().lock())?;
+            if fingerprint::verify(config.fingerprint.as_deref(), &master) == Some(false) {
+                return Err(CliError::FingerprintMismatch);
+            }
+            let entries = config.export_entries(&master)?;
+            match out {
+                Some(path) => {
+                    let mut file = io::BufWriter::new(export::create(path)?);
+                    export::write(&mut file, *format, &entries, group)?;
+                    file.flush()?;
+                    cli.notice(Msg::Exported.fill(&[&entries.len(), &path.display()]));
+                }
+                None => {
+                    let mut stdout = io::stdout().lock();
+                    export::write(&mut stdout, *format, &entries, group)?;
+                    stdout.flush()?;
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Import {
+            path,
+            allow_duplicates,
+        }) => {
+            let config = load_config(cli)?;
+            let import = sites::import(&config_file(cli)?, &config, path, *allow_duplicates)?;
+            let mut out = io::stdout().lock();
+            sites::write_import(&mut out, &import)?;
+            out.flush()?;
+            Ok(())
+        }
+        Some(Command::Doctor) => {
+            let report = doctor::Report::run(&doctor::system_probes(
+                cli.config.clone(),
+                agent_endpoint(cli),
+            ));
+            let mut out = io::stdout().lock();
+            report.write(&mut out)?;
+            out.flush()?;
+            report.result()
+        }
+        Some(Command::SelfTest) => selftest::run(&mut io::stdout().lock()),
+        Some(Command::Config { action }) => run_config(cli, action),
+        Some(Command::Fingerprint { save }) => {
+            let master = read_master(cli, &mut io::stdin().lock())?;
+            let fingerprint = match save {
+                true => {
+                    let path = config_file(cli)?;
+                    let fingerprint = fingerprint::save(&path, &master)?;
+                    cli.notice(Msg::FingerprintSaved.fill(&[&path.display()]));
+                    fingerprint
+                }
+                false => fp_fingerprint(&master),
+            };
+            println!("{}", fingerprint);
+            Ok(())
+        }
+        Some(Command::VerifyMaster) => {
+            let config = load_config(cli)?;
+            let master = read_master(cli, &mut io::stdin().lock())?;
+            match fingerprint::verify(config.fingerprint.as_deref(), &master) {
+                None => Err(CliError::NoFingerprint),
+                Some(true) => {
+                    println!("{}", Msg::FingerprintMatches.text());
+                    Ok(())
+                }
+                Some(false) => Err(CliError::FingerprintMismatch),
+            }
+        }
+        Some(Command::Man { out_dir }) => {
+            std::fs::create_dir_all(out_dir)?;
+            for path in man::write_pages(out_dir)? {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+        Some(Command::History {
+            action,
+            site,
+            since,
+        }) => {
+            let path = history::history_path().ok_or(CliError::NoStateDir)?;
+            if *action == Some(HistoryAction::Clear) {
+                if !history::clear(&path)? {
+                    cli.notice(Msg::NoHistory.fill(&[&path.display()]));
+                }
+                return Ok(());
+            }
+            let records = history::read(&path, |line| {
+                cli.warn(Msg::HistoryInvalid.fill(&[&path.display(), &line]))
+            })?;
+            let shown = history::filter(&records, site.as_deref(), *since, SystemTime::now());
+            let mut out = io::stdout().lock();
+            history::write_table(&mut out, &shown)?;
+            Ok(out.flush()?)
+        }
+        Some(Command::Pass { action }) => run_pass(cli, action, &mut Pass::default()),
+        Some(Command::NativeHost { .. }) => {
+            // Standard input is the extension's
+            let generator = stored_source(cli, || CliError::NativeHostLocked)?;
+            native_host::serve(
+                &generator,
+                &mut io::stdin().lock(),
+                &mut io::stdout().lock(),
+            )
+        }
+        Some(Command::InstallManifest {
+            browser,
+            extension_ids,
+            binary,
+        }) => {
+            let home = BaseDirs::new().ok_or(CliError::NoHomeDir)?;
+            let binary = match binary {
+                Some(binary) => binary.clone(),
+                None => std::env::current_exe()?,
+            };
+            let ids: Vec<&str> = extension_ids.iter().map(String::as_str).collect();
+            let path = native_host::install(*browser, home.home_dir(), &binary, &ids)?;
+            cli.notice(Msg::ManifestInstalled.fill(&[&path.display()]));
+            Ok(())
+        }
+        Some(Command::HandleUri { uri }) => {
+            let request = parse_fp_uri(uri)?;
+            let config = load_config(cli)?;
+            let generator = stored_source(cli, || CliError::UriHandlerLocked)?;
+            let clipboard = || Ok(CommandClipboard::detect()?);
+            let mut out = io::stdout().lock();
+            let copied = uri::handle(
+                &generator,
+                &config,
+                &request,
+                clipboard,
+                DEFAULT_TTL,
+                &mut out,
+            )?;
+            if let Some(guard) = copied {
+                // Handlers run without a terminal; stay until the clipboard is
+                // restored
+                std::thread::sleep(DEFAULT_TTL);
+                guard.restore()?;
+            }
+            Ok(out.flush()?)
+        }
+        Some(Command::RegisterUriHandler { binary }) => {
+            let dirs = BaseDirs::new().ok_or(CliError::NoHomeDir)?;
+            let binary = match binary {
+                Some(binary) => binary.clone(),
+                None => std::env::current_exe()?,
+            };
+            let path = uri::register(dirs.data_dir(), &binary)?;
+            cli.notice(Msg::UriHandlerRegistered.fill(&[&path.display()]));
+            Ok(())
+        }
+        Some(Command::Fido2 {
+            action: Fido2Action::Enroll { force },
+        }) => {
+            let path = config_file(cli)?;
+            if load_config(cli)?.fido2_credential.is_some() && !force {
+                return Err(CliError::Fido2Enrolled);
+            }
+            let touch = || cli.notice(Msg::Fido2Touch.text());
+            let credential = fido2::enroll(Fido2Tools::default(), &path, &mut Terminal, touch)?;
+            cli.notice(Msg::Fido2Saved.fill(&[&credential, &path.display()]));
+            Ok(())
+        }
+        Some(Command::Agent { action }) => run_agent(cli, *action),
+        #[cfg(target_os = "linux")]
+        Some(Command::Unlock { timeout }) => {
+            let cache = Cache::new().ok_or(CliError::NoRuntimeDir)?;
+            let mut input = io::stdin().lock();
+            let master = master::read_master(&mut Terminal, &mut input, Confirm::Auto, |_| {})?;
+            cache.store(&master, SystemTime::now(), *timeout)?;
+            cli.notice(Msg::UnlockedFor.fill(&[&unlock::format_remaining(*timeout)]));
+            Ok(())
+        }
+        #[cfg(target_os = "linux")]
+        Some(Command::Lock) => {
+            if let Some(cache) = Cache::new() {
+                cache.clear()?;
+            }
+            Ok(())
+        }
+        #[cfg(target_os = "linux")]
+        Some(Command::Status) => {
+            let remaining = match Cache::new() {
+                Some(cache) => cache.remaining(SystemTime::now())?,
+                None => None,
+            };
+            match remaining {
+                Some(remaining) => {
+                    let remaining = unlock::format_remaining(remaining);
+                    println!("{}", Msg::UnlockedLeft.fill(&[&remaining]))
+                }
+                None => println!("{}", Msg::Locked.text()),
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(Command::Unlock { .. } | Command::Lock | Command::Status) => {
+            Err(CliError::NoKernelKeyring)
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tui) => {
+            let config = load_config(cli)?;
+            let master = read_master(cli, &mut io::stdin().lock())?;
+            let generator = into_generator(master)?;
+            tui::run(&config, &generator)
+        }
+        Some(Command::Serve {
+            stdio: true,
+            allow_rekey,
+            ..
+        }) => stdio::run_rpc(*allow_rekey),
+        Some(Command::Serve { binary: true, .. }) => stdio::run_binary(),
+        #[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+        Some(Command::Serve {
+            #[cfg(feature = "http")]
+            http,
+            #[cfg(feature = "grpc")]
+            grpc,
+            #[cfg(all(unix, feature = "dbus"))]
+            dbus,
+            #[cfg(any(feature = "grpc", feature = "http"))]
+            i_know_what_im_doing,
+            ..
+        }) => {
+            #[cfg(not(feature = "http"))]
+            let http = &None;
+            #[cfg(not(feature = "grpc"))]
+            let grpc = &None;
+            #[cfg(not(all(unix, feature = "dbus")))]
+            let dbus = &false;
+            #[cfg(not(any(feature = "grpc", feature = "http")))]
+            let i_know_what_im_doing = &false;
+            let generator = match dbus {
+                true => {
+                    let master = read_master(cli, &mut io::stdin().lock())?;
+                    Some(into_generator(master)?)
+                }
+                false => None,
+            };
+            serve::run(
+                *http,
+                *grpc,
+                generator,
+                *i_know_what_im_doing,
+                &mut io::stdout().lock(),
+            )
+        }
+        #[cfg(not(any(feature = "grpc", feature = "http", all(unix, feature = "dbus"))))]
+        Some(Command::Serve { .. }) => {
+            unreachable!("--stdio and --binary are the only servers of this build")
+        }
+        #[cfg(feature = "keyring")]
+        Some(Command::Keyring { action }) => run_keyring(cli, *action, &OsKeyring),
+    }
+}
+
+/// Loads the layered configuration, asking on the terminal for the passphrase
+/// of each encrypted file, and reports its warnings
+fn load_config(cli: &Cli) -> Result<Config, CliError> {
+    let mut failed = None;
+    let env = |name: &str| std::env::var(name).ok();
+    let loaded = load_layered_with_passphrase(cli.config.as_deref(), env, |path| {
+        master::read_passphrase(&mut Terminal, path, false).map_err(|error| {
+            let source = io::Error::other(error.to_string());
+            failed = Some(error);
+            source
+        })
+    });
+    let loaded = match (loaded, failed) {
+        (Err(_), Some(error)) => return Err(error),
+        (loaded, _) => loaded?,
+    };
+    for warning in &loaded.warnings {
+        cli.report(warning);
+    }
+    Ok(loaded.config)
+}
+
+/// Returns the configuration file that commands changing the configuration
+/// edit: `--config`, or else the user's
+fn config_file(cli: &Cli) -> Result<PathBuf, CliError> {
+    cli.config
+        .clone()
+        .or_else(user_config_path)
+        .ok_or(CliError::NoConfigDir)
+}
+
+/// Runs `fp rename`, with notices on standard error
+fn run_rename(
+    cli: &Cli,
+    old: &str,
+    new: &str,
+    also_key: bool,
+    force: bool,
+    yes: bool,
+) -> Result<(), CliError> {
+    if also_key && !yes {
+        let question = Msg::RenameKeyConfirm.fill(&[&old, &new]);
+        if !sites::confirm(&mut io::stdin().lock(), &mut io::stderr(), &question)? {
+            cli.notice(Msg::SiteKept.fill(&[&old]));
+            return Ok(());
+        }
+    }
+    let renamed = sites::rename(&config_file(cli)?, old, new, also_key, force)?;
+    cli.notice(Msg::SiteRenamed.fill(&[&old, &new]));
+    if renamed.replaced.is_some() {
+        cli.notice(Msg::SiteRenameReplaced.fill(&[&new]));
+    }
+    if !renamed.aliases.is_empty() {
+        cli.notice(Msg::SiteRenameAliases.fill(&[&new, &renamed.aliases.join(", ")]));
+    }
+    match also_key {
+        true => cli.warn(Msg::RenameKeyChanged.fill(&[&new])),
+        false => {
+            let config = load_config(cli)?;
+            let resolved = config.resolve(new)?;
+            cli.notice(Msg::RenameKeyKept.fill(&[&new, &resolved.key]));
+        }
+    }
+    Ok(())
+}
+
+/// Runs `fp alias`, with notices on standard error and the list on stdout
+fn run_alias(cli: &Cli, action: &AliasAction) -> Result<(), CliError> {
+    match action {
+        AliasAction::Add { alias, target } => {
+            let config = load_config(cli)?;
+            match sites::add_alias(&config_file(cli)?, &config, alias, target)? {
+                Some(previous) if previous != *target => {
+                    cli.notice(Msg::AliasReplaced.fill(&[alias, target, &previous]))
+                }
+                _ => cli.notice(Msg::AliasSet.fill(&[alias, target])),
+            }
+            if config.sites.contains_key(alias) {
+                cli.warn(Msg::AliasShadowed.fill(&[alias]));
+            }
+            Ok(())
+        }
+        AliasAction::Rm { alias } => {
+            let target = sites::remove(&config_file(cli)?, alias)?;
+            cli.notice(Msg::AliasRemoved.fill(&[alias, &target]));
+            Ok(())
+        }
+        AliasAction::List => {
+            let config = load_config(cli)?;
+            let mut out = io::stdout().lock();
+            sites::write_aliases(&mut out, &config)?;
+            Ok(out.flush()?)
+        }
+    }
+}
+
+/// Runs `fp config`
+fn run_config(cli: &Cli, action: &ConfigAction) -> Result<(), CliError> {
+    let paths = config_paths(cli.config.as_deref(), |name| std::env::var(name).ok());
+    match action {
+        ConfigAction::Path => {
+            let mut out = io::stdout().lock();
+            config::write_paths(&mut out, &paths)?;
+            Ok(out.flush()?)
+        }
+        ConfigAction::Lint => {
+            let lint = Lint::run(&paths, |path| {
+                master::read_passphrase(&mut Terminal, path, false)
+            })?;
+            let mut out = io::stdout().lock();
+            lint.write(&mut out)?;
+            if lint.diagnostics.is_empty() {
+                writeln!(out, "{}", Msg::LintClean.fill(&[&lint.files]))?;
+            }
+            out.flush()?;
+            lint.result()
+        }
+        ConfigAction::Migrate { dry_run } => {
+            let mut out = io::stdout().lock();
+            config::migrate(&mut out, &config_file(cli)?, *dry_run)?;
+            Ok(out.flush()?)
+        }
+        ConfigAction::Encrypt => {
+            let path = config_file(cli)?;
+            let passphrase = master::read_passphrase(&mut Terminal, &path, true)?;
+            encrypt_file(&path, &passphrase)?;
+            cli.notice(Msg::ConfigEncrypted.fill(&[&path.display()]));
+            Ok(())
+        }
+        ConfigAction::Decrypt => {
+            let path = config_file(cli)?;
+            let data = std::fs::read(&path).map_err(|source| ConfigError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            if !is_encrypted(&data) {
+                cli.notice(Msg::ConfigNotEncrypted.fill(&[&path.display()]));
+                return Ok(());
+            }
+            let passphrase = master::read_passphrase(&mut Terminal, &path, false)?;
+            decrypt_file(&path, &passphrase)?;
+            cli.notice(Msg::ConfigDecrypted.fill(&[&path.display()]));
+            Ok(())
+        }
+    }
+}
+
+/// Runs `fp pass`, reporting each failed entry before failing as a whole
+fn run_pass(cli: &Cli, action: &PassAction, runner: &mut impl Runner) -> Result<(), CliError> {
+    let (failures, total) = match action {
+        PassAction::Sync => {
+            let config = load_config(cli)?;
+            let mut input = io::stdin().lock();
+            let master = read_master(cli, &mut input)?;
+            guard_fingerprint(cli, &config, &master, &mut input)?;
+            let generator = into_generator(master)?;
+            let failures = pass::sync(runner, &config, &generator);
+            (failures, config.sites.len())
+        }
+        PassAction::Rm { sites } => (pass::remove(runner, sites), sites.len().max(1)),
+    };
+    if failures.is_empty() {
+        return Ok(());
+    }
+    for failure in &failures {
+        eprintln!(
+            "fp: {}: {}: {}",
+            Msg::Error.text(),
+            failure.name,
+            failure.error
+        );
+    }
+    Err(PassError::Incomplete {
+        failed: failures.len(),
+        total,
+    }
+    .into())
+}
+
+/// Returns the endpoint of `--agent-endpoint`, or the user's default
+fn agent_endpoint(cli: &Cli) -> Option<Endpoint> {
+    cli.agent_endpoint
+        .clone()
+        .or_else(Endpoint::default_for_user)
+}
+
+/// Runs `fp agent`, with notices on standard error
+fn run_agent(cli: &Cli, action: AgentAction) -> Result<(), CliError> {
+    let endpoint = agent_endpoint(cli).ok_or(CliError::NoAgentEndpoint)?;
+    match action {
+        AgentAction::Start { foreground } => {
+            // Refuse before prompting if another agent answers
+            let listener = AgentListener::bind(&endpoint)?;
+            let config = load_config(cli)?;
+            let mut input = io::stdin().lock();
+            let master = read_master(cli, &mut input)?;
+            guard_fingerprint(cli, &config, &master, &mut input)?;
+            drop(input);
+            agent::run(listener, master, foreground, |endpoint| {
+                cli.notice(Msg::AgentStarted.fill(&[endpoint]))
+            })
+        }
+        AgentAction::Stop => {
+            match agent::stop(&endpoint)? {
+                true => cli.notice(Msg::AgentStopped.fill(&[&endpoint])),
+                false => cli.notice(Msg::AgentNotRunning.fill(&[&endpoint])),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Runs `fp keyring`, with notices on standard error and the status on stdout
+#[cfg(feature = "keyring")]
+fn run_keyring(cli: &Cli, action: KeyringAction, store: &impl Keyring) -> Result<(), CliError> {
+    let entry = format!("{}/{}", keyring::SERVICE, keyring::ACCOUNT);
+    match action {
+        KeyringAction::Set => {
+            let mut input = io::stdin().lock();
+            let master = master::read_master(&mut Terminal, &mut input, Confirm::Auto, |_| {})?;
+            store.set(&master)?;
+            cli.notice(Msg::KeyringStored.fill(&[&entry]));
+        }
+        KeyringAction::Clear => {
+            if !store.clear()? {
+                cli.notice(Msg::KeyringNotStored.fill(&[&entry]));
+            }
+        }
+        KeyringAction::Status => println!("{}", keyring::status(store)?),
+    }
+    Ok(())
+}
+
+/// Derives and prints the password for every key, in order
+fn run_derive(cli: &Cli) -> Result<(), CliError> {
+    let config = load_config(cli)?;
+    let blocklist = match cli.check_blocklist {
+        true => Some(blocklist::load(&cli.blocklist)?),
+        false => None,
+    };
+
+    let mut input = io::stdin().lock();
+    let agent = match cli.agent {
+        true => agent::connect(agent_endpoint(cli).as_ref(), |notice| cli.warn(notice)),
+        false => None,
+    };
+    let generator = match agent {
+        Some(agent) => Source::Agent(agent),
+        None => {
+            let master = read_master(cli, &mut input)?;
+            if blocklist
+                .as_ref()
+                .is_some_and(|list| list.contains(&master))
+            {
+                cli.warn(Msg::MasterBlocklisted.text());
+            }
+            if cli.batch {
+                guard_fingerprint(cli, &config, &master, &mut input)?;
+            }
+            if cli.interactive {
+                drop(input);
+                let mut session = Session::new(&config, master, cli.length)?;
+                return repl::run(&mut session, &mut repl::Stdio::new()?);
+            }
+            Source::Master(into_generator(master)?)
+        }
+    };
+    let show_timeout = cli.show_timeout.filter(|_| {
+        let terminal = io::stdout().is_terminal();
+        if !terminal {
+            cli.warn(Msg::ShowTimeoutNotTerminal.text());
+        }
+        terminal
+    });
+    if show_timeout.is_some() {
+        erase::catch_interrupt();
+    }
+
+    if cli.copy_user_then_pass {
+        return copy_user_then_pass(cli, &generator, &config, blocklist.as_ref(), &mut input);
+    }
+    let template = match cli.show_user {
+        true => Template::show_user(),
+        false => cli.format.clone().unwrap_or_default(),
+    };
+    let stdout = io::BufWriter::new(io::stdout().lock());
+    let trailing = output::trailing(
+        io::stdout().is_terminal(),
+        cli.newline_flag(),
+        cli.raw,
+        cli.null,
+        cli.batch || cli.keys.len() > 1,
+    );
+    let mut out = Records::new(stdout, cli.null, trailing);
+    let paint = paint(cli);
+    let started = Instant::now();
+    // --jobs conflicts with --agent
+    if let (Some(jobs), Source::Master(generator)) = (cli.jobs, &generator) {
+        // The reader thread locks standard input itself
+        drop(input);
+        let blocklist = blocklist.as_ref();
+        return run_jobs(cli, generator, &config, blocklist, &template, jobs, out);
+    }
+    let history = log_path(cli)?;
+    let mut lines = 0;
+    let mut keys = 0;
+    let mut emit = |name: &str| -> Result<(), CliError> {
+        let derived = derive_checked(cli, &generator, &config, name)?;
+        check_derived(cli, blocklist.as_ref(), name, &derived);
+        keys += 1;
+        if let Some(path) = &history {
+            history::append(path, &derived.record(SystemTime::now()))?;
+        }
+        if cli.entropy {
+            eprintln!(
+                "{}",
+                entropy::describe(derived.key, derived.password.len())?
+            );
+        }
+        #[cfg(feature = "autotype")]
+        if cli.autotype {
+            return autotype(cli, &derived);
+        }
+        #[cfg(unix)]
+        if cli.masked {
+            let option = if cli.paranoid {
+                "--paranoid"
+            } else {
+                "--masked"
+            };
+            let mut terminal = RawGuard::new(Tty::open(option)?)?;
+            let mut copy = |password: &str| copy_with_ttl(password, DEFAULT_TTL);
+            // --paranoid keeps the password off the clipboard
+            let copy: Option<mask::CopyFn> = match cli.paranoid {
+                true => None,
+                false => Some(&mut copy),
+            };
+            return mask::run(&derived.password, cli.mask_style, &mut terminal, copy);
+        }
+        #[cfg(feature = "qr")]
+        if cli.qr {
+            return show_qr(cli, &derived, out.raw());
+        }
+        if cli.copy {
+            return copy_password(name, &derived);
+        }
+        if cli.show_user && derived.username.is_none() {
+            cli.warn(Msg::NoUsernameShown.fill(&[&name]));
+        }
+        let record = template.render(
+            derived.key,
+            derived.scheme,
+            derived.username,
+            &derived.password,
+            paint,
+        );
+        lines += record.matches('\n').count() + 1;
+        out.write(&record)?;
+        Ok(())
+    };
+    if cli.batch {
+        while let Some(name) = master::read_line(&mut input)? {
+            if !name.is_empty() {
+                emit(&name)?;
+            }
+        }
+    } else {
+        for name in &cli.keys {
+            emit(name)?;
+        }
+    }
+    out.finish()?;
+    if cli.stats {
+        print_stats(keys, started);
+    }
+
+    if let Some(timeout) = show_timeout {
+        let display = Display::from_term(std::env::var("TERM").ok().as_deref());
+        let mut stdout = io::stdout().lock();
+        if erase::erase_after(&mut stdout, display, lines, timeout, erase::was_interrupted)? {
+            return Err(CliError::Interrupted);
+        }
+    }
+    Ok(())
+}
+
+/// Copies the password of `name` for `--copy`, and waits until the clipboard is
+/// restored
+fn copy_password(name: &str, derived: &Derived) -> Result<(), CliError> {
+    let guard = copy_with_ttl(&derived.password, DEFAULT_TTL)?;
+    eprintln!(
+        "{}",
+        Msg::PasswordCopied.fill(&[&name, &DEFAULT_TTL.as_secs()])
+    );
+    std::thread::sleep(DEFAULT_TTL);
+    guard.restore()?;
+    Ok(())
+}
+
+/// Copies the username and then the password of every key, for
+/// `--copy-user-then-pass`, each password until the clipboard is restored
+fn copy_user_then_pass(
+    cli: &Cli,
+    generator: &impl Generate,
+    config: &Config,
+    blocklist: Option<&Blocklist>,
+    input: &mut impl io::BufRead,
+) -> Result<(), CliError> {
+    let clipboard = CommandClipboard::detect()?;
+    let terminal = io::stdin().is_terminal();
+    let history = log_path(cli)?;
+    for name in &cli.keys {
+        let derived = derive_checked(cli, generator, config, name)?;
+        check_derived(cli, blocklist, name, &derived);
+        if let Some(path) = &history {
+            history::append(path, &derived.record(SystemTime::now()))?;
+        }
+        if derived.username.is_none() {
+            cli.warn(Msg::NoUsernameCopied.fill(&[&name]));
+        }
+        let wait = || {
+            copy::announce(&mut io::stderr(), name, terminal)?;
+            match terminal {
+                true => master::read_line(input).map(drop),
+                false => {
+                    std::thread::sleep(copy::USER_DELAY);
+                    Ok(())
+                }
+            }
+        };
+        let guard = copy::user_then_pass(
+            clipboard.clone(),
+            derived.username,
+            &derived.password,
+            DEFAULT_TTL,
+            wait,
+        )?;
+        eprintln!(
+            "{}",
+            Msg::PasswordCopied.fill(&[&name, &DEFAULT_TTL.as_secs()])
+        );
+        std::thread::sleep(DEFAULT_TTL);
+        guard.restore()?;
+    }
+    Ok(())
+}
+
+/// Returns how `--color` shows passwords on standard output
+fn paint(cli: &Cli) -> fn(&str) -> String {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let is_tty = io::stdout().is_terminal();
+    match color::enabled(cli.color, is_tty, no_color, cli.quiet, cli.raw) {
+        true => color::colorize,
+        false => str::to_string,
+    }
+}
+
+/// Returns the history file for `--log`, or `None` without it
+fn log_path(cli: &Cli) -> Result<Option<PathBuf>, CliError> {
+    match cli.log {
+        true => Ok(Some(history::history_path().ok_or(CliError::NoStateDir)?)),
+        false => Ok(None),
+    }
+}
+
+/// Streams the `--batch` keys of standard input through `jobs` threads, for
+/// `--jobs`
+///
+/// Every key is derived even if some fail; the records are written in input
+/// order, each failure is reported in its place, and then the run fails as a
+/// whole.
+fn run_jobs(
+    cli: &Cli,
+    generator: &FpGenerator,
+    config: &Config,
+    blocklist: Option<&Blocklist>,
+    template: &Template,
+    jobs: usize,
+    mut out: Records<impl Write>,
+) -> Result<(), CliError> {
+    let started = Instant::now();
+    let history = log_path(cli)?;
+    let paint = paint(cli);
+    let read = || -> Result<Option<String>, CliError> {
+        let mut input = io::stdin().lock();
+        loop {
+            match master::read_line(&mut input)? {
+                Some(name) if name.is_empty() => continue,
+                name => return Ok(name),
+            }
+        }
+    };
+    let progress = jobs::progress_bar(io::stderr().is_terminal() && !cli.quiet);
+    let mut failed = 0;
+    let total = jobs::stream_ordered(
+        read,
+        jobs,
+        |name| -> Result<(String, Record), CliError> {
+            let derived = derive_checked(cli, generator, config, name)?;
+            check_derived(cli, blocklist, name, &derived);
+            let record = derived.record(SystemTime::now());
+            Ok((
+                template.render(
+                    derived.key,
+                    derived.scheme,
+                    derived.username,
+                    &derived.password,
+                    paint,
+                ),
+                record,
+            ))
+        },
+        |name, result| -> Result<(), CliError> {
+            progress.inc(1);
+            match result {
+                Ok((text, record)) => {
+                    if let Some(path) = &history {
+                        history::append(path, &record)?;
+                    }
+                    out.write(&text)?;
+                }
+                Err(error) => {
+                    failed += 1;
+                    eprintln!("fp: {}: {}: {}", Msg::Error.text(), name, error);
+                }
+            }
+            Ok(())
+        },
+    );
+    progress.finish_and_clear();
+    let total = total?;
+    out.finish()?;
+    if cli.stats {
+        print_stats(total - failed, started);
+    }
+    if failed == 0 {
+        return Ok(());
+    }
+    Err(CliError::Incomplete { failed, total })
+}
+
+/// Prints the `--stats` of a `--batch` of `keys` that began at `started`
+fn print_stats(keys: usize, started: Instant) {
+    let stats = Stats {
+        keys,
+        elapsed: started.elapsed(),
+    };
+    eprintln!(
+        "{}",
+        Msg::BatchStats.fill(&[
+            &stats.keys,
+            &format_args!("{:.2}", stats.elapsed.as_secs_f64()),
+            &stats.per_second(),
+        ])
+    );
+}
+
+/// Types `derived` into the focused window after `--delay`
+#[cfg(feature = "autotype")]
+fn autotype(cli: &Cli, derived: &Derived) -> Result<(), CliError> {
+    if cli.tab_user && derived.username.is_none() {
+        cli.warn(Msg::NoUsername.fill(&[&derived.key]));
+    }
+    let strokes = autotype::strokes(derived.username, &derived.password, cli.tab_user);
+    let mut typist = autotype::system_typist()?;
+    cli.notice(Msg::TypingIn.fill(&[&format_args!("{:?}", cli.delay)]));
+    std::thread::sleep(cli.delay);
+    Ok(typist.type_strokes(&strokes)?)
+}
+
+/// Writes the QR code for `derived` to `out`, or to `--qr-out` for SVG
+#[cfg(feature = "qr")]
+fn show_qr(cli: &Cli, derived: &Derived, out: &mut impl Write) -> Result<(), CliError> {
+    let payload = qr::payload(derived.key, &derived.password, cli.qr_uri);
+    let code = qr::render(&payload, cli.qr_format)?;
+    match &cli.qr_out {
+        Some(path) => std::fs::write(path, code)?,
+        None => out.write_all(code.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Reads the master password from the source the arguments pick
+fn read_master(cli: &Cli, input: &mut impl io::BufRead) -> Result<Zeroizing<String>, CliError> {
+    if let Some(path) = &cli.password_file {
+        return master::read_master_file(path, cli.insecure_perms);
+    }
+    if let Some(path) = &cli.password_gpg {
+        return gpg::read_master_gpg(Gpg::default(), path);
+    }
+    #[cfg(unix)]
+    if let Some(fd) = cli.password_fd {
+        return master::read_master_fd(fd, input);
+    }
+    if let Some(spec) = &cli.master {
+        return read_master_source(cli, spec);
+    }
+    let confirm = Confirm::new(cli.confirm_flag(), cli.batch);
+    if let Some(spec) = &cli.pinentry {
+        return master::read_master_pinentry(spec, confirm);
+    }
+    let mut prompt =
+        || master::read_master(&mut Terminal, input, confirm, |warning| cli.warn(warning));
+    #[cfg(feature = "keyring")]
+    if cli.use_keyring {
+        return keyring::read_master_keyring(&OsKeyring, prompt, |notice| cli.warn(notice));
+    }
+    #[cfg(unix)]
+    if let Some(cache) = Cache::new().filter(|_| Terminal.is_terminal()) {
+        match cache.load(SystemTime::now()) {
+            Ok(Some(master)) => return Ok(master),
+            Ok(None) => {}
+            Err(error) => cli.warn(error),
+        }
+    }
+    prompt()
+}
+
+/// Reads the master password for `fp launcher --action`, which launchers run
+/// without a terminal: from `fp unlock`'s cache unless another source is
+/// given, and otherwise as [`read_master`] does
+fn launcher_master(
+    cli: &Cli,
+    input: &mut impl io::BufRead,
+) -> Result<Zeroizing<String>, CliError> {
+    #[cfg(unix)]
+    {
+        #[cfg(feature = "keyring")]
+        let use_keyring = cli.use_keyring;
+        #[cfg(not(feature = "keyring"))]
+        let use_keyring = false;
+        let explicit = cli.password_file.is_some()
+            || cli.password_gpg.is_some()
+            || cli.password_fd.is_some()
+            || cli.pinentry.is_some()
+            || cli.master.is_some();
+        if let Some(cache) = Cache::new().filter(|_| !explicit && !use_keyring) {
+            match cache.load(SystemTime::now()) {
+                Ok(Some(master)) => return Ok(master),
+                Ok(None) => {}
+                Err(error) => cli.warn(error),
+            }
+        }
+    }
+    read_master(cli, input)
+}
+
+/// Reads the master password from the device of `--master`
+fn read_master_source(cli: &Cli, spec: &str) -> Result<Zeroizing<String>, CliError> {
+    match spec.split_once(':').map_or(spec, |(source, _)| source) {
+        "fido2" => {
+            let config = load_config(cli)?;
+            let touch = || cli.notice(Msg::Fido2Touch.text());
+            fido2::read_master(Fido2Tools::default(), spec, &config, &mut Terminal, touch)
+        }
+        "yubikey" => {
+            let label = cli.yubikey_label.as_deref();
+            let touch = || cli.notice(Msg::YubiKeyTouch.text());
+            yubikey::read_master(Ykchalresp::default(), spec, label, touch)
+        }
+        #[cfg(unix)]
+        "ssh-agent" => {
+            let fingerprint = ssh_agent::parse_source(spec)?;
+            let mut agent = AgentClient::connect_env()?;
+            Ok(ssh_agent::master_from_agent(&mut agent, fingerprint)?)
+        }
+        _ => Err(CliError::UnknownMasterSource),
+    }
+}
+
+/// Finds the master password for `fp native-host` and `fp handle-uri` without
+/// asking for it, from the agent, `fp unlock`'s cache or the keyring, in that
+/// order, failing with `locked` if none has it
+fn stored_source(cli: &Cli, locked: fn() -> CliError) -> Result<Source, CliError> {
+    if let Some(agent) = agent::connect(agent_endpoint(cli).as_ref(), |notice| cli.warn(notice)) {
+        return Ok(Source::Agent(agent));
+    }
+    #[cfg(unix)]
+    if let Some(cache) = Cache::new() {
+        if let Some(master) = cache.load(SystemTime::now())? {
+            return Ok(Source::Master(into_generator(master)?));
+        }
+    }
+    #[cfg(feature = "keyring")]
+    {
+        let prompt = || Err(locked());
+        let master = keyring::read_master_keyring(&OsKeyring, prompt, |notice| cli.warn(notice))?;
+        Ok(Source::Master(into_generator(master)?))
+    }
+    #[cfg(not(feature = "keyring"))]
+    Err(locked())
+}
+
+/// Hands `master` over to a generator without leaving a copy behind
+pub(crate) fn into_generator(mut master: Zeroizing<String>) -> Result<FpGenerator, CliError> {
+    let master = std::mem::take(&mut *master);
+    Ok(FpGenerator::new(master, FpGenerator::DEFAULT_LENGTH)?)
+}
+
+/// Checks `master` against the saved fingerprint before a run deriving many
+/// passwords, asking on a terminal whether to go on if it does not match
+fn guard_fingerprint(
+    cli: &Cli,
+    config: &Config,
+    master: &str,
+    input: &mut impl io::BufRead,
+) -> Result<(), CliError> {
+    let interactive = !cli.quiet && io::stdin().is_terminal();
+    let ask = interactive.then_some(|| fingerprint::ask(input, &mut io::stderr()));
+    fingerprint::guard(config.fingerprint.as_deref(), master, ask)
+}
+
+/// A derived password and the key it was derived for, with the site's username
+/// for `--tab-user`
+pub(crate) struct Derived<'a> {
+    pub(crate) key: &'a str,
+    pub(crate) scheme: Scheme,
+    pub(crate) password: String,
+    /// Name of the configured site, or `None` for a literal key
+    pub(crate) site: Option<&'a str>,
+    pub(crate) username: Option<&'a str>,
+}
+
+impl Derived<'_> {
+    /// Returns the `--log` record of this password, derived at `now`
+    pub(crate) fn record(&self, now: SystemTime) -> Record {
+        Record::new(
+            self.site.unwrap_or(self.key),
+            self.password.len(),
+            self.scheme,
+            now,
+        )
+    }
+}
+
+/// What derives a password from a key
+pub(crate) trait Generate {
+    /// Derives the password of `length` characters for `key` under `scheme`
+    fn password(&self, scheme: Scheme, key: &str, length: usize) -> Result<String, CliError>;
+}
+
+impl Generate for FpGenerator {
+    fn password(&self, scheme: Scheme, key: &str, length: usize) -> Result<String, CliError> {
+        Ok(self.generate_with_scheme(scheme, key, length)?)
+    }
+}
+
+/// Where the passwords of a run come from
+enum Source {
+    /// The master password read for the run
+    Master(FpGenerator),
+    /// The agent holding it, for `--agent`
+    Agent(Agent),
+}
+
+impl Generate for Source {
+    fn password(&self, scheme: Scheme, key: &str, length: usize) -> Result<String, CliError> {
+        match self {
+            Source::Master(generator) => generator.password(scheme, key, length),
+            Source::Agent(agent) => agent.password(scheme, key, length),
+        }
+    }
+}
+
+/// Derives the password for a site name or key, at `length` if given
+pub(crate) fn derive<'a>(
+    generator: &impl Generate,
+    config: &'a Config,
+    name: &'a str,
+    length: Option<usize>,
+) -> Result<Derived<'a>, CliError> {
+    let resolved = config.resolve(name)?;
+    let length = length.unwrap_or(resolved.length);
+    let password = generator.password(resolved.scheme, resolved.key, length)?;
+    Ok(Derived {
+        key: resolved.key,
+        scheme: resolved.scheme,
+        password,
+        site: resolved.site,
+        username: resolved.username,
+    })
+}
+
+/// Derives the password for a site name or key as [`derive`] does at
+/// `--length`, held to the `--policy` if any
+///
+/// With `--find-length` the password is the shortest prefix of the length-32
+/// derivation, of at least `--min` or the configured length, that meets the
+/// policy, and the length chosen is reported on standard error.
+fn derive_checked<'a>(
+    cli: &Cli,
+    generator: &impl Generate,
+    config: &'a Config,
+    name: &'a str,
+) -> Result<Derived<'a>, CliError> {
+    let Some(policy) = &cli.policy else {
+        return derive(generator, config, name, cli.length);
+    };
+    if !cli.find_length {
+        let derived = derive(generator, config, name, cli.length)?;
+        let unmet = policy.unmet(&derived.password);
+        return match unmet.is_empty() {
+            true => Ok(derived),
+            false => Err(CliError::PolicyUnmet {
+                name: name.to_string(),
+                unmet,
+            }),
+        };
+    }
+    let mut derived = derive(generator, config, name, Some(MAX_LENGTH))?;
+    let min = match cli.min {
+        Some(min) => min,
+        None => config.resolve(name)?.length,
+    };
+    let length = shortest_length_satisfying(&derived.password, min, policy)?;
+    drop(Zeroizing::new(derived.password.split_off(length)));
+    if !cli.quiet {
+        eprintln!("fp: {}", Msg::LengthFound.fill(&[&name, &length]));
+    }
+    Ok(derived)
+}
+
+/// Warns about the password of `name` if `--check-pwned` finds it in breaches
+/// or `blocklist`, the one of `--check-blocklist`, lists it
+fn check_derived(cli: &Cli, blocklist: Option<&Blocklist>, name: &str, derived: &Derived) {
+    if let Some(blocklist) = blocklist {
+        if let Some(warning) = blocklist::check(blocklist, name, &derived.password) {
+            cli.warn(warning);
+        }
+    }
+    if cli.check_pwned {
+        if let Some(warning) = pwned::check(Curl::default(), name, &derived.password) {
+            cli.warn(warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use flowerpassword::config::Config;
+
+    const SITES: &str = "[defaults]\nlength = 12\n\n[sites.github]\nkey = \"github.com\"\nlength = 16\n\n[aliases]\ngh = \"github\"\n";
+
+    #[test]
+    fn test_command() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_derive_resolves_sites_and_aliases() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        assert_eq!(
+            derive(&generator, &config, "github", None)
+                .unwrap()
+                .password,
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(
+            derive(&generator, &config, "gh", Some(8)).unwrap().password,
+            "D04175F7"
+        );
+        // Unknown names are keys, at the configured default length
+        assert_eq!(
+            derive(&generator, &config, "github.com", None)
+                .unwrap()
+                .password,
+            "D04175F7A9c7"
+        );
+    }
+
+    #[test]
+    fn test_length_range() {
+        assert!(Cli::try_parse_from(["fp", "-l", "32", "github"]).is_ok());
+        for length in ["1", "33", "sixteen"] {
+            assert!(Cli::try_parse_from(["fp", "-l", length, "github"]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_format_is_parsed_up_front() {
+        let cli = Cli::try_parse_from(["fp", "--format", "{key}={password}", "github"]).unwrap();
+        assert!(cli.format.is_some());
+        let error = Cli::try_parse_from(["fp", "--format", "{pasword}", "github"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+        assert!(error
+            .to_string()
+            .contains("Unknown placeholder {pasword} at column 1"));
+    }
+
+    #[test]
+    #[cfg(feature = "qr")]
+    fn test_qr_arguments() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).map_err(|error| error.kind());
+        assert!(parse(&["fp", "--qr", "github"]).unwrap().check().is_ok());
+        let cli = parse(&["fp", "--qr", "github", "gitlab.com"]).unwrap();
+        assert_eq!(
+            cli.check().unwrap_err().kind(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+        assert!(parse(&["fp", "--qr", "--qr-format", "svg", "github"]).is_err());
+        assert!(parse(&["fp", "--qr", "--qr-format", "svg", "--qr-out", "a.svg", "x"]).is_ok());
+        assert!(parse(&["fp", "--qr-uri", "github"]).is_err());
+        assert!(parse(&["fp", "--qr", "--format", "{key}", "github"]).is_err());
+    }
+
+    #[test]
+    fn test_confirm_flags() {
+        let flag = |args: &[&str]| Cli::try_parse_from(args).unwrap().confirm_flag();
+        assert_eq!(flag(&["fp", "github"]), None);
+        assert_eq!(flag(&["fp", "--confirm", "github"]), Some(true));
+        assert_eq!(flag(&["fp", "--no-confirm", "github"]), Some(false));
+        assert_eq!(flag(&["fp", "--no-confirm", "--confirm", "x"]), Some(true));
+        assert_eq!(flag(&["fp", "--confirm", "--no-confirm", "x"]), Some(false));
+    }
+
+    #[test]
+    fn test_master_sources_conflict() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).map_err(|error| error.kind());
+        assert!(parse(&["fp", "--password-file", "m", "github"]).is_ok());
+        assert!(parse(&["fp", "--password-file", "m", "--insecure-perms", "x"]).is_ok());
+        assert!(parse(&["fp", "--insecure-perms", "github"]).is_err());
+        assert_eq!(
+            parse(&["fp", "--password-gpg", "m.gpg", "--password-file", "m", "x"]).unwrap_err(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse(&["fp", "--password-file", "m", "--confirm", "github"]).unwrap_err(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                parse(&["fp", "--password-fd", "3", "x"])
+                    .unwrap()
+                    .password_fd,
+                Some(3)
+            );
+            assert_eq!(
+                parse(&["fp", "--password-fd", "3", "--password-file", "m", "x"]).unwrap_err(),
+                clap::error::ErrorKind::ArgumentConflict
+            );
+        }
+    }
+
+    #[test]
+    fn test_master_argument() {
+        let cli = Cli::try_parse_from(["fp", "--master", "fido2:work", "github"]).unwrap();
+        assert_eq!(cli.master.as_deref(), Some("fido2:work"));
+        assert_eq!(cli.keys, ["github"]);
+        let error = Cli::try_parse_from(["fp", "--master", "fido2", "--pinentry", "x"]);
+        assert_eq!(
+            error.unwrap_err().kind(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+        let cli =
+            Cli::try_parse_from(["fp", "--master", "yubikey", "--yubikey-label", "work", "x"]);
+        assert_eq!(cli.unwrap().yubikey_label.as_deref(), Some("work"));
+        let spec = "ssh-agent:SHA256:jysidH8sUN9+mB/5Wx25m7gT4aa66R2kbn4o2cy0Vio";
+        let cli = Cli::try_parse_from(["fp", "--master", spec, "x"]).unwrap();
+        assert_eq!(cli.master.as_deref(), Some(spec));
+        let error = Cli::try_parse_from(["fp", "--yubikey-label", "work", "x"]);
+        assert_eq!(
+            error.unwrap_err().kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn test_pinentry_argument() {
+        let pinentry = |args: &[&str]| Cli::try_parse_from(args).unwrap().pinentry;
+        assert_eq!(pinentry(&["fp", "github"]), None);
+        assert_eq!(
+            pinentry(&["fp", "--pinentry", "github"]).as_deref(),
+            Some("auto")
+        );
+        assert_eq!(
+            pinentry(&["fp", "--pinentry=/usr/bin/pinentry-tty", "x"]).as_deref(),
+            Some("/usr/bin/pinentry-tty")
+        );
+        // The key after a bare --pinentry stays a key
+        let cli = Cli::try_parse_from(["fp", "--pinentry", "github"]).unwrap();
+        assert_eq!(cli.keys, ["github"]);
+        let error = Cli::try_parse_from(["fp", "--pinentry", "--password-file", "m", "x"]);
+        assert_eq!(
+            error.unwrap_err().kind(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "keyring")]
+    fn test_keyring_arguments() {
+        let cli = Cli::try_parse_from(["fp", "--use-keyring", "github"]).unwrap();
+        assert!(cli.use_keyring);
+        let error =
+            Cli::try_parse_from(["fp", "--use-keyring", "--password-file", "m", "x"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+
+        let cli = Cli::try_parse_from(["fp", "keyring", "status"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Keyring {
+                action: KeyringAction::Status
+            })
+        ));
+        assert!(Cli::try_parse_from(["fp", "keyring"]).is_err());
+        assert!(Cli::try_parse_from(["fp", "keyring", "get"]).is_err());
+    }
+
+    #[test]
+    fn test_completions_command() {
+        let cli = Cli::try_parse_from(["fp", "completions", "zsh"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Completions {
+                shell: clap_complete::Shell::Zsh
+            })
+        ));
+        let error = Cli::try_parse_from(["fp", "completions", "tcsh"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::InvalidValue);
+        assert!(error.to_string().contains("possible values: bash"));
+        assert!(Cli::try_parse_from(["fp", "--batch", "completions", "zsh"]).is_err());
+    }
+
+    #[test]
+    fn test_keys_or_batch() {
+        assert!(Cli::try_parse_from(["fp"]).is_err());
+        assert!(Cli::try_parse_from(["fp", "--batch"]).is_ok());
+        assert!(Cli::try_parse_from(["fp", "--batch", "github"]).is_err());
+    }
+}
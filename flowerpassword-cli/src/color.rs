@@ -0,0 +1,113 @@
+//! `--color`, passwords with their character classes in distinct colors
+//!
+//! Digits, uppercase letters, lowercase letters and anything else each get an
+//! ANSI color of their own, which helps to read a password off the screen and
+//! type it by hand. Only the password is colored, not the rest of a `--format`
+//! record.
+//!
+//! `auto` colors only on a terminal, and not with `NO_COLOR` set; `always`
+//! colors even when piped. `--quiet` and `--raw` always turn it off.
+
+use clap::ValueEnum;
+
+/// Color of digits: blue
+const DIGIT: &str = "\x1b[34m";
+/// Color of uppercase letters: green
+const UPPER: &str = "\x1b[32m";
+/// Color of lowercase letters: yellow
+const LOWER: &str = "\x1b[33m";
+/// Color of every other character: magenta
+const SYMBOL: &str = "\x1b[35m";
+/// Resets the color after a password
+const RESET: &str = "\x1b[0m";
+
+/// When to color passwords
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ColorChoice {
+    /// On a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Always, even when piped
+    Always,
+    /// Never
+    Never,
+}
+
+/// Returns whether to color passwords written to standard output
+///
+/// `no_color` is whether `NO_COLOR` is set to anything but the empty string.
+pub(crate) fn enabled(
+    choice: ColorChoice,
+    is_tty: bool,
+    no_color: bool,
+    quiet: bool,
+    raw: bool,
+) -> bool {
+    if quiet || raw {
+        return false;
+    }
+    match choice {
+        ColorChoice::Auto => is_tty && !no_color,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+/// Returns `password` with each run of one character class in its color,
+/// ending with a reset
+pub(crate) fn colorize(password: &str) -> String {
+    let mut out = String::with_capacity(password.len() * 4);
+    let mut current = None;
+    for c in password.chars() {
+        let color = match c {
+            '0'..='9' => DIGIT,
+            'A'..='Z' => UPPER,
+            'a'..='z' => LOWER,
+            _ => SYMBOL,
+        };
+        if current != Some(color) {
+            out.push_str(color);
+            current = Some(color);
+        }
+        out.push(c);
+    }
+    if current.is_some() {
+        out.push_str(RESET);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colorize() {
+        // fp_code("test", "key", 8)
+        assert_eq!(
+            colorize("KAC5fEf0"),
+            "\x1b[32mKAC\x1b[34m5\x1b[33mf\x1b[32mE\x1b[33mf\x1b[34m0\x1b[0m"
+        );
+        assert_eq!(colorize("a-1"), "\x1b[33ma\x1b[35m-\x1b[34m1\x1b[0m");
+        assert_eq!(colorize(""), "");
+    }
+
+    #[test]
+    fn test_enabled() {
+        use ColorChoice::{Always, Auto, Never};
+        // On a terminal, and only without NO_COLOR
+        assert!(enabled(Auto, true, false, false, false));
+        assert!(!enabled(Auto, true, true, false, false));
+        // Piped
+        assert!(!enabled(Auto, false, false, false, false));
+        assert!(enabled(Always, false, false, false, false));
+        assert!(enabled(Always, true, true, false, false));
+        assert!(!enabled(Never, true, false, false, false));
+        // --quiet and --raw win over everything
+        for (quiet, raw) in [(true, false), (false, true), (true, true)] {
+            for choice in [Auto, Always, Never] {
+                assert!(!enabled(choice, true, false, quiet, raw));
+            }
+        }
+    }
+}
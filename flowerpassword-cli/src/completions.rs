@@ -0,0 +1,235 @@
+//! `fp completions`, shell completion scripts
+//!
+//! The scripts are generated by clap_complete from the same definition clap
+//! parses with, so they always cover every subcommand and flag. For bash and
+//! fish they also complete site names and aliases, by running the hidden
+//! `fp __complete PREFIX` the way kubectl completes resource names. That mode
+//! only reads the configuration: it never asks for the master password, and it
+//! prints nothing at all if the configuration is missing or unreadable.
+
+use crate::cli::Cli;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use flowerpassword::config::{load_layered, Config};
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the hidden subcommand printing site names for completion
+pub(crate) const COMPLETE_COMMAND: &str = "__complete";
+
+/// Arguments of `fp __complete`
+///
+/// They are parsed apart from [`Cli`], since clap_complete would otherwise offer
+/// the hidden subcommand in the scripts it generates.
+#[derive(Debug, Parser)]
+pub(crate) struct CompleteArgs {
+    /// Configuration file to read instead of the user's
+    #[arg(long, value_name = "PATH")]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Start of the name being completed
+    #[arg(default_value = "")]
+    pub(crate) prefix: String,
+}
+
+/// Writes the completion script for `shell` to `out`
+pub(crate) fn write(shell: Shell, out: &mut dyn Write) -> io::Result<()> {
+    let mut command = Cli::command();
+    // Builds the command, adding the help subcommand listed below
+    clap_complete::generate(shell, &mut command, "fp", out);
+    match shell {
+        Shell::Bash => write!(
+            out,
+            r#"
+# Site names and aliases from the configuration, listed by `fp {complete}`
+_fp_sites() {{
+    _fp "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" config=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            {subcommands}) return ;;
+            --config) config=(--config "${{COMP_WORDS[i+1]}}") ;;
+        esac
+    done
+    case "$prev" in
+        {value_options}) return ;;
+    esac
+    [[ "$cur" == -* ]] && return
+    local IFS=$'\n'
+    COMPREPLY+=( $(fp {complete} "${{config[@]}}" -- "$cur" 2>/dev/null) )
+}}
+complete -F _fp_sites -o bashdefault -o default fp
+"#,
+            complete = COMPLETE_COMMAND,
+            subcommands = subcommands(&command).join("|"),
+            value_options = value_options(&command).join("|"),
+        ),
+        Shell::Fish => writeln!(
+            out,
+            r#"complete -c fp -n "__fish_fp_needs_command" -f -a "(fp {} -- (commandline -ct) 2>/dev/null)""#,
+            COMPLETE_COMMAND
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Names of the visible subcommands of a built `command`, including `help`
+fn subcommands(command: &clap::Command) -> Vec<&str> {
+    command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .map(|subcommand| subcommand.get_name())
+        .collect()
+}
+
+/// Spellings of the options that take a value, after which no key is completed
+fn value_options(command: &clap::Command) -> Vec<String> {
+    let mut options = Vec::new();
+    for arg in command.get_arguments() {
+        if arg.is_positional() || !arg.get_action().takes_values() {
+            continue;
+        }
+        options.extend(arg.get_long().map(|long| format!("--{}", long)));
+        options.extend(arg.get_short().map(|short| format!("-{}", short)));
+    }
+    options
+}
+
+/// Runs `fp __complete` with the arguments after the subcommand name
+pub(crate) fn run_complete(args: impl Iterator<Item = OsString>) {
+    let name = OsString::from(format!("fp {}", COMPLETE_COMMAND));
+    let args = CompleteArgs::parse_from(std::iter::once(name).chain(args));
+    let mut out = io::stdout().lock();
+    // A shell waiting on completions cannot show errors, so none are reported
+    let _ = complete(args.config.as_deref(), &args.prefix, &mut out).and_then(|()| out.flush());
+}
+
+/// Returns the site names and aliases in `config` that start with `prefix`
+pub(crate) fn site_names<'a>(config: &'a Config, prefix: &str) -> Vec<&'a str> {
+    let mut names: Vec<&str> = config
+        .sites
+        .keys()
+        .chain(config.aliases.keys())
+        .map(String::as_str)
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Prints the completions for `prefix`, one per line
+///
+/// A configuration that cannot be loaded has no names to offer.
+pub(crate) fn complete(config: Option<&Path>, prefix: &str, out: &mut dyn Write) -> io::Result<()> {
+    let Ok(loaded) = load_layered(config) else {
+        return Ok(());
+    };
+    for name in site_names(&loaded.config, prefix) {
+        writeln!(out, "{}", name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+    use flowerpassword::config::Config;
+
+    fn script(shell: Shell) -> String {
+        let mut out = Vec::new();
+        write(shell, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_every_shell_knows_the_flags() {
+        for shell in Shell::value_variants() {
+            let script = script(*shell);
+            for flag in ["length", "copy", "batch", "format", "password-file"] {
+                let option = match shell {
+                    Shell::Fish => format!("-l {}", flag),
+                    _ => format!("--{}", flag),
+                };
+                // The whole option, so that --copy-user-then-pass is no --copy
+                let whole = script.match_indices(&option).any(|(start, _)| {
+                    !script[start + option.len()..]
+                        .starts_with(|c: char| c == '-' || c.is_alphanumeric())
+                });
+                assert!(whole, "{} lacks {}", shell, option);
+            }
+            assert!(script.contains("completions"), "{}", shell);
+        }
+    }
+
+    #[test]
+    fn test_dynamic_hooks() {
+        let bash = script(Shell::Bash);
+        assert!(bash
+            .contains("COMPREPLY+=( $(fp __complete \"${config[@]}\" -- \"$cur\" 2>/dev/null) )"));
+        assert!(bash.contains("        --length|-l|--policy|--min|--config|"));
+        assert!(bash.contains("            completions|") && bash.contains("|help) return ;;"));
+        assert!(script(Shell::Fish).contains("(fp __complete -- (commandline -ct) 2>/dev/null)"));
+    }
+
+    #[test]
+    fn test_hidden_command_stays_out_of_scripts() {
+        for shell in Shell::value_variants() {
+            let script = script(*shell).replace("fp __complete", "");
+            assert!(!script.contains(COMPLETE_COMMAND), "{} offers it", shell);
+        }
+        let args = CompleteArgs::try_parse_from(["fp", "--config", "c.toml", "--", "gi"]);
+        assert_eq!(args.unwrap().prefix, "gi");
+    }
+
+    #[test]
+    fn test_site_names() {
+        let source = "[sites.github]\n[sites.gitlab]\nkey = \"gitlab.com\"\n[sites.mail]\n\n[aliases]\ngh = \"github\"\ngit = \"github\"\n";
+        let config = Config::from_toml_str(source, "config.toml").unwrap().config;
+        assert_eq!(site_names(&config, "gi"), ["git", "github", "gitlab"]);
+        assert_eq!(site_names(&config, "g"), ["gh", "git", "github", "gitlab"]);
+        assert_eq!(
+            site_names(&config, ""),
+            ["gh", "git", "github", "gitlab", "mail"]
+        );
+        assert!(site_names(&config, "x").is_empty());
+    }
+
+    #[test]
+    fn test_complete_without_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = Vec::new();
+        complete(Some(&dir.path().join("missing.toml")), "", &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    /// The fish script lists one flag per line, so changes show up in review
+    ///
+    /// The snapshot is of a Linux build with every feature, as in CI; run
+    /// `FP_UPDATE_SNAPSHOTS=1 cargo test -p flowerpassword-cli --all-features` to
+    /// accept a change.
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        feature = "dbus",
+        feature = "grpc",
+        feature = "http",
+        feature = "keyring",
+        feature = "qr"
+    ))]
+    fn test_fish_snapshot() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/fp.fish");
+        let script = script(Shell::Fish);
+        if std::env::var_os("FP_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(path, &script).unwrap();
+        }
+        let snapshot = std::fs::read_to_string(path).unwrap();
+        assert!(
+            script == snapshot,
+            "fish completions changed; rerun with FP_UPDATE_SNAPSHOTS=1 and review {}",
+            path
+        );
+    }
+}
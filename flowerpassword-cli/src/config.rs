@@ -0,0 +1,321 @@
+//! `fp config`, looking after the configuration files
+//!
+//! `fp config path` lists the file of every configuration layer in the order
+//! they are applied, later layers winning, and whether each exists.
+//! `fp config lint` checks every file that exists, or must, with
+//! [`lint`], decrypting encrypted ones, and fails if it found an error.
+//! `fp config migrate` upgrades one file to the current schema version,
+//! keeping a backup, or with `--dry-run` only tells what it would change.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::config::{
+    decrypt, is_encrypted, lint, migrate_file, migrate_str, ConfigError, ConfigPath, Diagnostic,
+    Migration,
+};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Column headers of `fp config path`
+const PATH_HEADER: [&str; 3] = ["LAYER", "STATUS", "PATH"];
+
+/// Writes the layer, whether it was found and the path of each of `paths`
+pub(crate) fn write_paths(out: &mut impl Write, paths: &[ConfigPath]) -> io::Result<()> {
+    let rows: Vec<[String; 3]> = paths
+        .iter()
+        .map(|layer| {
+            let status = match layer.found {
+                true => "found",
+                false => "missing",
+            };
+            [
+                layer.kind.to_string(),
+                status.to_string(),
+                layer.path.display().to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = PATH_HEADER.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = PATH_HEADER.map(str::to_string);
+    for [layer, status, path] in std::iter::once(&header).chain(&rows) {
+        writeln!(
+            out,
+            "{:<layer_width$}  {:<status_width$}  {}",
+            layer,
+            status,
+            path,
+            layer_width = widths[0],
+            status_width = widths[1],
+        )?;
+    }
+    Ok(())
+}
+
+/// What `fp config lint` found in the configuration files
+pub(crate) struct Lint {
+    /// How many files were checked
+    pub(crate) files: usize,
+    /// Every problem found, file by file in layer order
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl Lint {
+    /// Checks the file of every layer of `paths` that exists or is required,
+    /// asking `passphrase` for those that are encrypted
+    pub(crate) fn run(
+        paths: &[ConfigPath],
+        mut passphrase: impl FnMut(&Path) -> Result<String, CliError>,
+    ) -> Result<Self, CliError> {
+        let mut checked = Lint {
+            files: 0,
+            diagnostics: Vec::new(),
+        };
+        for layer in paths.iter().filter(|l| l.found || l.kind.is_required()) {
+            let path = &layer.path;
+            let origin = path.display().to_string();
+            let io_error = |source| ConfigError::Io {
+                path: path.clone(),
+                source,
+            };
+            let data = fs::read(path).map_err(io_error)?;
+            let data = match is_encrypted(&data) {
+                true => decrypt(&data, &passphrase(path)?, &origin)?,
+                false => Zeroizing::new(data),
+            };
+            let source = std::str::from_utf8(&data)
+                .map_err(|error| io_error(io::Error::new(io::ErrorKind::InvalidData, error)))?;
+            checked.diagnostics.extend(lint(source, &origin));
+            checked.files += 1;
+        }
+        Ok(checked)
+    }
+
+    /// Writes one line per diagnostic
+    pub(crate) fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        for diagnostic in &self.diagnostics {
+            writeln!(out, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if any error was found; warnings alone are only listed
+    pub(crate) fn result(&self) -> Result<(), CliError> {
+        let errors = self.diagnostics.iter().filter(|d| d.is_error()).count();
+        match errors {
+            0 => Ok(()),
+            errors => Err(CliError::Lint {
+                errors,
+                warnings: self.diagnostics.len() - errors,
+            }),
+        }
+    }
+}
+
+/// Upgrades the configuration file at `path` to the current schema version,
+/// or only reports what would change with `dry_run`, summing up on `out`
+pub(crate) fn migrate(out: &mut impl Write, path: &Path, dry_run: bool) -> Result<(), CliError> {
+    let (migration, backup) = match dry_run {
+        true => {
+            let source = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            let (_, migration) = migrate_str(&source, &path.display().to_string())?;
+            (migration, None)
+        }
+        false => {
+            let migrated = migrate_file(path)?;
+            (migrated.migration, migrated.backup)
+        }
+    };
+    let Migration { from, to, changes } = &migration;
+    let summary = match (migration.is_noop(), backup) {
+        (true, _) => Msg::MigrateCurrent.fill(&[&path.display(), to]),
+        (false, Some(backup)) => {
+            Msg::Migrated.fill(&[&path.display(), from, to, &backup.display()])
+        }
+        (false, None) => Msg::MigrateDryRun.fill(&[&path.display(), from, to]),
+    };
+    writeln!(out, "{}", summary)?;
+    for change in changes {
+        writeln!(out, "- {}", change)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::LayerKind;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write_paths() {
+        let paths = [
+            ConfigPath {
+                kind: LayerKind::User,
+                path: PathBuf::from("/home/me/.config/flowerpassword/config.toml"),
+                found: true,
+            },
+            ConfigPath {
+                kind: LayerKind::Environment,
+                path: PathBuf::from("work.toml"),
+                found: false,
+            },
+        ];
+        let mut out = Vec::new();
+        write_paths(&mut out, &paths).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "LAYER      STATUS   PATH\n\
+             user       found    /home/me/.config/flowerpassword/config.toml\n\
+             FP_CONFIG  missing  work.toml\n"
+        );
+        let mut out = Vec::new();
+        write_paths(&mut out, &[]).unwrap();
+        assert_eq!(out, b"LAYER  STATUS  PATH\n");
+    }
+
+    #[test]
+    fn test_lint() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.toml");
+        fs::write(&good, "[sites.github]\nkey = \"github.com\"\n").unwrap();
+        let bad = dir.path().join("bad.toml");
+        fs::write(
+            &bad,
+            "[defaults]\nlenght = 12\n\n[sites.mail]\nlength = 64\n",
+        )
+        .unwrap();
+        let layer = |kind, path: &PathBuf, found| ConfigPath {
+            kind,
+            path: path.clone(),
+            found,
+        };
+
+        let no_passphrase = |_: &Path| -> Result<String, CliError> { panic!("not encrypted") };
+        let lint = Lint::run(
+            &[
+                layer(LayerKind::System, &dir.path().join("absent.toml"), false),
+                layer(LayerKind::User, &good, true),
+            ],
+            no_passphrase,
+        )
+        .unwrap();
+        assert_eq!(lint.files, 1);
+        assert!(lint.diagnostics.is_empty());
+        assert!(lint.result().is_ok());
+
+        let lint = Lint::run(
+            &[
+                layer(LayerKind::User, &good, true),
+                layer(LayerKind::Override, &bad, true),
+            ],
+            no_passphrase,
+        )
+        .unwrap();
+        assert_eq!(lint.files, 2);
+        let mut out = Vec::new();
+        lint.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 2, "{}", out);
+        assert!(out.contains("bad.toml:2: warning: "), "{}", out);
+        assert!(out.contains("bad.toml:5: error: "), "{}", out);
+        assert!(matches!(
+            lint.result(),
+            Err(CliError::Lint {
+                errors: 1,
+                warnings: 1
+            })
+        ));
+
+        // A required file that is missing cannot be checked
+        let missing = layer(LayerKind::Override, &dir.path().join("absent.toml"), false);
+        assert!(matches!(
+            Lint::run(&[missing], no_passphrase),
+            Err(CliError::Config(ConfigError::Io { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_lint_decrypts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let source = "[sites.github]\nkye = \"github.com\"\n";
+        fs::write(
+            &path,
+            flowerpassword::config::encrypt(source.as_bytes(), "pw"),
+        )
+        .unwrap();
+        let layers = [ConfigPath {
+            kind: LayerKind::User,
+            path: path.clone(),
+            found: true,
+        }];
+        let mut asked = 0;
+        let lint = Lint::run(&layers, |asked_for| {
+            assert_eq!(asked_for, path);
+            asked += 1;
+            Ok("pw".to_string())
+        })
+        .unwrap();
+        assert_eq!(asked, 1);
+        assert_eq!(lint.diagnostics.len(), 1);
+        assert_eq!(lint.diagnostics[0].line, 2);
+        // An unknown key is only a warning
+        assert!(lint.result().is_ok());
+
+        let wrong = Lint::run(&layers, |_| Ok("wp".to_string()));
+        assert!(matches!(
+            wrong,
+            Err(CliError::Config(ConfigError::Decrypt { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let v0 = "[sites.github]\nkey = \"github.com\"\n";
+        fs::write(&path, v0).unwrap();
+        let run = |dry_run| {
+            let mut out = Vec::new();
+            migrate(&mut out, &path, dry_run).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            run(true),
+            format!(
+                "{} would be migrated from version 0 to 1\n- added `version = 1`\n",
+                path.display()
+            )
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), v0);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let migrated = run(false);
+        assert!(migrated.starts_with(&format!(
+            "Migrated {} from version 0 to 1, the original is in ",
+            path.display()
+        )));
+        assert!(migrated.ends_with(".bak\n- added `version = 1`\n"));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("version = 1\n"));
+
+        assert_eq!(
+            run(false),
+            format!("{} is already at version 1\n", path.display())
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
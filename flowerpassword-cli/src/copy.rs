@@ -0,0 +1,119 @@
+//! `--copy-user-then-pass`, filling a login form from the clipboard in two steps
+//!
+//! The username configured for the site is copied first. Once it is pasted,
+//! Enter on the terminal, or a few seconds without one, copies the password in
+//! its place, and the clipboard is put back as it was after `DEFAULT_TTL`. A
+//! site without a username skips the first step; a username is never derived
+//! or guessed.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::clipboard::{copy_with_ttl_using, Clipboard, ClipboardGuard};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// How long the username stays on the clipboard without a terminal to press
+/// Enter on
+pub(crate) const USER_DELAY: Duration = Duration::from_secs(5);
+
+/// Copies `username`, if any, to `clipboard`, calls `wait`, then copies
+/// `password` for `ttl` and returns its guard
+///
+/// The username is taken off the clipboard again before the password goes on,
+/// so the guard puts back what the clipboard held before either.
+pub(crate) fn user_then_pass<C: Clipboard + Clone + 'static>(
+    clipboard: C,
+    username: Option<&str>,
+    password: &str,
+    ttl: Duration,
+    wait: impl FnOnce() -> io::Result<()>,
+) -> Result<ClipboardGuard, CliError> {
+    if let Some(username) = username {
+        let guard = copy_with_ttl_using(clipboard.clone(), username, ttl)?;
+        wait()?;
+        guard.restore()?;
+    }
+    Ok(copy_with_ttl_using(clipboard, password, ttl)?)
+}
+
+/// Tells on `err` that the username of `name` is on the clipboard and how the
+/// password follows, by Enter on a terminal or else after [`USER_DELAY`]
+pub(crate) fn announce(err: &mut impl Write, name: &str, terminal: bool) -> io::Result<()> {
+    match terminal {
+        true => write!(err, "{} ", Msg::UserCopiedPressEnter.fill(&[&name]))?,
+        false => {
+            let seconds = USER_DELAY.as_secs();
+            writeln!(err, "{}", Msg::UserCopiedWaiting.fill(&[&name, &seconds]))?
+        }
+    }
+    err.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{MemoryClipboard, Outcome};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_user_then_pass_sequence() {
+        let clipboard = MemoryClipboard::new(Some("before"));
+        let seen = RefCell::new(Vec::new());
+        let guard = user_then_pass(
+            clipboard.clone(),
+            Some("octocat"),
+            "D04175F7A9c7Ab4a",
+            Duration::from_secs(60),
+            || {
+                seen.borrow_mut().push(clipboard.contents());
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The username was on the clipboard while waiting, the password after
+        assert_eq!(seen.into_inner(), [Some("octocat".to_string())]);
+        assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+    }
+
+    #[test]
+    fn test_without_username_copies_the_password_only() {
+        let clipboard = MemoryClipboard::new(None);
+        let guard = user_then_pass(
+            clipboard.clone(),
+            None,
+            "D04175F7A9c7Ab4a",
+            Duration::from_secs(60),
+            || panic!("nothing to wait for"),
+        )
+        .unwrap();
+        assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Cleared);
+    }
+
+    #[test]
+    fn test_failed_wait_leaves_the_clipboard() {
+        let clipboard = MemoryClipboard::new(Some("before"));
+        let result = user_then_pass(
+            clipboard.clone(),
+            Some("octocat"),
+            "D04175F7A9c7Ab4a",
+            Duration::from_secs(60),
+            || Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        );
+        assert!(matches!(result, Err(CliError::Io(_))));
+        // Dropping the username's guard put the clipboard back
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+    }
+
+    #[test]
+    fn test_announce() {
+        let mut err = Vec::new();
+        announce(&mut err, "github", false).unwrap();
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "Copied the username of github; the password follows in 5s\n"
+        );
+    }
+}
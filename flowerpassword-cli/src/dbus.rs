@@ -0,0 +1,370 @@
+//! `fp serve --dbus`, a session D-Bus service for desktop integration
+//!
+//! It owns the name `org.flowerpassword.Derive1` and exports the interface of
+//! the same name at `/org/flowerpassword/Derive1`, as described by
+//! `dbus/org.flowerpassword.Derive1.xml`:
+//!
+//! - `Derive(s key, u length) -> s` derives a password.
+//! - `Lock()` wipes the master password. `Derive` fails with
+//!   `org.flowerpassword.Error.Locked` after that.
+//! - The `Locked` property tells whether `Lock` was called.
+//! - The `DerivationPerformed(s key, t timestamp)` signal follows every
+//!   derivation. It carries the key and the seconds since the Unix epoch,
+//!   never the password.
+//!
+//! Unlike the HTTP and gRPC APIs, the service holds the master password: it is
+//! read once when `fp serve` starts. So every method call is checked against
+//! the caller's Unix user, as the bus reports it, and calls from other users
+//! fail with `org.flowerpassword.Error.AccessDenied`.
+
+use crate::i18n::Msg;
+use flowerpassword::FpGenerator;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zbus::fdo::DBusProxy;
+use zbus::message::Header;
+use zbus::object_server::SignalContext;
+use zbus::{connection, interface, Connection};
+
+/// The well-known name, and the name of the interface
+pub(crate) const NAME: &str = "org.flowerpassword.Derive1";
+
+/// The path of the exported object
+pub(crate) const PATH: &str = "/org/flowerpassword/Derive1";
+
+/// Why a method call was refused
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.flowerpassword.Error")]
+pub(crate) enum ServiceError {
+    /// The bus itself failed
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// `Lock` was called before
+    Locked(String),
+    /// The length is not between 2 and 32
+    InvalidLength(String),
+    /// The caller runs as another user
+    AccessDenied(String),
+}
+
+/// Refuses a caller running as another user than the service
+pub(crate) fn check_caller(caller: u32, own: u32) -> Result<(), ServiceError> {
+    if caller == own {
+        Ok(())
+    } else {
+        Err(ServiceError::AccessDenied(format!(
+            "Calls from user {} are refused",
+            caller
+        )))
+    }
+}
+
+/// Asks the bus for the Unix user of the sender of `header`
+async fn check_sender(connection: &Connection, header: &Header<'_>) -> Result<(), ServiceError> {
+    let Some(sender) = header.sender() else {
+        return Err(ServiceError::AccessDenied(
+            "Calls without a sender are refused".to_string(),
+        ));
+    };
+    let caller = DBusProxy::new(connection)
+        .await?
+        .get_connection_unix_user(sender.clone().into())
+        .await
+        .map_err(zbus::Error::from)?;
+    // SAFETY: getuid cannot fail and has no preconditions
+    check_caller(caller, unsafe { libc::getuid() })
+}
+
+/// The exported object, holding the master password until `Lock`
+pub(crate) struct Service {
+    generator: Option<FpGenerator>,
+}
+
+impl Service {
+    pub(crate) fn new(generator: FpGenerator) -> Self {
+        Service {
+            generator: Some(generator),
+        }
+    }
+
+    /// Derives the password for `key`, with which `Derive` answers
+    fn derive_password(&self, key: &str, length: u32) -> Result<String, ServiceError> {
+        let generator = self.generator.as_ref().ok_or_else(|| {
+            ServiceError::Locked("The master password was wiped by Lock".to_string())
+        })?;
+        generator
+            .generate_with_length(key, length as usize)
+            .map_err(|error| ServiceError::InvalidLength(error.to_string()))
+    }
+}
+
+#[interface(name = "org.flowerpassword.Derive1")]
+impl Service {
+    /// Derives the password for `key`
+    async fn derive(
+        &self,
+        key: String,
+        length: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<String, ServiceError> {
+        check_sender(connection, &header).await?;
+        let password = self.derive_password(&key, length)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self::derivation_performed(&ctxt, &key, timestamp).await?;
+        Ok(password)
+    }
+
+    /// Wipes the master password for the rest of the run
+    async fn lock(
+        &mut self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), ServiceError> {
+        check_sender(connection, &header).await?;
+        self.generator = None;
+        self.locked_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    /// Whether `Lock` was called
+    #[zbus(property)]
+    fn locked(&self) -> bool {
+        self.generator.is_none()
+    }
+
+    /// Sent after each derivation
+    #[zbus(signal)]
+    async fn derivation_performed(
+        ctxt: &SignalContext<'_>,
+        key: &str,
+        timestamp: u64,
+    ) -> zbus::Result<()>;
+}
+
+/// Connects with `builder`, exporting the service and owning its name
+async fn connect(
+    builder: connection::Builder<'_>,
+    generator: FpGenerator,
+) -> zbus::Result<Connection> {
+    builder
+        .name(NAME)?
+        .serve_at(PATH, Service::new(generator))?
+        .build()
+        .await
+}
+
+/// Serves on the session bus until the process is stopped, printing the name
+/// it owns to `out`
+pub(crate) async fn serve(generator: FpGenerator, out: &mut impl Write) -> io::Result<()> {
+    let builder = connection::Builder::session().map_err(io::Error::other)?;
+    let _connection = connect(builder, generator)
+        .await
+        .map_err(io::Error::other)?;
+    writeln!(out, "{}", Msg::DbusServing.fill(&[&NAME]))?;
+    out.flush()?;
+    std::future::pending().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::path::Path;
+    use std::process::{Child, Command, Stdio};
+
+    #[zbus::proxy(
+        interface = "org.flowerpassword.Derive1",
+        default_service = "org.flowerpassword.Derive1",
+        default_path = "/org/flowerpassword/Derive1"
+    )]
+    trait Derive1 {
+        fn derive(&self, key: &str, length: u32) -> zbus::Result<String>;
+
+        fn lock(&self) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn locked(&self) -> zbus::Result<bool>;
+
+        #[zbus(signal)]
+        fn derivation_performed(&self, key: String, timestamp: u64) -> zbus::Result<()>;
+    }
+
+    /// A private bus, stopped when dropped
+    struct Bus {
+        daemon: Child,
+        address: String,
+        _dir: tempfile::TempDir,
+    }
+
+    impl Bus {
+        /// Starts a dbus-daemon, or returns `None` if there is none to run
+        fn start() -> Option<Bus> {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("bus.conf");
+            std::fs::write(&config, bus_config(dir.path())).unwrap();
+            let mut daemon = match Command::new("dbus-daemon")
+                .arg("--nofork")
+                .arg("--print-address")
+                .arg(format!("--config-file={}", config.display()))
+                .stdout(Stdio::piped())
+                .spawn()
+            {
+                Ok(daemon) => daemon,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    eprintln!("dbus-daemon is not installed, skipping");
+                    return None;
+                }
+                Err(error) => panic!("{}", error),
+            };
+            let mut address = String::new();
+            let stdout = daemon.stdout.take().unwrap();
+            io::BufRead::read_line(&mut io::BufReader::new(stdout), &mut address).unwrap();
+            Some(Bus {
+                daemon,
+                address: address.trim().to_string(),
+                _dir: dir,
+            })
+        }
+
+        async fn connect(&self) -> Connection {
+            connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        }
+
+        async fn serve(&self, master: &str) -> Connection {
+            let builder = connection::Builder::address(self.address.as_str()).unwrap();
+            let generator = FpGenerator::new(master.to_string(), 16).unwrap();
+            connect(builder, generator).await.unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// A session bus that lets its user do anything, listening in `dir`
+    fn bus_config(dir: &Path) -> String {
+        format!(
+            r#"<busconfig>
+  <type>session</type>
+  <listen>unix:dir={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+            dir.display()
+        )
+    }
+
+    #[test]
+    fn test_check_caller() {
+        assert!(check_caller(1000, 1000).is_ok());
+        let error = check_caller(1001, 1000).unwrap_err();
+        assert!(matches!(error, ServiceError::AccessDenied(_)));
+        assert_eq!(
+            zbus::DBusError::name(&error).as_str(),
+            "org.flowerpassword.Error.AccessDenied"
+        );
+    }
+
+    #[test]
+    fn test_derive_password() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let mut service = Service::new(generator);
+        assert_eq!(
+            service.derive_password("github.com", 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(service.derive_password("key", 8).unwrap(), "KAC5fEf0");
+        let error = service.derive_password("key", 40).unwrap_err();
+        assert!(matches!(error, ServiceError::InvalidLength(_)));
+        service.generator = None;
+        let error = service.derive_password("key", 16).unwrap_err();
+        assert!(matches!(error, ServiceError::Locked(_)));
+    }
+
+    #[tokio::test]
+    async fn test_service() {
+        let Some(bus) = Bus::start() else {
+            return;
+        };
+        let _server = bus.serve("test").await;
+        let client = bus.connect().await;
+        let proxy = Derive1Proxy::new(&client).await.unwrap();
+        let mut signals = proxy.receive_derivation_performed().await.unwrap();
+
+        assert!(!proxy.locked().await.unwrap());
+        assert_eq!(
+            proxy.derive("github.com", 16).await.unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        let signal = signals.next().await.unwrap();
+        let args = signal.args().unwrap();
+        assert_eq!(args.key, "github.com");
+        assert!(args.timestamp > 1_600_000_000);
+
+        let error = proxy.derive("key", 40).await.unwrap_err();
+        assert!(
+            matches!(&error, zbus::Error::MethodError(name, Some(message), _)
+                if name.as_str() == "org.flowerpassword.Error.InvalidLength"
+                    && message == "Length must be between 2 and 32, got: 40"),
+            "{:?}",
+            error
+        );
+
+        proxy.lock().await.unwrap();
+        assert!(proxy.locked().await.unwrap());
+        let error = proxy.derive("github.com", 16).await.unwrap_err();
+        assert!(
+            matches!(&error, zbus::Error::MethodError(name, _, _)
+                if name.as_str() == "org.flowerpassword.Error.Locked"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_introspection_xml() {
+        let Some(bus) = Bus::start() else {
+            return;
+        };
+        let _server = bus.serve("test").await;
+        let client = bus.connect().await;
+        let xml = zbus::fdo::IntrospectableProxy::builder(&client)
+            .destination(NAME)
+            .unwrap()
+            .path(PATH)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+            .introspect()
+            .await
+            .unwrap();
+        let start = xml
+            .find(&format!(r#"<interface name="{}">"#, NAME))
+            .unwrap();
+        let end = start + xml[start..].find("</interface>").unwrap();
+        let exported: Vec<_> = xml[start..end].lines().map(str::trim).collect();
+        let file = include_str!("../dbus/org.flowerpassword.Derive1.xml");
+        let start = file.find("<interface").unwrap();
+        let end = start + file[start..].find("</interface>").unwrap();
+        let described: Vec<_> = file[start..end].lines().map(str::trim).collect();
+        assert_eq!(exported, described);
+    }
+}
@@ -0,0 +1,522 @@
+//! `fp doctor`, checks of what `fp` depends on
+//!
+//! Each [`Probe`] looks at one thing, such as the configuration or the
+//! clipboard, and reports a [`Finding`]: PASS, WARN or FAIL with a detail and,
+//! unless it passed, a hint on what to do.
+//!
+//! ```text
+//! STATUS  CHECK      DETAIL
+//! PASS    config     3 site(s), no problems
+//! WARN    clipboard  No clipboard program for this session
+//!                    hint: Install wl-clipboard on Wayland, or xclip or xsel on X11
+//! ```
+//!
+//! Nothing is ever derived, and no master password is asked for. The run fails
+//! only if a probe FAILs; a WARN is something that some options need.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::agent::{AgentClient, Endpoint, Request, SystemTransport};
+use flowerpassword::clipboard::CommandClipboard;
+use flowerpassword::config::{load_layered, ConfigError};
+use flowerpassword::harden::{Hardening, Measure};
+use flowerpassword::memlock::{LockStatus, LockedBuf};
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// Column headers of `fp doctor`
+const HEADER: [&str; 3] = ["STATUS", "CHECK", "DETAIL"];
+
+/// How a check went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level {
+    /// Everything is in place
+    Pass,
+    /// Some options will not work
+    Warn,
+    /// Runs will fail
+    Fail,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Pass => "PASS",
+            Level::Warn => "WARN",
+            Level::Fail => "FAIL",
+        })
+    }
+}
+
+/// What a probe found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Finding {
+    pub(crate) level: Level,
+    pub(crate) detail: String,
+    /// What to do about it
+    pub(crate) hint: Option<String>,
+}
+
+impl Finding {
+    pub(crate) fn pass(detail: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub(crate) fn warn(detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    pub(crate) fn fail(detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// One check of `fp doctor`
+pub(crate) trait Probe {
+    /// Name of what is checked, as shown in the CHECK column
+    fn name(&self) -> &'static str;
+    /// Runs the check
+    fn check(&self) -> Finding;
+}
+
+/// The findings of a run of probes, in the order they ran
+#[derive(Debug)]
+pub(crate) struct Report {
+    pub(crate) rows: Vec<(&'static str, Finding)>,
+}
+
+impl Report {
+    /// Runs every probe, one after the other
+    pub(crate) fn run(probes: &[Box<dyn Probe>]) -> Self {
+        Report {
+            rows: probes
+                .iter()
+                .map(|probe| (probe.name(), probe.check()))
+                .collect(),
+        }
+    }
+
+    /// Returns `CliError::Doctor` if any probe failed
+    pub(crate) fn result(&self) -> Result<(), CliError> {
+        let failed = self
+            .rows
+            .iter()
+            .filter(|(_, finding)| finding.level == Level::Fail)
+            .count();
+        match failed {
+            0 => Ok(()),
+            failed => Err(CliError::Doctor {
+                failed,
+                total: self.rows.len(),
+            }),
+        }
+    }
+
+    /// Writes the findings as columns under a header, with each hint on a line
+    /// of its own below
+    pub(crate) fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let name_width = self
+            .rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain([HEADER[1].len()])
+            .max()
+            .unwrap_or_default();
+        let [status, check, detail] = HEADER;
+        writeln!(out, "{:<6}  {:<name_width$}  {}", status, check, detail)?;
+        for (name, finding) in &self.rows {
+            writeln!(
+                out,
+                "{:<6}  {:<name_width$}  {}",
+                finding.level, name, finding.detail
+            )?;
+            if let Some(hint) = &finding.hint {
+                writeln!(
+                    out,
+                    "{:<6}  {:<name_width$}  {}: {}",
+                    "",
+                    "",
+                    Msg::Hint.text(),
+                    hint
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the probes of this system, reading the configuration at `config`
+/// or else the layered one
+pub(crate) fn system_probes(
+    config: Option<PathBuf>,
+    agent: Option<Endpoint>,
+) -> Vec<Box<dyn Probe>> {
+    let terminal = TerminalProbe {
+        is_tty: io::stdout().is_terminal(),
+        term: std::env::var("TERM").ok(),
+        tmux: std::env::var_os("TMUX").is_some(),
+    };
+    vec![
+        Box::new(ConfigProbe { path: config }),
+        Box::new(ClipboardProbe),
+        #[cfg(feature = "keyring")]
+        Box::new(KeyringProbe),
+        Box::new(AgentProbe { endpoint: agent }),
+        Box::new(MemlockProbe),
+        Box::new(HardeningProbe),
+        Box::new(terminal),
+    ]
+}
+
+/// Whether the configuration loads, and with which warnings
+struct ConfigProbe {
+    path: Option<PathBuf>,
+}
+
+impl Probe for ConfigProbe {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn check(&self) -> Finding {
+        match load_layered(self.path.as_deref()) {
+            Ok(loaded) => match loaded.warnings.first() {
+                None => Finding::pass(Msg::ConfigLoaded.fill(&[&loaded.config.sites.len()])),
+                Some(first) => Finding::warn(
+                    Msg::ConfigWarnings.fill(&[&loaded.warnings.len(), first]),
+                    Msg::ConfigHint.text(),
+                ),
+            },
+            Err(ConfigError::Encrypted { origin }) => Finding::warn(
+                Msg::ConfigEncryptedUnchecked.fill(&[&origin]),
+                Msg::ConfigEncryptedHint.text(),
+            ),
+            Err(error) => Finding::fail(error.to_string(), Msg::ConfigHint.text()),
+        }
+    }
+}
+
+/// Whether `--copy` has a clipboard program to go through
+struct ClipboardProbe;
+
+impl Probe for ClipboardProbe {
+    fn name(&self) -> &'static str {
+        "clipboard"
+    }
+
+    fn check(&self) -> Finding {
+        match CommandClipboard::detect() {
+            Ok(_) => Finding::pass(Msg::ClipboardFound.text()),
+            Err(_) => Finding::warn(Msg::ClipboardMissing.text(), Msg::ClipboardHint.text()),
+        }
+    }
+}
+
+/// Whether the OS keyring of `--use-keyring` can be read
+#[cfg(feature = "keyring")]
+struct KeyringProbe;
+
+#[cfg(feature = "keyring")]
+impl Probe for KeyringProbe {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn check(&self) -> Finding {
+        match crate::keyring::status(&crate::keyring::OsKeyring) {
+            Ok(status) => Finding::pass(status),
+            // Only `--use-keyring` runs need it
+            Err(error) => Finding::warn(error.to_string(), Msg::KeyringHint.text()),
+        }
+    }
+}
+
+/// Whether an agent answers at the endpoint of this user
+struct AgentProbe {
+    endpoint: Option<Endpoint>,
+}
+
+impl Probe for AgentProbe {
+    fn name(&self) -> &'static str {
+        "agent"
+    }
+
+    fn check(&self) -> Finding {
+        let Some(endpoint) = &self.endpoint else {
+            return Finding::warn(Msg::AgentNoEndpoint.text(), Msg::AgentHint.text());
+        };
+        let answered = AgentClient::connect(&mut SystemTransport, endpoint)
+            .and_then(|mut client| client.request(&Request::Status));
+        match answered {
+            Ok(_) => Finding::pass(Msg::AgentReachable.fill(&[&endpoint])),
+            Err(error) => Finding::warn(
+                Msg::AgentUnreachable.fill(&[&endpoint, &error]),
+                Msg::AgentHint.text(),
+            ),
+        }
+    }
+}
+
+/// Whether the pages of a `LockedBuf`, which hold the master password of the
+/// agent, can be locked in RAM
+struct MemlockProbe;
+
+impl MemlockProbe {
+    fn finding(status: LockStatus) -> Finding {
+        match status {
+            LockStatus::Locked => Finding::pass(Msg::MemlockOk.text()),
+            status => Finding::warn(
+                Msg::MemlockRefused.fill(&[&status]),
+                Msg::MemlockHint.text(),
+            ),
+        }
+    }
+}
+
+impl Probe for MemlockProbe {
+    fn name(&self) -> &'static str {
+        "memlock"
+    }
+
+    fn check(&self) -> Finding {
+        MemlockProbe::finding(LockedBuf::new(1).status())
+    }
+}
+
+/// Whether the core dump measures `main` applies with `harden` are in effect,
+/// keeping the master password out of core files
+struct HardeningProbe;
+
+impl HardeningProbe {
+    fn finding(hardening: Hardening) -> Finding {
+        let measures = [hardening.not_dumpable, hardening.no_core_files];
+        let applied = measures.iter().any(|measure| measure.is_applied());
+        let rest = measures
+            .iter()
+            .all(|measure| matches!(measure, Measure::Applied | Measure::Unsupported));
+        match applied && rest {
+            true => Finding::pass(Msg::HardeningOk.fill(&[&hardening])),
+            false => Finding::warn(
+                Msg::HardeningMissing.fill(&[&hardening]),
+                Msg::HardeningHint.text(),
+            ),
+        }
+    }
+}
+
+impl Probe for HardeningProbe {
+    fn name(&self) -> &'static str {
+        "hardening"
+    }
+
+    fn check(&self) -> Finding {
+        HardeningProbe::finding(Hardening::current())
+    }
+}
+
+/// Whether the terminal takes OSC 52, the escape sequence that sets the
+/// clipboard of the terminal's own machine, as over SSH
+struct TerminalProbe {
+    is_tty: bool,
+    term: Option<String>,
+    tmux: bool,
+}
+
+impl Probe for TerminalProbe {
+    fn name(&self) -> &'static str {
+        "terminal"
+    }
+
+    fn check(&self) -> Finding {
+        if !self.is_tty {
+            return Finding::warn(Msg::Osc52NoTerminal.text(), Msg::Osc52Hint.text());
+        }
+        match self.term.as_deref() {
+            None | Some("" | "dumb" | "linux") => {
+                let term = self.term.as_deref().unwrap_or_default();
+                Finding::warn(Msg::Osc52Dumb.fill(&[&term]), Msg::Osc52Hint.text())
+            }
+            Some(_) if self.tmux => Finding::warn(Msg::Osc52Tmux.text(), Msg::Osc52TmuxHint.text()),
+            Some(term) => Finding::pass(Msg::Osc52Ok.fill(&[&term])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A probe reporting a fixed finding
+    struct Fake(&'static str, Finding);
+
+    impl Probe for Fake {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn check(&self) -> Finding {
+            self.1.clone()
+        }
+    }
+
+    fn fake(name: &'static str, finding: Finding) -> Box<dyn Probe> {
+        Box::new(Fake(name, finding))
+    }
+
+    #[test]
+    fn test_only_failures_fail_the_run() {
+        let passing = Report::run(&[
+            fake("a", Finding::pass("fine")),
+            fake("b", Finding::warn("not quite", "look")),
+        ]);
+        assert!(passing.result().is_ok());
+        assert!(Report::run(&[]).result().is_ok());
+
+        let failing = Report::run(&[
+            fake("a", Finding::fail("broken", "fix it")),
+            fake("b", Finding::pass("fine")),
+            fake("c", Finding::fail("broken", "fix it")),
+        ]);
+        let error = failing.result().unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::Doctor {
+                failed: 2,
+                total: 3
+            }
+        ));
+        assert_eq!(error.exit_code(), crate::error::EXIT_BACKEND);
+        assert_eq!(error.to_string(), "2 of 3 checks failed");
+    }
+
+    #[test]
+    fn test_table() {
+        let report = Report::run(&[
+            fake("config", Finding::pass("2 sites, no problems")),
+            fake("clipboard", Finding::warn("none", "install one")),
+            fake("x", Finding::fail("broken", "fix it")),
+        ]);
+        let mut out = Vec::new();
+        report.write(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "STATUS  CHECK      DETAIL\n\
+             PASS    config     2 sites, no problems\n\
+             WARN    clipboard  none\n\
+             \x20                  hint: install one\n\
+             FAIL    x          broken\n\
+             \x20                  hint: fix it\n"
+        );
+    }
+
+    fn terminal(is_tty: bool, term: Option<&str>, tmux: bool) -> Level {
+        let probe = TerminalProbe {
+            is_tty,
+            term: term.map(String::from),
+            tmux,
+        };
+        probe.check().level
+    }
+
+    #[test]
+    fn test_terminal() {
+        assert_eq!(terminal(true, Some("xterm-256color"), false), Level::Pass);
+        assert_eq!(terminal(true, Some("tmux-256color"), true), Level::Warn);
+        assert_eq!(terminal(false, Some("xterm-256color"), false), Level::Warn);
+        for term in [None, Some(""), Some("dumb"), Some("linux")] {
+            assert_eq!(terminal(true, term, false), Level::Warn, "{:?}", term);
+        }
+    }
+
+    #[test]
+    fn test_agent_hint_names_the_command() {
+        let finding = AgentProbe { endpoint: None }.check();
+        assert_eq!(finding.level, Level::Warn);
+        let hint = finding.hint.unwrap();
+        assert!(hint.contains("fp agent start"), "{}", hint);
+    }
+
+    #[test]
+    fn test_memlock() {
+        assert_eq!(MemlockProbe::finding(LockStatus::Locked).level, Level::Pass);
+        let refused = MemlockProbe::finding(LockStatus::Refused(12));
+        assert_eq!(refused.level, Level::Warn);
+        assert!(refused
+            .detail
+            .starts_with("Master passwords may be swapped to disk, not locked: "));
+        assert!(refused.hint.unwrap().contains("ulimit -l"));
+        let unsupported = MemlockProbe::finding(LockStatus::Unsupported);
+        assert_eq!(unsupported.level, Level::Warn);
+        // Whether this system locks or not, the probe reports it
+        let level = MemlockProbe.check().level;
+        assert!(matches!(level, Level::Pass | Level::Warn));
+    }
+
+    #[test]
+    fn test_hardening() {
+        let hardening = Hardening {
+            not_dumpable: Measure::Applied,
+            no_core_files: Measure::Applied,
+        };
+        let finding = HardeningProbe::finding(hardening);
+        assert_eq!(finding.level, Level::Pass);
+        assert_eq!(
+            finding.detail,
+            "Core dumps of fp are prevented (not dumpable: applied, core file limit 0: applied)"
+        );
+        let other_unix = Hardening {
+            not_dumpable: Measure::Unsupported,
+            no_core_files: Measure::Applied,
+        };
+        assert_eq!(HardeningProbe::finding(other_unix).level, Level::Pass);
+
+        let refused = Hardening {
+            not_dumpable: Measure::Failed(1),
+            no_core_files: Measure::Applied,
+        };
+        let finding = HardeningProbe::finding(refused);
+        assert_eq!(finding.level, Level::Warn);
+        assert!(finding.detail.starts_with("Core dumps of fp may hold"));
+        assert!(finding.hint.unwrap().contains("ulimit -c 0"));
+        let unsupported = Hardening {
+            not_dumpable: Measure::Unsupported,
+            no_core_files: Measure::Unsupported,
+        };
+        assert_eq!(HardeningProbe::finding(unsupported).level, Level::Warn);
+
+        // main hardens before running the doctor; here the test harness did not
+        flowerpassword::harden::harden();
+        if cfg!(unix) {
+            assert!(Hardening::current().no_core_files.is_applied());
+        }
+        let level = HardeningProbe.check().level;
+        assert!(matches!(level, Level::Pass | Level::Warn));
+    }
+
+    #[test]
+    fn test_encrypted_config_is_a_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut data = flowerpassword::config::ENCRYPTED_MAGIC.to_vec();
+        data.extend_from_slice(&[0; 64]);
+        std::fs::write(&path, data).unwrap();
+        let finding = ConfigProbe { path: Some(path) }.check();
+        assert_eq!(finding.level, Level::Warn);
+        assert!(finding
+            .detail
+            .ends_with("config.toml is encrypted, so it was not checked"));
+    }
+}
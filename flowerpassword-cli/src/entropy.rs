@@ -0,0 +1,98 @@
+//! `--entropy`, an estimate of the strength of each derived password
+//!
+//! The bits come from `flowerpassword::entropy::estimate_entropy`, so `fp` gives
+//! the same numbers as the library and its bindings, the leading letter included.
+//! There is one scheme and no passphrase mode, so there are no per-word figures.
+//! The estimate goes to standard error, leaving standard output to passwords.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::entropy::{estimate_entropy, FpOptions};
+use std::fmt;
+
+/// A coarse rating of a number of bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Rating {
+    /// Under 48 bits
+    Weak,
+    /// 48 to 64 bits
+    Fair,
+    /// 64 to 80 bits
+    Good,
+    /// 80 bits and more
+    Strong,
+}
+
+impl Rating {
+    /// Rates `bits` of entropy
+    pub(crate) fn from_bits(bits: f64) -> Self {
+        match bits {
+            bits if bits < 48.0 => Rating::Weak,
+            bits if bits < 64.0 => Rating::Fair,
+            bits if bits < 80.0 => Rating::Good,
+            _ => Rating::Strong,
+        }
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Rating::Weak => Msg::Weak,
+            Rating::Fair => Msg::Fair,
+            Rating::Good => Msg::Good,
+            Rating::Strong => Msg::Strong,
+        };
+        f.write_str(msg.text())
+    }
+}
+
+/// Returns the line `--entropy` prints for a password of `length` derived for `key`
+pub(crate) fn describe(key: &str, length: usize) -> Result<String, CliError> {
+    let estimate = estimate_entropy(&FpOptions::classic(length)?);
+    Ok(Msg::Entropy.fill(&[
+        &key,
+        &format_args!("{:.1}", estimate.bits),
+        &Rating::from_bits(estimate.bits),
+        &length,
+        &estimate.alphabet_size,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(length: usize) -> f64 {
+        estimate_entropy(&FpOptions::classic(length).unwrap()).bits
+    }
+
+    #[test]
+    fn test_known_lengths() {
+        // 2.3 bits for the leading letter, 4.375 for every other character
+        assert!((bits(16) - 67.92).abs() < 0.01);
+        assert!((bits(8) - 32.92).abs() < 0.01);
+        assert_eq!(bits(32), 128.0);
+
+        assert_eq!(
+            describe("github.com", 16).unwrap(),
+            "github.com: about 67.9 bits, good (16 characters from 23 possible)"
+        );
+        assert_eq!(
+            describe("key", 32).unwrap(),
+            "key: about 128.0 bits, strong (32 characters from 23 possible)"
+        );
+        assert!(describe("key", 40).is_err());
+    }
+
+    #[test]
+    fn test_ratings() {
+        assert_eq!(Rating::from_bits(bits(8)), Rating::Weak);
+        assert_eq!(Rating::from_bits(47.9), Rating::Weak);
+        assert_eq!(Rating::from_bits(48.0), Rating::Fair);
+        assert_eq!(Rating::from_bits(bits(12)), Rating::Fair);
+        assert_eq!(Rating::from_bits(bits(16)), Rating::Good);
+        assert_eq!(Rating::from_bits(80.0), Rating::Strong);
+        assert_eq!(Rating::from_bits(bits(20)), Rating::Strong);
+    }
+}
@@ -0,0 +1,168 @@
+//! `--show-timeout`, passwords erased from the terminal after a while
+//!
+//! Once the passwords are printed, `fp` waits and then moves the cursor back up
+//! over their lines, clearing each one with ANSI sequences. A terminal that
+//! `$TERM` says is dumb, or that has no `$TERM`, gets a screenful of newlines
+//! instead, scrolling the passwords out of sight.
+//!
+//! On Unix, Ctrl-C during the wait ends it early: the passwords are still
+//! erased, and `fp` exits with status 130. Elsewhere Ctrl-C ends `fp` at once.
+
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Moves the cursor to the start of the line above
+const CURSOR_UP: &str = "\x1b[1A\r";
+
+/// Clears the whole line the cursor is on
+const CLEAR_LINE: &str = "\x1b[2K";
+
+/// Newlines printed on a dumb terminal, more than any screen is high
+pub(crate) const DUMB_LINES: usize = 100;
+
+/// How often the wait checks for Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How the terminal can be cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Display {
+    /// The terminal understands ANSI escape sequences
+    Ansi,
+    /// The terminal only prints text
+    Dumb,
+}
+
+impl Display {
+    /// Returns how a terminal with `$TERM` set to `term` can be cleared
+    pub(crate) fn from_term(term: Option<&str>) -> Self {
+        match term {
+            None | Some("") | Some("dumb") => Display::Dumb,
+            Some(_) => Display::Ansi,
+        }
+    }
+}
+
+/// Returns what erases the last `lines` lines printed on `display`
+pub(crate) fn erase_sequence(display: Display, lines: usize) -> String {
+    match display {
+        Display::Ansi => format!("{}{}", CURSOR_UP, CLEAR_LINE).repeat(lines),
+        Display::Dumb => "\n".repeat(DUMB_LINES),
+    }
+}
+
+/// Waits for `timeout` or until `interrupted` returns `true`, then erases the
+/// last `lines` lines written to `out`
+///
+/// Returns whether the wait was interrupted.
+pub(crate) fn erase_after(
+    out: &mut impl Write,
+    display: Display,
+    lines: usize,
+    timeout: Duration,
+    interrupted: impl Fn() -> bool,
+) -> io::Result<bool> {
+    let deadline = Instant::now() + timeout;
+    let interrupted = loop {
+        if interrupted() {
+            break true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break false;
+        }
+        thread::sleep((deadline - now).min(POLL_INTERVAL));
+    };
+    out.write_all(erase_sequence(display, lines).as_bytes())?;
+    out.flush()?;
+    Ok(interrupted)
+}
+
+/// Catches Ctrl-C for the rest of the run, so the wait can end and still erase
+#[cfg(unix)]
+pub(crate) fn catch_interrupt() {
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Does nothing: only Unix can catch Ctrl-C without a console handler
+#[cfg(not(unix))]
+pub(crate) fn catch_interrupt() {}
+
+/// Returns whether Ctrl-C was pressed since [`catch_interrupt`]
+pub(crate) fn was_interrupted() -> bool {
+    #[cfg(unix)]
+    return INTERRUPTED.load(std::sync::atomic::Ordering::SeqCst);
+    #[cfg(not(unix))]
+    return false;
+}
+
+#[cfg(unix)]
+static INTERRUPTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_display_from_term() {
+        assert_eq!(Display::from_term(Some("xterm-256color")), Display::Ansi);
+        assert_eq!(Display::from_term(Some("dumb")), Display::Dumb);
+        assert_eq!(Display::from_term(Some("")), Display::Dumb);
+        assert_eq!(Display::from_term(None), Display::Dumb);
+    }
+
+    #[test]
+    fn test_erase_sequences() {
+        assert_eq!(erase_sequence(Display::Ansi, 1), "\x1b[1A\r\x1b[2K");
+        assert_eq!(
+            erase_sequence(Display::Ansi, 2),
+            "\x1b[1A\r\x1b[2K\x1b[1A\r\x1b[2K"
+        );
+        assert_eq!(erase_sequence(Display::Ansi, 0), "");
+        assert_eq!(erase_sequence(Display::Dumb, 1), "\n".repeat(DUMB_LINES));
+    }
+
+    #[test]
+    fn test_erase_after_the_timeout() {
+        let mut out = Vec::new();
+        let start = Instant::now();
+        let interrupted = erase_after(
+            &mut out,
+            Display::Ansi,
+            1,
+            Duration::from_millis(120),
+            || false,
+        )
+        .unwrap();
+        assert!(!interrupted);
+        assert!(start.elapsed() >= Duration::from_millis(120));
+        assert_eq!(out, b"\x1b[1A\r\x1b[2K");
+    }
+
+    #[test]
+    fn test_interrupt_still_erases() {
+        let mut out = Vec::new();
+        let polls = Cell::new(0);
+        let start = Instant::now();
+        let interrupted = erase_after(&mut out, Display::Dumb, 3, Duration::from_secs(60), || {
+            polls.set(polls.get() + 1);
+            polls.get() == 3
+        })
+        .unwrap();
+        assert!(interrupted);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(out, "\n".repeat(DUMB_LINES).into_bytes());
+    }
+}
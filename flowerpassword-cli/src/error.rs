@@ -0,0 +1,648 @@
+//! Errors reported by `fp`
+
+#[cfg(feature = "autotype")]
+use crate::autotype::AutotypeError;
+use crate::gpg::GpgError;
+use crate::i18n::{self, Msg};
+#[cfg(feature = "keyring")]
+use crate::keyring::KeyringError;
+use crate::menu::MenuError;
+use crate::pass::PassError;
+use flowerpassword::agent::AgentError;
+use flowerpassword::binary::BinaryError;
+use flowerpassword::clipboard::ClipboardError;
+use flowerpassword::config::ConfigError;
+use flowerpassword::fido2::Fido2Error;
+use flowerpassword::native_host::NativeHostError;
+use flowerpassword::pinentry::PinentryError;
+use flowerpassword::policy::{PolicyError, Requirement};
+use flowerpassword::rpc::RpcError;
+use flowerpassword::ssh_agent::SshAgentError;
+use flowerpassword::uri::FpUriError;
+use flowerpassword::yubikey::YubiKeyError;
+use flowerpassword::FlowerPasswordError;
+use std::fmt;
+use std::io;
+#[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Exit status for invalid arguments, configuration or input, as clap uses
+pub(crate) const EXIT_USAGE: u8 = 2;
+
+/// Exit status for failed reads and writes
+pub(crate) const EXIT_IO: u8 = 3;
+
+/// Exit status for failures of gpg, the clipboard and other external backends
+pub(crate) const EXIT_BACKEND: u8 = 4;
+
+/// Exit status for a master password that does not match the saved fingerprint
+pub(crate) const EXIT_FINGERPRINT: u8 = 5;
+
+/// Exit status after Ctrl-C, as shells report SIGINT
+pub(crate) const EXIT_INTERRUPTED: u8 = 130;
+
+/// Every exit status with its meaning, for the man pages
+pub(crate) const EXIT_STATUSES: [(u8, &str); 6] = [
+    (0, "Success"),
+    (
+        EXIT_USAGE,
+        "Usage or validation error: arguments, configuration, input",
+    ),
+    (
+        EXIT_IO,
+        "Reading or writing a file, descriptor or stream failed",
+    ),
+    (
+        EXIT_BACKEND,
+        "gpg, the clipboard, the keyring, another backend, a doctor check or the self-test failed",
+    ),
+    (
+        EXIT_FINGERPRINT,
+        "The master password does not match the saved fingerprint",
+    ),
+    (
+        EXIT_INTERRUPTED,
+        "Ctrl-C while --show-timeout waited or the terminal was prompting",
+    ),
+];
+
+/// Error type for everything that stops a run
+#[derive(Debug)]
+pub(crate) enum CliError {
+    /// The configuration could not be loaded or a name could not be resolved
+    Config(ConfigError),
+    /// A length given on the command line or in the configuration is invalid
+    Derive(FlowerPasswordError),
+    /// No length meets the `--policy`, or `--min` is out of range
+    Policy(PolicyError),
+    /// The password of a site does not meet the `--policy`, with the
+    /// requirements it misses
+    PolicyUnmet {
+        name: String,
+        unmet: Vec<Requirement>,
+    },
+    /// Reading the master password or keys, or writing passwords, failed
+    Io(io::Error),
+    /// Standard input ended before a master password was read
+    NoMaster,
+    /// The master password was typed differently the second time
+    MasterMismatch,
+    /// An encrypted configuration file, and no terminal to ask its passphrase on
+    PassphraseNeedsTerminal,
+    /// The passphrase of `fp config encrypt` was typed differently the second time
+    PassphraseMismatch,
+    /// A `--password-file` or `--password-fd` ended before a master password
+    EmptyMaster(String),
+    /// A `--password-file` that other users may read, with its mode bits
+    #[cfg(unix)]
+    InsecurePermissions { path: PathBuf, mode: u32 },
+    /// A `--password-fd` that is not open for reading
+    #[cfg(unix)]
+    BadFd(i32),
+    /// A file of the `fp unlock` cache that other users may access, with its
+    /// mode bits
+    #[cfg(unix)]
+    InsecureCache { path: PathBuf, mode: u32 },
+    /// `fp unlock` without `$XDG_RUNTIME_DIR` to keep the cache in
+    #[cfg(unix)]
+    NoRuntimeDir,
+    /// `fp unlock`, `fp lock` or `fp status` without a kernel keyring to keep the
+    /// key of the cache in
+    #[cfg(not(target_os = "linux"))]
+    NoKernelKeyring,
+    /// A `--password-gpg` file could not be decrypted
+    Gpg(GpgError),
+    /// The `--pinentry` program failed or its dialog was cancelled
+    Pinentry(PinentryError),
+    /// No program to run for `--pinentry`, with the value given
+    NoPinentry(String),
+    /// `fp pass` could not run pass, or some entries failed
+    Pass(PassError),
+    /// A password could not be copied to the clipboard
+    Clipboard(ClipboardError),
+    /// `fp serve --stdio` could not read or write a message
+    Rpc(RpcError),
+    /// `fp serve --binary` read a malformed frame, or could not read or write
+    /// one
+    Binary(BinaryError),
+    /// `fp native-host` read a message too large to answer, or could not read
+    /// or write one
+    NativeHost(NativeHostError),
+    /// `fp native-host` found no master password in the agent, `fp unlock`'s
+    /// cache or the keyring
+    NativeHostLocked,
+    /// `fp handle-uri` got a link it cannot read
+    Uri(FpUriError),
+    /// `fp handle-uri` found no master password in the agent, `fp unlock`'s
+    /// cache or the keyring
+    UriHandlerLocked,
+    /// The FIDO2 authenticator of `--master fido2` or `fp fido2 enroll` failed
+    Fido2(Fido2Error),
+    /// `--master fido2` without a credential from `fp fido2 enroll`
+    NoFido2Credential,
+    /// `fp fido2 enroll` with a credential enrolled already, without --force
+    Fido2Enrolled,
+    /// The YubiKey of `--master yubikey` failed
+    YubiKey(YubiKeyError),
+    /// The ssh-agent of `--master ssh-agent` could not be used
+    SshAgent(SshAgentError),
+    /// The rofi of `fp menu --rofi` could not be run
+    Menu(MenuError),
+    /// A `--blocklist` file could not be read
+    Blocklist { path: PathBuf, source: io::Error },
+    /// A `--master` value naming no source; it is not kept, since it may be a
+    /// master password given by mistake
+    UnknownMasterSource,
+    /// The agent could not be listened on or talked to
+    Agent(AgentError),
+    /// The agent answered a request with an error, with its message
+    AgentRefused(String),
+    /// No endpoint for the agent of this user
+    NoAgentEndpoint,
+    /// The OS keyring could not be read or written by `fp keyring`
+    #[cfg(feature = "keyring")]
+    Keyring(KeyringError),
+    /// The password could not be encoded as a QR code
+    #[cfg(feature = "qr")]
+    Qr(qrcode::types::QrError),
+    /// `--autotype` could not type the password
+    #[cfg(feature = "autotype")]
+    Autotype(AutotypeError),
+    /// An option that draws on the terminal ran without one
+    #[cfg(unix)]
+    NoTerminal(&'static str),
+    /// No home directory to keep the `--log` history in
+    NoStateDir,
+    /// No home directory to keep the configuration in, and no `--config`
+    NoConfigDir,
+    /// No home directory to install a native messaging host manifest in
+    NoHomeDir,
+    /// `fp alias rm` of an alias the configuration file does not have
+    UnknownAlias { alias: String, path: PathBuf },
+    /// `fp find` with a query no site matches
+    NoMatch(String),
+    /// `fp find --select` answered with something other than the number of a
+    /// listed site
+    NoPick(String),
+    /// `fp verify-master` without a saved fingerprint
+    NoFingerprint,
+    /// The master password does not match the saved fingerprint
+    FingerprintMismatch,
+    /// `fp serve` on an address other machines can reach
+    #[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+    NotLoopback(SocketAddr),
+    /// Some `--jobs` keys failed, as reported one by one
+    Incomplete { failed: usize, total: usize },
+    /// Some `fp doctor` checks failed, as shown in its table
+    Doctor { failed: usize, total: usize },
+    /// Some `fp self-test` vectors failed, as shown one by one
+    SelfTest { failed: usize, total: usize },
+    /// `fp config lint` found problems, as listed one by one
+    Lint { errors: usize, warnings: usize },
+    /// `fp audit` found groups of sites sharing a password, as listed
+    Reused { groups: usize },
+    /// Ctrl-C ended the run
+    Interrupted,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Config(error) => write!(f, "{}", error),
+            CliError::Derive(error) => write!(f, "{}", error),
+            CliError::Policy(error) => write!(f, "{}", error),
+            CliError::PolicyUnmet { name, unmet } => {
+                let unmet: Vec<&str> = unmet.iter().map(|requirement| requirement.name()).collect();
+                i18n::write(f, Msg::PolicyUnmet, &[name, &unmet.join(", ")])
+            }
+            CliError::Io(error) => write!(f, "{}", error),
+            CliError::NoMaster => f.write_str(Msg::NoMaster.text()),
+            CliError::MasterMismatch => f.write_str(Msg::MasterMismatch.text()),
+            CliError::PassphraseNeedsTerminal => f.write_str(Msg::PassphraseNeedsTerminal.text()),
+            CliError::PassphraseMismatch => f.write_str(Msg::PassphraseMismatch.text()),
+            CliError::EmptyMaster(source) => i18n::write(f, Msg::EmptyMaster, &[source]),
+            #[cfg(unix)]
+            CliError::InsecurePermissions { path, mode } => i18n::write(
+                f,
+                Msg::InsecurePermissions,
+                &[&path.display(), &format_args!("{:03o}", mode)],
+            ),
+            #[cfg(unix)]
+            CliError::InsecureCache { path, mode } => i18n::write(
+                f,
+                Msg::InsecureCache,
+                &[&path.display(), &format_args!("{:03o}", mode)],
+            ),
+            #[cfg(unix)]
+            CliError::NoRuntimeDir => f.write_str(Msg::NoRuntimeDir.text()),
+            #[cfg(not(target_os = "linux"))]
+            CliError::NoKernelKeyring => f.write_str(Msg::NoKernelKeyring.text()),
+            CliError::Gpg(error) => write!(f, "{}", error),
+            CliError::Pinentry(error) => write!(f, "{}", error),
+            CliError::NoPinentry(spec) => i18n::write(f, Msg::NoPinentry, &[spec]),
+            CliError::Pass(error) => write!(f, "{}", error),
+            CliError::Clipboard(error) => write!(f, "{}", error),
+            CliError::Rpc(error) => write!(f, "{}", error),
+            CliError::Binary(error) => write!(f, "{}", error),
+            CliError::NativeHost(error) => write!(f, "{}", error),
+            CliError::Fido2(error) => write!(f, "{}", error),
+            CliError::NoFido2Credential => f.write_str(Msg::NoFido2Credential.text()),
+            CliError::Fido2Enrolled => f.write_str(Msg::Fido2Enrolled.text()),
+            CliError::YubiKey(error) => write!(f, "{}", error),
+            CliError::SshAgent(error) => write!(f, "{}", error),
+            CliError::Menu(error) => write!(f, "{}", error),
+            CliError::UnknownMasterSource => f.write_str(Msg::UnknownMasterSource.text()),
+            CliError::NativeHostLocked => f.write_str(Msg::NativeHostLocked.text()),
+            CliError::Uri(error) => write!(f, "{}", error),
+            CliError::UriHandlerLocked => f.write_str(Msg::UriHandlerLocked.text()),
+            CliError::Agent(error) => write!(f, "{}", error),
+            CliError::AgentRefused(message) => i18n::write(f, Msg::AgentRefused, &[message]),
+            CliError::NoAgentEndpoint => f.write_str(Msg::AgentNoEndpoint.text()),
+            #[cfg(feature = "keyring")]
+            CliError::Keyring(error) => write!(f, "{}", error),
+            #[cfg(unix)]
+            CliError::BadFd(fd) => i18n::write(f, Msg::BadFd, &[fd]),
+            #[cfg(feature = "qr")]
+            CliError::Qr(error) => i18n::write(f, Msg::QrEncode, &[error]),
+            #[cfg(feature = "autotype")]
+            CliError::Autotype(error) => write!(f, "{}", error),
+            #[cfg(unix)]
+            CliError::NoTerminal(option) => i18n::write(f, Msg::NoTerminal, &[option]),
+            CliError::NoStateDir => f.write_str(Msg::NoStateDir.text()),
+            CliError::NoConfigDir => f.write_str(Msg::NoConfigDir.text()),
+            CliError::NoHomeDir => f.write_str(Msg::NoHomeDir.text()),
+            CliError::UnknownAlias { alias, path } => {
+                i18n::write(f, Msg::UnknownAlias, &[alias, &path.display()])
+            }
+            CliError::NoMatch(query) => i18n::write(f, Msg::NoMatch, &[query]),
+            CliError::NoPick(answer) => i18n::write(f, Msg::NoPick, &[answer]),
+            CliError::NoFingerprint => f.write_str(Msg::NoFingerprint.text()),
+            CliError::FingerprintMismatch => f.write_str(Msg::FingerprintMismatch.text()),
+            #[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+            CliError::NotLoopback(addr) => i18n::write(f, Msg::NotLoopback, &[addr]),
+            CliError::Incomplete { failed, total } => {
+                i18n::write(f, Msg::KeysIncomplete, &[failed, total])
+            }
+            CliError::Doctor { failed, total } => {
+                i18n::write(f, Msg::DoctorIncomplete, &[failed, total])
+            }
+            CliError::SelfTest { failed, total } => {
+                i18n::write(f, Msg::SelfTestFailed, &[failed, total])
+            }
+            CliError::Lint { errors, warnings } => {
+                i18n::write(f, Msg::LintFindings, &[errors, warnings])
+            }
+            CliError::Reused { groups } => i18n::write(f, Msg::AuditFailed, &[groups]),
+            CliError::Blocklist { path, source } => {
+                i18n::write(f, Msg::BlocklistUnreadable, &[&path.display(), source])
+            }
+            CliError::Interrupted => f.write_str(Msg::Interrupted.text()),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl CliError {
+    /// Returns the exit status documented for this class of error
+    pub(crate) fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Config(ConfigError::Io { .. }) | CliError::Io(_) => EXIT_IO,
+            CliError::NoStateDir | CliError::NoConfigDir | CliError::NoHomeDir => EXIT_IO,
+            CliError::Gpg(GpgError::Io(_)) => EXIT_IO,
+            CliError::Pinentry(PinentryError::Io(_)) => EXIT_IO,
+            CliError::Agent(AgentError::Io(_)) => EXIT_IO,
+            CliError::Rpc(RpcError::Io(_)) => EXIT_IO,
+            CliError::Binary(BinaryError::Io(_)) => EXIT_IO,
+            CliError::NativeHost(NativeHostError::Io(_)) => EXIT_IO,
+            CliError::Fido2(Fido2Error::InvalidSource | Fido2Error::InvalidCredentialId) => {
+                EXIT_USAGE
+            }
+            CliError::Fido2(_) => EXIT_BACKEND,
+            CliError::YubiKey(
+                YubiKeyError::InvalidSource
+                | YubiKeyError::InvalidSlot(_)
+                | YubiKeyError::LabelTooLong(_),
+            ) => EXIT_USAGE,
+            CliError::YubiKey(_) => EXIT_BACKEND,
+            CliError::SshAgent(SshAgentError::Io(_)) => EXIT_IO,
+            CliError::SshAgent(SshAgentError::InvalidSource) => EXIT_USAGE,
+            CliError::SshAgent(_) => EXIT_BACKEND,
+            CliError::Menu(MenuError::Io(_)) => EXIT_IO,
+            CliError::Menu(_) => EXIT_BACKEND,
+            CliError::Blocklist { .. } => EXIT_IO,
+            CliError::Pass(PassError::Io(_)) => EXIT_IO,
+            CliError::Pass(PassError::OutsidePrefix(_)) => EXIT_USAGE,
+            CliError::Gpg(_) | CliError::Pass(_) | CliError::Clipboard(_) => EXIT_BACKEND,
+            CliError::Pinentry(_) => EXIT_BACKEND,
+            CliError::Agent(AgentError::AlreadyRunning(_)) | CliError::NoAgentEndpoint => EXIT_USAGE,
+            CliError::Agent(_) | CliError::AgentRefused(_) => EXIT_BACKEND,
+            CliError::Doctor { .. } | CliError::SelfTest { .. } => EXIT_BACKEND,
+            CliError::Config(_)
+            | CliError::Derive(_)
+            | CliError::Policy(_)
+            | CliError::Rpc(_)
+            | CliError::Binary(_)
+            | CliError::NativeHost(_)
+            | CliError::NativeHostLocked
+            | CliError::Uri(_)
+            | CliError::UriHandlerLocked
+            | CliError::NoFido2Credential
+            | CliError::Fido2Enrolled
+            | CliError::UnknownMasterSource
+            | CliError::PolicyUnmet { .. }
+            | CliError::NoMaster
+            | CliError::MasterMismatch
+            | CliError::PassphraseNeedsTerminal
+            | CliError::PassphraseMismatch
+            | CliError::EmptyMaster(_)
+            | CliError::NoPinentry(_)
+            | CliError::UnknownAlias { .. }
+            | CliError::NoMatch(_)
+            | CliError::NoPick(_)
+            | CliError::NoFingerprint
+            | CliError::Incomplete { .. }
+            | CliError::Lint { .. }
+            | CliError::Reused { .. } => EXIT_USAGE,
+            #[cfg(unix)]
+            CliError::InsecurePermissions { .. }
+            | CliError::BadFd(_)
+            | CliError::InsecureCache { .. }
+            | CliError::NoRuntimeDir
+            | CliError::NoTerminal(_) => EXIT_USAGE,
+            #[cfg(not(target_os = "linux"))]
+            CliError::NoKernelKeyring => EXIT_BACKEND,
+            #[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+            CliError::NotLoopback(_) => EXIT_USAGE,
+            #[cfg(feature = "keyring")]
+            CliError::Keyring(_) => EXIT_BACKEND,
+            #[cfg(feature = "qr")]
+            CliError::Qr(_) => EXIT_USAGE,
+            #[cfg(all(feature = "autotype", not(any(windows, target_os = "macos"))))]
+            CliError::Autotype(AutotypeError::Io(_)) => EXIT_IO,
+            #[cfg(feature = "autotype")]
+            CliError::Autotype(_) => EXIT_BACKEND,
+            CliError::FingerprintMismatch => EXIT_FINGERPRINT,
+            CliError::Interrupted => EXIT_INTERRUPTED,
+        }
+    }
+}
+
+#[cfg(feature = "autotype")]
+impl From<AutotypeError> for CliError {
+    fn from(error: AutotypeError) -> Self {
+        CliError::Autotype(error)
+    }
+}
+
+impl From<AgentError> for CliError {
+    fn from(error: AgentError) -> Self {
+        CliError::Agent(error)
+    }
+}
+
+impl From<ClipboardError> for CliError {
+    fn from(error: ClipboardError) -> Self {
+        CliError::Clipboard(error)
+    }
+}
+
+impl From<ConfigError> for CliError {
+    fn from(error: ConfigError) -> Self {
+        CliError::Config(error)
+    }
+}
+
+impl From<FlowerPasswordError> for CliError {
+    fn from(error: FlowerPasswordError) -> Self {
+        CliError::Derive(error)
+    }
+}
+
+impl From<GpgError> for CliError {
+    fn from(error: GpgError) -> Self {
+        CliError::Gpg(error)
+    }
+}
+
+impl From<PinentryError> for CliError {
+    fn from(error: PinentryError) -> Self {
+        CliError::Pinentry(error)
+    }
+}
+
+impl From<PassError> for CliError {
+    fn from(error: PassError) -> Self {
+        CliError::Pass(error)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl From<KeyringError> for CliError {
+    fn from(error: KeyringError) -> Self {
+        CliError::Keyring(error)
+    }
+}
+
+impl From<BinaryError> for CliError {
+    fn from(error: BinaryError) -> Self {
+        CliError::Binary(error)
+    }
+}
+
+impl From<Fido2Error> for CliError {
+    fn from(error: Fido2Error) -> Self {
+        CliError::Fido2(error)
+    }
+}
+
+impl From<YubiKeyError> for CliError {
+    fn from(error: YubiKeyError) -> Self {
+        CliError::YubiKey(error)
+    }
+}
+
+impl From<SshAgentError> for CliError {
+    fn from(error: SshAgentError) -> Self {
+        CliError::SshAgent(error)
+    }
+}
+
+impl From<MenuError> for CliError {
+    fn from(error: MenuError) -> Self {
+        CliError::Menu(error)
+    }
+}
+
+impl From<FpUriError> for CliError {
+    fn from(error: FpUriError) -> Self {
+        CliError::Uri(error)
+    }
+}
+
+impl From<NativeHostError> for CliError {
+    fn from(error: NativeHostError) -> Self {
+        CliError::NativeHost(error)
+    }
+}
+
+impl From<RpcError> for CliError {
+    fn from(error: RpcError) -> Self {
+        CliError::Rpc(error)
+    }
+}
+
+impl From<PolicyError> for CliError {
+    fn from(error: PolicyError) -> Self {
+        CliError::Policy(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Io(error)
+    }
+}
+
+#[cfg(feature = "qr")]
+impl From<qrcode::types::QrError> for CliError {
+    fn from(error: qrcode::types::QrError) -> Self {
+        CliError::Qr(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_exit_codes() {
+        let io = || io::Error::other("disk on fire");
+        let config_io = ConfigError::Io {
+            path: PathBuf::from("config.toml"),
+            source: io(),
+        };
+        assert_eq!(CliError::Io(io()).exit_code(), EXIT_IO);
+        assert_eq!(CliError::Config(config_io).exit_code(), EXIT_IO);
+        assert_eq!(CliError::Gpg(GpgError::Io(io())).exit_code(), EXIT_IO);
+
+        assert_eq!(
+            CliError::Gpg(GpgError::NotInstalled).exit_code(),
+            EXIT_BACKEND
+        );
+        let failed = GpgError::Failed("No secret key".to_string());
+        assert_eq!(CliError::Gpg(failed).exit_code(), EXIT_BACKEND);
+        assert_eq!(CliError::Pass(PassError::Io(io())).exit_code(), EXIT_IO);
+        let pinentry = CliError::Pinentry(PinentryError::Io(io()));
+        assert_eq!(pinentry.exit_code(), EXIT_IO);
+        let cancelled = CliError::Pinentry(PinentryError::Cancelled);
+        assert_eq!(cancelled.exit_code(), EXIT_BACKEND);
+        let incomplete = PassError::Incomplete {
+            failed: 1,
+            total: 2,
+        };
+        assert_eq!(CliError::Pass(incomplete).exit_code(), EXIT_BACKEND);
+        let clipboard = CliError::Clipboard(ClipboardError::Unavailable);
+        assert_eq!(clipboard.exit_code(), EXIT_BACKEND);
+        let self_test = CliError::SelfTest {
+            failed: 1,
+            total: 44,
+        };
+        assert_eq!(self_test.exit_code(), EXIT_BACKEND);
+        assert_eq!(CliError::Agent(AgentError::Io(io())).exit_code(), EXIT_IO);
+        assert_eq!(CliError::Rpc(RpcError::Io(io())).exit_code(), EXIT_IO);
+        let binary = CliError::Binary(BinaryError::Io(io()));
+        assert_eq!(binary.exit_code(), EXIT_IO);
+        let native_host = CliError::NativeHost(NativeHostError::Io(io()));
+        assert_eq!(native_host.exit_code(), EXIT_IO);
+        let timeout = Fido2Error::UserPresenceTimeout(std::time::Duration::from_secs(30));
+        assert_eq!(CliError::Fido2(timeout).exit_code(), EXIT_BACKEND);
+        let timeout = YubiKeyError::TouchTimeout(std::time::Duration::from_secs(15));
+        assert_eq!(CliError::YubiKey(timeout).exit_code(), EXIT_BACKEND);
+        let ssh_agent = CliError::SshAgent(SshAgentError::Io(io()));
+        assert_eq!(ssh_agent.exit_code(), EXIT_IO);
+        let ambiguous = CliError::SshAgent(SshAgentError::AmbiguousKey(2));
+        assert_eq!(ambiguous.exit_code(), EXIT_BACKEND);
+        assert_eq!(CliError::Menu(MenuError::Io(io())).exit_code(), EXIT_IO);
+        let blocklist = CliError::Blocklist {
+            path: PathBuf::from("words.txt"),
+            source: io(),
+        };
+        assert_eq!(blocklist.exit_code(), EXIT_IO);
+        let rofi = CliError::Menu(MenuError::NotInstalled("rofi"));
+        assert_eq!(rofi.exit_code(), EXIT_BACKEND);
+        let locked = CliError::AgentRefused("Agent is locked".to_string());
+        assert_eq!(locked.exit_code(), EXIT_BACKEND);
+        #[cfg(feature = "keyring")]
+        {
+            let keyring = KeyringError("locked".to_string());
+            assert_eq!(CliError::Keyring(keyring).exit_code(), EXIT_BACKEND);
+        }
+
+        #[cfg(all(feature = "autotype", not(any(windows, target_os = "macos"))))]
+        {
+            use crate::autotype::AutotypeError;
+            let autotype = CliError::Autotype(AutotypeError::NotInstalled("xdotool"));
+            assert_eq!(autotype.exit_code(), EXIT_BACKEND);
+            assert_eq!(
+                CliError::Autotype(AutotypeError::Io(io())).exit_code(),
+                EXIT_IO
+            );
+        }
+
+        assert_eq!(CliError::Interrupted.exit_code(), EXIT_INTERRUPTED);
+
+        let usage = [
+            CliError::from(FlowerPasswordError::InvalidLength(40)),
+            CliError::from(PolicyError::Unsatisfiable(vec![Requirement::Digit])),
+            CliError::PolicyUnmet {
+                name: "github".to_string(),
+                unmet: vec![Requirement::Upper],
+            },
+            CliError::NoMaster,
+            CliError::MasterMismatch,
+            CliError::PassphraseNeedsTerminal,
+            CliError::PassphraseMismatch,
+            CliError::EmptyMaster("master".to_string()),
+            CliError::NoPinentry("auto".to_string()),
+            CliError::Pass(PassError::OutsidePrefix("../x".to_string())),
+            CliError::Agent(AgentError::AlreadyRunning("agent.sock".to_string())),
+            CliError::NoAgentEndpoint,
+            CliError::Rpc(RpcError::MessageTooLarge(1 << 20)),
+            CliError::Binary(BinaryError::UnsupportedVersion(2)),
+            CliError::NativeHost(NativeHostError::MessageTooLarge(1 << 21)),
+            CliError::NativeHostLocked,
+            CliError::Uri(FpUriError::InvalidScheme),
+            CliError::UriHandlerLocked,
+            CliError::Fido2(Fido2Error::InvalidSource),
+            CliError::NoFido2Credential,
+            CliError::Fido2Enrolled,
+            CliError::YubiKey(YubiKeyError::InvalidSlot("3".to_string())),
+            CliError::YubiKey(YubiKeyError::LabelTooLong(64)),
+            CliError::SshAgent(SshAgentError::InvalidSource),
+            CliError::UnknownMasterSource,
+            CliError::NoMatch("zzz".to_string()),
+            CliError::NoPick("3".to_string()),
+            CliError::Lint {
+                errors: 0,
+                warnings: 1,
+            },
+            CliError::Reused { groups: 1 },
+        ];
+        #[cfg(unix)]
+        let usage = usage.into_iter().chain([
+            CliError::BadFd(1),
+            CliError::NoTerminal("--masked"),
+            CliError::NoRuntimeDir,
+            CliError::InsecurePermissions {
+                path: PathBuf::from("master"),
+                mode: 0o644,
+            },
+        ]);
+        for error in usage {
+            assert_eq!(error.exit_code(), EXIT_USAGE, "{}", error);
+        }
+    }
+}
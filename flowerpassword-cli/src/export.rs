@@ -0,0 +1,110 @@
+//! `fp export`, every configured site with its password, for another manager
+//!
+//! `fp export --format browser-csv` derives the password of every configured
+//! site after one master password prompt and writes them in the
+//! `name,url,username,password` CSV the Chrome and Firefox importers read.
+//! `keepass-csv` writes the `Group,Title,Username,Password,URL,Notes` columns
+//! KeePass maps on import and `keepass-xml` a KeePass 2.x XML file, both with
+//! every entry in the `--group`.
+//!
+//! It is the one command that writes passwords to a file, so `--out PATH` warns
+//! and asks before deriving, unless `--yes`, and creates the file for its owner
+//! only. Standard output takes `--stdout`, even when it is a terminal, so that
+//! a forgotten redirection does not print every password.
+
+use clap::ValueEnum;
+use flowerpassword::config::{
+    write_browser_csv, write_keepass_csv, write_keepass_xml, ExportEntry,
+};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// What `fp export` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ExportFormat {
+    /// `name,url,username,password`, for the Chrome and Firefox importers
+    BrowserCsv,
+    /// `Group,Title,Username,Password,URL,Notes`, for KeePass
+    KeepassCsv,
+    /// The KeePass 2.x XML format
+    KeepassXml,
+}
+
+/// Group of the KeePass formats unless `--group`
+pub(crate) const DEFAULT_GROUP: &str = "Flower Password";
+
+/// Writes `entries` to `out` in `format`, in `group` for the KeePass formats
+pub(crate) fn write(
+    out: &mut impl Write,
+    format: ExportFormat,
+    entries: &[ExportEntry],
+    group: &str,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::BrowserCsv => write_browser_csv(out, entries),
+        ExportFormat::KeepassCsv => write_keepass_csv(out, entries, group),
+        ExportFormat::KeepassXml => write_keepass_xml(out, entries, group),
+    }
+}
+
+/// Creates or truncates the `--out` file, readable by its owner only when
+/// created
+pub(crate) fn create(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+
+    const SITES: &str = include_str!("../tests/fixtures/launcher.toml");
+
+    #[test]
+    fn test_write_browser_csv() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let entries = config.export_entries("test").unwrap();
+        let mut out = Vec::new();
+        write(&mut out, ExportFormat::BrowserCsv, &entries, DEFAULT_GROUP).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = text.split_terminator("\r\n").collect();
+        assert_eq!(rows[0], "name,url,username,password");
+        assert_eq!(rows[2], "github,https://github.com,,D04175F7A9c7Ab4a");
+        assert_eq!(rows.len(), 4);
+    }
+
+    #[test]
+    fn test_write_keepass_csv() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let entries = config.export_entries("test").unwrap();
+        let mut out = Vec::new();
+        write(&mut out, ExportFormat::KeepassCsv, &entries, "Work").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = text.split_terminator("\r\n").collect();
+        assert_eq!(rows[0], "Group,Title,Username,Password,URL,Notes");
+        assert_eq!(rows[2], "Work,github,,D04175F7A9c7Ab4a,https://github.com,");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        create(&path).unwrap().write_all(b"secret").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Exporting again replaces the file
+        create(&path).unwrap().write_all(b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+    }
+}
@@ -0,0 +1,585 @@
+//! `--master fido2` and `fp fido2 enroll`, a master password that takes a
+//! security key to derive
+//!
+//! The authenticator is driven with the command-line tools of libfido2:
+//! `fido2-token` finds it and tells whether it has a PIN, `fido2-cred` creates
+//! the credential with the hmac-secret extension, and `fido2-assert` asserts it
+//! with the salt of [`flowerpassword::fido2`], whose output is the master
+//! password. `fp fido2 enroll` keeps the ID of the credential as
+//! `fido2_credential` in the configuration; it is not secret.
+//!
+//! The PIN of an authenticator that has one is asked for on the terminal and
+//! handed to the tools on their standard input, in a session of their own so
+//! that they cannot ask on the terminal a second time. A touch not given
+//! within [`DEFAULT_TIMEOUT`] stops the tool.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::master::Prompter;
+use crate::sites;
+use crate::tool;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flowerpassword::config::{edit_file, set_fido2_credential, Config};
+use flowerpassword::fido2::{
+    self, master_from_fido2, CredentialId, Fido2Error, HmacSecret, DEFAULT_TIMEOUT, OUTPUT_LEN,
+};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Label of `--master fido2` without one
+pub(crate) const DEFAULT_LABEL: &str = "default";
+
+/// Client data hash of every request: nothing verifies the attestation or the
+/// signature, so it need not be fresh
+const CLIENT_DATA_HASH: [u8; 32] = [0; 32];
+
+/// An [`HmacSecret`] running the libfido2 tools, on the first authenticator
+/// `fido2-token -L` lists
+#[derive(Debug, Default)]
+pub(crate) struct Fido2Tools {
+    /// Directory of the tools, instead of `PATH`
+    dir: Option<PathBuf>,
+    device: Option<String>,
+}
+
+impl Fido2Tools {
+    #[cfg(test)]
+    fn in_dir(dir: &Path) -> Self {
+        Fido2Tools {
+            dir: Some(dir.to_path_buf()),
+            device: None,
+        }
+    }
+
+    fn program(&self, name: &str) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+
+    /// Returns the path of the authenticator, listing them the first time
+    fn device(&mut self) -> Result<String, Fido2Error> {
+        if let Some(device) = &self.device {
+            return Ok(device.clone());
+        }
+        let listing = self.run("fido2-token", &["-L"], None, DEFAULT_TIMEOUT)?;
+        let listing = String::from_utf8_lossy(&listing);
+        let device = listing
+            .lines()
+            .find_map(|line| line.split_once(": ").map(|(device, _)| device.to_string()))
+            .ok_or(Fido2Error::NotFound)?;
+        self.device = Some(device.clone());
+        Ok(device)
+    }
+
+    /// Runs the tool `name` with `args`, giving it `pin` on standard input, and
+    /// returns its standard output, wiped on drop since it may hold the secret
+    fn run(
+        &self,
+        name: &str,
+        args: &[&str],
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Zeroizing<Vec<u8>>, Fido2Error> {
+        let mut command = Command::new(self.program(name));
+        command
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: setsid is async-signal-safe; without a controlling
+            // terminal the tools read the PIN from standard input
+            unsafe {
+                command.pre_exec(|| match libc::setsid() {
+                    -1 => Err(io::Error::last_os_error()),
+                    _ => Ok(()),
+                });
+            }
+        }
+        let mut child = command.spawn().map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => Fido2Error::Device(Msg::NotInstalled.fill(&[&name])),
+            _ => Fido2Error::Device(error.to_string()),
+        })?;
+        let mut stdin = child.stdin.take().expect("standard input is piped");
+        if let Some(pin) = pin {
+            let line = Zeroizing::new(format!("{}\n", pin));
+            match stdin.write_all(line.as_bytes()) {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+                    return Err(Fido2Error::Device(error.to_string()));
+                }
+                _ => {}
+            }
+        }
+        drop(stdin);
+
+        let status = match tool::wait_timeout(&mut child, timeout) {
+            Ok(Some(status)) => status,
+            Ok(None) => return Err(Fido2Error::UserPresenceTimeout(timeout)),
+            Err(error) => return Err(Fido2Error::Device(error.to_string())),
+        };
+        let mut stdout = Zeroizing::new(Vec::new());
+        let mut stderr = String::new();
+        let read = child
+            .stdout
+            .take()
+            .map_or(Ok(0), |mut out| out.read_to_end(&mut stdout))
+            .and_then(|_| {
+                child
+                    .stderr
+                    .take()
+                    .map_or(Ok(0), |mut err| err.read_to_string(&mut stderr))
+            });
+        read.map_err(|error| Fido2Error::Device(error.to_string()))?;
+        if !status.success() {
+            return Err(tool_error(&stderr, status.to_string(), timeout));
+        }
+        Ok(stdout)
+    }
+
+    /// Runs the tool `name` with `input` in a file of its own for `-i`, since
+    /// standard input is the PIN's
+    fn run_with_input(
+        &self,
+        name: &str,
+        args: &[&str],
+        input: &[String],
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Zeroizing<Vec<u8>>, Fido2Error> {
+        let file =
+            InputFile::create(input).map_err(|error| Fido2Error::Device(error.to_string()))?;
+        let path = file.0.to_string_lossy();
+        let mut all = vec!["-i", &path];
+        all.extend_from_slice(args);
+        self.run(name, &all, pin, timeout)
+    }
+}
+
+impl HmacSecret for Fido2Tools {
+    /// Also fails with `Fido2Error::ExtensionUnsupported` for an authenticator
+    /// without hmac-secret, before anything is asked of it
+    fn requires_pin(&mut self) -> Result<bool, Fido2Error> {
+        let device = self.device()?;
+        let info = self.run("fido2-token", &["-I", &device], None, DEFAULT_TIMEOUT)?;
+        let info = String::from_utf8_lossy(&info);
+        let listed = |prefix: &str, name: &str| {
+            info.lines()
+                .filter_map(|line| line.strip_prefix(prefix))
+                .any(|values| values.split(',').any(|value| value.trim() == name))
+        };
+        if !listed("extension strings:", "hmac-secret") {
+            return Err(Fido2Error::ExtensionUnsupported);
+        }
+        Ok(listed("options:", "clientPin"))
+    }
+
+    fn make_credential(
+        &mut self,
+        rp_id: &str,
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Fido2Error> {
+        let device = self.device()?;
+        let input = [
+            STANDARD.encode(CLIENT_DATA_HASH),
+            rp_id.to_string(),
+            fido2::RELYING_PARTY.to_string(),
+            STANDARD.encode(fido2::RELYING_PARTY),
+        ];
+        let mut args = vec!["-M", "-h"];
+        if pin.is_some() {
+            args.push("-v");
+        }
+        args.extend_from_slice(&[&device, "es256"]);
+        let output = self.run_with_input("fido2-cred", &args, &input, pin, timeout)?;
+        // The credential ID is the fifth line
+        String::from_utf8_lossy(&output)
+            .lines()
+            .nth(4)
+            .and_then(|id| STANDARD.decode(id).ok())
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Fido2Error::Device(Msg::Fido2Output.fill(&[&"fido2-cred"])))
+    }
+
+    fn hmac_secret(
+        &mut self,
+        rp_id: &str,
+        credential: &[u8],
+        salt: &[u8; 32],
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<[u8; OUTPUT_LEN], Fido2Error> {
+        let device = self.device()?;
+        let input = [
+            STANDARD.encode(CLIENT_DATA_HASH),
+            rp_id.to_string(),
+            STANDARD.encode(credential),
+            STANDARD.encode(salt),
+        ];
+        let mut args = vec!["-G", "-h"];
+        if pin.is_some() {
+            args.push("-v");
+        }
+        args.push(&device);
+        let output = self.run_with_input("fido2-assert", &args, &input, pin, timeout)?;
+        // The hmac-secret output is the last line
+        let text = std::str::from_utf8(&output).unwrap_or_default();
+        let mut secret = Zeroizing::new([0; OUTPUT_LEN]);
+        match text
+            .lines()
+            .last()
+            .map(|line| STANDARD.decode_slice(line, &mut *secret))
+        {
+            Some(Ok(OUTPUT_LEN)) => Ok(*secret),
+            _ => Err(Fido2Error::Device(
+                Msg::Fido2Output.fill(&[&"fido2-assert"]),
+            )),
+        }
+    }
+}
+
+/// Maps what a tool wrote to standard error to an error, or to `status` if it
+/// wrote nothing
+fn tool_error(stderr: &str, status: String, timeout: Duration) -> Fido2Error {
+    let has = |code: &str| stderr.contains(code);
+    if has("FIDO_ERR_PIN_INVALID") {
+        Fido2Error::PinInvalid(None)
+    } else if has("FIDO_ERR_PIN_BLOCKED") || has("FIDO_ERR_PIN_AUTH_BLOCKED") {
+        Fido2Error::PinBlocked
+    } else if has("FIDO_ERR_PIN_REQUIRED") {
+        Fido2Error::PinRequired
+    } else if has("FIDO_ERR_NO_CREDENTIALS") {
+        Fido2Error::NoCredential
+    } else if has("FIDO_ERR_UNSUPPORTED_EXTENSION") {
+        Fido2Error::ExtensionUnsupported
+    } else if has("FIDO_ERR_ACTION_TIMEOUT") || has("FIDO_ERR_USER_ACTION_TIMEOUT") {
+        Fido2Error::UserPresenceTimeout(timeout)
+    } else {
+        Fido2Error::Device(match stderr.trim() {
+            "" => status,
+            message => message.to_string(),
+        })
+    }
+}
+
+/// The input of a tool, in a file removed on drop
+struct InputFile(PathBuf);
+
+impl InputFile {
+    fn create(lines: &[String]) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        let name = format!("fp-fido2-{}-{}", std::process::id(), nanos);
+        let path = std::env::temp_dir().join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        // Removed again even if writing fails
+        let input = InputFile(path);
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+        Ok(input)
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Asks `prompter` for the PIN if `authenticator` has one
+///
+/// # Errors
+///
+/// Returns `Fido2Error::PinRequired` if it has one and there is no terminal to
+/// ask on.
+fn ask_pin(
+    authenticator: &mut impl HmacSecret,
+    prompter: &mut impl Prompter,
+) -> Result<Option<Zeroizing<String>>, CliError> {
+    if !authenticator.requires_pin()? {
+        return Ok(None);
+    }
+    if !prompter.is_terminal() {
+        return Err(Fido2Error::PinRequired.into());
+    }
+    Ok(Some(Zeroizing::new(prompter.prompt(Msg::Fido2Pin.text())?)))
+}
+
+/// Reads the master password of `--master fido2[:LABEL]` from the credential
+/// enrolled in `config`
+///
+/// `on_touch` is called before waiting for the authenticator to be touched.
+pub(crate) fn read_master(
+    mut authenticator: impl HmacSecret,
+    spec: &str,
+    config: &Config,
+    prompter: &mut impl Prompter,
+    on_touch: impl FnOnce(),
+) -> Result<Zeroizing<String>, CliError> {
+    let label = fido2::parse_source(spec)?.unwrap_or(DEFAULT_LABEL);
+    let credential = config
+        .fido2_credential
+        .as_deref()
+        .ok_or(CliError::NoFido2Credential)?
+        .parse::<CredentialId>()?;
+    let pin = ask_pin(&mut authenticator, prompter)?;
+    let pin = pin.as_deref().map(String::as_str);
+    let master = master_from_fido2(
+        &mut authenticator,
+        &credential,
+        label,
+        pin,
+        DEFAULT_TIMEOUT,
+        on_touch,
+    )?;
+    Ok(master)
+}
+
+/// Creates a credential on `authenticator` and saves its ID in the
+/// configuration at `path`, creating the file if needed
+///
+/// `on_touch` is called before waiting for the authenticator to be touched.
+pub(crate) fn enroll(
+    mut authenticator: impl HmacSecret,
+    path: &Path,
+    prompter: &mut impl Prompter,
+    on_touch: impl FnOnce(),
+) -> Result<CredentialId, CliError> {
+    let pin = ask_pin(&mut authenticator, prompter)?;
+    let pin = pin.as_deref().map(String::as_str);
+    let credential = fido2::enroll(&mut authenticator, pin, DEFAULT_TIMEOUT, on_touch)?;
+    sites::create_if_missing(path)?;
+    edit_file(path, |document| {
+        set_fido2_credential(document, Some(&credential.to_string()));
+        Ok(())
+    })?;
+    Ok(credential)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+
+    /// Writes the tool `name` into `dir` as a shell script
+    #[cfg(unix)]
+    fn tool(dir: &Path, name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// A terminal answering every prompt with `answer`
+    struct Answer(Option<&'static str>);
+
+    impl Prompter for Answer {
+        fn is_terminal(&self) -> bool {
+            self.0.is_some()
+        }
+
+        fn prompt(&mut self, _prompt: &str) -> io::Result<String> {
+            Ok(self.0.unwrap().to_string())
+        }
+    }
+
+    /// Fake libfido2 tools in `dir`: an authenticator with `options`, asserting
+    /// with the output 0xab... and logging the input and PIN it got
+    #[cfg(unix)]
+    fn fake_tools(dir: &Path, options: &str) {
+        tool(
+            dir,
+            "fido2-token",
+            &format!(
+                "case \"$1\" in\n\
+                 -L) echo '/dev/hidraw7: vendor=0x1050, product=0x0407 (Yubico YubiKey)' ;;\n\
+                 -I) printf 'options: {}\\nextension strings: credProtect, hmac-secret\\n' ;;\n\
+                 esac\n",
+                options
+            ),
+        );
+        let log = dir.join("log");
+        tool(
+            dir,
+            "fido2-cred",
+            &format!(
+                "cat \"$2\" > '{0}'\nread pin && echo \"$pin\" >> '{0}'\n\
+                 printf 'cdh\\nflowerpassword\\npacked\\nauthdata\\nwAE=\\nsig\\n'\n",
+                log.display()
+            ),
+        );
+        tool(
+            dir,
+            "fido2-assert",
+            &format!(
+                "[ -t 0 ] && exit 9\ncat \"$2\" > '{0}'\nread pin && echo \"$pin\" >> '{0}'\n\
+                 printf 'cdh\\nflowerpassword\\nauthdata\\nsig\\n\
+                 q6urq6urq6urq6urq6urq6urq6urq6urq6urq6urq6s=\\n'\n",
+                log.display()
+            ),
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_enroll_and_read_master() {
+        let dir = tempfile::tempdir().unwrap();
+        fake_tools(dir.path(), "rk, up, noclientPin");
+        let path = dir.path().join("config.toml");
+        let tools = Fido2Tools::in_dir(dir.path());
+        let credential = enroll(tools, &path, &mut Answer(None), || {}).unwrap();
+        assert_eq!(credential.to_string(), "c001");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "fido2_credential = \"c001\"\n"
+        );
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        assert_eq!(
+            log,
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\nflowerpassword\nflowerpassword\n\
+             Zmxvd2VycGFzc3dvcmQ=\n"
+        );
+
+        let config = Config::from_toml_str("fido2_credential = \"c001\"\n", "config.toml")
+            .unwrap()
+            .config;
+        let tools = Fido2Tools::in_dir(dir.path());
+        let mut touched = false;
+        let master = read_master(tools, "fido2", &config, &mut Answer(None), || {
+            touched = true
+        })
+        .unwrap();
+        assert!(touched);
+        assert_eq!(*master, "ab".repeat(32));
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        let input: Vec<&str> = log.lines().collect();
+        // The credential, then the salt of the default label
+        assert_eq!(input[2], "wAE=");
+        assert_eq!(input[3], "17Y5x8Jrpx/C53OatBliQibZOhCsWuPQhXUSS83oBGM=");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pin_goes_to_standard_input() {
+        let dir = tempfile::tempdir().unwrap();
+        fake_tools(dir.path(), "rk, up, clientPin");
+        let config = Config::from_toml_str("fido2_credential = \"c001\"\n", "config.toml")
+            .unwrap()
+            .config;
+        let tools = Fido2Tools::in_dir(dir.path());
+        let master = read_master(
+            tools,
+            "fido2:work",
+            &config,
+            &mut Answer(Some("1234")),
+            || {},
+        )
+        .unwrap();
+        assert_eq!(*master, "ab".repeat(32));
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        assert_eq!(log.lines().last(), Some("1234"));
+
+        // Without a terminal to ask on, the authenticator is not touched
+        let tools = Fido2Tools::in_dir(dir.path());
+        let error = read_master(tools, "fido2", &config, &mut Answer(None), || {
+            panic!("touched")
+        })
+        .unwrap_err();
+        assert!(matches!(error, CliError::Fido2(Fido2Error::PinRequired)));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_tool_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        fake_tools(dir.path(), "clientPin");
+        tool(
+            dir.path(),
+            "fido2-assert",
+            "echo 'fido2-assert: fido_dev_get_assert: FIDO_ERR_PIN_INVALID' >&2\nexit 1\n",
+        );
+        let config = Config::from_toml_str("fido2_credential = \"c001\"\n", "config.toml")
+            .unwrap()
+            .config;
+        let tools = Fido2Tools::in_dir(dir.path());
+        let error =
+            read_master(tools, "fido2", &config, &mut Answer(Some("0000")), || {}).unwrap_err();
+        assert!(matches!(error, CliError::Fido2(Fido2Error::PinInvalid(_))));
+
+        tool(dir.path(), "fido2-assert", "sleep 5\n");
+        let mut tools = Fido2Tools::in_dir(dir.path());
+        let timeout = Duration::from_millis(200);
+        let error = tools
+            .hmac_secret(fido2::RELYING_PARTY, &[0xc0, 1], &[0; 32], None, timeout)
+            .unwrap_err();
+        assert!(matches!(error, Fido2Error::UserPresenceTimeout(_)));
+
+        let mut tools = Fido2Tools::in_dir(&dir.path().join("missing"));
+        let error = tools.requires_pin().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "FIDO2 error: fido2-token is not installed or not on PATH"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_no_hmac_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        tool(
+            dir.path(),
+            "fido2-token",
+            "case \"$1\" in\n-L) echo '/dev/hidraw7: U2F key' ;;\n-I) echo 'options: up' ;;\nesac\n",
+        );
+        let mut tools = Fido2Tools::in_dir(dir.path());
+        assert!(matches!(
+            tools.requires_pin(),
+            Err(Fido2Error::ExtensionUnsupported)
+        ));
+        tool(dir.path(), "fido2-token", "true\n");
+        let mut tools = Fido2Tools::in_dir(dir.path());
+        assert!(matches!(tools.requires_pin(), Err(Fido2Error::NotFound)));
+    }
+
+    #[test]
+    fn test_read_master_needs_enrollment() {
+        let error = read_master(
+            Fido2Tools::default(),
+            "fido2",
+            &Config::from_toml_str("", "config.toml").unwrap().config,
+            &mut Answer(None),
+            || {},
+        )
+        .unwrap_err();
+        assert!(matches!(error, CliError::NoFido2Credential));
+        let error = read_master(
+            Fido2Tools::default(),
+            "fido2:",
+            &Config::from_toml_str("", "config.toml").unwrap().config,
+            &mut Answer(None),
+            || {},
+        )
+        .unwrap_err();
+        assert!(matches!(error, CliError::Fido2(Fido2Error::InvalidSource)));
+    }
+}
@@ -0,0 +1,167 @@
+//! `fp find`, fuzzy search over the configured sites
+//!
+//! `fp find QUERY` prints the sites matching `QUERY` as [`Config::find`] ranks
+//! them, best first, with the field that matched and its score. Like `fp list`
+//! it never needs the master password.
+//!
+//! `--select` derives the password of the best match instead. If another match
+//! scores within [`TIE_THRESHOLD`] of it, the close ones are listed on standard
+//! error and the number of the one to derive is read from standard input.
+//!
+//! [`Config::find`]: flowerpassword::config::Config::find
+//! [`TIE_THRESHOLD`]: flowerpassword::config::TIE_THRESHOLD
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::master;
+use flowerpassword::config::{is_tie, MatchField, SiteMatch, TIE_THRESHOLD};
+use std::io::{self, BufRead, Write};
+
+/// Column headers of the table
+const HEADER: [&str; 4] = ["SITE", "FIELD", "MATCH", "SCORE"];
+
+/// Names `field` in the table and the prompt
+fn field_name(field: MatchField) -> &'static str {
+    match field {
+        MatchField::Name => "name",
+        MatchField::Key => "key",
+        MatchField::Alias => "alias",
+        MatchField::Notes => "notes",
+    }
+}
+
+/// Writes `matches` as columns padded to their widest cell
+pub(crate) fn write(out: &mut impl Write, matches: &[SiteMatch]) -> io::Result<()> {
+    let rows: Vec<[String; 4]> = matches
+        .iter()
+        .map(|found| {
+            [
+                found.site.to_string(),
+                field_name(found.field).to_string(),
+                found.text.to_string(),
+                found.score.to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = HEADER.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = HEADER.map(str::to_string);
+    for row in std::iter::once(&header).chain(&rows) {
+        let [site, field, text, score] = row;
+        writeln!(
+            out,
+            "{:<site_width$}  {:<field_width$}  {:<text_width$}  {}",
+            site,
+            field,
+            text,
+            score,
+            site_width = widths[0],
+            field_width = widths[1],
+            text_width = widths[2],
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the site `--select` derives for: the best of `matches`, or the one
+/// picked on `input` among those scoring too close to it
+///
+/// `matches` must be ranked, as returned by `Config::find` for `query`.
+pub(crate) fn select<'a>(
+    query: &str,
+    matches: &[SiteMatch<'a>],
+    input: &mut impl BufRead,
+    err: &mut impl Write,
+) -> Result<&'a str, CliError> {
+    let Some(best) = matches.first() else {
+        return Err(CliError::NoMatch(query.to_string()));
+    };
+    if !is_tie(matches) {
+        return Ok(best.site);
+    }
+    let close: Vec<&SiteMatch> = matches
+        .iter()
+        .take_while(|found| best.score - found.score <= TIE_THRESHOLD)
+        .collect();
+    writeln!(err, "{}", Msg::FindTie.fill(&[&query]))?;
+    for (number, found) in close.iter().enumerate() {
+        writeln!(
+            err,
+            "  {}) {} ({}: {})",
+            number + 1,
+            found.site,
+            field_name(found.field),
+            found.text
+        )?;
+    }
+    write!(err, "{} ", Msg::FindPick.fill(&[&close.len()]))?;
+    err.flush()?;
+    let answer = master::read_line(input)?.unwrap_or_default();
+    match answer.trim().parse::<usize>() {
+        Ok(number) if (1..=close.len()).contains(&number) => Ok(close[number - 1].site),
+        _ => Err(CliError::NoPick(answer.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+
+    const SITES: &str = include_str!("../tests/fixtures/launcher.toml");
+
+    fn pick(query: &str, answer: &str) -> (Result<String, CliError>, String) {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let matches = config.find(query);
+        let mut err = Vec::new();
+        let site = select(query, &matches, &mut answer.as_bytes(), &mut err);
+        (site.map(str::to_string), String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn test_write_table() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut out = Vec::new();
+        write(&mut out, &config.find("git")).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+SITE          FIELD  MATCH         SCORE
+github        name   github        64
+gitlab        name   gitlab        64
+digitalocean  name   digitalocean  56
+"
+        );
+    }
+
+    #[test]
+    fn test_select_clear_winner() {
+        let (site, err) = pick("gith", "");
+        assert_eq!(site.unwrap(), "github");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_select_tie_asks() {
+        let (site, err) = pick("git", "2\n");
+        assert_eq!(site.unwrap(), "gitlab");
+        assert!(err.contains("  1) github (name: github)\n"), "{}", err);
+        assert!(err.contains("  2) gitlab (name: gitlab)\n"), "{}", err);
+        assert!(!err.contains("digitalocean"), "{}", err);
+
+        for answer in ["", "3\n", "x\n"] {
+            let (site, _) = pick("git", answer);
+            assert!(matches!(site, Err(CliError::NoPick(_))), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn test_select_no_match() {
+        let (site, _) = pick("zzz", "");
+        assert!(matches!(site, Err(CliError::NoMatch(query)) if query == "zzz"));
+    }
+}
@@ -0,0 +1,159 @@
+//! `fp fingerprint` and `fp verify-master`, catching a mistyped master password
+//!
+//! `fp fingerprint --save` keeps the short fingerprint of the master password,
+//! from [`fp_fingerprint`], as `fingerprint` at the top of the configuration.
+//! Only those few characters are stored, never the master password.
+//! `fp verify-master` then checks a master password against it, and `--batch`
+//! and `fp pass sync` check before deriving anything, since a typo there makes
+//! every password of the run wrong.
+//!
+//! On a mismatch a terminal is asked whether to go on anyway; without one, or
+//! with `--quiet`, the run stops with exit status 5.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::sites;
+use flowerpassword::config::{edit_file, set_fingerprint};
+use flowerpassword::fp_fingerprint;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// Returns whether `master` has the fingerprint `stored`, or `None` if there
+/// is none to compare with
+pub(crate) fn verify(stored: Option<&str>, master: &str) -> Option<bool> {
+    stored.map(|stored| fp_fingerprint(master) == stored)
+}
+
+/// Stops a run whose master password does not match the stored fingerprint
+///
+/// `ask`, given on a terminal, is asked whether to go on anyway.
+///
+/// # Errors
+///
+/// Returns `CliError::FingerprintMismatch` on a mismatch that is not confirmed,
+/// or that there was no `ask` for.
+pub(crate) fn guard<F>(stored: Option<&str>, master: &str, ask: Option<F>) -> Result<(), CliError>
+where
+    F: FnOnce() -> io::Result<bool>,
+{
+    if verify(stored, master) != Some(false) {
+        return Ok(());
+    }
+    match ask.map(|ask| ask()).transpose()? {
+        Some(true) => Ok(()),
+        _ => Err(CliError::FingerprintMismatch),
+    }
+}
+
+/// Warns about a mismatch on `err` and asks whether to go on, reading the
+/// answer from `input`
+///
+/// Only `y` or `yes`, in any case, goes on.
+pub(crate) fn ask(input: &mut impl BufRead, err: &mut impl Write) -> io::Result<bool> {
+    writeln!(
+        err,
+        "fp: {}: {}",
+        Msg::Warning.text(),
+        Msg::FingerprintWarning.text()
+    )?;
+    sites::confirm(input, err, Msg::FingerprintContinue.text())
+}
+
+/// Stores the fingerprint of `master` in the configuration at `path`, creating
+/// the file if needed, and returns it
+pub(crate) fn save(path: &Path, master: &str) -> Result<String, CliError> {
+    let fingerprint = fp_fingerprint(master);
+    sites::create_if_missing(path)?;
+    edit_file(path, |document| {
+        set_fingerprint(document, Some(&fingerprint));
+        Ok(())
+    })?;
+    Ok(fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+    use std::fs;
+
+    #[test]
+    fn test_save_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flowerpassword").join("config.toml");
+        let fingerprint = save(&path, "correct horse battery staple").unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text, format!("fingerprint = \"{}\"\n", fingerprint));
+        assert_eq!(fingerprint.len(), flowerpassword_core::FINGERPRINT_LENGTH);
+        for part in ["correct", "horse", "battery", "staple"] {
+            assert!(!text.contains(part));
+        }
+
+        let stored = Config::load(&path).unwrap().config.fingerprint;
+        assert_eq!(
+            verify(stored.as_deref(), "correct horse battery staple"),
+            Some(true)
+        );
+        assert_eq!(
+            verify(stored.as_deref(), "correct horse battery stapel"),
+            Some(false)
+        );
+        assert_eq!(verify(None, "anything"), None);
+
+        // Saving again replaces it, and keeps the rest of the file
+        fs::write(&path, format!("{}\n[sites.github]\n", text)).unwrap();
+        let other = save(&path, "test").unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            format!("fingerprint = \"{}\"\n\n[sites.github]\n", other)
+        );
+    }
+
+    #[test]
+    fn test_guard() {
+        let stored = fp_fingerprint("test");
+        let never = None::<fn() -> io::Result<bool>>;
+        assert!(guard(Some(&stored), "test", never).is_ok());
+        assert!(guard(None, "tset", never).is_ok());
+        // Without a terminal to ask, a mismatch stops the run
+        let error = guard(Some(&stored), "tset", never).unwrap_err();
+        assert!(matches!(error, CliError::FingerprintMismatch));
+        assert_eq!(error.exit_code(), crate::error::EXIT_FINGERPRINT);
+
+        assert!(guard(Some(&stored), "tset", Some(|| Ok(true))).is_ok());
+        assert!(matches!(
+            guard(Some(&stored), "tset", Some(|| Ok(false))),
+            Err(CliError::FingerprintMismatch)
+        ));
+        // A match never asks
+        assert!(guard(
+            Some(&stored),
+            "test",
+            Some(|| -> io::Result<bool> { panic!() })
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_ask() {
+        let answer = |text: &str| {
+            let mut err = Vec::new();
+            let yes = ask(&mut text.as_bytes(), &mut err).unwrap();
+            (yes, String::from_utf8(err).unwrap())
+        };
+        let (yes, err) = answer("y\n");
+        assert!(yes);
+        assert_eq!(
+            err,
+            "fp: warning: The master password does not match the saved fingerprint; \
+             every password of this run would be wrong\n\
+             Continue with this master password anyway? [y/N] "
+        );
+        assert!(answer("YES\n").0);
+        for text in ["\n", "n\n", "", "yess\n"] {
+            assert!(!answer(text).0, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! `--password-gpg`, a master password kept in a gpg-encrypted file
+//!
+//! The file is decrypted with `gpg --quiet --decrypt`, so gpg-agent and its
+//! pinentry ask for the passphrase as usual; like gpg itself, a curses or tty
+//! pinentry needs `$GPG_TTY`. The first line of the plaintext is the master
+//! password, as with `--password-file`, and the captured output is zeroed once it
+//! has been read.
+
+use crate::error::CliError;
+use crate::i18n::{self, Msg};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Error type for decrypting the master password file
+#[derive(Debug)]
+pub(crate) enum GpgError {
+    /// The gpg program is not on `PATH`
+    NotInstalled,
+    /// gpg ran and failed, with what it wrote to standard error
+    Failed(String),
+    /// gpg could not be started
+    Io(io::Error),
+}
+
+impl fmt::Display for GpgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpgError::NotInstalled => i18n::write(f, Msg::NotInstalled, &[&"gpg"]),
+            GpgError::Failed(message) => i18n::write(f, Msg::GpgFailed, &[message]),
+            GpgError::Io(error) => i18n::write(f, Msg::GpgIo, &[error]),
+        }
+    }
+}
+
+impl Error for GpgError {}
+
+/// A way to decrypt a gpg file
+pub(crate) trait Decrypt {
+    /// Returns the plaintext of the file at `path`
+    fn decrypt(&mut self, path: &Path) -> Result<Zeroizing<Vec<u8>>, GpgError>;
+}
+
+/// A [`Decrypt`] running the `gpg` program
+#[derive(Debug, Clone)]
+pub(crate) struct Gpg {
+    program: PathBuf,
+}
+
+impl Default for Gpg {
+    fn default() -> Self {
+        Gpg {
+            program: PathBuf::from("gpg"),
+        }
+    }
+}
+
+impl Decrypt for Gpg {
+    fn decrypt(&mut self, path: &Path) -> Result<Zeroizing<Vec<u8>>, GpgError> {
+        // Standard input is left alone for --batch keys; pinentry uses the tty
+        let output = Command::new(&self.program)
+            .args(["--quiet", "--decrypt"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => GpgError::NotInstalled,
+                _ => GpgError::Io(error),
+            })?;
+        let plaintext = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(GpgError::Failed(match stderr.trim() {
+                "" => output.status.to_string(),
+                message => message.to_string(),
+            }));
+        }
+        Ok(plaintext)
+    }
+}
+
+/// Reads the master password from the first line of the gpg file at `path`,
+/// into a buffer wiped on drop like the plaintext it is copied from
+///
+/// # Errors
+///
+/// Returns `CliError::Gpg` if decryption fails, and `CliError::EmptyMaster` if
+/// the plaintext is empty or not UTF-8.
+pub(crate) fn read_master_gpg(
+    mut gpg: impl Decrypt,
+    path: &Path,
+) -> Result<Zeroizing<String>, CliError> {
+    let plaintext = gpg.decrypt(path)?;
+    let empty = || CliError::EmptyMaster(path.display().to_string());
+    let text = std::str::from_utf8(&plaintext).map_err(|_| empty())?;
+    // Copied once at its final size, so no reallocation leaves a copy behind
+    let line = text.lines().next().ok_or_else(empty)?;
+    Ok(Zeroizing::new(line.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeGpg(Result<&'static str, &'static str>);
+
+    impl Decrypt for FakeGpg {
+        fn decrypt(&mut self, _path: &Path) -> Result<Zeroizing<Vec<u8>>, GpgError> {
+            match self.0 {
+                Ok(plaintext) => Ok(Zeroizing::new(plaintext.as_bytes().to_vec())),
+                Err(message) => Err(GpgError::Failed(message.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_line_is_the_master() {
+        let path = Path::new("master.gpg");
+        assert_eq!(
+            *read_master_gpg(FakeGpg(Ok("test\n")), path).unwrap(),
+            "test"
+        );
+        assert_eq!(
+            *read_master_gpg(FakeGpg(Ok("test\r\nnotes\n")), path).unwrap(),
+            "test"
+        );
+        assert_eq!(*read_master_gpg(FakeGpg(Ok("test")), path).unwrap(), "test");
+        assert_eq!(
+            read_master_gpg(FakeGpg(Ok("")), path)
+                .unwrap_err()
+                .to_string(),
+            "No master password in master.gpg"
+        );
+    }
+
+    #[test]
+    fn test_failure_carries_gpg_stderr() {
+        let error = read_master_gpg(
+            FakeGpg(Err("gpg: decryption failed: No secret key")),
+            Path::new("master.gpg"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "gpg could not decrypt the master password: gpg: decryption failed: No secret key"
+        );
+    }
+
+    #[test]
+    fn test_missing_program() {
+        let mut gpg = Gpg {
+            program: PathBuf::from("/nonexistent/gpg"),
+        };
+        assert!(matches!(
+            gpg.decrypt(Path::new("master.gpg")),
+            Err(GpgError::NotInstalled)
+        ));
+    }
+}
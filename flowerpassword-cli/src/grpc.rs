@@ -0,0 +1,207 @@
+//! `fp serve --grpc`, the service of `proto/flowerpassword.proto`
+//!
+//! `Derive` answers one [`DeriveRequest`], and `DeriveStream` answers a stream
+//! of them in order, as they arrive, for batches. Every call needs the metadata
+//! `authorization: Bearer <token>` with the token of [`crate::serve`], or fails
+//! with `UNAUTHENTICATED`.
+//!
+//! An invalid length fails with `INVALID_ARGUMENT`, the library's message as
+//! both the status message and its details; in `DeriveStream` that ends the
+//! stream after the passwords before it.
+
+// tonic's services and interceptors return its large `Status` by value
+#![allow(clippy::result_large_err)]
+
+use crate::serve::{self, authorized};
+use flowerpassword::FlowerPasswordError;
+use proto::flower_password_server::{FlowerPassword, FlowerPasswordServer};
+use proto::{DeriveRequest, DeriveResponse};
+use std::io;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::StreamExt;
+use tonic::codegen::{BoxStream, Bytes};
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+/// The messages and services generated from `proto/flowerpassword.proto`
+pub(crate) mod proto {
+    // The client is only used by the tests
+    #![allow(dead_code)]
+    tonic::include_proto!("flowerpassword.v1");
+}
+
+/// Returns the status of a refused length
+pub(crate) fn status(error: FlowerPasswordError) -> Status {
+    let message = error.to_string();
+    let details = Bytes::from(message.clone().into_bytes());
+    Status::with_details(Code::InvalidArgument, message, details)
+}
+
+/// Answers one request
+fn derive(mut request: DeriveRequest) -> Result<DeriveResponse, Status> {
+    let length = request.length.map(|length| length as usize);
+    let password = serve::derive(&mut request.password, &request.key, length).map_err(status)?;
+    Ok(DeriveResponse { password })
+}
+
+/// The `FlowerPassword` service, holding no master password
+struct Service;
+
+#[tonic::async_trait]
+impl FlowerPassword for Service {
+    async fn derive(
+        &self,
+        request: Request<DeriveRequest>,
+    ) -> Result<Response<DeriveResponse>, Status> {
+        derive(request.into_inner()).map(Response::new)
+    }
+
+    type DeriveStreamStream = BoxStream<DeriveResponse>;
+
+    async fn derive_stream(
+        &self,
+        request: Request<Streaming<DeriveRequest>>,
+    ) -> Result<Response<Self::DeriveStreamStream>, Status> {
+        let responses = request.into_inner().map(|request| request.and_then(derive));
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
+
+/// Serves the service on `listener` until the process is stopped, checking
+/// every call against `token`
+pub(crate) async fn serve(listener: TcpListener, token: String) -> io::Result<()> {
+    let check = move |request: Request<()>| {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .map(|value| value.as_bytes());
+        if authorized(authorization, &token) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("Missing or wrong bearer token"))
+        }
+    };
+    Server::builder()
+        .add_service(FlowerPasswordServer::with_interceptor(Service, check))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::flower_password_client::FlowerPasswordClient;
+    use tonic::transport::Channel;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    /// Starts a server on an ephemeral port and connects a client to it
+    async fn client() -> FlowerPasswordClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, TOKEN.to_string()));
+        FlowerPasswordClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap()
+    }
+
+    fn request(key: &str, length: Option<u32>) -> DeriveRequest {
+        DeriveRequest {
+            password: "test".to_string(),
+            key: key.to_string(),
+            length,
+        }
+    }
+
+    fn authorized<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        let value = format!("Bearer {}", token).parse().unwrap();
+        request.metadata_mut().insert("authorization", value);
+        request
+    }
+
+    #[tokio::test]
+    async fn test_derive() {
+        let mut client = client().await;
+        let response = client
+            .derive(authorized(request("github.com", Some(16)), TOKEN))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().password, "D04175F7A9c7Ab4a");
+        // The length defaults to 16
+        let response = client
+            .derive(authorized(request("key", None), TOKEN))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().password, "KAC5fEf00146FD68");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_length() {
+        let mut client = client().await;
+        let error = client
+            .derive(authorized(request("key", Some(40)), TOKEN))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(error.message(), "Length must be between 2 and 32, got: 40");
+        assert_eq!(error.details(), error.message().as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated() {
+        let mut client = client().await;
+        let error = client
+            .derive(Request::new(request("key", None)))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::Unauthenticated);
+        let error = client
+            .derive(authorized(request("key", None), "0123456789abcdeF"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn test_derive_stream() {
+        let mut client = client().await;
+        let requests = vec![
+            request("github.com", None),
+            request("key", Some(8)),
+            request("key", Some(1)),
+            request("github.com", None),
+        ];
+        let mut responses = client
+            .derive_stream(authorized(tokio_stream::iter(requests), TOKEN))
+            .await
+            .unwrap()
+            .into_inner();
+        let password = |response: Option<DeriveResponse>| response.unwrap().password;
+        assert_eq!(
+            password(responses.message().await.unwrap()),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(password(responses.message().await.unwrap()), "KAC5fEf0");
+        // The invalid length ends the stream
+        let error = responses.message().await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(error.message(), "Length must be between 2 and 32, got: 1");
+
+        let unauthenticated = client
+            .derive_stream(tokio_stream::iter(vec![request("key", None)]))
+            .await
+            .unwrap_err();
+        assert_eq!(unauthenticated.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_status() {
+        let status = status(FlowerPasswordError::InvalidLength(33));
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Length must be between 2 and 32, got: 33");
+        assert_eq!(status.details(), status.message().as_bytes());
+    }
+}
@@ -0,0 +1,313 @@
+//! `--log` and `fp history`, a record of which sites were derived and when
+//!
+//! Only with `--log` does `fp` append one JSON line per derived password to
+//! `history.jsonl` in the user's state directory, such as
+//! `$XDG_STATE_HOME/flowerpassword` on Linux:
+//!
+//! ```text
+//! {"ts":1791980000,"site":"github","length":16,"scheme":"v1"}
+//! ```
+//!
+//! `ts` is in seconds since the Unix epoch, and `site` is the configured site
+//! name, or the key for names that are not configured. Neither the password nor
+//! the master password is ever written. The file is created readable by its owner
+//! only, and each record is a single append, so runs can log side by side.
+//!
+//! `fp history [SITE] [--since 30d]` prints the records, oldest first, and
+//! `fp history clear` removes the file.
+
+use directories::ProjectDirs;
+use flowerpassword::Scheme;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the history file in the state directory
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Column headers of `fp history`
+const HEADER: [&str; 4] = ["TIME", "SITE", "LENGTH", "SCHEME"];
+
+/// One derived password, without the password
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Record {
+    /// Seconds since the Unix epoch
+    pub(crate) ts: u64,
+    /// Site name, or the key if it is not configured
+    pub(crate) site: String,
+    /// Length of the password
+    pub(crate) length: usize,
+    /// Derivation scheme
+    pub(crate) scheme: String,
+}
+
+impl Record {
+    /// Returns the record of a password for `site` derived under `scheme` at `now`
+    pub(crate) fn new(site: &str, length: usize, scheme: Scheme, now: SystemTime) -> Self {
+        Record {
+            ts: unix_seconds(now),
+            site: site.to_string(),
+            length,
+            scheme: scheme.name().to_string(),
+        }
+    }
+}
+
+/// Returns the path of the history file
+pub(crate) fn history_path() -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("", "", "flowerpassword")?;
+    let dir = dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir());
+    Some(dir.join(HISTORY_FILE))
+}
+
+/// Appends `record` to the history at `path`, creating it for its owner only
+pub(crate) fn append(path: &Path, record: &Record) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    // One write of a short line, so concurrent appends do not interleave
+    options.open(path)?.write_all(line.as_bytes())
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// Reads the records of the history at `path`, none if it does not exist
+///
+/// `on_invalid` is called with the number of each line that is not a record,
+/// which is then skipped.
+pub(crate) fn read(path: &Path, mut on_invalid: impl FnMut(usize)) -> io::Result<Vec<Record>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(_) => on_invalid(index + 1),
+        }
+    }
+    Ok(records)
+}
+
+/// Removes the history at `path`, returning whether there was one
+pub(crate) fn clear(path: &Path) -> io::Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Returns the records for `site`, if given, from `since` before `now` on
+pub(crate) fn filter<'a>(
+    records: &'a [Record],
+    site: Option<&str>,
+    since: Option<Duration>,
+    now: SystemTime,
+) -> Vec<&'a Record> {
+    let cutoff = since.map(|since| unix_seconds(now).saturating_sub(since.as_secs()));
+    records
+        .iter()
+        .filter(|record| site.is_none_or(|site| record.site == site))
+        .filter(|record| cutoff.is_none_or(|cutoff| record.ts >= cutoff))
+        .collect()
+}
+
+/// Writes `records` as columns under a header
+pub(crate) fn write_table(out: &mut impl Write, records: &[&Record]) -> io::Result<()> {
+    let site_width = records
+        .iter()
+        .map(|record| record.site.chars().count())
+        .chain([HEADER[1].len()])
+        .max()
+        .unwrap_or_default();
+    let [time, site, length, scheme] = HEADER;
+    writeln!(
+        out,
+        "{:<20}  {:<site_width$}  {:<6}  {}",
+        time, site, length, scheme
+    )?;
+    for record in records {
+        writeln!(
+            out,
+            "{:<20}  {:<site_width$}  {:<6}  {}",
+            format_utc(record.ts),
+            record.site,
+            record.length,
+            record.scheme
+        )?;
+    }
+    Ok(())
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 time in UTC
+pub(crate) fn format_utc(ts: u64) -> String {
+    let (days, seconds) = (ts / 86_400, ts % 86_400);
+    // Howard Hinnant's civil_from_days, for days since 1970-01-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ts: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(ts)
+    }
+
+    #[test]
+    fn test_record_shape() {
+        let record = Record::new("github", 16, Scheme::V1, at(1_791_980_000));
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"ts":1791980000,"site":"github","length":16,"scheme":"v1"}"#
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let day = 86_400;
+        let now = 100 * day;
+        let records = [
+            Record::new("github", 16, Scheme::V1, at(now - 40 * day)),
+            Record::new("mail", 12, Scheme::V1, at(now - 30 * day)),
+            Record::new("github", 16, Scheme::V1, at(now - 30 * day + 1)),
+            Record::new("github", 20, Scheme::V1, at(now)),
+        ];
+        let since = |text: &str| Some(crate::duration::parse_duration(text).unwrap());
+        let lengths = |filtered: Vec<&Record>| -> Vec<usize> {
+            filtered.iter().map(|record| record.length).collect()
+        };
+        assert_eq!(filter(&records, None, None, at(now)).len(), 4);
+        assert_eq!(
+            lengths(filter(&records, Some("github"), None, at(now))),
+            [16, 16, 20]
+        );
+        // The cutoff itself is included
+        assert_eq!(
+            lengths(filter(&records, None, since("30d"), at(now))),
+            [12, 16, 20]
+        );
+        assert_eq!(
+            lengths(filter(&records, Some("github"), since("30d"), at(now))),
+            [16, 20]
+        );
+        assert_eq!(lengths(filter(&records, None, since("1s"), at(now))), [20]);
+        assert!(filter(&records, Some("gitlab"), None, at(now)).is_empty());
+        // A window reaching before the epoch keeps everything
+        assert_eq!(filter(&records, None, since("1000d"), at(now)).len(), 4);
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(HISTORY_FILE);
+        assert!(read(&path, |_| panic!()).unwrap().is_empty());
+        let first = Record::new("github", 16, Scheme::V1, at(1));
+        let second = Record::new("github.com", 8, Scheme::V1, at(2));
+        append(&path, &first).unwrap();
+        append(&path, &second).unwrap();
+        assert_eq!(read(&path, |_| panic!()).unwrap(), [first.clone(), second]);
+
+        let mut text = fs::read_to_string(&path).unwrap();
+        text.push_str("not json\n\n");
+        fs::write(&path, text).unwrap();
+        let mut invalid = Vec::new();
+        assert_eq!(read(&path, |line| invalid.push(line)).unwrap().len(), 2);
+        assert_eq!(invalid, [3]);
+
+        assert!(clear(&path).unwrap());
+        assert!(!clear(&path).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_permissions_on_creation() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("state");
+        let path = state.join(HISTORY_FILE);
+        append(&path, &Record::new("github", 16, Scheme::V1, at(1))).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&state), 0o700);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_791_980_000), "2026-10-14T12:13:20Z");
+        assert_eq!(format_utc(4_107_542_399), "2100-02-28T23:59:59Z");
+    }
+
+    #[test]
+    fn test_table() {
+        let records = [
+            Record::new("github", 16, Scheme::V1, at(0)),
+            Record::new("mail.example.org", 8, Scheme::V1, at(86_400)),
+        ];
+        let mut out = Vec::new();
+        write_table(&mut out, &records.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TIME                  SITE              LENGTH  SCHEME\n\
+             1970-01-01T00:00:00Z  github            16      v1\n\
+             1970-01-02T00:00:00Z  mail.example.org  8       v1\n"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! `fp serve --http`, a loopback HTTP API for browser extensions
+//!
+//! `POST /derive` takes an [`FpRequest`] and answers with the password:
+//!
+//! ```text
+//! POST /derive
+//! Authorization: Bearer 3b1f…
+//!
+//! {"password": "test", "key": "github.com", "length": 16}
+//!
+//! 200 {"password": "D04175F7A9c7Ab4a"}
+//! ```
+//!
+//! Every request needs the bearer token of [`crate::serve`]. Errors share one
+//! schema, `{"error": {"kind": "InvalidLength", "message": "…"}}`, with the
+//! `kind`s of [`ApiError`], and nothing is ever cached.
+
+use crate::serve::{self, authorized};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::{AUTHORIZATION, CACHE_CONTROL};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use flowerpassword::FlowerPasswordError;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// The body of `POST /derive`, the arguments of [`flowerpassword::fp_code`]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FpRequest {
+    /// Master password
+    password: String,
+    key: String,
+    /// 16 if left out
+    length: Option<usize>,
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ApiError {
+    /// The bearer token is missing or wrong
+    Unauthorized,
+    /// Nothing answers at this path
+    NotFound,
+    /// The body is not an `FpRequest`
+    InvalidRequest(String),
+    /// The length is not between 2 and 32
+    InvalidLength(usize),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::InvalidRequest(_) | ApiError::InvalidLength(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Returns the error in the schema of every failed response
+    pub(crate) fn to_json(&self) -> Value {
+        let (kind, message) = match self {
+            ApiError::Unauthorized => ("Unauthorized", "Missing or wrong bearer token".to_string()),
+            ApiError::NotFound => ("NotFound", "Only POST /derive is served".to_string()),
+            ApiError::InvalidRequest(message) => ("InvalidRequest", message.clone()),
+            ApiError::InvalidLength(length) => (
+                "InvalidLength",
+                FlowerPasswordError::InvalidLength(*length).to_string(),
+            ),
+        };
+        let mut error = json!({ "kind": kind, "message": message });
+        if let ApiError::InvalidLength(length) = self {
+            error["length"] = json!(length);
+        }
+        json!({ "error": error })
+    }
+}
+
+impl From<FlowerPasswordError> for ApiError {
+    fn from(error: FlowerPasswordError) -> Self {
+        match error {
+            FlowerPasswordError::InvalidLength(length) => ApiError::InvalidLength(length),
+        }
+    }
+}
+
+/// Answers `POST /derive` with `body`, given the `Authorization` header
+pub(crate) fn derive(
+    token: &str,
+    authorization: Option<&[u8]>,
+    body: &[u8],
+) -> Result<String, ApiError> {
+    if !authorized(authorization, token) {
+        return Err(ApiError::Unauthorized);
+    }
+    let mut request: FpRequest = serde_json::from_slice(body)
+        .map_err(|error| ApiError::InvalidRequest(format!("Invalid request: {}", error)))?;
+    Ok(serve::derive(
+        &mut request.password,
+        &request.key,
+        request.length,
+    )?)
+}
+
+/// Returns the routes of the API, checking requests against `token`
+fn router(token: String) -> Router {
+    Router::new()
+        .route("/derive", post(handle_derive))
+        .fallback(|| async { respond(Err(ApiError::NotFound)) })
+        .with_state(Arc::new(token))
+}
+
+async fn handle_derive(
+    State(token): State<Arc<String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let authorization = headers.get(AUTHORIZATION).map(HeaderValue::as_bytes);
+    respond(derive(&token, authorization, &body))
+}
+
+fn respond(result: Result<String, ApiError>) -> Response {
+    let (status, body) = match result {
+        Ok(password) => (StatusCode::OK, json!({ "password": password })),
+        Err(error) => (error.status(), error.to_json()),
+    };
+    let mut response = (status, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// Serves the API on `listener` until the process is stopped
+pub(crate) async fn serve(listener: TcpListener, token: String) -> io::Result<()> {
+    axum::serve(listener, router(token)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn bearer(token: &str) -> Vec<u8> {
+        format!("Bearer {}", token).into_bytes()
+    }
+
+    #[test]
+    fn test_derive() {
+        let auth = bearer(TOKEN);
+        let derive = |body: &str| derive(TOKEN, Some(&auth), body.as_bytes());
+        assert_eq!(
+            derive(r#"{"password":"test","key":"github.com","length":16}"#).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        // The length defaults to 16
+        assert_eq!(
+            derive(r#"{"password":"password","key":"key"}"#).unwrap(),
+            "K3A2a66Bf88b628c"
+        );
+        assert_eq!(
+            derive(r#"{"password":"test","key":"key","length":40}"#),
+            Err(ApiError::InvalidLength(40))
+        );
+        for body in [
+            "",
+            "not json",
+            r#"{"key":"key"}"#,
+            r#"{"password":"test","key":"key","extra":1}"#,
+        ] {
+            assert!(
+                matches!(derive(body), Err(ApiError::InvalidRequest(_))),
+                "{}",
+                body
+            );
+        }
+        // The token is checked before the body
+        assert_eq!(
+            super::derive(TOKEN, None, b"not json"),
+            Err(ApiError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn test_error_schema() {
+        assert_eq!(
+            ApiError::InvalidLength(40).to_json(),
+            json!({ "error": {
+                "kind": "InvalidLength",
+                "message": "Length must be between 2 and 32, got: 40",
+                "length": 40
+            }})
+        );
+        assert_eq!(
+            ApiError::Unauthorized.to_json()["error"]["kind"],
+            "Unauthorized"
+        );
+        assert_eq!(ApiError::Unauthorized.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(ApiError::NotFound.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            ApiError::InvalidRequest(String::new()).status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
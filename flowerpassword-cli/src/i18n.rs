@@ -0,0 +1,956 @@
+//! Messages of `fp`, in English and Simplified Chinese
+//!
+//! Prompts, errors, warnings and summaries are looked up by [`Msg`] in the
+//! catalog of the language `--lang` picks, or else the locale: `$LC_ALL`,
+//! `$LC_MESSAGES` and `$LANG`, the first one set. A message missing from a
+//! catalog falls back to English.
+//!
+//! Only what `fp` itself reports is translated. Errors from the library, such
+//! as those of the configuration, keep their English `Display`, and so do clap's
+//! usage errors and `--help`.
+
+use clap::ValueEnum;
+use std::fmt::{self, Write};
+use std::sync::OnceLock;
+
+/// A language `fp` speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Lang {
+    /// English
+    #[value(name = "en")]
+    En,
+    /// Simplified Chinese
+    #[value(name = "zh-CN")]
+    ZhCn,
+}
+
+impl Lang {
+    /// Returns the language of the locale, looking variables up through `env`
+    pub(crate) fn from_locale(env: impl Fn(&str) -> Option<String>) -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(env)
+            .find(|value| !value.is_empty());
+        match locale {
+            Some(locale) if is_simplified_chinese(&locale) => Lang::ZhCn,
+            _ => Lang::En,
+        }
+    }
+
+    fn catalog(self) -> &'static [(Msg, &'static str)] {
+        match self {
+            Lang::En => &[],
+            Lang::ZhCn => ZH_CN,
+        }
+    }
+}
+
+/// Tells Simplified Chinese locales apart from the Traditional ones of Taiwan,
+/// Hong Kong and Macau, which keep English
+fn is_simplified_chinese(locale: &str) -> bool {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    matches!(name, "zh_CN" | "zh_SG") || name.starts_with("zh-Hans") || name.starts_with("zh_Hans")
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Sets the language of the rest of the run; only the first call counts
+pub(crate) fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// Returns the language of the run, English until [`set_lang`]
+pub(crate) fn lang() -> Lang {
+    LANG.get().copied().unwrap_or(Lang::En)
+}
+
+/// A message, with `{}` for each of its arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Msg {
+    MasterPrompt,
+    ConfirmPrompt,
+    Warning,
+    Error,
+    ConfirmNeedsTerminal,
+    ShowTimeoutNotTerminal,
+    NoUsername,
+    NoUsernameShown,
+    NoUsernameCopied,
+    UserCopiedPressEnter,
+    UserCopiedWaiting,
+    PasswordCopied,
+    TypingIn,
+    UnlockedFor,
+    UnlockedLeft,
+    Locked,
+    KeyringStored,
+    KeyringNotStored,
+    KeyringHasMaster,
+    KeyringNoMaster,
+    KeyringEmpty,
+    KeyringError,
+    Entropy,
+    Weak,
+    Fair,
+    Good,
+    Strong,
+    NoMaster,
+    MasterMismatch,
+    PassphrasePrompt,
+    PassphraseConfirm,
+    PassphraseNeedsTerminal,
+    PassphraseMismatch,
+    ConfigEncrypted,
+    ConfigDecrypted,
+    ConfigNotEncrypted,
+    ConfigEncryptedUnchecked,
+    ConfigEncryptedHint,
+    EmptyMaster,
+    FileDescriptor,
+    InsecurePermissions,
+    InsecureCache,
+    NoRuntimeDir,
+    NoKernelKeyring,
+    BadFd,
+    PinentryDescription,
+    NoPinentry,
+    QrEncode,
+    NoTerminal,
+    Interrupted,
+    KeysIncomplete,
+    BatchStats,
+    NoStateDir,
+    NoHistory,
+    HistoryInvalid,
+    DoctorIncomplete,
+    SelfTestPassed,
+    SelfTestFailed,
+    LintFindings,
+    AuditReused,
+    AuditClean,
+    AuditFailed,
+    ImportCreated,
+    ImportCreatedUser,
+    ImportSkipped,
+    ImportSummary,
+    ImportDuplicates,
+    ExportPlaintext,
+    ExportConfirm,
+    ExportCancelled,
+    Exported,
+    NativeHostLocked,
+    Fido2Pin,
+    Fido2Touch,
+    Fido2Saved,
+    Fido2Enrolled,
+    Fido2Output,
+    NoFido2Credential,
+    YubiKeyTouch,
+    UnknownMasterSource,
+    ManifestInstalled,
+    UriHandlerLocked,
+    UriHandlerRegistered,
+    LengthFound,
+    Pwned,
+    PwnedUnchecked,
+    Blocklisted,
+    MasterBlocklisted,
+    BlocklistUnreadable,
+    PolicyUnmet,
+    LintClean,
+    MigrateCurrent,
+    MigrateDryRun,
+    Migrated,
+    NoConfigDir,
+    NoHomeDir,
+    NoFingerprint,
+    FingerprintMismatch,
+    FingerprintWarning,
+    FingerprintContinue,
+    FingerprintMatches,
+    FingerprintSaved,
+    SiteAdded,
+    SiteReplaced,
+    SiteRemoveConfirm,
+    SiteRemoved,
+    SiteKept,
+    SiteRenamed,
+    SiteRenameReplaced,
+    SiteRenameAliases,
+    RenameKeyKept,
+    RenameKeyConfirm,
+    RenameKeyChanged,
+    AliasesDangling,
+    AliasSet,
+    AliasReplaced,
+    AliasRemoved,
+    AliasShadowed,
+    UnknownAlias,
+    NoteSet,
+    NoteAppended,
+    NoMatch,
+    NoPick,
+    FindTie,
+    FindPick,
+    NotLoopback,
+    HttpListening,
+    GrpcListening,
+    DbusServing,
+    ServeToken,
+    Hint,
+    ConfigLoaded,
+    ConfigWarnings,
+    ConfigHint,
+    ClipboardFound,
+    ClipboardMissing,
+    ClipboardHint,
+    KeyringHint,
+    AgentNoEndpoint,
+    AgentReachable,
+    AgentUnreachable,
+    AgentHint,
+    AgentStarted,
+    AgentStopped,
+    AgentNotRunning,
+    AgentLocked,
+    AgentRefused,
+    MemlockOk,
+    MemlockRefused,
+    MemlockHint,
+    HardeningOk,
+    HardeningMissing,
+    HardeningHint,
+    Osc52Ok,
+    Osc52NoTerminal,
+    Osc52Dumb,
+    Osc52Hint,
+    Osc52Tmux,
+    Osc52TmuxHint,
+    NotInstalled,
+    GpgFailed,
+    GpgIo,
+    MenuFailed,
+    MenuIo,
+    PassFailed,
+    PassIo,
+    PassOutsidePrefix,
+    PassIncomplete,
+    NoDisplay,
+    CannotType,
+    TypingFailed,
+    TypingIo,
+    ReplHelp,
+    ReplCopied,
+    ReplLocked,
+    ReplAlreadyLocked,
+    UnknownCommand,
+    Usage,
+    BadLength,
+    TooManyWords,
+    MaskKeys,
+    MaskKeysNoCopy,
+    MaskCopied,
+    MaskLength,
+    TuiReady,
+    TuiCopied,
+    TuiRestored,
+}
+
+impl Msg {
+    /// Returns the message in the language of the run
+    pub(crate) fn text(self) -> &'static str {
+        self.text_in(lang())
+    }
+
+    /// Returns the message in `lang`, or in English if `lang` lacks it
+    pub(crate) fn text_in(self, lang: Lang) -> &'static str {
+        translate(lang.catalog(), self)
+    }
+
+    /// Returns the message in the language of the run, with `args` in order
+    /// for its `{}`
+    pub(crate) fn fill(self, args: &[&dyn fmt::Display]) -> String {
+        fill(self.text(), args)
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            Msg::MasterPrompt => "Master password: ",
+            Msg::ConfirmPrompt => "Repeat master password: ",
+            Msg::Warning => "warning",
+            Msg::Error => "error",
+            Msg::ConfirmNeedsTerminal => {
+                "--confirm needs a terminal, reading the master password once from standard input"
+            }
+            Msg::ShowTimeoutNotTerminal => {
+                "Standard output is not a terminal, ignoring --show-timeout"
+            }
+            Msg::NoUsername => "No username configured for {}, typing the password and Enter",
+            Msg::NoUsernameShown => "No username configured for {}, printing an empty one",
+            Msg::NoUsernameCopied => "No username configured for {}, copying the password only",
+            Msg::UserCopiedPressEnter => {
+                "Copied the username of {}; press Enter to copy the password"
+            }
+            Msg::UserCopiedWaiting => "Copied the username of {}; the password follows in {}s",
+            Msg::PasswordCopied => "Copied the password of {}; the clipboard is restored in {}s",
+            Msg::TypingIn => "Typing in {}, focus the window to type into",
+            Msg::UnlockedFor => "Unlocked for {}",
+            Msg::UnlockedLeft => "Unlocked, {} left",
+            Msg::Locked => "Locked",
+            Msg::KeyringStored => "Stored the master password as {}",
+            Msg::KeyringNotStored => "No master password stored as {}",
+            Msg::KeyringHasMaster => "Master password stored in the keyring as {}",
+            Msg::KeyringNoMaster => "Master password not stored in the keyring as {}",
+            Msg::KeyringEmpty => "No master password in the keyring, see fp keyring set",
+            Msg::KeyringError => "Keyring error: {}",
+            Msg::Entropy => "{}: about {} bits, {} ({} characters from {} possible)",
+            Msg::Weak => "weak",
+            Msg::Fair => "fair",
+            Msg::Good => "good",
+            Msg::Strong => "strong",
+            Msg::NoMaster => "No master password on standard input",
+            Msg::MasterMismatch => "Master passwords do not match",
+            Msg::PassphrasePrompt => "Passphrase for {}: ",
+            Msg::PassphraseConfirm => "Repeat the passphrase: ",
+            Msg::PassphraseNeedsTerminal => {
+                "The passphrase of an encrypted configuration can only be typed on a terminal"
+            }
+            Msg::PassphraseMismatch => "Passphrases do not match",
+            Msg::ConfigEncrypted => "Encrypted {}",
+            Msg::ConfigDecrypted => "Decrypted {}",
+            Msg::ConfigNotEncrypted => "{} is not encrypted",
+            Msg::ConfigEncryptedUnchecked => "{} is encrypted, so it was not checked",
+            Msg::ConfigEncryptedHint => "fp config lint asks for its passphrase and checks it",
+            Msg::EmptyMaster => "No master password in {}",
+            Msg::FileDescriptor => "file descriptor {}",
+            Msg::InsecurePermissions => {
+                "{} is readable by other users (mode {}); chmod 600 it or pass --insecure-perms"
+            }
+            Msg::InsecureCache => {
+                "{} is accessible by other users (mode {}); remove it and run fp unlock again"
+            }
+            Msg::NoRuntimeDir => {
+                "fp unlock needs $XDG_RUNTIME_DIR, a private directory removed at logout, to cache the master password in"
+            }
+            Msg::NoKernelKeyring => {
+                "fp unlock, fp lock and fp status need the Linux kernel keyring to keep the key of their cache out of files, and are not supported on this system"
+            }
+            Msg::BadFd => "File descriptor {} is not open for reading the master password",
+            Msg::PinentryDescription => "Enter the master password for fp",
+            Msg::NoPinentry => "No pinentry program found for --pinentry={}",
+            Msg::QrEncode => "Cannot encode the QR code: {}",
+            Msg::NoTerminal => "{} needs a terminal",
+            Msg::Interrupted => "Interrupted",
+            Msg::KeysIncomplete => "{} of {} keys failed",
+            Msg::BatchStats => "{} keys in {}s, {} keys/s",
+            Msg::NoStateDir => "No home directory to keep the history in",
+            Msg::NoHistory => "No history at {}",
+            Msg::HistoryInvalid => "{}:{}: not a history record, skipped",
+            Msg::DoctorIncomplete => "{} of {} checks failed",
+            Msg::SelfTestPassed => "{} known-answer vectors passed",
+            Msg::SelfTestFailed => "{} of {} known-answer vectors failed",
+            Msg::LintFindings => "Problems in the configuration: errors: {}, warnings: {}",
+            Msg::AuditReused => "Same password: {}",
+            Msg::AuditClean => "No two of the {} sites share a password",
+            Msg::AuditFailed => {
+                "{} group(s) of sites share a password; give them other lengths or keys"
+            }
+            Msg::ImportCreated => "Created {}: key {}",
+            Msg::ImportCreatedUser => "Created {}: key {}, username {}",
+            Msg::ImportSkipped => "Skipped line {}: {}",
+            Msg::ImportSummary => "Sites created: {}, rows skipped: {}",
+            Msg::ImportDuplicates => {
+                "--allow-duplicates imports the other accounts of a domain as NAME-2 and so on"
+            }
+            Msg::ExportPlaintext => {
+                "{} will hold every configured password in plaintext; delete it once imported"
+            }
+            Msg::ExportConfirm => "Write the passwords of {} sites to {}? [y/N]",
+            Msg::ExportCancelled => "Nothing exported",
+            Msg::Exported => "Exported {} sites to {}",
+            Msg::NativeHostLocked => {
+                "No master password for the browser: run fp agent start or fp unlock first"
+            }
+            Msg::Fido2Pin => "FIDO2 PIN: ",
+            Msg::Fido2Touch => "Touch your security key",
+            Msg::Fido2Saved => "Enrolled the FIDO2 credential {} in {}",
+            Msg::Fido2Enrolled => {
+                "A FIDO2 credential is enrolled already; --force replaces it, \
+                 and every password derived from it"
+            }
+            Msg::Fido2Output => "{} wrote something other than expected",
+            Msg::NoFido2Credential => "No FIDO2 credential enrolled, see fp fido2 enroll",
+            Msg::YubiKeyTouch => "Touch your YubiKey",
+            Msg::UnknownMasterSource => {
+                "Invalid master source, expected fido2[:LABEL], yubikey[:SLOT] \
+                 or ssh-agent[:FINGERPRINT]"
+            }
+            Msg::ManifestInstalled => "Installed the native messaging host manifest {}",
+            Msg::UriHandlerLocked => {
+                "No master password for the link: run fp agent start or fp unlock first"
+            }
+            Msg::UriHandlerRegistered => "Registered the flowerpassword:// handler {}",
+            Msg::LengthFound => "{}: length {} is the shortest meeting the policy",
+            Msg::Pwned => "The password of {} appears {} times in Pwned Passwords",
+            Msg::PwnedUnchecked => "Could not check {} against Pwned Passwords: {}",
+            Msg::Blocklisted => "The password of {} is on the blocklist of common passwords",
+            Msg::MasterBlocklisted => "The master password is on the blocklist of common passwords",
+            Msg::BlocklistUnreadable => "Cannot read the blocklist {}: {}",
+            Msg::PolicyUnmet => "The password of {} does not meet {}; see --find-length",
+            Msg::LintClean => "{} configuration file(s), no problems",
+            Msg::MigrateCurrent => "{} is already at version {}",
+            Msg::MigrateDryRun => "{} would be migrated from version {} to {}",
+            Msg::Migrated => "Migrated {} from version {} to {}, the original is in {}",
+            Msg::NoConfigDir => "No home directory to keep the configuration in, see --config",
+            Msg::NoHomeDir => "No home directory to install the manifest in",
+            Msg::NoFingerprint => "No fingerprint saved, see fp fingerprint --save",
+            Msg::FingerprintMismatch => "The master password does not match the saved fingerprint",
+            Msg::FingerprintWarning => {
+                "The master password does not match the saved fingerprint; \
+                 every password of this run would be wrong"
+            }
+            Msg::FingerprintContinue => "Continue with this master password anyway? [y/N]",
+            Msg::FingerprintMatches => "The master password matches the saved fingerprint",
+            Msg::FingerprintSaved => "Saved the fingerprint in {}",
+            Msg::SiteAdded => "Added site {}: key {}, length {}, scheme {}",
+            Msg::SiteReplaced => "Replaced site {}: key {}, length {}, scheme {}",
+            Msg::SiteRemoveConfirm => "Remove the site {} from the configuration? [y/N]",
+            Msg::SiteRemoved => "Removed site {}: key {}, length {}, scheme {}",
+            Msg::SiteKept => "Kept site {}",
+            Msg::SiteRenamed => "Renamed site {} to {}",
+            Msg::SiteRenameReplaced => "Replaced the site that was configured as {}",
+            Msg::SiteRenameAliases => "Aliases now pointing to {}: {}",
+            Msg::RenameKeyKept => {
+                "Only the name changed: {} still derives with the key {}, so its password is the same"
+            }
+            Msg::RenameKeyConfirm => {
+                "Renaming {} to {} with --also-key changes its key, and so its password. Continue? [y/N]"
+            }
+            Msg::RenameKeyChanged => {
+                "{} now derives with its new name as the key: its password has changed"
+            }
+            Msg::AliasesDangling => "Aliases still pointing to {} now lead nowhere: {}",
+            Msg::AliasSet => "Alias {} now points to {}",
+            Msg::AliasReplaced => "Alias {} now points to {} instead of {}",
+            Msg::AliasRemoved => "Removed alias {}, which pointed to {}",
+            Msg::AliasShadowed => "The site {} comes first, so its alias of the same name is not used",
+            Msg::UnknownAlias => "No alias {} in {}",
+            Msg::NoteSet => "Set the notes of {}",
+            Msg::NoteAppended => "Added a line to the notes of {}",
+            Msg::NoMatch => "No site matches {}",
+            Msg::NoPick => "Not the number of a listed site: {}",
+            Msg::FindTie => "Several sites match {} about as well:",
+            Msg::FindPick => "Number of the site to derive [1-{}]:",
+            Msg::NotLoopback => {
+                "{} is not a loopback address; other machines could reach it, \
+                 see --i-know-what-im-doing"
+            }
+            Msg::HttpListening => "Listening on http://{}",
+            Msg::GrpcListening => "Listening for gRPC on {}",
+            Msg::DbusServing => "Serving {} on the session bus",
+            Msg::ServeToken => "Token: {}",
+            Msg::Hint => "hint",
+            Msg::ConfigLoaded => "{} site(s), no problems",
+            Msg::ConfigWarnings => "{} warnings, the first: {}",
+            Msg::ConfigHint => "Fix the file and line named in the detail",
+            Msg::ClipboardFound => "A clipboard program applies to this session",
+            Msg::ClipboardMissing => "No clipboard program for this session, --copy will fail",
+            Msg::ClipboardHint => "Install wl-clipboard on Wayland, or xclip or xsel on X11",
+            Msg::KeyringHint => "Start and unlock the OS keyring; only --use-keyring needs it",
+            Msg::AgentNoEndpoint => "No agent socket: $XDG_RUNTIME_DIR is not set",
+            Msg::AgentReachable => "An agent answers at {}",
+            Msg::AgentUnreachable => "No agent answers at {}: {}",
+            Msg::AgentHint => "Run fp agent start; only --agent needs it",
+            Msg::AgentStarted => "The agent answers at {}",
+            Msg::AgentStopped => "Stopped the agent at {}",
+            Msg::AgentNotRunning => "No agent answers at {}",
+            Msg::AgentLocked => "The agent at {} is locked",
+            Msg::AgentRefused => "The agent refused: {}",
+            Msg::MemlockOk => "Master passwords are kept in memory locked out of swap",
+            Msg::MemlockRefused => "Master passwords may be swapped to disk, {}",
+            Msg::MemlockHint => {
+                "Raise the locked memory limit with ulimit -l, or LimitMEMLOCK= for a service"
+            }
+            Msg::HardeningOk => "Core dumps of fp are prevented ({})",
+            Msg::HardeningMissing => "Core dumps of fp may hold the master password ({})",
+            Msg::HardeningHint => "Run fp with ulimit -c 0, or turn crash dumps off for it",
+            Msg::Osc52Ok => "{} should take OSC 52 clipboard sequences",
+            Msg::Osc52NoTerminal => "Standard output is not a terminal",
+            Msg::Osc52Dumb => "TERM={} takes no OSC 52 clipboard sequences",
+            Msg::Osc52Hint => "Run fp doctor in the terminal that shows the passwords",
+            Msg::Osc52Tmux => "tmux passes OSC 52 on only with set-clipboard",
+            Msg::Osc52TmuxHint => "Add \"set -g set-clipboard on\" to ~/.tmux.conf",
+            Msg::NotInstalled => "{} is not installed or not on PATH",
+            Msg::GpgFailed => "gpg could not decrypt the master password: {}",
+            Msg::GpgIo => "Cannot run gpg: {}",
+            Msg::MenuFailed => "rofi failed: {}",
+            Msg::MenuIo => "Cannot run rofi: {}",
+            Msg::PassFailed => "pass failed: {}",
+            Msg::PassIo => "Cannot run pass: {}",
+            Msg::PassOutsidePrefix => "The entry for {} would not be under {}/",
+            Msg::PassIncomplete => "{} of {} pass entries failed",
+            Msg::NoDisplay => {
+                "No display to type into: neither $WAYLAND_DISPLAY nor $DISPLAY is set"
+            }
+            Msg::CannotType => "{} cannot type {}",
+            Msg::TypingFailed => "{} could not type the password: {}",
+            Msg::TypingIo => "Cannot run the typing program: {}",
+            Msg::ReplHelp => {
+                "\
+KEY [LENGTH]        derive the password for a site name or key
+:length N|default   set or reset the length
+:copy on|off        copy passwords instead of printing them
+:lock               forget the master password until the next key
+:quit               exit"
+            }
+            Msg::ReplCopied => "Copied the password for {} for {} seconds",
+            Msg::ReplLocked => "Locked: the master password is wiped",
+            Msg::ReplAlreadyLocked => "Already locked",
+            Msg::UnknownCommand => "Unknown command :{}, see :help",
+            Msg::Usage => "Usage: {}",
+            Msg::BadLength => "Invalid length {}, expected 2 to 32",
+            Msg::TooManyWords => "Expected a key and at most a length",
+            Msg::MaskKeys => "r reveal, c copy, q quit",
+            Msg::MaskKeysNoCopy => "r reveal, q quit",
+            Msg::MaskCopied => "copied",
+            Msg::MaskLength => "{} ({} chars)",
+            Msg::TuiReady => "Enter copies the password, Esc quits",
+            Msg::TuiCopied => "Copied the password for {}, clipboard restored in {}s",
+            Msg::TuiRestored => "Clipboard restored",
+        }
+    }
+}
+
+/// The Simplified Chinese catalog
+const ZH_CN: &[(Msg, &str)] = &[
+    (Msg::MasterPrompt, "主密码："),
+    (Msg::ConfirmPrompt, "再次输入主密码："),
+    (Msg::Warning, "警告"),
+    (Msg::Error, "错误"),
+    (
+        Msg::ConfirmNeedsTerminal,
+        "--confirm 需要终端，将从标准输入读取一次主密码",
+    ),
+    (
+        Msg::ShowTimeoutNotTerminal,
+        "标准输出不是终端，忽略 --show-timeout",
+    ),
+    (Msg::NoUsername, "{} 未配置用户名，只输入密码并回车"),
+    (Msg::NoUsernameShown, "{} 未配置用户名，输出空用户名"),
+    (Msg::NoUsernameCopied, "{} 未配置用户名，只复制密码"),
+    (
+        Msg::UserCopiedPressEnter,
+        "已复制 {} 的用户名；按回车键复制密码",
+    ),
+    (
+        Msg::UserCopiedWaiting,
+        "已复制 {} 的用户名；{} 秒后复制密码",
+    ),
+    (
+        Msg::PasswordCopied,
+        "已复制 {} 的密码；剪贴板将在 {} 秒后恢复",
+    ),
+    (Msg::TypingIn, "{} 后开始输入，请切换到要输入的窗口"),
+    (Msg::UnlockedFor, "已解锁 {}"),
+    (Msg::UnlockedLeft, "已解锁，剩余 {}"),
+    (Msg::Locked, "已锁定"),
+    (Msg::KeyringStored, "主密码已存储为 {}"),
+    (Msg::KeyringNotStored, "没有存储为 {} 的主密码"),
+    (Msg::KeyringHasMaster, "钥匙串中存有主密码，条目为 {}"),
+    (Msg::KeyringNoMaster, "钥匙串中没有主密码，条目为 {}"),
+    (
+        Msg::KeyringEmpty,
+        "钥匙串中没有主密码，请参阅 fp keyring set",
+    ),
+    (Msg::KeyringError, "钥匙串错误：{}"),
+    (Msg::Entropy, "{}：约 {} 位，{}（{} 个字符，共 {} 种可能）"),
+    (Msg::Weak, "弱"),
+    (Msg::Fair, "一般"),
+    (Msg::Good, "良好"),
+    (Msg::Strong, "强"),
+    (Msg::NoMaster, "标准输入中没有主密码"),
+    (Msg::MasterMismatch, "两次输入的主密码不一致"),
+    (Msg::PassphrasePrompt, "{} 的口令："),
+    (Msg::PassphraseConfirm, "再次输入口令："),
+    (
+        Msg::PassphraseNeedsTerminal,
+        "加密配置的口令只能在终端中输入",
+    ),
+    (Msg::PassphraseMismatch, "两次输入的口令不一致"),
+    (Msg::ConfigEncrypted, "已加密 {}"),
+    (Msg::ConfigDecrypted, "已解密 {}"),
+    (Msg::ConfigNotEncrypted, "{} 未加密"),
+    (Msg::ConfigEncryptedUnchecked, "{} 已加密，因此未检查"),
+    (
+        Msg::ConfigEncryptedHint,
+        "fp config lint 会询问其口令并进行检查",
+    ),
+    (Msg::EmptyMaster, "{} 中没有主密码"),
+    (Msg::FileDescriptor, "文件描述符 {}"),
+    (
+        Msg::InsecurePermissions,
+        "其他用户可以读取 {}（权限 {}）；请对其执行 chmod 600，或使用 --insecure-perms",
+    ),
+    (
+        Msg::InsecureCache,
+        "其他用户可以访问 {}（权限 {}）；请删除它并重新运行 fp unlock",
+    ),
+    (
+        Msg::NoRuntimeDir,
+        "fp unlock 需要 $XDG_RUNTIME_DIR（注销时删除的私有目录）来缓存主密码",
+    ),
+    (
+        Msg::NoKernelKeyring,
+        "fp unlock、fp lock 和 fp status 需要 Linux 内核密钥环，以免缓存的密钥写入文件，因此不支持本系统",
+    ),
+    (Msg::BadFd, "文件描述符 {} 未打开，无法读取主密码"),
+    (Msg::PinentryDescription, "输入 fp 的主密码"),
+    (Msg::NoPinentry, "找不到 --pinentry={} 的 pinentry 程序"),
+    (Msg::QrEncode, "无法生成二维码：{}"),
+    (Msg::NoTerminal, "{} 需要终端"),
+    (Msg::Interrupted, "已中断"),
+    (Msg::KeysIncomplete, "{} 个密钥失败，共 {} 个"),
+    (Msg::BatchStats, "{} 个密钥，用时 {} 秒，每秒 {} 个"),
+    (Msg::NoStateDir, "没有可保存历史记录的主目录"),
+    (Msg::NoHistory, "{} 处没有历史记录"),
+    (Msg::HistoryInvalid, "{}:{}：不是历史记录，已跳过"),
+    (Msg::DoctorIncomplete, "{} 项检查失败，共 {} 项"),
+    (Msg::SelfTestPassed, "{} 个已知答案向量全部通过"),
+    (Msg::SelfTestFailed, "{} 个已知答案向量失败，共 {} 个"),
+    (Msg::LintFindings, "配置中有 {} 个错误和 {} 个警告"),
+    (Msg::AuditReused, "密码相同：{}"),
+    (Msg::AuditClean, "{} 个站点的密码各不相同"),
+    (
+        Msg::AuditFailed,
+        "有 {} 组站点的密码相同；请为它们设置不同的长度或密钥",
+    ),
+    (Msg::ImportCreated, "已创建 {}：密钥 {}"),
+    (Msg::ImportCreatedUser, "已创建 {}：密钥 {}，用户名 {}"),
+    (Msg::ImportSkipped, "已跳过第 {} 行：{}"),
+    (Msg::ImportSummary, "已创建 {} 个站点，跳过 {} 行"),
+    (
+        Msg::ImportDuplicates,
+        "--allow-duplicates 会将同一域名的其他账号导入为 NAME-2 等",
+    ),
+    (
+        Msg::ExportPlaintext,
+        "{} 将以明文保存所有已配置的密码；导入后请删除",
+    ),
+    (Msg::ExportConfirm, "要将 {} 个站点的密码写入 {} 吗？[y/N]"),
+    (Msg::ExportCancelled, "未导出任何内容"),
+    (Msg::Exported, "已将 {} 个站点导出到 {}"),
+    (
+        Msg::NativeHostLocked,
+        "浏览器没有可用的主密码：请先运行 fp agent start 或 fp unlock",
+    ),
+    (Msg::Fido2Pin, "FIDO2 PIN："),
+    (Msg::Fido2Touch, "请触摸安全密钥"),
+    (Msg::Fido2Saved, "已登记 FIDO2 凭据 {}，保存在 {}"),
+    (
+        Msg::Fido2Enrolled,
+        "已登记 FIDO2 凭据；--force 会替换它，以及由它派生的所有密码",
+    ),
+    (Msg::Fido2Output, "{} 的输出不符合预期"),
+    (
+        Msg::NoFido2Credential,
+        "没有登记 FIDO2 凭据，请参阅 fp fido2 enroll",
+    ),
+    (Msg::YubiKeyTouch, "请触摸 YubiKey"),
+    (
+        Msg::UnknownMasterSource,
+        "无效的主密码来源，应为 fido2[:LABEL]、yubikey[:SLOT] 或 ssh-agent[:FINGERPRINT]",
+    ),
+    (Msg::ManifestInstalled, "已安装本地消息主机清单 {}"),
+    (
+        Msg::UriHandlerLocked,
+        "链接没有可用的主密码：请先运行 fp agent start 或 fp unlock",
+    ),
+    (
+        Msg::UriHandlerRegistered,
+        "已注册 flowerpassword:// 处理程序 {}",
+    ),
+    (Msg::LengthFound, "{}：满足策略的最短长度为 {}"),
+    (Msg::Pwned, "{} 的密码在 Pwned Passwords 中出现了 {} 次"),
+    (Msg::PwnedUnchecked, "无法在 Pwned Passwords 中检查 {}：{}"),
+    (Msg::Blocklisted, "{} 的密码在常用密码黑名单中"),
+    (Msg::MasterBlocklisted, "主密码在常用密码黑名单中"),
+    (Msg::BlocklistUnreadable, "无法读取黑名单 {}：{}"),
+    (Msg::PolicyUnmet, "{} 的密码不满足 {}；请参阅 --find-length"),
+    (Msg::LintClean, "{} 个配置文件，没有问题"),
+    (Msg::MigrateCurrent, "{} 已是版本 {}"),
+    (Msg::MigrateDryRun, "{} 将从版本 {} 迁移到 {}"),
+    (
+        Msg::Migrated,
+        "已将 {} 从版本 {} 迁移到 {}，原文件保存在 {}",
+    ),
+    (Msg::NoConfigDir, "没有可保存配置的主目录，请参阅 --config"),
+    (Msg::NoHomeDir, "没有可安装清单的主目录"),
+    (
+        Msg::NoFingerprint,
+        "没有保存的指纹，请参阅 fp fingerprint --save",
+    ),
+    (Msg::FingerprintMismatch, "主密码与保存的指纹不符"),
+    (
+        Msg::FingerprintWarning,
+        "主密码与保存的指纹不符；本次生成的所有密码都将是错误的",
+    ),
+    (Msg::FingerprintContinue, "仍要使用此主密码继续吗？[y/N]"),
+    (Msg::FingerprintMatches, "主密码与保存的指纹相符"),
+    (Msg::FingerprintSaved, "已将指纹保存到 {}"),
+    (Msg::SiteAdded, "已添加站点 {}：密钥 {}，长度 {}，方案 {}"),
+    (
+        Msg::SiteReplaced,
+        "已替换站点 {}：密钥 {}，长度 {}，方案 {}",
+    ),
+    (Msg::SiteRemoveConfirm, "要从配置中删除站点 {} 吗？[y/N]"),
+    (Msg::SiteRemoved, "已删除站点 {}：密钥 {}，长度 {}，方案 {}"),
+    (Msg::SiteKept, "已保留站点 {}"),
+    (Msg::SiteRenamed, "已将站点 {} 重命名为 {}"),
+    (Msg::SiteRenameReplaced, "已替换原先配置为 {} 的站点"),
+    (Msg::SiteRenameAliases, "现在指向 {} 的别名：{}"),
+    (
+        Msg::RenameKeyKept,
+        "只有名称改变：{} 仍使用密钥 {} 生成，因此密码不变",
+    ),
+    (
+        Msg::RenameKeyConfirm,
+        "使用 --also-key 将 {} 重命名为 {} 会改变其密钥，从而改变其密码。继续吗？[y/N]",
+    ),
+    (
+        Msg::RenameKeyChanged,
+        "{} 现在使用新名称作为密钥：其密码已改变",
+    ),
+    (Msg::AliasesDangling, "仍指向 {} 的别名现在无处可指：{}"),
+    (Msg::AliasSet, "别名 {} 现在指向 {}"),
+    (Msg::AliasReplaced, "别名 {} 现在指向 {}，而不是 {}"),
+    (Msg::AliasRemoved, "已删除别名 {}，它原先指向 {}"),
+    (Msg::AliasShadowed, "站点 {} 优先，因此同名的别名不会被使用"),
+    (Msg::UnknownAlias, "没有别名 {}（{}）"),
+    (Msg::NoteSet, "已设置 {} 的备注"),
+    (Msg::NoteAppended, "已在 {} 的备注中添加一行"),
+    (Msg::NoMatch, "没有站点匹配 {}"),
+    (Msg::NoPick, "不是所列站点的编号：{}"),
+    (Msg::FindTie, "有几个站点与 {} 的匹配程度相近："),
+    (Msg::FindPick, "要生成密码的站点编号 [1-{}]："),
+    (
+        Msg::NotLoopback,
+        "{} 不是环回地址，其他机器可能访问到它，请参阅 --i-know-what-im-doing",
+    ),
+    (Msg::HttpListening, "正在监听 http://{}"),
+    (Msg::GrpcListening, "正在监听 gRPC：{}"),
+    (Msg::DbusServing, "正在会话总线上提供 {}"),
+    (Msg::ServeToken, "令牌：{}"),
+    (Msg::Hint, "提示"),
+    (Msg::ConfigLoaded, "{} 个站点，没有问题"),
+    (Msg::ConfigWarnings, "{} 条警告，第一条：{}"),
+    (Msg::ConfigHint, "请修正详情中所指的文件和行"),
+    (Msg::ClipboardFound, "当前会话有可用的剪贴板程序"),
+    (
+        Msg::ClipboardMissing,
+        "当前会话没有剪贴板程序，--copy 将会失败",
+    ),
+    (
+        Msg::ClipboardHint,
+        "Wayland 下请安装 wl-clipboard，X11 下请安装 xclip 或 xsel",
+    ),
+    (
+        Msg::KeyringHint,
+        "请启动并解锁系统密钥环；只有 --use-keyring 需要它",
+    ),
+    (
+        Msg::AgentNoEndpoint,
+        "没有代理套接字：未设置 $XDG_RUNTIME_DIR",
+    ),
+    (Msg::AgentReachable, "{} 处的代理有响应"),
+    (Msg::AgentUnreachable, "{} 处没有代理响应：{}"),
+    (Msg::AgentHint, "请运行 fp agent start；只有 --agent 需要它"),
+    (Msg::AgentStarted, "代理在 {} 处响应"),
+    (Msg::AgentStopped, "已停止 {} 处的代理"),
+    (Msg::AgentNotRunning, "{} 处没有代理响应"),
+    (Msg::AgentLocked, "{} 处的代理已锁定"),
+    (Msg::AgentRefused, "代理拒绝了请求：{}"),
+    (Msg::MemlockOk, "主密码保存在锁定的内存中，不会换出到交换区"),
+    (Msg::MemlockRefused, "主密码可能被换出到磁盘，{}"),
+    (
+        Msg::MemlockHint,
+        "请用 ulimit -l 提高锁定内存上限，服务则设置 LimitMEMLOCK=",
+    ),
+    (Msg::HardeningOk, "已阻止 fp 生成核心转储（{}）"),
+    (Msg::HardeningMissing, "fp 的核心转储可能包含主密码（{}）"),
+    (
+        Msg::HardeningHint,
+        "请用 ulimit -c 0 运行 fp，或为它关闭崩溃转储",
+    ),
+    (Msg::Osc52Ok, "{} 应当支持 OSC 52 剪贴板序列"),
+    (Msg::Osc52NoTerminal, "标准输出不是终端"),
+    (Msg::Osc52Dumb, "TERM={} 不支持 OSC 52 剪贴板序列"),
+    (Msg::Osc52Hint, "请在显示密码的终端中运行 fp doctor"),
+    (
+        Msg::Osc52Tmux,
+        "tmux 只有启用 set-clipboard 才会转发 OSC 52",
+    ),
+    (
+        Msg::Osc52TmuxHint,
+        "在 ~/.tmux.conf 中加入 \"set -g set-clipboard on\"",
+    ),
+    (Msg::NotInstalled, "未安装 {}，或它不在 PATH 中"),
+    (Msg::GpgFailed, "gpg 无法解密主密码：{}"),
+    (Msg::GpgIo, "无法运行 gpg：{}"),
+    (Msg::MenuFailed, "rofi 运行失败：{}"),
+    (Msg::MenuIo, "无法运行 rofi：{}"),
+    (Msg::PassFailed, "pass 失败：{}"),
+    (Msg::PassIo, "无法运行 pass：{}"),
+    (Msg::PassOutsidePrefix, "{} 的条目将不在 {}/ 之下"),
+    (Msg::PassIncomplete, "{} 个 pass 条目失败，共 {} 个"),
+    (
+        Msg::NoDisplay,
+        "没有可供输入的显示：$WAYLAND_DISPLAY 和 $DISPLAY 均未设置",
+    ),
+    (Msg::CannotType, "{} 无法输入 {}"),
+    (Msg::TypingFailed, "{} 无法输入密码：{}"),
+    (Msg::TypingIo, "无法运行输入程序：{}"),
+    (
+        Msg::ReplHelp,
+        "\
+密钥 [长度]         为站点名称或密钥生成密码
+:length N|default   设置或重置长度
+:copy on|off        复制密码而不是打印
+:lock               清除主密码，直到输入下一个密钥
+:quit               退出",
+    ),
+    (Msg::ReplCopied, "已复制 {} 的密码，保留 {} 秒"),
+    (Msg::ReplLocked, "已锁定：主密码已清除"),
+    (Msg::ReplAlreadyLocked, "已经锁定"),
+    (Msg::UnknownCommand, "未知命令 :{}，请参阅 :help"),
+    (Msg::Usage, "用法：{}"),
+    (Msg::BadLength, "无效的长度 {}，应为 2 到 32"),
+    (Msg::TooManyWords, "应为一个密钥，最多再加一个长度"),
+    (Msg::MaskKeys, "r 显示，c 复制，q 退出"),
+    (Msg::MaskKeysNoCopy, "r 显示，q 退出"),
+    (Msg::MaskCopied, "已复制"),
+    (Msg::MaskLength, "{}（{} 个字符）"),
+    (Msg::TuiReady, "回车复制密码，Esc 退出"),
+    (Msg::TuiCopied, "已复制 {} 的密码，{} 秒后恢复剪贴板"),
+    (Msg::TuiRestored, "剪贴板已恢复"),
+];
+
+/// Returns `msg` from `catalog`, or in English if the catalog lacks it
+fn translate(catalog: &[(Msg, &'static str)], msg: Msg) -> &'static str {
+    catalog
+        .iter()
+        .find(|(translated, _)| *translated == msg)
+        .map_or_else(|| msg.english(), |(_, text)| text)
+}
+
+/// Replaces each `{}` of `template` with the next of `args`
+fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut parts = template.split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    let mut args = args.iter();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            // Writing to a String cannot fail
+            let _ = write!(text, "{}", arg);
+        }
+        text.push_str(part);
+    }
+    text
+}
+
+/// Writes `msg` filled with `args` to `f`, for `Display` implementations
+pub(crate) fn write(f: &mut fmt::Formatter, msg: Msg, args: &[&dyn fmt::Display]) -> fmt::Result {
+    f.write_str(&msg.fill(args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_zh_cn_prompts() {
+        assert_eq!(Msg::MasterPrompt.text_in(Lang::ZhCn), "主密码：");
+        assert_eq!(Msg::ConfirmPrompt.text_in(Lang::ZhCn), "再次输入主密码：");
+        assert_eq!(Msg::MasterPrompt.text_in(Lang::En), "Master password: ");
+    }
+
+    #[test]
+    fn test_untranslated_falls_back_to_english() {
+        let partial = [(Msg::MasterPrompt, "主密码：")];
+        assert_eq!(translate(&partial, Msg::MasterPrompt), "主密码：");
+        assert_eq!(
+            translate(&partial, Msg::MasterMismatch),
+            "Master passwords do not match"
+        );
+        assert_eq!(translate(&[], Msg::Locked), "Locked");
+    }
+
+    #[test]
+    fn test_translations_keep_the_placeholders() {
+        for (msg, text) in ZH_CN {
+            assert_eq!(
+                text.matches("{}").count(),
+                msg.english().matches("{}").count(),
+                "{:?}",
+                msg
+            );
+        }
+        let mut msgs: Vec<String> = ZH_CN.iter().map(|(msg, _)| format!("{:?}", msg)).collect();
+        msgs.sort();
+        msgs.dedup();
+        assert_eq!(msgs.len(), ZH_CN.len(), "a message is translated twice");
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            fill(Msg::PassIncomplete.text_in(Lang::ZhCn), &[&1, &3]),
+            "1 个 pass 条目失败，共 3 个"
+        );
+        assert_eq!(
+            fill(Msg::UnlockedLeft.english(), &[&"14m 05s"]),
+            "Unlocked, 14m 05s left"
+        );
+        // Missing arguments leave their place empty
+        assert_eq!(fill("{} and {}", &[&"this"]), "this and ");
+        assert_eq!(fill("no arguments", &[&"extra"]), "no arguments");
+    }
+
+    #[test]
+    fn test_lang_from_locale() {
+        assert_eq!(Lang::from_locale(locale(&[])), Lang::En);
+        assert_eq!(
+            Lang::from_locale(locale(&[("LANG", "zh_CN.UTF-8")])),
+            Lang::ZhCn
+        );
+        assert_eq!(
+            Lang::from_locale(locale(&[("LANG", "zh_CN.UTF-8"), ("LC_ALL", "C")])),
+            Lang::En
+        );
+        assert_eq!(
+            Lang::from_locale(locale(&[("LC_MESSAGES", "zh_SG"), ("LANG", "en_US")])),
+            Lang::ZhCn
+        );
+        assert_eq!(
+            Lang::from_locale(locale(&[("LC_ALL", ""), ("LANG", "zh-Hans-CN")])),
+            Lang::ZhCn
+        );
+        assert_eq!(
+            Lang::from_locale(locale(&[("LANG", "zh_TW.UTF-8")])),
+            Lang::En
+        );
+        assert_eq!(Lang::from_locale(locale(&[("LANG", "zh_HK")])), Lang::En);
+        assert_eq!(
+            Lang::from_locale(locale(&[("LANG", "zh-Hant-TW")])),
+            Lang::En
+        );
+        assert_eq!(Lang::from_locale(locale(&[("LANG", "fr_FR")])), Lang::En);
+    }
+}
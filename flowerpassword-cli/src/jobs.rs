@@ -0,0 +1,400 @@
+//! `--jobs`, deriving a `--batch` on several threads
+//!
+//! The keys stream through three stages, so memory stays flat however long the
+//! input is:
+//!
+//! - A reader thread reads the keys one by one.
+//! - N workers take the next key as soon as they are free, so that no thread
+//!   idles while another has a long run of slow keys.
+//! - The calling thread puts the results back in input order and writes them.
+//!
+//! At most [`WINDOW_PER_WORKER`] keys per worker are between being read and
+//! being written. Once that many are in flight, the reader waits for the writer,
+//! whatever the workers still have to do or the writer is waiting for.
+//!
+//! A key that fails is reported when its turn to be written comes, and the
+//! others still derive.
+//!
+//! The progress bar goes to standard error, and only on a terminal without
+//! `--quiet`, so standard output carries the records and nothing else.
+//!
+//! WASI preview 1 has no threads, so there every key is derived in turn on the
+//! main thread, without a progress bar.
+
+#[cfg(not(target_os = "wasi"))]
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// Whether this target can spawn threads, which WASI preview 1 cannot
+const HAS_THREADS: bool = cfg!(not(target_os = "wasi"));
+
+/// Keys in flight per worker, between being read and being written
+pub(crate) const WINDOW_PER_WORKER: usize = 64;
+
+/// What reaches the writer: the index of an item, and the item with its result
+/// or the error reading it
+type Done<T, R, E> = (usize, Result<(T, R), E>);
+
+/// Returns how many threads to derive keys on for `--jobs`
+fn workers(jobs: usize, has_threads: bool) -> usize {
+    match has_threads {
+        true => jobs.max(1),
+        false => 1,
+    }
+}
+
+/// Free places for keys in flight, which the reader takes and the writer gives
+/// back
+struct Window {
+    /// Free places, and whether the writer has stopped
+    state: Mutex<(usize, bool)>,
+    changed: Condvar,
+}
+
+impl Window {
+    fn new(size: usize) -> Self {
+        Window {
+            state: Mutex::new((size, false)),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, (usize, bool)> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits for a free place and takes it, or returns `false` once the writer
+    /// has stopped
+    fn acquire(&self) -> bool {
+        let mut state = self.lock();
+        while state.0 == 0 && !state.1 {
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        if state.1 {
+            return false;
+        }
+        state.0 -= 1;
+        true
+    }
+
+    /// Gives a place back after a key was written
+    fn release(&self) {
+        self.lock().0 += 1;
+        self.changed.notify_one();
+    }
+
+    /// Stops the reader waiting for places
+    fn close(&self) {
+        self.lock().1 = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Applies `work` to every item `read` returns until it returns `None`, on
+/// `jobs` threads, and hands each item and its result to `write` in the order
+/// they were read
+///
+/// Returns how many items were written. Stops at the first error of `read` or
+/// `write`, after writing every item read before it.
+pub(crate) fn stream_ordered<T, R, E>(
+    mut read: impl FnMut() -> Result<Option<T>, E> + Send,
+    jobs: usize,
+    work: impl Fn(&T) -> R + Sync,
+    mut write: impl FnMut(T, R) -> Result<(), E>,
+) -> Result<usize, E>
+where
+    T: Send,
+    R: Send,
+    E: Send,
+{
+    let workers = workers(jobs, HAS_THREADS);
+    if workers == 1 {
+        let mut written = 0;
+        while let Some(item) = read()? {
+            let result = work(&item);
+            write(item, result)?;
+            written += 1;
+        }
+        return Ok(written);
+    }
+
+    let window = Window::new(workers * WINDOW_PER_WORKER);
+    // The window keeps the channel from ever filling, so the reader never
+    // blocks on it
+    let (items, queue) = mpsc::sync_channel::<(usize, T)>(workers * WINDOW_PER_WORKER);
+    let queue = Mutex::new(queue);
+    let (results, done) = mpsc::channel::<Done<T, R, E>>();
+    let (window, queue, work) = (&window, &queue, &work);
+    thread::scope(|scope| {
+        let reader_results = results.clone();
+        scope.spawn(move || {
+            let mut index = 0;
+            while window.acquire() {
+                match read() {
+                    Ok(Some(item)) => {
+                        if items.send((index, item)).is_err() {
+                            break;
+                        }
+                        index += 1;
+                    }
+                    Ok(None) => break,
+                    Err(error) => {
+                        let _ = reader_results.send((index, Err(error)));
+                        break;
+                    }
+                }
+            }
+        });
+        for _ in 0..workers {
+            let results = results.clone();
+            scope.spawn(move || loop {
+                let next = queue.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                let Ok((index, item)) = next else {
+                    break;
+                };
+                let result = work(&item);
+                if results.send((index, Ok((item, result)))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(results);
+        let written = write_in_order(done, window, &mut write);
+        window.close();
+        written
+    })
+}
+
+/// Writes the results coming from `done` in the order of their indices,
+/// giving each place in `window` back once written
+fn write_in_order<T, R, E>(
+    done: Receiver<Done<T, R, E>>,
+    window: &Window,
+    write: &mut impl FnMut(T, R) -> Result<(), E>,
+) -> Result<usize, E> {
+    // Bounded by the window, however late the next result is
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (index, result) in done {
+        pending.insert(index, result);
+        while let Some(result) = pending.remove(&next) {
+            let (item, result) = result?;
+            write(item, result)?;
+            window.release();
+            next += 1;
+        }
+    }
+    Ok(next)
+}
+
+/// How fast a `--batch` went, for `--stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stats {
+    /// Keys derived
+    pub(crate) keys: usize,
+    /// Time from the first key read to the last record written
+    pub(crate) elapsed: Duration,
+}
+
+impl Stats {
+    /// Returns the keys derived per second, rounded down
+    pub(crate) fn per_second(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1);
+        (self.keys as u128 * 1_000_000 / micros) as u64
+    }
+}
+
+/// Returns the progress bar for `--jobs`, drawn if `shown`
+#[cfg(not(target_os = "wasi"))]
+pub(crate) fn progress_bar(shown: bool) -> ProgressBar {
+    if !shown {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template("{spinner} {pos} keys, {per_sec}")
+        .expect("the template is valid");
+    ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr()).with_style(style)
+}
+
+/// The progress bar of targets that cannot draw one
+#[cfg(target_os = "wasi")]
+pub(crate) struct ProgressBar;
+
+#[cfg(target_os = "wasi")]
+impl ProgressBar {
+    pub(crate) fn inc(&self, _delta: u64) {}
+
+    pub(crate) fn finish_and_clear(&self) {}
+}
+
+/// Returns no progress bar, as there is no terminal to draw one on
+#[cfg(target_os = "wasi")]
+pub(crate) fn progress_bar(_shown: bool) -> ProgressBar {
+    ProgressBar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns a reader of `items`
+    fn reader<T: Clone + Send + Sync>(
+        items: &[T],
+    ) -> impl FnMut() -> Result<Option<T>, ()> + Send + '_ {
+        let mut items = items.iter();
+        move || Ok(items.next().cloned())
+    }
+
+    /// Streams `items` through `work` and collects what is written
+    fn collect<T, R>(items: &[T], jobs: usize, work: impl Fn(&T) -> R + Sync) -> Vec<R>
+    where
+        T: Clone + Send + Sync,
+        R: Send,
+    {
+        let mut written = Vec::new();
+        let count = stream_ordered(reader(items), jobs, work, |_, result| {
+            written.push(result);
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        assert_eq!(count, written.len());
+        written
+    }
+
+    #[test]
+    fn test_order_is_kept() {
+        let items: Vec<usize> = (0..10_000).collect();
+        let squares = collect(&items, 8, |&item| {
+            // Uneven work, so that threads finish out of order
+            if item % 7 == 0 {
+                thread::yield_now();
+            }
+            item * item
+        });
+        let expected: Vec<usize> = items.iter().map(|item| item * item).collect();
+        assert_eq!(squares, expected);
+    }
+
+    #[test]
+    fn test_failures_do_not_stop_the_rest() {
+        let items = ["a", "", "b", "", "c"];
+        let results = collect(&items, 3, |item| match item.is_empty() {
+            true => Err("empty"),
+            false => Ok(item.to_uppercase()),
+        });
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        assert_eq!(failed, 2);
+        assert_eq!(results[0], Ok("A".to_string()));
+        assert_eq!(results[4], Ok("C".to_string()));
+    }
+
+    #[test]
+    fn test_workers() {
+        assert_eq!(workers(8, true), 8);
+        assert_eq!(workers(0, true), 1);
+        assert_eq!(workers(8, false), 1);
+        assert_eq!(HAS_THREADS, !cfg!(target_os = "wasi"));
+    }
+
+    #[test]
+    fn test_odd_job_counts() {
+        assert_eq!(collect(&[1, 2, 3], 0, |n| n + 1), [2, 3, 4]);
+        assert_eq!(collect(&[1, 2, 3], 64, |n| n + 1), [2, 3, 4]);
+        assert!(collect(&[] as &[u8], 4, |n| *n).is_empty());
+    }
+
+    #[test]
+    fn test_window_bounds_the_keys_in_flight() {
+        // The first key is slow, so everything after it piles up behind it
+        let items: Vec<usize> = (0..10_000).collect();
+        let read = AtomicUsize::new(0);
+        let mut items_iter = items.iter();
+        let mut most_ahead = 0;
+        let mut written = 0;
+        stream_ordered(
+            || {
+                read.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, ()>(items_iter.next().copied())
+            },
+            4,
+            |&item| {
+                if item == 0 {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                item
+            },
+            |item, _| {
+                // Reads minus writes, counting the read that returned `None`
+                let ahead = read.load(Ordering::SeqCst) - written;
+                most_ahead = most_ahead.max(ahead);
+                assert_eq!(item, written);
+                written += 1;
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(written, items.len());
+        assert!(most_ahead <= 4 * WINDOW_PER_WORKER + 1, "{}", most_ahead);
+    }
+
+    #[test]
+    fn test_errors_stop_the_stream() {
+        let items: Vec<usize> = (0..1_000).collect();
+        let mut seen = 0;
+        let error = stream_ordered(
+            reader(&items),
+            4,
+            |&item| item,
+            |item, _| {
+                if item == 500 {
+                    return Err(());
+                }
+                seen += 1;
+                Ok(())
+            },
+        );
+        assert_eq!(error, Err(()));
+        assert_eq!(seen, 500);
+
+        // A failed read comes after every item read before it
+        let mut next = 0;
+        let mut written = Vec::new();
+        let error = stream_ordered(
+            || {
+                next += 1;
+                match next {
+                    1..=100 => Ok(Some(next)),
+                    _ => Err("read failed"),
+                }
+            },
+            4,
+            |&item| item,
+            |item, _| {
+                written.push(item);
+                Ok(())
+            },
+        );
+        assert_eq!(error, Err("read failed"));
+        assert_eq!(written, (1..=100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = |keys, millis| Stats {
+            keys,
+            elapsed: Duration::from_millis(millis),
+        };
+        assert_eq!(stats(10_000, 2_000).per_second(), 5_000);
+        assert_eq!(stats(3, 1_500).per_second(), 2);
+        assert_eq!(stats(0, 1_000).per_second(), 0);
+        // No division by zero for a run too short to measure
+        assert_eq!(stats(5, 0).per_second(), 5_000_000);
+    }
+}
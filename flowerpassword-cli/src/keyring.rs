@@ -0,0 +1,209 @@
+//! `--use-keyring` and `fp keyring`, a master password kept in the OS keyring
+//!
+//! The master password is stored as a generic password under the service
+//! [`SERVICE`] and the account [`ACCOUNT`]: in the Keychain on macOS, through the
+//! Secret Service (GNOME Keyring, KWallet) on Linux and the BSDs, and in the
+//! Credential Manager on Windows.
+//!
+//! Reading with `--use-keyring` never stops a run: if the keyring is locked,
+//! unavailable or has no entry, `fp` says so and prompts as usual.
+
+use crate::error::CliError;
+use crate::i18n::{self, Msg};
+use std::error::Error;
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Service name the master password is stored under
+pub(crate) const SERVICE: &str = "flowerpassword";
+
+/// Account name the master password is stored under
+pub(crate) const ACCOUNT: &str = "master";
+
+/// Error type for keyring backends, with the backend's explanation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyringError(pub(crate) String);
+
+impl fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        i18n::write(f, Msg::KeyringError, &[&self.0])
+    }
+}
+
+impl Error for KeyringError {}
+
+/// Storage for one master password
+pub(crate) trait Keyring {
+    /// Returns the stored master password, or `None` if there is none
+    fn get(&self) -> Result<Option<String>, KeyringError>;
+
+    /// Stores `master`, replacing any stored one
+    fn set(&self, master: &str) -> Result<(), KeyringError>;
+
+    /// Removes the stored master password, returning whether there was one
+    fn clear(&self) -> Result<bool, KeyringError>;
+}
+
+/// The OS keyring entry for [`SERVICE`] and [`ACCOUNT`]
+pub(crate) struct OsKeyring;
+
+impl OsKeyring {
+    fn entry() -> Result<::keyring::Entry, KeyringError> {
+        ::keyring::Entry::new(SERVICE, ACCOUNT).map_err(|error| KeyringError(error.to_string()))
+    }
+}
+
+impl Keyring for OsKeyring {
+    fn get(&self) -> Result<Option<String>, KeyringError> {
+        match Self::entry()?.get_password() {
+            Ok(master) => Ok(Some(master)),
+            Err(::keyring::Error::NoEntry) => Ok(None),
+            Err(error) => Err(KeyringError(error.to_string())),
+        }
+    }
+
+    fn set(&self, master: &str) -> Result<(), KeyringError> {
+        Self::entry()?
+            .set_password(master)
+            .map_err(|error| KeyringError(error.to_string()))
+    }
+
+    fn clear(&self) -> Result<bool, KeyringError> {
+        match Self::entry()?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(::keyring::Error::NoEntry) => Ok(false),
+            Err(error) => Err(KeyringError(error.to_string())),
+        }
+    }
+}
+
+/// Reads the master password from `keyring`, or from `fallback` if it cannot
+///
+/// Why the keyring was passed over goes to `on_notice`.
+pub(crate) fn read_master_keyring(
+    keyring: &impl Keyring,
+    fallback: impl FnOnce() -> Result<Zeroizing<String>, CliError>,
+    on_notice: impl FnOnce(&str),
+) -> Result<Zeroizing<String>, CliError> {
+    match keyring.get() {
+        Ok(Some(master)) if !master.is_empty() => return Ok(Zeroizing::new(master)),
+        Ok(_) => on_notice(Msg::KeyringEmpty.text()),
+        Err(error) => on_notice(&error.to_string()),
+    }
+    fallback()
+}
+
+/// Returns what `fp keyring status` prints
+pub(crate) fn status(keyring: &impl Keyring) -> Result<String, KeyringError> {
+    let status = if keyring.get()?.is_some() {
+        Msg::KeyringHasMaster
+    } else {
+        Msg::KeyringNoMaster
+    };
+    Ok(status.fill(&[&format_args!("{}/{}", SERVICE, ACCOUNT)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// An in-memory keyring, optionally failing like a locked one
+    #[derive(Default)]
+    struct MemoryKeyring {
+        master: RefCell<Option<String>>,
+        locked: bool,
+    }
+
+    impl MemoryKeyring {
+        fn check(&self) -> Result<(), KeyringError> {
+            if self.locked {
+                return Err(KeyringError("the collection is locked".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    impl Keyring for MemoryKeyring {
+        fn get(&self) -> Result<Option<String>, KeyringError> {
+            self.check()?;
+            Ok(self.master.borrow().clone())
+        }
+
+        fn set(&self, master: &str) -> Result<(), KeyringError> {
+            self.check()?;
+            *self.master.borrow_mut() = Some(master.to_string());
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<bool, KeyringError> {
+            self.check()?;
+            Ok(self.master.borrow_mut().take().is_some())
+        }
+    }
+
+    fn read(keyring: &MemoryKeyring) -> (Result<String, CliError>, Option<String>) {
+        let mut notice = None;
+        let master = read_master_keyring(
+            keyring,
+            || Ok(Zeroizing::new("prompted".to_string())),
+            |text| notice = Some(text.to_string()),
+        );
+        (master.map(|master| master.to_string()), notice)
+    }
+
+    #[test]
+    fn test_set_get_clear() {
+        let keyring = MemoryKeyring::default();
+        assert_eq!(
+            status(&keyring).unwrap(),
+            "Master password not stored in the keyring as flowerpassword/master"
+        );
+        keyring.set("test").unwrap();
+        assert_eq!(keyring.get().unwrap().as_deref(), Some("test"));
+        assert_eq!(
+            status(&keyring).unwrap(),
+            "Master password stored in the keyring as flowerpassword/master"
+        );
+        keyring.set("password").unwrap();
+        assert_eq!(keyring.get().unwrap().as_deref(), Some("password"));
+        assert!(keyring.clear().unwrap());
+        assert!(!keyring.clear().unwrap());
+        assert_eq!(keyring.get().unwrap(), None);
+    }
+
+    #[test]
+    fn test_stored_master_skips_the_prompt() {
+        let keyring = MemoryKeyring::default();
+        keyring.set("test").unwrap();
+        let (master, notice) = read(&keyring);
+        assert_eq!(master.unwrap(), "test");
+        assert_eq!(notice, None);
+    }
+
+    #[test]
+    fn test_falls_back_to_the_prompt() {
+        let (master, notice) = read(&MemoryKeyring::default());
+        assert_eq!(master.unwrap(), "prompted");
+        assert_eq!(
+            notice.as_deref(),
+            Some("No master password in the keyring, see fp keyring set")
+        );
+
+        let locked = MemoryKeyring {
+            locked: true,
+            ..MemoryKeyring::default()
+        };
+        let (master, notice) = read(&locked);
+        assert_eq!(master.unwrap(), "prompted");
+        assert_eq!(
+            notice.as_deref(),
+            Some("Keyring error: the collection is locked")
+        );
+        assert!(status(&locked).is_err());
+
+        // A prompt that fails still fails the run
+        let error = read_master_keyring(&locked, || Err(CliError::NoMaster), |_| {}).unwrap_err();
+        assert!(matches!(error, CliError::NoMaster));
+    }
+}
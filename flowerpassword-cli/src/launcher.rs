@@ -0,0 +1,163 @@
+//! `fp launcher`, the script filter of Raycast and Alfred
+//!
+//! `--query TEXT` prints the configured sites fuzzy-matching `TEXT`, best first
+//! as [`Config::find`] ranks them, in the JSON both launchers read:
+//!
+//! ```text
+//! {"items":[{"title":"github","subtitle":"github.com · 16 chars","arg":"github"}]}
+//! ```
+//!
+//! The items carry names, keys and lengths only, so a query never needs the
+//! master password. `--action ARG` then takes the `arg` of the picked item and
+//! copies its password to the clipboard, restoring the clipboard after
+//! `DEFAULT_TTL`.
+
+use crate::cli::derive;
+use crate::error::CliError;
+use flowerpassword::clipboard::{copy_with_ttl_using, Clipboard, ClipboardGuard};
+use flowerpassword::config::{Config, DEFAULT_LENGTH};
+use flowerpassword::FpGenerator;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// One site in the script filter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Item {
+    /// Site name
+    pub(crate) title: String,
+    /// Key and length the site derives with
+    pub(crate) subtitle: String,
+    /// What `--action` gets back: the site name
+    pub(crate) arg: String,
+}
+
+/// The whole output of `--query`
+#[derive(Serialize)]
+struct Items<'a> {
+    items: &'a [Item],
+}
+
+/// Returns the sites matching `query`, best match first
+pub(crate) fn items(config: &Config, query: &str) -> Vec<Item> {
+    let default_length = config.defaults.length.unwrap_or(DEFAULT_LENGTH);
+    config
+        .find(query)
+        .into_iter()
+        .filter_map(|found| {
+            let site = config.sites.get(found.site)?;
+            let key = site.key.as_deref().unwrap_or(found.site);
+            let length = site.length.unwrap_or(default_length);
+            Some(Item {
+                title: found.site.to_string(),
+                subtitle: format!("{} · {} chars", key, length),
+                arg: found.site.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Writes `items` as script filter JSON, on one line
+pub(crate) fn write(out: &mut impl Write, items: &[Item]) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &Items { items })?;
+    writeln!(out)
+}
+
+/// Copies the password for the site `arg` to `clipboard` for `ttl`
+pub(crate) fn action<C: Clipboard + 'static>(
+    generator: &FpGenerator,
+    config: &Config,
+    arg: &str,
+    clipboard: C,
+    ttl: Duration,
+) -> Result<ClipboardGuard, CliError> {
+    let derived = derive(generator, config, arg, None)?;
+    Ok(copy_with_ttl_using(clipboard, &derived.password, ttl)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{MemoryClipboard, Outcome};
+    use flowerpassword::config::Config;
+
+    const SITES: &str = include_str!("../tests/fixtures/launcher.toml");
+
+    fn json(query: &str) -> String {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut out = Vec::new();
+        write(&mut out, &items(&config, query)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_fixture() {
+        assert_eq!(
+            json("git"),
+            include_str!("../tests/fixtures/launcher-git.json")
+        );
+        assert_eq!(json("zzz"), "{\"items\":[]}\n");
+    }
+
+    #[test]
+    fn test_schema() {
+        let value: serde_json::Value = serde_json::from_str(&json("")).unwrap();
+        let items = value["items"].as_array().unwrap();
+        assert_eq!(items.len(), 3);
+        for item in items {
+            let item = item.as_object().unwrap();
+            let mut fields: Vec<_> = item.keys().map(String::as_str).collect();
+            fields.sort_unstable();
+            assert_eq!(fields, ["arg", "subtitle", "title"]);
+            assert!(item.values().all(serde_json::Value::is_string));
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_order() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let titles = |query| {
+            items(&config, query)
+                .into_iter()
+                .map(|item| item.title)
+                .collect::<Vec<_>>()
+        };
+        // Tight matches first, then by name
+        assert_eq!(titles("git"), ["github", "gitlab", "digitalocean"]);
+        assert_eq!(titles("glab"), ["gitlab"]);
+        // Aliases point at their site
+        assert_eq!(titles("gh"), ["github"]);
+        // Everything, by name, for an empty query
+        assert_eq!(titles(""), ["digitalocean", "github", "gitlab"]);
+    }
+
+    #[test]
+    fn test_no_secrets() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let json = json("");
+        for site in ["github", "gitlab", "digitalocean"] {
+            let password = derive(&generator, &config, site, None).unwrap().password;
+            assert!(!json.contains(&password), "{}", site);
+        }
+        assert!(!json.contains("test"));
+    }
+
+    #[test]
+    fn test_action() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let clipboard = MemoryClipboard::new(Some("before"));
+        let guard = action(
+            &generator,
+            &config,
+            "github",
+            clipboard.clone(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+    }
+}
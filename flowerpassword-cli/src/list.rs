@@ -0,0 +1,438 @@
+//! `fp list` and `fp show`, overviews of the configured sites
+//!
+//! Every site is listed with the key and length it derives with and the scheme,
+//! never with a password, so listing does not ask for the master password.
+//! `--output json` prints an array of objects with the fields `name`, `key`,
+//! `length` and `scheme`; `--output csv` prints the same columns under a header.
+//! `--long` adds the notes of each site, as a `notes` field or column; the table
+//! joins their lines with ` / ` to keep one row per site.
+//!
+//! `fp show NAME` prints everything about the site `NAME` resolves to: its
+//! settings, the aliases leading to it and its notes, again without a password.
+
+use crate::error::CliError;
+use clap::ValueEnum;
+use flowerpassword::config::{Config, ConfigError, DEFAULT_LENGTH};
+use flowerpassword::DEFAULT_SCHEME;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How `fp list` prints the sites
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ListFormat {
+    /// Aligned columns under a header
+    Table,
+    /// A JSON array of objects
+    Json,
+    /// Comma-separated values under a header
+    Csv,
+}
+
+/// Order of the listed sites
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SortKey {
+    /// By site name
+    Name,
+    /// By password length, then by site name
+    Length,
+}
+
+/// One listed site
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Entry<'a> {
+    /// Site name
+    pub(crate) name: &'a str,
+    /// Key the site derives with
+    pub(crate) key: &'a str,
+    /// Length the site derives with
+    pub(crate) length: usize,
+    /// Derivation scheme
+    pub(crate) scheme: &'static str,
+    /// Notes of the site, listed with `--long`
+    #[serde(skip)]
+    pub(crate) notes: Option<&'a str>,
+}
+
+/// An entry with its notes, for `--long --output json`
+#[derive(Serialize)]
+struct LongEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a Entry<'a>,
+    notes: Option<&'a str>,
+}
+
+/// Column headers of the table
+const HEADER: [&str; 5] = ["NAME", "KEY", "LENGTH", "SCHEME", "NOTES"];
+
+/// Column headers of the CSV
+const CSV_HEADER: [&str; 5] = ["name", "key", "length", "scheme", "notes"];
+
+/// Number of columns without `--long`
+const SHORT: usize = 4;
+
+/// Returns the sites whose name or key contains `filter`, ignoring case, in
+/// `sort` order
+pub(crate) fn entries<'a>(
+    config: &'a Config,
+    filter: Option<&str>,
+    sort: SortKey,
+) -> Vec<Entry<'a>> {
+    let filter = filter.map(str::to_lowercase);
+    let default_length = config.defaults.length.unwrap_or(DEFAULT_LENGTH);
+    let mut entries: Vec<Entry> = config
+        .sites
+        .iter()
+        .map(|(name, site)| Entry {
+            name,
+            key: site.key.as_deref().unwrap_or(name),
+            length: site.length.unwrap_or(default_length),
+            scheme: site.scheme.unwrap_or(DEFAULT_SCHEME).name(),
+            notes: site.notes.as_deref(),
+        })
+        .filter(|entry| match &filter {
+            Some(filter) => {
+                entry.name.to_lowercase().contains(filter)
+                    || entry.key.to_lowercase().contains(filter)
+            }
+            None => true,
+        })
+        .collect();
+    // The sites come sorted by name, and the sort is stable
+    if sort == SortKey::Length {
+        entries.sort_by_key(|entry| entry.length);
+    }
+    entries
+}
+
+/// Writes `entries` to `out` in `format`, with their notes if `long`
+pub(crate) fn write(
+    out: &mut impl Write,
+    entries: &[Entry],
+    format: ListFormat,
+    long: bool,
+) -> io::Result<()> {
+    let columns = if long { HEADER.len() } else { SHORT };
+    match format {
+        ListFormat::Table => write_table(out, entries, columns),
+        ListFormat::Json if long => {
+            let entries: Vec<LongEntry> = entries
+                .iter()
+                .map(|entry| LongEntry {
+                    entry,
+                    notes: entry.notes,
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *out, &entries)?;
+            writeln!(out)
+        }
+        ListFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, entries)?;
+            writeln!(out)
+        }
+        ListFormat::Csv => {
+            write_csv_record(out, &CSV_HEADER[..columns])?;
+            for entry in entries {
+                let length = entry.length.to_string();
+                let notes = entry.notes.unwrap_or_default();
+                let record = [entry.name, entry.key, &length, entry.scheme, notes];
+                write_csv_record(out, &record[..columns])?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Writes the first `columns` columns of `entries`, each padded to its widest
+/// cell but the last
+fn write_table(out: &mut impl Write, entries: &[Entry], columns: usize) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|entry| {
+            let notes = entry.notes.unwrap_or_default();
+            [
+                entry.name.to_string(),
+                entry.key.to_string(),
+                entry.length.to_string(),
+                entry.scheme.to_string(),
+                notes.lines().collect::<Vec<_>>().join(" / "),
+            ][..columns]
+                .to_vec()
+        })
+        .collect();
+    let header: Vec<String> = HEADER[..columns].iter().map(|h| h.to_string()).collect();
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            if !line.is_empty() {
+                line.push_str("  ");
+            }
+            line.push_str(&format!("{:<width$}", cell, width = width));
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Labels of the lines of `fp show`, padded to the same width
+const LABEL_WIDTH: usize = 8;
+
+/// Writes what `fp show` tells about the site `name` resolves to
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::UnknownSite` if `name` is
+/// neither a site nor an alias leading to one.
+pub(crate) fn show(out: &mut impl Write, config: &Config, name: &str) -> Result<(), CliError> {
+    let resolved = config.resolve(name)?;
+    let (Some(site), Some(settings)) = (
+        resolved.site,
+        resolved.site.and_then(|site| config.sites.get(site)),
+    ) else {
+        return Err(ConfigError::UnknownSite {
+            name: name.to_string(),
+        }
+        .into());
+    };
+    let aliases: Vec<&str> = config
+        .aliases
+        .keys()
+        .filter(|alias| {
+            config
+                .resolve(alias)
+                .is_ok_and(|resolved| resolved.site == Some(site) && resolved.alias.is_some())
+        })
+        .map(String::as_str)
+        .collect();
+
+    let mut line = |label: &str, value: &str| {
+        writeln!(out, "{:<width$}  {}", label, value, width = LABEL_WIDTH)
+    };
+    line("name", site)?;
+    line("key", resolved.key)?;
+    line("length", &resolved.length.to_string())?;
+    line("scheme", resolved.scheme.name())?;
+    if let Some(username) = resolved.username {
+        line("username", username)?;
+    }
+    if !aliases.is_empty() {
+        line("aliases", &aliases.join(", "))?;
+    }
+    if let Some(notes) = &settings.notes {
+        let mut lines = notes.lines();
+        line("notes", lines.next().unwrap_or_default())?;
+        for rest in lines {
+            line("", rest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one CSV record, quoting fields that need it
+fn write_csv_record(out: &mut impl Write, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+
+    const FIXTURE: &str = r#"
+[defaults]
+length = 20
+
+[sites.github]
+key = "github.com"
+length = 16
+
+[sites.GitLab]
+key = "gitlab.com"
+
+[sites.mail]
+key = "mail.example.org"
+length = 12
+username = "jane"
+notes = """用户名是旧邮箱
+label={sq1}"""
+
+[sites."bank, main"]
+length = 32
+
+[aliases]
+post = "mail"
+m = "post"
+"#;
+
+    fn render(entries: &[Entry], format: ListFormat) -> String {
+        let mut out = Vec::new();
+        write(&mut out, entries, format, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn render_long(entries: &[Entry], format: ListFormat) -> String {
+        let mut out = Vec::new();
+        write(&mut out, entries, format, true).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn shown(name: &str) -> Result<String, CliError> {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let mut out = Vec::new();
+        show(&mut out, &config, name)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn names<'a>(entries: &[Entry<'a>]) -> Vec<&'a str> {
+        entries.iter().map(|entry| entry.name).collect()
+    }
+
+    #[test]
+    fn test_table() {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let entries = entries(&config, None, SortKey::Name);
+        assert_eq!(
+            render(&entries, ListFormat::Table),
+            "NAME        KEY               LENGTH  SCHEME\n\
+             GitLab      gitlab.com        20      v1\n\
+             bank, main  bank, main        32      v1\n\
+             github      github.com        16      v1\n\
+             mail        mail.example.org  12      v1\n"
+        );
+        assert_eq!(
+            render(&[], ListFormat::Table),
+            "NAME  KEY  LENGTH  SCHEME\n"
+        );
+    }
+
+    #[test]
+    fn test_filter_ignores_case_and_matches_keys() {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let matches = |filter| names(&entries(&config, Some(filter), SortKey::Name));
+        assert_eq!(matches("GIT"), ["GitLab", "github"]);
+        assert_eq!(matches("example"), ["mail"]);
+        assert_eq!(matches(".COM"), ["GitLab", "github"]);
+        assert!(matches("nothing").is_empty());
+    }
+
+    #[test]
+    fn test_sort() {
+        let sites = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        assert_eq!(
+            names(&entries(&sites, None, SortKey::Length)),
+            ["mail", "github", "GitLab", "bank, main"]
+        );
+
+        // Equal lengths keep the name order
+        let ties = Config::from_toml_str("[sites.b]\n[sites.a]\n[sites.c]\n", "config.toml")
+            .unwrap()
+            .config;
+        assert_eq!(
+            names(&entries(&ties, None, SortKey::Length)),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let entries = entries(&config, Some("mail"), SortKey::Name);
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&entries, ListFormat::Json)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "name": "mail", "key": "mail.example.org", "length": 12, "scheme": "v1" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let entries = entries(&config, Some("a"), SortKey::Name);
+        assert_eq!(
+            render(&entries, ListFormat::Csv),
+            "name,key,length,scheme\r\n\
+             GitLab,gitlab.com,20,v1\r\n\
+             \"bank, main\",\"bank, main\",32,v1\r\n\
+             mail,mail.example.org,12,v1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_long() {
+        let config = Config::from_toml_str(FIXTURE, "config.toml")
+            .unwrap()
+            .config;
+        let entries = entries(&config, Some("ma"), SortKey::Name);
+        assert_eq!(
+            render_long(&entries, ListFormat::Table),
+            "NAME        KEY               LENGTH  SCHEME  NOTES\n\
+             bank, main  bank, main        32      v1\n\
+             mail        mail.example.org  12      v1      用户名是旧邮箱 / label={sq1}\n"
+        );
+        assert_eq!(
+            render_long(&entries, ListFormat::Csv),
+            "name,key,length,scheme,notes\r\n\
+             \"bank, main\",\"bank, main\",32,v1,\r\n\
+             mail,mail.example.org,12,v1,\"用户名是旧邮箱\nlabel={sq1}\"\r\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render_long(&entries[1..], ListFormat::Json)).unwrap();
+        assert_eq!(json[0]["notes"], "用户名是旧邮箱\nlabel={sq1}");
+        assert_eq!(json[0]["name"], "mail");
+    }
+
+    #[test]
+    fn test_show() {
+        let expected = "\
+name      mail
+key       mail.example.org
+length    12
+scheme    v1
+username  jane
+aliases   m, post
+notes     用户名是旧邮箱
+          label={sq1}
+";
+        assert_eq!(shown("mail").unwrap(), expected);
+        // Aliases show the site they lead to
+        assert_eq!(shown("m").unwrap(), expected);
+        assert_eq!(
+            shown("github").unwrap(),
+            "name      github\nkey       github.com\nlength    16\nscheme    v1\n"
+        );
+        assert!(matches!(
+            shown("example.com"),
+            Err(CliError::Config(ConfigError::UnknownSite { .. }))
+        ));
+    }
+}
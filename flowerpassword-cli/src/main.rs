@@ -0,0 +1,129 @@
+//! `fp`, the Flower Password command line
+//!
+//! Derives the password for each site given on the command line, or for each
+//! line of standard input with `--batch`. Site names are looked up in the layered
+//! configuration of `flowerpassword::config`; anything else is used as a key.
+//!
+//! ```text
+//! $ fp github gitlab.com
+//! Master password:
+//! D04175F7A9c7Ab4a
+//! ...
+//! ```
+//!
+//! # Exit status
+//!
+//! | Code | Meaning                                                                                  |
+//! |------|------------------------------------------------------------------------------------------|
+//! | 0    | Success                                                                                  |
+//! | 2    | Usage or validation error: arguments, configuration, input                               |
+//! | 3    | Reading or writing a file, descriptor or stream failed                                   |
+//! | 4    | gpg, the clipboard, the keyring, another backend, a doctor check or the self-test failed |
+//! | 5    | The master password does not match the saved fingerprint                                 |
+//! | 130  | Ctrl-C while `--show-timeout` waited or the terminal was prompting                       |
+
+mod agent;
+#[cfg(feature = "autotype")]
+mod autotype;
+mod blocklist;
+mod cli;
+mod color;
+mod completions;
+mod config;
+mod copy;
+#[cfg(all(unix, feature = "dbus"))]
+mod dbus;
+mod doctor;
+mod duration;
+mod entropy;
+mod erase;
+mod error;
+mod export;
+mod fido2;
+mod find;
+mod fingerprint;
+mod gpg;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod i18n;
+mod jobs;
+#[cfg(feature = "keyring")]
+mod keyring;
+mod launcher;
+mod list;
+mod man;
+#[cfg(unix)]
+mod mask;
+mod master;
+mod menu;
+mod native_host;
+mod output;
+#[cfg(unix)]
+mod paranoid;
+mod pass;
+mod pwned;
+#[cfg(feature = "qr")]
+mod qr;
+mod repl;
+mod selftest;
+#[cfg(any(feature = "grpc", feature = "http", all(unix, feature = "dbus")))]
+mod serve;
+mod sites;
+mod stdio;
+mod template;
+#[cfg(unix)]
+mod termios;
+mod tool;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(unix)]
+mod unlock;
+mod uri;
+mod yubikey;
+
+#[cfg(all(
+    target_os = "wasi",
+    any(feature = "autotype", feature = "keyring", feature = "tui")
+))]
+compile_error!("the autotype, keyring and tui features need a desktop or a terminal, which WASI has neither of");
+
+use clap::Parser;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    // Before anything can read a master password, so no core file holds it
+    flowerpassword::harden::harden();
+
+    let mut args = std::env::args_os();
+    if args
+        .nth(1)
+        .is_some_and(|arg| arg == completions::COMPLETE_COMMAND)
+    {
+        completions::run_complete(args);
+        return ExitCode::SUCCESS;
+    }
+
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut cli = cli::Cli::parse();
+    i18n::set_lang(
+        cli.lang
+            .unwrap_or_else(|| i18n::Lang::from_locale(|name| std::env::var(name).ok())),
+    );
+    #[cfg(unix)]
+    if let Err(error) = paranoid::enforce(&mut cli) {
+        error.exit();
+    }
+    if let Err(error) = cli.check() {
+        error.exit();
+    }
+    match cli::run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("fp: {}: {}", i18n::Msg::Error.text(), error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! `fp man`, the man pages, for packagers
+//!
+//! The pages are rendered by clap_mangen from the same clap definitions as
+//! `--help`: `fp.1`, plus `fp-list.1`, `fp-keyring-set.1` and so on for every
+//! visible subcommand. Each page ends with the exit statuses of
+//! [`EXIT_STATUSES`] and the variables of [`environment`], so neither is written
+//! twice.
+
+use crate::error::EXIT_STATUSES;
+use clap::CommandFactory;
+use clap_mangen::Man;
+use flowerpassword::config::{ENV_CONFIG, ENV_LENGTH};
+use roff::{bold, roman, Roff};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Returns the environment variables `fp` reads, with what each one changes
+pub(crate) fn environment() -> Vec<(&'static str, &'static str)> {
+    let mut variables = vec![
+        (
+            ENV_CONFIG,
+            "Another configuration file, read after the user's",
+        ),
+        (ENV_LENGTH, "Password length, instead of the configured one"),
+        (
+            "XDG_CONFIG_HOME",
+            "Where the user's configuration is looked up on Linux",
+        ),
+        (
+            "TERM",
+            "Whether --show-timeout erases with escape sequences or newlines",
+        ),
+        (
+            "XDG_STATE_HOME",
+            "Where fp --log keeps the history on Linux",
+        ),
+        ("NO_COLOR", "Turns off --color auto when set and not empty"),
+        ("GPG_TTY", "The terminal gpg asks on for --password-gpg"),
+        (
+            "PASSWORD_STORE_DIR",
+            "The store fp pass writes to, as pass reads it",
+        ),
+    ];
+    if cfg!(unix) {
+        variables.push((
+            "XDG_RUNTIME_DIR",
+            "Where fp unlock keeps the cached master password",
+        ));
+    }
+    if cfg!(all(
+        feature = "autotype",
+        not(any(windows, target_os = "macos"))
+    )) {
+        variables.extend([
+            ("WAYLAND_DISPLAY", "Makes --autotype type with ydotool"),
+            (
+                "DISPLAY",
+                "Makes --autotype type with xdotool, without Wayland",
+            ),
+        ]);
+    }
+    variables
+}
+
+/// Writes the page of `fp` and of every visible subcommand into `dir`
+///
+/// Returns the paths written, `fp.1` first.
+pub(crate) fn write_pages(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut command = crate::cli::Cli::command().disable_help_subcommand(true);
+    command.build();
+    let mut written = Vec::new();
+    write_page(&command, dir, &mut written)?;
+    Ok(written)
+}
+
+fn write_page(command: &clap::Command, dir: &Path, written: &mut Vec<PathBuf>) -> io::Result<()> {
+    let man = Man::new(command.clone());
+    let path = dir.join(man.get_filename());
+    let mut out = BufWriter::new(File::create(&path)?);
+    man.render(&mut out)?;
+    out.write_all(contract().to_roff().as_bytes())?;
+    out.flush()?;
+    written.push(path);
+    for subcommand in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        write_page(subcommand, dir, written)?;
+    }
+    Ok(())
+}
+
+/// Returns the EXIT STATUS and ENVIRONMENT sections
+fn contract() -> Roff {
+    let mut roff = Roff::new();
+    roff.control("SH", ["EXIT STATUS"]);
+    for (code, meaning) in EXIT_STATUSES {
+        roff.control("TP", []);
+        roff.text([bold(code.to_string())]);
+        roff.text([roman(meaning)]);
+    }
+    roff.control("SH", ["ENVIRONMENT"]);
+    for (name, meaning) in environment() {
+        roff.control("TP", []);
+        roff.text([bold(name)]);
+        roff.text([roman(meaning)]);
+    }
+    roff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = write_pages(dir.path()).unwrap();
+        let names: Vec<String> = written
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names[0], "fp.1");
+        for name in [
+            "fp-list.1",
+            "fp-completions.1",
+            "fp-pass.1",
+            "fp-pass-sync.1",
+        ] {
+            assert!(names.iter().any(|written| written == name), "{}", name);
+        }
+        assert!(!names.iter().any(|name| name.starts_with("fp-man")));
+        assert!(!names.iter().any(|name| name.starts_with("fp-help")));
+
+        let page = std::fs::read_to_string(dir.path().join("fp.1")).unwrap();
+        for section in [
+            ".SH NAME",
+            ".SH SYNOPSIS",
+            ".SH OPTIONS",
+            ".SH \"EXIT STATUS\"",
+        ] {
+            assert!(page.contains(section), "{}", section);
+        }
+        assert!(page.contains(".SH ENVIRONMENT"));
+        assert!(page.contains("\\-\\-length"));
+        assert!(page.contains("\\fBFP_CONFIG\\fR"));
+        for (code, meaning) in EXIT_STATUSES {
+            assert!(page.contains(&format!("\\fB{}\\fR", code)), "{}", code);
+            assert!(page.contains(&meaning.replace('-', "\\-")), "{}", meaning);
+        }
+
+        let list = std::fs::read_to_string(dir.path().join("fp-list.1")).unwrap();
+        assert!(list.contains("fp\\-list"));
+        assert!(list.contains("\\-\\-filter"));
+        assert!(list.contains(".SH \"EXIT STATUS\""));
+    }
+}
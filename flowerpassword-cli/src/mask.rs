@@ -0,0 +1,498 @@
+//! `--masked`, a password shown in part until a key reveals it
+//!
+//! The password is drawn on the terminal, not standard output, with only its first
+//! and last characters visible, such as `K3••••••••••••8c (16 chars)`. Then, with the
+//! terminal in raw mode:
+//!
+//! | Key                  | Effect                                      |
+//! |----------------------|---------------------------------------------|
+//! | `r`                  | reveal the whole password, or mask it again |
+//! | `c`                  | copy the password to the clipboard          |
+//! | `q`, Esc, Ctrl-C/D   | quit, leaving the masked form on screen     |
+//!
+//! The terminal mode is restored when `fp` quits, fails or panics, or is sent
+//! SIGTERM, through [`TermiosGuard`]. A copied
+//! password is replaced by the previous clipboard content when `fp` quits, or
+//! after [`DEFAULT_TTL`](flowerpassword::clipboard::DEFAULT_TTL) if that is sooner.
+//!
+//! Without a way to copy, as under `--paranoid`, `c` does nothing and is left
+//! out of the keys shown.
+//!
+//! `--masked` exists on Unix, where the terminal is `/dev/tty`.
+//!
+//! `--mask-style FIRST+LAST` sets how many characters stay visible at each end,
+//! `2+2` by default. Whatever the style, fewer than half the characters show: a
+//! password of 2 characters is masked completely, and one of 3 shows only the
+//! first.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::termios::TermiosGuard;
+use flowerpassword::clipboard::{ClipboardError, ClipboardGuard};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+
+/// Puts a password on the clipboard, returning the guard that restores it
+pub(crate) type CopyFn<'a> = &'a mut dyn FnMut(&str) -> Result<ClipboardGuard, ClipboardError>;
+
+/// Character standing for each hidden character
+pub(crate) const MASK_CHAR: char = '•';
+
+/// Returns the cursor to the start of the line and clears it
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// How many characters `--masked` leaves visible at each end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MaskStyle {
+    /// Visible characters at the start
+    pub(crate) first: usize,
+    /// Visible characters at the end
+    pub(crate) last: usize,
+}
+
+impl Default for MaskStyle {
+    fn default() -> Self {
+        MaskStyle { first: 2, last: 2 }
+    }
+}
+
+impl std::fmt::Display for MaskStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}+{}", self.first, self.last)
+    }
+}
+
+/// Parses `--mask-style`: `FIRST+LAST` such as `2+2` or `4+0`, or `none` for `0+0`
+pub(crate) fn parse_mask_style(text: &str) -> Result<MaskStyle, String> {
+    if text == "none" {
+        return Ok(MaskStyle { first: 0, last: 0 });
+    }
+    let invalid = || {
+        format!(
+            "invalid mask style {:?}, expected FIRST+LAST such as 2+2, or none",
+            text
+        )
+    };
+    let (first, last) = text.split_once('+').ok_or_else(invalid)?;
+    Ok(MaskStyle {
+        first: first.parse().map_err(|_| invalid())?,
+        last: last.parse().map_err(|_| invalid())?,
+    })
+}
+
+/// Returns `password` with all but the ends `style` shows replaced by
+/// [`MASK_CHAR`], and its length
+pub(crate) fn mask(password: &str, style: MaskStyle) -> String {
+    let chars: Vec<char> = password.chars().collect();
+    let len = chars.len();
+    // Always hide more than half, so short passwords cannot be read off
+    let visible = len.saturating_sub(1) / 2;
+    let first = style.first.min(visible);
+    let last = style.last.min(visible - first);
+    let mut masked: String = chars[..first].iter().collect();
+    masked.extend(std::iter::repeat_n(MASK_CHAR, len - first - last));
+    masked.extend(&chars[len - last..]);
+    Msg::MaskLength.fill(&[&masked, &len])
+}
+
+/// A terminal in raw mode, which reports keys as they are pressed
+pub(crate) trait Screen: Write {
+    /// Returns the next byte typed, or `None` at end of input
+    fn read_key(&mut self) -> io::Result<Option<u8>>;
+}
+
+/// A terminal whose line discipline can be switched off
+pub(crate) trait RawMode {
+    /// Switches to raw mode, remembering the mode to restore
+    fn enable(&mut self) -> io::Result<()>;
+
+    /// Restores the mode from before [`enable`](Self::enable)
+    fn restore(&mut self) -> io::Result<()>;
+}
+
+/// A terminal kept in raw mode until dropped, also when unwinding from a panic
+pub(crate) struct RawGuard<T: RawMode>(T);
+
+impl<T: RawMode> RawGuard<T> {
+    /// Switches `terminal` to raw mode
+    pub(crate) fn new(mut terminal: T) -> io::Result<Self> {
+        terminal.enable()?;
+        Ok(RawGuard(terminal))
+    }
+}
+
+impl<T: RawMode> Deref for RawGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: RawMode> DerefMut for RawGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: RawMode> Drop for RawGuard<T> {
+    fn drop(&mut self) {
+        // Nothing better to do if the terminal went away meanwhile
+        let _ = self.0.restore();
+    }
+}
+
+/// Shows `password` masked on `screen` and handles keys until one quits
+///
+/// `copy` puts the password on the clipboard; the guard it returns is kept until
+/// the next copy or the end. Without it, `c` is ignored.
+pub(crate) fn run(
+    password: &str,
+    style: MaskStyle,
+    screen: &mut impl Screen,
+    mut copy: Option<CopyFn>,
+) -> Result<(), CliError> {
+    let masked = mask(password, style);
+    let mut revealed = false;
+    let mut note: Option<String> = None;
+    let mut _clipboard = None;
+    let keys = match copy {
+        Some(_) => Msg::MaskKeys.text(),
+        None => Msg::MaskKeysNoCopy.text(),
+    };
+    loop {
+        let shown = if revealed { password } else { &masked };
+        match &note {
+            Some(note) => write!(screen, "{}{}  {}  {}", CLEAR_LINE, shown, note, keys)?,
+            None => write!(screen, "{}{}  {}", CLEAR_LINE, shown, keys)?,
+        }
+        screen.flush()?;
+        note = None;
+        match screen.read_key()? {
+            Some(b'r') => revealed = !revealed,
+            Some(b'c') => {
+                let Some(copy) = copy.as_mut() else {
+                    continue;
+                };
+                // Restore the previous copy first, or it would be restored later
+                _clipboard = None;
+                note = Some(match copy(password) {
+                    Ok(guard) => {
+                        _clipboard = Some(guard);
+                        Msg::MaskCopied.text().to_string()
+                    }
+                    Err(error) => error.to_string(),
+                });
+            }
+            // Esc, Ctrl-C and Ctrl-D arrive as bytes in raw mode
+            Some(b'q' | 0x1b | 0x03 | 0x04) | None => break,
+            Some(_) => {}
+        }
+    }
+    write!(screen, "{}{}\r\n", CLEAR_LINE, masked)?;
+    screen.flush()?;
+    Ok(())
+}
+
+/// The controlling terminal, `/dev/tty`
+pub(crate) struct Tty {
+    // Dropped before the terminal it restores is closed
+    saved: Option<TermiosGuard>,
+    file: std::fs::File,
+}
+
+impl Tty {
+    /// Opens the controlling terminal for `option`
+    ///
+    /// # Errors
+    ///
+    /// Returns `CliError::NoTerminal` naming `option` if there is none.
+    pub(crate) fn open(option: &'static str) -> Result<Self, CliError> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|_| CliError::NoTerminal(option))?;
+        Ok(Tty { saved: None, file })
+    }
+}
+
+impl RawMode for Tty {
+    fn enable(&mut self) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+        let fd = self.file.as_raw_fd();
+        // `fd` is open for the lifetime of `self.file`, which outlives the guard
+        let saved = TermiosGuard::save(fd)?;
+        let mut termios = saved.saved();
+        // SAFETY: a termios from tcgetattr
+        unsafe { libc::cfmakeraw(&mut termios) };
+        // SAFETY: as above
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.saved = Some(saved);
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        match self.saved.take() {
+            Some(saved) => saved.restore(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for Tty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Screen for Tty {
+    fn read_key(&mut self) -> io::Result<Option<u8>> {
+        use std::io::Read;
+        let mut key = [0];
+        Ok(match self.file.read(&mut key)? {
+            0 => None,
+            _ => Some(key[0]),
+        })
+    }
+}
+
+impl<T: RawMode + Screen> Screen for RawGuard<T> {
+    fn read_key(&mut self) -> io::Result<Option<u8>> {
+        self.0.read_key()
+    }
+}
+
+impl<T: RawMode + Write> Write for RawGuard<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{copy_with_ttl_using, MemoryClipboard, DEFAULT_TTL};
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    const PASSWORD: &str = "K3A2a66Bf88b628c";
+
+    /// Keys to press, and everything drawn
+    struct Script {
+        keys: VecDeque<u8>,
+        drawn: Vec<u8>,
+    }
+
+    impl Script {
+        fn new(keys: &str) -> Self {
+            Script {
+                keys: keys.bytes().collect(),
+                drawn: Vec::new(),
+            }
+        }
+
+        fn drawn(&self) -> String {
+            String::from_utf8(self.drawn.clone()).unwrap()
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.drawn.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Screen for Script {
+        fn read_key(&mut self) -> io::Result<Option<u8>> {
+            Ok(self.keys.pop_front())
+        }
+    }
+
+    /// Records the modes a terminal went through
+    #[derive(Clone, Default)]
+    struct FakeMode(Rc<RefCell<Vec<&'static str>>>);
+
+    impl RawMode for FakeMode {
+        fn enable(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().push("raw");
+            Ok(())
+        }
+
+        fn restore(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().push("restored");
+            Ok(())
+        }
+    }
+
+    fn style(first: usize, last: usize) -> MaskStyle {
+        MaskStyle { first, last }
+    }
+
+    /// Returns `n` mask characters
+    fn dots(n: usize) -> String {
+        MASK_CHAR.to_string().repeat(n)
+    }
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(
+            mask(PASSWORD, MaskStyle::default()),
+            format!("K3{}8c (16 chars)", dots(12))
+        );
+        assert_eq!(
+            mask(PASSWORD, style(4, 0)),
+            format!("K3A2{} (16 chars)", dots(12))
+        );
+        assert_eq!(
+            mask(PASSWORD, style(0, 0)),
+            format!("{} (16 chars)", dots(16))
+        );
+        // At most 7 of 16 characters show
+        assert_eq!(
+            mask(PASSWORD, style(10, 10)),
+            format!("K3A2a66{} (16 chars)", dots(9))
+        );
+        assert_eq!(
+            mask(PASSWORD, style(1, 10)),
+            format!("K{}8b628c (16 chars)", dots(9))
+        );
+    }
+
+    #[test]
+    fn test_short_passwords_stay_hidden() {
+        let style = MaskStyle::default();
+        assert_eq!(mask("K3", style), format!("{} (2 chars)", dots(2)));
+        assert_eq!(mask("K3A", style), format!("K{} (3 chars)", dots(2)));
+        assert_eq!(mask("K3A2", style), format!("K{} (4 chars)", dots(3)));
+        assert_eq!(mask("K3A2a", style), format!("K3{} (5 chars)", dots(3)));
+        assert_eq!(mask("K3A2a6", style), format!("K3{} (6 chars)", dots(4)));
+        assert_eq!(mask("K3A2a66", style), format!("K3{}6 (7 chars)", dots(4)));
+        assert_eq!(mask("K3A2a66B", style), format!("K3{}B (8 chars)", dots(5)));
+        assert_eq!(mask("", style), " (0 chars)");
+    }
+
+    #[test]
+    fn test_parse_mask_style() {
+        assert_eq!(parse_mask_style("2+2"), Ok(style(2, 2)));
+        assert_eq!(parse_mask_style("4+0"), Ok(style(4, 0)));
+        assert_eq!(parse_mask_style("none"), Ok(style(0, 0)));
+        for invalid in ["", "2", "2+", "+2", "a+b", "-1+2"] {
+            assert!(parse_mask_style(invalid).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(MaskStyle::default().to_string(), "2+2");
+    }
+
+    #[test]
+    fn test_reveal_and_quit() {
+        let mut screen = Script::new("rxrq");
+        run(
+            PASSWORD,
+            MaskStyle::default(),
+            &mut screen,
+            Some(&mut |_| unreachable!()),
+        )
+        .unwrap();
+        let drawn = screen.drawn();
+        let frames: Vec<&str> = drawn.split(CLEAR_LINE).skip(1).collect();
+        assert_eq!(
+            frames,
+            [
+                "K3••••••••••••8c (16 chars)  r reveal, c copy, q quit",
+                "K3A2a66Bf88b628c  r reveal, c copy, q quit",
+                "K3A2a66Bf88b628c  r reveal, c copy, q quit",
+                "K3••••••••••••8c (16 chars)  r reveal, c copy, q quit",
+                "K3••••••••••••8c (16 chars)\r\n",
+            ]
+        );
+
+        // The end of input quits as well
+        let mut screen = Script::new("r");
+        run(
+            PASSWORD,
+            MaskStyle::default(),
+            &mut screen,
+            Some(&mut |_| unreachable!()),
+        )
+        .unwrap();
+        assert!(screen.drawn().ends_with("K3••••••••••••8c (16 chars)\r\n"));
+    }
+
+    #[test]
+    fn test_copy() {
+        let clipboard = MemoryClipboard::new(Some("shopping list"));
+        let mut screen = Script::new("cq");
+        let mut copy = |password: &str| {
+            let guard = copy_with_ttl_using(clipboard.clone(), password, DEFAULT_TTL);
+            assert_eq!(clipboard.contents().as_deref(), Some(PASSWORD));
+            guard
+        };
+        run(PASSWORD, MaskStyle::default(), &mut screen, Some(&mut copy)).unwrap();
+        assert!(screen
+            .drawn()
+            .contains("K3••••••••••••8c (16 chars)  copied  r reveal, c copy, q quit"));
+        // Quitting puts the previous content back
+        assert_eq!(clipboard.contents().as_deref(), Some("shopping list"));
+
+        let mut screen = Script::new("c");
+        run(
+            PASSWORD,
+            MaskStyle::default(),
+            &mut screen,
+            Some(&mut |_| Err(ClipboardError::Unavailable)),
+        )
+        .unwrap();
+        assert!(screen
+            .drawn()
+            .contains(&format!("  {}  ", ClipboardError::Unavailable)));
+    }
+
+    #[test]
+    fn test_without_copy() {
+        let mut screen = Script::new("crq");
+        run(PASSWORD, MaskStyle::default(), &mut screen, None).unwrap();
+        let drawn = screen.drawn();
+        let frames: Vec<&str> = drawn.split(CLEAR_LINE).skip(1).collect();
+        // `c` is a key like any other, and not offered
+        assert_eq!(
+            frames,
+            [
+                "K3••••••••••••8c (16 chars)  r reveal, q quit",
+                "K3••••••••••••8c (16 chars)  r reveal, q quit",
+                "K3A2a66Bf88b628c  r reveal, q quit",
+                "K3••••••••••••8c (16 chars)\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_mode_is_restored() {
+        let mode = FakeMode::default();
+        drop(RawGuard::new(mode.clone()).unwrap());
+        assert_eq!(*mode.0.borrow(), ["raw", "restored"]);
+
+        let mode = FakeMode::default();
+        let guarded = mode.clone();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = RawGuard::new(guarded).unwrap();
+            panic!("while showing the password");
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(*mode.0.borrow(), ["raw", "restored"]);
+    }
+}
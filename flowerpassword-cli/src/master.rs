@@ -0,0 +1,544 @@
+//! Reading the master password
+//!
+//! On a terminal the master password is prompted for without echo, twice unless
+//! confirmation is off, since a typo would silently give wrong passwords for
+//! every site. Otherwise it is the first line of standard input, so that `fp` can
+//! be driven by scripts; with `--batch` the remaining lines are the keys.
+//!
+//! `--password-file` and `--password-fd` read the first line of a file or an
+//! inherited file descriptor instead, after gpg's options of the same names, so
+//! scripts need not put the master password in argv or the environment.
+//!
+//! `--pinentry` asks a pinentry program, the dialog GnuPG uses, twice unless
+//! confirmation is off.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::pinentry::{resolve_program, Pinentry};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Whether to ask for the master password twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Confirm {
+    /// Twice on a terminal, once otherwise: the default for single-shot runs
+    Auto,
+    /// Twice on a terminal, and a warning otherwise: `--confirm`
+    Requested,
+    /// Once: `--no-confirm`, and the default with `--batch`
+    Off,
+}
+
+impl Confirm {
+    /// Picks the behaviour for `--confirm` or `--no-confirm`, if given
+    pub(crate) fn new(explicit: Option<bool>, batch: bool) -> Self {
+        match explicit {
+            Some(true) => Confirm::Requested,
+            Some(false) => Confirm::Off,
+            None if batch => Confirm::Off,
+            None => Confirm::Auto,
+        }
+    }
+}
+
+/// How the master password is asked for on a terminal
+pub(crate) trait Prompter {
+    /// Returns whether standard input is a terminal that can be prompted on
+    fn is_terminal(&self) -> bool;
+
+    /// Reads a password after showing `prompt`, without echo
+    fn prompt(&mut self, prompt: &str) -> io::Result<String>;
+}
+
+/// Bytes reserved for a master password read from a line
+const SECRET_CAPACITY: usize = 1024;
+
+/// Whether this target can prompt without echo: WASI has no terminal control,
+/// and no `/dev/tty` or `/dev/stdin` path in its sandbox for `rpassword`
+const CAN_PROMPT: bool = cfg!(not(target_os = "wasi"));
+
+/// Returns whether to prompt for the master password rather than read it as
+/// the first line of standard input
+pub(crate) fn prompts(can_prompt: bool, stdin_is_terminal: bool) -> bool {
+    can_prompt && stdin_is_terminal
+}
+
+/// The process's terminal, prompted on with `rpassword`
+pub(crate) struct Terminal;
+
+impl Prompter for Terminal {
+    fn is_terminal(&self) -> bool {
+        prompts(CAN_PROMPT, io::stdin().is_terminal())
+    }
+
+    fn prompt(&mut self, prompt: &str) -> io::Result<String> {
+        // rpassword turns echo off, and a Ctrl-C meanwhile must not leave it off
+        #[cfg(unix)]
+        let _saved = crate::termios::save_tty();
+        rpassword::prompt_password(prompt)
+    }
+}
+
+/// Reads the master password from the terminal, or the first line of `input`,
+/// into a buffer wiped on drop
+///
+/// `on_warning` is called when `--confirm` was given without a terminal.
+///
+/// # Errors
+///
+/// Returns `CliError::MasterMismatch` if the two entries differ, and
+/// `CliError::NoMaster` if `input` is empty.
+pub(crate) fn read_master(
+    prompter: &mut impl Prompter,
+    input: &mut impl BufRead,
+    confirm: Confirm,
+    on_warning: impl FnOnce(&str),
+) -> Result<Zeroizing<String>, CliError> {
+    if prompter.is_terminal() {
+        let master = Zeroizing::new(prompter.prompt(Msg::MasterPrompt.text())?);
+        if confirm != Confirm::Off {
+            let again = Zeroizing::new(prompter.prompt(Msg::ConfirmPrompt.text())?);
+            if again != master {
+                return Err(CliError::MasterMismatch);
+            }
+        }
+        return Ok(master);
+    }
+    if confirm == Confirm::Requested {
+        on_warning(Msg::ConfirmNeedsTerminal.text());
+    }
+    read_secret_line(input)?.ok_or(CliError::NoMaster)
+}
+
+/// Reads the master password from the first line of the file at `path`
+///
+/// # Errors
+///
+/// On Unix, returns `CliError::InsecurePermissions` if other users may read the
+/// file, unless `insecure_perms` is set.
+pub(crate) fn read_master_file(
+    path: &Path,
+    insecure_perms: bool,
+) -> Result<Zeroizing<String>, CliError> {
+    let file = File::open(path)?;
+    #[cfg(unix)]
+    if !insecure_perms {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = file.metadata()?.permissions().mode() & 0o777;
+        if mode & 0o004 != 0 {
+            return Err(CliError::InsecurePermissions {
+                path: path.to_path_buf(),
+                mode,
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = insecure_perms;
+    read_secret_line(&mut BufReader::new(file))?
+        .ok_or_else(|| CliError::EmptyMaster(path.display().to_string()))
+}
+
+/// Reads the master password from the first line of file descriptor `fd`
+///
+/// Descriptor 0 is read through `input`, so that `--batch` keys can follow the
+/// master password on standard input.
+///
+/// # Errors
+///
+/// Returns `CliError::BadFd` for standard output and error, and for
+/// descriptors that are not open.
+#[cfg(unix)]
+pub(crate) fn read_master_fd(
+    fd: i32,
+    input: &mut impl BufRead,
+) -> Result<Zeroizing<String>, CliError> {
+    use std::os::unix::io::FromRawFd;
+
+    let master = match fd {
+        0 => read_secret_line(input)?,
+        1 | 2 => return Err(CliError::BadFd(fd)),
+        _ => {
+            // SAFETY: F_GETFD only inspects the descriptor table
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                return Err(CliError::BadFd(fd));
+            }
+            // SAFETY: the descriptor is open, was inherited for this purpose, and
+            // nothing else in the process uses it
+            let file = unsafe { File::from_raw_fd(fd) };
+            read_secret_line(&mut BufReader::new(file))?
+        }
+    };
+    master.ok_or_else(|| CliError::EmptyMaster(Msg::FileDescriptor.fill(&[&fd])))
+}
+
+/// Reads the master password from the pinentry program of `--pinentry=SPEC`,
+/// `auto` for the first `pinentry` on `PATH`
+///
+/// # Errors
+///
+/// Returns `CliError::NoPinentry` if there is no such program, and
+/// `CliError::Pinentry` if it fails or the dialog is cancelled.
+pub(crate) fn read_master_pinentry(
+    spec: &str,
+    confirm: Confirm,
+) -> Result<Zeroizing<String>, CliError> {
+    let program = resolve_program(spec).ok_or_else(|| CliError::NoPinentry(spec.to_string()))?;
+    let (mut pinentry, mut child) = Pinentry::spawn(&program)?;
+    let master = ask_pinentry(&mut pinentry, confirm);
+    // A cancelled dialog still says goodbye, but the cancellation is reported
+    let bye = pinentry.bye();
+    child.wait()?;
+    let master = master?;
+    bye?;
+    Ok(master)
+}
+
+/// Asks `pinentry` for the master password, twice unless `confirm` is off
+///
+/// A dialog always has someone to ask, so [`Confirm::Auto`] asks twice as
+/// [`Confirm::Requested`] does.
+fn ask_pinentry<R: BufRead, W: Write>(
+    pinentry: &mut Pinentry<R, W>,
+    confirm: Confirm,
+) -> Result<Zeroizing<String>, CliError> {
+    let description = Msg::PinentryDescription.text();
+    let master = pinentry.get_pin(description, Msg::MasterPrompt.text().trim_end())?;
+    if confirm != Confirm::Off {
+        let again = pinentry.get_pin(description, Msg::ConfirmPrompt.text().trim_end())?;
+        if *again != *master {
+            return Err(CliError::MasterMismatch);
+        }
+    }
+    Ok(master)
+}
+
+/// Asks on the terminal for the passphrase of the encrypted configuration file
+/// at `path`, twice with `confirm`
+///
+/// # Errors
+///
+/// Returns `CliError::PassphraseNeedsTerminal` without a terminal, since
+/// standard input carries the master password, and
+/// `CliError::PassphraseMismatch` if the two entries differ.
+pub(crate) fn read_passphrase(
+    prompter: &mut impl Prompter,
+    path: &Path,
+    confirm: bool,
+) -> Result<String, CliError> {
+    if !prompter.is_terminal() {
+        return Err(CliError::PassphraseNeedsTerminal);
+    }
+    let passphrase = prompter.prompt(&Msg::PassphrasePrompt.fill(&[&path.display()]))?;
+    if confirm && prompter.prompt(Msg::PassphraseConfirm.text())? != passphrase {
+        return Err(CliError::PassphraseMismatch);
+    }
+    Ok(passphrase)
+}
+
+/// Reads one line without its line ending, or `None` at the end of `input`
+pub(crate) fn read_line(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    strip_line_ending(&mut line);
+    Ok(Some(line))
+}
+
+/// Reads one line as [`read_line`] does, into a buffer wiped on drop
+///
+/// The buffer has room for any sensible master password from the start, so
+/// that growing it does not leave copies behind in freed memory.
+fn read_secret_line(input: &mut impl BufRead) -> io::Result<Option<Zeroizing<String>>> {
+    let mut line = Zeroizing::new(String::with_capacity(SECRET_CAPACITY));
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    strip_line_ending(&mut line);
+    Ok(Some(line))
+}
+
+/// Removes a trailing `\n` or `\r\n`
+fn strip_line_ending(line: &mut String) {
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::pinentry::PinentryError;
+
+    #[test]
+    fn test_prompts_only_where_it_can() {
+        assert!(prompts(true, true));
+        assert!(!prompts(true, false));
+        // WASI reads standard input even from a terminal
+        assert!(!prompts(false, true));
+        assert!(!prompts(false, false));
+        assert_eq!(CAN_PROMPT, !cfg!(target_os = "wasi"));
+    }
+
+    /// A terminal answering prompts from a list, or no terminal at all
+    struct FakeTerminal {
+        answers: Vec<&'static str>,
+        prompts: Vec<String>,
+    }
+
+    impl FakeTerminal {
+        fn new(answers: &[&'static str]) -> Self {
+            FakeTerminal {
+                answers: answers.iter().rev().copied().collect(),
+                prompts: Vec::new(),
+            }
+        }
+    }
+
+    impl Prompter for FakeTerminal {
+        fn is_terminal(&self) -> bool {
+            !self.answers.is_empty()
+        }
+
+        fn prompt(&mut self, prompt: &str) -> io::Result<String> {
+            self.prompts.push(prompt.to_string());
+            Ok(self.answers.pop().unwrap().to_string())
+        }
+    }
+
+    fn read(
+        terminal: &mut FakeTerminal,
+        input: &str,
+        confirm: Confirm,
+    ) -> (Result<String, CliError>, Vec<String>) {
+        let mut warnings = Vec::new();
+        let master = read_master(terminal, &mut input.as_bytes(), confirm, |warning| {
+            warnings.push(warning.to_string())
+        });
+        (master.map(|master| master.to_string()), warnings)
+    }
+
+    #[test]
+    fn test_confirm_defaults() {
+        assert_eq!(Confirm::new(None, false), Confirm::Auto);
+        assert_eq!(Confirm::new(None, true), Confirm::Off);
+        assert_eq!(Confirm::new(Some(true), true), Confirm::Requested);
+        assert_eq!(Confirm::new(Some(false), false), Confirm::Off);
+    }
+
+    #[test]
+    fn test_confirmation_match() {
+        let mut terminal = FakeTerminal::new(&["test", "test"]);
+        let (master, warnings) = read(&mut terminal, "", Confirm::Auto);
+        assert_eq!(master.unwrap(), "test");
+        assert_eq!(
+            terminal.prompts,
+            [Msg::MasterPrompt.text(), Msg::ConfirmPrompt.text()]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_confirmation_mismatch() {
+        let mut terminal = FakeTerminal::new(&["test", "tset"]);
+        let (master, _) = read(&mut terminal, "", Confirm::Requested);
+        let error = master.unwrap_err();
+        assert!(matches!(error, CliError::MasterMismatch));
+        // Neither attempt is echoed back
+        assert_eq!(error.to_string(), "Master passwords do not match");
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let path = Path::new("config.toml");
+        let mut terminal = FakeTerminal::new(&["secret"]);
+        assert_eq!(
+            read_passphrase(&mut terminal, path, false).unwrap(),
+            "secret"
+        );
+        assert_eq!(terminal.prompts, ["Passphrase for config.toml: "]);
+
+        let mut terminal = FakeTerminal::new(&["secret", "secret"]);
+        assert_eq!(
+            read_passphrase(&mut terminal, path, true).unwrap(),
+            "secret"
+        );
+        let mut terminal = FakeTerminal::new(&["secret", "sercet"]);
+        assert!(matches!(
+            read_passphrase(&mut terminal, path, true),
+            Err(CliError::PassphraseMismatch)
+        ));
+        assert!(matches!(
+            read_passphrase(&mut FakeTerminal::new(&[]), path, false),
+            Err(CliError::PassphraseNeedsTerminal)
+        ));
+    }
+
+    #[test]
+    fn test_confirmation_off() {
+        let mut terminal = FakeTerminal::new(&["test"]);
+        let (master, _) = read(&mut terminal, "", Confirm::Off);
+        assert_eq!(master.unwrap(), "test");
+        assert_eq!(terminal.prompts, [Msg::MasterPrompt.text()]);
+    }
+
+    #[test]
+    fn test_confirmation_without_terminal() {
+        let mut terminal = FakeTerminal::new(&[]);
+        let (master, warnings) = read(&mut terminal, "test\ngithub\n", Confirm::Requested);
+        assert_eq!(master.unwrap(), "test");
+        assert_eq!(warnings, [Msg::ConfirmNeedsTerminal.text()]);
+        // Scripts that did not ask for confirmation are not warned
+        let (master, warnings) = read(&mut terminal, "test\n", Confirm::Auto);
+        assert_eq!(master.unwrap(), "test");
+        assert!(warnings.is_empty());
+        let (master, _) = read(&mut terminal, "", Confirm::Auto);
+        assert!(matches!(master, Err(CliError::NoMaster)));
+    }
+
+    /// Asks a pinentry answering with `pins`, returning the result and the
+    /// commands it was sent
+    fn ask(pins: &[&str], confirm: Confirm) -> (Result<Zeroizing<String>, CliError>, String) {
+        let mut replies = String::from("OK Pleased to meet you\n");
+        for pin in pins {
+            match *pin {
+                "cancel" => replies.push_str("OK\nOK\nERR 83886179 Operation cancelled\n"),
+                pin => replies.push_str(&format!("OK\nOK\nD {}\nOK\n", pin)),
+            }
+        }
+        let mut sent = Vec::new();
+        let master = {
+            let mut pinentry = Pinentry::new(replies.as_bytes(), &mut sent).unwrap();
+            ask_pinentry(&mut pinentry, confirm)
+        };
+        (master, String::from_utf8(sent).unwrap())
+    }
+
+    #[test]
+    fn test_ask_pinentry() {
+        let (master, sent) = ask(&["test", "test"], Confirm::Auto);
+        assert_eq!(master.unwrap().as_str(), "test");
+        assert_eq!(
+            sent,
+            "SETDESC Enter the master password for fp\nSETPROMPT Master password:\nGETPIN\n\
+             SETDESC Enter the master password for fp\nSETPROMPT Repeat master password:\nGETPIN\n"
+        );
+
+        let (master, sent) = ask(&["test"], Confirm::Off);
+        assert_eq!(master.unwrap().as_str(), "test");
+        assert_eq!(sent.matches("GETPIN").count(), 1);
+
+        let (master, _) = ask(&["test", "tset"], Confirm::Requested);
+        assert!(matches!(master, Err(CliError::MasterMismatch)));
+        // A cancelled dialog is not a prompt to fall back from
+        let (master, _) = ask(&["cancel"], Confirm::Auto);
+        assert!(matches!(
+            master,
+            Err(CliError::Pinentry(PinentryError::Cancelled))
+        ));
+        let (master, _) = ask(&["test", "cancel"], Confirm::Auto);
+        assert!(matches!(
+            master,
+            Err(CliError::Pinentry(PinentryError::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn test_no_pinentry() {
+        let error = read_master_pinentry("/nonexistent/pinentry", Confirm::Off).unwrap_err();
+        assert!(matches!(error, CliError::NoPinentry(_)));
+        assert_eq!(
+            error.to_string(),
+            "No pinentry program found for --pinentry=/nonexistent/pinentry"
+        );
+    }
+
+    #[test]
+    fn test_read_master_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master");
+        std::fs::write(&path, "test\nignored\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        assert_eq!(*read_master_file(&path, false).unwrap(), "test");
+
+        std::fs::write(&path, "").unwrap();
+        let error = read_master_file(&path, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("No master password in {}", path.display())
+        );
+        assert!(matches!(
+            read_master_file(&dir.path().join("missing"), false),
+            Err(CliError::Io(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_read_master_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master");
+        std::fs::write(&path, "test\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let error = read_master_file(&path, false).unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::InsecurePermissions { mode: 0o644, .. }
+        ));
+        assert_eq!(*read_master_file(&path, true).unwrap(), "test");
+
+        // Group access is left to the user
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        assert_eq!(*read_master_file(&path, false).unwrap(), "test");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_read_master_fd() {
+        let mut input = "test\ngithub\n".as_bytes();
+        assert_eq!(*read_master_fd(0, &mut input).unwrap(), "test");
+        assert_eq!(input, b"github\n");
+        for fd in [1, 2, 1000] {
+            assert!(matches!(
+                read_master_fd(fd, &mut input),
+                Err(CliError::BadFd(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read_line_strips_line_endings() {
+        let mut input = "test\r\nsecond\nlast".as_bytes();
+        assert_eq!(read_line(&mut input).unwrap().unwrap(), "test");
+        assert_eq!(read_line(&mut input).unwrap().unwrap(), "second");
+        assert_eq!(read_line(&mut input).unwrap().unwrap(), "last");
+        assert_eq!(read_line(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_secret_line() {
+        let mut input = "test\r\ngithub\n".as_bytes();
+        let master = read_secret_line(&mut input).unwrap().unwrap();
+        assert_eq!(*master, "test");
+        assert!(master.capacity() >= SECRET_CAPACITY);
+        assert_eq!(input, b"github\n");
+        assert_eq!(read_secret_line(&mut "".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_line_keeps_other_whitespace() {
+        let mut input = " pass word \t\n".as_bytes();
+        assert_eq!(read_line(&mut input).unwrap().unwrap(), " pass word \t");
+    }
+}
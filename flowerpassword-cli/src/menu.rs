@@ -0,0 +1,230 @@
+//! `fp menu`, for rofi and dmenu
+//!
+//! Without arguments it prints the configured site names in order, one per
+//! line, for `rofi -dmenu` or `dmenu` to offer; the configuration refuses names
+//! with line breaks, so every line is one site. `--selected NAME` then copies
+//! the password of the picked site to the clipboard, and `--rofi` does both in
+//! one go, running rofi on the names and reading the pick back.
+
+use crate::error::CliError;
+use crate::i18n::{self, Msg};
+use crate::launcher;
+use flowerpassword::clipboard::{Clipboard, ClipboardGuard};
+use flowerpassword::config::Config;
+use flowerpassword::FpGenerator;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Arguments of `rofi`: a case-insensitive menu that only returns its own
+/// entries
+pub(crate) const ROFI_ARGS: &[&str] = &["-dmenu", "-i", "-no-custom", "-p", "fp"];
+
+/// Exit status of rofi when the menu is dismissed
+const ROFI_CANCELLED: i32 = 1;
+
+/// Error type for running the menu program
+#[derive(Debug)]
+pub(crate) enum MenuError {
+    /// The menu program is not on `PATH`
+    NotInstalled(&'static str),
+    /// The menu program ran and failed, with what it wrote to standard error
+    Failed(String),
+    /// The menu program could not be started or talked to
+    Io(io::Error),
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MenuError::NotInstalled(program) => i18n::write(f, Msg::NotInstalled, &[program]),
+            MenuError::Failed(message) => i18n::write(f, Msg::MenuFailed, &[message]),
+            MenuError::Io(error) => i18n::write(f, Msg::MenuIo, &[error]),
+        }
+    }
+}
+
+impl Error for MenuError {}
+
+/// A menu offering site names and returning the one picked
+pub(crate) trait Pick {
+    /// Returns the name picked among `names`, one per line, or `None` if the
+    /// menu was dismissed
+    fn pick(&mut self, names: &[u8]) -> Result<Option<String>, MenuError>;
+}
+
+/// A [`Pick`] running `rofi -dmenu`
+#[derive(Debug, Clone)]
+pub(crate) struct Rofi {
+    program: PathBuf,
+}
+
+impl Default for Rofi {
+    fn default() -> Self {
+        Rofi {
+            program: PathBuf::from("rofi"),
+        }
+    }
+}
+
+impl Pick for Rofi {
+    fn pick(&mut self, names: &[u8]) -> Result<Option<String>, MenuError> {
+        let mut child = Command::new(&self.program)
+            .args(ROFI_ARGS)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => MenuError::NotInstalled("rofi"),
+                _ => MenuError::Io(error),
+            })?;
+        let mut stdin = child.stdin.take().expect("standard input is piped");
+        match stdin.write_all(names) {
+            Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(MenuError::Io(error));
+            }
+            _ => drop(stdin),
+        }
+        let mut stdout = String::new();
+        if let Some(mut out) = child.stdout.take() {
+            out.read_to_string(&mut stdout).map_err(MenuError::Io)?;
+        }
+        let output = child.wait_with_output().map_err(MenuError::Io)?;
+        match output.status.code() {
+            Some(0) => Ok(stdout.lines().next().map(str::to_string)),
+            Some(ROFI_CANCELLED) => Ok(None),
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(MenuError::Failed(match stderr.trim() {
+                    "" => output.status.to_string(),
+                    message => message.to_string(),
+                }))
+            }
+        }
+    }
+}
+
+/// Writes the names of the sites of `config`, one per line
+pub(crate) fn write(out: &mut impl Write, config: &Config) -> io::Result<()> {
+    for name in config.sites.keys() {
+        writeln!(out, "{}", name)?;
+    }
+    Ok(())
+}
+
+/// Has `picker` offer the sites of `config` and returns the one picked
+pub(crate) fn pick(picker: &mut impl Pick, config: &Config) -> Result<Option<String>, MenuError> {
+    let mut names = Vec::new();
+    write(&mut names, config).map_err(MenuError::Io)?;
+    picker.pick(&names)
+}
+
+/// Checks that `name` is a site of `config`
+///
+/// A line typed into dmenu that names none is refused rather than derived as a
+/// key, since a typo would give a password that works nowhere.
+pub(crate) fn check(config: &Config, name: &str) -> Result<(), CliError> {
+    match config.sites.contains_key(name) {
+        true => Ok(()),
+        false => Err(CliError::NoMatch(name.to_string())),
+    }
+}
+
+/// Copies the password of the site `name` to `clipboard` for `ttl`
+pub(crate) fn selected<C: Clipboard + 'static>(
+    generator: &FpGenerator,
+    config: &Config,
+    name: &str,
+    clipboard: C,
+    ttl: Duration,
+) -> Result<ClipboardGuard, CliError> {
+    check(config, name)?;
+    launcher::action(generator, config, name, clipboard, ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{MemoryClipboard, Outcome};
+    use flowerpassword::config::Config;
+
+    const SITES: &str = include_str!("../tests/fixtures/launcher.toml");
+
+    #[test]
+    fn test_write() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut out = Vec::new();
+        write(&mut out, &config).unwrap();
+        assert_eq!(out, b"digitalocean\ngithub\ngitlab\n");
+    }
+
+    #[test]
+    fn test_selected_copies() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let clipboard = MemoryClipboard::new(Some("before"));
+        let ttl = Duration::from_secs(60);
+        let guard = selected(&generator, &config, "github", clipboard.clone(), ttl).unwrap();
+        assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+
+        // Aliases and literal keys are not in the menu
+        for name in ["gh", "github.com"] {
+            let error = selected(&generator, &config, name, clipboard.clone(), ttl);
+            assert!(matches!(error, Err(CliError::NoMatch(_))), "{}", name);
+        }
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+    }
+
+    /// Writes a fake rofi into `dir` that logs its arguments and input, then
+    /// answers with `script`
+    #[cfg(unix)]
+    fn fake_rofi(dir: &std::path::Path, script: &str) -> Rofi {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("rofi");
+        let log = dir.join("log");
+        let script = format!(
+            "#!/bin/sh\necho \"$@\" > '{0}'\ncat >> '{0}'\n{1}",
+            log.display(),
+            script
+        );
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Rofi { program: path }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rofi() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let dir = tempfile::tempdir().unwrap();
+        let mut rofi = fake_rofi(dir.path(), "echo gitlab\n");
+        let picked = pick(&mut rofi, &config).unwrap();
+        assert_eq!(picked.as_deref(), Some("gitlab"));
+        let log = std::fs::read_to_string(dir.path().join("log")).unwrap();
+        assert_eq!(
+            log,
+            "-dmenu -i -no-custom -p fp\ndigitalocean\ngithub\ngitlab\n"
+        );
+
+        // Escape dismisses the menu
+        let mut rofi = fake_rofi(dir.path(), "exit 1\n");
+        assert_eq!(pick(&mut rofi, &config).unwrap(), None);
+
+        let mut rofi = fake_rofi(dir.path(), "echo 'cannot open display' >&2\nexit 2\n");
+        let error = pick(&mut rofi, &config).unwrap_err();
+        assert_eq!(error.to_string(), "rofi failed: cannot open display");
+
+        let mut rofi = Rofi {
+            program: dir.path().join("missing"),
+        };
+        let error = pick(&mut rofi, &config).unwrap_err();
+        assert!(matches!(error, MenuError::NotInstalled("rofi")));
+    }
+}
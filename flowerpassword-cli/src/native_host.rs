@@ -0,0 +1,196 @@
+//! `fp native-host` and `fp install-manifest`, for browser extensions
+//!
+//! The browser starts `fp native-host` when its extension connects and talks
+//! to it over standard input and output in the framing of
+//! [`flowerpassword::native_host`]. A `derive` request names the web origin of
+//! the page, whose registrable domain is the key. The master password is the
+//! host's own, from the agent of `fp agent start`, `fp unlock`'s cache or the
+//! OS keyring: it is never taken from the extension, nor prompted for.
+//!
+//! Browsers run the `path` of a host manifest without arguments of their
+//! choosing, so `fp install-manifest` writes a script running
+//! `fp native-host` next to the manifest, and the manifest naming it and the
+//! extensions allowed to connect.
+
+use crate::cli::Generate;
+use crate::error::CliError;
+use clap::ValueEnum;
+use flowerpassword::native_host::{
+    install_manifest, origin_to_key, read_message, write_message, Browser, NativeHostError,
+    Request, Response, HOST_NAME,
+};
+use flowerpassword::DEFAULT_SCHEME;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Browser of `fp install-manifest --browser`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum BrowserArg {
+    /// Google Chrome
+    Chrome,
+    /// Chromium
+    Chromium,
+    /// Mozilla Firefox
+    Firefox,
+}
+
+impl From<BrowserArg> for Browser {
+    fn from(browser: BrowserArg) -> Self {
+        match browser {
+            BrowserArg::Chrome => Browser::Chrome,
+            BrowserArg::Chromium => Browser::Chromium,
+            BrowserArg::Firefox => Browser::Firefox,
+        }
+    }
+}
+
+/// Answers the messages of `input` on `output` with passwords of `generator`
+/// until `input` ends
+///
+/// A message that is not a request is answered with an error and the next one
+/// read; one too large to read ends the session, since what follows it cannot
+/// be framed.
+pub(crate) fn serve(
+    generator: &impl Generate,
+    input: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(), CliError> {
+    loop {
+        let response = match read_message::<_, Request>(input) {
+            Ok(Some(request)) => respond(generator, request),
+            Ok(None) => return Ok(()),
+            Err(error @ NativeHostError::Malformed(_)) => Response::Error {
+                message: error.to_string(),
+            },
+            Err(error) => return Err(error.into()),
+        };
+        write_message(output, &response)?;
+        output.flush()?;
+    }
+}
+
+/// Derives the password of a request's origin
+fn respond(generator: &impl Generate, request: Request) -> Response {
+    let Request::Derive { origin, length } = request;
+    let derived = origin_to_key(&origin)
+        .map_err(CliError::from)
+        .and_then(|key| generator.password(DEFAULT_SCHEME, &key, length));
+    match derived {
+        Ok(password) => Response::Password { password },
+        Err(error) => Response::Error {
+            message: error.to_string(),
+        },
+    }
+}
+
+/// Installs the host for `browser` under `home`, running `binary`, for the
+/// extensions of `extension_ids`, and returns the path of the manifest
+pub(crate) fn install(
+    browser: BrowserArg,
+    home: &Path,
+    binary: &Path,
+    extension_ids: &[&str],
+) -> Result<PathBuf, CliError> {
+    let browser = Browser::from(browser);
+    let binary = std::path::absolute(binary)?;
+    let dir = browser
+        .manifest_dir(home)
+        .ok_or(io::Error::from(io::ErrorKind::Unsupported))?;
+    std::fs::create_dir_all(&dir)?;
+    let script = dir.join(HOST_NAME);
+    write_script(&script, &binary)?;
+    Ok(install_manifest(browser, home, &script, extension_ids)?)
+}
+
+/// Writes the executable script at `path` running `binary native-host` with
+/// the arguments of the browser
+fn write_script(path: &Path, binary: &Path) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o755);
+    }
+    let quoted = binary.to_string_lossy().replace('\'', r"'\''");
+    let mut file = options.open(path)?;
+    writeln!(file, "#!/bin/sh")?;
+    writeln!(file, "exec '{}' native-host \"$@\"", quoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::FpGenerator;
+
+    fn frames(requests: &[&[u8]]) -> Vec<u8> {
+        let mut wire = Vec::new();
+        for body in requests {
+            wire.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+            wire.extend_from_slice(body);
+        }
+        wire
+    }
+
+    fn responses(mut wire: &[u8]) -> Vec<Response> {
+        let mut responses = Vec::new();
+        while let Some(response) = read_message(&mut wire).unwrap() {
+            responses.push(response);
+        }
+        responses
+    }
+
+    #[test]
+    fn test_serve() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let input = frames(&[
+            br#"{"cmd":"derive","origin":"https://github.com","length":16}"#,
+            br#"{"cmd":"derive","origin":"https://github.com","master":"x","length":16}"#,
+            br#"{"cmd":"derive","origin":"chrome://settings","length":16}"#,
+            br#"{"cmd":"derive","origin":"https://github.com","length":40}"#,
+            br#"{"cmd":"derive","origin":"https://gist.github.com","length":16}"#,
+        ]);
+        let mut output = Vec::new();
+        serve(&generator, &mut input.as_slice(), &mut output).unwrap();
+        let responses = responses(&output);
+        let password = Response::Password {
+            password: "D04175F7A9c7Ab4a".to_string(),
+        };
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0], password);
+        for response in &responses[1..4] {
+            assert!(matches!(response, Response::Error { .. }), "{:?}", response);
+        }
+        // The key is the registrable domain
+        assert_eq!(responses[4], password);
+    }
+
+    #[test]
+    fn test_serve_too_large() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let input = u32::MAX.to_ne_bytes();
+        let result = serve(&generator, &mut input.as_slice(), &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(CliError::NativeHost(NativeHostError::MessageTooLarge(_)))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install() {
+        let home = tempfile::tempdir().unwrap();
+        let binary = Path::new("/opt/it's/fp");
+        let path = install(BrowserArg::Chromium, home.path(), binary, &["abc"]).unwrap();
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let script = Path::new(manifest["path"].as_str().unwrap());
+        assert_eq!(script.parent(), path.parent());
+        assert_eq!(
+            std::fs::read_to_string(script).unwrap(),
+            "#!/bin/sh\nexec '/opt/it'\\''s/fp' native-host \"$@\"\n"
+        );
+        assert_eq!(manifest["allowed_origins"][0], "chrome-extension://abc/");
+    }
+}
@@ -0,0 +1,127 @@
+//! Writing records to standard output
+//!
+//! Standard output carries the records and nothing else, so scripts can read
+//! it directly; prompts, warnings and errors go to the terminal or standard
+//! error. Records end with a newline, or with NUL for `--null`, except the last
+//! one when [`trailing`] says so: a single password piped into `wl-copy` or a
+//! login form should not carry a newline along.
+
+use std::io::{self, Write};
+
+/// Returns whether the last record is terminated
+///
+/// `--newline` and `--no-newline` decide if given, as `newline`. Otherwise
+/// `--raw` leaves it unterminated, and so does piping a single record, since
+/// only a terminal needs the newline. `--null` and several records, from
+/// `--batch` or more than one key, stay terminated for `xargs -0` and `read`
+/// loops, which would drop an unterminated last record.
+pub(crate) fn trailing(
+    is_tty: bool,
+    newline: Option<bool>,
+    raw: bool,
+    null: bool,
+    several: bool,
+) -> bool {
+    match newline {
+        Some(newline) => newline,
+        None => !raw && (is_tty || null || several),
+    }
+}
+
+/// A sink for records, terminating each one before the next is written
+pub(crate) struct Records<W: Write> {
+    out: W,
+    terminator: &'static [u8],
+    trailing: bool,
+    pending: bool,
+}
+
+impl<W: Write> Records<W> {
+    /// Ends records with NUL if `null`, and the last one at all only if `trailing`
+    pub(crate) fn new(out: W, null: bool, trailing: bool) -> Self {
+        Records {
+            out,
+            terminator: if null { b"\0" } else { b"\n" },
+            trailing,
+            pending: false,
+        }
+    }
+
+    /// Writes one record
+    pub(crate) fn write(&mut self, record: &str) -> io::Result<()> {
+        if self.pending {
+            self.out.write_all(self.terminator)?;
+        }
+        self.out.write_all(record.as_bytes())?;
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Returns the underlying writer, for output that is not a record
+    #[cfg(feature = "qr")]
+    pub(crate) fn raw(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /// Terminates the last record if wanted and flushes the output
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if self.pending && self.trailing {
+            self.out.write_all(self.terminator)?;
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(null: bool, trailing: bool, records: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut sink = Records::new(&mut out, null, trailing);
+        for record in records {
+            sink.write(record).unwrap();
+        }
+        sink.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn test_terminators() {
+        assert_eq!(written(false, true, &["a", "b"]), b"a\nb\n");
+        assert_eq!(written(false, false, &["a", "b"]), b"a\nb");
+        assert_eq!(written(true, true, &["a", "b"]), b"a\0b\0");
+        assert_eq!(written(true, false, &["a"]), b"a");
+        assert_eq!(written(false, true, &[]), b"");
+    }
+
+    #[test]
+    fn test_trailing() {
+        let bools = [false, true];
+        for is_tty in bools {
+            for raw in bools {
+                for null in bools {
+                    for several in bools {
+                        let flags = (is_tty, raw, null, several);
+                        // An explicit flag always wins
+                        assert!(trailing(is_tty, Some(true), raw, null, several));
+                        assert!(!trailing(is_tty, Some(false), raw, null, several));
+                        let expected = !raw && (is_tty || null || several);
+                        assert_eq!(
+                            trailing(is_tty, None, raw, null, several),
+                            expected,
+                            "{:?}",
+                            flags
+                        );
+                    }
+                }
+            }
+        }
+        // The cases the defaults are for
+        assert!(trailing(true, None, false, false, false));
+        assert!(!trailing(false, None, false, false, false));
+        assert!(!trailing(true, None, true, false, false));
+        assert!(trailing(false, None, false, false, true));
+        assert!(trailing(false, None, false, true, false));
+    }
+}
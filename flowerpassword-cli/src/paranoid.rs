@@ -0,0 +1,272 @@
+//! `--paranoid`, the strictest way to run `fp`
+//!
+//! [`apply`] checks the parsed arguments against the policy and fills in what it
+//! implies, before [`Cli::check`] and the run see them:
+//!
+//! - The master password comes from `--password-fd` or `--pinentry` only, and
+//!   the pinentry asks for it twice.
+//! - The password is shown `--masked`, one at a time, revealed by hand and never
+//!   copied to the clipboard.
+//! - Nothing is kept: no `--log` history.
+//! - Standard output is not a file, which would keep whatever reaches it.
+//!
+//! An argument that would weaken any of these is refused with a usage error
+//! naming it, rather than overridden.
+
+use crate::cli::Cli;
+use clap::error::ErrorKind;
+use std::fmt;
+use std::io;
+
+/// Why `--paranoid` refuses to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Violation {
+    /// An argument that weakens the policy
+    Conflict(&'static str),
+    /// Neither `--password-fd` nor `--pinentry`
+    NoSource,
+    /// More than one key, which `--masked` cannot show
+    ManyKeys,
+    /// Standard output is redirected to a regular file
+    StdoutIsFile,
+}
+
+impl Violation {
+    /// Returns the kind of usage error to report this as
+    fn kind(self) -> ErrorKind {
+        match self {
+            Violation::NoSource => ErrorKind::MissingRequiredArgument,
+            _ => ErrorKind::ArgumentConflict,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Conflict(flag) => {
+                write!(f, "--paranoid and {} cannot be used together", flag)
+            }
+            Violation::NoSource => f.write_str(
+                "--paranoid reads the master password from --password-fd or --pinentry only",
+            ),
+            Violation::ManyKeys => f.write_str("--paranoid shows one password at a time"),
+            Violation::StdoutIsFile => {
+                f.write_str("--paranoid refuses to run with standard output redirected to a file")
+            }
+        }
+    }
+}
+
+/// Returns the arguments given in `cli` that `--paranoid` refuses, in the order
+/// of `--help`
+fn refused(cli: &Cli) -> Vec<&'static str> {
+    let mut given = vec![
+        ("--batch", cli.batch),
+        ("--interactive", cli.interactive),
+        ("--no-confirm", cli.no_confirm),
+        ("--password-file", cli.password_file.is_some()),
+        ("--password-gpg", cli.password_gpg.is_some()),
+        ("--agent", cli.agent),
+    ];
+    #[cfg(feature = "keyring")]
+    given.push(("--use-keyring", cli.use_keyring));
+    given.extend([
+        ("--format", cli.format.is_some()),
+        ("--null", cli.null),
+        ("--raw", cli.raw),
+        ("--show-timeout", cli.show_timeout.is_some()),
+    ]);
+    #[cfg(feature = "autotype")]
+    given.push(("--autotype", cli.autotype));
+    given.extend([
+        ("--copy", cli.copy),
+        ("--copy-user-then-pass", cli.copy_user_then_pass),
+        ("--log", cli.log),
+    ]);
+    #[cfg(feature = "qr")]
+    given.push(("--qr", cli.qr));
+    given
+        .into_iter()
+        .filter_map(|(flag, set)| set.then_some(flag))
+        .collect()
+}
+
+/// Checks `cli`, which has `--paranoid`, against the policy, and turns on
+/// `--masked` and `--confirm`
+///
+/// `stdout_is_file` tells whether standard output is a regular file.
+pub(crate) fn apply(cli: &mut Cli, stdout_is_file: bool) -> Result<(), Violation> {
+    if let Some(flag) = refused(cli).first() {
+        return Err(Violation::Conflict(flag));
+    }
+    if cli.password_fd.is_none() && cli.pinentry.is_none() {
+        return Err(Violation::NoSource);
+    }
+    if cli.keys.len() != 1 {
+        return Err(Violation::ManyKeys);
+    }
+    if stdout_is_file {
+        return Err(Violation::StdoutIsFile);
+    }
+    cli.masked = true;
+    cli.confirm = true;
+    Ok(())
+}
+
+/// Returns whether standard output is a regular file
+fn stdout_is_file() -> bool {
+    use std::os::fd::AsFd;
+    io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(std::fs::File::from)
+        .and_then(|file| file.metadata())
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Applies `--paranoid`, if given, to `cli`
+///
+/// # Errors
+///
+/// Returns the usage error to exit with if the policy refuses the run.
+pub(crate) fn enforce(cli: &mut Cli) -> Result<(), clap::Error> {
+    if !cli.paranoid {
+        return Ok(());
+    }
+    apply(cli, stdout_is_file()).map_err(|violation| {
+        <Cli as clap::CommandFactory>::command().error(violation.kind(), violation)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Parses `fp --paranoid` followed by `args`
+    fn parse(args: &[&str]) -> Cli {
+        let args = ["fp", "--paranoid"].iter().chain(args);
+        Cli::try_parse_from(args).unwrap()
+    }
+
+    fn apply_to(args: &[&str]) -> Result<Cli, Violation> {
+        let mut cli = parse(args);
+        apply(&mut cli, false)?;
+        Ok(cli)
+    }
+
+    #[test]
+    fn test_allowed() {
+        let cli = apply_to(&["--password-fd", "3", "github"]).unwrap();
+        assert!(cli.masked);
+        assert_eq!(cli.confirm_flag(), Some(true));
+        assert!(cli.check().is_ok());
+
+        let cli = apply_to(&["--pinentry", "--mask-style", "none", "github"]);
+        assert!(cli.unwrap().masked);
+        assert!(apply_to(&["--pinentry=/usr/bin/pinentry-tty", "-l", "20", "x"]).is_ok());
+        // Nothing it prints can be kept
+        assert!(apply_to(&["--pinentry", "--entropy", "--quiet", "github"]).is_ok());
+    }
+
+    #[test]
+    fn test_each_conflict() {
+        let conflicts: &[(&[&str], &str)] = &[
+            (&["--password-file", "m", "github"], "--password-file"),
+            (&["--password-gpg", "m.gpg", "github"], "--password-gpg"),
+            (&["--pinentry", "--agent", "github"], "--agent"),
+            (&["--pinentry", "--no-confirm", "github"], "--no-confirm"),
+            (&["--pinentry", "--log", "github"], "--log"),
+            (&["--pinentry", "--batch"], "--batch"),
+            (&["--pinentry", "--batch", "--jobs", "4"], "--batch"),
+            (&["--pinentry", "--interactive"], "--interactive"),
+            (&["--pinentry", "--format", "{key}", "github"], "--format"),
+            (&["--pinentry", "--null", "github"], "--null"),
+            (&["--pinentry", "--raw", "github"], "--raw"),
+            (&["--pinentry", "--copy", "github"], "--copy"),
+            (
+                &["--pinentry", "--copy-user-then-pass", "x"],
+                "--copy-user-then-pass",
+            ),
+            (
+                &["--pinentry", "--show-timeout", "5s", "x"],
+                "--show-timeout",
+            ),
+            // The first conflict is named
+            (&["--password-file", "m", "--log", "x"], "--password-file"),
+        ];
+        for (args, flag) in conflicts {
+            assert_eq!(
+                apply_to(args).err(),
+                Some(Violation::Conflict(flag)),
+                "{:?}",
+                args
+            );
+        }
+        #[cfg(feature = "keyring")]
+        assert_eq!(
+            apply_to(&["--use-keyring", "github"]).err(),
+            Some(Violation::Conflict("--use-keyring"))
+        );
+        #[cfg(feature = "autotype")]
+        assert_eq!(
+            apply_to(&["--pinentry", "--autotype", "github"]).err(),
+            Some(Violation::Conflict("--autotype"))
+        );
+        #[cfg(feature = "qr")]
+        assert_eq!(
+            apply_to(&["--pinentry", "--qr", "github"]).err(),
+            Some(Violation::Conflict("--qr"))
+        );
+    }
+
+    #[test]
+    fn test_master_source() {
+        assert_eq!(apply_to(&["github"]).err(), Some(Violation::NoSource));
+        assert_eq!(
+            Violation::NoSource.kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn test_one_key() {
+        assert_eq!(
+            apply_to(&["--pinentry", "github", "gitlab"]).err(),
+            Some(Violation::ManyKeys)
+        );
+    }
+
+    #[test]
+    fn test_stdout_is_file() {
+        let mut cli = parse(&["--password-fd", "3", "github"]);
+        assert_eq!(apply(&mut cli, true), Err(Violation::StdoutIsFile));
+        // Nothing is turned on for a run that is refused
+        assert!(!cli.masked);
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(
+            Violation::Conflict("--log").to_string(),
+            "--paranoid and --log cannot be used together"
+        );
+        assert_eq!(
+            Violation::StdoutIsFile.to_string(),
+            "--paranoid refuses to run with standard output redirected to a file"
+        );
+        assert_eq!(
+            Violation::Conflict("--raw").kind(),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn test_without_paranoid() {
+        let mut cli = Cli::try_parse_from(["fp", "--log", "github"]).unwrap();
+        enforce(&mut cli).unwrap();
+        assert!(!cli.masked);
+        assert!(!cli.confirm);
+    }
+}
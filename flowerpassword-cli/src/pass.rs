@@ -0,0 +1,364 @@
+//! `fp pass`, derived passwords kept in the `pass` password store
+//!
+//! `fp pass sync` stores the password of every configured site as
+//! `flowerpassword/<name>` with `pass insert --multiline --force`, so they sit
+//! next to the store's random secrets; pass itself honours
+//! `$PASSWORD_STORE_DIR`. `fp pass rm` removes them again. Nothing outside the
+//! `flowerpassword/` prefix is ever touched: a site name that would leave it is
+//! refused before pass runs.
+//!
+//! A failure on one entry is reported and the others are still handled, and the
+//! run then fails as a whole.
+
+use crate::cli::derive;
+use crate::error::CliError;
+use crate::i18n::{self, Msg};
+use flowerpassword::config::Config;
+use flowerpassword::FpGenerator;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// The directory of the store that `fp pass` keeps its entries in
+pub(crate) const PREFIX: &str = "flowerpassword";
+
+/// Error type for running pass
+#[derive(Debug)]
+pub(crate) enum PassError {
+    /// The pass program is not on `PATH`
+    NotInstalled,
+    /// pass ran and failed, with what it wrote to standard error
+    Failed(String),
+    /// pass could not be started or fed
+    Io(io::Error),
+    /// A site name that would put its entry outside `flowerpassword/`
+    OutsidePrefix(String),
+    /// Some entries failed, as reported one by one
+    Incomplete { failed: usize, total: usize },
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassError::NotInstalled => i18n::write(f, Msg::NotInstalled, &[&"pass"]),
+            PassError::Failed(message) => i18n::write(f, Msg::PassFailed, &[message]),
+            PassError::Io(error) => i18n::write(f, Msg::PassIo, &[error]),
+            PassError::OutsidePrefix(name) => i18n::write(
+                f,
+                Msg::PassOutsidePrefix,
+                &[&format_args!("{:?}", name), &PREFIX],
+            ),
+            PassError::Incomplete { failed, total } => {
+                i18n::write(f, Msg::PassIncomplete, &[failed, total])
+            }
+        }
+    }
+}
+
+impl Error for PassError {}
+
+/// A way to run pass
+pub(crate) trait Runner {
+    /// Runs pass with `args`, writing `input` to its standard input
+    fn run(&mut self, args: &[&str], input: &[u8]) -> Result<(), PassError>;
+}
+
+/// A [`Runner`] running the `pass` program
+#[derive(Debug, Clone)]
+pub(crate) struct Pass {
+    program: PathBuf,
+}
+
+impl Default for Pass {
+    fn default() -> Self {
+        Pass {
+            program: PathBuf::from("pass"),
+        }
+    }
+}
+
+impl Runner for Pass {
+    fn run(&mut self, args: &[&str], input: &[u8]) -> Result<(), PassError> {
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => PassError::NotInstalled,
+                _ => PassError::Io(error),
+            })?;
+        // A password is far below a pipe buffer, so this cannot block on stderr;
+        // the pipe is closed at the end of the statement, ending the input
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input)
+            .map_err(PassError::Io)?;
+        let output = child.wait_with_output().map_err(PassError::Io)?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PassError::Failed(match stderr.trim() {
+                "" => output.status.to_string(),
+                message => message.to_string(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the entry `fp pass` keeps the password of site `name` in
+///
+/// # Errors
+///
+/// Returns `PassError::OutsidePrefix` if `name` is empty, absolute, or has an
+/// empty, `.` or `..` component, any of which could name another entry.
+pub(crate) fn entry(name: &str) -> Result<String, PassError> {
+    let safe = !name.contains(['\\', '\0', '\n', '\r'])
+        && name
+            .split('/')
+            .all(|component| !matches!(component, "" | "." | ".."));
+    if !safe {
+        return Err(PassError::OutsidePrefix(name.to_string()));
+    }
+    Ok(format!("{}/{}", PREFIX, name))
+}
+
+/// An entry that `fp pass` could not handle, and why
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) name: String,
+    pub(crate) error: CliError,
+}
+
+/// Stores the password of every site in `config` under `flowerpassword/`
+///
+/// Returns the sites that failed, in name order, after trying them all.
+pub(crate) fn sync(
+    runner: &mut impl Runner,
+    config: &Config,
+    generator: &FpGenerator,
+) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    for name in config.sites.keys() {
+        let stored = entry(name).map_err(CliError::from).and_then(|entry| {
+            let derived = derive(generator, config, name, None)?;
+            let input = Zeroizing::new(format!("{}\n", derived.password));
+            let args = ["insert", "--multiline", "--force", &entry];
+            Ok(runner.run(&args, input.as_bytes())?)
+        });
+        if let Err(error) = stored {
+            failures.push(Failure {
+                name: name.clone(),
+                error,
+            });
+        }
+    }
+    failures
+}
+
+/// Removes the entries of the sites `names`, or the whole `flowerpassword/`
+/// directory when there are none
+///
+/// Returns the sites that failed, after trying them all.
+pub(crate) fn remove(runner: &mut impl Runner, names: &[String]) -> Vec<Failure> {
+    if names.is_empty() {
+        let removed = runner.run(&["rm", "--recursive", "--force", PREFIX], b"");
+        return match removed {
+            Ok(()) => Vec::new(),
+            Err(error) => vec![Failure {
+                name: format!("{}/", PREFIX),
+                error: error.into(),
+            }],
+        };
+    }
+    let mut failures = Vec::new();
+    for name in names {
+        let removed = entry(name).and_then(|entry| runner.run(&["rm", "--force", &entry], b""));
+        if let Err(error) = removed {
+            failures.push(Failure {
+                name: name.clone(),
+                error: error.into(),
+            });
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+
+    /// A [`Runner`] recording every invocation, failing for arguments in `fail`
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<(Vec<String>, String)>,
+        fail: Vec<&'static str>,
+    }
+
+    impl Runner for Recorder {
+        fn run(&mut self, args: &[&str], input: &[u8]) -> Result<(), PassError> {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            let input = String::from_utf8(input.to_vec()).unwrap();
+            let failed = args.iter().any(|arg| self.fail.contains(&arg.as_str()));
+            self.calls.push((args, input));
+            match failed {
+                true => Err(PassError::Failed("gpg: no public key".to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    const SITES: &str = r#"
+[sites.github]
+key = "github.com"
+length = 16
+
+[sites."work/mail"]
+key = "key"
+length = 8
+"#;
+
+    fn generator() -> FpGenerator {
+        FpGenerator::new("test".to_string(), FpGenerator::DEFAULT_LENGTH).unwrap()
+    }
+
+    fn call(args: &[&str], input: &str) -> (Vec<String>, String) {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        (args, input.to_string())
+    }
+
+    #[test]
+    fn test_sync_invocations() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut pass = Recorder::default();
+        assert!(sync(&mut pass, &config, &generator()).is_empty());
+        assert_eq!(
+            pass.calls,
+            [
+                call(
+                    &["insert", "--multiline", "--force", "flowerpassword/github"],
+                    "D04175F7A9c7Ab4a\n"
+                ),
+                call(
+                    &[
+                        "insert",
+                        "--multiline",
+                        "--force",
+                        "flowerpassword/work/mail"
+                    ],
+                    "KAC5fEf0\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_failure_does_not_stop_the_rest() {
+        let config = Config::from_toml_str("[sites.a]\n[sites.c]\n[sites.d]\n", "config.toml")
+            .unwrap()
+            .config;
+        let mut pass = Recorder {
+            fail: vec!["flowerpassword/c"],
+            ..Recorder::default()
+        };
+        let failures = sync(&mut pass, &config, &generator());
+        let failed: Vec<&str> = failures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(failed, ["c"]);
+        assert_eq!(
+            failures[0].error.to_string(),
+            "pass failed: gpg: no public key"
+        );
+        let entries: Vec<&str> = pass
+            .calls
+            .iter()
+            .map(|(args, _)| args[3].as_str())
+            .collect();
+        assert_eq!(
+            entries,
+            ["flowerpassword/a", "flowerpassword/c", "flowerpassword/d"]
+        );
+    }
+
+    #[test]
+    fn test_entries_stay_under_the_prefix() {
+        assert_eq!(entry("github").unwrap(), "flowerpassword/github");
+        assert_eq!(entry("work/mail").unwrap(), "flowerpassword/work/mail");
+        assert_eq!(entry("..hidden").unwrap(), "flowerpassword/..hidden");
+        for name in [
+            "",
+            "..",
+            "../email",
+            "work/../../email",
+            "/etc/passwd",
+            "work//mail",
+            "work/",
+            "./github",
+            "a\\..\\b",
+            "line\nbreak",
+        ] {
+            assert!(
+                matches!(entry(name), Err(PassError::OutsidePrefix(_))),
+                "{:?}",
+                name
+            );
+        }
+
+        // Unsafe names never reach pass, and the others are still synced
+        let config = Config::from_toml_str("[sites.\"../email\"]\n[sites.github]\n", "config.toml")
+            .unwrap()
+            .config;
+        let mut pass = Recorder::default();
+        let failures = sync(&mut pass, &config, &generator());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "../email");
+        assert_eq!(pass.calls.len(), 1);
+        assert_eq!(pass.calls[0].0[3], "flowerpassword/github");
+    }
+
+    #[test]
+    fn test_remove_invocations() {
+        let mut pass = Recorder::default();
+        assert!(remove(&mut pass, &[]).is_empty());
+        assert_eq!(
+            pass.calls,
+            [call(
+                &["rm", "--recursive", "--force", "flowerpassword"],
+                ""
+            )]
+        );
+
+        let mut pass = Recorder {
+            fail: vec!["flowerpassword/github"],
+            ..Recorder::default()
+        };
+        let names = ["github", "../x", "mail"].map(String::from);
+        let failures = remove(&mut pass, &names);
+        let failed: Vec<&str> = failures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(failed, ["github", "../x"]);
+        assert_eq!(
+            pass.calls,
+            [
+                call(&["rm", "--force", "flowerpassword/github"], ""),
+                call(&["rm", "--force", "flowerpassword/mail"], ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_program() {
+        let mut pass = Pass {
+            program: PathBuf::from("/nonexistent/pass"),
+        };
+        assert!(matches!(
+            pass.run(&["ls"], b""),
+            Err(PassError::NotInstalled)
+        ));
+    }
+}
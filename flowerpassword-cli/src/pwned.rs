@@ -0,0 +1,71 @@
+//! `--check-pwned`, a warning for derived passwords known from breaches
+//!
+//! Each password is looked up in Pwned Passwords as `flowerpassword::hibp`
+//! does it, over `curl`, so only the first five hex characters of its SHA-1
+//! hash leave the machine. A hit does not stop the run: the password is still
+//! printed, with a warning to change the site's length or key. Neither does a
+//! lookup that fails, since being offline is no reason to withhold a password.
+
+use crate::i18n::Msg;
+use flowerpassword::hibp::{check_pwned_with, RangeClient};
+
+/// Returns the warning for the password of `name` if `client` finds it in
+/// breaches or cannot tell
+pub(crate) fn check(client: impl RangeClient, name: &str, password: &str) -> Option<String> {
+    match check_pwned_with(client, password) {
+        Ok(None) => None,
+        Ok(Some(count)) => Some(Msg::Pwned.fill(&[&name, &count])),
+        Err(error) => Some(Msg::PwnedUnchecked.fill(&[&name, &error])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::hibp::HibpError;
+
+    /// A client answering with `body`, recording the prefixes it was sent
+    struct Canned {
+        body: Result<&'static str, &'static str>,
+        sent: Vec<String>,
+    }
+
+    impl RangeClient for Canned {
+        fn get_range(&mut self, prefix: &str) -> Result<String, HibpError> {
+            self.sent.push(prefix.to_string());
+            self.body
+                .map(str::to_string)
+                .map_err(|message| HibpError::Http(message.to_string()))
+        }
+    }
+
+    fn canned(body: Result<&'static str, &'static str>) -> Canned {
+        Canned {
+            body,
+            sent: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let mut hit = canned(Ok("1E4C9B93F3F0682250B6CF8331B7EE68FD8:42\r\n"));
+        assert_eq!(
+            check(&mut hit, "github", "password").as_deref(),
+            Some("The password of github appears 42 times in Pwned Passwords")
+        );
+        assert_eq!(hit.sent, ["5BAA6"]);
+
+        let mut miss = canned(Ok("0000000000000000000000000000000000A:3\r\n"));
+        assert_eq!(check(&mut miss, "github", "password"), None);
+
+        let mut down = canned(Err("Could not resolve host"));
+        assert_eq!(
+            check(&mut down, "github", "password").as_deref(),
+            Some(
+                "Could not check github against Pwned Passwords: \
+                 Pwned Passwords request failed: Could not resolve host"
+            )
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! `--qr` output
+//!
+//! Renders a derived password as a QR code, to carry it to a phone without a
+//! cloud clipboard. The payload is the password and nothing else, so any scanner
+//! shows exactly what to type. With `--qr-uri` it is instead the
+//! `flowerpassword://derive` link for the key and length, for apps that derive
+//! the password themselves; that QR does not contain the password at all.
+
+use clap::ValueEnum;
+use flowerpassword::uri::FpUriRequest;
+use qrcode::render::{svg, unicode};
+use qrcode::types::QrError;
+use qrcode::QrCode;
+
+/// Width and height of one module in SVG output, in pixels
+const SVG_MODULE_SIZE: u32 = 8;
+
+/// Escape sequences for black on bright white, so the code scans on dark themes
+const ANSI_START: &str = "\x1b[30;107m";
+const ANSI_END: &str = "\x1b[0m";
+
+/// How `--qr` renders the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum QrFormat {
+    /// Unicode half blocks with explicit colours, two rows per line
+    Ansi,
+    /// `#` and spaces, two characters per module
+    Ascii,
+    /// An SVG image, written to `--qr-out`
+    Svg,
+}
+
+/// Returns what the QR code for `password` should hold
+pub(crate) fn payload(key: &str, password: &str, uri: bool) -> String {
+    if uri {
+        FpUriRequest {
+            key: key.to_string(),
+            length: Some(password.chars().count()),
+            copy: false,
+        }
+        .to_string()
+    } else {
+        password.to_string()
+    }
+}
+
+/// Renders `payload` as a QR code in `format`, with the standard quiet zone
+pub(crate) fn render(payload: &str, format: QrFormat) -> Result<String, QrError> {
+    let code = QrCode::new(payload.as_bytes())?;
+    Ok(match format {
+        QrFormat::Ansi => {
+            let blocks = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Dark)
+                .light_color(unicode::Dense1x2::Light)
+                .build();
+            let mut out = String::new();
+            for line in blocks.lines() {
+                out.push_str(ANSI_START);
+                out.push_str(line);
+                out.push_str(ANSI_END);
+                out.push('\n');
+            }
+            out
+        }
+        QrFormat::Ascii => {
+            let mut out = code
+                .render::<char>()
+                .dark_color('#')
+                .light_color(' ')
+                .module_dimensions(2, 1)
+                .build();
+            out.push('\n');
+            out
+        }
+        QrFormat::Svg => code
+            .render::<svg::Color>()
+            .module_dimensions(SVG_MODULE_SIZE, SVG_MODULE_SIZE)
+            .build(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Modules of the quiet zone `qrcode` draws around every code
+    const QUIET_ZONE: usize = 4;
+
+    /// Decodes a square module matrix that still has its quiet zone
+    fn decode(matrix: &[Vec<bool>]) -> String {
+        let size = matrix.len() - 2 * QUIET_ZONE;
+        let grid = rqrr::SimpleGrid::from_func(size, |x, y| matrix[y + QUIET_ZONE][x + QUIET_ZONE]);
+        rqrr::Grid::new(grid).decode().unwrap().1
+    }
+
+    fn decode_ascii(text: &str) -> String {
+        let matrix: Vec<Vec<bool>> = text
+            .lines()
+            .map(|line| {
+                let line = line.as_bytes();
+                line.chunks(2).map(|module| module == b"##").collect()
+            })
+            .collect();
+        decode(&matrix)
+    }
+
+    fn decode_ansi(text: &str) -> String {
+        let mut matrix = Vec::new();
+        for line in text.lines() {
+            let blocks = line
+                .strip_prefix(ANSI_START)
+                .and_then(|line| line.strip_suffix(ANSI_END))
+                .unwrap();
+            let (top, bottom): (Vec<bool>, Vec<bool>) = blocks
+                .chars()
+                .map(|c| match c {
+                    '█' => (true, true),
+                    '▀' => (true, false),
+                    '▄' => (false, true),
+                    ' ' => (false, false),
+                    c => panic!("unexpected {:?}", c),
+                })
+                .unzip();
+            matrix.push(top);
+            matrix.push(bottom);
+        }
+        // An odd number of rows leaves a blank half row at the bottom
+        let width = matrix[0].len();
+        matrix.truncate(width);
+        decode(&matrix)
+    }
+
+    /// Reads back the `M{x} {y}h{w}v{h}H{x}V{y}` rectangles of the dark path
+    fn decode_svg(svg: &str) -> String {
+        let width: usize = svg
+            .split("width=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let modules = width / SVG_MODULE_SIZE as usize;
+        let mut matrix = vec![vec![false; modules]; modules];
+
+        let path = svg.split(" d=\"").nth(1).unwrap();
+        let path = path.split('"').next().unwrap();
+        for rect in path.split('M').skip(1) {
+            let numbers: Vec<usize> = rect
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|n| !n.is_empty())
+                .map(|n| n.parse().unwrap())
+                .collect();
+            let [left, top, w, h] = [numbers[0], numbers[1], numbers[2], numbers[3]]
+                .map(|n| n / SVG_MODULE_SIZE as usize);
+            for row in &mut matrix[top..top + h] {
+                for module in &mut row[left..left + w] {
+                    *module = true;
+                }
+            }
+        }
+        decode(&matrix)
+    }
+
+    #[test]
+    fn test_payload() {
+        assert_eq!(
+            payload("github.com", "D04175F7A9c7Ab4a", false),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(
+            payload("mail.google.com", "D04175F7A9c7", true),
+            "flowerpassword://derive?key=mail.google.com&len=12"
+        );
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for password in ["D04175F7A9c7Ab4a", "K3A2a66Bf88b628c0dA3e7Fc4bB5a6D2"] {
+            assert_eq!(
+                decode_ascii(&render(password, QrFormat::Ascii).unwrap()),
+                password
+            );
+            assert_eq!(
+                decode_ansi(&render(password, QrFormat::Ansi).unwrap()),
+                password
+            );
+            assert_eq!(
+                decode_svg(&render(password, QrFormat::Svg).unwrap()),
+                password
+            );
+        }
+        let uri = payload("github.com", "D04175F7", true);
+        assert_eq!(decode_svg(&render(&uri, QrFormat::Svg).unwrap()), uri);
+    }
+
+    #[test]
+    fn test_ansi_resets_every_line() {
+        let text = render("D04175F7", QrFormat::Ansi).unwrap();
+        assert!(text
+            .lines()
+            .all(|line| line.starts_with(ANSI_START) && line.ends_with(ANSI_END)));
+    }
+}
@@ -0,0 +1,533 @@
+//! `fp --interactive`, deriving many passwords under one master password
+//!
+//! The master password is read once, from the same sources as for a single run,
+//! into a buffer wiped on drop, and handed to an [`FpGenerator`], which keeps it
+//! in locked memory and wipes it when dropped. Each line is then
+//! a site name or key, optionally followed by a length, or one of the commands:
+//!
+//! | Command              | Effect                                                  |
+//! |----------------------|---------------------------------------------------------|
+//! | `:length N`          | derive N characters unless a line gives its own length  |
+//! | `:length default`    | go back to the configured lengths                       |
+//! | `:copy on` or `off`  | copy passwords to the clipboard instead of printing them |
+//! | `:lock`              | wipe the master password; the next key asks for it again |
+//! | `:help`              | list the commands                                       |
+//! | `:quit`              | wipe the master password and exit, as does Ctrl-D        |
+//!
+//! Ctrl-C discards the line being typed. Copied passwords stay on the clipboard
+//! for [`DEFAULT_TTL`], or until the next copy or the end of the session, and are
+//! then replaced by what was there before.
+//!
+//! Without a terminal, lines come from standard input as they would with
+//! `--batch`, and the master password after `:lock` is the line following the
+//! first key that needs it.
+
+use crate::cli::{derive, into_generator};
+use crate::error::CliError;
+use crate::i18n::{self, Msg};
+use crate::master::{self, Confirm, Prompter, Terminal};
+use flowerpassword::clipboard::{copy_with_ttl, ClipboardError, ClipboardGuard, DEFAULT_TTL};
+use flowerpassword::config::Config;
+use flowerpassword::FpGenerator;
+use flowerpassword_core::validate_length;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use zeroize::Zeroizing;
+
+/// Prompt shown for every line
+pub(crate) const PROMPT: &str = "fp> ";
+
+/// One line read from the console
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Input {
+    /// A line, without its line ending
+    Line(String),
+    /// The line was cancelled with Ctrl-C
+    Interrupted,
+    /// The input ended, or Ctrl-D was pressed
+    Eof,
+}
+
+/// Where the session reads lines from and sends its results
+pub(crate) trait Console {
+    /// Reads a line after showing `prompt`
+    fn read_line(&mut self, prompt: &str) -> io::Result<Input>;
+
+    /// Reads the master password again after `:lock`
+    fn read_master(&mut self) -> Result<Zeroizing<String>, CliError>;
+
+    /// Writes a password, or anything else meant for standard output
+    fn print(&mut self, text: &str) -> io::Result<()>;
+
+    /// Tells the user something that is not a result
+    fn notice(&mut self, text: &str);
+
+    /// Puts `password` on the clipboard until the guard is dropped
+    fn copy(&mut self, password: &str) -> Result<ClipboardGuard, ClipboardError>;
+}
+
+/// A parsed input line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// Derive for a site name or key, at a length if given
+    Derive { name: String, length: Option<usize> },
+    /// `:length`, with `None` for the configured lengths
+    Length(Option<usize>),
+    /// `:copy on` or `:copy off`
+    Copy(bool),
+    /// `:lock`
+    Lock,
+    /// `:help`
+    Help,
+    /// `:quit`
+    Quit,
+    /// A blank line
+    Nothing,
+}
+
+/// Error type for lines that do not parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LineError {
+    /// A `:` command that does not exist
+    UnknownCommand(String),
+    /// A command with missing or wrong arguments, with how to use it
+    Usage(&'static str),
+    /// A length that is not a number between 2 and 32
+    BadLength(String),
+    /// More than a key and a length
+    TooManyWords,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineError::UnknownCommand(command) => i18n::write(f, Msg::UnknownCommand, &[command]),
+            LineError::Usage(usage) => i18n::write(f, Msg::Usage, &[usage]),
+            LineError::BadLength(length) => {
+                i18n::write(f, Msg::BadLength, &[&format_args!("{:?}", length)])
+            }
+            LineError::TooManyWords => f.write_str(Msg::TooManyWords.text()),
+        }
+    }
+}
+
+impl Error for LineError {}
+
+fn parse_length(word: &str) -> Result<usize, LineError> {
+    word.parse()
+        .ok()
+        .filter(|&length| validate_length(length).is_ok())
+        .ok_or_else(|| LineError::BadLength(word.to_string()))
+}
+
+/// Parses one input line
+pub(crate) fn parse(line: &str) -> Result<Action, LineError> {
+    let mut words = line.split_whitespace();
+    let Some(first) = words.next() else {
+        return Ok(Action::Nothing);
+    };
+    let argument = words.next();
+    if words.next().is_some() {
+        return Err(LineError::TooManyWords);
+    }
+
+    let Some(command) = first.strip_prefix(':') else {
+        return Ok(Action::Derive {
+            name: first.to_string(),
+            length: argument.map(parse_length).transpose()?,
+        });
+    };
+    let action = match (command, argument) {
+        ("length" | "l", Some("default")) => Action::Length(None),
+        ("length" | "l", Some(length)) => Action::Length(Some(parse_length(length)?)),
+        ("length" | "l", None) => return Err(LineError::Usage(":length N|default")),
+        ("copy" | "c", Some("on")) => Action::Copy(true),
+        ("copy" | "c", Some("off")) => Action::Copy(false),
+        ("copy" | "c", _) => return Err(LineError::Usage(":copy on|off")),
+        ("lock", None) => Action::Lock,
+        ("lock", Some(_)) => return Err(LineError::Usage(":lock")),
+        ("help" | "h" | "?", None) => Action::Help,
+        ("help" | "h" | "?", Some(_)) => return Err(LineError::Usage(":help")),
+        ("quit" | "q" | "exit", None) => Action::Quit,
+        ("quit" | "q" | "exit", Some(_)) => return Err(LineError::Usage(":quit")),
+        _ => return Err(LineError::UnknownCommand(command.to_string())),
+    };
+    Ok(action)
+}
+
+/// The state of an interactive session
+pub(crate) struct Session<'a> {
+    config: &'a Config,
+    generator: Option<FpGenerator>,
+    length: Option<usize>,
+    copy: bool,
+    clipboard: Option<ClipboardGuard>,
+}
+
+impl<'a> Session<'a> {
+    /// Starts unlocked with `master`, deriving at `length` if given
+    pub(crate) fn new(
+        config: &'a Config,
+        master: Zeroizing<String>,
+        length: Option<usize>,
+    ) -> Result<Self, CliError> {
+        Ok(Session {
+            config,
+            generator: Some(into_generator(master)?),
+            length,
+            copy: false,
+            clipboard: None,
+        })
+    }
+
+    /// Returns whether the master password has been wiped
+    pub(crate) fn is_locked(&self) -> bool {
+        self.generator.is_none()
+    }
+
+    /// Wipes the master password and restores the clipboard
+    pub(crate) fn lock(&mut self) {
+        self.generator = None;
+        self.clipboard = None;
+    }
+
+    /// Carries out one action other than `:quit`
+    fn apply(&mut self, console: &mut impl Console, action: Action) -> Result<(), CliError> {
+        match action {
+            Action::Derive { name, length } => {
+                let generator = match &mut self.generator {
+                    Some(generator) => generator,
+                    locked => {
+                        let master = console.read_master()?;
+                        locked.insert(into_generator(master)?)
+                    }
+                };
+                let derived = derive(generator, self.config, &name, length.or(self.length))?;
+                if self.copy {
+                    // Restore the previous copy first, or it would be restored later
+                    self.clipboard = None;
+                    self.clipboard = Some(console.copy(&derived.password)?);
+                    console.notice(&Msg::ReplCopied.fill(&[&derived.key, &DEFAULT_TTL.as_secs()]));
+                } else {
+                    console.print(&format!("{}\n", derived.password))?;
+                }
+            }
+            Action::Length(length) => self.length = length,
+            Action::Copy(copy) => self.copy = copy,
+            Action::Lock if self.is_locked() => console.notice(Msg::ReplAlreadyLocked.text()),
+            Action::Lock => {
+                self.lock();
+                console.notice(Msg::ReplLocked.text());
+            }
+            Action::Help => console.print(&format!("{}\n", Msg::ReplHelp.text()))?,
+            Action::Quit | Action::Nothing => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reads and carries out lines until `:quit` or the end of the input
+///
+/// Mistakes on a line, such as an unknown command or a master password that
+/// does not match, are reported and the session goes on; only I/O errors end it.
+/// The master password is wiped on the way out either way.
+pub(crate) fn run(session: &mut Session, console: &mut impl Console) -> Result<(), CliError> {
+    let result = (|| loop {
+        let line = match console.read_line(PROMPT)? {
+            Input::Line(line) => line,
+            Input::Interrupted => continue,
+            Input::Eof => return Ok(()),
+        };
+        match parse(&line) {
+            Ok(Action::Quit) => return Ok(()),
+            Ok(action) => match session.apply(console, action) {
+                Err(CliError::Io(error)) => return Err(CliError::Io(error)),
+                Err(error) => console.notice(&error.to_string()),
+                Ok(()) => {}
+            },
+            Err(error) => console.notice(&error.to_string()),
+        }
+    })();
+    session.lock();
+    result
+}
+
+/// The process's console: a line editor on a terminal, plain lines otherwise
+pub(crate) struct Stdio {
+    editor: Option<DefaultEditor>,
+}
+
+impl Stdio {
+    pub(crate) fn new() -> Result<Self, CliError> {
+        let editor = if Terminal.is_terminal() {
+            Some(DefaultEditor::new().map_err(readline_error)?)
+        } else {
+            None
+        };
+        Ok(Stdio { editor })
+    }
+}
+
+fn readline_error(error: ReadlineError) -> io::Error {
+    match error {
+        ReadlineError::Io(error) => error,
+        error => io::Error::other(error),
+    }
+}
+
+impl Console for Stdio {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Input> {
+        let Some(editor) = &mut self.editor else {
+            return Ok(match master::read_line(&mut io::stdin().lock())? {
+                Some(line) => Input::Line(line),
+                None => Input::Eof,
+            });
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                editor.add_history_entry(&line).map_err(readline_error)?;
+                Ok(Input::Line(line))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => Ok(Input::Eof),
+            Err(error) => Err(readline_error(error)),
+        }
+    }
+
+    fn read_master(&mut self) -> Result<Zeroizing<String>, CliError> {
+        let mut input = io::stdin().lock();
+        master::read_master(&mut Terminal, &mut input, Confirm::Off, |_| {})
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        out.write_all(text.as_bytes())?;
+        out.flush()
+    }
+
+    fn notice(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+
+    fn copy(&mut self, password: &str) -> Result<ClipboardGuard, ClipboardError> {
+        copy_with_ttl(password, DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{copy_with_ttl_using, MemoryClipboard};
+    use flowerpassword::config::Config;
+    use flowerpassword::memlock::LockStatus;
+    use std::collections::VecDeque;
+
+    /// A console replaying `inputs`, with `masters` for unlocking after `:lock`
+    struct Script {
+        inputs: VecDeque<Input>,
+        masters: VecDeque<&'static str>,
+        printed: String,
+        notices: Vec<String>,
+        clipboard: MemoryClipboard,
+    }
+
+    impl Script {
+        fn new(lines: &[&str]) -> Self {
+            Script {
+                inputs: lines.iter().map(|line| line_or_key(line)).collect(),
+                masters: VecDeque::new(),
+                printed: String::new(),
+                notices: Vec::new(),
+                clipboard: MemoryClipboard::new(Some("shopping list")),
+            }
+        }
+    }
+
+    fn line_or_key(line: &str) -> Input {
+        match line {
+            "^C" => Input::Interrupted,
+            "^D" => Input::Eof,
+            line => Input::Line(line.to_string()),
+        }
+    }
+
+    impl Console for Script {
+        fn read_line(&mut self, prompt: &str) -> io::Result<Input> {
+            assert_eq!(prompt, PROMPT);
+            Ok(self.inputs.pop_front().unwrap_or(Input::Eof))
+        }
+
+        fn read_master(&mut self) -> Result<Zeroizing<String>, CliError> {
+            self.masters
+                .pop_front()
+                .map(|master| Zeroizing::new(master.to_string()))
+                .ok_or(CliError::NoMaster)
+        }
+
+        fn print(&mut self, text: &str) -> io::Result<()> {
+            self.printed.push_str(text);
+            Ok(())
+        }
+
+        fn notice(&mut self, text: &str) {
+            self.notices.push(text.to_string());
+        }
+
+        fn copy(&mut self, password: &str) -> Result<ClipboardGuard, ClipboardError> {
+            copy_with_ttl_using(self.clipboard.clone(), password, DEFAULT_TTL)
+        }
+    }
+
+    const SITES: &str = "[sites.github]\nkey = \"github.com\"\nlength = 16\n";
+
+    fn run_script(console: &mut Script) -> Session<'static> {
+        let config = Box::leak(Box::new(
+            Config::from_toml_str(SITES, "config.toml").unwrap().config,
+        ));
+        let mut session = Session::new(config, Zeroizing::new("test".to_string()), None).unwrap();
+        run(&mut session, console).unwrap();
+        session
+    }
+
+    #[test]
+    fn test_parse() {
+        let derive = |name: &str, length| Action::Derive {
+            name: name.to_string(),
+            length,
+        };
+        assert_eq!(parse("github"), Ok(derive("github", None)));
+        assert_eq!(
+            parse("  github.com 12 "),
+            Ok(derive("github.com", Some(12)))
+        );
+        assert_eq!(parse(":length 24"), Ok(Action::Length(Some(24))));
+        assert_eq!(parse(":length default"), Ok(Action::Length(None)));
+        assert_eq!(parse(":copy on"), Ok(Action::Copy(true)));
+        assert_eq!(parse(":copy off"), Ok(Action::Copy(false)));
+        assert_eq!(parse(":lock"), Ok(Action::Lock));
+        assert_eq!(parse(":q"), Ok(Action::Quit));
+        assert_eq!(parse(""), Ok(Action::Nothing));
+        assert_eq!(parse("   "), Ok(Action::Nothing));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse(":lenght 24"),
+            Err(LineError::UnknownCommand("lenght".to_string()))
+        );
+        assert_eq!(
+            parse("github 40"),
+            Err(LineError::BadLength("40".to_string()))
+        );
+        assert_eq!(parse(":length"), Err(LineError::Usage(":length N|default")));
+        assert_eq!(
+            parse(":copy yes").unwrap_err().to_string(),
+            "Usage: :copy on|off"
+        );
+        assert!(parse(":quit now").is_err());
+        assert_eq!(parse("a b c"), Err(LineError::TooManyWords));
+    }
+
+    #[test]
+    fn test_session_derives_and_keeps_going() {
+        let mut console = Script::new(&[
+            "github",
+            ":lenght 8",
+            "^C",
+            "github.com 8",
+            ":length 12",
+            "github",
+            "github 16",
+            ":length default",
+            "github",
+            ":quit",
+            "never read",
+        ]);
+        let session = run_script(&mut console);
+        assert_eq!(
+            console.printed,
+            "D04175F7A9c7Ab4a\nD04175F7\nD04175F7A9c7\nD04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\n"
+        );
+        assert_eq!(console.notices, ["Unknown command :lenght, see :help"]);
+        assert_eq!(console.inputs, [Input::Line("never read".to_string())]);
+        assert!(session.is_locked());
+    }
+
+    #[test]
+    fn test_lock_wipes_and_asks_again() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let session = Session::new(&config, Zeroizing::new("test".to_string()), None).unwrap();
+        // Held in a `LockedBuf`, which zeroes its pages when the generator drops
+        let generator = session.generator.as_ref().unwrap();
+        if cfg!(any(unix, windows)) {
+            assert_ne!(generator.memory_status(), LockStatus::Unsupported);
+        }
+
+        let mut console = Script::new(&["github", ":lock", "github", "github", "^D", "github"]);
+        console.masters.push_back("password");
+        let session = run_script(&mut console);
+        // The second master password gives different passwords
+        assert_eq!(
+            console.printed,
+            "D04175F7A9c7Ab4a\nKC35d04D964297c7\nKC35d04D964297c7\n"
+        );
+        assert!(console.masters.is_empty());
+        assert_eq!(console.notices, ["Locked: the master password is wiped"]);
+        assert!(session.is_locked());
+    }
+
+    #[test]
+    fn test_failed_unlock_stays_locked() {
+        let mut console = Script::new(&[":lock", ":lock", "github"]);
+        let session = run_script(&mut console);
+        assert!(console.printed.is_empty());
+        assert_eq!(
+            console.notices,
+            [
+                "Locked: the master password is wiped",
+                "Already locked",
+                "No master password on standard input"
+            ]
+        );
+        assert!(session.is_locked());
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut console = Script::new(&[":copy on", "github", ":copy off", "github"]);
+        let clipboard = console.clipboard.clone();
+        run_script(&mut console);
+        assert_eq!(console.printed, "D04175F7A9c7Ab4a\n");
+        assert_eq!(
+            console.notices,
+            ["Copied the password for github.com for 30 seconds"]
+        );
+        // Ending the session restores the clipboard
+        assert_eq!(clipboard.contents().as_deref(), Some("shopping list"));
+    }
+
+    #[test]
+    fn test_copy_replaces_the_previous_copy() {
+        let mut console = Script::new(&[":copy on", "github", "gitlab.com"]);
+        let clipboard = console.clipboard.clone();
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut session = Session::new(&config, Zeroizing::new("test".to_string()), None).unwrap();
+        for _ in 0..3 {
+            let line = match console.inputs.pop_front().unwrap() {
+                Input::Line(line) => line,
+                input => panic!("unexpected {:?}", input),
+            };
+            session.apply(&mut console, parse(&line).unwrap()).unwrap();
+        }
+        let gitlab = FpGenerator::new("test".to_string(), 16)
+            .unwrap()
+            .generate("gitlab.com");
+        assert_eq!(clipboard.contents(), Some(gitlab));
+        session.lock();
+        assert_eq!(clipboard.contents().as_deref(), Some("shopping list"));
+    }
+}
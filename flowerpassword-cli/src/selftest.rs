@@ -0,0 +1,120 @@
+//! `fp self-test`, checking this build against the known-answer vectors
+//!
+//! Every vector of `flowerpassword::vectors` is derived again and shown as
+//! PASS or FAIL with its master password, key and length, so a build that
+//! would give different passwords is caught before it is trusted.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::vectors::VectorResult;
+use std::io::{self, Write};
+
+/// Writes one line per vector and a summary, and fails if any vector did
+pub(crate) fn write(out: &mut impl Write, results: &[VectorResult]) -> Result<(), CliError> {
+    let mut failed = 0;
+    for result in results {
+        let vector = result.vector;
+        let label = format!("{:?} {:?} {}", vector.password, vector.key, vector.length);
+        match (&result.actual, result.passed()) {
+            (_, true) => writeln!(out, "PASS  {}", label)?,
+            (Ok(actual), false) => writeln!(
+                out,
+                "FAIL  {}: expected {}, got {}",
+                label, vector.expected, actual
+            )?,
+            (Err(error), false) => writeln!(out, "FAIL  {}: {}", label, error)?,
+        }
+        failed += usize::from(!result.passed());
+    }
+    match failed {
+        0 => {
+            writeln!(out, "{}", Msg::SelfTestPassed.fill(&[&results.len()]))?;
+            Ok(())
+        }
+        failed => Err(CliError::SelfTest {
+            failed,
+            total: results.len(),
+        }),
+    }
+}
+
+/// Runs the known-answer vectors and writes their results to `out`
+pub(crate) fn run(out: &mut impl Write) -> Result<(), CliError> {
+    write(out, &flowerpassword::vectors::self_test())?;
+    Ok(io::Write::flush(out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::vectors::{KnownAnswer, KNOWN_ANSWERS};
+    use flowerpassword::FlowerPasswordError;
+
+    static WRONG: KnownAnswer = KnownAnswer {
+        password: "test",
+        key: "github.com",
+        length: 16,
+        expected: "D04175F7A9c7Ab4b",
+    };
+
+    static INVALID: KnownAnswer = KnownAnswer {
+        password: "",
+        key: "",
+        length: 40,
+        expected: "",
+    };
+
+    #[test]
+    fn test_every_vector_passes() {
+        let mut out = Vec::new();
+        write(&mut out, &flowerpassword::vectors::self_test()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), KNOWN_ANSWERS.len() + 1);
+        assert!(lines[..KNOWN_ANSWERS.len()]
+            .iter()
+            .all(|line| line.starts_with("PASS  ")));
+        assert!(lines.contains(&"PASS  \"test\" \"github.com\" 16"));
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!("{} known-answer vectors passed", KNOWN_ANSWERS.len())
+        );
+    }
+
+    #[test]
+    fn test_failures_are_shown_and_fail() {
+        let results = [
+            VectorResult {
+                vector: &KNOWN_ANSWERS[0],
+                actual: Ok(KNOWN_ANSWERS[0].expected.to_string()),
+            },
+            VectorResult {
+                vector: &WRONG,
+                actual: Ok("D04175F7A9c7Ab4a".to_string()),
+            },
+            VectorResult {
+                vector: &INVALID,
+                actual: Err(FlowerPasswordError::InvalidLength(40)),
+            },
+        ];
+        let mut out = Vec::new();
+        let error = write(&mut out, &results).unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::SelfTest {
+                failed: 2,
+                total: 3
+            }
+        ));
+        assert_eq!(error.to_string(), "2 of 3 known-answer vectors failed");
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("PASS  "));
+        assert_eq!(
+            lines[1],
+            "FAIL  \"test\" \"github.com\" 16: expected D04175F7A9c7Ab4b, got D04175F7A9c7Ab4a"
+        );
+        assert!(lines[2].starts_with("FAIL  \"\" \"\" 40: "), "{}", lines[2]);
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,240 @@
+//! `fp serve`, local APIs for browser extensions, infrastructure tooling and
+//! the desktop
+//!
+//! `--http ADDR` serves the HTTP API of [`crate::http`], `--grpc ADDR` the
+//! gRPC one of [`crate::grpc`] and `--dbus` the D-Bus service of
+//! [`crate::dbus`]; one run can serve all three. The HTTP and gRPC APIs work
+//! the same way:
+//!
+//! - Each request carries the master password, which is wiped once its
+//!   password is derived; the server keeps none.
+//! - Every request needs the bearer token printed once when the server starts,
+//!   the same for both APIs and random for each run.
+//! - The servers bind only to loopback addresses, since anyone who can reach
+//!   them and learns the token can derive passwords; `--i-know-what-im-doing`
+//!   lifts that.
+//!
+//! The D-Bus service is different: it holds the master password, read when the
+//! server starts, and trusts the bus to tell which user is calling.
+
+#[cfg(all(unix, feature = "dbus"))]
+use crate::dbus::serve as serve_dbus;
+use crate::error::CliError;
+#[cfg(feature = "grpc")]
+use crate::grpc::serve as serve_grpc;
+#[cfg(feature = "http")]
+use crate::http::serve as serve_http;
+use crate::i18n::Msg;
+use flowerpassword::FpGenerator;
+#[cfg(any(feature = "grpc", feature = "http"))]
+use flowerpassword::{fp_code, FlowerPasswordError};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use zeroize::Zeroize;
+
+/// Random bytes in a token, printed as twice as many hex digits
+const TOKEN_BYTES: usize = 32;
+
+/// Returns whether a server may listen on `addr`
+///
+/// # Errors
+///
+/// Returns `CliError::NotLoopback` for an address other machines can reach,
+/// unless `allow_remote`.
+pub(crate) fn check_bind(addr: SocketAddr, allow_remote: bool) -> Result<(), CliError> {
+    if addr.ip().is_loopback() || allow_remote {
+        Ok(())
+    } else {
+        Err(CliError::NotLoopback(addr))
+    }
+}
+
+/// Returns whether `authorization`, the value of the header or metadata,
+/// carries `token`
+///
+/// The token is compared in constant time.
+#[cfg(any(feature = "grpc", feature = "http"))]
+pub(crate) fn authorized(authorization: Option<&[u8]>, token: &str) -> bool {
+    let Some(given) = authorization.and_then(|value| value.strip_prefix(b"Bearer ")) else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Derives the password of a request, of 16 characters unless `length` is
+/// given, and wipes its master password
+#[cfg(any(feature = "grpc", feature = "http"))]
+pub(crate) fn derive(
+    password: &mut String,
+    key: &str,
+    length: Option<usize>,
+) -> Result<String, FlowerPasswordError> {
+    let derived = fp_code(password, key, length.unwrap_or(FpGenerator::DEFAULT_LENGTH));
+    password.zeroize();
+    derived
+}
+
+/// Returns a new random token
+fn new_token() -> io::Result<String> {
+    let mut bytes = [0; TOKEN_BYTES];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    let mut token = String::with_capacity(TOKEN_BYTES * 2);
+    for byte in bytes {
+        let _ = write!(token, "{:02x}", byte);
+    }
+    bytes.zeroize();
+    Ok(token)
+}
+
+/// Binds `addr`, if given, and prints where it listens with `msg`
+async fn bind(
+    addr: Option<SocketAddr>,
+    msg: Msg,
+    out: &mut impl Write,
+) -> io::Result<Option<TcpListener>> {
+    let Some(addr) = addr else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(addr).await?;
+    writeln!(out, "{}", msg.fill(&[&listener.local_addr()?]))?;
+    Ok(Some(listener))
+}
+
+/// Serves the HTTP API on `http`, the gRPC one on `grpc` and the D-Bus service
+/// with `dbus` until the process is stopped, printing where they listen and the
+/// token to `out`
+pub(crate) fn run(
+    http: Option<SocketAddr>,
+    grpc: Option<SocketAddr>,
+    dbus: Option<FpGenerator>,
+    allow_remote: bool,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    for addr in http.iter().chain(&grpc) {
+        check_bind(*addr, allow_remote)?;
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let http = bind(http, Msg::HttpListening, out).await?;
+        let grpc = bind(grpc, Msg::GrpcListening, out).await?;
+        // The D-Bus service alone needs no token
+        let mut token = String::new();
+        if http.is_some() || grpc.is_some() {
+            token = new_token()?;
+            writeln!(out, "{}", Msg::ServeToken.fill(&[&token]))?;
+        }
+        out.flush()?;
+        tokio::try_join!(
+            async {
+                match http {
+                    Some(listener) => serve_http(listener, token.clone()).await,
+                    None => Ok(()),
+                }
+            },
+            async {
+                match grpc {
+                    Some(listener) => serve_grpc(listener, token.clone()).await,
+                    None => Ok(()),
+                }
+            },
+            async {
+                match dbus {
+                    Some(generator) => serve_dbus(generator, out).await,
+                    None => Ok(()),
+                }
+            },
+        )?;
+        Ok(())
+    })
+}
+
+/// There is no `--http` to bind without the `http` feature
+#[cfg(not(feature = "http"))]
+async fn serve_http(_: TcpListener, _: String) -> io::Result<()> {
+    unreachable!("--http needs the http feature")
+}
+
+/// There is no `--grpc` to bind without the `grpc` feature
+#[cfg(not(feature = "grpc"))]
+async fn serve_grpc(_: TcpListener, _: String) -> io::Result<()> {
+    unreachable!("--grpc needs the grpc feature")
+}
+
+/// There is no `--dbus` without the `dbus` feature, or off Unix
+#[cfg(not(all(unix, feature = "dbus")))]
+async fn serve_dbus(_: FpGenerator, _: &mut impl Write) -> io::Result<()> {
+    unreachable!("--dbus needs the dbus feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_bind() {
+        for addr in ["127.0.0.1:0", "127.0.0.2:8080", "[::1]:0"] {
+            assert!(check_bind(addr.parse().unwrap(), false).is_ok(), "{}", addr);
+        }
+        for addr in ["0.0.0.0:0", "192.168.1.2:80", "[::]:0"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            let error = check_bind(addr, false).unwrap_err();
+            assert!(matches!(error, CliError::NotLoopback(a) if a == addr));
+            assert_eq!(error.exit_code(), crate::error::EXIT_USAGE);
+            assert!(check_bind(addr, true).is_ok());
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "grpc", feature = "http"))]
+    fn test_authorized() {
+        const TOKEN: &str = "0123456789abcdef";
+        let bearer = |token: &str| format!("Bearer {}", token).into_bytes();
+        assert!(authorized(Some(&bearer(TOKEN)), TOKEN));
+        assert!(!authorized(None, TOKEN));
+        assert!(!authorized(Some(TOKEN.as_bytes()), TOKEN));
+        assert!(!authorized(Some(&bearer("0123456789abcdeF")), TOKEN));
+        assert!(!authorized(Some(&bearer("0123456789abcde")), TOKEN));
+        assert!(!authorized(Some(b"bearer 0123456789abcdef"), TOKEN));
+        assert!(!authorized(Some(&bearer("")), TOKEN));
+    }
+
+    #[test]
+    #[cfg(any(feature = "grpc", feature = "http"))]
+    fn test_derive() {
+        let mut password = "test".to_string();
+        assert_eq!(
+            derive(&mut password, "github.com", Some(16)).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert!(password.is_empty());
+        let mut password = "password".to_string();
+        assert_eq!(
+            derive(&mut password, "key", None).unwrap(),
+            "K3A2a66Bf88b628c"
+        );
+        // Wiped even when the length is refused
+        let mut password = "test".to_string();
+        assert!(matches!(
+            derive(&mut password, "key", Some(40)),
+            Err(FlowerPasswordError::InvalidLength(40))
+        ));
+        assert!(password.is_empty());
+    }
+
+    #[test]
+    fn test_new_token() {
+        let token = new_token().unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert!(token.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_ne!(token, new_token().unwrap());
+    }
+}
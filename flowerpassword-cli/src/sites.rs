@@ -0,0 +1,594 @@
+//! `fp alias`, `fp add`, `fp rm`, `fp rename`, `fp note` and `fp import`,
+//! editing the sites of the configuration file
+//!
+//! Edits go to the file of `--config`, or else the user's, created if missing,
+//! through the comment-preserving edits of `flowerpassword::config`: comments,
+//! ordering and every other setting of the file survive, and the file is
+//! replaced atomically.
+//!
+//! Names resolve as `fp` derives with them: an exact site name first, then an
+//! alias, followed through other aliases, and anything else is a literal key.
+//! `fp alias add` checks the alias against the whole layered configuration
+//! before writing, so an alias that loops or leads nowhere is refused.
+//!
+//! `fp add` refuses to replace a configured site without `--force`, and `fp rm`
+//! asks before removing one unless `--yes`. `fp rename` keeps the key a site
+//! derives with, so its password stays the same, unless `--also-key`. `fp note`
+//! replaces the notes of a site, or adds a line to them with `--append`; they
+//! are stored as typed and never expanded. `fp import` adds a site for every
+//! login of a browser's password CSV, keyed by its domain, and leaves its
+//! password behind; logins it cannot map to a new site are listed instead.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::master;
+use flowerpassword::config::{
+    add_site, edit_file, remove_alias, remove_site, rename_site, set_alias, set_notes,
+    write_atomic, Config, ConfigError, Import, SiteConfig, SiteRename, SkipReason, DEFAULT_LENGTH,
+};
+use flowerpassword::{Scheme, DEFAULT_SCHEME};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// Creates an empty configuration file at `path` if there is none yet
+pub(crate) fn create_if_missing(path: &Path) -> Result<(), CliError> {
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, b"")?;
+    }
+    Ok(())
+}
+
+/// Points `alias` at `target` in the file at `path`, returning the previous
+/// target
+///
+/// `config` is the layered configuration the alias must resolve in.
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::AliasCycle` if the alias would
+/// lead back to itself, or `ConfigError::UnknownAliasTarget` if it would lead
+/// to neither a site nor an alias.
+pub(crate) fn add_alias(
+    path: &Path,
+    config: &Config,
+    alias: &str,
+    target: &str,
+) -> Result<Option<String>, CliError> {
+    let mut check = config.clone();
+    check.aliases.insert(alias.to_string(), target.to_string());
+    if !check.sites.contains_key(target) && !check.aliases.contains_key(target) {
+        return Err(ConfigError::UnknownAliasTarget {
+            alias: alias.to_string(),
+            target: target.to_string(),
+        }
+        .into());
+    }
+    check.resolve(target)?;
+
+    create_if_missing(path)?;
+    Ok(edit_file(path, |document| {
+        set_alias(document, alias, target)
+    })?)
+}
+
+/// Removes `alias` from the file at `path`, returning the target it pointed to
+///
+/// # Errors
+///
+/// Returns `CliError::UnknownAlias` if the file has no such alias.
+pub(crate) fn remove(path: &Path, alias: &str) -> Result<String, CliError> {
+    let unknown = || CliError::UnknownAlias {
+        alias: alias.to_string(),
+        path: path.to_path_buf(),
+    };
+    if !path.exists() {
+        return Err(unknown());
+    }
+    edit_file(path, |document| Ok(remove_alias(document, alias)))?.ok_or_else(unknown)
+}
+
+/// Adds the site `name` with the settings of `site` to the file at `path`,
+/// returning the settings it replaced
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::SiteExists` if the site is
+/// configured in the file and `force` is `false`.
+pub(crate) fn add(
+    path: &Path,
+    name: &str,
+    site: &SiteConfig,
+    force: bool,
+) -> Result<Option<SiteConfig>, CliError> {
+    create_if_missing(path)?;
+    Ok(edit_file(path, |document| {
+        add_site(document, name, site, force)
+    })?)
+}
+
+/// Removes the site `name` from the file at `path`, returning its settings
+/// and the aliases of the file left pointing at it
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::UnknownSite` if the file does
+/// not configure the site.
+pub(crate) fn remove_site_from(
+    path: &Path,
+    name: &str,
+) -> Result<(SiteConfig, Vec<String>), CliError> {
+    let unknown = || ConfigError::UnknownSite {
+        name: name.to_string(),
+    };
+    if !path.exists() {
+        return Err(unknown().into());
+    }
+    Ok(edit_file(path, |document| {
+        let site = remove_site(document, name).ok_or_else(unknown)?;
+        let dangling = document
+            .get("aliases")
+            .and_then(|aliases| aliases.as_table_like())
+            .map(|aliases| {
+                aliases
+                    .iter()
+                    .filter(|(_, target)| target.as_str() == Some(name))
+                    .map(|(alias, _)| alias.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok((site, dangling))
+    })?)
+}
+
+/// Renames the site `old` of the file at `path` to `new`, rewiring its aliases
+///
+/// With `also_key` the site derives with `new` as its key afterwards, which
+/// changes its password; otherwise it keeps deriving with its old key.
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::UnknownSite` if the file does
+/// not configure `old`, or `ConfigError::SiteExists` if it configures `new` and
+/// `force` is `false`.
+pub(crate) fn rename(
+    path: &Path,
+    old: &str,
+    new: &str,
+    also_key: bool,
+    force: bool,
+) -> Result<SiteRename, CliError> {
+    if !path.exists() {
+        return Err(ConfigError::UnknownSite {
+            name: old.to_string(),
+        }
+        .into());
+    }
+    let new_key = also_key.then_some(new);
+    Ok(edit_file(path, |document| {
+        rename_site(document, old, new, new_key, force)
+    })?)
+}
+
+/// Sets the notes of the site `name` of the file at `path` to `text`, or adds
+/// `text` as a line of its own with `append`, returning the previous notes
+///
+/// # Errors
+///
+/// Returns `CliError::Config` with `ConfigError::UnknownSite` if the file does
+/// not configure the site.
+pub(crate) fn note(
+    path: &Path,
+    name: &str,
+    text: &str,
+    append: bool,
+) -> Result<Option<String>, CliError> {
+    if !path.exists() {
+        return Err(ConfigError::UnknownSite {
+            name: name.to_string(),
+        }
+        .into());
+    }
+    Ok(edit_file(path, |document| {
+        set_notes(document, name, text, append)
+    })?)
+}
+
+/// Adds a site to the file at `path` for every login of the browser export
+/// `csv` that `config` has no account for yet, returning what was created and
+/// skipped
+///
+/// With `allow_duplicates` another account on a configured domain becomes a
+/// site too, named like `github-2`. Nothing is written when no site is created.
+///
+/// # Errors
+///
+/// Returns `CliError::Io` if `csv` cannot be read, or `CliError::Config` with
+/// `ConfigError::Parse` if it is not a CSV with a `url` column.
+pub(crate) fn import(
+    path: &Path,
+    config: &Config,
+    csv: &Path,
+    allow_duplicates: bool,
+) -> Result<Import, CliError> {
+    let text = fs::read_to_string(csv)?;
+    let import = config.plan_import(&text, &csv.display().to_string(), allow_duplicates)?;
+    if !import.created.is_empty() {
+        create_if_missing(path)?;
+        edit_file(path, |document| import.apply(document))?;
+    }
+    Ok(import)
+}
+
+/// Writes a line for every site `import` created and every row it skipped,
+/// then the totals
+pub(crate) fn write_import(out: &mut impl Write, import: &Import) -> io::Result<()> {
+    for (name, site) in &import.created {
+        let key = site.key.as_deref().unwrap_or(name);
+        match &site.username {
+            Some(username) => writeln!(
+                out,
+                "{}",
+                Msg::ImportCreatedUser.fill(&[name, &key, username])
+            )?,
+            None => writeln!(out, "{}", Msg::ImportCreated.fill(&[name, &key]))?,
+        }
+    }
+    for row in &import.skipped {
+        writeln!(
+            out,
+            "{}",
+            Msg::ImportSkipped.fill(&[&row.line, &row.reason])
+        )?;
+    }
+    let (created, skipped) = (import.created.len(), import.skipped.len());
+    writeln!(out, "{}", Msg::ImportSummary.fill(&[&created, &skipped]))?;
+    let duplicates = import
+        .skipped
+        .iter()
+        .any(|row| matches!(row.reason, SkipReason::DuplicateAccount(_)));
+    if duplicates {
+        writeln!(out, "{}", Msg::ImportDuplicates.text())?;
+    }
+    Ok(())
+}
+
+/// Returns the key, length and scheme the site `name` with `site` derives with
+/// under `config`
+pub(crate) fn describe<'a>(
+    config: &Config,
+    name: &'a str,
+    site: &'a SiteConfig,
+) -> (&'a str, usize, Scheme) {
+    let length = site
+        .length
+        .or(config.defaults.length)
+        .unwrap_or(DEFAULT_LENGTH);
+    let scheme = site.scheme.unwrap_or(DEFAULT_SCHEME);
+    (site.key.as_deref().unwrap_or(name), length, scheme)
+}
+
+/// Writes `question` to `err` and reads the answer from `input`
+///
+/// Only `y` or `yes`, in any case, is a yes.
+pub(crate) fn confirm(
+    input: &mut impl BufRead,
+    err: &mut impl Write,
+    question: &str,
+) -> io::Result<bool> {
+    write!(err, "{} ", question)?;
+    err.flush()?;
+    let answer = master::read_line(input)?.unwrap_or_default();
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Writes every alias of `config` with its target, one per line, in name order
+pub(crate) fn write_aliases(out: &mut impl Write, config: &Config) -> io::Result<()> {
+    for (alias, target) in &config.aliases {
+        writeln!(out, "{} -> {}", alias, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# My sites
+[sites.bank]
+key = \"secure.examplebank.co.uk\"
+
+[aliases]
+money = \"bank\"
+";
+
+    fn write_config(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("config.toml");
+        fs::write(&path, CONFIG).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_add_keeps_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let config = Config::load(&path).unwrap().config;
+
+        assert_eq!(add_alias(&path, &config, "b", "money").unwrap(), None);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}b = \"money\"\n", CONFIG)
+        );
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.resolve("b").unwrap().key, "secure.examplebank.co.uk");
+
+        let previous = add_alias(&path, &config, "b", "bank").unwrap();
+        assert_eq!(previous.as_deref(), Some("money"));
+        // The site comes first, so an alias of the same name never loops
+        add_alias(&path, &config, "bank", "money").unwrap();
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.resolve("bank").unwrap().alias, None);
+    }
+
+    #[test]
+    fn test_add_refuses_cycles_and_dangling_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let config = Config::load(&path).unwrap().config;
+
+        let error = add_alias(&path, &config, "bank2", "bank2").unwrap_err();
+        assert!(
+            matches!(error, CliError::Config(ConfigError::AliasCycle { .. })),
+            "{:?}",
+            error
+        );
+        // So is an alias closing a loop of others
+        let mut looping = config.clone();
+        looping.aliases.insert("x".to_string(), "y".to_string());
+        let error = add_alias(&path, &looping, "y", "x").unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::Config(ConfigError::AliasCycle { .. })
+        ));
+        let error = add_alias(&path, &config, "shop", "nowhere").unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::Config(ConfigError::UnknownAliasTarget { .. })
+        ));
+        // Nothing was written
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn test_add_creates_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flowerpassword").join("config.toml");
+        let mut config = Config::default();
+        config
+            .sites
+            .insert("github".to_string(), Default::default());
+
+        add_alias(&path, &config, "gh", "github").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[aliases]\ngh = \"github\"\n"
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        assert_eq!(remove(&path, "money").unwrap(), "bank");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# My sites\n[sites.bank]\nkey = \"secure.examplebank.co.uk\"\n"
+        );
+        assert!(matches!(
+            remove(&path, "money"),
+            Err(CliError::UnknownAlias { .. })
+        ));
+        assert!(matches!(
+            remove(&dir.path().join("missing.toml"), "money"),
+            Err(CliError::UnknownAlias { .. })
+        ));
+    }
+
+    #[test]
+    fn test_add_and_remove_sites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let site = SiteConfig {
+            key: Some("github.com".to_string()),
+            length: Some(20),
+            scheme: Some(Scheme::V2),
+            ..Default::default()
+        };
+        assert_eq!(add(&path, "github", &site, false).unwrap(), None);
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.sites["github"], site);
+        assert_eq!(
+            describe(&config, "github", &site),
+            ("github.com", 20, Scheme::V2)
+        );
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# My sites\n"));
+
+        let other = SiteConfig::default();
+        assert!(matches!(
+            add(&path, "github", &other, false),
+            Err(CliError::Config(ConfigError::SiteExists { .. }))
+        ));
+        assert_eq!(add(&path, "github", &other, true).unwrap(), Some(site));
+        assert_eq!(
+            describe(&config, "github", &other),
+            ("github", 16, DEFAULT_SCHEME)
+        );
+
+        // The alias money is left pointing at nothing
+        let (removed, dangling) = remove_site_from(&path, "bank").unwrap();
+        assert_eq!(removed.key.as_deref(), Some("secure.examplebank.co.uk"));
+        assert_eq!(dangling, ["money"]);
+        assert!(!Config::load(&path)
+            .unwrap()
+            .config
+            .sites
+            .contains_key("bank"));
+        assert!(matches!(
+            remove_site_from(&path, "bank"),
+            Err(CliError::Config(ConfigError::UnknownSite { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let rename_to = |new, also_key, force| rename(&path, "bank", new, also_key, force);
+
+        let renamed = rename_to("examplebank", false, false).unwrap();
+        assert_eq!(renamed.aliases, ["money"]);
+        assert!(!renamed.key_pinned);
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(
+            config.resolve("money").unwrap().key,
+            "secure.examplebank.co.uk"
+        );
+        assert!(matches!(
+            rename_to("elsewhere", false, false),
+            Err(CliError::Config(ConfigError::UnknownSite { .. }))
+        ));
+
+        add(&path, "bank", &SiteConfig::default(), false).unwrap();
+        assert!(matches!(
+            rename(&path, "bank", "examplebank", false, false),
+            Err(CliError::Config(ConfigError::SiteExists { .. }))
+        ));
+        // A site deriving from its name keeps that name as its key
+        let renamed = rename(&path, "bank", "other", false, false).unwrap();
+        assert!(renamed.key_pinned);
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.resolve("other").unwrap().key, "bank");
+
+        let renamed = rename(&path, "other", "examplebank", true, true).unwrap();
+        assert!(renamed.replaced.is_some());
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.resolve("examplebank").unwrap().key, "examplebank");
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let csv = dir.path().join("passwords.csv");
+        fs::write(
+            &csv,
+            "name,url,username,password\n\
+             github,https://github.com/login,octocat,hunter2\n\
+             github,https://www.github.com/,octocat,hunter2\n\
+             github,https://github.com/login,work,hunter3\n\
+             app,android://hash@com.example.app/,,secret\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap().config;
+
+        let import = import(&path, &config, &csv, false).unwrap();
+        let mut out = Vec::new();
+        write_import(&mut out, &import).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+Created github: key github.com, username octocat
+Skipped line 3: same account as `github`
+Skipped line 4: another account on github.com already exists
+Skipped line 5: unsupported URL `android://hash@com.example.app/`
+Sites created: 1, rows skipped: 3
+--allow-duplicates imports the other accounts of a domain as NAME-2 and so on
+"
+        );
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# My sites\n"), "{}", text);
+        assert!(
+            text.contains("[sites.github]\nkey = \"github.com\"\nusername = \"octocat\"\n"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("hunter"), "{}", text);
+
+        let config = Config::load(&path).unwrap().config;
+        let import = super::import(&path, &config, &csv, true).unwrap();
+        let names: Vec<&str> = import
+            .created
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["github-2"]);
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.resolve("github-2").unwrap().username, Some("work"));
+    }
+
+    #[test]
+    fn test_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path());
+        let notes = |path: &Path| {
+            Config::load(path).unwrap().config.sites["bank"]
+                .notes
+                .clone()
+        };
+
+        assert_eq!(note(&path, "bank", "用户名是旧邮箱", false).unwrap(), None);
+        assert_eq!(notes(&path).as_deref(), Some("用户名是旧邮箱"));
+        let previous = note(&path, "bank", "label={{sq1}}", true).unwrap();
+        assert_eq!(previous.as_deref(), Some("用户名是旧邮箱"));
+        assert_eq!(
+            notes(&path).as_deref(),
+            Some("用户名是旧邮箱\nlabel={{sq1}}")
+        );
+        note(&path, "bank", "replaced", false).unwrap();
+        assert_eq!(notes(&path).as_deref(), Some("replaced"));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# My sites\n"));
+
+        assert!(matches!(
+            note(&path, "money", "alias", false),
+            Err(CliError::Config(ConfigError::UnknownSite { .. }))
+        ));
+        assert!(matches!(
+            note(&dir.path().join("missing.toml"), "bank", "text", false),
+            Err(CliError::Config(ConfigError::UnknownSite { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_confirm() {
+        let answer = |text: &str| {
+            let mut err = Vec::new();
+            let yes = confirm(&mut text.as_bytes(), &mut err, "Sure? [y/N]").unwrap();
+            (yes, String::from_utf8(err).unwrap())
+        };
+        assert_eq!(answer("y\n"), (true, "Sure? [y/N] ".to_string()));
+        assert!(answer("Yes\n").0);
+        for text in ["\n", "n\n", "", "yess\n"] {
+            assert!(!answer(text).0, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_write_aliases() {
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert("money".to_string(), "bank".to_string());
+        config.aliases.insert("b".to_string(), "money".to_string());
+        let mut out = Vec::new();
+        write_aliases(&mut out, &config).unwrap();
+        assert_eq!(out, b"b -> money\nmoney -> bank\n");
+    }
+}
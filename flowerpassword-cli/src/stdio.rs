@@ -0,0 +1,34 @@
+//! `fp serve --stdio` and `fp serve --binary`, a child process for editor
+//! plugins, GUI shells and programs deriving in bulk
+//!
+//! With `--stdio` the parent talks JSON-RPC 2.0 to [`flowerpassword::rpc`] over
+//! the standard input and output of `fp`: one message per line, or framed with
+//! `Content-Length` headers, whichever its first message uses. Nothing is
+//! prompted for; the master password comes with `setMaster`, which is accepted
+//! once per process unless `--allow-rekey`, and `lock` forgets it.
+//!
+//! With `--binary` it speaks the framed protocol of [`flowerpassword::binary`]
+//! instead, where the master password comes in a handshake frame and every
+//! request after it is a key and a length, without any parsing.
+//!
+//! Both stop when standard input is closed.
+
+use crate::error::CliError;
+use flowerpassword::binary;
+use flowerpassword::rpc::{self, RpcSession};
+use std::io;
+
+/// Answers the JSON-RPC requests of standard input on standard output until
+/// standard input ends
+pub(crate) fn run_rpc(allow_rekey: bool) -> Result<(), CliError> {
+    let mut session = RpcSession::new(allow_rekey);
+    rpc::serve(io::stdin().lock(), &mut io::stdout().lock(), &mut session)?;
+    Ok(())
+}
+
+/// Answers the binary protocol frames of standard input on standard output
+/// until standard input ends
+pub(crate) fn run_binary() -> Result<(), CliError> {
+    binary::serve(io::stdin().lock(), io::stdout().lock())?;
+    Ok(())
+}
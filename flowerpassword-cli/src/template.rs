@@ -0,0 +1,327 @@
+//! `--format` output templates
+//!
+//! A template is literal text with placeholders in braces, filled in for every
+//! derived password:
+//!
+//! | Placeholder  | Value                                    |
+//! |--------------|------------------------------------------|
+//! | `{key}`      | key the password was derived for         |
+//! | `{password}` | the password                             |
+//! | `{length}`   | length of the password                   |
+//! | `{scheme}`   | derivation scheme, `v1` or `v2`          |
+//! | `{username}` | username configured for the site, if any |
+//!
+//! `{{` and `}}` stand for literal braces. Anything else in braces is an error
+//! naming the token and the column it starts at, so typos are caught before the
+//! master password is asked for.
+
+use flowerpassword::Scheme;
+use std::error::Error;
+use std::fmt;
+
+/// Value a placeholder is replaced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Key,
+    Password,
+    Length,
+    Scheme,
+    Username,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "key" => Some(Field::Key),
+            "password" => Some(Field::Password),
+            "length" => Some(Field::Length),
+            "scheme" => Some(Field::Scheme),
+            "username" => Some(Field::Username),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed `--format` template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+    segments: Vec<Segment>,
+}
+
+/// Error type for templates that do not parse
+///
+/// Columns count characters from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TemplateError {
+    /// A placeholder with an unknown name, such as `{pasword}`
+    UnknownPlaceholder { token: String, column: usize },
+    /// A `{` with no matching `}`
+    Unclosed { column: usize },
+    /// A `}` that neither closes a placeholder nor is doubled
+    UnmatchedClose { column: usize },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder { token, column } => write!(
+                f,
+                "Unknown placeholder {} at column {}, expected {{key}}, {{password}}, {{length}}, {{scheme}} or {{username}}",
+                token, column
+            ),
+            TemplateError::Unclosed { column } => write!(
+                f,
+                "Unclosed {{ at column {}, write {{{{ for a literal brace",
+                column
+            ),
+            TemplateError::UnmatchedClose { column } => write!(
+                f,
+                "Unmatched }} at column {}, write }}}} for a literal brace",
+                column
+            ),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+impl Template {
+    /// Parses a template, as clap's value parser for `--format`
+    pub(crate) fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().zip(1..).peekable();
+
+        while let Some((c, column)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(next, _)| next == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(next, _)| next == '}').is_some() => literal.push('}'),
+                '}' => return Err(TemplateError::UnmatchedClose { column }),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(('}', _)) => break,
+                            // Report the outer brace, which is the one left open
+                            Some(('{', _)) | None => {
+                                return Err(TemplateError::Unclosed { column })
+                            }
+                            Some((c, _)) => name.push(c),
+                        }
+                    }
+                    let field = Field::from_name(&name).ok_or_else(|| {
+                        TemplateError::UnknownPlaceholder {
+                            token: format!("{{{}}}", name),
+                            column,
+                        }
+                    })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    /// Fills in the template for `password`, derived for `key` under `scheme`,
+    /// with the password as `paint` returns it; `{length}` counts the bare
+    /// password, and `{username}` is empty without a `username`
+    pub(crate) fn render(
+        &self,
+        key: &str,
+        scheme: Scheme,
+        username: Option<&str>,
+        password: &str,
+        paint: impl Fn(&str) -> String,
+    ) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field(Field::Key) => out.push_str(key),
+                Segment::Field(Field::Password) => out.push_str(&paint(password)),
+                Segment::Field(Field::Length) => {
+                    out.push_str(&password.chars().count().to_string())
+                }
+                Segment::Field(Field::Scheme) => out.push_str(scheme.name()),
+                Segment::Field(Field::Username) => out.push_str(username.unwrap_or_default()),
+            }
+        }
+        out
+    }
+}
+
+impl Template {
+    /// The username and a Tab before the password, for `--show-user`
+    pub(crate) fn show_user() -> Self {
+        Template {
+            segments: vec![
+                Segment::Field(Field::Username),
+                Segment::Literal("\t".to_string()),
+                Segment::Field(Field::Password),
+            ],
+        }
+    }
+}
+
+impl Default for Template {
+    /// The password alone
+    fn default() -> Self {
+        Template {
+            segments: vec![Segment::Field(Field::Password)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(text: &str) -> Segment {
+        Segment::Literal(text.to_string())
+    }
+
+    #[test]
+    fn test_tokenizes_fields_and_literals() {
+        let template = Template::parse("{key}: {password} ({length}, {scheme})").unwrap();
+        assert_eq!(
+            template.segments,
+            [
+                Segment::Field(Field::Key),
+                literal(": "),
+                Segment::Field(Field::Password),
+                literal(" ("),
+                Segment::Field(Field::Length),
+                literal(", "),
+                Segment::Field(Field::Scheme),
+                literal(")"),
+            ]
+        );
+        assert_eq!(
+            template.render(
+                "github.com",
+                Scheme::V1,
+                None,
+                "D04175F7A9c7Ab4a",
+                str::to_string
+            ),
+            "github.com: D04175F7A9c7Ab4a (16, v1)"
+        );
+        assert_eq!(
+            template.render(
+                "github.com",
+                Scheme::V1,
+                None,
+                "D04175F7",
+                |password| format!("<{}>", password)
+            ),
+            "github.com: <D04175F7> (8, v1)"
+        );
+        assert_eq!(Template::parse("").unwrap().segments, []);
+    }
+
+    #[test]
+    fn test_escapes() {
+        let template = Template::parse("{{\"{key}\": \"{password}\"}}").unwrap();
+        assert_eq!(
+            template.render("github.com", Scheme::V1, None, "D04175F7", str::to_string),
+            "{\"github.com\": \"D04175F7\"}"
+        );
+        assert_eq!(
+            Template::parse("{{key}}").unwrap().segments,
+            [literal("{key}")]
+        );
+        assert_eq!(
+            Template::parse("{{{key}}}").unwrap().render(
+                "k",
+                Scheme::V1,
+                None,
+                "p",
+                str::to_string
+            ),
+            "{k}"
+        );
+    }
+
+    #[test]
+    fn test_error_spans() {
+        assert_eq!(
+            Template::parse("{key}: {pasword}").unwrap_err(),
+            TemplateError::UnknownPlaceholder {
+                token: "{pasword}".to_string(),
+                column: 8
+            }
+        );
+        // Columns count characters, not bytes
+        assert_eq!(
+            Template::parse("密码 {}").unwrap_err(),
+            TemplateError::UnknownPlaceholder {
+                token: "{}".to_string(),
+                column: 4
+            }
+        );
+        assert_eq!(
+            Template::parse("a {key").unwrap_err(),
+            TemplateError::Unclosed { column: 3 }
+        );
+        assert_eq!(
+            Template::parse("{key {password}").unwrap_err(),
+            TemplateError::Unclosed { column: 1 }
+        );
+        assert_eq!(
+            Template::parse("{key}}").unwrap_err(),
+            TemplateError::UnmatchedClose { column: 6 }
+        );
+    }
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            Template::parse("{Key}").unwrap_err().to_string(),
+            "Unknown placeholder {Key} at column 1, expected {key}, {password}, {length}, {scheme} or {username}"
+        );
+        assert_eq!(
+            Template::parse("x}").unwrap_err().to_string(),
+            "Unmatched } at column 2, write }} for a literal brace"
+        );
+    }
+
+    #[test]
+    fn test_default_is_the_password() {
+        assert_eq!(
+            Template::default().render("github.com", Scheme::V1, None, "pw", str::to_string),
+            "pw"
+        );
+    }
+
+    #[test]
+    fn test_username() {
+        let template = Template::parse("{username}\t{password}").unwrap();
+        assert_eq!(
+            template.render(
+                "github.com",
+                Scheme::V1,
+                Some("octocat"),
+                "pw",
+                str::to_string
+            ),
+            "octocat\tpw"
+        );
+        assert_eq!(
+            template.render("github.com", Scheme::V1, None, "pw", str::to_string),
+            "\tpw"
+        );
+    }
+}
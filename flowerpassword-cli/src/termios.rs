@@ -0,0 +1,395 @@
+//! Restoring the terminal after no-echo and raw-mode sections
+//!
+//! The master password prompt turns echo off, and `--masked` and `fp tui` put
+//! the terminal in raw mode. A [`TermiosGuard`] saves the state of the terminal
+//! before such a section and puts it back when dropped, so also on errors and
+//! when unwinding from a panic.
+//!
+//! While any guard is held, SIGINT and SIGTERM are caught too: the handler puts
+//! back every saved state, innermost first, and exits with 128 plus the signal
+//! number, so 130 for Ctrl-C, as shells report it. A panic hook restores them
+//! the same way before the message is printed. Once the last guard is dropped,
+//! the previous signal handlers are back.
+//!
+//! Guards nest: each saves the state it found, and they are dropped in the
+//! reverse order of their creation, as scopes do. The saved states are kept in
+//! a fixed array, which the signal handler reads without locking or allocating.
+
+use std::cell::UnsafeCell;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Once, PoisonError};
+
+/// Most guards held at once
+const MAX_DEPTH: usize = 8;
+
+/// Signals that restore the terminal and exit while a guard is held
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// A saved terminal state: the descriptor and its attributes
+type Saved = (RawFd, libc::termios);
+
+/// The saved states, of which the first [`DEPTH`] are held
+struct Stack(UnsafeCell<[Saved; MAX_DEPTH]>);
+
+// SAFETY: slots are only written under `CHANGE`, at or above `DEPTH`, before
+// `DEPTH` is raised past them; readers only read slots below `DEPTH`
+unsafe impl Sync for Stack {}
+
+// SAFETY: termios is plain data, for which all zeroes is a valid value
+static STACK: Stack = Stack(UnsafeCell::new(unsafe { std::mem::zeroed() }));
+
+/// How many guards are held
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes pushing and popping guards, and holds the signal handlers that
+/// were installed before the first guard
+static CHANGE: Mutex<Option<[libc::sigaction; 2]>> = Mutex::new(None);
+
+/// Installs the panic hook, once
+static PANIC_HOOK: Once = Once::new();
+
+fn change() -> MutexGuard<'static, Option<[libc::sigaction; 2]>> {
+    CHANGE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Puts back every held state, innermost first
+///
+/// Only calls async-signal-safe functions, for the signal handler.
+fn restore_all() {
+    let depth = DEPTH.load(Ordering::SeqCst);
+    for index in (0..depth).rev() {
+        // SAFETY: the slots below `DEPTH` were written before it was raised, and
+        // are not written again until it is lowered
+        let (fd, termios) = unsafe { STACK.0.get().cast::<Saved>().add(index).read() };
+        // SAFETY: tcsetattr only reads `termios`; a closed `fd` makes it fail
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    }
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    restore_all();
+    // SAFETY: _exit is async-signal-safe, unlike exit
+    unsafe { libc::_exit(128 + signal) }
+}
+
+/// Catches [`SIGNALS`], returning the handlers they had
+fn install_handlers() -> io::Result<[libc::sigaction; 2]> {
+    // SAFETY: sigaction is plain data, and the handler is async-signal-safe
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: [libc::sigaction; 2] = std::mem::zeroed();
+        for (signal, previous) in SIGNALS.iter().zip(&mut previous) {
+            if libc::sigaction(*signal, &action, previous) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(previous)
+    }
+}
+
+/// Puts back the handlers [`install_handlers`] returned
+fn uninstall_handlers(previous: &[libc::sigaction; 2]) {
+    for (signal, previous) in SIGNALS.iter().zip(previous) {
+        // SAFETY: `previous` came from sigaction
+        unsafe { libc::sigaction(*signal, previous, std::ptr::null_mut()) };
+    }
+}
+
+/// A terminal's state, put back when dropped, or by a signal or panic meanwhile
+pub(crate) struct TermiosGuard {
+    fd: RawFd,
+    saved: libc::termios,
+    /// Position in the stack, and `None` once restored
+    index: Option<usize>,
+}
+
+impl TermiosGuard {
+    /// Saves the state of the terminal `fd`, which must stay open until the guard
+    /// is dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `tcgetattr`, such as for a descriptor that is not a
+    /// terminal, or an error if too many guards are held already.
+    pub(crate) fn save(fd: RawFd) -> io::Result<Self> {
+        // SAFETY: termios is plain data that tcgetattr fills in
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: tcgetattr fails on descriptors that are not open terminals
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_all();
+                previous(info);
+            }));
+        });
+
+        let mut handlers = change();
+        let index = DEPTH.load(Ordering::SeqCst);
+        if index == MAX_DEPTH {
+            return Err(io::Error::other("too many terminal states saved at once"));
+        }
+        if index == 0 {
+            *handlers = Some(install_handlers()?);
+        }
+        // SAFETY: `CHANGE` is held and the slot is at `DEPTH`, so nothing else
+        // reads or writes it
+        unsafe { STACK.0.get().cast::<Saved>().add(index).write((fd, saved)) };
+        DEPTH.store(index + 1, Ordering::SeqCst);
+        Ok(TermiosGuard {
+            fd,
+            saved,
+            index: Some(index),
+        })
+    }
+
+    /// Returns the state saved
+    pub(crate) fn saved(&self) -> libc::termios {
+        self.saved
+    }
+
+    /// Puts the saved state back now
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `tcsetattr`. The guard is released either way.
+    pub(crate) fn restore(mut self) -> io::Result<()> {
+        self.release()
+    }
+
+    fn release(&mut self) -> io::Result<()> {
+        let Some(index) = self.index.take() else {
+            return Ok(());
+        };
+        let mut handlers = change();
+        // SAFETY: `saved` came from tcgetattr on the same descriptor
+        let result = match unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        };
+        debug_assert_eq!(
+            DEPTH.load(Ordering::SeqCst),
+            index + 1,
+            "terminal guards dropped out of order"
+        );
+        // Only lowered once restored, so that a signal meanwhile restores it too
+        DEPTH.store(index, Ordering::SeqCst);
+        if index == 0 {
+            if let Some(previous) = handlers.take() {
+                uninstall_handlers(&previous);
+            }
+        }
+        result
+    }
+}
+
+impl Drop for TermiosGuard {
+    fn drop(&mut self) {
+        // Nothing better to do if the terminal went away meanwhile
+        let _ = self.release();
+    }
+}
+
+/// The state of the controlling terminal, saved while this is held
+pub(crate) struct SavedTty {
+    // Dropped before the terminal it restores is closed
+    _guard: TermiosGuard,
+    _tty: File,
+}
+
+/// Saves the state of the controlling terminal, `/dev/tty`, or returns `None`
+/// if there is none
+pub(crate) fn save_tty() -> Option<SavedTty> {
+    let tty = File::open("/dev/tty").ok()?;
+    let guard = TermiosGuard::save(tty.as_raw_fd()).ok()?;
+    Some(SavedTty {
+        _guard: guard,
+        _tty: tty,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    /// Serializes the tests, which share the stack
+    static TESTS: Mutex<()> = Mutex::new(());
+
+    fn serial() -> MutexGuard<'static, ()> {
+        TESTS.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Opens a pseudo-terminal, returning its terminal end
+    fn terminal() -> (OwnedFd, OwnedFd) {
+        let (mut controller, mut terminal) = (0, 0);
+        // SAFETY: openpty writes two new descriptors, which are owned here
+        let result = unsafe {
+            libc::openpty(
+                &mut controller,
+                &mut terminal,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(result, 0, "{}", io::Error::last_os_error());
+        // SAFETY: see above
+        unsafe {
+            (
+                OwnedFd::from_raw_fd(controller),
+                OwnedFd::from_raw_fd(terminal),
+            )
+        }
+    }
+
+    fn get(fd: RawFd) -> libc::termios {
+        // SAFETY: termios is plain data that tcgetattr fills in
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut termios) }, 0);
+        termios
+    }
+
+    fn set(fd: RawFd, termios: &libc::termios) {
+        // SAFETY: `termios` came from tcgetattr
+        assert_eq!(unsafe { libc::tcsetattr(fd, libc::TCSANOW, termios) }, 0);
+    }
+
+    fn echo_off(fd: RawFd) {
+        let mut termios = get(fd);
+        termios.c_lflag &= !libc::ECHO;
+        set(fd, &termios);
+    }
+
+    fn raw(fd: RawFd) {
+        let mut termios = get(fd);
+        // SAFETY: a termios from tcgetattr
+        unsafe { libc::cfmakeraw(&mut termios) };
+        set(fd, &termios);
+    }
+
+    fn echoes(fd: RawFd) -> bool {
+        get(fd).c_lflag & libc::ECHO != 0
+    }
+
+    fn canonical(fd: RawFd) -> bool {
+        get(fd).c_lflag & libc::ICANON != 0
+    }
+
+    /// Returns the handler SIGINT has
+    fn sigint_handler() -> libc::sighandler_t {
+        // SAFETY: sigaction is plain data, only read
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, std::ptr::null(), &mut action);
+            action.sa_sigaction
+        }
+    }
+
+    #[test]
+    fn test_drop_restores() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let fd = terminal.as_raw_fd();
+        let guard = TermiosGuard::save(fd).unwrap();
+        echo_off(fd);
+        assert!(!echoes(fd));
+        drop(guard);
+        assert!(echoes(fd));
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+
+        let guard = TermiosGuard::save(fd).unwrap();
+        raw(fd);
+        guard.restore().unwrap();
+        assert!(echoes(fd) && canonical(fd));
+    }
+
+    #[test]
+    fn test_nested_guards_unwind_in_order() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let fd = terminal.as_raw_fd();
+        let outer = TermiosGuard::save(fd).unwrap();
+        echo_off(fd);
+        let inner = TermiosGuard::save(fd).unwrap();
+        raw(fd);
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 2);
+
+        drop(inner);
+        // Back to what the inner guard found: canonical, without echo
+        assert!(canonical(fd) && !echoes(fd));
+        drop(outer);
+        assert!(canonical(fd) && echoes(fd));
+    }
+
+    #[test]
+    fn test_restore_all() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let fd = terminal.as_raw_fd();
+        let outer = TermiosGuard::save(fd).unwrap();
+        echo_off(fd);
+        let inner = TermiosGuard::save(fd).unwrap();
+        raw(fd);
+
+        // What the signal handler and the panic hook do
+        restore_all();
+        assert!(canonical(fd) && echoes(fd));
+        drop(inner);
+        drop(outer);
+        assert!(canonical(fd) && echoes(fd));
+    }
+
+    #[test]
+    fn test_panics_unwind_to_the_saved_state() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let fd = terminal.as_raw_fd();
+        let result = std::panic::catch_unwind(|| {
+            let _guard = TermiosGuard::save(fd).unwrap();
+            raw(fd);
+            panic!("while raw");
+        });
+        assert!(result.is_err());
+        assert!(canonical(fd) && echoes(fd));
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_handlers_only_while_held() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let before = sigint_handler();
+        let guard = TermiosGuard::save(terminal.as_raw_fd()).unwrap();
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        assert_eq!(sigint_handler(), handler);
+        drop(guard);
+        assert_eq!(sigint_handler(), before);
+    }
+
+    #[test]
+    fn test_limits() {
+        let _serial = serial();
+        let (_controller, terminal) = terminal();
+        let fd = terminal.as_raw_fd();
+        let guards: Vec<_> = (0..MAX_DEPTH)
+            .map(|_| TermiosGuard::save(fd).unwrap())
+            .collect();
+        assert!(TermiosGuard::save(fd).is_err());
+        // Dropped innermost first
+        guards.into_iter().rev().for_each(drop);
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+
+        // Not a terminal
+        let file = File::open("/dev/null").unwrap();
+        assert!(TermiosGuard::save(file.as_raw_fd()).is_err());
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,27 @@
+//! Running the external tools of `--master` that wait on a device
+
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant};
+
+/// How often a running tool is checked on while waiting for a touch
+const POLL: Duration = Duration::from_millis(50);
+
+/// Waits for `child` to exit, killing it if it has not within `timeout`
+///
+/// Returns `None` if it was killed. The tools write a few lines at most, which
+/// fit in the pipes, so they are read once it has exited.
+pub(crate) fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait()? {
+            Some(status) => return Ok(Some(status)),
+            None if Instant::now() < deadline => std::thread::sleep(POLL),
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(None);
+            }
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! `fp tui`, a full-screen picker over the configured sites
+//!
+//! After one master password prompt, the configured sites are listed, the best
+//! matches for the filter first, as `fp`'s fuzzy lookup ranks them. Typing
+//! filters, Up and Down move, and Enter copies the selected site's password to
+//! the clipboard, which is restored after `DEFAULT_TTL` or on quitting. Esc
+//! clears the filter, or quits once it is empty; Ctrl-C always quits.
+//!
+//! Keys go through [`Picker::handle`], which only updates the picker, so the
+//! handling is tested without a terminal. The terminal is restored on the way
+//! out, errors included, and on panic by the hook `ratatui::try_init` installs.
+//! On Unix, SIGINT and SIGTERM put its mode back too, through
+//! [`termios`](crate::termios).
+
+use crate::cli::derive;
+use crate::error::CliError;
+use crate::i18n::Msg;
+use flowerpassword::clipboard::{copy_with_ttl, ClipboardGuard, DEFAULT_TTL};
+use flowerpassword::config::Config;
+use flowerpassword::FpGenerator;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How often the countdown is redrawn while no key is pressed
+const TICK: Duration = Duration::from_millis(250);
+
+/// What a key asks the picker loop to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action<'a> {
+    /// Nothing beyond redrawing
+    None,
+    /// Copy the password for this site
+    Derive(&'a str),
+    /// Leave the picker
+    Quit,
+}
+
+/// The filter and selection of the picker
+pub(crate) struct Picker<'a> {
+    config: &'a Config,
+    filter: String,
+    matches: Vec<&'a str>,
+    selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    /// Returns a picker over the sites of `config`, all of them listed
+    pub(crate) fn new(config: &'a Config) -> Self {
+        let mut picker = Picker {
+            config,
+            filter: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        picker.refilter();
+        picker
+    }
+
+    /// Returns the filter typed so far
+    pub(crate) fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Returns the sites matching the filter, best match first
+    pub(crate) fn matches(&self) -> &[&'a str] {
+        &self.matches
+    }
+
+    /// Returns the selected site, if any site matches
+    pub(crate) fn selected(&self) -> Option<&'a str> {
+        self.matches.get(self.selected).copied()
+    }
+
+    /// Updates the picker for `key` and returns what the loop should do
+    pub(crate) fn handle(&mut self, key: KeyEvent) -> Action<'a> {
+        // Windows reports releases too; everything else only presses and repeats
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if control => return Action::Quit,
+            KeyCode::Esc if self.filter.is_empty() => return Action::Quit,
+            KeyCode::Esc => self.filter.clear(),
+            KeyCode::Enter => return self.selected().map_or(Action::None, Action::Derive),
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                return Action::None;
+            }
+            KeyCode::Down => {
+                if self.selected + 1 < self.matches.len() {
+                    self.selected += 1;
+                }
+                return Action::None;
+            }
+            KeyCode::Backspace => {
+                if self.filter.pop().is_none() {
+                    return Action::None;
+                }
+            }
+            KeyCode::Char(c) if !control && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.filter.push(c)
+            }
+            _ => return Action::None,
+        }
+        self.refilter();
+        Action::None
+    }
+
+    fn refilter(&mut self) {
+        self.matches = self
+            .config
+            .find(&self.filter)
+            .into_iter()
+            .map(|found| found.site)
+            .collect();
+        self.selected = 0;
+    }
+}
+
+/// What the status line reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Status {
+    /// Nothing copied yet
+    Ready,
+    /// A password was copied and the clipboard is restored at `until`
+    Copied { site: String, until: Instant },
+    /// Deriving or copying failed
+    Failed(String),
+}
+
+impl Status {
+    /// Returns the status line at `now`, `restored` once the clipboard is back
+    pub(crate) fn line(&self, now: Instant, restored: bool) -> String {
+        match self {
+            Status::Ready => Msg::TuiReady.text().to_string(),
+            Status::Copied { site, until } => {
+                let left = until.saturating_duration_since(now);
+                if restored || left.is_zero() {
+                    Msg::TuiRestored.text().to_string()
+                } else {
+                    // Round up, so the count never shows 0s while still on
+                    let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+                    Msg::TuiCopied.fill(&[site, &seconds])
+                }
+            }
+            Status::Failed(error) => format!("fp: {}", error),
+        }
+    }
+}
+
+/// Draws the filter, the matching sites and the status line
+pub(crate) fn draw(frame: &mut Frame, picker: &Picker, status: &str) {
+    let [filter, list, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    frame.render_widget(Paragraph::new(format!("> {}", picker.filter())), filter);
+    let sites = List::new(picker.matches().iter().copied())
+        .highlight_symbol("> ")
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(picker.selected().map(|_| picker.selected));
+    frame.render_stateful_widget(sites, list, &mut state);
+    frame.render_widget(Paragraph::new(status), footer);
+}
+
+/// The terminal in raw mode on the alternate screen, restored on drop
+struct Screen {
+    terminal: DefaultTerminal,
+    /// The mode from before, for signals to restore
+    #[cfg(unix)]
+    _saved: Option<crate::termios::SavedTty>,
+}
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        #[cfg(unix)]
+        let saved = crate::termios::save_tty();
+        match ratatui::try_init() {
+            Ok(terminal) => Ok(Screen {
+                terminal,
+                #[cfg(unix)]
+                _saved: saved,
+            }),
+            Err(error) => {
+                ratatui::restore();
+                Err(error)
+            }
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Runs the picker until the user quits
+pub(crate) fn run(config: &Config, generator: &FpGenerator) -> Result<(), CliError> {
+    let mut screen = Screen::enter()?;
+    let mut picker = Picker::new(config);
+    let mut status = Status::Ready;
+    let mut clipboard: Option<ClipboardGuard> = None;
+    loop {
+        let restored = clipboard.as_ref().is_none_or(ClipboardGuard::is_expired);
+        let line = status.line(Instant::now(), restored);
+        screen.terminal.draw(|frame| draw(frame, &picker, &line))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        match picker.handle(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Derive(site) => {
+                // Restore what the last copy replaced before copying again
+                clipboard = None;
+                let copied = derive(generator, config, site, None)
+                    .and_then(|derived| Ok(copy_with_ttl(&derived.password, DEFAULT_TTL)?));
+                status = match copied {
+                    Ok(guard) => {
+                        clipboard = Some(guard);
+                        Status::Copied {
+                            site: site.to_string(),
+                            until: Instant::now() + DEFAULT_TTL,
+                        }
+                    }
+                    Err(error) => Status::Failed(error.to_string()),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::config::Config;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    const SITES: &str = r#"
+[sites.github]
+key = "github.com"
+
+[sites.gitlab]
+key = "gitlab.com"
+
+[sites.mail]
+key = "mail.example.org"
+"#;
+
+    fn press<'a>(picker: &mut Picker<'a>, code: KeyCode) -> Action<'a> {
+        picker.handle(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn typed(picker: &mut Picker, text: &str) {
+        for c in text.chars() {
+            assert_eq!(press(picker, KeyCode::Char(c)), Action::None);
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_lists_every_site() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let picker = Picker::new(&config);
+        assert_eq!(picker.matches(), ["github", "gitlab", "mail"]);
+        assert_eq!(picker.selected(), Some("github"));
+    }
+
+    #[test]
+    fn test_typing_filters_and_resets_the_selection() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut picker = Picker::new(&config);
+        press(&mut picker, KeyCode::Down);
+        assert_eq!(picker.selected(), Some("gitlab"));
+
+        typed(&mut picker, "gl");
+        assert_eq!(picker.filter(), "gl");
+        assert_eq!(picker.matches(), ["gitlab"]);
+        assert_eq!(picker.selected(), Some("gitlab"));
+
+        press(&mut picker, KeyCode::Backspace);
+        assert_eq!(picker.filter(), "g");
+        assert_eq!(picker.selected(), picker.matches().first().copied());
+
+        typed(&mut picker, "zzz");
+        assert!(picker.matches().is_empty());
+        assert_eq!(picker.selected(), None);
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::None);
+    }
+
+    #[test]
+    fn test_arrows_stay_in_the_list() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut picker = Picker::new(&config);
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(picker.selected(), Some("github"));
+        for _ in 0..5 {
+            press(&mut picker, KeyCode::Down);
+        }
+        assert_eq!(picker.selected(), Some("mail"));
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Derive("gitlab"));
+    }
+
+    #[test]
+    fn test_esc_clears_then_quits() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut picker = Picker::new(&config);
+        typed(&mut picker, "mail");
+        assert_eq!(press(&mut picker, KeyCode::Esc), Action::None);
+        assert_eq!(picker.filter(), "");
+        assert_eq!(picker.matches().len(), 3);
+        assert_eq!(press(&mut picker, KeyCode::Esc), Action::Quit);
+
+        typed(&mut picker, "mail");
+        let interrupt = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(picker.handle(interrupt), Action::Quit);
+        assert_eq!(picker.filter(), "mail");
+    }
+
+    #[test]
+    fn test_releases_and_modified_keys_are_ignored() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut picker = Picker::new(&config);
+        let mut release = KeyEvent::new(KeyCode::Char('m'), KeyModifiers::NONE);
+        release.kind = KeyEventKind::Release;
+        assert_eq!(picker.handle(release), Action::None);
+        let alt = KeyEvent::new(KeyCode::Char('m'), KeyModifiers::ALT);
+        assert_eq!(picker.handle(alt), Action::None);
+        assert_eq!(picker.filter(), "");
+        assert_eq!(press(&mut picker, KeyCode::Backspace), Action::None);
+        assert_eq!(picker.matches().len(), 3);
+    }
+
+    #[test]
+    fn test_countdown() {
+        let now = Instant::now();
+        let copied = Status::Copied {
+            site: "github".to_string(),
+            until: now + Duration::from_millis(29_500),
+        };
+        assert_eq!(
+            copied.line(now, false),
+            "Copied the password for github, clipboard restored in 30s"
+        );
+        assert_eq!(
+            copied.line(now + Duration::from_secs(29), false),
+            "Copied the password for github, clipboard restored in 1s"
+        );
+        assert_eq!(
+            copied.line(now + Duration::from_secs(30), false),
+            "Clipboard restored"
+        );
+        assert_eq!(copied.line(now, true), "Clipboard restored");
+        assert_eq!(
+            Status::Failed("No clipboard".to_string()).line(now, true),
+            "fp: No clipboard"
+        );
+    }
+
+    #[test]
+    fn test_draw_headless() {
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let mut picker = Picker::new(&config);
+        typed(&mut picker, "git");
+        press(&mut picker, KeyCode::Down);
+        let mut terminal = Terminal::new(TestBackend::new(30, 5)).unwrap();
+        terminal
+            .draw(|frame| draw(frame, &picker, "Enter copies the password"))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> = (0..5)
+            .map(|y| (0..30).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "> git                         ",
+                "  github                      ",
+                "> gitlab                      ",
+                "                              ",
+                "Enter copies the password     ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,608 @@
+//! `fp unlock`, `fp lock` and `fp status`, a master password cached for a while
+//!
+//! `fp unlock --timeout 15m` asks for the master password once and leaves it in
+//! the runtime directory, `$XDG_RUNTIME_DIR/flowerpassword`, until the timeout
+//! runs out. Without `$XDG_RUNTIME_DIR` nothing is cached: the shared temporary
+//! directory outlives the session and usually sits on disk. Runs that would
+//! prompt on a terminal for the master password use it instead; piped input is
+//! still read as usual, so scripts behave alike whether or not the user is
+//! unlocked. `fp lock` overwrites and removes the cache.
+//!
+//! The master password is sealed with ChaCha20-Poly1305 under a key made for
+//! each unlock. The key never touches the filesystem: it is kept in the user's
+//! Linux kernel keyring, which drops it when the timeout runs out, so the cache
+//! file alone reveals nothing. Other Unix systems have no such keyring, and
+//! `fp unlock` fails there. The file and the directory are only accessible to
+//! their owner, which is checked on the very handle that is then read or
+//! overwritten, so the cache is bound to the user. The expiry time, the user id
+//! and, on Linux, the boot id are authenticated along with the ciphertext, so a
+//! cache that outlives its timeout or a reboot, or that was edited, is ignored.
+//!
+//! A cache file has this layout:
+//!
+//! | Bytes | Content                                      |
+//! |-------|----------------------------------------------|
+//! | 8     | [`MAGIC`]                                    |
+//! | 8     | expiry, seconds since the Unix epoch, big endian |
+//! | 4     | user id, big endian                          |
+//! | 1     | length of the boot id, 0 if unknown          |
+//! | n     | boot id                                      |
+//! | 12    | ChaCha20-Poly1305 nonce                      |
+//! | rest  | ciphertext and 16-byte tag                   |
+
+use crate::error::CliError;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs::{self, DirBuilder, File, OpenOptions};
+#[cfg(target_os = "linux")]
+use std::ffi::{CStr, CString};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// First bytes of a cache file, with the format version
+const MAGIC: &[u8; 8] = b"FPUNLCK1";
+
+/// Name of the cache file in the runtime directory
+const CACHE_FILE: &str = "unlock";
+
+/// Where the system publishes an id that changes on every boot
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Length of the ChaCha20-Poly1305 nonce in bytes
+const NONCE_LEN: usize = 12;
+
+/// Formats a remaining time for `fp status`, such as `14m 05s`
+pub(crate) fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+/// The contents of the cache file and its key
+struct Sealed {
+    data: Vec<u8>,
+    key: Zeroizing<Vec<u8>>,
+}
+
+/// Where the key of a cache file is kept, out of the filesystem
+pub(crate) trait KeyStore {
+    /// Keeps `key` under `name`, replacing any key there, until `timeout` runs
+    /// out
+    fn set(&self, name: &str, key: &[u8], timeout: Duration) -> Result<(), CliError>;
+
+    /// Returns the key under `name`, or `None` if there is none
+    fn get(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, CliError>;
+
+    /// Drops the key under `name`, returning whether there was one
+    fn clear(&self, name: &str) -> Result<bool, CliError>;
+}
+
+/// The user keyring of the Linux kernel, holding `user` keys
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct KernelKeyring;
+
+#[cfg(target_os = "linux")]
+impl KernelKeyring {
+    /// Returns the serial number of the key under `name`, if there is one
+    fn search(name: &CStr) -> io::Result<Option<libc::c_long>> {
+        // SAFETY: both strings are NUL-terminated and outlive the call
+        let id = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                libc::KEYCTL_SEARCH,
+                libc::KEY_SPEC_USER_KEYRING,
+                c"user".as_ptr(),
+                name.as_ptr(),
+                0,
+            )
+        };
+        if id >= 0 {
+            return Ok(Some(id));
+        }
+        let error = io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::ENOKEY | libc::EKEYEXPIRED | libc::EKEYREVOKED) => Ok(None),
+            _ => Err(error),
+        }
+    }
+
+    fn name(name: &str) -> io::Result<CString> {
+        CString::new(name).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl KeyStore for KernelKeyring {
+    fn set(&self, name: &str, key: &[u8], timeout: Duration) -> Result<(), CliError> {
+        let name = Self::name(name)?;
+        // SAFETY: the strings are NUL-terminated and `key` is valid for its
+        // length, all for the duration of the call
+        let id = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                c"user".as_ptr(),
+                name.as_ptr(),
+                key.as_ptr(),
+                key.len(),
+                libc::KEY_SPEC_USER_KEYRING,
+            )
+        };
+        if id < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // Rounded up, since a timeout of zero would keep the key forever
+        let seconds = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+        let seconds = seconds.clamp(1, u64::from(u32::MAX)) as libc::c_uint;
+        // SAFETY: KEYCTL_SET_TIMEOUT only takes integers
+        if unsafe { libc::syscall(libc::SYS_keyctl, libc::KEYCTL_SET_TIMEOUT, id, seconds) } < 0 {
+            let error = io::Error::last_os_error();
+            // SAFETY: KEYCTL_INVALIDATE only takes integers
+            unsafe { libc::syscall(libc::SYS_keyctl, libc::KEYCTL_INVALIDATE, id) };
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, CliError> {
+        let Some(id) = Self::search(&Self::name(name)?)? else {
+            return Ok(None);
+        };
+        // Room for more than a key, so that a longer payload is noticed
+        let mut key = Zeroizing::new(vec![0; 64]);
+        // SAFETY: the buffer is valid for writes of its length
+        let len = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                libc::KEYCTL_READ,
+                id,
+                key.as_mut_ptr(),
+                key.len(),
+            )
+        };
+        match usize::try_from(len) {
+            Ok(len) if len <= key.len() => {
+                key.truncate(len);
+                Ok(Some(key))
+            }
+            Ok(_) => Ok(None),
+            Err(_) => match io::Error::last_os_error().raw_os_error() {
+                // Expired or revoked since the search
+                Some(libc::ENOKEY | libc::EKEYEXPIRED | libc::EKEYREVOKED) => Ok(None),
+                _ => Err(io::Error::last_os_error().into()),
+            },
+        }
+    }
+
+    fn clear(&self, name: &str) -> Result<bool, CliError> {
+        let Some(id) = Self::search(&Self::name(name)?)? else {
+            return Ok(false);
+        };
+        // SAFETY: KEYCTL_INVALIDATE only takes integers
+        if unsafe { libc::syscall(libc::SYS_keyctl, libc::KEYCTL_INVALIDATE, id) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl KeyStore for KernelKeyring {
+    fn set(&self, _name: &str, _key: &[u8], _timeout: Duration) -> Result<(), CliError> {
+        Err(CliError::NoKernelKeyring)
+    }
+
+    fn get(&self, _name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, CliError> {
+        Ok(None)
+    }
+
+    fn clear(&self, _name: &str) -> Result<bool, CliError> {
+        Ok(false)
+    }
+}
+
+/// The unlock cache of one user in one runtime directory, with its key in `K`
+#[derive(Debug, Clone)]
+pub(crate) struct Cache<K = KernelKeyring> {
+    dir: PathBuf,
+    uid: u32,
+    boot_id: Option<String>,
+    keys: K,
+}
+
+impl Cache {
+    /// The cache of the current user in the runtime directory, or `None` if
+    /// `$XDG_RUNTIME_DIR` is not set
+    pub(crate) fn new() -> Option<Self> {
+        // SAFETY: getuid cannot fail
+        let uid = unsafe { libc::getuid() };
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(runtime) if !runtime.is_empty() => PathBuf::from(runtime).join("flowerpassword"),
+            _ => return None,
+        };
+        let boot_id = fs::read_to_string(BOOT_ID_PATH)
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty() && id.len() <= u8::MAX as usize);
+        Some(Cache {
+            dir,
+            uid,
+            boot_id,
+            keys: KernelKeyring,
+        })
+    }
+}
+
+impl<K: KeyStore> Cache<K> {
+    fn cache_path(&self) -> PathBuf {
+        self.dir.join(CACHE_FILE)
+    }
+
+    /// Names the key after the cache file, so that each runtime directory has
+    /// its own
+    fn key_name(&self) -> String {
+        format!("flowerpassword:{}", self.cache_path().display())
+    }
+
+    /// Caches `master` for `timeout` from `now`
+    pub(crate) fn store(
+        &self,
+        master: &str,
+        now: SystemTime,
+        timeout: Duration,
+    ) -> Result<(), CliError> {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        self.check_private(&self.dir)?;
+
+        let mut key = Zeroizing::new([0; 32]);
+        OsRng.fill_bytes(&mut *key);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut data = self.header(now + timeout);
+        data.extend_from_slice(&nonce);
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: master.as_bytes(),
+                    aad: &data,
+                },
+            )
+            .expect("a master password fits in a single message");
+        data.extend_from_slice(&ciphertext);
+
+        self.keys.set(&self.key_name(), &*key, timeout)?;
+        write_private(&self.cache_path(), &data)?;
+        Ok(())
+    }
+
+    /// Returns the cached master password, or `None` if there is none or it is
+    /// stale: expired at `now`, from another boot or user, or tampered with
+    ///
+    /// # Errors
+    ///
+    /// Returns `CliError::InsecureCache` if other users may access the cache.
+    pub(crate) fn load(&self, now: SystemTime) -> Result<Option<Zeroizing<String>>, CliError> {
+        let Some(Sealed { data, key }) = self.read()? else {
+            return Ok(None);
+        };
+        let Some(header_len) = self.valid_header(&data, now) else {
+            return Ok(None);
+        };
+        if key.len() != 32 || data.len() < header_len + NONCE_LEN {
+            return Ok(None);
+        }
+        let (aad, rest) = data.split_at(header_len + NONCE_LEN);
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key)).decrypt(
+            Nonce::from_slice(&aad[header_len..]),
+            Payload { msg: rest, aad },
+        );
+        let Ok(plaintext) = plaintext else {
+            return Ok(None);
+        };
+        match String::from_utf8(plaintext) {
+            Ok(master) => Ok(Some(Zeroizing::new(master))),
+            Err(error) => {
+                drop(Zeroizing::new(error.into_bytes()));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns how long the cache stays valid after `now`, or `None` if it is
+    /// missing or stale
+    pub(crate) fn remaining(&self, now: SystemTime) -> Result<Option<Duration>, CliError> {
+        if self.load(now)?.is_none() {
+            return Ok(None);
+        }
+        let Some(Sealed { data, .. }) = self.read()? else {
+            return Ok(None);
+        };
+        Ok(expiry(&data).and_then(|expires| expires.duration_since(now).ok()))
+    }
+
+    /// Drops the key, then overwrites and removes the cache file, returning
+    /// whether there was a cache
+    ///
+    /// # Errors
+    ///
+    /// Returns `CliError::InsecureCache` if other users may access the cache,
+    /// which is then left alone.
+    pub(crate) fn clear(&self) -> Result<bool, CliError> {
+        let exists = self.check_dir()?;
+        let mut found = self.keys.clear(&self.key_name())?;
+        if !exists {
+            return Ok(found);
+        }
+        let path = self.cache_path();
+        match OpenOptions::new().write(true).open(&path) {
+            Ok(mut file) => {
+                // Overwrite what was checked, not whatever the path names by now
+                self.check_private_file(&path, &file)?;
+                let len = file.metadata()?.len() as usize;
+                file.write_all(&vec![0; len])?;
+                file.sync_all()?;
+                fs::remove_file(&path)?;
+                found = true;
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        Ok(found)
+    }
+
+    /// Reads the cache file, checking that only its owner may, and its key
+    fn read(&self) -> Result<Option<Sealed>, CliError> {
+        if !self.check_dir()? {
+            return Ok(None);
+        }
+        let path = self.cache_path();
+        let data = match File::open(&path) {
+            Ok(mut file) => {
+                // Read what was checked, not whatever the path names by now
+                self.check_private_file(&path, &file)?;
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                data
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let Some(key) = self.keys.get(&self.key_name())? else {
+            return Ok(None);
+        };
+        Ok(Some(Sealed { data, key }))
+    }
+
+    /// Returns whether the cache directory exists, checking that only its owner
+    /// may access it
+    fn check_dir(&self) -> Result<bool, CliError> {
+        match fs::metadata(&self.dir) {
+            Ok(_) => self.check_private(&self.dir).map(|()| true),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn check_private(&self, path: &Path) -> Result<(), CliError> {
+        let metadata = fs::metadata(path)?;
+        self.check_owner(path, metadata.uid(), metadata.mode())
+    }
+
+    fn check_private_file(&self, path: &Path, file: &File) -> Result<(), CliError> {
+        let metadata = file.metadata()?;
+        self.check_owner(path, metadata.uid(), metadata.mode())
+    }
+
+    fn check_owner(&self, path: &Path, uid: u32, mode: u32) -> Result<(), CliError> {
+        if uid != self.uid || mode & 0o077 != 0 {
+            return Err(CliError::InsecureCache {
+                path: path.to_path_buf(),
+                mode: mode & 0o777,
+            });
+        }
+        Ok(())
+    }
+
+    /// The authenticated header up to the nonce
+    fn header(&self, expires: SystemTime) -> Vec<u8> {
+        let expires = expires
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let boot_id = self.boot_id.as_deref().unwrap_or_default().as_bytes();
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&expires.to_be_bytes());
+        header.extend_from_slice(&self.uid.to_be_bytes());
+        header.push(boot_id.len() as u8);
+        header.extend_from_slice(boot_id);
+        header
+    }
+
+    /// Returns the length of the header of `data` if it is current at `now`
+    fn valid_header(&self, data: &[u8], now: SystemTime) -> Option<usize> {
+        let expires = expiry(data)?;
+        // The header is only current if it is exactly the one this cache writes
+        let header = self.header(expires);
+        (expires > now && data.starts_with(&header)).then_some(header.len())
+    }
+}
+
+/// Returns the expiry time in the header of `data`
+fn expiry(data: &[u8]) -> Option<SystemTime> {
+    let data = data.strip_prefix(MAGIC)?;
+    let seconds = u64::from_be_bytes(data.get(..8)?.try_into().ok()?);
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Replaces `path` with `data` in a file only its owner may access
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    let _ = fs::remove_file(&temporary);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temporary)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    thread_local! {
+        static KEYS: RefCell<HashMap<String, Vec<u8>>> = RefCell::default();
+    }
+
+    /// A key store shared by the caches of one test, which runs on its own
+    /// thread
+    #[derive(Debug, Clone, Copy)]
+    struct MemoryKeys;
+
+    impl KeyStore for MemoryKeys {
+        fn set(&self, name: &str, key: &[u8], _timeout: Duration) -> Result<(), CliError> {
+            KEYS.with(|keys| keys.borrow_mut().insert(name.to_string(), key.to_vec()));
+            Ok(())
+        }
+
+        fn get(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, CliError> {
+            Ok(KEYS.with(|keys| keys.borrow().get(name).cloned().map(Zeroizing::new)))
+        }
+
+        fn clear(&self, name: &str) -> Result<bool, CliError> {
+            Ok(KEYS.with(|keys| keys.borrow_mut().remove(name).is_some()))
+        }
+    }
+
+    fn cache(dir: &Path, boot_id: Option<&str>) -> Cache<MemoryKeys> {
+        Cache {
+            dir: dir.join("flowerpassword"),
+            // SAFETY: getuid cannot fail
+            uid: unsafe { libc::getuid() },
+            boot_id: boot_id.map(str::to_string),
+            keys: MemoryKeys,
+        }
+    }
+
+    /// Stores `master` at the epoch, to expire `seconds` later
+    fn store(cache: &Cache<MemoryKeys>, master: &str, seconds: u64) -> Result<(), CliError> {
+        cache.store(master, at(0), Duration::from_secs(seconds))
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(Duration::from_secs(42)), "42s");
+        assert_eq!(format_remaining(Duration::from_secs(845)), "14m 05s");
+        assert_eq!(format_remaining(Duration::from_secs(3725)), "1h 02m 05s");
+    }
+
+    #[test]
+    fn test_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Some("boot-a"));
+        store(&cache, "test", 1_000).unwrap();
+
+        assert_eq!(cache.load(at(999)).unwrap().unwrap().as_str(), "test");
+        assert_eq!(
+            cache.remaining(at(400)).unwrap(),
+            Some(Duration::from_secs(600))
+        );
+        assert!(cache.load(at(1_000)).unwrap().is_none());
+        assert_eq!(cache.remaining(at(2_000)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_stale_caches_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        store(&cache(dir.path(), Some("boot-a")), "test", 1_000).unwrap();
+        // After a reboot
+        assert!(cache(dir.path(), Some("boot-b"))
+            .load(at(10))
+            .unwrap()
+            .is_none());
+        assert!(cache(dir.path(), None).load(at(10)).unwrap().is_none());
+
+        // Extending the expiry in place breaks the tag
+        let path = dir.path().join("flowerpassword").join(CACHE_FILE);
+        let mut data = fs::read(&path).unwrap();
+        data[8..16].copy_from_slice(&5_000u64.to_be_bytes());
+        fs::write(&path, data).unwrap();
+        assert!(cache(dir.path(), Some("boot-a"))
+            .load(at(10))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_files_are_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), None);
+        store(&cache, "test", 1_000).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&cache.dir), 0o700);
+        assert_eq!(mode(&cache.cache_path()), 0o600);
+        // Only the cache file is written; its key is not
+        let names: Vec<_> = fs::read_dir(&cache.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, [CACHE_FILE]);
+        let key = cache.keys.get(&cache.key_name()).unwrap().unwrap();
+        assert_eq!(key.len(), 32);
+        assert!(!fs::read(cache.cache_path())
+            .unwrap()
+            .windows(key.len())
+            .any(|w| w == key.as_slice()));
+        // The password is not in the file
+        assert!(!fs::read(cache.cache_path())
+            .unwrap()
+            .windows(4)
+            .any(|w| w == b"test"));
+
+        fs::set_permissions(cache.cache_path(), fs::Permissions::from_mode(0o644)).unwrap();
+        let error = cache.load(at(10)).unwrap_err();
+        assert!(matches!(error, CliError::InsecureCache { mode: 0o644, .. }));
+
+        fs::set_permissions(cache.cache_path(), fs::Permissions::from_mode(0o600)).unwrap();
+        fs::set_permissions(&cache.dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(cache.load(at(10)).is_err());
+        assert!(store(&cache, "test", 1_000).is_err());
+        // Nor is anything overwritten in a directory others may access
+        assert!(matches!(
+            cache.clear().unwrap_err(),
+            CliError::InsecureCache { mode: 0o755, .. }
+        ));
+        assert!(cache.cache_path().exists());
+    }
+
+    #[test]
+    fn test_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), None);
+        assert!(!cache.clear().unwrap());
+        store(&cache, "test", 1_000).unwrap();
+        assert!(cache.clear().unwrap());
+        assert!(!cache.cache_path().exists());
+        assert!(cache.keys.get(&cache.key_name()).unwrap().is_none());
+        assert!(cache.load(at(10)).unwrap().is_none());
+        assert!(!cache.clear().unwrap());
+    }
+}
@@ -0,0 +1,192 @@
+//! `fp handle-uri` and `fp register-uri-handler`, for `flowerpassword://` links
+//!
+//! The desktop runs `fp handle-uri URI` when a link of [`SCHEME`] is opened, as
+//! [`flowerpassword::uri`] describes them. There is no terminal to ask on, so
+//! the master password is the one of the agent, `fp unlock`'s cache or the OS
+//! keyring, as for `fp native-host`. With `copy=1` the password goes to the
+//! clipboard until `DEFAULT_TTL` has passed; otherwise it is printed.
+//!
+//! `fp register-uri-handler` makes this fp the handler: a desktop entry and
+//! `xdg-mime` on Linux and the BSDs, and the `HKEY_CURRENT_USER` classes of the
+//! registry on Windows. macOS only takes handlers from application bundles.
+
+use crate::cli::{derive, Generate};
+use crate::error::CliError;
+#[cfg(any(all(unix, not(target_os = "macos")), windows))]
+use crate::i18n::Msg;
+use flowerpassword::clipboard::{copy_with_ttl_using, Clipboard, ClipboardGuard};
+use flowerpassword::config::Config;
+use flowerpassword::uri::FpUriRequest;
+#[cfg(any(all(unix, not(target_os = "macos")), windows))]
+use flowerpassword::uri::SCHEME;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+#[cfg(any(all(unix, not(target_os = "macos")), windows))]
+use std::process::Command;
+use std::time::Duration;
+
+/// Name of the desktop entry of the handler
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) const DESKTOP_ENTRY: &str = "flowerpassword.desktop";
+
+/// Derives the password of `request`, copying it to the clipboard `clipboard`
+/// returns for `ttl` if it asks for that, and printing it to `out` otherwise
+///
+/// The clipboard is only looked for when the password goes to it.
+pub(crate) fn handle<C: Clipboard + 'static>(
+    generator: &impl Generate,
+    config: &Config,
+    request: &FpUriRequest,
+    clipboard: impl FnOnce() -> Result<C, CliError>,
+    ttl: Duration,
+    out: &mut impl Write,
+) -> Result<Option<ClipboardGuard>, CliError> {
+    let derived = derive(generator, config, &request.key, request.length)?;
+    if request.copy {
+        let guard = copy_with_ttl_using(clipboard()?, &derived.password, ttl)?;
+        return Ok(Some(guard));
+    }
+    writeln!(out, "{}", derived.password)?;
+    Ok(None)
+}
+
+/// Runs `program` with `args`, failing with what it wrote to standard error
+#[cfg(any(all(unix, not(target_os = "macos")), windows))]
+fn run(program: &str, args: &[&str]) -> io::Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(error.kind(), Msg::NotInstalled.fill(&[&program]))
+            }
+            _ => error,
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(io::Error::other(match stderr.trim() {
+        "" => format!("{}: {}", program, output.status),
+        message => format!("{}: {}", program, message),
+    }))
+}
+
+/// Quotes `path` as an argument of the `Exec` key of a desktop entry, whose
+/// quoting rule is applied after the escapes of a string value
+#[cfg(all(unix, not(target_os = "macos")))]
+fn exec_quote(path: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '\\' => quoted.push_str(r"\\\\"),
+            '"' | '`' | '$' => {
+                quoted.push_str(r"\\");
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the desktop entry running `binary handle-uri` into `data_dir` and
+/// makes it the default handler of the scheme, returning its path
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn register(data_dir: &Path, binary: &Path) -> Result<PathBuf, CliError> {
+    let binary = std::path::absolute(binary)?;
+    let dir = data_dir.join("applications");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(DESKTOP_ENTRY);
+    let mime = format!("x-scheme-handler/{}", SCHEME);
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Flower Password\n\
+         Exec={} handle-uri %u\n\
+         MimeType={};\n\
+         NoDisplay=true\n\
+         Terminal=false\n",
+        exec_quote(&binary),
+        mime
+    );
+    std::fs::write(&path, entry)?;
+    run("xdg-mime", &["default", DESKTOP_ENTRY, &mime])?;
+    Ok(path)
+}
+
+/// Adds the classes of the scheme running `binary handle-uri` to the registry
+/// of the user, returning their key
+#[cfg(windows)]
+pub(crate) fn register(_data_dir: &Path, binary: &Path) -> Result<PathBuf, CliError> {
+    let binary = std::path::absolute(binary)?;
+    let key = format!(r"HKCU\Software\Classes\{}", SCHEME);
+    let command = format!("\"{}\" handle-uri \"%1\"", binary.display());
+    let description = format!("URL:{}", SCHEME);
+    run("reg", &["add", &key, "/ve", "/d", &description, "/f"])?;
+    run("reg", &["add", &key, "/v", "URL Protocol", "/d", "", "/f"])?;
+    let open = format!(r"{}\shell\open\command", key);
+    run("reg", &["add", &open, "/ve", "/d", &command, "/f"])?;
+    Ok(PathBuf::from(key))
+}
+
+/// macOS only registers the schemes of application bundles
+#[cfg(not(any(all(unix, not(target_os = "macos")), windows)))]
+pub(crate) fn register(_data_dir: &Path, _binary: &Path) -> Result<PathBuf, CliError> {
+    Err(io::Error::from(io::ErrorKind::Unsupported).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowerpassword::clipboard::{MemoryClipboard, Outcome};
+    use flowerpassword::config::Config;
+    use flowerpassword::uri::parse_fp_uri;
+    use flowerpassword::FpGenerator;
+
+    const SITES: &str = include_str!("../tests/fixtures/launcher.toml");
+
+    #[test]
+    fn test_handle() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let config = Config::from_toml_str(SITES, "config.toml").unwrap().config;
+        let clipboard = MemoryClipboard::new(Some("before"));
+        let ttl = Duration::from_secs(60);
+
+        let request = parse_fp_uri("flowerpassword://derive?key=github.com&len=16&copy=1").unwrap();
+        let mut out = Vec::new();
+        let guard = handle(
+            &generator,
+            &config,
+            &request,
+            || Ok(clipboard.clone()),
+            ttl,
+            &mut out,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(out.is_empty());
+        assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(clipboard.contents().as_deref(), Some("before"));
+
+        // Printed without copy, at the site's length for a configured name
+        let request = parse_fp_uri("flowerpassword://derive?key=github").unwrap();
+        let nowhere = || -> Result<MemoryClipboard, CliError> { panic!("looked for") };
+        let guard = handle(&generator, &config, &request, nowhere, ttl, &mut out).unwrap();
+        assert!(guard.is_none());
+        assert_eq!(out, b"D04175F7A9c7Ab4a\n");
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn test_exec_quote() {
+        assert_eq!(exec_quote(Path::new("/usr/bin/fp")), "\"/usr/bin/fp\"");
+        assert_eq!(
+            exec_quote(Path::new(r#"/opt/a b/$x"\%"#)),
+            r#""/opt/a b/\\$x\\"\\\\%%""#
+        );
+    }
+}
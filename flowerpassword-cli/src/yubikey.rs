@@ -0,0 +1,273 @@
+//! `--master yubikey`, a master password from a YubiKey's HMAC-SHA1
+//! challenge-response slot
+//!
+//! The key is driven with `ykchalresp` of yubikey-personalization, which sends
+//! the challenge of [`flowerpassword::yubikey`] for `--yubikey-label` to the
+//! slot and prints the response in hex. Whether the slot waits for a touch is
+//! found out by sending the bare [`CHALLENGE_PREFIX`] with `-N` first, which
+//! fails rather than wait on such a slot; a touch not given within
+//! [`DEFAULT_TIMEOUT`] stops the tool.
+
+use crate::error::CliError;
+use crate::i18n::Msg;
+use crate::tool;
+use flowerpassword::yubikey::{
+    self, master_from_yubikey, ChallengeResponse, Slot, YubiKeyError, CHALLENGE_PREFIX,
+    DEFAULT_TIMEOUT, RESPONSE_LEN,
+};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Label of `--master yubikey` without `--yubikey-label`
+pub(crate) const DEFAULT_LABEL: &str = "default";
+
+/// A [`ChallengeResponse`] running `ykchalresp`, on the first YubiKey
+#[derive(Debug, Clone)]
+pub(crate) struct Ykchalresp {
+    program: PathBuf,
+}
+
+impl Default for Ykchalresp {
+    fn default() -> Self {
+        Ykchalresp {
+            program: PathBuf::from("ykchalresp"),
+        }
+    }
+}
+
+impl Ykchalresp {
+    #[cfg(test)]
+    fn at(program: &std::path::Path) -> Self {
+        Ykchalresp {
+            program: program.to_path_buf(),
+        }
+    }
+
+    /// Sends `challenge` to `slot`, failing at once on a slot that waits for a
+    /// touch unless `block`, and returns the standard output, wiped on drop
+    /// since it holds the response
+    fn run(
+        &self,
+        slot: Slot,
+        challenge: &[u8],
+        block: bool,
+        timeout: Duration,
+    ) -> Result<Zeroizing<Vec<u8>>, YubiKeyError> {
+        let hex: String = challenge
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let mut command = Command::new(&self.program);
+        command.arg(format!("-{}", slot)).arg("-H");
+        if !block {
+            command.arg("-N");
+        }
+        let mut child = command
+            .args(["-x", &hex])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => {
+                    YubiKeyError::Device(Msg::NotInstalled.fill(&[&"ykchalresp"]))
+                }
+                _ => YubiKeyError::Device(error.to_string()),
+            })?;
+        let status = match tool::wait_timeout(&mut child, timeout) {
+            Ok(Some(status)) => status,
+            Ok(None) => return Err(YubiKeyError::TouchTimeout(timeout)),
+            Err(error) => return Err(YubiKeyError::Device(error.to_string())),
+        };
+        let mut stdout = Zeroizing::new(Vec::new());
+        let mut stderr = String::new();
+        let read = child
+            .stdout
+            .take()
+            .map_or(Ok(0), |mut out| out.read_to_end(&mut stdout))
+            .and_then(|_| {
+                child
+                    .stderr
+                    .take()
+                    .map_or(Ok(0), |mut err| err.read_to_string(&mut stderr))
+            });
+        read.map_err(|error| YubiKeyError::Device(error.to_string()))?;
+        if !status.success() {
+            return Err(tool_error(&stderr, status.to_string(), slot, timeout));
+        }
+        Ok(stdout)
+    }
+}
+
+impl ChallengeResponse for Ykchalresp {
+    fn requires_touch(&mut self, slot: Slot) -> Result<bool, YubiKeyError> {
+        match self.run(slot, CHALLENGE_PREFIX.as_bytes(), false, DEFAULT_TIMEOUT) {
+            Ok(_) => Ok(false),
+            Err(YubiKeyError::Device(message)) if message.contains("would block") => Ok(true),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn challenge_response(
+        &mut self,
+        slot: Slot,
+        challenge: &[u8],
+        timeout: Duration,
+    ) -> Result<[u8; RESPONSE_LEN], YubiKeyError> {
+        let output = self.run(slot, challenge, true, timeout)?;
+        let text = std::str::from_utf8(&output).unwrap_or_default().trim();
+        let unexpected = || YubiKeyError::Device(Msg::Fido2Output.fill(&[&"ykchalresp"]));
+        if text.len() != RESPONSE_LEN * 2 {
+            return Err(unexpected());
+        }
+        let mut response = Zeroizing::new([0; RESPONSE_LEN]);
+        for (i, byte) in response.iter_mut().enumerate() {
+            *byte = text
+                .get(i * 2..i * 2 + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(unexpected)?;
+        }
+        Ok(*response)
+    }
+}
+
+/// Maps what `ykchalresp` wrote to standard error to an error, or to `status`
+/// if it wrote nothing
+fn tool_error(stderr: &str, status: String, slot: Slot, timeout: Duration) -> YubiKeyError {
+    let lower = stderr.to_lowercase();
+    if lower.contains("no yubikey") {
+        YubiKeyError::NotFound
+    } else if lower.contains("timeout") {
+        YubiKeyError::TouchTimeout(timeout)
+    } else if lower.contains("not configured") || lower.contains("not programmed") {
+        YubiKeyError::SlotNotConfigured(slot)
+    } else {
+        YubiKeyError::Device(match stderr.trim() {
+            "" => status,
+            message => message.to_string(),
+        })
+    }
+}
+
+/// Reads the master password of `--master yubikey[:SLOT]` for `label`
+///
+/// `on_touch` is called before waiting for the key to be touched.
+pub(crate) fn read_master(
+    key: impl ChallengeResponse,
+    spec: &str,
+    label: Option<&str>,
+    on_touch: impl FnOnce(),
+) -> Result<Zeroizing<String>, CliError> {
+    let slot = yubikey::parse_source(spec)?;
+    let label = label.unwrap_or(DEFAULT_LABEL);
+    Ok(master_from_yubikey(
+        key,
+        slot,
+        label,
+        DEFAULT_TIMEOUT,
+        on_touch,
+    )?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    /// Writes a fake `ykchalresp` into `dir` that logs its arguments and
+    /// answers with 3f8a..., after a touch if `touch`
+    fn fake_ykchalresp(dir: &Path, touch: bool) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("ykchalresp");
+        let log = dir.join("log");
+        let script = format!(
+            "#!/bin/sh\necho \"$@\" >> '{}'\n\
+             case \"$3\" in -N) [ {} = true ] && {{ \
+             echo 'Yubikey core error: operation would block' >&2; exit 1; }} ;; esac\n\
+             echo 3f8a01c25e77900bd416a9ee42005c816df328b7\n",
+            log.display(),
+            touch
+        );
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_master() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = Ykchalresp::at(&fake_ykchalresp(dir.path(), false));
+        let master = read_master(key, "yubikey", Some("personal"), || panic!("touched")).unwrap();
+        assert_eq!(*master, "3f8a01c25e77900bd416a9ee42005c816df328b7");
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        // The bare prefix without blocking, then "flowerpassword:personal"
+        assert_eq!(
+            log,
+            "-2 -H -N -x 666c6f77657270617373776f72643a\n\
+             -2 -H -x 666c6f77657270617373776f72643a706572736f6e616c\n"
+        );
+        assert_eq!(
+            flowerpassword::fp_code(&master, "github.com", 16).unwrap(),
+            flowerpassword::fp_code("3f8a01c25e77900bd416a9ee42005c816df328b7", "github.com", 16)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_touch() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = Ykchalresp::at(&fake_ykchalresp(dir.path(), true));
+        let mut touched = false;
+        let master = read_master(key, "yubikey:1", None, || touched = true).unwrap();
+        assert!(touched);
+        assert_eq!(master.len(), 40);
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        assert!(log.ends_with("-1 -H -x 666c6f77657270617373776f72643a64656661756c74\n"));
+
+        fs::write(dir.path().join("ykchalresp"), "#!/bin/sh\nsleep 5\n").unwrap();
+        let mut key = Ykchalresp::at(&dir.path().join("ykchalresp"));
+        let timeout = Duration::from_millis(200);
+        let error = key
+            .challenge_response(Slot::Two, b"x", timeout)
+            .unwrap_err();
+        assert!(matches!(error, YubiKeyError::TouchTimeout(_)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fake_ykchalresp(dir.path(), false);
+        fs::write(
+            &path,
+            "#!/bin/sh\necho 'Yubikey core error: no yubikey present' >&2\nexit 1\n",
+        )
+        .unwrap();
+        let error = read_master(Ykchalresp::at(&path), "yubikey", None, || {}).unwrap_err();
+        assert!(matches!(error, CliError::YubiKey(YubiKeyError::NotFound)));
+
+        fs::write(&path, "#!/bin/sh\necho zz\n").unwrap();
+        let error = read_master(Ykchalresp::at(&path), "yubikey", None, || {}).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YubiKey error: ykchalresp wrote something other than expected"
+        );
+
+        let missing = Ykchalresp::at(&dir.path().join("missing"));
+        let error = read_master(missing, "yubikey", None, || {}).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YubiKey error: ykchalresp is not installed or not on PATH"
+        );
+
+        let error = read_master(Ykchalresp::default(), "yubikey:3", None, || {}).unwrap_err();
+        assert!(matches!(
+            error,
+            CliError::YubiKey(YubiKeyError::InvalidSlot(_))
+        ));
+    }
+}
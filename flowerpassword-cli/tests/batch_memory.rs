@@ -0,0 +1,117 @@
+//! Memory ceiling of the streaming `--jobs` pipeline
+//!
+//! A counting allocator tracks the bytes allocated and not yet freed while a
+//! batch of [`KEYS`] keys, generated as it is read, streams through
+//! `jobs::stream_ordered` on four threads, in order. The peak must stay under
+//! [`CEILING`], far below what holding the keys or their passwords would take.
+//!
+//! This runs without the test harness, so that no other test allocates
+//! meanwhile.
+
+use flowerpassword::fp_code;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Only the pipeline is used; without the harness its unit tests are left out
+// but their imports are not
+#[path = "../src/jobs.rs"]
+#[allow(dead_code, unused_imports)]
+mod jobs;
+
+/// Keys in the synthetic batch
+const KEYS: usize = 200_000;
+
+/// Most bytes the pipeline may hold at once
+const CEILING: usize = 1 << 20;
+
+/// The system allocator, counting the bytes it has out
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn grew(size: usize) {
+    let live = LIVE.fetch_add(size, Ordering::SeqCst) + size;
+    PEAK.fetch_max(live, Ordering::SeqCst);
+}
+
+// SAFETY: every call is passed on to `System` unchanged
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+            grew(new_size);
+        }
+        new
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn key(n: usize) -> String {
+    format!("host{}.example.com", n)
+}
+
+fn main() {
+    let first = fp_code("test", &key(0), 16).unwrap();
+    let last = fp_code("test", &key(KEYS - 1), 16).unwrap();
+    let input_bytes: usize = (0..KEYS).map(|n| key(n).len() + 1).sum();
+
+    let baseline = LIVE.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let mut read = 0;
+    let mut written = 0;
+    let mut output_bytes = 0;
+    let count = jobs::stream_ordered(
+        || {
+            let next = (read < KEYS).then(|| key(read));
+            read += 1;
+            Ok::<_, ()>(next)
+        },
+        4,
+        |key| fp_code("test", key, 16).unwrap(),
+        |key, password| {
+            assert_eq!(key, self::key(written), "out of order");
+            match written {
+                0 => assert_eq!(password, first),
+                n if n == KEYS - 1 => assert_eq!(password, last),
+                _ => assert_eq!(password.len(), 16),
+            }
+            written += 1;
+            output_bytes += password.len() + 1;
+            Ok(())
+        },
+    )
+    .unwrap();
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+
+    assert_eq!(count, KEYS);
+    assert_eq!(written, KEYS);
+    // Holding either the input or the output would break the ceiling
+    assert!(input_bytes > 4 * CEILING && output_bytes > 2 * CEILING);
+    assert!(
+        peak < CEILING,
+        "{} bytes at the peak, over the {} byte ceiling",
+        peak,
+        CEILING
+    );
+    println!(
+        "batch_memory: {} keys, {} bytes at the peak of {} allowed",
+        KEYS, peak, CEILING
+    );
+}
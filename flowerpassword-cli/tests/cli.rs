@@ -0,0 +1,3713 @@
+//! End-to-end tests running the `fp` binary
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const CONFIG: &str = "[sites.github]\nkey = \"github.com\"\nlength = 16\n";
+
+/// Runs `fp` with `args`, `stdin` and a configuration directory of its own
+fn fp(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .env_remove("FP_CONFIG")
+        .env_remove("FP_LENGTH")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .env_remove("LANG")
+        .env_remove("XDG_STATE_HOME")
+        .env_remove("XDG_RUNTIME_DIR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // fp may exit before reading, for example on a usage error
+    if let Err(error) = child.stdin.take().unwrap().write_all(stdin.as_bytes()) {
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+    child.wait_with_output().unwrap()
+}
+
+/// A directory holding `config.toml` with [`CONFIG`]
+fn config_dir() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("config.toml"), CONFIG).unwrap();
+    dir
+}
+
+fn config_arg(dir: &Path) -> String {
+    dir.join("config.toml").display().to_string()
+}
+
+#[test]
+fn test_keys() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "github", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\n".to_vec()
+    );
+    let output = fp(dir.path(), &["key"], "password\n");
+    assert_eq!(output.stdout, b"K3A2a66Bf88b628c".to_vec());
+}
+
+#[test]
+fn test_batch() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "--batch", "-l", "8"],
+        "test\ngithub\n\ngithub.com\r\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7\nD04175F7\n".to_vec());
+}
+
+#[test]
+fn test_format() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &[
+            "--config",
+            &config,
+            "--format",
+            "{key}: {password}",
+            "github",
+        ],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"github.com: D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp(
+        dir.path(),
+        &[
+            "--config",
+            &config,
+            "--batch",
+            "--null",
+            "-l",
+            "8",
+            "--format",
+            "{{\"{key}\":[{length},\"{scheme}\"]}}",
+        ],
+        "test\ngithub\ngithub.com\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"{\"github.com\":[8,\"v1\"]}\0{\"github.com\":[8,\"v1\"]}\0".to_vec()
+    );
+
+    let output = fp(
+        dir.path(),
+        &["--format", "{key} {pass}", "github"],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Unknown placeholder {pass} at column 7")
+    );
+}
+
+#[test]
+fn test_confirm_without_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["--confirm", "github.com"], "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: warning: --confirm needs a terminal, reading the master password once from standard input\n"
+    );
+}
+
+#[test]
+fn test_password_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("master");
+    std::fs::write(&path, "test\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+    let path = path.display().to_string();
+    let output = fp(
+        dir.path(),
+        &["--password-file", &path, "--batch"],
+        "github.com\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a\n".to_vec());
+
+    let output = fp(
+        dir.path(),
+        &["--password-file", &path, "--confirm", "github.com"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_password_fd_from_a_pipe() {
+    let dir = tempfile::tempdir().unwrap();
+    // The shell hands fp the read end of a pipe as descriptor 3
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg("printf 'test\\n' | \"$0\" --password-fd 3 github.com 3<&0 </dev/null")
+        .arg(env!("CARGO_BIN_EXE_fp"))
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp(dir.path(), &["--password-fd", "9", "github.com"], "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: File descriptor 9 is not open for reading the master password\n"
+    );
+}
+
+/// A `gpg` printing the "plaintext" of existing files, with shell builtins only
+#[cfg(unix)]
+const FAKE_GPG: &str = r#"#!/bin/sh
+[ "$1 $2" = "--quiet --decrypt" ] || exit 9
+if [ ! -f "$3" ]; then
+    echo "gpg: decryption failed: No secret key" >&2
+    exit 2
+fi
+while read -r line; do echo "$line"; done < "$3"
+"#;
+
+/// Runs `fp --password-gpg` with only a fake `gpg` script, if any, on `PATH`
+#[cfg(unix)]
+fn fp_with_gpg(script: Option<&str>, file: &str) -> Output {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    if let Some(script) = script {
+        let gpg = dir.path().join("gpg");
+        std::fs::write(&gpg, script).unwrap();
+        std::fs::set_permissions(&gpg, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::fs::write(dir.path().join("master.gpg"), "test\n").unwrap();
+    Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--password-gpg", file, "github.com"])
+        .current_dir(dir.path())
+        .env("PATH", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn test_password_gpg() {
+    let output = fp_with_gpg(Some(FAKE_GPG), "master.gpg");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp_with_gpg(Some(FAKE_GPG), "missing.gpg");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: gpg could not decrypt the master password: gpg: decryption failed: No secret key\n"
+    );
+
+    let output = fp_with_gpg(None, "master.gpg");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: gpg is not installed or not on PATH\n"
+    );
+}
+
+#[test]
+fn test_quiet_output_is_only_passwords() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--quiet", "--no-newline", "--confirm", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+    assert!(output.stderr.is_empty());
+
+    let output = fp(
+        dir.path(),
+        &["-q", "--no-newline", "--batch"],
+        "test\ngithub.com\nkey\n",
+    );
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nKAC5fEf00146FD68".to_vec()
+    );
+    let output = fp(
+        dir.path(),
+        &["-0", "--no-newline", "--batch"],
+        "test\na\nb\n",
+    );
+    assert_eq!(output.stdout.iter().filter(|&&b| b == 0).count(), 1);
+}
+
+#[test]
+fn test_piped_newlines() {
+    let dir = tempfile::tempdir().unwrap();
+    let stdout = |args: &[&str], stdin: &str| {
+        let output = fp(dir.path(), args, stdin);
+        assert!(output.status.success(), "{:?}", output);
+        output.stdout
+    };
+    // A single piped password has no newline, unless asked for
+    assert_eq!(stdout(&["github.com"], "test\n"), b"D04175F7A9c7Ab4a");
+    assert_eq!(
+        stdout(&["--newline", "github.com"], "test\n"),
+        b"D04175F7A9c7Ab4a\n"
+    );
+    assert_eq!(
+        stdout(&["--newline", "--no-newline", "github.com"], "test\n"),
+        b"D04175F7A9c7Ab4a"
+    );
+    assert_eq!(
+        stdout(&["--raw", "github.com"], "test\n"),
+        b"D04175F7A9c7Ab4a"
+    );
+    assert_eq!(
+        stdout(&["--raw", "--newline", "github.com"], "test\n"),
+        b"D04175F7A9c7Ab4a\n"
+    );
+    // Several records stay lines
+    assert_eq!(
+        stdout(&["github.com", "key"], "test\n"),
+        b"D04175F7A9c7Ab4a\nKAC5fEf00146FD68\n"
+    );
+    assert_eq!(
+        stdout(&["--batch"], "test\ngithub.com\n"),
+        b"D04175F7A9c7Ab4a\n"
+    );
+    assert_eq!(
+        stdout(&["--raw", "github.com", "key"], "test\n"),
+        b"D04175F7A9c7Ab4a\nKAC5fEf00146FD68"
+    );
+    assert_eq!(
+        stdout(&["-0", "github.com"], "test\n"),
+        b"D04175F7A9c7Ab4a\0"
+    );
+
+    let output = fp(dir.path(), &["--raw", "--entropy", "github.com"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Writes the master password and 10 000 keys for `--batch` into `dir`
+///
+/// They are read from a file, since a pipe written in full before the output is
+/// read would fill up.
+fn many_keys(dir: &Path) -> std::fs::File {
+    let keys: Vec<String> = (0..10_000).map(|n| format!("site{}.example", n)).collect();
+    let path = dir.join("keys.txt");
+    std::fs::write(&path, format!("test\n{}\n", keys.join("\n"))).unwrap();
+    std::fs::File::open(path).unwrap()
+}
+
+/// Runs `fp --batch` with `args` on the keys of [`many_keys`]
+fn fp_many(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fp"))
+        .arg("--batch")
+        .args(args)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .env_remove("FP_CONFIG")
+        .env_remove("FP_LENGTH")
+        .stdin(many_keys(dir))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_jobs_keep_the_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let sequential = fp_many(dir.path(), &[]);
+    assert!(sequential.status.success(), "{:?}", sequential);
+    let parallel = fp_many(dir.path(), &["--jobs", "8"]);
+    assert!(parallel.status.success(), "{:?}", parallel);
+    assert_eq!(
+        parallel.stdout.iter().filter(|&&b| b == b'\n').count(),
+        10_000
+    );
+    assert_eq!(parallel.stdout, sequential.stdout);
+    // Without a terminal there is no progress bar
+    assert!(parallel.stderr.is_empty());
+}
+
+#[test]
+fn test_jobs_stream_through_pipes() {
+    // Written and read at once: the whole input would never fit in a pipe, so
+    // this only ends if fp writes as it reads
+    const KEYS: usize = 50_000;
+    let key = |n: usize| format!("host{}.example.com", n);
+    let dir = tempfile::tempdir().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--batch", "--jobs", "4", "--stats"])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .env_remove("FP_CONFIG")
+        .env_remove("FP_LENGTH")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .env_remove("LANG")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || {
+        let mut input = std::io::BufWriter::new(&mut stdin);
+        writeln!(input, "test").unwrap();
+        for n in 0..KEYS {
+            writeln!(input, "{}", key(n)).unwrap();
+        }
+    });
+    let stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    let mut lines = 0;
+    for (n, line) in std::io::BufRead::lines(stdout).enumerate() {
+        let expected = flowerpassword::fp_code("test", &key(n), 16).unwrap();
+        assert_eq!(line.unwrap(), expected, "line {}", n);
+        lines += 1;
+    }
+    writer.join().unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(lines, KEYS);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with(&format!("{} keys in ", KEYS)) && stderr.ends_with(" keys/s\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_stats() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--batch", "--stats"],
+        "test
+key
+github.com
+",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"KAC5fEf00146FD68\nD04175F7A9c7Ab4a\n".to_vec()
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("2 keys in "), "{}", stderr);
+    let output = fp(
+        dir.path(),
+        &["--stats", "key"],
+        "test
+",
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_jobs_count_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("aliases.toml");
+    std::fs::write(&config, "[aliases]\nbroken = \"missing\"\n").unwrap();
+    let config = config.display().to_string();
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "--batch", "-j", "4"],
+        "test\ngithub.com\nbroken\nkey\nbroken\n",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    // The other keys are still derived, in order
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nKAC5fEf00146FD68\n".to_vec()
+    );
+    let failure = "fp: error: broken: Alias `broken` points to unknown site `missing`\n";
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{}{}fp: error: 2 of 4 keys failed\n", failure, failure)
+    );
+
+    let output = fp(dir.path(), &["--jobs", "4", "github"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+    let output = fp(dir.path(), &["--batch", "--jobs", "0"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_jobs_progress_stays_off_stdout() {
+    let (controller, terminal) = openpty();
+    let dir = tempfile::tempdir().unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--batch", "--jobs", "8"])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .stdin(many_keys(dir.path()))
+        .stdout(Stdio::piped())
+        .stderr(terminal)
+        .spawn()
+        .unwrap();
+    let _screen = Screen::new(controller);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, fp_many(dir.path(), &[]).stdout);
+}
+
+#[test]
+fn test_history_keeps_no_secrets() {
+    let dir = config_dir();
+    let master = "correct horse battery staple";
+    let input = format!("{}\n", master);
+    let plain = fp(dir.path(), &["github", "key", "-l", "8"], &input);
+    assert!(plain.status.success(), "{:?}", plain);
+    let passwords = String::from_utf8(plain.stdout).unwrap();
+    let history = dir.path().join(".local/state/flowerpassword/history.jsonl");
+    assert!(!history.exists());
+
+    let logged = fp(dir.path(), &["--log", "github", "key", "-l", "8"], &input);
+    assert_eq!(String::from_utf8(logged.stdout).unwrap(), passwords);
+    let text = std::fs::read_to_string(&history).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"ts\":"));
+    assert!(lines[0].ends_with(",\"site\":\"github\",\"length\":8,\"scheme\":\"v1\"}"));
+    assert!(lines[1].ends_with(",\"site\":\"key\",\"length\":8,\"scheme\":\"v1\"}"));
+    for secret in passwords.lines().chain([master, "correct", "staple"]) {
+        assert!(!text.contains(secret), "{:?}", secret);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&history).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let output = fp(dir.path(), &["history", "github", "--since", "1h"], "");
+    assert!(output.status.success(), "{:?}", output);
+    let shown = String::from_utf8(output.stdout).unwrap();
+    let shown: Vec<&str> = shown.lines().collect();
+    assert_eq!(shown.len(), 2);
+    assert!(shown[0].starts_with("TIME "));
+    assert!(shown[1].ends_with("  github  8       v1"));
+
+    let output = fp(dir.path(), &["history", "clear"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!history.exists());
+    let output = fp(dir.path(), &["history"], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "TIME                  SITE  LENGTH  SCHEME\n"
+    );
+}
+
+#[test]
+fn test_entropy_goes_to_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--quiet", "--entropy", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "github.com: about 67.9 bits, good (16 characters from 23 possible)\n"
+    );
+
+    let output = fp(dir.path(), &["--entropy", "-l", "8", "key"], "test\n");
+    assert_eq!(output.stdout, b"KAC5fEf0".to_vec());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "key: about 32.9 bits, weak (8 characters from 23 possible)\n"
+    );
+}
+
+#[test]
+fn test_interactive() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "-i"],
+        "test\ngithub\n:length 8\ngithub.com\n:lenght 4\n:lock\ngithub.com 16\npassword\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nD04175F7\nKC35d04D964297c7\n".to_vec()
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Unknown command :lenght, see :help\nLocked: the master password is wiped\n"
+    );
+
+    let output = fp(dir.path(), &["-i", "github"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["--length", "40", "github.com"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+    let output = fp(
+        dir.path(),
+        &["--format", "{pasword}", "github.com"],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let output = fp(dir.path(), &["github.com"], "");
+    assert_eq!(output.status.code(), Some(2));
+
+    let missing = dir.path().join("missing").display().to_string();
+    let output = fp(dir.path(), &["--password-file", &missing, "github.com"], "");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+
+    #[cfg(unix)]
+    assert_eq!(fp_with_gpg(None, "master.gpg").status.code(), Some(4));
+}
+
+/// Runs `fp` with its runtime directory in `dir`
+#[cfg(unix)]
+fn fp_in_runtime(dir: &Path, args: &[&str], stdin: impl AsRef<[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(args)
+        .env("XDG_RUNTIME_DIR", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // fp status exits without reading
+    if let Err(error) = child.stdin.take().unwrap().write_all(stdin.as_ref()) {
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn test_unlock_lock_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp_in_runtime(dir.path(), &["status"], "");
+    assert_eq!(output.stdout, b"Locked\n".to_vec());
+
+    let output = fp_in_runtime(dir.path(), &["unlock", "--timeout", "1h"], "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Unlocked for 1h 00m 00s\n"
+    );
+    let cache = dir.path().join("flowerpassword").join("unlock");
+    assert!(cache.exists());
+    let output = fp_in_runtime(dir.path(), &["status"], "");
+    let status = String::from_utf8(output.stdout).unwrap();
+    assert!(status.starts_with("Unlocked, "), "{}", status);
+    assert!(status.ends_with(" left\n"), "{}", status);
+
+    // Without a terminal the master password is still read from standard input
+    let output = fp_in_runtime(dir.path(), &["github.com"], "password\n");
+    assert_eq!(output.stdout, b"KC35d04D964297c7".to_vec());
+
+    let output = fp_in_runtime(dir.path(), &["lock"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!cache.exists());
+    let output = fp_in_runtime(dir.path(), &["status"], "");
+    assert_eq!(output.stdout, b"Locked\n".to_vec());
+
+    let output = fp_in_runtime(dir.path(), &["unlock", "--timeout", "soon"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Stops the agent of a test even if it fails
+#[cfg(unix)]
+struct AgentGuard<'a>(&'a Path);
+
+#[cfg(unix)]
+impl Drop for AgentGuard<'_> {
+    fn drop(&mut self) {
+        fp_in_runtime(self.0, &["agent", "stop"], "");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_agent_start_stop() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp_in_runtime(dir.path(), &["agent", "stop"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No agent answers at "));
+
+    // fp agent start returns once the agent answers in the background
+    let output = fp_in_runtime(dir.path(), &["agent", "start"], "test\n");
+    let _guard = AgentGuard(dir.path());
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("The agent answers at "));
+    let socket = dir.path().join("flowerpassword").join("agent.sock");
+    let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // The agent derives without reading the master password
+    let output = fp_in_runtime(dir.path(), &["--agent", "github.com"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp_in_runtime(dir.path(), &["agent", "start"], "test\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    let output = fp_in_runtime(dir.path(), &["agent", "stop"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Stopped the agent at "));
+    assert!(!socket.exists());
+
+    // Without an agent the master password is read as usual
+    let output = fp_in_runtime(dir.path(), &["--agent", "github.com"], "password\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"KC35d04D964297c7".to_vec());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_agent_endpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("elsewhere").join("agent.sock");
+    let endpoint = socket.display().to_string();
+    let args = |args: &[&'static str]| {
+        let mut all = args.to_vec();
+        all.extend_from_slice(&["--agent-endpoint", endpoint.as_str()]);
+        all
+    };
+
+    let output = fp_in_runtime(dir.path(), &args(&["agent", "start"]), "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(socket.exists());
+    assert!(!dir.path().join("flowerpassword").join("agent.sock").exists());
+
+    let output = fp_in_runtime(dir.path(), &args(&["--agent", "github.com"]), "");
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp_in_runtime(dir.path(), &args(&["agent", "stop"]), "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!socket.exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_unlock_needs_runtime_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    // fp never falls back to the shared temporary directory
+    let output = fp(dir.path(), &["unlock"], "test\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("$XDG_RUNTIME_DIR"), "{}", stderr);
+    let output = fp(dir.path(), &["status"], "");
+    assert_eq!(output.stdout, b"Locked\n".to_vec());
+    assert!(fp(dir.path(), &["lock"], "").status.success());
+}
+
+#[test]
+fn test_launcher_query() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    let config = format!("{}/launcher.toml", fixtures);
+    // Standard input is empty: a query never asks for the master password
+    let output = fp(
+        dir.path(),
+        &["launcher", "--config", &config, "--query", "git"],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let expected = std::fs::read(format!("{}/launcher-git.json", fixtures)).unwrap();
+    assert_eq!(output.stdout, expected);
+
+    let output = fp(dir.path(), &["launcher"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_menu() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/launcher.toml");
+    // Standard input is empty: listing never asks for the master password
+    let output = fp(dir.path(), &["menu", "--config", config], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"digitalocean\ngithub\ngitlab\n");
+
+    // Only the sites of the list are derived, checked before anything is read
+    let output = fp(
+        dir.path(),
+        &["menu", "--config", config, "--selected", "gh"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: No site matches gh\n"
+    );
+    let output = fp(dir.path(), &["menu", "--selected", "github", "--rofi"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+/// Runs `fp menu --rofi` with a fake rofi answering with `script`, outside of
+/// any graphical session
+#[cfg(unix)]
+fn fp_menu_rofi(dir: &Path, script: &str) -> Output {
+    use std::os::unix::fs::PermissionsExt;
+
+    let rofi = dir.join("rofi");
+    std::fs::write(&rofi, format!("#!/bin/sh\ncat > /dev/null\n{}", script)).unwrap();
+    std::fs::set_permissions(&rofi, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let config = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/launcher.toml");
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap());
+    Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["menu", "--rofi", "--config", config])
+        .env("PATH", path)
+        .env("HOME", dir)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn test_menu_rofi() {
+    let dir = tempfile::tempdir().unwrap();
+    // Dismissing the menu does nothing
+    let output = fp_menu_rofi(dir.path(), "exit 1\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+
+    // The pick is copied, here to no clipboard
+    let output = fp_menu_rofi(dir.path(), "echo github\n");
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("clipboard"));
+    let output = fp_menu_rofi(dir.path(), "echo nowhere\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    let output = fp_menu_rofi(dir.path(), "echo 'cannot open display' >&2\nexit 2\n");
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: rofi failed: cannot open display\n"
+    );
+}
+
+#[test]
+fn test_find() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/launcher.toml");
+    // Standard input is empty: searching never asks for the master password
+    let output = fp(dir.path(), &["find", "--config", config, "gith"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "SITE    FIELD  MATCH   SCORE\ngithub  name   github  84\n"
+    );
+
+    let output = fp(dir.path(), &["find", "--config", config, "zzz"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_find_select() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/launcher.toml");
+    let output = fp(
+        dir.path(),
+        &["find", "--config", config, "--select", "gith"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    // github and gitlab tie for "git": the answer picks one before the master
+    // password is read
+    let gitlab = fp(dir.path(), &["--config", config, "gitlab"], "test\n");
+    let output = fp(
+        dir.path(),
+        &["find", "--config", config, "--select", "git"],
+        "2\ntest\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, gitlab.stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("  1) github (name: github)"), "{}", stderr);
+
+    let output = fp(
+        dir.path(),
+        &["find", "--config", config, "--select", "git"],
+        "9\ntest\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_list() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    // Standard input is empty: listing never asks for the master password
+    let output = fp(dir.path(), &["list", "--config", &config], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "NAME    KEY         LENGTH  SCHEME\ngithub  github.com  16      v1\n"
+    );
+
+    let output = fp(
+        dir.path(),
+        &[
+            "list", "--config", &config, "--output", "csv", "--filter", "HUB",
+        ],
+        "",
+    );
+    assert_eq!(
+        output.stdout,
+        b"name,key,length,scheme\r\ngithub,github.com,16,v1\r\n".to_vec()
+    );
+    let output = fp(
+        dir.path(),
+        &[
+            "list", "--config", &config, "--output", "json", "--filter", "lab",
+        ],
+        "",
+    );
+    assert_eq!(output.stdout, b"[]\n".to_vec());
+
+    let output = fp(dir.path(), &["list", "--sort", "size"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_note_show() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["note", "--config", &config, "github", "用户名是旧邮箱"],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Set the notes of github"));
+    let output = fp(
+        dir.path(),
+        &[
+            "note",
+            "--config",
+            &config,
+            "--append",
+            "github",
+            "label={sq1}",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    // Showing and listing never ask for the master password
+    let output = fp(dir.path(), &["show", "--config", &config, "github"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name      github\nkey       github.com\nlength    16\nscheme    v1\n\
+         notes     用户名是旧邮箱\n          label={sq1}\n"
+    );
+    let output = fp(dir.path(), &["list", "--config", &config, "--long"], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "NAME    KEY         LENGTH  SCHEME  NOTES\n\
+         github  github.com  16      v1      用户名是旧邮箱 / label={sq1}\n"
+    );
+    // The notes are not part of the key: the password is the same
+    let output = fp(dir.path(), &["--config", &config, "github"], "test\n");
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let output = fp(
+        dir.path(),
+        &["note", "--config", &config, "gitlab", "x"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let output = fp(dir.path(), &["show", "--config", &config, "gitlab"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_show_user() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[sites.github]\nkey = \"github.com\"\nusername = \"octocat\"\n\n[sites.bare]\nkey = \"github.com\"\n",
+    )
+    .unwrap();
+    let config = path.to_str().unwrap();
+
+    let output = fp(
+        dir.path(),
+        &["--config", config, "--show-user", "github"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"octocat\tD04175F7A9c7Ab4a".to_vec());
+
+    // Without a username the shape stays the same, with a warning
+    let output = fp(
+        dir.path(),
+        &["--config", config, "--show-user", "bare"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"\tD04175F7A9c7Ab4a".to_vec());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("No username configured for bare"),
+        "{}",
+        stderr
+    );
+
+    let json = r#"{{"username": "{username}", "password": "{password}"}}"#;
+    let output = fp(
+        dir.path(),
+        &["--config", config, "--format", json, "github", "bare"],
+        "test\n",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records[0]["username"], "octocat");
+    assert_eq!(records[1]["username"], "");
+
+    let output = fp(
+        dir.path(),
+        &[
+            "--config",
+            config,
+            "--show-user",
+            "--format",
+            "{password}",
+            "github",
+        ],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let output = fp(
+        dir.path(),
+        &[
+            "--config",
+            config,
+            "--copy-user-then-pass",
+            "--show-user",
+            "github",
+        ],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_audit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let config = path.to_str().unwrap();
+    // site0.example and site3.example both derive K0 at length 2 under "password"
+    std::fs::write(
+        &path,
+        "[sites.pin-a]\nkey = \"site0.example\"\nlength = 2\n\n\
+         [sites.pin-b]\nkey = \"site3.example\"\nlength = 2\n\n\
+         [sites.github]\nkey = \"github.com\"\n",
+    )
+    .unwrap();
+    let output = fp(
+        dir.path(),
+        &["audit", "--config", config, "--check-reuse"],
+        "password\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "Same password: pin-a, pin-b\n");
+    assert!(!stdout.contains("K0"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("1 group(s) of sites share a password"),
+        "{}",
+        stderr
+    );
+
+    std::fs::write(
+        &path,
+        "[sites.pin-a]\nkey = \"site0.example\"\nlength = 2\n\n\
+         [sites.github]\nkey = \"github.com\"\n",
+    )
+    .unwrap();
+    let output = fp(dir.path(), &["audit", "--config", config], "password\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"No two of the 2 sites share a password\n".to_vec()
+    );
+}
+
+#[test]
+fn test_find_length() {
+    let dir = config_dir();
+    let policy = "require-digit,require-upper,require-lower";
+    let args = ["site33.example", "--policy", policy];
+
+    // "K60F9E0F24C5" has no lowercase letter, its 13th character is one
+    let output = fp(dir.path(), &[&args[..], &["-l", "12"]].concat(), "test\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("The password of site33.example does not meet require-lower"),
+        "{}",
+        stderr
+    );
+    let find = [&args[..], &["--find-length", "--min", "12"]].concat();
+    let output = fp(dir.path(), &find, "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"K60F9E0F24C5e".to_vec());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "fp: site33.example: length 13 is the shortest meeting the policy\n"
+    );
+    let output = fp(dir.path(), &["site33.example", "-l", "13"], "test\n");
+    assert_eq!(output.stdout, b"K60F9E0F24C5e".to_vec());
+
+    // Not one of the 32 characters of k866 is a lowercase letter
+    let output = fp(
+        dir.path(),
+        &[
+            "k866",
+            "--policy",
+            "require-digit,require-lower",
+            "--find-length",
+        ],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("No length satisfies the policy; unmet: require-lower"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_policy_arguments() {
+    let dir = config_dir();
+    for (args, message) in [
+        (
+            &["--policy", "require-symbol"][..],
+            "Unknown requirement `require-symbol`",
+        ),
+        (&["--policy", "require-digit,"], "empty requirement"),
+        (&["--find-length"], "--policy <REQUIREMENTS>"),
+        (
+            &["--policy", "require-digit", "--min", "12"],
+            "--find-length",
+        ),
+        (
+            &["--policy", "require-digit", "--find-length", "-l", "12"],
+            "cannot be used with",
+        ),
+    ] {
+        let output = fp(dir.path(), &[&["github"], args].concat(), "test\n");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(message), "{:?}: {}", args, stderr);
+    }
+}
+
+/// Splits CSV text into records of fields, undoing RFC 4180 quoting
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = vec![vec![String::new()]];
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let record = records.last_mut().unwrap();
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                record.last_mut().unwrap().push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(String::new()),
+            ('\r', false) => {}
+            ('\n', false) => records.push(vec![String::new()]),
+            (c, _) => record.last_mut().unwrap().push(c),
+        }
+    }
+    records.pop();
+    records
+}
+
+#[test]
+fn test_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[sites.github]\nkey = \"github.com\"\nusername = \"octo, cat\"\nlength = 16\n\n\
+         [sites.router]\nkey = \"home router\"\nlength = 8\n",
+    )
+    .unwrap();
+    let config = path.to_str().unwrap();
+    let out = dir.path().join("export.csv");
+    let out_arg = out.to_str().unwrap();
+
+    // Refused without an answer, then written after one
+    let output = fp(
+        dir.path(),
+        &["export", "--config", config, "--out", out_arg],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!out.exists());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("in plaintext"), "{}", stderr);
+    let output = fp(
+        dir.path(),
+        &["export", "--config", config, "--out", out_arg],
+        "y\ntest\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+
+    let csv = std::fs::read_to_string(&out).unwrap();
+    let router = String::from_utf8(fp(dir.path(), &["home router", "-l", "8"], "test\n").stdout);
+    assert_eq!(
+        parse_csv(&csv),
+        [
+            vec!["name", "url", "username", "password"],
+            vec![
+                "github",
+                "https://github.com",
+                "octo, cat",
+                "D04175F7A9c7Ab4a"
+            ],
+            vec!["router", "", "", &router.unwrap()],
+        ]
+    );
+
+    let output = fp(
+        dir.path(),
+        &["export", "--config", config, "--stdout"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, csv.into_bytes());
+
+    // Standard output only with --stdout, and --yes only for --out
+    for args in [&["export"][..], &["export", "--stdout", "--yes"]] {
+        let output = fp(
+            dir.path(),
+            &[args, &["--config", config]].concat(),
+            "test\n",
+        );
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_export_keepass() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[sites.github]\nkey = \"github.com\"\nusername = \"octocat\"\nlength = 16\n\
+         notes = \"2FA <on> & \\\"backup\\\" codes\"\n\n\
+         [sites.gitlab]\nkey = \"gitlab.com\"\nlength = 16\n",
+    )
+    .unwrap();
+    let config = path.to_str().unwrap();
+    let export = |format: &str| {
+        let args = [
+            "export",
+            "--config",
+            config,
+            "--stdout",
+            "--group",
+            "Web & Mail",
+        ];
+        let output = fp(
+            dir.path(),
+            &[&args[..], &["--format", format]].concat(),
+            "test\n",
+        );
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let gitlab = String::from_utf8(fp(dir.path(), &["gitlab.com", "-l", "16"], "test\n").stdout);
+    let gitlab = gitlab.unwrap();
+
+    // The columns in the order of KeePass's CSV import
+    assert_eq!(
+        parse_csv(&export("keepass-csv")),
+        [
+            vec!["Group", "Title", "Username", "Password", "URL", "Notes"],
+            vec![
+                "Web & Mail",
+                "github",
+                "octocat",
+                "D04175F7A9c7Ab4a",
+                "https://github.com",
+                "2FA <on> & \"backup\" codes",
+            ],
+            vec![
+                "Web & Mail",
+                "gitlab",
+                "",
+                &gitlab,
+                "https://gitlab.com",
+                ""
+            ],
+        ]
+    );
+
+    let xml = export("keepass-xml");
+    let document = roxmltree::Document::parse(&xml).unwrap();
+    let root = document.root_element();
+    assert_eq!(root.tag_name().name(), "KeePassFile");
+    fn child<'a, 'input>(
+        node: roxmltree::Node<'a, 'input>,
+        tag: &str,
+    ) -> roxmltree::Node<'a, 'input> {
+        node.children()
+            .find(|child| child.has_tag_name(tag))
+            .unwrap()
+    }
+    let group = child(child(root, "Root"), "Group");
+    assert_eq!(child(group, "Name").text(), Some("Web & Mail"));
+    let entries: Vec<Vec<(String, String)>> = group
+        .children()
+        .filter(|node| node.has_tag_name("Entry"))
+        .map(|entry| {
+            entry
+                .children()
+                .filter(|node| node.has_tag_name("String"))
+                .map(|string| {
+                    let text = |tag| child(string, tag).text().unwrap_or_default().to_string();
+                    (text("Key"), text("Value"))
+                })
+                .collect()
+        })
+        .collect();
+    assert_eq!(entries.len(), 2);
+    let value = |entry: usize, key: &str| {
+        let (_, value) = entries[entry].iter().find(|(name, _)| name == key).unwrap();
+        value.clone()
+    };
+    assert_eq!(value(0, "Password"), "D04175F7A9c7Ab4a");
+    assert_eq!(value(0, "Notes"), "2FA <on> & \"backup\" codes");
+    assert_eq!(value(1, "Password"), gitlab);
+    assert_eq!(value(1, "URL"), "https://gitlab.com");
+}
+
+#[test]
+fn test_import() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let csv = dir.path().join("chrome.csv");
+    std::fs::write(
+        &csv,
+        "name,url,username,password,note\r\n\
+         github.com,https://github.com/login,,hunter2,\r\n\
+         gitlab.com,https://gitlab.com/users/sign_in,\"doe, jane\",\"p,w\",\r\n\
+         gitlab.com,https://gitlab.com/,work,pw,\r\n\
+         app,android://hash@com.example.app/,someone,secret,\r\n\
+         nothing,,someone,secret,\r\n",
+    )
+    .unwrap();
+    let csv = csv.to_str().unwrap();
+
+    let output = fp(dir.path(), &["import", csv, "--config", &config], "");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "\
+Created gitlab: key gitlab.com, username doe, jane
+Skipped line 2: same account as `github`
+Skipped line 4: another account on gitlab.com already exists
+Skipped line 5: unsupported URL `android://hash@com.example.app/`
+Skipped line 6: no URL
+Sites created: 1, rows skipped: 4
+--allow-duplicates imports the other accounts of a domain as NAME-2 and so on
+"
+    );
+    let output = fp(dir.path(), &["show", "gitlab", "--config", &config], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("doe, jane"), "{}", stdout);
+    let text = std::fs::read_to_string(&config).unwrap();
+    assert!(
+        !text.contains("p,w") && !text.contains("hunter2"),
+        "{}",
+        text
+    );
+
+    let args = ["import", csv, "--config", &config, "--allow-duplicates"];
+    let output = fp(dir.path(), &args, "");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("Created gitlab-2: key gitlab.com, username work\n"),
+        "{}",
+        stdout
+    );
+
+    let output = fp(
+        dir.path(),
+        &["import", "missing.csv", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+}
+
+/// Parses the JSON-RPC responses `fp serve --stdio` wrote one per line
+fn rpc_responses(stdout: &[u8]) -> Vec<serde_json::Value> {
+    String::from_utf8(stdout.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_serve_stdio() {
+    let dir = config_dir();
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"derive","params":{"key":"github.com","length":16}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"setMaster","params":{"password":"test"}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"derive","params":{"key":"github.com","length":16}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"deriveBatch","params":{"keys":["site0.example","site3.example"],"length":2}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"setMaster","params":{"password":"password"}}"#,
+        r#"{"jsonrpc":"2.0","id":6,"method":"nope"}"#,
+    ];
+    let output = fp(
+        dir.path(),
+        &["serve", "--stdio"],
+        &(requests.join("\n") + "\n"),
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let responses = rpc_responses(&output.stdout);
+    assert_eq!(responses.len(), 6);
+    assert_eq!(responses[0]["error"]["code"], -32001);
+    assert_eq!(responses[1]["result"]["state"], "unlocked");
+    assert_eq!(responses[2]["result"]["password"], "D04175F7A9c7Ab4a");
+    assert_eq!(responses[3]["id"], 4);
+    assert_eq!(responses[4]["error"]["code"], -32002);
+    assert_eq!(responses[5]["error"]["code"], -32601);
+
+    // --allow-rekey takes another master password
+    let output = fp(
+        dir.path(),
+        &["serve", "--stdio", "--allow-rekey"],
+        &[
+            requests[1],
+            requests[4],
+            r#"{"jsonrpc":"2.0","id":7,"method":"derive","params":{"key":"key","length":16}}"#,
+        ]
+        .join("\n"),
+    );
+    let responses = rpc_responses(&output.stdout);
+    assert_eq!(responses[1]["result"]["allowRekey"], true);
+    assert_eq!(responses[2]["result"]["password"], "K3A2a66Bf88b628c");
+}
+
+#[test]
+fn test_serve_stdio_content_length() {
+    let dir = config_dir();
+    let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let input = [
+        frame(r#"{"jsonrpc":"2.0","id":1,"method":"setMaster","params":{"password":"test"}}"#),
+        frame(r#"{"jsonrpc":"2.0","id":2,"method":"derive","params":{"key":"github.com","length":16}}"#),
+    ]
+    .concat();
+    let output = fp(dir.path(), &["serve", "--stdio"], &input);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let body = r#"{"id":2,"jsonrpc":"2.0","result":{"password":"D04175F7A9c7Ab4a"}}"#;
+    assert!(stdout.ends_with(&frame(body)), "{}", stdout);
+    assert!(stdout.starts_with("Content-Length: "), "{}", stdout);
+
+    // --allow-rekey only goes with --stdio
+    let output = fp(dir.path(), &["serve", "--allow-rekey"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_serve_binary() {
+    use flowerpassword::binary::{
+        read_handshake_reply, read_response, write_handshake, write_request, Status,
+    };
+    use std::io::{BufReader, BufWriter};
+
+    const FRAMES: usize = 100_000;
+    let key = |i: usize| format!("site{}.example", i);
+    let length = |i: usize| (2 + i % 31) as u8;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["serve", "--binary"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = child.stdin.take().unwrap();
+    // Write every frame while the responses are read, as a pipelining parent would
+    let writer = std::thread::spawn(move || {
+        let mut stdin = BufWriter::new(stdin);
+        write_handshake(&mut stdin, "test").unwrap();
+        for i in 0..FRAMES {
+            write_request(&mut stdin, &key(i), length(i)).unwrap();
+        }
+        // An out-of-range length is answered, not fatal
+        write_request(&mut stdin, "github.com", 40).unwrap();
+        stdin.flush().unwrap();
+    });
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    assert_eq!(read_handshake_reply(&mut stdout).unwrap(), Status::Ok);
+    for i in 0..FRAMES {
+        let (status, password) = read_response(&mut stdout).unwrap();
+        assert_eq!(status, Status::Ok, "frame {}", i);
+        assert_eq!(password.len(), usize::from(length(i)), "frame {}", i);
+        if i % 997 == 0 {
+            let expected = flowerpassword::fp_code("test", &key(i), password.len()).unwrap();
+            assert_eq!(password, expected, "frame {}", i);
+        }
+    }
+    let (status, password) = read_response(&mut stdout).unwrap();
+    assert_eq!((status, password.as_str()), (Status::InvalidLength, ""));
+    writer.join().unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_serve_binary_bad_frames() {
+    let dir = config_dir();
+    // Version 2 is refused with a status byte before fp exits
+    let output = fp(dir.path(), &["serve", "--binary"], "\x02\x00\x04test");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(output.stdout, [3]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Unsupported protocol version 2"),
+        "{}",
+        stderr
+    );
+
+    // A request cut short after the handshake
+    let output = fp(
+        dir.path(),
+        &["serve", "--binary"],
+        "\x01\x00\x04test\x01\x00\x0agithub",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(output.stdout, [0]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Input ended in the middle of a frame"),
+        "{}",
+        stderr
+    );
+
+    let output = fp(dir.path(), &["serve", "--binary", "--stdio"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+/// Frames `bodies` as native messages
+fn native_messages(bodies: &[&str]) -> Vec<u8> {
+    let mut wire = Vec::new();
+    for body in bodies {
+        wire.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+        wire.extend_from_slice(body.as_bytes());
+    }
+    wire
+}
+
+/// Parses the native messages of `wire`
+fn native_responses(mut wire: &[u8]) -> Vec<serde_json::Value> {
+    let mut responses = Vec::new();
+    while let Some((header, rest)) = wire.split_first_chunk::<4>() {
+        let (body, rest) = rest.split_at(u32::from_ne_bytes(*header) as usize);
+        responses.push(serde_json::from_slice(body).unwrap());
+        wire = rest;
+    }
+    responses
+}
+
+#[test]
+#[cfg(unix)]
+fn test_native_host() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp_in_runtime(dir.path(), &["agent", "start"], "test\n");
+    let _guard = AgentGuard(dir.path());
+    assert!(output.status.success(), "{:?}", output);
+
+    // As Chrome runs it, with the origin of the extension
+    let requests = native_messages(&[
+        r#"{"cmd":"derive","origin":"https://github.com","length":16}"#,
+        r#"{"cmd":"derive","origin":"https://github.com","length":16,"master":"x"}"#,
+        r#"{"cmd":"derive","origin":"https://www.github.com:443","length":16}"#,
+    ]);
+    let args = ["native-host", "chrome-extension://abcdefghijklmnop/"];
+    let output = fp_in_runtime(dir.path(), &args, &requests);
+    assert!(output.status.success(), "{:?}", output);
+    let responses = native_responses(&output.stdout);
+    let password = serde_json::json!({"result": "password", "password": "D04175F7A9c7Ab4a"});
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0], password);
+    // The master password never comes from the extension
+    assert_eq!(responses[1]["result"], "error");
+    assert_eq!(responses[2], password);
+}
+
+#[test]
+#[cfg(all(unix, not(feature = "keyring")))]
+fn test_native_host_locked() {
+    let dir = tempfile::tempdir().unwrap();
+    let requests =
+        native_messages(&[r#"{"cmd":"derive","origin":"https://github.com","length":16}"#]);
+    // Nothing is read from standard input for the master password
+    let output = fp_in_runtime(dir.path(), &["native-host"], &requests);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("run fp agent start or fp unlock first"),
+        "{}",
+        stderr
+    );
+
+    let output = fp_in_runtime(dir.path(), &["unlock", "--timeout", "1h"], "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    let output = fp_in_runtime(dir.path(), &["native-host"], &requests);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        native_responses(&output.stdout)[0]["password"],
+        "D04175F7A9c7Ab4a"
+    );
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_install_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let args = [
+        "install-manifest",
+        "--browser",
+        "firefox",
+        "--extension-id",
+        "fp@example.org",
+        "--binary",
+        "/usr/local/bin/fp",
+    ];
+    let output = fp_in_runtime(dir.path(), &args, "");
+    assert!(output.status.success(), "{:?}", output);
+    let hosts = dir.path().join(".mozilla/native-messaging-hosts");
+    let manifest = hosts.join("com.flowerpassword.native_host.json");
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&*manifest.to_string_lossy()));
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(manifest["name"], "com.flowerpassword.native_host");
+    assert_eq!(manifest["type"], "stdio");
+    assert_eq!(
+        manifest["allowed_extensions"],
+        serde_json::json!(["fp@example.org"])
+    );
+    let script = hosts.join("com.flowerpassword.native_host");
+    assert_eq!(manifest["path"], script.to_str().unwrap());
+    let script = std::fs::read_to_string(&script).unwrap();
+    assert_eq!(
+        script,
+        "#!/bin/sh\nexec '/usr/local/bin/fp' native-host \"$@\"\n"
+    );
+
+    // Chromium-based browsers take extension IDs as origins
+    let output = fp_in_runtime(
+        dir.path(),
+        &[
+            "install-manifest",
+            "--browser",
+            "chrome",
+            "--extension-id",
+            "abcdefghijklmnop",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let manifest = dir
+        .path()
+        .join(".config/google-chrome/NativeMessagingHosts/com.flowerpassword.native_host.json");
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    let origins = serde_json::json!(["chrome-extension://abcdefghijklmnop/"]);
+    assert_eq!(manifest["allowed_origins"], origins);
+
+    let output = fp_in_runtime(dir.path(), &["install-manifest", "--browser", "chrome"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_handle_uri() {
+    let dir = tempfile::tempdir().unwrap();
+    let uri = "flowerpassword://derive?key=github.com&len=16";
+    #[cfg(not(feature = "keyring"))]
+    {
+        // Nothing is read from standard input for the master password
+        let output = fp_in_runtime(dir.path(), &["handle-uri", uri], "test\n");
+        assert_eq!(output.status.code(), Some(2), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("No master password for the link"),
+            "{}",
+            stderr
+        );
+    }
+
+    let output = fp_in_runtime(dir.path(), &["agent", "start"], "test\n");
+    let _guard = AgentGuard(dir.path());
+    assert!(output.status.success(), "{:?}", output);
+    let output = fp_in_runtime(dir.path(), &["handle-uri", uri], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a\n");
+
+    for uri in [
+        "flowerpassword://derive?key=github.com&len=99",
+        "flowerpassword://derive?key=github.com&user=me",
+        "flowerpassworé://derive?key=github.com",
+    ] {
+        let output = fp_in_runtime(dir.path(), &["handle-uri", uri], "");
+        assert_eq!(output.status.code(), Some(2), "{}: {:?}", uri, output);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_register_uri_handler() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let xdg_mime = dir.path().join("xdg-mime");
+    std::fs::write(
+        &xdg_mime,
+        format!("#!/bin/sh\necho \"$@\" > '{}'\n", log.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&xdg_mime, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let data = dir.path().join("data");
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap()
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["register-uri-handler", "--binary", "/opt/fp"])
+        .env("PATH", path)
+        .env("HOME", dir.path())
+        .env("XDG_DATA_HOME", &data)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Registered the flowerpassword:// handler"));
+    let entry = std::fs::read_to_string(data.join("applications/flowerpassword.desktop")).unwrap();
+    assert!(
+        entry.contains("\nExec=\"/opt/fp\" handle-uri %u\n"),
+        "{}",
+        entry
+    );
+    assert!(
+        entry.contains("\nMimeType=x-scheme-handler/flowerpassword;\n"),
+        "{}",
+        entry
+    );
+    assert_eq!(
+        std::fs::read_to_string(log).unwrap(),
+        "default flowerpassword.desktop x-scheme-handler/flowerpassword\n"
+    );
+}
+
+/// Writes fake libfido2 tools into `dir`: an authenticator without a PIN whose
+/// hmac-secret output is 0xab... for the credential c001 only
+#[cfg(unix)]
+fn fake_fido2_tools(dir: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tools = [
+        (
+            "fido2-token",
+            "case \"$1\" in\n\
+             -L) echo '/dev/hidraw7: vendor=0x1050, product=0x0407 (Yubico YubiKey)' ;;\n\
+             -I) printf 'options: rk, up, noclientPin\\nextension strings: hmac-secret\\n' ;;\n\
+             esac\n",
+        ),
+        (
+            "fido2-cred",
+            "printf 'cdh\\nflowerpassword\\npacked\\nauthdata\\nwAE=\\nsig\\n'\n",
+        ),
+        (
+            "fido2-assert",
+            "sed -n 3p \"$2\" | grep -qx wAE= || { echo FIDO_ERR_NO_CREDENTIALS >&2; exit 1; }\n\
+             printf 'cdh\\nflowerpassword\\nauthdata\\nsig\\n\
+             q6urq6urq6urq6urq6urq6urq6urq6urq6urq6urq6s=\\n'\n",
+        ),
+    ];
+    for (name, script) in tools {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+/// Runs `fp` with the fake tools of `dir` first on `PATH`
+#[cfg(unix)]
+fn fp_with_tools(dir: &Path, args: &[&str]) -> Output {
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap());
+    Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(args)
+        .env("PATH", path)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn test_master_fido2() {
+    let dir = tempfile::tempdir().unwrap();
+    fake_fido2_tools(dir.path());
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, CONFIG).unwrap();
+    let config = config.to_str().unwrap();
+
+    let output = fp_with_tools(
+        dir.path(),
+        &["--master", "fido2", "--config", config, "github"],
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("see fp fido2 enroll"));
+
+    let output = fp_with_tools(dir.path(), &["fido2", "enroll", "--config", config]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Touch your security key"), "{}", stderr);
+    assert!(
+        stderr.contains("Enrolled the FIDO2 credential c001 in "),
+        "{}",
+        stderr
+    );
+    let text = std::fs::read_to_string(config).unwrap();
+    assert_eq!(text, format!("fido2_credential = \"c001\"\n{}", CONFIG));
+
+    // Enrolling again would change every password
+    let output = fp_with_tools(dir.path(), &["fido2", "enroll", "--config", config]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    // The master password is the hex of the hmac-secret output
+    let output = fp_with_tools(
+        dir.path(),
+        &["--master", "fido2", "--config", config, "github"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let expected = fp(
+        dir.path(),
+        &["github.com"],
+        &format!("{}\n", "ab".repeat(32)),
+    );
+    assert_eq!(output.stdout, expected.stdout);
+
+    let output = fp_with_tools(
+        dir.path(),
+        &["--master", "fido", "--config", config, "github"],
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: Invalid master source, expected fido2[:LABEL], yubikey[:SLOT] \
+         or ssh-agent[:FINGERPRINT]\n"
+    );
+
+    // A credential the authenticator does not hold
+    let edited = std::fs::read_to_string(config)
+        .unwrap()
+        .replace("c001", "c002");
+    std::fs::write(config, edited).unwrap();
+    let output = fp_with_tools(
+        dir.path(),
+        &["--master", "fido2", "--config", config, "github"],
+    );
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not hold the enrolled"));
+}
+
+/// Writes a fake `ykchalresp` into `dir`: a slot 2 waiting for a touch whose
+/// response is 3f8a... for the challenge of the label "default" only
+#[cfg(unix)]
+fn fake_ykchalresp(dir: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join("ykchalresp");
+    let script = "#!/bin/sh\n\
+                  [ \"$1\" = -2 ] || { echo 'Yubikey core error: timeout' >&2; exit 1; }\n\
+                  [ \"$3\" = -N ] && { echo 'Yubikey core error: operation would block' >&2; exit 1; }\n\
+                  [ \"$4\" = 666c6f77657270617373776f72643a64656661756c74 ] || exit 0\n\
+                  echo 3f8a01c25e77900bd416a9ee42005c816df328b7\n";
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_master_yubikey() {
+    let dir = tempfile::tempdir().unwrap();
+    fake_ykchalresp(dir.path());
+
+    // The master password is the hex of the response
+    let output = fp_with_tools(dir.path(), &["--master", "yubikey", "github"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Touch your YubiKey"), "{}", stderr);
+    let expected = fp(
+        dir.path(),
+        &["github"],
+        "3f8a01c25e77900bd416a9ee42005c816df328b7\n",
+    );
+    assert_eq!(output.stdout, expected.stdout);
+
+    // Another label sends another challenge
+    let output = fp_with_tools(
+        dir.path(),
+        &["--master", "yubikey", "--yubikey-label", "work", "github"],
+    );
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let output = fp_with_tools(dir.path(), &["--master", "yubikey:1", "github"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("not touched within 15 seconds"),
+        "{:?}",
+        output
+    );
+    let output = fp_with_tools(dir.path(), &["--master", "yubikey:3", "github"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let output = fp_with_tools(dir.path(), &["--yubikey-label", "work", "github"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+/// Serves a fake ssh-agent at `path` holding an Ed25519 key for each of
+/// `keys`, whose public key and signature repeat that byte
+#[cfg(unix)]
+fn fake_ssh_agent(path: &Path, keys: &'static [u8]) {
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+
+    fn string(out: &mut Vec<u8>, bytes: &[u8]) {
+        out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        out.extend_from_slice(bytes);
+    }
+    fn blob(byte: u8, len: usize) -> Vec<u8> {
+        let mut blob = Vec::new();
+        string(&mut blob, b"ssh-ed25519");
+        string(&mut blob, &vec![byte; len]);
+        blob
+    }
+
+    let listener = UnixListener::bind(path).unwrap();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().map_while(Result::ok) {
+            let mut len = [0; 4];
+            while stream.read_exact(&mut len).is_ok() {
+                let mut message = vec![0; u32::from_be_bytes(len) as usize];
+                stream.read_exact(&mut message).unwrap();
+                let mut reply = Vec::new();
+                if message[0] == 11 {
+                    reply.push(12);
+                    reply.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+                    for &key in keys {
+                        string(&mut reply, &blob(key, 32));
+                        string(&mut reply, b"key");
+                    }
+                } else {
+                    // The key blob of the request starts at byte 5
+                    reply.push(14);
+                    string(&mut reply, &blob(message[5 + 4 + 11 + 4], 64));
+                }
+                stream
+                    .write_all(&(reply.len() as u32).to_be_bytes())
+                    .unwrap();
+                stream.write_all(&reply).unwrap();
+            }
+        }
+    });
+}
+
+/// Runs `fp` with `SSH_AUTH_SOCK` set to `socket`, or unset without one
+#[cfg(unix)]
+fn fp_with_ssh_agent(dir: &Path, socket: Option<&Path>, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fp"));
+    command
+        .args(args)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .env_remove("SSH_AUTH_SOCK")
+        .stdin(Stdio::null());
+    if let Some(socket) = socket {
+        command.env("SSH_AUTH_SOCK", socket);
+    }
+    command.output().unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn test_master_ssh_agent() {
+    let dir = tempfile::tempdir().unwrap();
+    let one = dir.path().join("one.sock");
+    let two = dir.path().join("two.sock");
+    fake_ssh_agent(&one, &[0x5a]);
+    fake_ssh_agent(&two, &[0x5a, 0x22]);
+
+    // The master password is the hex SHA-256 of the signature blob
+    let expected = fp(
+        dir.path(),
+        &["github"],
+        "dd02e33f153f28052e3876397f9e8af0bd314906d70ac3a0dfb0335e200fb41b\n",
+    );
+    let output = fp_with_ssh_agent(dir.path(), Some(&one), &["--master", "ssh-agent", "github"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, expected.stdout);
+
+    // Among several keys, one is chosen by its fingerprint
+    let output = fp_with_ssh_agent(dir.path(), Some(&two), &["--master", "ssh-agent", "github"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("choose one by fingerprint"));
+    let spec = "ssh-agent:SHA256:jysidH8sUN9+mB/5Wx25m7gT4aa66R2kbn4o2cy0Vio";
+    let output = fp_with_ssh_agent(dir.path(), Some(&two), &["--master", spec, "github"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, expected.stdout);
+
+    let output = fp_with_ssh_agent(dir.path(), None, &["--master", "ssh-agent", "github"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: SSH_AUTH_SOCK is not set\n"
+    );
+    let output = fp_with_ssh_agent(dir.path(), None, &["--master", "ssh-agent:", "github"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_check_pwned() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let master = dir.path().join("master");
+    std::fs::write(&master, "test\n").unwrap();
+    std::fs::set_permissions(&master, std::fs::Permissions::from_mode(0o600)).unwrap();
+    let master = master.to_str().unwrap();
+    // SHA-1("D04175F7A9c7Ab4a") = 77022 5511EF97EFB0776E9CC4CD3148B891B5F05
+    let curl = dir.path().join("curl");
+    let log = dir.path().join("log");
+    let hit = format!(
+        "#!/bin/sh\necho \"$@\" > '{}'\nprintf '5511EF97EFB0776E9CC4CD3148B891B5F05:7\\r\\n'\n",
+        log.display()
+    );
+    std::fs::write(&curl, hit).unwrap();
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let args = ["--password-file", master, "--check-pwned", "github.com"];
+    let output = fp_with_tools(dir.path(), &args);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("The password of github.com appears 7 times in Pwned Passwords"),
+        "{:?}",
+        output
+    );
+    // Only the prefix of the hash is sent
+    let sent = std::fs::read_to_string(&log).unwrap();
+    assert!(
+        sent.ends_with(" https://api.pwnedpasswords.com/range/77022\n"),
+        "{}",
+        sent
+    );
+    assert!(!sent.contains("5511EF") && !sent.contains("D04175F7A9c7Ab4a"));
+
+    // A lookup that fails still prints the password
+    let down = "#!/bin/sh\necho 'curl: (6) Could not resolve host' >&2\nexit 6\n";
+    std::fs::write(&curl, down).unwrap();
+    let output = fp_with_tools(dir.path(), &args);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Could not check github.com against Pwned Passwords"));
+
+    // Nothing is sent without the flag
+    std::fs::remove_file(&log).unwrap();
+    let output = fp_with_tools(dir.path(), &["--password-file", master, "github.com"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty() && !log.exists());
+}
+
+#[test]
+fn test_check_blocklist() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--check-blocklist", "github.com"],
+        "password\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("The master password is on the blocklist of common passwords"));
+
+    // Derived passwords are checked against the --blocklist files too
+    let words = dir.path().join("words.txt");
+    std::fs::write(&words, "d04175f7a9c7ab4a \n").unwrap();
+    let words = words.to_str().unwrap();
+    let args = ["--check-blocklist", "--blocklist", words, "github.com"];
+    let output = fp(dir.path(), &args, "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: warning: The master password is on the blocklist of common passwords\n\
+         fp: warning: The password of github.com is on the blocklist of common passwords\n"
+    );
+    let output = fp(dir.path(), &["--check-blocklist", "github.com"], "test\n");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("github.com"));
+
+    let missing = dir.path().join("missing.txt");
+    let args = [
+        "--check-blocklist",
+        "--blocklist",
+        missing.to_str().unwrap(),
+        "github.com",
+    ];
+    let output = fp(dir.path(), &args, "test\n");
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Cannot read the blocklist"));
+    let output = fp(dir.path(), &["--blocklist", words, "github.com"], "test\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_add_scheme() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &[
+            "add",
+            "hub",
+            "--key",
+            "github.com",
+            "--scheme",
+            "v2",
+            "--config",
+            &config,
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Added site hub: key github.com, length 16, scheme v2\n"
+    );
+    let output = fp(
+        dir.path(),
+        &[
+            "--config",
+            &config,
+            "--format",
+            "{scheme} {password}",
+            "hub",
+        ],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"v2 K4Ab3e328cA49fE8".to_vec());
+    let output = fp(dir.path(), &["show", "hub", "--config", &config], "");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("scheme    v2\n"));
+
+    let output = fp(
+        dir.path(),
+        &["add", "shop", "--scheme", "v3", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected v1 or v2, got `v3`"));
+}
+
+#[test]
+fn test_add_rm() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &[
+            "add",
+            "bank",
+            "--key",
+            "secure.examplebank.co.uk",
+            "--length",
+            "20",
+            "--config",
+            &config,
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Added site bank: key secure.examplebank.co.uk, length 20, scheme v1\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["list", "--config", &config, "--output", "csv"],
+        "",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name,key,length,scheme\r\n\
+         bank,secure.examplebank.co.uk,20,v1\r\n\
+         github,github.com,16,v1\r\n"
+    );
+
+    // An existing site needs --force, and invalid input is refused
+    let output = fp(dir.path(), &["add", "github", "--config", &config], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Site `github` already exists"));
+    for args in [["--length", "64"], ["--key", ""]] {
+        let mut all = vec!["add", "shop", "--config", &config];
+        all.extend(args);
+        let output = fp(dir.path(), &all, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    }
+    let output = fp(
+        dir.path(),
+        &["add", "github", "--force", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Replaced site github: key github, length 16, scheme v1\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["add", "github", "--force", "--quiet", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+
+    // fp rm asks first
+    let output = fp(dir.path(), &["rm", "bank", "--config", &config], "n\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Remove the site bank from the configuration? [y/N] fp: Kept site bank\n"
+    );
+    let output = fp(dir.path(), &["rm", "bank", "--config", &config], "y\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .ends_with("fp: Removed site bank: key secure.examplebank.co.uk, length 20, scheme v1\n"));
+    let output = fp(
+        dir.path(),
+        &["rm", "github", "--yes", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let output = fp(dir.path(), &["list", "--config", &config], "");
+    assert_eq!(output.stdout, b"NAME  KEY  LENGTH  SCHEME\n".to_vec());
+    let output = fp(
+        dir.path(),
+        &["rm", "github", "--yes", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_unlock_only_on_linux() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["--help"], "");
+    let help = String::from_utf8(output.stdout).unwrap();
+    for command in ["unlock", "lock", "status"] {
+        let listed = help
+            .lines()
+            .any(|line| line.starts_with(&format!("  {} ", command)));
+        assert_eq!(listed, cfg!(target_os = "linux"), "{}", command);
+    }
+    if cfg!(target_os = "linux") {
+        return;
+    }
+    for command in ["unlock", "lock", "status"] {
+        let output = fp(dir.path(), &[command], "test\n");
+        assert_eq!(output.status.code(), Some(4), "{:?}", output);
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("need the Linux kernel keyring"));
+    }
+}
+
+#[test]
+fn test_rename() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let run = |args: &[&str], stdin: &str| {
+        let mut all = args.to_vec();
+        all.extend(["--config", &config]);
+        fp(dir.path(), &all, stdin)
+    };
+    assert!(run(&["alias", "add", "gh", "github"], "").status.success());
+    assert!(run(&["add", "gitlab"], "").status.success());
+
+    let output = run(&["rename", "github", "gitlab"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Site `gitlab` already exists"));
+
+    // The site keeps its key, and so its password
+    let output = run(&["rename", "github", "hub"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Renamed site github to hub\n\
+         fp: Aliases now pointing to hub: gh\n\
+         fp: Only the name changed: hub still derives with the key github.com, \
+         so its password is the same\n"
+    );
+    let output = run(&["hub", "gh"], "test\n");
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\n".to_vec()
+    );
+
+    // --also-key asks, since the password changes
+    let output = run(&["rename", "gitlab", "lab", "--also-key"], "n\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with(
+        "Renaming gitlab to lab with --also-key changes its key, and so its password. \
+         Continue? [y/N] fp: Kept site gitlab\n"
+    ));
+    let output = run(&["list", "--output", "csv"], "");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\ngitlab,gitlab,16,v1\r\n"));
+    let output = run(&["rename", "gitlab", "lab", "--also-key"], "y\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(
+        "fp: warning: lab now derives with its new name as the key: its password has changed\n"
+    ));
+    let output = run(&["list", "--output", "csv"], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name,key,length,scheme\r\nhub,github.com,16,v1\r\nlab,lab,16,v1\r\n"
+    );
+}
+
+#[test]
+fn test_alias() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["alias", "add", "gh", "github", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Alias gh now points to github\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["alias", "add", "hub", "gh", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let text = std::fs::read_to_string(dir.path().join("config.toml")).unwrap();
+    assert_eq!(
+        text,
+        format!("{}\n[aliases]\ngh = \"github\"\nhub = \"gh\"\n", CONFIG)
+    );
+
+    // Site names, then aliases, then literal keys
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "hub", "gh", "github", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"D04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\nD04175F7A9c7Ab4a\n".to_vec()
+    );
+    // An alias named like a site is shadowed by it
+    let output = fp(
+        dir.path(),
+        &["alias", "add", "github", "hub", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("The site github comes first"), "{}", stderr);
+    let output = fp(dir.path(), &["--config", &config, "github"], "test\n");
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    // Loops and aliases to nothing are refused, and leave the file alone
+    let text = std::fs::read_to_string(dir.path().join("config.toml")).unwrap();
+    let output = fp(
+        dir.path(),
+        &["alias", "add", "gh", "hub", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Alias cycle: "));
+    let output = fp(
+        dir.path(),
+        &["alias", "add", "x", "nowhere", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("config.toml")).unwrap(),
+        text
+    );
+
+    let output = fp(dir.path(), &["alias", "list", "--config", &config], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "gh -> github\ngithub -> hub\nhub -> gh\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["alias", "rm", "github", "--config", &config],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: Removed alias github, which pointed to hub\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["alias", "rm", "github", "--config", &config],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_config_path() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(dir.path(), &["config", "path", "--config", &config], "");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(lines[0], ["LAYER", "STATUS", "PATH"]);
+    if cfg!(target_os = "linux") {
+        let user = dir.path().join("flowerpassword").join("config.toml");
+        assert!(lines.contains(&vec!["user", "missing", &user.display().to_string()]));
+    }
+    assert_eq!(
+        lines.last().unwrap(),
+        &["override", "found", config.as_str()]
+    );
+
+    // A missing override is shown, not an error
+    let output = fp(
+        dir.path(),
+        &["config", "path", "--config", "nowhere.toml"],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("override  missing  nowhere.toml"));
+}
+
+#[test]
+fn test_config_warnings() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("typo.toml");
+    std::fs::write(&config, "[sites.github]\nkey = \"github.com\"\nlenght = 8\n").unwrap();
+    let config = config.display().to_string();
+    let output = fp(dir.path(), &["--config", &config, "github"], "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "fp: {}:3: warning: unknown key `lenght` in [sites.github], did you mean `length`?\n",
+            config
+        )
+    );
+    let output = fp(dir.path(), &["--config", &config, "-q", "github"], "test\n");
+    assert!(output.stderr.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn test_config_lint() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(dir.path(), &["config", "lint", "--config", &config], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"1 configuration file(s), no problems\n".to_vec()
+    );
+
+    let typo = dir.path().join("typo.toml");
+    std::fs::write(&typo, "[sites.github]\nkye = \"github.com\"\n").unwrap();
+    let typo = typo.display().to_string();
+    let output = fp(dir.path(), &["config", "lint", "--config", &typo], "");
+    // Warnings alone are listed without failing
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{}:2: warning: unknown key `kye` in [sites.github], did you mean `key`?\n",
+            typo
+        )
+    );
+    assert!(output.stderr.is_empty(), "{:?}", output.stderr);
+
+    let invalid = dir.path().join("invalid.toml");
+    std::fs::write(&invalid, "[sites.github]\nkye = \"github.com\"\nlength = 64\n").unwrap();
+    let invalid = invalid.display().to_string();
+    let output = fp(dir.path(), &["config", "lint", "--config", &invalid], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: Problems in the configuration: errors: 1, warnings: 1\n"
+    );
+}
+
+#[test]
+fn test_config_migrate() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let args = ["config", "migrate", "--config", &config, "--dry-run"];
+    let output = fp(dir.path(), &args, "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{} would be migrated from version 0 to 1\n- added `version = 1`\n",
+            config
+        )
+    );
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), CONFIG);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let output = fp(dir.path(), &args[..4], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(&config).unwrap(),
+        format!("version = 1\n{}", CONFIG)
+    );
+    let backups: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bak"))
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), CONFIG);
+
+    let output = fp(dir.path(), &args, "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{} is already at version 1\n", config)
+    );
+}
+
+/// Opens a pseudo-terminal, returning its controller and terminal ends
+#[cfg(unix)]
+fn openpty() -> (std::fs::File, std::os::fd::OwnedFd) {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    let (mut controller, mut terminal) = (0, 0);
+    // SAFETY: openpty writes two new file descriptors, which are owned here
+    let result = unsafe {
+        libc::openpty(
+            &mut controller,
+            &mut terminal,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    assert_eq!(result, 0, "{}", std::io::Error::last_os_error());
+    // SAFETY: see above
+    unsafe {
+        (
+            std::fs::File::from(OwnedFd::from_raw_fd(controller)),
+            OwnedFd::from_raw_fd(terminal),
+        )
+    }
+}
+
+/// What a program writes to a pseudo-terminal, read on a thread of its own
+#[cfg(unix)]
+struct Screen {
+    chunks: std::sync::mpsc::Receiver<Vec<u8>>,
+    shown: Vec<u8>,
+}
+
+#[cfg(unix)]
+impl Screen {
+    fn new(mut controller: std::fs::File) -> Self {
+        let (sender, chunks) = std::sync::mpsc::channel();
+        // Reading ends with EIO once the program has exited
+        std::thread::spawn(move || {
+            use std::io::Read;
+            let mut chunk = [0; 256];
+            while let Ok(n @ 1..) = controller.read(&mut chunk) {
+                if sender.send(chunk[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Screen {
+            chunks,
+            shown: Vec::new(),
+        }
+    }
+
+    /// Waits until `text` has been shown
+    fn wait_for(&mut self, text: &str) {
+        while !String::from_utf8_lossy(&self.shown).contains(text) {
+            let chunk = self
+                .chunks
+                .recv_timeout(std::time::Duration::from_secs(10))
+                .unwrap_or_else(|_| panic!("{:?} never shown", text));
+            self.shown.extend(chunk);
+        }
+    }
+
+    /// Returns everything shown until the program exited
+    fn finish(mut self) -> String {
+        while let Ok(chunk) = self.chunks.recv_timeout(std::time::Duration::from_secs(10)) {
+            self.shown.extend(chunk);
+        }
+        String::from_utf8_lossy(&self.shown).into_owned()
+    }
+}
+
+const COLORED: &str = "\x1b[32mKAC\x1b[34m5\x1b[33mf\x1b[32mE\x1b[33mf\x1b[34m0\x1b[0m";
+
+#[test]
+fn test_color_when_piped() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["key", "-l", "8"], "test\n");
+    assert_eq!(output.stdout, b"KAC5fEf0".to_vec());
+    let output = fp(
+        dir.path(),
+        &["--color", "always", "key", "-l", "8"],
+        "test\n",
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), COLORED);
+    for wins in ["--raw", "--quiet"] {
+        let output = fp(
+            dir.path(),
+            &["--color", "always", wins, "key", "-l", "8"],
+            "test\n",
+        );
+        assert_eq!(output.stdout, b"KAC5fEf0".to_vec(), "{}", wins);
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_color_on_a_terminal() {
+    for (no_color, shown) in [("", COLORED), ("1", "KAC5fEf0\r\n")] {
+        let (controller, terminal) = openpty();
+        let dir = tempfile::tempdir().unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+            .args(["key", "-l", "8"])
+            .env("XDG_CONFIG_HOME", dir.path())
+            .env("HOME", dir.path())
+            .env("NO_COLOR", no_color)
+            .stdin(Stdio::piped())
+            .stdout(terminal)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+        let mut screen = Screen::new(controller);
+        screen.wait_for(shown);
+        assert!(child.wait().unwrap().success());
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_show_timeout_erases_on_sigint() {
+    let (controller, terminal) = openpty();
+    let dir = tempfile::tempdir().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--show-timeout", "1h", "--color", "never", "github.com"])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .env("TERM", "xterm")
+        .stdin(Stdio::piped())
+        .stdout(terminal)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+
+    let mut screen = Screen::new(controller);
+    screen.wait_for("D04175F7A9c7Ab4a");
+    // SAFETY: kill has no memory effects
+    assert_eq!(unsafe { libc::kill(child.id() as i32, libc::SIGINT) }, 0);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: Interrupted\n"
+    );
+    let shown = screen.finish();
+    assert!(
+        shown.ends_with("D04175F7A9c7Ab4a\r\n\x1b[1A\r\x1b[2K"),
+        "{:?}",
+        shown
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_masked() {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let (controller, terminal) = openpty();
+    let mut keys = controller.try_clone().unwrap();
+    // Held open until the screen is read, since a hangup may discard what fp
+    // wrote last before it reaches this end
+    let held = terminal.try_clone().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fp"));
+    command
+        .args(["--masked", "--mask-style", "1+3", "github.com"])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(terminal);
+    // SAFETY: setsid and ioctl are async-signal-safe; the terminal is standard
+    // error, which becomes the controlling terminal /dev/tty of the new session
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(2, libc::TIOCSCTTY, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().unwrap();
+    drop(command);
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+
+    let mut screen = Screen::new(controller);
+    let masked = format!("D{}b4a (16 chars)", "•".repeat(12));
+    screen.wait_for(&format!("{}  r reveal, c copy, q quit", masked));
+    keys.write_all(b"r").unwrap();
+    screen.wait_for("D04175F7A9c7Ab4a  r reveal");
+    keys.write_all(b"q").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    // Nothing reaches standard output
+    assert!(output.stdout.is_empty());
+    let last = format!("\r\x1b[2K{}\r\n", masked);
+    screen.wait_for(&last);
+    drop(held);
+    assert!(screen.finish().ends_with(&last));
+
+    // The terminal is back in canonical mode
+    // SAFETY: termios is plain data that tcgetattr fills in
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { libc::tcgetattr(keys.as_raw_fd(), &mut termios) },
+        0
+    );
+    assert_ne!(termios.c_lflag & libc::ICANON, 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_masked_needs_a_terminal() {
+    use std::os::unix::process::CommandExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fp"));
+    command
+        .args(["--masked", "github.com"])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("HOME", dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // SAFETY: setsid is async-signal-safe; it leaves no controlling terminal
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: --masked needs a terminal\n"
+    );
+
+    let output = fp(dir.path(), &["--masked", "github", "gitlab"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// A pinentry answering every GETPIN with `test`, and noting it in the file of
+/// its own name with `.log` appended
+#[cfg(unix)]
+const FAKE_PINENTRY: &str = r#"#!/bin/sh
+echo "OK Pleased to meet you"
+while read -r command rest; do
+    case "$command" in
+        GETPIN) echo GETPIN >> "$0.log"; echo "D test"; echo OK ;;
+        BYE) echo OK; exit 0 ;;
+        *) echo OK ;;
+    esac
+done
+"#;
+
+/// Writes [`FAKE_PINENTRY`] to `dir`, returning its path
+#[cfg(unix)]
+fn fake_pinentry(dir: &Path) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("pinentry");
+    std::fs::write(&path, FAKE_PINENTRY).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// `fp` running in a session of its own, on a pseudo-terminal
+#[cfg(unix)]
+struct OnTerminal {
+    child: std::process::Child,
+    screen: Screen,
+    /// Where to type keys
+    keys: std::fs::File,
+    /// The terminal end, held open until the screen is read
+    held: std::os::fd::OwnedFd,
+}
+
+/// Starts `fp` with `args` and a pseudo-terminal as its controlling terminal and
+/// standard input, with `master` as descriptor 3 if given
+#[cfg(unix)]
+fn fp_on_terminal(dir: &Path, args: &[&str], master: Option<std::fs::File>) -> OnTerminal {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let (controller, terminal) = openpty();
+    let keys = controller.try_clone().unwrap();
+    let held = terminal.try_clone().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fp"));
+    command
+        .args(args)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("HOME", dir)
+        .env_remove("LANG")
+        .stdin(terminal.try_clone().unwrap())
+        .stdout(Stdio::piped())
+        .stderr(terminal);
+    let master_fd = master.as_ref().map(AsRawFd::as_raw_fd);
+    // SAFETY: setsid, ioctl, dup2 and fcntl are async-signal-safe; the terminal is
+    // standard error, which becomes the controlling terminal /dev/tty
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setsid() < 0 || libc::ioctl(2, libc::TIOCSCTTY, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // dup2 leaves close-on-exec set if the descriptor was 3 already
+            if let Some(fd) = master_fd {
+                if libc::dup2(fd, 3) < 0 || libc::fcntl(3, libc::F_SETFD, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn().unwrap();
+    drop(command);
+    drop(master);
+    OnTerminal {
+        child,
+        screen: Screen::new(controller),
+        keys,
+        held,
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_encrypted_config() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let prompt = format!("Passphrase for {}: ", config);
+    let mut fp_tty = fp_on_terminal(
+        dir.path(),
+        &["config", "encrypt", "--config", &config],
+        None,
+    );
+    fp_tty.screen.wait_for(&prompt);
+    fp_tty.keys.write_all(b"correct horse\n").unwrap();
+    fp_tty.screen.wait_for("Repeat the passphrase: ");
+    fp_tty.keys.write_all(b"correct horse\n").unwrap();
+    let output = fp_tty.child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let data = std::fs::read(&config).unwrap();
+    assert!(data.starts_with(b"FPCRYPT"));
+    assert!(!String::from_utf8_lossy(&data).contains("github"));
+
+    // Standard input carries the master password, so only a terminal will do
+    let output = fp(dir.path(), &["list", "--config", &config], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: The passphrase of an encrypted configuration can only be typed on a terminal\n"
+    );
+
+    let args = ["--no-confirm", "--config", &config, "github"];
+    let mut fp_tty = fp_on_terminal(dir.path(), &args, None);
+    fp_tty.screen.wait_for(&prompt);
+    fp_tty.keys.write_all(b"correct horse\n").unwrap();
+    fp_tty.screen.wait_for("Master password: ");
+    fp_tty.keys.write_all(b"test\n").unwrap();
+    let output = fp_tty.child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+
+    let mut fp_tty = fp_on_terminal(
+        dir.path(),
+        &["config", "decrypt", "--config", &config],
+        None,
+    );
+    fp_tty.screen.wait_for(&prompt);
+    fp_tty.keys.write_all(b"correct horse\n").unwrap();
+    let output = fp_tty.child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), CONFIG);
+
+    let output = fp(dir.path(), &["config", "decrypt", "--config", &config], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("fp: {} is not encrypted\n", config)
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_paranoid_with_pinentry() {
+    let dir = tempfile::tempdir().unwrap();
+    let pinentry = fake_pinentry(dir.path());
+    let option = format!("--pinentry={}", pinentry.display());
+    let mut fp = fp_on_terminal(dir.path(), &["--paranoid", &option, "github.com"], None);
+
+    let masked = format!("D0{}4a (16 chars)", "•".repeat(12));
+    // Masked, with no key to copy
+    fp.screen.wait_for(&format!("{}  r reveal, q quit", masked));
+    fp.keys.write_all(b"c").unwrap();
+    fp.keys.write_all(b"r").unwrap();
+    fp.screen.wait_for("D04175F7A9c7Ab4a  r reveal, q quit");
+    fp.keys.write_all(b"q").unwrap();
+    let output = fp.child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    fp.screen.wait_for(&format!("\r\x1b[2K{}\r\n", masked));
+    drop(fp.held);
+    assert!(!fp.screen.finish().contains("copied"));
+    // Confirmed, so asked twice
+    let log = std::fs::read_to_string(dir.path().join("pinentry.log")).unwrap();
+    assert_eq!(log, "GETPIN\nGETPIN\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_paranoid_with_password_fd() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("master");
+    std::fs::write(&path, "test\n").unwrap();
+    let master = std::fs::File::open(&path).unwrap();
+    let args = ["--paranoid", "--password-fd", "3", "-l", "8", "key"];
+    let mut fp = fp_on_terminal(dir.path(), &args, Some(master));
+
+    fp.screen.wait_for("KA•••••0 (8 chars)  r reveal, q quit");
+    fp.keys.write_all(b"r").unwrap();
+    fp.screen.wait_for("KAC5fEf0  r reveal, q quit");
+    fp.keys.write_all(b"q").unwrap();
+    let output = fp.child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_paranoid_refusals() {
+    let dir = tempfile::tempdir().unwrap();
+    let refused = [
+        (
+            &["--paranoid", "--pinentry", "--log", "github"][..],
+            "--paranoid and --log cannot be used together",
+        ),
+        (
+            &["--paranoid", "--password-file", "m", "github"],
+            "--paranoid and --password-file cannot be used together",
+        ),
+        (
+            &["--paranoid", "--password-fd", "3", "--raw", "github"],
+            "--paranoid and --raw cannot be used together",
+        ),
+        (
+            &["--paranoid", "--pinentry", "--copy", "github"],
+            "--paranoid and --copy cannot be used together",
+        ),
+        (
+            &["--paranoid", "github"],
+            "--paranoid reads the master password from --password-fd or --pinentry only",
+        ),
+        (
+            &["--paranoid", "--pinentry", "github", "gitlab"],
+            "--paranoid shows one password at a time",
+        ),
+    ];
+    for (args, message) in refused {
+        let output = fp(dir.path(), args, "test\n");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.starts_with(&format!("error: {}\n", message)),
+            "{}",
+            stderr
+        );
+    }
+
+    // Nothing is left in a file standard output goes to
+    let out = dir.path().join("out");
+    let output = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--paranoid", "--pinentry", "github"])
+        .env("HOME", dir.path())
+        .stdout(std::fs::File::create(&out).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("--paranoid refuses to run with standard output redirected to a file"));
+    assert_eq!(std::fs::read(&out).unwrap(), b"");
+}
+
+/// Returns whether the terminal `fd` echoes what is typed
+#[cfg(unix)]
+fn echoes(fd: &impl std::os::fd::AsRawFd) -> bool {
+    // SAFETY: termios is plain data that tcgetattr fills in
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::tcgetattr(fd.as_raw_fd(), &mut termios) }, 0);
+    termios.c_lflag & libc::ECHO != 0
+}
+
+#[test]
+#[cfg(unix)]
+fn test_interrupted_prompt_restores_the_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut fp = fp_on_terminal(dir.path(), &["github.com"], None);
+    fp.screen.wait_for("Master password: ");
+    // The prompt may still be switching echo off
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while echoes(&fp.held) {
+        assert!(std::time::Instant::now() < deadline, "echo never off");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    // SAFETY: kill has no memory effects
+    assert_eq!(unsafe { libc::kill(fp.child.id() as i32, libc::SIGINT) }, 0);
+    let output = fp.child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(echoes(&fp.held));
+    // What is typed now shows again
+    fp.keys.write_all(b"probe\n").unwrap();
+    fp.screen.wait_for("probe");
+
+    // And the same for SIGTERM, in raw mode
+    let path = dir.path().join("master");
+    std::fs::write(&path, "test\n").unwrap();
+    let master = std::fs::File::open(&path).unwrap();
+    let args = ["--masked", "--password-fd", "3", "github.com"];
+    let mut fp = fp_on_terminal(dir.path(), &args, Some(master));
+    fp.screen.wait_for("r reveal, c copy, q quit");
+    // SAFETY: as above
+    assert_eq!(
+        unsafe { libc::kill(fp.child.id() as i32, libc::SIGTERM) },
+        0
+    );
+    let output = fp.child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(143), "{:?}", output);
+    assert!(echoes(&fp.held));
+}
+
+#[test]
+fn test_show_timeout_needs_a_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--show-timeout", "1h", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a".to_vec());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: warning: Standard output is not a terminal, ignoring --show-timeout\n"
+    );
+}
+
+#[test]
+fn test_completions() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["completions", "bash"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("--length"));
+
+    let output = fp(dir.path(), &["completions", "cmd"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("possible values"));
+}
+
+#[test]
+fn test_dynamic_completion() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = dir.path().join("sites.toml");
+    std::fs::write(
+        &fixture,
+        "[sites.github]\n[sites.gitlab]\n[sites.mail]\n\n[aliases]\ngh = \"github\"\n",
+    )
+    .unwrap();
+    let fixture = fixture.display().to_string();
+    // Standard input is empty: nothing may wait for a master password
+    let output = fp(dir.path(), &["__complete", "--config", &fixture, "g"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"gh\ngithub\ngitlab\n".to_vec());
+    assert!(output.stderr.is_empty());
+
+    let missing = dir.path().join("missing.toml").display().to_string();
+    let output = fp(dir.path(), &["__complete", "--config", &missing, "g"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+}
+
+#[test]
+fn test_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["github"], "");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: No master password on standard input\n"
+    );
+
+    let output = fp(dir.path(), &["-l", "40", "github"], "test\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_doctor() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(dir.path(), &["doctor", "--config", &config], "");
+    // Only the configuration can fail, the rest are warnings at worst
+    assert!(output.status.success(), "{:?}", output);
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        table.starts_with("STATUS  CHECK      DETAIL\n"),
+        "{}",
+        table
+    );
+    assert!(table.contains("\nPASS    config     1 site(s), no problems\n"));
+    for check in ["clipboard", "agent", "memlock", "hardening", "terminal"] {
+        assert!(table.contains(&format!("  {:<9}  ", check)), "{}", table);
+    }
+    // Standard output is a pipe here
+    assert!(table.contains("\nWARN    terminal   Standard output is not a terminal\n"));
+
+    let broken = dir.path().join("broken.toml");
+    std::fs::write(&broken, "[sites.github\n").unwrap();
+    let output = fp(
+        dir.path(),
+        &["doctor", "--config", broken.to_str().unwrap()],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.contains("\nFAIL    config     "), "{}", table);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("fp: error: "), "{}", stderr);
+    assert!(stderr.contains(" checks failed"), "{}", stderr);
+}
+
+#[test]
+fn test_self_test() {
+    let dir = tempfile::tempdir().unwrap();
+    // Never asks for the master password, standard input is left alone
+    let output = fp(dir.path(), &["self-test"], "");
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    let vectors = flowerpassword::vectors::KNOWN_ANSWERS.len();
+    assert_eq!(lines.len(), vectors + 1, "{}", stdout);
+    assert!(lines[..vectors]
+        .iter()
+        .all(|line| line.starts_with("PASS  ")));
+    assert!(!stdout.contains("FAIL"), "{}", stdout);
+    assert_eq!(
+        lines[vectors],
+        format!("{} known-answer vectors passed", vectors)
+    );
+}
+
+#[test]
+fn test_fingerprint() {
+    let dir = config_dir();
+    let config = config_arg(dir.path());
+    let output = fp(
+        dir.path(),
+        &["verify-master", "--config", &config],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    let output = fp(
+        dir.path(),
+        &["fingerprint", "--save", "--config", &config],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let fingerprint = String::from_utf8(output.stdout).unwrap();
+    let fingerprint = fingerprint.trim_end();
+    assert_eq!(fingerprint.len(), 6);
+    let text = std::fs::read_to_string(&config).unwrap();
+    assert_eq!(
+        text,
+        format!("fingerprint = \"{}\"\n{}", fingerprint, CONFIG)
+    );
+    let printed = fp(dir.path(), &["fingerprint", "--config", &config], "test\n");
+    assert_eq!(
+        String::from_utf8(printed.stdout).unwrap().trim_end(),
+        fingerprint
+    );
+
+    let output = fp(
+        dir.path(),
+        &["verify-master", "--config", &config],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        output.stdout,
+        b"The master password matches the saved fingerprint\n".to_vec()
+    );
+    let output = fp(
+        dir.path(),
+        &["verify-master", "--config", &config],
+        "tset\n",
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+
+    // Without a terminal to confirm on, a batch with a mistyped master stops
+    // before deriving anything
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "--batch"],
+        "tset\ngithub\n",
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "fp: error: The master password does not match the saved fingerprint\n"
+    );
+    let output = fp(
+        dir.path(),
+        &["--config", &config, "--batch"],
+        "test\ngithub\n",
+    );
+    assert_eq!(output.stdout, b"D04175F7A9c7Ab4a\n".to_vec());
+    // Single runs are not checked
+    let output = fp(dir.path(), &["--config", &config, "github"], "tset\n");
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn test_localized_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["--lang", "zh-CN", "github"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: 错误: 标准输入中没有主密码\n"
+    );
+
+    let in_locale = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_fp"))
+            .args(args)
+            .env("XDG_CONFIG_HOME", dir.path())
+            .env("HOME", dir.path())
+            .env_remove("FP_CONFIG")
+            .env_remove("FP_LENGTH")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env("LANG", "zh_CN.UTF-8")
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let output = in_locale(&["github"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: 错误: 标准输入中没有主密码\n"
+    );
+    // --lang wins over the locale
+    let output = in_locale(&["--lang", "en", "github"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fp: error: No master password on standard input\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--copy", "github.com"])
+        .env("HOME", dir.path())
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    // The password goes to the clipboard, here to none, and never to stdout
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No clipboard found"));
+
+    let output = fp(
+        dir.path(),
+        &["--copy", "--format", "{key}", "github.com"],
+        "test\n",
+    );
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+#[cfg(feature = "qr")]
+fn test_qr_leaves_out_the_plaintext() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(
+        dir.path(),
+        &["--qr", "--qr-format", "ascii", "github.com"],
+        "test\n",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("##"));
+    assert!(!stdout.contains("D04175F7"));
+
+    let svg = dir.path().join("password.svg");
+    let svg_arg = svg.display().to_string();
+    let args = [
+        "--qr",
+        "--qr-format",
+        "svg",
+        "--qr-out",
+        &svg_arg,
+        "github.com",
+    ];
+    let output = fp(dir.path(), &args, "test\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(std::fs::read_to_string(svg).unwrap().contains("<svg"));
+}
+
+#[test]
+#[cfg(feature = "qr")]
+fn test_qr_conflicts_with_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["--qr", "--copy", "github.com"], "test\n");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'--qr' cannot be used with '--copy'"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "autotype"))]
+fn test_autotype_feeds_xdotool() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[sites.github]\nkey = \"github.com\"\nusername = \"octocat\"\n",
+    )
+    .unwrap();
+    // Records its arguments and standard input where the test can read them
+    let xdotool = dir.path().join("xdotool");
+    std::fs::write(
+        &xdotool,
+        "#!/bin/sh\necho \"$@\" > \"$0.args\"\ncat > \"$0.stdin\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&xdotool, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["--config", &config_arg(dir.path())])
+        .args(["--autotype", "--tab-user", "--delay", "0", "github"])
+        .env("HOME", dir.path())
+        .env("PATH", path)
+        .env("DISPLAY", ":99")
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+
+    // The password goes through standard input, never the arguments
+    let args = std::fs::read_to_string(dir.path().join("xdotool.args")).unwrap();
+    assert_eq!(args, "-\n");
+    let script = std::fs::read_to_string(dir.path().join("xdotool.stdin")).unwrap();
+    assert_eq!(
+        script,
+        "key --clearmodifiers o c t o c a t Tab \
+         D 0 4 1 7 5 F 7 A 9 c 7 A b 4 a Return\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pass_sync_goes_on_after_a_failure() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[sites.bad]\n[sites.github]\nkey = \"github.com\"\n",
+    )
+    .unwrap();
+    // Logs its arguments and input, and fails for flowerpassword/bad
+    let pass = dir.path().join("pass");
+    std::fs::write(
+        &pass,
+        "#!/bin/sh\necho \"$@\" >> \"$0.log\"\ncat >> \"$0.log\"\n\
+         case \"$4\" in flowerpassword/bad) echo 'no key' >&2; exit 1;; esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&pass, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+        .args(["pass", "sync", "--config", &config_arg(dir.path())])
+        .env("HOME", dir.path())
+        .env("PATH", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"test\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "fp: error: bad: pass failed: no key\nfp: error: 1 of 2 pass entries failed\n"
+    );
+    let log = std::fs::read_to_string(dir.path().join("pass.log")).unwrap();
+    assert!(log.starts_with("insert --multiline --force flowerpassword/bad\n"));
+    assert!(log.ends_with("insert --multiline --force flowerpassword/github\nD04175F7A9c7Ab4a\n"));
+}
+
+/// A running `fp serve --http`, killed when dropped
+#[cfg(feature = "http")]
+struct Server {
+    child: std::process::Child,
+    addr: String,
+    token: String,
+}
+
+#[cfg(feature = "http")]
+impl Server {
+    fn start(dir: &Path, args: &[&str]) -> Self {
+        use std::io::BufRead;
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fp"))
+            .arg("serve")
+            .args(args)
+            .env("HOME", dir)
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("LANG")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .unwrap();
+        let mut lines = std::io::BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut next = |prefix: &str| {
+            let line = lines.next().unwrap().unwrap();
+            line.strip_prefix(prefix).unwrap().to_string()
+        };
+        let addr = next("Listening on http://");
+        #[cfg(feature = "grpc")]
+        if args.contains(&"--grpc") {
+            next("Listening for gRPC on ")
+                .parse::<std::net::SocketAddr>()
+                .unwrap();
+        }
+        let token = next("Token: ");
+        Server { child, addr, token }
+    }
+
+    /// Sends `method path` with `body` and returns the status and body
+    fn request(&self, method: &str, path: &str, auth: Option<&str>, body: &str) -> (u16, String) {
+        use std::io::Read;
+
+        let mut stream = std::net::TcpStream::connect(&self.addr).unwrap();
+        let auth = auth.map_or(String::new(), |auth| format!("Authorization: {}\r\n", auth));
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            self.addr,
+            auth,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(
+            head.to_lowercase().contains("cache-control: no-store"),
+            "{}",
+            head
+        );
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    }
+
+    fn derive(&self, auth: Option<&str>, body: &str) -> (u16, serde_json::Value) {
+        let (status, body) = self.request("POST", "/derive", auth, body);
+        (status, serde_json::from_str(&body).unwrap())
+    }
+}
+
+#[cfg(feature = "http")]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+#[cfg(feature = "http")]
+fn test_serve_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = Server::start(dir.path(), &["--http", "127.0.0.1:0"]);
+    assert_eq!(server.token.len(), 64);
+    let bearer = format!("Bearer {}", server.token);
+    let body = r#"{"password":"test","key":"github.com","length":16}"#;
+
+    let (status, json) = server.derive(Some(&bearer), body);
+    assert_eq!(status, 200);
+    assert_eq!(json, serde_json::json!({ "password": "D04175F7A9c7Ab4a" }));
+    let (status, json) = server.derive(Some(&bearer), r#"{"password":"password","key":"key"}"#);
+    assert_eq!(
+        (status, &json["password"]),
+        (200, &"K3A2a66Bf88b628c".into())
+    );
+
+    // Without the token, or with another one
+    let wrong = format!("Bearer {}", "0".repeat(64));
+    for auth in [None, Some(wrong.as_str()), Some(server.token.as_str())] {
+        let (status, json) = server.derive(auth, body);
+        assert_eq!(status, 401, "{:?}", auth);
+        assert_eq!(json["error"]["kind"], "Unauthorized");
+    }
+
+    let (status, json) = server.derive(
+        Some(&bearer),
+        r#"{"password":"test","key":"key","length":40}"#,
+    );
+    assert_eq!(status, 400);
+    assert_eq!(
+        json,
+        serde_json::json!({ "error": {
+            "kind": "InvalidLength",
+            "message": "Length must be between 2 and 32, got: 40",
+            "length": 40
+        }})
+    );
+    for body in [
+        "not json",
+        r#"{"key":"key"}"#,
+        r#"{"password":"t","key":"k","x":1}"#,
+    ] {
+        let (status, json) = server.derive(Some(&bearer), body);
+        assert_eq!(status, 400, "{}", body);
+        assert_eq!(json["error"]["kind"], "InvalidRequest");
+    }
+
+    let (status, body) = server.request("GET", "/", Some(&bearer), "");
+    assert_eq!(status, 404);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["error"]["kind"], "NotFound");
+}
+
+#[test]
+#[cfg(feature = "http")]
+fn test_serve_http_stays_on_loopback() {
+    let dir = tempfile::tempdir().unwrap();
+    for addr in ["0.0.0.0:0", "[::]:0"] {
+        #[cfg(feature = "grpc")]
+        let args = ["serve", "--http", "127.0.0.1:0", "--grpc", addr];
+        #[cfg(not(feature = "grpc"))]
+        let args = ["serve", "--http", addr];
+        let output = fp(dir.path(), &args, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", output);
+        assert!(output.stdout.is_empty());
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!(
+                "fp: error: {} is not a loopback address; other machines could reach it, \
+                 see --i-know-what-im-doing\n",
+                addr
+            )
+        );
+    }
+
+    // The override serves on every address, loopback among them
+    let args = ["--http", "0.0.0.0:0", "--i-know-what-im-doing"];
+    let mut server = Server::start(dir.path(), &args);
+    let port = server.addr.strip_prefix("0.0.0.0:").unwrap();
+    server.addr = format!("127.0.0.1:{}", port);
+    let bearer = format!("Bearer {}", server.token);
+    let (status, json) = server.derive(Some(&bearer), r#"{"password":"test","key":"key"}"#);
+    assert_eq!(
+        (status, &json["password"]),
+        (200, &"KAC5fEf00146FD68".into())
+    );
+}
+
+#[test]
+#[cfg(all(feature = "grpc", feature = "http"))]
+fn test_serve_http_and_grpc() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fp(dir.path(), &["serve"], "");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    // One token for both
+    let args = ["--http", "127.0.0.1:0", "--grpc", "127.0.0.1:0"];
+    let server = Server::start(dir.path(), &args);
+    let bearer = format!("Bearer {}", server.token);
+    let (status, json) = server.derive(Some(&bearer), r#"{"password":"test","key":"key"}"#);
+    assert_eq!(
+        (status, &json["password"]),
+        (200, &"KAC5fEf00146FD68".into())
+    );
+}
@@ -0,0 +1,573 @@
+# Print an optspec for argparse to handle cmd's options that are independent of any subcommand.
+function __fish_fp_global_optspecs
+    string join \n l/length= policy= find-length min= config= batch j/jobs= stats i/interactive confirm no-confirm password-file= insecure-perms password-fd= password-gpg= pinentry= master= yubikey-label= agent agent-endpoint= use-keyring paranoid format= 0/null newline no-newline raw q/quiet color= lang= show-timeout= masked mask-style= autotype delay= tab-user show-user copy copy-user-then-pass log entropy check-pwned check-blocklist blocklist= qr qr-format= qr-out= qr-uri h/help V/version
+end
+
+function __fish_fp_needs_command
+    # Figure out if the current invocation already has a command.
+    set -l cmd (commandline -opc)
+    set -e cmd[1]
+    argparse -s (__fish_fp_global_optspecs) -- $cmd 2>/dev/null
+    or return
+    if set -q argv[1]
+        # Also print the command, so this can be used to figure out what it is.
+        echo $argv[1]
+        return 1
+    end
+    return 0
+end
+
+function __fish_fp_using_subcommand
+    set -l cmd (__fish_fp_needs_command)
+    test -z "$cmd"
+    and return 1
+    contains -- $cmd[1] $argv
+end
+
+complete -c fp -n "__fish_fp_needs_command" -s l -l length -d 'Password length, instead of the configured one' -r
+complete -c fp -n "__fish_fp_needs_command" -l policy -d 'Fail unless the password meets these comma-separated requirements: require-digit, require-upper, require-lower' -r
+complete -c fp -n "__fish_fp_needs_command" -l min -d 'Shortest length --find-length tries, instead of the configured one' -r
+complete -c fp -n "__fish_fp_needs_command" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_needs_command" -s j -l jobs -d 'Derive the --batch keys on N threads, streaming them in their order, with a progress bar on a terminal' -r
+complete -c fp -n "__fish_fp_needs_command" -l password-file -d 'Read the master password from the first line of a file' -r -F
+complete -c fp -n "__fish_fp_needs_command" -l password-fd -d 'Read the master password from the first line of an inherited file descriptor' -r
+complete -c fp -n "__fish_fp_needs_command" -l password-gpg -d 'Read the master password from the first line of a gpg-encrypted file' -r -F
+complete -c fp -n "__fish_fp_needs_command" -l pinentry -d 'Ask a pinentry program for the master password: the first pinentry on PATH, or PROGRAM with --pinentry=PROGRAM' -r
+complete -c fp -n "__fish_fp_needs_command" -l master -d 'Derive the master password from a device: fido2, or fido2:LABEL for another one from the same security key; yubikey, or yubikey:SLOT for slot 1 rather than 2; ssh-agent, or ssh-agent:FINGERPRINT among several Ed25519 keys' -r
+complete -c fp -n "__fish_fp_needs_command" -l yubikey-label -d 'Label of the challenge sent by --master yubikey, for another master password from the same slot [default: default]' -r
+complete -c fp -n "__fish_fp_needs_command" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_needs_command" -l format -d 'Output template with {key}, {password}, {length}, {scheme} and {username}; {{ and }} are literal braces' -r
+complete -c fp -n "__fish_fp_needs_command" -l color -d 'Color digits, uppercase and lowercase letters and symbols apart' -r -f -a "auto\t'On a terminal, unless `NO_COLOR` is set'
+always\t'Always, even when piped'
+never\t'Never'"
+complete -c fp -n "__fish_fp_needs_command" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_needs_command" -l show-timeout -d 'Erase the passwords from the terminal after this long, such as 10s' -r
+complete -c fp -n "__fish_fp_needs_command" -l mask-style -d 'Characters --masked leaves visible at each end, as FIRST+LAST or none' -r
+complete -c fp -n "__fish_fp_needs_command" -l delay -d 'How long --autotype waits before typing, to focus the window, such as 5s' -r
+complete -c fp -n "__fish_fp_needs_command" -l blocklist -d 'Add the passwords listed in FILE, one per line, to the blocklist of --check-blocklist' -r -F
+complete -c fp -n "__fish_fp_needs_command" -l qr-format -d 'How to render --qr' -r -f -a "ansi\t'Unicode half blocks with explicit colours, two rows per line'
+ascii\t'`#` and spaces, two characters per module'
+svg\t'An SVG image, written to `--qr-out`'"
+complete -c fp -n "__fish_fp_needs_command" -l qr-out -d 'File to write the SVG of --qr-format svg to' -r -F
+complete -c fp -n "__fish_fp_needs_command" -l find-length -d 'Derive at length 32 and output the shortest prefix, of at least --min characters, that meets the --policy; the length chosen is reported'
+complete -c fp -n "__fish_fp_needs_command" -l batch -d 'Read site names or keys from standard input, one per line, after the master password when it is not a terminal'
+complete -c fp -n "__fish_fp_needs_command" -l stats -d 'Print how many --batch keys were derived per second, at the end'
+complete -c fp -n "__fish_fp_needs_command" -s i -l interactive -d 'Read the master password once, then keys and :commands line by line'
+complete -c fp -n "__fish_fp_needs_command" -l confirm -d 'Ask for the master password twice; the default on a terminal without --batch'
+complete -c fp -n "__fish_fp_needs_command" -l no-confirm -d 'Ask for the master password once'
+complete -c fp -n "__fish_fp_needs_command" -l insecure-perms -d 'Accept a --password-file that other users may read'
+complete -c fp -n "__fish_fp_needs_command" -l agent -d 'Ask the agent of `fp agent start` for the passwords, reading the master password as usual if no agent answers unlocked'
+complete -c fp -n "__fish_fp_needs_command" -l use-keyring -d 'Read the master password from the OS keyring, prompting if it cannot'
+complete -c fp -n "__fish_fp_needs_command" -l paranoid -d 'Take no chances: the master password only from --password-fd or --pinentry, confirmed, and shown --masked without copying; no --log, and no standard output redirected to a file'
+complete -c fp -n "__fish_fp_needs_command" -s 0 -l null -d 'End every output record with NUL instead of a newline'
+complete -c fp -n "__fish_fp_needs_command" -l newline -d 'Terminate the last record, even when piped'
+complete -c fp -n "__fish_fp_needs_command" -l no-newline -d 'Leave the last record unterminated, even on a terminal'
+complete -c fp -n "__fish_fp_needs_command" -l raw -d 'Print the passwords and nothing else: no trailing newline unless --newline, and no decoration even on a terminal'
+complete -c fp -n "__fish_fp_needs_command" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_needs_command" -l masked -d 'Show the password masked on the terminal, with keys to reveal or copy it'
+complete -c fp -n "__fish_fp_needs_command" -l autotype -d 'Type the password into the focused window instead of printing it'
+complete -c fp -n "__fish_fp_needs_command" -l tab-user -d 'Have --autotype type the configured username and Tab first, and Enter last'
+complete -c fp -n "__fish_fp_needs_command" -l show-user -d 'Print the username configured for the site and a Tab before the password'
+complete -c fp -n "__fish_fp_needs_command" -l copy -d 'Copy the password instead of printing it, and restore the clipboard after 30s'
+complete -c fp -n "__fish_fp_needs_command" -l copy-user-then-pass -d 'Copy the username configured for the site, then the password once Enter is pressed or after 5s, instead of printing the password'
+complete -c fp -n "__fish_fp_needs_command" -l log -d 'Append the site, length and time of each derived password to the history, never the password'
+complete -c fp -n "__fish_fp_needs_command" -l entropy -d 'Print an estimate of each password\'s strength to standard error'
+complete -c fp -n "__fish_fp_needs_command" -l check-pwned -d 'Warn about passwords found in Pwned Passwords, sending only the start of their SHA-1 hash over curl'
+complete -c fp -n "__fish_fp_needs_command" -l check-blocklist -d 'Warn about master and derived passwords on the built-in blocklist of common passwords'
+complete -c fp -n "__fish_fp_needs_command" -l qr -d 'Show the password as a QR code instead of printing it'
+complete -c fp -n "__fish_fp_needs_command" -l qr-uri -d 'Encode the flowerpassword://derive link for the key instead of the password'
+complete -c fp -n "__fish_fp_needs_command" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_needs_command" -s V -l version -d 'Print version'
+complete -c fp -n "__fish_fp_needs_command" -a "completions" -d 'Print the completion script for a shell to standard output'
+complete -c fp -n "__fish_fp_needs_command" -a "list" -d 'List the configured sites with their keys and lengths, never passwords'
+complete -c fp -n "__fish_fp_needs_command" -a "show" -d 'Show everything about a site or alias except its password'
+complete -c fp -n "__fish_fp_needs_command" -a "note" -d 'Set the notes of a site in the configuration file'
+complete -c fp -n "__fish_fp_needs_command" -a "find" -d 'Fuzzy-search the configured sites by name, key, alias and notes'
+complete -c fp -n "__fish_fp_needs_command" -a "launcher" -d 'Script filter for Raycast and Alfred: list matching sites as JSON, or copy the password of the picked one'
+complete -c fp -n "__fish_fp_needs_command" -a "menu" -d 'Site names for rofi or dmenu, one per line, or copy the password of the picked one'
+complete -c fp -n "__fish_fp_needs_command" -a "add" -d 'Add a site to the configuration file'
+complete -c fp -n "__fish_fp_needs_command" -a "rm" -d 'Remove a site from the configuration file, after asking'
+complete -c fp -n "__fish_fp_needs_command" -a "rename" -d 'Rename a site of the configuration file, keeping its key and so its password, and the aliases pointing at it'
+complete -c fp -n "__fish_fp_needs_command" -a "alias" -d 'Manage short names for sites in the configuration file'
+complete -c fp -n "__fish_fp_needs_command" -a "audit" -d 'Derive every configured site and report those sharing a password, never printing the passwords'
+complete -c fp -n "__fish_fp_needs_command" -a "export" -d 'Write every configured site with its password, for importing into another password manager'
+complete -c fp -n "__fish_fp_needs_command" -a "import" -d 'Add a site for every login of a browser\'s password CSV, keyed by its domain; the passwords in it are ignored'
+complete -c fp -n "__fish_fp_needs_command" -a "doctor" -d 'Check the configuration, clipboard, keyring, agent and terminal'
+complete -c fp -n "__fish_fp_needs_command" -a "self-test" -d 'Check that this build reproduces the known-answer vectors'
+complete -c fp -n "__fish_fp_needs_command" -a "config" -d 'Inspect and maintain the configuration files'
+complete -c fp -n "__fish_fp_needs_command" -a "fingerprint" -d 'Print the short fingerprint of the master password'
+complete -c fp -n "__fish_fp_needs_command" -a "verify-master" -d 'Check a master password against the saved fingerprint'
+complete -c fp -n "__fish_fp_needs_command" -a "history" -d 'Show when passwords were derived with --log, oldest first'
+complete -c fp -n "__fish_fp_needs_command" -a "man" -d 'Write the man pages of fp and its subcommands'
+complete -c fp -n "__fish_fp_needs_command" -a "pass" -d 'Keep the derived passwords in the pass store, under flowerpassword/'
+complete -c fp -n "__fish_fp_needs_command" -a "unlock" -d 'Cache the master password for a while, for runs that would prompt'
+complete -c fp -n "__fish_fp_needs_command" -a "lock" -d 'Wipe the cached master password'
+complete -c fp -n "__fish_fp_needs_command" -a "status" -d 'Tell whether the master password is cached, and for how long'
+complete -c fp -n "__fish_fp_needs_command" -a "tui" -d 'Pick sites from a full-screen list and copy their passwords'
+complete -c fp -n "__fish_fp_needs_command" -a "serve" -d 'Derive passwords over standard input and output, HTTP, gRPC or the session D-Bus'
+complete -c fp -n "__fish_fp_needs_command" -a "native-host" -d 'Answer a browser extension over native messaging, as the browser runs it, with the master password of the agent, `fp unlock` or the keyring'
+complete -c fp -n "__fish_fp_needs_command" -a "install-manifest" -d 'Let a browser extension run `fp native-host`, writing its host manifest and a script running this fp'
+complete -c fp -n "__fish_fp_needs_command" -a "handle-uri" -d 'Derive the password a flowerpassword:// link asks for, as the desktop runs it, with the master password of the agent, `fp unlock` or the keyring'
+complete -c fp -n "__fish_fp_needs_command" -a "register-uri-handler" -d 'Have the desktop open flowerpassword:// links with `fp handle-uri`'
+complete -c fp -n "__fish_fp_needs_command" -a "fido2" -d 'Use a security key for --master fido2'
+complete -c fp -n "__fish_fp_needs_command" -a "agent" -d 'Keep the master password in a background agent for --agent runs'
+complete -c fp -n "__fish_fp_needs_command" -a "keyring" -d 'Manage the master password stored in the OS keyring'
+complete -c fp -n "__fish_fp_needs_command" -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand completions" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand completions" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand completions" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand completions" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand completions" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand list" -l output -d 'How to print the sites' -r -f -a "table\t'Aligned columns under a header'
+json\t'A JSON array of objects'
+csv\t'Comma-separated values under a header'"
+complete -c fp -n "__fish_fp_using_subcommand list" -l filter -d 'Show only sites whose name or key contains this, ignoring case' -r
+complete -c fp -n "__fish_fp_using_subcommand list" -l sort -d 'Order of the sites' -r -f -a "name\t'By site name'
+length\t'By password length, then by site name'"
+complete -c fp -n "__fish_fp_using_subcommand list" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand list" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand list" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand list" -s l -l long -d 'Also show the notes of each site'
+complete -c fp -n "__fish_fp_using_subcommand list" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand list" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand show" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand show" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand show" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand show" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand show" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand note" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand note" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand note" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand note" -l append -d 'Add the text as a new line instead of replacing the notes'
+complete -c fp -n "__fish_fp_using_subcommand note" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand note" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand find" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand find" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand find" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand find" -l select -d 'Derive the password of the best match, asking which one if several match about as well'
+complete -c fp -n "__fish_fp_using_subcommand find" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand find" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand launcher" -l query -d 'Print the sites fuzzy-matching this as script filter items' -r
+complete -c fp -n "__fish_fp_using_subcommand launcher" -l action -d 'Copy the password for the site of this item\'s arg' -r
+complete -c fp -n "__fish_fp_using_subcommand launcher" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand launcher" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand launcher" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand launcher" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand launcher" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand menu" -l selected -d 'Copy the password of this site, as picked from the list' -r
+complete -c fp -n "__fish_fp_using_subcommand menu" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand menu" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand menu" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand menu" -l rofi -d 'Run rofi on the list and copy the password of the picked site'
+complete -c fp -n "__fish_fp_using_subcommand menu" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand menu" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand add" -l key -d 'Key to derive with, instead of the name' -r
+complete -c fp -n "__fish_fp_using_subcommand add" -l length -d 'Password length, instead of the default' -r
+complete -c fp -n "__fish_fp_using_subcommand add" -l scheme -d 'Derivation scheme, v1 or v2, instead of the default' -r
+complete -c fp -n "__fish_fp_using_subcommand add" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand add" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand add" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand add" -l force -d 'Replace the site if the file configures it already'
+complete -c fp -n "__fish_fp_using_subcommand add" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand add" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand rm" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand rm" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand rm" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand rm" -s y -l yes -d 'Do not ask'
+complete -c fp -n "__fish_fp_using_subcommand rm" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand rm" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand rename" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand rename" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand rename" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand rename" -l also-key -d 'Derive with the new name as the key too, which changes the password'
+complete -c fp -n "__fish_fp_using_subcommand rename" -l force -d 'Replace the site configured under the new name, if any'
+complete -c fp -n "__fish_fp_using_subcommand rename" -s y -l yes -d 'Do not ask before --also-key changes the password'
+complete -c fp -n "__fish_fp_using_subcommand rename" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand rename" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -f -a "add" -d 'Point ALIAS at a site or another alias, replacing what it pointed to'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -f -a "rm" -d 'Remove an alias'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -f -a "list" -d 'List the aliases with what they point to'
+complete -c fp -n "__fish_fp_using_subcommand alias; and not __fish_seen_subcommand_from add rm list help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from add" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from add" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from add" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from add" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from add" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from rm" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from rm" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from rm" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from rm" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from rm" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from list" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from list" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from list" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from list" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from list" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "add" -d 'Point ALIAS at a site or another alias, replacing what it pointed to'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "rm" -d 'Remove an alias'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "list" -d 'List the aliases with what they point to'
+complete -c fp -n "__fish_fp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand audit" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand audit" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand audit" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand audit" -l check-reuse -d 'Report sites that derive the same password; the only check for now, and the one run without any'
+complete -c fp -n "__fish_fp_using_subcommand audit" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand audit" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand export" -l format -d 'What to write' -r -f -a "browser-csv\t'`name,url,username,password`, for the Chrome and Firefox importers'
+keepass-csv\t'`Group,Title,Username,Password,URL,Notes`, for KeePass'
+keepass-xml\t'The KeePass 2.x XML format'"
+complete -c fp -n "__fish_fp_using_subcommand export" -l out -d 'File to write, created readable by its owner only' -r -F
+complete -c fp -n "__fish_fp_using_subcommand export" -l group -d 'Group to put the entries of the KeePass formats in' -r
+complete -c fp -n "__fish_fp_using_subcommand export" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand export" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand export" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand export" -l stdout -d 'Write to standard output instead, even a terminal'
+complete -c fp -n "__fish_fp_using_subcommand export" -s y -l yes -d 'Do not ask before writing the passwords to --out'
+complete -c fp -n "__fish_fp_using_subcommand export" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand export" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand import" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand import" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand import" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand import" -l allow-duplicates -d 'Import other accounts on a configured domain as NAME-2 and so on'
+complete -c fp -n "__fish_fp_using_subcommand import" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand import" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand doctor" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand doctor" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand doctor" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand doctor" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand doctor" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand self-test" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand self-test" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand self-test" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand self-test" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand self-test" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "path" -d 'Show the file of each configuration layer, in order, and which exist'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "lint" -d 'Check every configuration file for typos, wrong types and bad lengths, failing on errors but not on warnings'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "migrate" -d 'Upgrade the configuration file to the current schema version, keeping a backup'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "encrypt" -d 'Encrypt the configuration file with a passphrase asked for on the terminal'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "decrypt" -d 'Decrypt the configuration file back to plain TOML'
+complete -c fp -n "__fish_fp_using_subcommand config; and not __fish_seen_subcommand_from path lint migrate encrypt decrypt help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from path" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from path" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from path" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from path" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from path" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from lint" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from lint" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from lint" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from lint" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from lint" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -l dry-run -d 'Only tell what would change'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from migrate" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from encrypt" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from encrypt" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from encrypt" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from encrypt" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from encrypt" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from decrypt" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from decrypt" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from decrypt" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from decrypt" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from decrypt" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "path" -d 'Show the file of each configuration layer, in order, and which exist'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "lint" -d 'Check every configuration file for typos, wrong types and bad lengths, failing on errors but not on warnings'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "migrate" -d 'Upgrade the configuration file to the current schema version, keeping a backup'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "encrypt" -d 'Encrypt the configuration file with a passphrase asked for on the terminal'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "decrypt" -d 'Decrypt the configuration file back to plain TOML'
+complete -c fp -n "__fish_fp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -l save -d 'Also keep it in the configuration, for verify-master and --batch'
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand fingerprint" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand verify-master" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand verify-master" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand verify-master" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand verify-master" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand verify-master" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -l since -d 'Only what is newer than this, such as 30d or 12h' -r
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -a "clear" -d 'Remove the whole history'
+complete -c fp -n "__fish_fp_using_subcommand history; and not __fish_seen_subcommand_from clear help" -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from clear" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from clear" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from clear" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from clear" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from clear" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from help" -f -a "clear" -d 'Remove the whole history'
+complete -c fp -n "__fish_fp_using_subcommand history; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand man" -l out-dir -d 'Directory to write fp.1 and the other pages to' -r -F
+complete -c fp -n "__fish_fp_using_subcommand man" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand man" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand man" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand man" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand man" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -f -a "sync" -d 'Store the password of every configured site as flowerpassword/<name>'
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -f -a "rm" -d 'Remove the entries of these sites, or all of flowerpassword/'
+complete -c fp -n "__fish_fp_using_subcommand pass; and not __fish_seen_subcommand_from sync rm help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from sync" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from sync" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from sync" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from sync" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from sync" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from rm" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from rm" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from rm" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from rm" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from rm" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from help" -f -a "sync" -d 'Store the password of every configured site as flowerpassword/<name>'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from help" -f -a "rm" -d 'Remove the entries of these sites, or all of flowerpassword/'
+complete -c fp -n "__fish_fp_using_subcommand pass; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand unlock" -l timeout -d 'How long to keep it, such as 90s, 15m or 1h30m' -r
+complete -c fp -n "__fish_fp_using_subcommand unlock" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand unlock" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand unlock" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand unlock" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand unlock" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand lock" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand lock" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand lock" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand lock" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand lock" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand status" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand status" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand status" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand status" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand status" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand tui" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand tui" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand tui" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand tui" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand tui" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand serve" -l http -d 'Serve the HTTP API here, such as 127.0.0.1:0 for any free port' -r
+complete -c fp -n "__fish_fp_using_subcommand serve" -l grpc -d 'Serve the gRPC API here, such as 127.0.0.1:0 for any free port' -r
+complete -c fp -n "__fish_fp_using_subcommand serve" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand serve" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand serve" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand serve" -l stdio -d 'Answer JSON-RPC 2.0 on standard input and output, one message per line or with Content-Length headers, until standard input closes'
+complete -c fp -n "__fish_fp_using_subcommand serve" -l binary -d 'Answer the length-prefixed binary protocol on standard input and output, the master password coming in its handshake frame'
+complete -c fp -n "__fish_fp_using_subcommand serve" -l allow-rekey -d 'Accept setMaster more than once with --stdio'
+complete -c fp -n "__fish_fp_using_subcommand serve" -l dbus -d 'Serve org.flowerpassword.Derive1 on the session bus, with the master password read now'
+complete -c fp -n "__fish_fp_using_subcommand serve" -l i-know-what-im-doing -d 'Listen on an address other machines can reach too'
+complete -c fp -n "__fish_fp_using_subcommand serve" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand serve" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand native-host" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand native-host" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand native-host" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand native-host" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand native-host" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l browser -d 'Browser to install the host for' -r -f -a "chrome\t'Google Chrome'
+chromium\t'Chromium'
+firefox\t'Mozilla Firefox'"
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l extension-id -d 'ID of an extension allowed to connect, repeatable' -r
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l binary -d 'fp to run instead of this one' -r -F
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand install-manifest" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand handle-uri" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand handle-uri" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand handle-uri" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand handle-uri" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand handle-uri" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -l binary -d 'fp to run instead of this one' -r -F
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand register-uri-handler" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -f -a "enroll" -d 'Create a credential on the security key and keep its ID in the configuration'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and not __fish_seen_subcommand_from enroll help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -l force -d 'Replace the credential enrolled already, which changes every password'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from enroll" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from help" -f -a "enroll" -d 'Create a credential on the security key and keep its ID in the configuration'
+complete -c fp -n "__fish_fp_using_subcommand fido2; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -f -a "start" -d 'Read the master password and serve it until `fp agent stop`'
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -f -a "stop" -d 'Have the agent wipe the master password and exit'
+complete -c fp -n "__fish_fp_using_subcommand agent; and not __fish_seen_subcommand_from start stop help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -l foreground -d 'Stay in the foreground, as a service manager expects; always so on Windows'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from start" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from stop" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from stop" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from stop" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from stop" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from stop" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from help" -f -a "start" -d 'Read the master password and serve it until `fp agent stop`'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from help" -f -a "stop" -d 'Have the agent wipe the master password and exit'
+complete -c fp -n "__fish_fp_using_subcommand agent; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -f -a "set" -d 'Prompt for the master password and store it'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -f -a "clear" -d 'Remove the stored master password'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -f -a "status" -d 'Tell whether a master password is stored'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and not __fish_seen_subcommand_from set clear status help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from set" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from set" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from set" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from set" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from set" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from clear" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from clear" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from clear" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from clear" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from clear" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from status" -l config -d 'Configuration file to read instead of the user\'s' -r -F
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from status" -l agent-endpoint -d 'Where `fp agent`, --agent and `fp doctor` find the agent: a socket path, or a named pipe \\\\.\\pipe\\NAME on Windows, instead of the user\'s default' -r
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from status" -l lang -d 'Language of the prompts and messages, instead of the one of $LANG' -r -f -a "en\t'English'
+zh-CN\t'Simplified Chinese'"
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from status" -s q -l quiet -d 'Print nothing but the passwords and errors, leaving out warnings and notices'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from status" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from help" -f -a "set" -d 'Prompt for the master password and store it'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from help" -f -a "clear" -d 'Remove the stored master password'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from help" -f -a "status" -d 'Tell whether a master password is stored'
+complete -c fp -n "__fish_fp_using_subcommand keyring; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "completions" -d 'Print the completion script for a shell to standard output'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "list" -d 'List the configured sites with their keys and lengths, never passwords'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "show" -d 'Show everything about a site or alias except its password'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "note" -d 'Set the notes of a site in the configuration file'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "find" -d 'Fuzzy-search the configured sites by name, key, alias and notes'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "launcher" -d 'Script filter for Raycast and Alfred: list matching sites as JSON, or copy the password of the picked one'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "menu" -d 'Site names for rofi or dmenu, one per line, or copy the password of the picked one'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "add" -d 'Add a site to the configuration file'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "rm" -d 'Remove a site from the configuration file, after asking'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "rename" -d 'Rename a site of the configuration file, keeping its key and so its password, and the aliases pointing at it'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "alias" -d 'Manage short names for sites in the configuration file'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "audit" -d 'Derive every configured site and report those sharing a password, never printing the passwords'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "export" -d 'Write every configured site with its password, for importing into another password manager'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "import" -d 'Add a site for every login of a browser\'s password CSV, keyed by its domain; the passwords in it are ignored'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "doctor" -d 'Check the configuration, clipboard, keyring, agent and terminal'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "self-test" -d 'Check that this build reproduces the known-answer vectors'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "config" -d 'Inspect and maintain the configuration files'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "fingerprint" -d 'Print the short fingerprint of the master password'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "verify-master" -d 'Check a master password against the saved fingerprint'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "history" -d 'Show when passwords were derived with --log, oldest first'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "man" -d 'Write the man pages of fp and its subcommands'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "pass" -d 'Keep the derived passwords in the pass store, under flowerpassword/'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "unlock" -d 'Cache the master password for a while, for runs that would prompt'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "lock" -d 'Wipe the cached master password'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "status" -d 'Tell whether the master password is cached, and for how long'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "tui" -d 'Pick sites from a full-screen list and copy their passwords'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "serve" -d 'Derive passwords over standard input and output, HTTP, gRPC or the session D-Bus'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "native-host" -d 'Answer a browser extension over native messaging, as the browser runs it, with the master password of the agent, `fp unlock` or the keyring'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "install-manifest" -d 'Let a browser extension run `fp native-host`, writing its host manifest and a script running this fp'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "handle-uri" -d 'Derive the password a flowerpassword:// link asks for, as the desktop runs it, with the master password of the agent, `fp unlock` or the keyring'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "register-uri-handler" -d 'Have the desktop open flowerpassword:// links with `fp handle-uri`'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "fido2" -d 'Use a security key for --master fido2'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "agent" -d 'Keep the master password in a background agent for --agent runs'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "keyring" -d 'Manage the master password stored in the OS keyring'
+complete -c fp -n "__fish_fp_using_subcommand help; and not __fish_seen_subcommand_from completions list show note find launcher menu add rm rename alias audit export import doctor self-test config fingerprint verify-master history man pass unlock lock status tui serve native-host install-manifest handle-uri register-uri-handler fido2 agent keyring help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "add" -d 'Point ALIAS at a site or another alias, replacing what it pointed to'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "rm" -d 'Remove an alias'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "list" -d 'List the aliases with what they point to'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "path" -d 'Show the file of each configuration layer, in order, and which exist'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "lint" -d 'Check every configuration file for typos, wrong types and bad lengths, failing on errors but not on warnings'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "migrate" -d 'Upgrade the configuration file to the current schema version, keeping a backup'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "encrypt" -d 'Encrypt the configuration file with a passphrase asked for on the terminal'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "decrypt" -d 'Decrypt the configuration file back to plain TOML'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from history" -f -a "clear" -d 'Remove the whole history'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from pass" -f -a "sync" -d 'Store the password of every configured site as flowerpassword/<name>'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from pass" -f -a "rm" -d 'Remove the entries of these sites, or all of flowerpassword/'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from fido2" -f -a "enroll" -d 'Create a credential on the security key and keep its ID in the configuration'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from agent" -f -a "start" -d 'Read the master password and serve it until `fp agent stop`'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from agent" -f -a "stop" -d 'Have the agent wipe the master password and exit'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from keyring" -f -a "set" -d 'Prompt for the master password and store it'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from keyring" -f -a "clear" -d 'Remove the stored master password'
+complete -c fp -n "__fish_fp_using_subcommand help; and __fish_seen_subcommand_from keyring" -f -a "status" -d 'Tell whether a master password is stored'
+complete -c fp -n "__fish_fp_needs_command" -f -a "(fp __complete -- (commandline -ct) 2>/dev/null)"
@@ -0,0 +1,99 @@
+//! The hand-rolled HMAC-MD5 used before the move to the RustCrypto `hmac` and
+//! `md-5` crates
+//!
+//! Enabled by the deprecated `legacy-md5` feature, which makes `fp_code` use it
+//! instead of `hmac_md5_hex`. Both give identical results; this module will be
+//! removed in the next release.
+
+use crate::Wiped;
+use crate::{to_hex, MD5_HEX_LENGTH};
+use md5_legacy as md5;
+
+/// Computes HMAC-MD5 hash
+///
+/// This function implements HMAC-MD5 to match the behavior of blueimp-md5's
+/// two-parameter mode used in the JavaScript implementation.
+///
+/// Special case: When key is empty, returns regular MD5 hash (not HMAC)
+/// to match blueimp-md5 behavior.
+pub(crate) fn hmac_md5(message_bytes: &[u8], key_bytes: &[u8]) -> [u8; MD5_HEX_LENGTH] {
+    // Special case: empty key returns regular MD5 (matching blueimp-md5 behavior)
+    if key_bytes.is_empty() {
+        let digest = Wiped(md5::compute(message_bytes).0);
+        return to_hex(&digest);
+    }
+
+    const BLOCK_SIZE: usize = 64;
+
+    // Prepare the key; it and both pads are wiped when they go out of scope
+    let mut key_block = Wiped([0u8; BLOCK_SIZE]);
+    if key_bytes.len() > BLOCK_SIZE {
+        // If key is longer than block size, hash it
+        let digest = Wiped(md5::compute(key_bytes).0);
+        key_block[..16].copy_from_slice(&*digest);
+    } else {
+        // Otherwise, use key as-is (padded with zeros)
+        key_block[..key_bytes.len()].copy_from_slice(key_bytes);
+    }
+
+    // Create inner and outer padded keys
+    let mut ipad = Wiped([0x36u8; BLOCK_SIZE]);
+    let mut opad = Wiped([0x5cu8; BLOCK_SIZE]);
+
+    for i in 0..BLOCK_SIZE {
+        ipad[i] ^= key_block[i];
+        opad[i] ^= key_block[i];
+    }
+
+    // Compute inner hash: H(K XOR ipad, message)
+    let mut inner = md5::Context::new();
+    inner.consume(&ipad[..]);
+    inner.consume(message_bytes);
+    let inner_hash = Wiped(inner.finalize().0);
+
+    // Compute outer hash: H(K XOR opad, inner_hash)
+    let mut outer = md5::Context::new();
+    outer.consume(&opad[..]);
+    outer.consume(&inner_hash[..]);
+    let outer_hash = Wiped(outer.finalize().0);
+
+    // Return as hex string
+    to_hex(&outer_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hmac_md5_hex;
+    use crate::tests::{XorShift, HMAC_VECTORS};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_vectors() {
+        for &(message, key, expected) in HMAC_VECTORS {
+            assert_eq!(&hmac_md5(message, key), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_matches_hmac_crate_on_random_inputs() {
+        let mut rng = XorShift(0xd1b5_4a32_d192_ed03);
+        for _ in 0..2000 {
+            // Up to 160 bytes, so keys longer than the 64-byte block and messages
+            // spanning several blocks occur
+            let bytes = |rng: &mut XorShift| -> Vec<u8> {
+                let len = (rng.next() % 161) as usize;
+                (0..len).map(|_| rng.next() as u8).collect()
+            };
+            let message = bytes(&mut rng);
+            let key = bytes(&mut rng);
+            assert_eq!(
+                hmac_md5(&message, &key),
+                hmac_md5_hex(&message, &key),
+                "message {:?}, key {:?}",
+                message,
+                key
+            );
+        }
+    }
+}
@@ -0,0 +1,1168 @@
+//! Flower Password algorithm
+//!
+//! The dependency-light core of the [`flowerpassword`] crate: `fp_code`,
+//! `fp_verify`, `fp_fingerprint` and, with the `heapless` feature, the allocation-free
+//! `fp_code_heapless`. It only needs `alloc` and depends on nothing but the
+//! RustCrypto `md-5` and `hmac` crates (and optionally `heapless`); without the
+//! default `rustcrypto` feature, or with `vendored-md5`, it uses an in-tree MD5
+//! and HMAC and depends on nothing at all.
+//! Every derivation wipes its intermediate hashes before returning. Most users
+//! want the `flowerpassword` facade, which re-exports this API and adds
+//! configuration, bindings and protocols.
+//!
+//! [`flowerpassword`]: https://docs.rs/flowerpassword
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "scheme-v1-md5")] {
+//! use flowerpassword_core::fp_code;
+//!
+//! let password = fp_code("test", "github.com", 16).unwrap();
+//! assert_eq!(password, "D04175F7A9c7Ab4a");
+//! # }
+//! ```
+//!
+//! # Schemes
+//!
+//! Two derivation schemes can be compiled in, each behind its own feature:
+//!
+//! - `scheme-v1-md5` (default): the classic HMAC-MD5 scheme.
+//! - `scheme-v2-sha256`: the same transformation over HMAC-SHA256, see
+//!   `fp_code_v2`. Its passwords differ from v1 ones.
+//!
+//! Without v1 nothing depends on or contains MD5. At least one scheme is
+//! needed; a build with neither fails with a `compile_error!` naming both
+//! features. These items exist in each combination:
+//!
+//! | Features | Items |
+//! |----------|-------|
+//! | v1 | `fp_code`, `fp_code_into`, `fp_verify`, `fp_fingerprint`, `KeyStream`, and `fp_code_heapless`, `fp_code_batch_simd` and `fp_code_tiny` with their features |
+//! | v2 | `fp_code_v2`, `fp_verify_v2` |
+//! | v1 and v2 | both of the above |
+//! | neither | none: the build fails |
+//!
+//! Every build has [`fp_code_default`], [`Scheme`], [`DEFAULT_SCHEME`],
+//! [`validate_length`], [`FlowerPasswordError`] and the length and fingerprint
+//! constants. The default scheme is v1 when it is compiled in, unless the
+//! `default-scheme-v2` feature picks v2; without v1 it is v2. The `heapless`,
+//! `simd`, `tiny`, `rustcrypto`, `vendored-md5` and `legacy-md5` features are
+//! v1 backends and turn `scheme-v1-md5` on.
+//!
+//! A build without MD5 needs `default-features = false`:
+//!
+//! ```toml
+//! flowerpassword-core = { version = "1", default-features = false, features = ["std", "scheme-v2-sha256"] }
+//! ```
+
+#![no_std]
+
+#[cfg(not(any(feature = "scheme-v1-md5", feature = "scheme-v2-sha256")))]
+compile_error!(
+    "flowerpassword-core needs a derivation scheme: enable `scheme-v1-md5` for `fp_code`, \
+     or `scheme-v2-sha256` for `fp_code_v2`"
+);
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+use alloc::string::String;
+#[cfg(all(feature = "scheme-v1-md5", test))]
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(all(feature = "rustcrypto", not(feature = "vendored-md5")))]
+use rustcrypto::{HmacMd5, Md5};
+#[cfg(all(
+    feature = "scheme-v1-md5",
+    any(feature = "vendored-md5", not(feature = "rustcrypto"))
+))]
+use vendored::{HmacMd5, Md5};
+#[cfg(all(
+    feature = "scheme-v1-md5",
+    any(
+        feature = "simd",
+        feature = "vendored-md5",
+        not(feature = "rustcrypto")
+    )
+))]
+use wipe::wipe_words;
+use wipe::{wipe, Wiped};
+
+#[cfg(feature = "legacy-md5")]
+mod legacy;
+#[cfg(all(feature = "rustcrypto", not(feature = "vendored-md5")))]
+mod rustcrypto;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "heapless")]
+mod stack;
+#[cfg(feature = "scheme-v1-md5")]
+mod streaming;
+#[cfg(feature = "tiny")]
+mod tiny;
+#[cfg(feature = "scheme-v2-sha256")]
+mod v2;
+#[cfg(all(
+    feature = "scheme-v1-md5",
+    any(
+        feature = "simd",
+        feature = "vendored-md5",
+        not(feature = "rustcrypto")
+    )
+))]
+mod vendored;
+mod wipe;
+
+#[cfg(all(feature = "scheme-v1-md5", not(feature = "legacy-md5")))]
+use hmac_md5_hex as hmac_md5;
+#[cfg(feature = "legacy-md5")]
+use legacy::hmac_md5;
+
+#[cfg(feature = "simd")]
+pub use simd::fp_code_batch_simd;
+#[cfg(feature = "heapless")]
+pub use stack::fp_code_heapless;
+#[cfg(feature = "scheme-v1-md5")]
+pub use streaming::KeyStream;
+#[cfg(feature = "tiny")]
+pub use tiny::{fp_code_tiny, FpErrorCode};
+#[cfg(feature = "scheme-v2-sha256")]
+pub use v2::{fp_code_v2, fp_verify_v2};
+
+/// Minimum valid password length
+pub const MIN_LENGTH: usize = 2;
+
+/// Maximum valid password length
+pub const MAX_LENGTH: usize = 32;
+
+/// Key that [`fp_fingerprint`] derives under
+pub const FINGERPRINT_KEY: &str = "flowerpassword/fingerprint";
+
+/// Length of a master password fingerprint
+pub const FINGERPRINT_LENGTH: usize = 6;
+
+/// Magic string used for character transformation rules
+/// This is part of the Flower Password algorithm specification
+const MAGIC_STRING: &str = "sunlovesnow1990090127xykab";
+
+/// MD5 hash length in hexadecimal characters
+const MD5_HEX_LENGTH: usize = 32;
+
+/// Error type for Flower Password operations
+#[derive(Debug, Clone)]
+pub enum FlowerPasswordError {
+    /// Length parameter is outside the valid range
+    InvalidLength(usize),
+}
+
+impl fmt::Display for FlowerPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlowerPasswordError::InvalidLength(len) => {
+                write!(
+                    f,
+                    "Length must be between {} and {}, got: {}",
+                    MIN_LENGTH, MAX_LENGTH, len
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FlowerPasswordError {}
+
+/// Lowercase hex digits
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encodes an MD5 digest as lowercase hex into `out`
+#[cfg(feature = "scheme-v1-md5")]
+#[inline]
+fn hex_lower(digest: &[u8; 16], out: &mut [u8; MD5_HEX_LENGTH]) {
+    for (pair, byte) in out.chunks_exact_mut(2).zip(digest) {
+        pair[0] = HEX_DIGITS[usize::from(byte >> 4)];
+        pair[1] = HEX_DIGITS[usize::from(byte & 0x0f)];
+    }
+}
+
+/// Returns an MD5 digest as lowercase hex, via [`hex_lower`]
+#[cfg(feature = "scheme-v1-md5")]
+#[inline]
+fn to_hex(digest: &[u8; 16]) -> [u8; MD5_HEX_LENGTH] {
+    let mut hex = [0u8; MD5_HEX_LENGTH];
+    hex_lower(digest, &mut hex);
+    hex
+}
+
+/// Which ASCII bytes occur in `MAGIC_STRING`, as bit `byte % 32` of word `byte / 32`
+///
+/// The words are combined with masks and shifted in registers. A byte table
+/// indexed by a secret would be read from memory, and the dudect-style test below
+/// measures a difference in that even with the whole table in cache. A `u128`
+/// set is no better: 32-bit targets shift it through an indexed stack copy.
+const MAGIC_BITS: [u32; 4] = magic_bits();
+
+const fn magic_bits() -> [u32; 4] {
+    let magic = MAGIC_STRING.as_bytes();
+    let mut bits = [0; 4];
+    let mut i = 0;
+    while i < magic.len() {
+        bits[(magic[i] >> 5) as usize] |= 1 << (magic[i] & 31);
+        i += 1;
+    }
+    bits
+}
+
+/// Returns `0xff` if `byte` occurs in `MAGIC_STRING`, otherwise `0`
+///
+/// Every word of the set is read whatever the byte, so nothing branches on it or
+/// indexes memory with it. Non-ASCII bytes select no word.
+#[inline]
+fn magic_mask(byte: u8) -> u8 {
+    let word = u32::from(byte >> 5);
+    let mut bits = 0;
+    for (i, &set) in (0u32..).zip(&MAGIC_BITS) {
+        // All ones when `word == i`: only zero wraps to a set top bit
+        bits |= set & ((word ^ i).wrapping_sub(1) >> 31).wrapping_neg();
+    }
+    ((bits >> (byte & 31)) as u8 & 1).wrapping_neg()
+}
+
+/// Returns `0xff` if `byte` is an ASCII digit, otherwise `0`, without branching
+#[inline]
+fn digit_mask(byte: u8) -> u8 {
+    // Below 10 the subtraction wraps and sets the high byte
+    (u16::from(byte.wrapping_sub(b'0')).wrapping_sub(10) >> 8) as u8
+}
+
+/// Computes HMAC-MD5 as lowercase hex
+///
+/// This matches the behavior of blueimp-md5's two-parameter mode used in the
+/// JavaScript implementation, including its special case: when key is empty,
+/// the result is the plain MD5 hash of the message, not an HMAC.
+///
+/// Everything stays on the stack, and the raw digest is wiped once encoded.
+#[cfg(feature = "scheme-v1-md5")]
+#[cfg_attr(all(feature = "legacy-md5", not(feature = "simd")), allow(dead_code))]
+#[inline]
+fn hmac_md5_hex(message: &[u8], key: &[u8]) -> [u8; MD5_HEX_LENGTH] {
+    // Special case: empty key returns regular MD5 (matching blueimp-md5 behavior)
+    if key.is_empty() {
+        return to_hex(&Wiped(Md5::digest(message)));
+    }
+
+    let mut mac = HmacMd5::new(key);
+    mac.update(message);
+    to_hex(&Wiped(mac.finalize()))
+}
+
+/// Salt of the rule hash
+const RULE_SALT: &[u8] = b"kise";
+
+/// Salt of the source hash
+const SOURCE_SALT: &[u8] = b"snow";
+
+/// Returns HMAC states keyed with [`RULE_SALT`] and [`SOURCE_SALT`]
+///
+/// Keying compresses the padded inner and outer keys, two of the four MD5
+/// compressions of each salted HMAC. With `std` that happens once per process and
+/// every call clones the cached states.
+#[cfg(feature = "scheme-v1-md5")]
+#[cfg_attr(feature = "legacy-md5", allow(dead_code))]
+fn salt_macs() -> [HmacMd5; 2] {
+    fn new() -> [HmacMd5; 2] {
+        [RULE_SALT, SOURCE_SALT].map(HmacMd5::new)
+    }
+
+    #[cfg(feature = "std")]
+    {
+        static MACS: std::sync::OnceLock<[HmacMd5; 2]> = std::sync::OnceLock::new();
+        MACS.get_or_init(new).clone()
+    }
+    #[cfg(not(feature = "std"))]
+    new()
+}
+
+/// Computes the rule and source hashes from the base hash, as lowercase hex
+///
+/// Equivalent to `hmac_md5_hex` with each salt, but starts from the prekeyed
+/// states of [`salt_macs`].
+#[cfg(feature = "scheme-v1-md5")]
+#[cfg_attr(feature = "legacy-md5", allow(dead_code))]
+#[inline]
+fn salted_hashes(base_hash: &[u8; MD5_HEX_LENGTH]) -> [[u8; MD5_HEX_LENGTH]; 2] {
+    salt_macs().map(|mut mac| {
+        mac.update(base_hash);
+        to_hex(&Wiped(mac.finalize()))
+    })
+}
+
+/// Checks that `length` is a valid password length
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between
+/// [`MIN_LENGTH`] and [`MAX_LENGTH`].
+pub fn validate_length(length: usize) -> Result<(), FlowerPasswordError> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        Err(FlowerPasswordError::InvalidLength(length))
+    } else {
+        Ok(())
+    }
+}
+
+/// Compares two strings without exiting early on the first difference
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Returns character `index` of the password for the given rule and source hashes
+///
+/// Letters whose rule character appears in the magic string are uppercased, and
+/// a leading digit is replaced with `K` so the password always starts with a
+/// letter. Both hashes are lowercase hex, so this works on bytes throughout.
+///
+/// Every choice is a bitwise select on masks rather than a branch, so the time
+/// taken does not depend on the hashes.
+#[inline]
+fn password_byte(
+    index: usize,
+    rule_hash: &[u8; MD5_HEX_LENGTH],
+    source_hash: &[u8; MD5_HEX_LENGTH],
+) -> u8 {
+    let ch = source_hash[index];
+    let digit = digit_mask(ch);
+    // Clearing 0x20 uppercases the lowercase hex letters
+    let upper = magic_mask(rule_hash[index]) & !digit;
+    let ch = ch & !(upper & 0x20);
+    let lead = digit & 0u8.wrapping_sub(u8::from(index == 0));
+    (b'K' & lead) | (ch & !lead)
+}
+
+/// Core algorithm to generate Flower Password from MD5 hashes
+///
+/// Makes a single pass over the first `length` bytes and allocates only the
+/// result.
+#[cfg(all(feature = "scheme-v1-md5", any(feature = "simd", test)))]
+fn generate_password(
+    rule_hash: &[u8; MD5_HEX_LENGTH],
+    source_hash: &[u8; MD5_HEX_LENGTH],
+    length: usize,
+) -> String {
+    let mut result = String::with_capacity(length);
+    for index in 0..length {
+        result.push(char::from(password_byte(index, rule_hash, source_hash)));
+    }
+    result
+}
+
+/// Generates a Flower Password based on master password and key
+///
+/// # Arguments
+///
+/// * `password` - Master password
+/// * `key` - Domain or service identifier
+/// * `length` - Output password length (2-32 characters)
+///
+/// # Returns
+///
+/// Returns `Ok(String)` with the generated password, or `Err(FlowerPasswordError)`
+/// if the length is invalid.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_code;
+///
+/// let password = fp_code("test", "github.com", 16).unwrap();
+/// assert_eq!(password, "D04175F7A9c7Ab4a");
+/// ```
+#[cfg(feature = "scheme-v1-md5")]
+pub fn fp_code(password: &str, key: &str, length: usize) -> Result<String, FlowerPasswordError> {
+    validate_length(length)?;
+    Ok(derive_string(password.as_bytes(), key.as_bytes(), length))
+}
+
+/// Writes the Flower Password for master password and key into `out`
+///
+/// The password length is `out.len()`, and each character is written as one
+/// ASCII byte: the same characters `fp_code(password, key, out.len())` returns,
+/// without allocating a `String`.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if `out.len()` is not between 2
+/// and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_code_into;
+///
+/// let mut password = [0u8; 16];
+/// fp_code_into("test", "github.com", &mut password).unwrap();
+/// assert_eq!(&password, b"D04175F7A9c7Ab4a");
+/// ```
+#[cfg(feature = "scheme-v1-md5")]
+pub fn fp_code_into(password: &str, key: &str, out: &mut [u8]) -> Result<(), FlowerPasswordError> {
+    validate_length(out.len())?;
+    derive_into(
+        password.as_bytes(),
+        key.as_bytes(),
+        out,
+        &mut Scratch::default(),
+    );
+    Ok(())
+}
+
+/// The intermediate hashes of one derivation
+///
+/// [`derive_into`] fills one of these on its caller's stack and wipes it before
+/// returning, so no hash of the master password outlives the derivation in the
+/// buffers this crate owns. The HMAC and MD5 states inside `hmac` and `md-5` are
+/// out of its reach; the in-tree MD5 of `vendored-md5` wipes its own.
+#[cfg(feature = "scheme-v1-md5")]
+#[derive(Default)]
+struct Scratch {
+    base: [u8; MD5_HEX_LENGTH],
+    /// The rule and source hashes
+    salted: [[u8; MD5_HEX_LENGTH]; 2],
+}
+
+#[cfg(feature = "scheme-v1-md5")]
+impl Scratch {
+    fn wipe(&mut self) {
+        wipe(&mut self.base);
+        for hash in &mut self.salted {
+            wipe(hash);
+        }
+    }
+}
+
+/// Writes the password for master password and key into `out`, then wipes `scratch`
+///
+/// `out.len()` must already be a valid length. Every derivation path that is not
+/// batched goes through here.
+#[cfg(feature = "scheme-v1-md5")]
+fn derive_into(password: &[u8], key: &[u8], out: &mut [u8], scratch: &mut Scratch) {
+    // Generate base MD5 hash from password and key using HMAC
+    scratch.base = hmac_md5(password, key);
+
+    // Generate rule and source hashes using fixed salts
+    #[cfg(not(feature = "legacy-md5"))]
+    {
+        scratch.salted = salted_hashes(&scratch.base);
+    }
+    #[cfg(feature = "legacy-md5")]
+    {
+        scratch.salted = [RULE_SALT, SOURCE_SALT].map(|salt| hmac_md5(&scratch.base, salt));
+    }
+
+    let [rule_hash, source_hash] = &scratch.salted;
+    for (index, byte) in out.iter_mut().enumerate() {
+        *byte = password_byte(index, rule_hash, source_hash);
+    }
+    scratch.wipe();
+}
+
+/// Derives the password as a `String`, wiping every buffer but the result
+///
+/// `length` must already be valid.
+#[cfg(feature = "scheme-v1-md5")]
+fn derive_string(password: &[u8], key: &[u8], length: usize) -> String {
+    let mut out = Wiped([0; MAX_LENGTH]);
+    derive_into(password, key, &mut out[..length], &mut Scratch::default());
+    String::from(core::str::from_utf8(&out[..length]).expect("passwords are ASCII"))
+}
+
+/// Checks whether `candidate` is the Flower Password for master password and key
+///
+/// The length is taken from `candidate` itself, so a password generated at any
+/// valid length verifies. Candidates of an invalid length never match, and the
+/// comparison does not exit early on the first differing character.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_verify;
+///
+/// assert!(fp_verify("test", "github.com", "D04175F7A9c7Ab4a"));
+/// assert!(!fp_verify("test", "gitlab.com", "D04175F7A9c7Ab4a"));
+/// ```
+#[cfg(feature = "scheme-v1-md5")]
+pub fn fp_verify(password: &str, key: &str, candidate: &str) -> bool {
+    match fp_code(password, key, candidate.len()) {
+        Ok(expected) => constant_time_eq(&expected, candidate),
+        Err(FlowerPasswordError::InvalidLength(_)) => false,
+    }
+}
+
+/// Returns a short fingerprint of a master password, to catch typos in it
+///
+/// The fingerprint is the [`FINGERPRINT_LENGTH`]-character password for
+/// [`FINGERPRINT_KEY`], so it is as hard to reverse as any derived password, and
+/// too short to be worth using as one. Two master passwords share one only by
+/// rare chance.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_fingerprint;
+///
+/// assert_eq!(fp_fingerprint("test"), fp_fingerprint("test"));
+/// assert_ne!(fp_fingerprint("test"), fp_fingerprint("tesst"));
+/// ```
+#[cfg(feature = "scheme-v1-md5")]
+pub fn fp_fingerprint(password: &str) -> String {
+    derive_string(
+        password.as_bytes(),
+        FINGERPRINT_KEY.as_bytes(),
+        FINGERPRINT_LENGTH,
+    )
+}
+
+/// A derivation scheme compiled into this build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scheme {
+    /// The classic HMAC-MD5 scheme of `fp_code`
+    #[cfg(feature = "scheme-v1-md5")]
+    V1,
+    /// The HMAC-SHA256 scheme of `fp_code_v2`
+    #[cfg(feature = "scheme-v2-sha256")]
+    V2,
+}
+
+impl Scheme {
+    /// Returns the name of the scheme, `v1` or `v2`
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "scheme-v1-md5")]
+            Scheme::V1 => "v1",
+            #[cfg(feature = "scheme-v2-sha256")]
+            Scheme::V2 => "v2",
+        }
+    }
+
+    /// Returns the scheme named `name`, as [`Scheme::name`] gives it, or `None`
+    /// if there is no such scheme or it is not compiled into this build
+    pub fn from_name(name: &str) -> Option<Scheme> {
+        match name {
+            #[cfg(feature = "scheme-v1-md5")]
+            "v1" => Some(Scheme::V1),
+            #[cfg(feature = "scheme-v2-sha256")]
+            "v2" => Some(Scheme::V2),
+            _ => None,
+        }
+    }
+
+    /// Generates a password with this scheme
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2
+    /// and 32.
+    pub fn fp_code(
+        self,
+        password: &str,
+        key: &str,
+        length: usize,
+    ) -> Result<String, FlowerPasswordError> {
+        match self {
+            #[cfg(feature = "scheme-v1-md5")]
+            Scheme::V1 => fp_code(password, key, length),
+            #[cfg(feature = "scheme-v2-sha256")]
+            Scheme::V2 => fp_code_v2(password, key, length),
+        }
+    }
+}
+
+/// The scheme of [`fp_code_default`]: v1, unless `default-scheme-v2` is enabled
+#[cfg(all(feature = "scheme-v1-md5", not(feature = "default-scheme-v2")))]
+pub const DEFAULT_SCHEME: Scheme = Scheme::V1;
+
+/// The scheme of [`fp_code_default`]: v2, as `default-scheme-v2` is enabled or
+/// v1 is not compiled in
+#[cfg(all(
+    feature = "scheme-v2-sha256",
+    any(feature = "default-scheme-v2", not(feature = "scheme-v1-md5"))
+))]
+pub const DEFAULT_SCHEME: Scheme = Scheme::V2;
+
+/// Generates a password with [`DEFAULT_SCHEME`]
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::{fp_code_default, DEFAULT_SCHEME};
+///
+/// let password = fp_code_default("test", "github.com", 16).unwrap();
+/// assert_eq!(password, DEFAULT_SCHEME.fp_code("test", "github.com", 16).unwrap());
+/// ```
+#[cfg(any(feature = "scheme-v1-md5", feature = "scheme-v2-sha256"))]
+pub fn fp_code_default(
+    password: &str,
+    key: &str,
+    length: usize,
+) -> Result<String, FlowerPasswordError> {
+    DEFAULT_SCHEME.fp_code(password, key, length)
+}
+
+/// The original `char`-based transform, kept as a reference for
+/// `generate_password`
+#[cfg(all(feature = "scheme-v1-md5", test))]
+fn generate_password_reference(rule_hash: &str, source_hash: &str, length: usize) -> String {
+    let rule_chars: Vec<char> = rule_hash.chars().collect();
+    let mut source_chars: Vec<char> = source_hash.chars().collect();
+
+    // Apply transformation rules: uppercase letters based on magic string pattern
+    for i in 0..MD5_HEX_LENGTH {
+        let ch = source_chars[i];
+        // Check if character is a letter (not a digit)
+        if !ch.is_ascii_digit() {
+            // Check if rule character exists in magic string
+            if MAGIC_STRING.contains(rule_chars[i]) {
+                source_chars[i] = ch.to_ascii_uppercase();
+            }
+        }
+    }
+
+    let transformed_hash: String = source_chars.iter().collect();
+    let first_char = transformed_hash.chars().next().unwrap();
+
+    // Ensure first character is always a letter (replace with 'K' if it's a digit)
+    let first = if first_char.is_ascii_digit() {
+        'K'
+    } else {
+        first_char
+    };
+
+    // Build the final password
+    let mut result = String::with_capacity(length);
+    result.push(first);
+    result.push_str(&transformed_hash[1..length]);
+
+    result
+}
+
+/// Internals exposed for the `flowerpassword` criterion benches; not part of the
+/// public API
+#[cfg(feature = "scheme-v1-md5")]
+#[doc(hidden)]
+pub mod bench {
+    use crate::MD5_HEX_LENGTH;
+
+    /// The HMAC-MD5 that `fp_code` uses, as lowercase hex
+    pub fn hmac_md5(message: &[u8], key: &[u8]) -> [u8; MD5_HEX_LENGTH] {
+        crate::hmac_md5(message, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::alloc::{GlobalAlloc, Layout};
+    use core::cell::Cell;
+    use std::alloc::System;
+
+    /// xorshift64, to generate reproducible inputs without a dependency
+    pub(crate) struct XorShift(pub(crate) u64);
+
+    impl XorShift {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A string of up to `max_chars` characters, mixing ASCII and multi-byte ones
+        #[cfg(feature = "heapless")]
+        pub(crate) fn string(&mut self, max_chars: u64) -> String {
+            let count = self.next() % (max_chars + 1);
+            (0..count)
+                .map(|_| match self.next() % 4 {
+                    0 => '密',
+                    1 => '🌸',
+                    _ => char::from(b' ' + (self.next() % 95) as u8),
+                })
+                .collect()
+        }
+
+        /// A random lowercase hex digest
+        #[cfg(feature = "scheme-v1-md5")]
+        fn hex(&mut self) -> [u8; MD5_HEX_LENGTH] {
+            let mut hex = [0; MD5_HEX_LENGTH];
+            for ch in &mut hex {
+                *ch = b"0123456789abcdef"[(self.next() % 16) as usize];
+            }
+            hex
+        }
+    }
+
+    std::thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+        static FREED_SECRETS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Marker that the wiping tests put in secrets, to look for in freed memory
+    pub(crate) const SECRET_MARKER: &[u8] = b"wipe-me-3f6a2c";
+
+    /// Counts the allocations made on each thread, so parallel tests do not interfere,
+    /// and the freed blocks that still contain [`SECRET_MARKER`]
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let block = core::slice::from_raw_parts(ptr, layout.size());
+            if block
+                .windows(SECRET_MARKER.len())
+                .any(|window| window == SECRET_MARKER)
+            {
+                let _ = FREED_SECRETS.try_with(|count| count.set(count.get() + 1));
+            }
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Returns how many allocations `f` makes on this thread
+    pub(crate) fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let value = f();
+        (ALLOCATIONS.with(Cell::get) - before, value)
+    }
+
+    /// Returns how many blocks `f` frees on this thread with [`SECRET_MARKER`] still in them
+    pub(crate) fn freed_secrets(f: impl FnOnce()) -> usize {
+        let before = FREED_SECRETS.with(Cell::get);
+        f();
+        FREED_SECRETS.with(Cell::get) - before
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_known_answers() {
+        assert_eq!(fp_code("password", "key", 16).unwrap(), "K3A2a66Bf88b628c");
+        assert_eq!(
+            fp_code("test", "github.com", 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(
+            fp_code("password", "key", 32).unwrap(),
+            "K3A2a66Bf88b628c2Cd7cDA9958f6b26"
+        );
+    }
+
+    /// HMAC-MD5 (message, key, hex) vectors: RFC 2104, RFC 2202's longer-than-block
+    /// keys, and the empty key, which falls back to plain MD5
+    #[cfg(feature = "scheme-v1-md5")]
+    pub(crate) const HMAC_VECTORS: &[(&[u8], &[u8], &str)] = &[
+        (b"Hi There", &[0x0b; 16], "9294727a3638bb1c13f48ef8158bfc9d"),
+        (
+            b"what do ya want for nothing?",
+            b"Jefe",
+            "750c783e6ab0b503eaa86e310a5db738",
+        ),
+        (&[0xdd; 50], &[0xaa; 16], "56be34521d144c88dbb8c733f0e8b3f6"),
+        (
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            &[0xaa; 80],
+            "6b1ab7fe4bd7bf8f0b62e6ce61b9d0cd",
+        ),
+        (
+            b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data",
+            &[0xaa; 80],
+            "6f630fad67cda0ee1fb1f562db3aa53e",
+        ),
+        (b"", b"", "d41d8cd98f00b204e9800998ecf8427e"),
+        (b"abc", b"", "900150983cd24fb0d6963f7d28e17f72"),
+    ];
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_hmac_md5_vectors() {
+        for &(message, key, expected) in HMAC_VECTORS {
+            assert_eq!(
+                &hmac_md5_hex(message, key),
+                expected.as_bytes(),
+                "key {:?}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_salted_hashes() {
+        let mut rng = XorShift(0x6a09_e667_f3bc_c908);
+        for _ in 0..100 {
+            let base_hash = rng.hex();
+            // Twice, so the cached states are used as well as created
+            for _ in 0..2 {
+                assert_eq!(
+                    salted_hashes(&base_hash),
+                    [
+                        hmac_md5_hex(&base_hash, RULE_SALT),
+                        hmac_md5_hex(&base_hash, SOURCE_SALT)
+                    ]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_length() {
+        assert!(validate_length(MIN_LENGTH).is_ok());
+        assert!(validate_length(MAX_LENGTH).is_ok());
+        assert!(matches!(
+            validate_length(MIN_LENGTH - 1),
+            Err(FlowerPasswordError::InvalidLength(1))
+        ));
+        assert!(matches!(
+            validate_length(MAX_LENGTH + 1),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_fp_code_into() {
+        for length in MIN_LENGTH..=MAX_LENGTH {
+            let mut out = [b'#'; MAX_LENGTH + 1];
+            fp_code_into("password", "key", &mut out[..length]).unwrap();
+            assert_eq!(
+                &out[..length],
+                fp_code("password", "key", length).unwrap().as_bytes()
+            );
+            // Nothing past the end is touched
+            assert!(out[length..].iter().all(|&byte| byte == b'#'));
+        }
+        for length in [0, 1, 33] {
+            assert!(matches!(
+                fp_code_into("password", "key", &mut [0; 33][..length]),
+                Err(FlowerPasswordError::InvalidLength(l)) if l == length
+            ));
+        }
+    }
+
+    #[test]
+    fn test_masks() {
+        for byte in 0..=u8::MAX {
+            let magic = byte.is_ascii() && MAGIC_STRING.contains(char::from(byte));
+            assert_eq!(magic_mask(byte), if magic { 0xff } else { 0 }, "{}", byte);
+            let digit = byte.is_ascii_digit();
+            assert_eq!(digit_mask(byte), if digit { 0xff } else { 0 }, "{}", byte);
+        }
+    }
+
+    #[test]
+    fn test_password_byte_matches_branching_rule() {
+        for &rule in HEX_DIGITS {
+            for &ch in HEX_DIGITS {
+                for index in [0, 1, 31] {
+                    let expected = if ch.is_ascii_digit() {
+                        if index == 0 {
+                            b'K'
+                        } else {
+                            ch
+                        }
+                    } else if MAGIC_STRING.contains(char::from(rule)) {
+                        ch.to_ascii_uppercase()
+                    } else {
+                        ch
+                    };
+                    let actual =
+                        password_byte(index, &[rule; MD5_HEX_LENGTH], &[ch; MD5_HEX_LENGTH]);
+                    assert_eq!(actual, expected, "{} {} {}", rule, ch, index);
+                }
+            }
+        }
+    }
+
+    /// Welch's t-statistic between the timings of two input classes, cropped at
+    /// the 90th percentile of both to drop interrupts and migrations
+    fn welch_t(mut timings: [std::vec::Vec<u64>; 2]) -> f64 {
+        let mut all: std::vec::Vec<u64> = timings.concat();
+        all.sort_unstable();
+        let crop = all[all.len() * 9 / 10];
+        let [a, b] = [0, 1].map(|class| {
+            let class = &mut timings[class];
+            class.retain(|&t| t <= crop);
+            let n = class.len() as f64;
+            let mean = class.iter().sum::<u64>() as f64 / n;
+            let var = class
+                .iter()
+                .map(|&t| (t as f64 - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0);
+            (n, mean, var)
+        });
+        (a.1 - b.1) / (a.2 / a.0 + b.2 / b.0).sqrt()
+    }
+
+    /// Times `transform` over a fixed pair of hashes against random ones, dudect style
+    fn timing_t(transform: fn(&[u8; MD5_HEX_LENGTH], &[u8; MD5_HEX_LENGTH]) -> u8) -> f64 {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        let mut rng = XorShift(0x1f83_d9ab_fb41_bd6b);
+        let hex =
+            |rng: &mut XorShift| core::array::from_fn(|_| HEX_DIGITS[(rng.next() & 15) as usize]);
+        // Magic rule characters and letters everywhere: the class that takes the
+        // uppercasing path at every position
+        let fixed = ([b'a'; MD5_HEX_LENGTH], [b'b'; MD5_HEX_LENGTH]);
+        let mut timings = [std::vec::Vec::new(), std::vec::Vec::new()];
+        let mut inputs = std::vec::Vec::new();
+        for _ in 0..400_000 {
+            let class = (rng.next() & 1) as usize;
+            inputs.push((
+                class,
+                if class == 0 {
+                    fixed
+                } else {
+                    (hex(&mut rng), hex(&mut rng))
+                },
+            ));
+        }
+        for (class, (rule, source)) in &inputs {
+            let (rule, source) = (black_box(rule), black_box(source));
+            let start = Instant::now();
+            for _ in 0..8 {
+                black_box(transform(rule, source));
+            }
+            timings[*class].push(start.elapsed().as_nanos() as u64);
+        }
+        welch_t(timings)
+    }
+
+    #[test]
+    #[ignore = "statistical timing test; run with `cargo test --release -- --ignored`"]
+    fn test_transformation_is_constant_time() {
+        fn branch_free(rule: &[u8; MD5_HEX_LENGTH], source: &[u8; MD5_HEX_LENGTH]) -> u8 {
+            (0..MD5_HEX_LENGTH).fold(0, |acc, i| acc ^ password_byte(i, rule, source))
+        }
+        /// The transformation as it was written before, branching on the hashes
+        fn branching(rule: &[u8; MD5_HEX_LENGTH], source: &[u8; MD5_HEX_LENGTH]) -> u8 {
+            (0..MD5_HEX_LENGTH).fold(0, |acc, i| {
+                let ch = source[i];
+                let byte = if ch.is_ascii_digit() {
+                    if i == 0 {
+                        b'K'
+                    } else {
+                        ch
+                    }
+                } else if MAGIC_STRING.as_bytes().contains(&rule[i]) {
+                    ch.to_ascii_uppercase()
+                } else {
+                    ch
+                };
+                acc ^ byte
+            })
+        }
+
+        // The branching version is only a reference point: the compiler may make
+        // it branch-free too, so it is reported rather than asserted on
+        std::eprintln!("branching t = {:.1}", timing_t(branching));
+        let t = timing_t(branch_free);
+        std::eprintln!("branch-free t = {:.1}", t);
+        // dudect treats |t| above 10 as a definite leak
+        assert!(t.abs() < 10.0, "t = {:.1}", t);
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_hex_lower() {
+        let mut rng = XorShift(0x3c6e_f372_fe94_f82b);
+        let digests = [[0; 16], [0xff; 16]]
+            .into_iter()
+            .chain((0..1000).map(|_| core::array::from_fn(|_| rng.next() as u8)));
+        for digest in digests {
+            let expected: String = digest.iter().map(|b| alloc::format!("{:02x}", b)).collect();
+            let mut out = [0; MD5_HEX_LENGTH];
+            hex_lower(&digest, &mut out);
+            assert_eq!(out, expected.as_bytes());
+            assert_eq!(to_hex(&digest), out);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_verify() {
+        assert!(fp_verify("password", "key", "K3A2a66Bf88b628c"));
+        assert!(!fp_verify("password", "key", "K3A2a66Bf88b628C"));
+        assert!(!fp_verify("password", "key", "K"));
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_fingerprint() {
+        let fingerprint = fp_fingerprint("test");
+        assert_eq!(fingerprint.len(), FINGERPRINT_LENGTH);
+        assert_eq!(
+            fingerprint,
+            fp_code("test", FINGERPRINT_KEY, FINGERPRINT_LENGTH).unwrap()
+        );
+        assert_ne!(fingerprint, fp_fingerprint("Test"));
+    }
+
+    #[test]
+    fn test_default_scheme() {
+        let (name, password) = match DEFAULT_SCHEME {
+            #[cfg(feature = "scheme-v1-md5")]
+            Scheme::V1 => ("v1", "D04175F7A9c7Ab4a"),
+            #[cfg(feature = "scheme-v2-sha256")]
+            Scheme::V2 => ("v2", "K4Ab3e328cA49fE8"),
+        };
+        let v1_default = cfg!(all(
+            feature = "scheme-v1-md5",
+            not(feature = "default-scheme-v2")
+        ));
+        assert_eq!(name, if v1_default { "v1" } else { "v2" });
+        assert_eq!(DEFAULT_SCHEME.name(), name);
+        assert_eq!(Scheme::from_name(name), Some(DEFAULT_SCHEME));
+        assert_eq!(Scheme::from_name("V1"), None);
+        assert_eq!(Scheme::from_name("v3"), None);
+        assert_eq!(fp_code_default("test", "github.com", 16).unwrap(), password);
+        assert!(matches!(
+            fp_code_default("test", "github.com", 33),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "scheme-v1-md5", feature = "scheme-v2-sha256"))]
+    fn test_schemes() {
+        assert_eq!(
+            Scheme::V1.fp_code("password", "key", 16).unwrap(),
+            fp_code("password", "key", 16).unwrap()
+        );
+        assert_eq!(
+            Scheme::V2.fp_code("password", "key", 16).unwrap(),
+            fp_code_v2("password", "key", 16).unwrap()
+        );
+        assert_ne!(
+            fp_code("password", "key", 16).unwrap(),
+            fp_code_v2("password", "key", 16).unwrap()
+        );
+        assert_eq!([Scheme::V1.name(), Scheme::V2.name()], ["v1", "v2"]);
+        assert_eq!(Scheme::from_name("v2"), Some(Scheme::V2));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("K3A2", "K3A2"));
+        assert!(!constant_time_eq("K3A2", "K3A3"));
+        assert!(!constant_time_eq("K3A2", "K3A"));
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_generate_password_matches_reference() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5000 {
+            let rule_hash = rng.hex();
+            let source_hash = rng.hex();
+            let rule_str = core::str::from_utf8(&rule_hash).unwrap();
+            let source_str = core::str::from_utf8(&source_hash).unwrap();
+            for length in MIN_LENGTH..=MAX_LENGTH {
+                assert_eq!(
+                    generate_password(&rule_hash, &source_hash, length),
+                    generate_password_reference(rule_str, source_str, length),
+                    "rule {}, source {}, length {}",
+                    rule_str,
+                    source_str,
+                    length
+                );
+            }
+        }
+        // A leading digit, in both the uppercased and untouched cases
+        let rule_hash = *b"s0000000000000000000000000000000";
+        let source_hash = *b"1abcdef000000000000000000000000f";
+        assert_eq!(
+            generate_password(&rule_hash, &source_hash, 8),
+            generate_password_reference(
+                &rule_hash.map(char::from).iter().collect::<String>(),
+                &source_hash.map(char::from).iter().collect::<String>(),
+                8
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_generate_password_allocates_once() {
+        let base_hash = hmac_md5(b"password", b"key");
+        let rule_hash = hmac_md5(&base_hash, RULE_SALT);
+        let source_hash = hmac_md5(&base_hash, SOURCE_SALT);
+        let rule_str = core::str::from_utf8(&rule_hash).unwrap();
+        let source_str = core::str::from_utf8(&source_hash).unwrap();
+
+        let (count, password) = allocations(|| generate_password(&rule_hash, &source_hash, 16));
+        assert_eq!(count, 1);
+        let (reference_count, reference) =
+            allocations(|| generate_password_reference(rule_str, source_str, 16));
+        // At least the two `Vec<char>`, the transformed `String` and the
+        // result, plus any regrowth while collecting
+        assert!(reference_count >= 4, "{}", reference_count);
+        assert_eq!(password, reference);
+        assert_eq!(password, "K3A2a66Bf88b628c");
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_fp_code_allocates_only_the_result() {
+        // The base, rule and source hashes stay in `[u8; 32]` buffers between the
+        // HMAC stages, whichever backend computes them; only the password is a `String`
+        for length in [MIN_LENGTH, 16, MAX_LENGTH] {
+            for (password, key) in [("password", "key"), ("密码", ""), ("", &"k".repeat(100))] {
+                let (count, result) = allocations(|| fp_code(password, key, length));
+                assert_eq!(count, 1, "{} {} {}", password, key, length);
+                assert_eq!(result.unwrap().len(), length);
+            }
+        }
+        let (count, _) = allocations(|| fp_code("password", "key", 33));
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "scheme-v1-md5")]
+    fn test_derive_into_wipes_scratch() {
+        for (password, key) in [("password", "key"), ("密码", ""), ("", &"k".repeat(100))] {
+            let mut scratch = Scratch::default();
+            let mut out = [0; 16];
+            derive_into(password.as_bytes(), key.as_bytes(), &mut out, &mut scratch);
+            assert_eq!(&out, fp_code(password, key, 16).unwrap().as_bytes());
+            assert!(scratch.base.iter().all(|&byte| byte == 0));
+            assert!(scratch.salted.iter().flatten().all(|&byte| byte == 0));
+        }
+
+        // The wipe covers what the derivation wrote: a filled scratch comes back zeroed
+        let mut scratch = Scratch {
+            base: [b'b'; MD5_HEX_LENGTH],
+            salted: [[b's'; MD5_HEX_LENGTH]; 2],
+        };
+        derive_into(b"password", b"key", &mut [0; 2], &mut scratch);
+        assert_eq!(scratch.base, [0; MD5_HEX_LENGTH]);
+        assert_eq!(scratch.salted, [[0; MD5_HEX_LENGTH]; 2]);
+    }
+}
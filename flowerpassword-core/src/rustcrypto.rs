@@ -0,0 +1,56 @@
+//! MD5 and HMAC-MD5 from the RustCrypto `md-5` and `hmac` crates
+//!
+//! Thin wrappers giving the RustCrypto types the same interface as the in-tree
+//! implementation in `vendored`, so the rest of the crate does not depend on
+//! which one the features select.
+
+use hmac::{Hmac, Mac};
+use md5::Digest;
+
+/// An incremental MD5 hash
+#[derive(Clone)]
+pub(crate) struct Md5(md5::Md5);
+
+impl Md5 {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Md5(md5::Md5::new())
+    }
+
+    /// The MD5 digest of `message`
+    #[inline]
+    pub(crate) fn digest(message: &[u8]) -> [u8; 16] {
+        md5::Md5::digest(message).into()
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    #[inline]
+    pub(crate) fn finalize(self) -> [u8; 16] {
+        self.0.finalize().into()
+    }
+}
+
+/// HMAC-MD5 keyed once, so that clones start after the padded keys
+#[derive(Clone)]
+pub(crate) struct HmacMd5(Hmac<md5::Md5>);
+
+impl HmacMd5 {
+    #[inline]
+    pub(crate) fn new(key: &[u8]) -> Self {
+        HmacMd5(Hmac::new_from_slice(key).expect("HMAC takes keys of any length"))
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    #[inline]
+    pub(crate) fn finalize(self) -> [u8; 16] {
+        self.0.finalize().into_bytes().into()
+    }
+}
@@ -0,0 +1,524 @@
+//! Multi-lane MD5 for batch derivation
+//!
+//! [`fp_code_batch_simd`] derives passwords for many keys at once. Every HMAC
+//! step of `fp_code` compresses exactly one block per key, and the keys are
+//! independent, so the compressions of 8 keys (AVX2) or 4 keys (SSE2) run side by
+//! side in the lanes of one vector register. The CPU features are detected at
+//! runtime with `std`, and taken from the compile-time target features without
+//! it. Other architectures, and whatever does not fill a register, use a scalar
+//! MD5, so results never depend on the backend.
+
+use crate::vendored::{
+    compress as compress_scalar, digest, message_index, shift, word, Block, State, BLOCK_SIZE,
+    INIT, K,
+};
+use crate::Md5;
+use crate::{
+    generate_password, hmac_md5_hex, to_hex, validate_length, FlowerPasswordError, MD5_HEX_LENGTH,
+    RULE_SALT, SOURCE_SALT,
+};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// Defines a function compressing one block into each of `$lanes` states, with
+/// the lanes of one `$vec` register holding one state each
+///
+/// The steps are the same as in [`compress_scalar`]; the intrinsics are named
+/// directly so that they inline into the `target_feature` function.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+macro_rules! compress_lanes {
+    (
+        $(#[$attr:meta])*
+        fn $name:ident($lanes:literal x $vec:ty) {
+            load: $load:ident,
+            store: $store:ident,
+            set1: $set1:ident,
+            add: $add:ident,
+            and: $and:ident,
+            andnot: $andnot:ident,
+            or: $or:ident,
+            xor: $xor:ident,
+            sll: $sll:ident,
+            srl: $srl:ident,
+        }
+    ) => {
+        $(#[$attr])*
+        unsafe fn $name(states: &mut [State], blocks: &[Block]) {
+            debug_assert!(states.len() == $lanes && blocks.len() == $lanes);
+
+            // Transpose: register `w` holds message word `w` of every lane
+            let mut m = [$set1(0); 16];
+            for (w, register) in m.iter_mut().enumerate() {
+                let mut lanes = [0u32; $lanes];
+                for (lane, block) in lanes.iter_mut().zip(blocks) {
+                    *lane = word(block, w);
+                }
+                *register = $load(lanes.as_ptr() as *const $vec);
+            }
+            let mut initial = [$set1(0); 4];
+            for (k, register) in initial.iter_mut().enumerate() {
+                let mut lanes = [0u32; $lanes];
+                for (lane, state) in lanes.iter_mut().zip(states.iter()) {
+                    *lane = state[k];
+                }
+                *register = $load(lanes.as_ptr() as *const $vec);
+            }
+
+            let [mut a, mut b, mut c, mut d] = initial;
+            let ones = $set1(-1);
+            for (i, &k) in K.iter().enumerate() {
+                let f = match i / 16 {
+                    0 => $or($and(b, c), $andnot(b, d)),
+                    1 => $or($and(d, b), $andnot(d, c)),
+                    2 => $xor($xor(b, c), d),
+                    _ => $xor(c, $or(b, $xor(d, ones))),
+                };
+                let t = $add($add(a, f), $add($set1(k as i32), m[message_index(i)]));
+                let s = shift(i) as i32;
+                let rotated = $or(
+                    $sll(t, _mm_cvtsi32_si128(s)),
+                    $srl(t, _mm_cvtsi32_si128(32 - s)),
+                );
+                a = d;
+                d = c;
+                c = b;
+                b = $add(b, rotated);
+            }
+
+            for (k, register) in [a, b, c, d].into_iter().enumerate() {
+                let mut lanes = [0u32; $lanes];
+                $store(lanes.as_mut_ptr() as *mut $vec, register);
+                for (state, lane) in states.iter_mut().zip(lanes) {
+                    state[k] = state[k].wrapping_add(lane);
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+compress_lanes! {
+    /// # Safety
+    ///
+    /// The CPU must support SSE2.
+    #[target_feature(enable = "sse2")]
+    fn compress_sse2(4 x __m128i) {
+        load: _mm_loadu_si128,
+        store: _mm_storeu_si128,
+        set1: _mm_set1_epi32,
+        add: _mm_add_epi32,
+        and: _mm_and_si128,
+        andnot: _mm_andnot_si128,
+        or: _mm_or_si128,
+        xor: _mm_xor_si128,
+        sll: _mm_sll_epi32,
+        srl: _mm_srl_epi32,
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+compress_lanes! {
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    fn compress_avx2(8 x __m256i) {
+        load: _mm256_loadu_si256,
+        store: _mm256_storeu_si256,
+        set1: _mm256_set1_epi32,
+        add: _mm256_add_epi32,
+        and: _mm256_and_si256,
+        andnot: _mm256_andnot_si256,
+        or: _mm256_or_si256,
+        xor: _mm256_xor_si256,
+        sll: _mm256_sll_epi32,
+        srl: _mm256_srl_epi32,
+    }
+}
+
+/// Whether the CPU supports `$feature`: detected at runtime with `std`, and
+/// from the compile-time target features without it
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+macro_rules! has_feature {
+    ($feature:tt) => {{
+        #[cfg(feature = "std")]
+        let detected = std::is_x86_feature_detected!($feature);
+        #[cfg(not(feature = "std"))]
+        let detected = cfg!(target_feature = $feature);
+        detected
+    }};
+}
+
+/// How compressions are spread over lanes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// One state at a time
+    Scalar,
+    /// Four states per SSE2 register
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse2,
+    /// Eight states per AVX2 register
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+}
+
+impl Backend {
+    /// Every backend this CPU supports, widest last
+    fn available() -> Vec<Backend> {
+        #[allow(unused_mut)]
+        let mut backends = vec![Backend::Scalar];
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if has_feature!("sse2") {
+                backends.push(Backend::Sse2);
+            }
+            if has_feature!("avx2") {
+                backends.push(Backend::Avx2);
+            }
+        }
+        backends
+    }
+
+    /// The widest backend this CPU supports
+    fn detect() -> Backend {
+        *Backend::available().last().unwrap_or(&Backend::Scalar)
+    }
+
+    /// States compressed together
+    fn lanes(self) -> usize {
+        match self {
+            Backend::Scalar => 1,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => 4,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => 8,
+        }
+    }
+
+    /// The next narrower backend, for what does not fill a register
+    fn narrower(self) -> Backend {
+        match self {
+            Backend::Scalar => Backend::Scalar,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => Backend::Scalar,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => Backend::Sse2,
+        }
+    }
+}
+
+/// Compresses `blocks[i]` into `states[i]` for every `i`
+fn compress_each(backend: Backend, states: &mut [State], blocks: &[Block]) {
+    debug_assert_eq!(states.len(), blocks.len());
+    let lanes = backend.lanes();
+    for (states, blocks) in states.chunks_mut(lanes).zip(blocks.chunks(lanes)) {
+        if states.len() < lanes {
+            compress_each(backend.narrower(), states, blocks);
+            continue;
+        }
+        match backend {
+            Backend::Scalar => compress_scalar(&mut states[0], &blocks[0]),
+            // Safety: `available` only lists backends the CPU supports
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => unsafe { compress_sse2(states, blocks) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe { compress_avx2(states, blocks) },
+        }
+    }
+}
+
+/// Pads the end of a message into `blocks`
+///
+/// `tail` is the part of the message not yet compressed, and `total_len` the
+/// length of the whole message in bytes.
+fn pad(tail: &[u8], total_len: usize, blocks: &mut Vec<Block>) {
+    blocks.clear();
+    let mut chunks = tail.chunks_exact(BLOCK_SIZE);
+    for chunk in chunks.by_ref() {
+        blocks.push(chunk.try_into().expect("chunks are 64 bytes"));
+    }
+    let rest = chunks.remainder();
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..rest.len()].copy_from_slice(rest);
+    block[rest.len()] = 0x80;
+    if rest.len() >= BLOCK_SIZE - 8 {
+        blocks.push(block);
+        block = [0u8; BLOCK_SIZE];
+    }
+    block[BLOCK_SIZE - 8..].copy_from_slice(&((total_len as u64) * 8).to_le_bytes());
+    blocks.push(block);
+}
+
+/// The final block of a message whose uncompressed `tail` fits in one block
+fn final_block(tail: &[u8], total_len: usize) -> Block {
+    debug_assert!(tail.len() < BLOCK_SIZE - 8);
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..tail.len()].copy_from_slice(tail);
+    block[tail.len()] = 0x80;
+    block[BLOCK_SIZE - 8..].copy_from_slice(&((total_len as u64) * 8).to_le_bytes());
+    block
+}
+
+/// The HMAC key block for `key`, hashed first if it is longer than a block
+fn key_block(key: &[u8]) -> Block {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..16].copy_from_slice(&Md5::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    block
+}
+
+/// `block` XORed with `pad` in every byte
+fn xor_pad(block: &Block, pad: u8) -> Block {
+    block.map(|byte| byte ^ pad)
+}
+
+/// Runs the outer HMAC pass over the inner chaining states, left in `states`
+///
+/// `outer` holds the state after the outer padded key of each lane.
+fn finish_hmac(backend: Backend, states: &mut [State], outer: &[State], blocks: &mut Vec<Block>) {
+    blocks.clear();
+    blocks.extend(
+        states
+            .iter()
+            .map(|state| final_block(&digest(state), BLOCK_SIZE + 16)),
+    );
+    states.copy_from_slice(outer);
+    compress_each(backend, states, blocks);
+}
+
+/// Derives a password for each key with `backend`
+fn derive(backend: Backend, password: &[u8], keys: &[&str], length: usize) -> Vec<String> {
+    let mut blocks = Vec::with_capacity(2 * keys.len());
+    let key_blocks: Vec<Block> = keys.iter().map(|key| key_block(key.as_bytes())).collect();
+
+    // Base hash: HMAC keyed with each site key over the shared master password
+    let mut states = vec![INIT; keys.len()];
+    blocks.extend(key_blocks.iter().map(|block| xor_pad(block, 0x36)));
+    compress_each(backend, &mut states, &blocks);
+    let mut message_blocks = Vec::new();
+    pad(password, BLOCK_SIZE + password.len(), &mut message_blocks);
+    for block in &message_blocks {
+        blocks.clear();
+        blocks.resize(keys.len(), *block);
+        compress_each(backend, &mut states, &blocks);
+    }
+    let mut outer = vec![INIT; keys.len()];
+    blocks.clear();
+    blocks.extend(key_blocks.iter().map(|block| xor_pad(block, 0x5c)));
+    compress_each(backend, &mut outer, &blocks);
+    finish_hmac(backend, &mut states, &outer, &mut blocks);
+
+    // blueimp-md5 hashes without HMAC when the key is empty
+    let plain = keys
+        .iter()
+        .any(|key| key.is_empty())
+        .then(|| hmac_md5_hex(password, b""));
+    let base_hashes: Vec<[u8; MD5_HEX_LENGTH]> = keys
+        .iter()
+        .zip(&states)
+        .map(|(key, state)| match plain {
+            Some(plain) if key.is_empty() => plain,
+            _ => to_hex(&digest(state)),
+        })
+        .collect();
+
+    // Rule and source hashes: the salts are fixed, so their padded keys are
+    // compressed once and the two halves of `states` share them
+    let salted = |pad: u8| {
+        [RULE_SALT, SOURCE_SALT].map(|salt| {
+            let mut state = INIT;
+            compress_scalar(&mut state, &xor_pad(&key_block(salt), pad));
+            state
+        })
+    };
+    let [rule_inner, source_inner] = salted(0x36);
+    let [rule_outer, source_outer] = salted(0x5c);
+    let n = keys.len();
+    states.clear();
+    states.resize(n, rule_inner);
+    states.resize(2 * n, source_inner);
+    outer.clear();
+    outer.resize(n, rule_outer);
+    outer.resize(2 * n, source_outer);
+    blocks.clear();
+    for _ in 0..2 {
+        blocks.extend(
+            base_hashes
+                .iter()
+                .map(|hash| final_block(hash, BLOCK_SIZE + MD5_HEX_LENGTH)),
+        );
+    }
+    compress_each(backend, &mut states, &blocks);
+    finish_hmac(backend, &mut states, &outer, &mut blocks);
+
+    let (rule_states, source_states) = states.split_at(n);
+    rule_states
+        .iter()
+        .zip(source_states)
+        .map(|(rule, source)| {
+            generate_password(&to_hex(&digest(rule)), &to_hex(&digest(source)), length)
+        })
+        .collect()
+}
+
+/// Generates a Flower Password for each key, hashing several keys at once
+///
+/// Returns the same passwords as calling `fp_code` for each key, in the order of
+/// `keys`, using AVX2 or SSE2 lanes where the CPU supports them. Larger batches
+/// amortize better; below a few dozen keys the gain is small.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::{fp_code, fp_code_batch_simd};
+///
+/// let passwords = fp_code_batch_simd("test", &["github.com", "example.com"], 16).unwrap();
+/// assert_eq!(passwords[0], "D04175F7A9c7Ab4a");
+/// assert_eq!(passwords[1], fp_code("test", "example.com", 16).unwrap());
+/// ```
+pub fn fp_code_batch_simd(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+) -> Result<Vec<String>, FlowerPasswordError> {
+    validate_length(length)?;
+    Ok(derive(Backend::detect(), password.as_bytes(), keys, length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::XorShift;
+    use crate::{fp_code, MAX_LENGTH, MIN_LENGTH};
+    use alloc::format;
+
+    fn random_bytes(rng: &mut XorShift, max_len: u64) -> Vec<u8> {
+        let len = rng.next() % (max_len + 1);
+        (0..len).map(|_| rng.next() as u8).collect()
+    }
+
+    /// Plain MD5 through the scalar compression, to check it alone against [`Md5`]
+    fn md5_scalar(message: &[u8]) -> [u8; 16] {
+        let mut state = INIT;
+        let mut blocks = Vec::new();
+        pad(message, message.len(), &mut blocks);
+        for block in &blocks {
+            compress_scalar(&mut state, block);
+        }
+        digest(&state)
+    }
+
+    #[test]
+    fn test_scalar_md5_matches_md5() {
+        let mut rng = XorShift(0xbb67_ae85_84ca_a73b);
+        // Every length around the padding boundaries, then random ones
+        for len in 0..=200 {
+            let message = vec![b'a'; len];
+            assert_eq!(md5_scalar(&message), Md5::digest(&message));
+        }
+        for _ in 0..500 {
+            let message = random_bytes(&mut rng, 300);
+            assert_eq!(md5_scalar(&message), Md5::digest(&message));
+        }
+    }
+
+    #[test]
+    fn test_lanes_match_scalar() {
+        let mut rng = XorShift(0x3c6e_f372_fe94_f82b);
+        for backend in Backend::available() {
+            // Counts that fill registers exactly and leave every remainder
+            for count in 0..=20 {
+                let mut states: Vec<State> = (0..count)
+                    .map(|_| core::array::from_fn(|_| rng.next() as u32))
+                    .collect();
+                let blocks: Vec<Block> = (0..count)
+                    .map(|_| core::array::from_fn(|_| rng.next() as u8))
+                    .collect();
+                let mut expected = states.clone();
+                for (state, block) in expected.iter_mut().zip(&blocks) {
+                    compress_scalar(state, block);
+                }
+                compress_each(backend, &mut states, &blocks);
+                assert_eq!(states, expected, "{:?} with {} states", backend, count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_vectors() {
+        let vectors = [
+            ("password", "key", 16, "K3A2a66Bf88b628c"),
+            ("test", "github.com", 16, "D04175F7A9c7Ab4a"),
+            ("mypassword", "example.com", 12, "K0CA12CecFFB"),
+            ("12345", "site", 16, "K05a62bfea0C1553"),
+        ];
+        for backend in Backend::available() {
+            for (password, key, length, expected) in vectors {
+                assert_eq!(
+                    derive(backend, password.as_bytes(), &[key], length),
+                    [expected]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_fp_code_on_random_corpus() {
+        let mut rng = XorShift(0xa54f_f53a_5f1d_36f1);
+        for backend in Backend::available() {
+            for round in 0..40 {
+                // Masters up to 150 bytes span several blocks; keys up to 100 bytes
+                // include ones longer than a block, and empty keys take the plain
+                // MD5 path
+                let password = format!("{:x}", rng.next()).repeat((rng.next() % 10) as usize);
+                let keys: Vec<String> = (0..(rng.next() % 70))
+                    .map(|i| match i % 9 {
+                        0 => String::new(),
+                        1 => format!("{:x}", rng.next()).repeat(6),
+                        _ => format!("{:x}.example", rng.next() >> (rng.next() % 60)),
+                    })
+                    .collect();
+                let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+                let length = MIN_LENGTH + round % (MAX_LENGTH - MIN_LENGTH + 1);
+                let expected: Vec<String> = keys
+                    .iter()
+                    .map(|key| fp_code(&password, key, length).unwrap())
+                    .collect();
+                assert_eq!(
+                    derive(backend, password.as_bytes(), &keys, length),
+                    expected,
+                    "{:?}, password {:?}",
+                    backend,
+                    password
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fp_code_batch_simd() {
+        assert!(fp_code_batch_simd("test", &[], 16).unwrap().is_empty());
+        assert!(matches!(
+            fp_code_batch_simd("test", &["a"], 33),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+        let keys: Vec<String> = (0..100).map(|i| format!("site{}.example", i)).collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let passwords = fp_code_batch_simd("密码🌸", &keys, 32).unwrap();
+        for (key, password) in keys.iter().zip(passwords) {
+            assert_eq!(password, fp_code("密码🌸", key, 32).unwrap());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Allocation-free Flower Password derivation
+//!
+//! [`fp_code_heapless`] derives the same passwords as [`fp_code`](crate::fp_code),
+//! but keeps every intermediate value in fixed-size stack arrays, so it runs on
+//! targets with no heap at all. Enable the `heapless` feature, usually together
+//! with `default-features = false`.
+//!
+//! # Stack usage
+//!
+//! The deepest point is an HMAC computation while the base, rule and source
+//! digests are live: the three 32-byte hex digests, the HMAC state (two MD5
+//! cores with their 64-byte block buffers, about 200 bytes) and a 16-byte raw
+//! digest come to roughly 350 bytes, plus the MD5 transform's own frame and the
+//! 32-byte output. Budget about 1 KiB including the compiler's temporaries.
+
+use crate::{derive_into, validate_length, FlowerPasswordError, Scratch, Wiped, MD5_HEX_LENGTH};
+use heapless::String;
+
+/// Generates a Flower Password without allocating
+///
+/// Takes the master password and key as bytes (the UTF-8 encoding of the strings
+/// passed to `fp_code`) and returns the same password in a fixed-capacity string.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_code_heapless;
+///
+/// let password = fp_code_heapless(b"test", b"github.com", 16).unwrap();
+/// assert_eq!(password, "D04175F7A9c7Ab4a");
+/// ```
+#[inline]
+pub fn fp_code_heapless(
+    password: &[u8],
+    key: &[u8],
+    length: usize,
+) -> Result<String<MD5_HEX_LENGTH>, FlowerPasswordError> {
+    validate_length(length)?;
+
+    let mut out = Wiped([0; MD5_HEX_LENGTH]);
+    derive_into(password, key, &mut out[..length], &mut Scratch::default());
+
+    let mut result = String::new();
+    for &byte in &out[..length] {
+        // At most MD5_HEX_LENGTH ASCII characters, which always fit
+        let _ = result.push(char::from(byte));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::XorShift;
+    use crate::{fp_code, MAX_LENGTH, MIN_LENGTH};
+
+    #[test]
+    fn test_known_vectors() {
+        // The full known-answer set lives in `flowerpassword::vectors`, which
+        // checks `fp_code_heapless` against every entry
+        let vectors = [
+            ("password", "key", 16, "K3A2a66Bf88b628c"),
+            ("test", "github.com", 16, "D04175F7A9c7Ab4a"),
+            ("mypassword", "example.com", 12, "K0CA12CecFFB"),
+            ("12345", "site", 16, "K05a62bfea0C1553"),
+        ];
+        for (password, key, length, expected) in vectors {
+            let derived = fp_code_heapless(password.as_bytes(), key.as_bytes(), length).unwrap();
+            assert_eq!(derived, expected);
+        }
+    }
+
+    #[test]
+    fn test_matches_fp_code_at_all_lengths() {
+        for length in MIN_LENGTH..=MAX_LENGTH {
+            assert_eq!(
+                fp_code_heapless(b"password", b"key", length).unwrap(),
+                fp_code("password", "key", length).unwrap().as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_fp_code_on_random_inputs() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            // Up to 40 characters, so keys longer than the 64-byte block occur
+            let password = rng.string(40);
+            let key = rng.string(40);
+            let length = MIN_LENGTH + (rng.next() as usize) % (MAX_LENGTH - MIN_LENGTH + 1);
+            assert_eq!(
+                fp_code_heapless(password.as_bytes(), key.as_bytes(), length).unwrap(),
+                fp_code(&password, &key, length).unwrap().as_str(),
+                "password {:?}, key {:?}, length {}",
+                password,
+                key,
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_inputs() {
+        assert_eq!(fp_code_heapless(b"", b"", 16).unwrap(), "K930B0264e62DDFC");
+        assert_eq!(
+            fp_code_heapless(b"password", b"", 16).unwrap(),
+            "eB3b1cA3D6B54c00"
+        );
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        for length in [0, 1, 33, 100] {
+            assert!(matches!(
+                fp_code_heapless(b"password", b"key", length),
+                Err(FlowerPasswordError::InvalidLength(l)) if l == length
+            ));
+        }
+    }
+}
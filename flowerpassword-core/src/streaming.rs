@@ -0,0 +1,196 @@
+//! Incremental key input
+//!
+//! [`KeyStream`] accepts the key in pieces, so a key of any size (a key file, say)
+//! can be fed from a reader without holding it in memory.
+//!
+//! HMAC uses a key longer than the MD5 block as its MD5 digest, so only the first
+//! block is buffered: once the key outgrows it, the buffered bytes and everything
+//! after them go through an incremental MD5 instead. The result is the same as
+//! passing the whole key to `fp_code`.
+
+use crate::{derive_string, validate_length, wipe, FlowerPasswordError, Md5, Wiped};
+use alloc::string::String;
+
+/// MD5 block size, the longest key HMAC-MD5 uses as given
+const BLOCK_SIZE: usize = 64;
+
+/// A key fed in pieces, for deriving passwords from keys too large to hold
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::{fp_code, KeyStream};
+///
+/// let mut key = KeyStream::new();
+/// key.update(b"github");
+/// key.update(b".com");
+/// assert_eq!(key.fp_code("test", 16).unwrap(), fp_code("test", "github.com", 16).unwrap());
+/// ```
+#[derive(Clone)]
+pub struct KeyStream {
+    block: [u8; BLOCK_SIZE],
+    len: usize,
+    /// Set once the key is longer than a block
+    hasher: Option<Md5>,
+}
+
+impl KeyStream {
+    /// Creates an empty key
+    pub fn new() -> Self {
+        KeyStream {
+            block: [0; BLOCK_SIZE],
+            len: 0,
+            hasher: None,
+        }
+    }
+
+    /// Appends `bytes` to the key
+    pub fn update(&mut self, bytes: &[u8]) {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(bytes);
+        } else if let Some(rest) = self.block.get_mut(self.len..self.len + bytes.len()) {
+            rest.copy_from_slice(bytes);
+            self.len += bytes.len();
+        } else {
+            let mut hasher = Md5::new();
+            hasher.update(&self.block[..self.len]);
+            hasher.update(bytes);
+            self.hasher = Some(hasher);
+        }
+    }
+
+    /// Generates the Flower Password for master password and the key so far
+    ///
+    /// For a key whose bytes are the UTF-8 encoding of `key`, this is exactly
+    /// `fp_code(password, key, length)`. The stream is left unchanged, so more
+    /// can be appended afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn fp_code(&self, password: &str, length: usize) -> Result<String, FlowerPasswordError> {
+        validate_length(length)?;
+        let digest: Wiped<16>;
+        let key = match &self.hasher {
+            Some(hasher) => {
+                digest = Wiped(hasher.clone().finalize());
+                &digest[..]
+            }
+            None => &self.block[..self.len],
+        };
+        Ok(derive_string(password.as_bytes(), key, length))
+    }
+}
+
+impl Drop for KeyStream {
+    /// Wipes the buffered start of the key
+    ///
+    /// The running MD5 of a longer key lives in `md-5`'s state, which cannot be
+    /// wiped from here, unless the in-tree MD5 of `vendored-md5` is used: it wipes
+    /// its state itself.
+    fn drop(&mut self) {
+        wipe(&mut self.block);
+    }
+}
+
+impl Default for KeyStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for KeyStream {
+    /// Shows only whether the key outgrew the block, never its bytes
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("KeyStream")
+            .field("hashed", &self.hasher.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+    use crate::tests::{allocations, freed_secrets, XorShift, SECRET_MARKER};
+    use alloc::boxed::Box;
+    use alloc::format;
+
+    #[test]
+    fn test_matches_fp_code() {
+        let mut rng = XorShift(0x510e_527f_ade6_82d1);
+        for len in (0..=200).chain([1000, 4097]) {
+            let key: String = (0..len)
+                .map(|_| char::from(b' ' + (rng.next() % 95) as u8))
+                .collect();
+            let expected = fp_code("master", &key, 16).unwrap();
+
+            let mut whole = KeyStream::new();
+            whole.update(key.as_bytes());
+            assert_eq!(whole.fp_code("master", 16).unwrap(), expected, "{}", len);
+
+            // Random pieces, including empty ones and ones spanning the block boundary
+            let mut pieces = KeyStream::new();
+            let mut rest = key.as_bytes();
+            while !rest.is_empty() {
+                let (piece, tail) = rest.split_at((rng.next() % 80) as usize % (rest.len() + 1));
+                pieces.update(piece);
+                rest = tail;
+            }
+            assert_eq!(pieces.fp_code("master", 16).unwrap(), expected, "{}", len);
+        }
+    }
+
+    #[test]
+    fn test_unicode_and_lengths() {
+        let key = "例え.jp 🌸".repeat(9);
+        let mut stream = KeyStream::new();
+        for ch in key.chars() {
+            stream.update(ch.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        for length in 2..=32 {
+            assert_eq!(
+                stream.fp_code("test", length).unwrap(),
+                fp_code("test", &key, length).unwrap()
+            );
+        }
+        assert!(matches!(
+            stream.fp_code("test", 33),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+        assert_eq!(
+            KeyStream::new().fp_code("test", 16).unwrap(),
+            fp_code("test", "", 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_does_not_allocate_while_streaming() {
+        let chunk = [b'k'; 4096];
+        let mut stream = KeyStream::new();
+        let (count, ()) = allocations(|| {
+            for _ in 0..1024 {
+                stream.update(&chunk);
+            }
+        });
+        assert_eq!(count, 0);
+        assert_eq!(
+            stream.fp_code("test", 16).unwrap(),
+            fp_code("test", &"k".repeat(4 << 20), 16).unwrap()
+        );
+        assert!(!format!("{:?}", stream).contains('k'));
+    }
+
+    #[test]
+    fn test_wiped_on_drop() {
+        let freed = freed_secrets(|| {
+            let mut key = Box::new(KeyStream::new());
+            key.update(SECRET_MARKER);
+            assert_eq!(
+                key.fp_code("test", 16).unwrap(),
+                fp_code("test", core::str::from_utf8(SECRET_MARKER).unwrap(), 16).unwrap()
+            );
+        });
+        assert_eq!(freed, 0);
+    }
+}
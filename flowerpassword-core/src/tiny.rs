@@ -0,0 +1,92 @@
+//! Formatting-free Flower Password derivation
+//!
+//! [`fp_code_tiny`] derives the same passwords as [`fp_code`](crate::fp_code),
+//! for builds where every kilobyte counts: it writes into a caller buffer, keeps
+//! every intermediate value in fixed-size arrays, and reports errors as a
+//! [`FpErrorCode`] byte with no `Display`, so nothing on its path pulls in
+//! `core::fmt` or `String`. Enable the `tiny` feature, usually together with
+//! `default-features = false`, and build with `panic = "abort"`.
+//!
+//! `check-size.sh` measures what this saves against `fp_code` on a bare-metal
+//! target.
+
+use crate::{derive_into, Scratch, MAX_LENGTH, MIN_LENGTH};
+
+/// Error code returned by [`fp_code_tiny`]
+///
+/// The discriminants are stable, so the code can be passed across FFI or stored
+/// as a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FpErrorCode {
+    /// The output buffer is not between 2 and 32 bytes long
+    InvalidLength = 1,
+}
+
+/// Writes the Flower Password for master password and key into `out`
+///
+/// Takes the master password and key as bytes (the UTF-8 encoding of the strings
+/// passed to `fp_code`). The password length is `out.len()`, and each character is
+/// written as one ASCII byte.
+///
+/// # Errors
+///
+/// Returns `FpErrorCode::InvalidLength` if `out.len()` is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_code_tiny;
+///
+/// let mut password = [0u8; 16];
+/// fp_code_tiny(b"test", b"github.com", &mut password).unwrap();
+/// assert_eq!(&password, b"D04175F7A9c7Ab4a");
+/// ```
+pub fn fp_code_tiny(password: &[u8], key: &[u8], out: &mut [u8]) -> Result<(), FpErrorCode> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&out.len()) {
+        return Err(FpErrorCode::InvalidLength);
+    }
+
+    derive_into(password, key, out, &mut Scratch::default());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+    use crate::tests::XorShift;
+    use alloc::string::String;
+
+    #[test]
+    fn test_matches_fp_code() {
+        let mut rng = XorShift(0x9b05_688c_2b3e_6c1f);
+        for _ in 0..200 {
+            let password: String = (0..rng.next() % 20)
+                .map(|_| char::from(b' ' + (rng.next() % 95) as u8))
+                .collect();
+            let key = "例え.jp".repeat((rng.next() % 12) as usize);
+            let mut out = [0; MAX_LENGTH];
+            for length in MIN_LENGTH..=MAX_LENGTH {
+                fp_code_tiny(password.as_bytes(), key.as_bytes(), &mut out[..length]).unwrap();
+                assert_eq!(
+                    &out[..length],
+                    fp_code(&password, &key, length).unwrap().as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        for length in [0, 1, 33, 64] {
+            let mut out = [b'#'; 64];
+            assert_eq!(
+                fp_code_tiny(b"test", b"github.com", &mut out[..length]),
+                Err(FpErrorCode::InvalidLength)
+            );
+            assert!(out.iter().all(|&byte| byte == b'#'));
+        }
+        assert_eq!(FpErrorCode::InvalidLength as u8, 1);
+    }
+}
@@ -0,0 +1,173 @@
+//! The v2 scheme: the Flower Password transformation over HMAC-SHA256
+//!
+//! [`fp_code_v2`] hashes like [`fp_code`](crate::fp_code), with HMAC-SHA256 in
+//! place of HMAC-MD5:
+//!
+//! 1. The base hash is HMAC-SHA256 of the master password keyed with the key, as
+//!    lowercase hex. An empty key is an ordinary HMAC key here, unlike the plain
+//!    MD5 that v1 falls back to for compatibility with blueimp-md5.
+//! 2. The rule and source hashes are HMAC-SHA256 of the base hash keyed with
+//!    `kise` and `snow`.
+//! 3. The first 32 hex characters of both go through the same uppercasing and
+//!    leading-`K` transformation as v1.
+//!
+//! So v2 passwords look like v1 ones and have the same lengths, but are
+//! unrelated to them: moving a site to v2 changes its password.
+
+use crate::{
+    constant_time_eq, password_byte, validate_length, wipe, FlowerPasswordError, Wiped, HEX_DIGITS,
+    MAX_LENGTH, MD5_HEX_LENGTH, RULE_SALT, SOURCE_SALT,
+};
+use alloc::string::String;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// SHA-256 hash length in hexadecimal characters
+const SHA256_HEX_LENGTH: usize = 64;
+
+/// Computes HMAC-SHA256 of `message` keyed with `key`, as lowercase hex
+///
+/// The raw digest is wiped once encoded.
+fn hmac_sha256_hex(message: &[u8], key: &[u8]) -> [u8; SHA256_HEX_LENGTH] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    let digest: Wiped<32> = Wiped(mac.finalize().into_bytes().into());
+    let mut hex = [0u8; SHA256_HEX_LENGTH];
+    for (pair, byte) in hex.chunks_exact_mut(2).zip(digest.iter()) {
+        pair[0] = HEX_DIGITS[usize::from(byte >> 4)];
+        pair[1] = HEX_DIGITS[usize::from(byte & 0x0f)];
+    }
+    hex
+}
+
+/// Writes the v2 password for master password and key into `out`
+///
+/// `out.len()` must already be a valid length. Every hash is wiped before
+/// returning.
+fn derive_into(password: &[u8], key: &[u8], out: &mut [u8]) {
+    let mut base = hmac_sha256_hex(password, key);
+    let mut salted = [RULE_SALT, SOURCE_SALT].map(|salt| hmac_sha256_hex(&base, salt));
+    wipe(&mut base);
+
+    let [rule_hash, source_hash] = &salted;
+    let mut rule = Wiped([0; MD5_HEX_LENGTH]);
+    let mut source = Wiped([0; MD5_HEX_LENGTH]);
+    rule.copy_from_slice(&rule_hash[..MD5_HEX_LENGTH]);
+    source.copy_from_slice(&source_hash[..MD5_HEX_LENGTH]);
+    for (index, byte) in out.iter_mut().enumerate() {
+        *byte = password_byte(index, &rule, &source);
+    }
+    for hash in &mut salted {
+        wipe(hash);
+    }
+}
+
+/// Generates a password with the v2 scheme, HMAC-SHA256 in place of HMAC-MD5
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_code_v2;
+///
+/// let password = fp_code_v2("test", "github.com", 16).unwrap();
+/// assert_eq!(password, "K4Ab3e328cA49fE8");
+/// ```
+pub fn fp_code_v2(password: &str, key: &str, length: usize) -> Result<String, FlowerPasswordError> {
+    validate_length(length)?;
+    let mut out = Wiped([0; MAX_LENGTH]);
+    derive_into(password.as_bytes(), key.as_bytes(), &mut out[..length]);
+    Ok(String::from(
+        core::str::from_utf8(&out[..length]).expect("passwords are ASCII"),
+    ))
+}
+
+/// Checks whether `candidate` is the v2 password for master password and key
+///
+/// Works like [`fp_verify`](crate::fp_verify): the length is taken from
+/// `candidate`, and the comparison does not exit early.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword_core::fp_verify_v2;
+///
+/// assert!(fp_verify_v2("test", "github.com", "K4Ab3e328cA49fE8"));
+/// assert!(!fp_verify_v2("test", "github.com", "D04175F7A9c7Ab4a"));
+/// ```
+pub fn fp_verify_v2(password: &str, key: &str, candidate: &str) -> bool {
+    match fp_code_v2(password, key, candidate.len()) {
+        Ok(expected) => constant_time_eq(&expected, candidate),
+        Err(FlowerPasswordError::InvalidLength(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::allocations;
+    use crate::MIN_LENGTH;
+
+    #[test]
+    fn test_known_answers() {
+        // Computed independently with Python's `hmac` and `hashlib`
+        let vectors = [
+            ("test", "github.com", 16, "K4Ab3e328cA49fE8"),
+            ("password", "key", 16, "Kc00C26cC485D3f3"),
+            ("password", "key", 32, "Kc00C26cC485D3f3b1C9F4871622E9aF"),
+            ("test", "key", 8, "Kcd39923"),
+            ("", "", 16, "K42D50c45F6cE896"),
+            ("密码", "", 16, "Dd854090b57EC851"),
+        ];
+        for (password, key, length, expected) in vectors {
+            assert_eq!(fp_code_v2(password, key, length).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256_vectors() {
+        // RFC 4231 test cases 1 and 2
+        assert_eq!(
+            &hmac_sha256_hex(b"Hi There", &[0x0b; 20]),
+            b"b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            &hmac_sha256_hex(b"what do ya want for nothing?", b"Jefe"),
+            b"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_lengths() {
+        let full = fp_code_v2("password", "key", MAX_LENGTH).unwrap();
+        for length in MIN_LENGTH..=MAX_LENGTH {
+            assert_eq!(
+                fp_code_v2("password", "key", length).unwrap(),
+                full[..length]
+            );
+        }
+        for length in [0, 1, 33] {
+            assert!(matches!(
+                fp_code_v2("password", "key", length),
+                Err(FlowerPasswordError::InvalidLength(l)) if l == length
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        assert!(fp_verify_v2("password", "key", "Kc00C26cC485D3f3"));
+        assert!(!fp_verify_v2("password", "key", "Kc00C26cC485D3f4"));
+        assert!(!fp_verify_v2("password", "key", "K"));
+    }
+
+    #[test]
+    fn test_allocates_only_the_result() {
+        let (count, password) = allocations(|| fp_code_v2("password", "key", 16));
+        assert_eq!(count, 1);
+        assert_eq!(password.unwrap().len(), 16);
+    }
+}
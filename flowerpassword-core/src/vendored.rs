@@ -0,0 +1,344 @@
+//! In-tree MD5 and HMAC-MD5
+//!
+//! A self-contained RFC 1321 MD5 with the RFC 2104 HMAC around it, for builds
+//! that may not depend on the `md-5` and `hmac` crates. The `vendored-md5`
+//! feature selects it, and so does leaving out `rustcrypto`; either way
+//! `fp_code` gives the same results. The scalar compression function is also
+//! the fallback of the `simd` backend.
+//!
+//! Unlike the RustCrypto types, [`Md5`] wipes its chaining state and buffered
+//! block when dropped, so neither the HMAC states nor a hashed key outlive their
+//! use in memory this crate owns.
+
+use crate::{wipe, wipe_words, Wiped};
+
+/// MD5 block size in bytes
+pub(crate) const BLOCK_SIZE: usize = 64;
+
+/// Chaining state of one MD5 computation
+pub(crate) type State = [u32; 4];
+
+/// One 64-byte MD5 block
+pub(crate) type Block = [u8; BLOCK_SIZE];
+
+/// Initial MD5 chaining state
+pub(crate) const INIT: State = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+
+/// Per-step additive constants, `floor(abs(sin(i + 1)) * 2^32)`
+pub(crate) const K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Left-rotation amounts, four per round
+const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// Rotation of step `i`
+#[inline(always)]
+pub(crate) fn shift(i: usize) -> u32 {
+    SHIFTS[(i / 16) * 4 + i % 4]
+}
+
+/// Message word used by step `i`
+#[inline(always)]
+pub(crate) fn message_index(i: usize) -> usize {
+    match i / 16 {
+        0 => i,
+        1 => (5 * i + 1) % 16,
+        2 => (3 * i + 5) % 16,
+        _ => (7 * i) % 16,
+    }
+}
+
+/// Little-endian word `w` of `block`
+#[inline(always)]
+pub(crate) fn word(block: &Block, w: usize) -> u32 {
+    u32::from_le_bytes([
+        block[4 * w],
+        block[4 * w + 1],
+        block[4 * w + 2],
+        block[4 * w + 3],
+    ])
+}
+
+/// Compresses one block into one state
+pub(crate) fn compress(state: &mut State, block: &Block) {
+    let [mut a, mut b, mut c, mut d] = *state;
+    for (i, &k) in K.iter().enumerate() {
+        let f = match i / 16 {
+            0 => (b & c) | (!b & d),
+            1 => (d & b) | (!d & c),
+            2 => b ^ c ^ d,
+            _ => c ^ (b | !d),
+        };
+        let t = a
+            .wrapping_add(f)
+            .wrapping_add(k)
+            .wrapping_add(word(block, message_index(i)));
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(t.rotate_left(shift(i)));
+    }
+    for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// Serializes a final chaining state as the MD5 digest
+pub(crate) fn digest(state: &State) -> [u8; 16] {
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// An incremental MD5 hash, wiped on drop
+#[derive(Clone)]
+#[cfg_attr(
+    all(feature = "rustcrypto", not(feature = "vendored-md5")),
+    allow(dead_code)
+)]
+pub(crate) struct Md5 {
+    state: State,
+    /// Bytes not yet compressed, at most one block short
+    block: Block,
+    buffered: usize,
+    /// Message length in bytes, modulo 2^64 as RFC 1321 counts it
+    len: u64,
+}
+
+#[cfg_attr(
+    all(feature = "rustcrypto", not(feature = "vendored-md5")),
+    allow(dead_code)
+)]
+impl Md5 {
+    pub(crate) fn new() -> Self {
+        Md5 {
+            state: INIT,
+            block: [0; BLOCK_SIZE],
+            buffered: 0,
+            len: 0,
+        }
+    }
+
+    /// The MD5 digest of `message`
+    pub(crate) fn digest(message: &[u8]) -> [u8; 16] {
+        let mut hasher = Md5::new();
+        hasher.update(message);
+        hasher.finalize()
+    }
+
+    pub(crate) fn update(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+        if self.buffered > 0 {
+            let take = (BLOCK_SIZE - self.buffered).min(bytes.len());
+            self.block[self.buffered..self.buffered + take].copy_from_slice(&bytes[..take]);
+            self.buffered += take;
+            bytes = &bytes[take..];
+            if self.buffered < BLOCK_SIZE {
+                return;
+            }
+            compress(&mut self.state, &self.block);
+            self.buffered = 0;
+        }
+        let mut chunks = bytes.chunks_exact(BLOCK_SIZE);
+        for chunk in chunks.by_ref() {
+            compress(
+                &mut self.state,
+                chunk.try_into().expect("chunks are 64 bytes"),
+            );
+        }
+        let rest = chunks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Appends the padding and length, returning the digest
+    pub(crate) fn finalize(mut self) -> [u8; 16] {
+        let bits = self.len.wrapping_mul(8).to_le_bytes();
+        self.block[self.buffered] = 0x80;
+        self.block[self.buffered + 1..].fill(0);
+        if self.buffered >= BLOCK_SIZE - 8 {
+            compress(&mut self.state, &self.block);
+            self.block = [0; BLOCK_SIZE];
+        }
+        self.block[BLOCK_SIZE - 8..].copy_from_slice(&bits);
+        compress(&mut self.state, &self.block);
+        digest(&self.state)
+    }
+}
+
+impl Drop for Md5 {
+    fn drop(&mut self) {
+        wipe_words(&mut self.state);
+        wipe(&mut self.block);
+    }
+}
+
+/// HMAC-MD5 keyed once, so that clones start after the padded keys
+#[derive(Clone)]
+#[cfg_attr(
+    all(feature = "rustcrypto", not(feature = "vendored-md5")),
+    allow(dead_code)
+)]
+pub(crate) struct HmacMd5 {
+    inner: Md5,
+    outer: Md5,
+}
+
+#[cfg_attr(
+    all(feature = "rustcrypto", not(feature = "vendored-md5")),
+    allow(dead_code)
+)]
+impl HmacMd5 {
+    /// Keys the inner and outer hashes, hashing keys longer than a block first
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut block = Wiped([0u8; BLOCK_SIZE]);
+        if key.len() > BLOCK_SIZE {
+            block[..16].copy_from_slice(&*Wiped(Md5::digest(key)));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Md5::new();
+        inner.update(&*Wiped(block.map(|byte| byte ^ 0x36)));
+        let mut outer = Md5::new();
+        outer.update(&*Wiped(block.map(|byte| byte ^ 0x5c)));
+        HmacMd5 { inner, outer }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.inner.update(bytes);
+    }
+
+    pub(crate) fn finalize(self) -> [u8; 16] {
+        let HmacMd5 { inner, mut outer } = self;
+        outer.update(&*Wiped(inner.finalize()));
+        outer.finalize()
+    }
+}
+
+#[cfg(all(test, any(feature = "vendored-md5", not(feature = "rustcrypto"))))]
+mod tests {
+    use super::*;
+    use crate::tests::{freed_secrets, HMAC_VECTORS, SECRET_MARKER};
+    use crate::to_hex;
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+
+    fn hex(digest: [u8; 16]) -> alloc::string::String {
+        alloc::string::String::from_utf8(to_hex(&digest).to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_rfc_1321_suite() {
+        // RFC 1321, appendix A.5
+        let suite: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (message, expected) in suite {
+            assert_eq!(hex(Md5::digest(message)), expected, "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn test_hmac_vectors() {
+        for &(message, key, expected) in HMAC_VECTORS {
+            // The empty key is blueimp-md5's plain MD5 special case, handled by the caller
+            let digest = if key.is_empty() {
+                Md5::digest(message)
+            } else {
+                let mut mac = HmacMd5::new(key);
+                mac.update(message);
+                mac.finalize()
+            };
+            assert_eq!(hex(digest), expected, "key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_split_updates() {
+        let message: Vec<u8> = (0..200u8).collect();
+        for len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 200] {
+            let message = &message[..len];
+            let expected = Md5::digest(message);
+            for split in 0..=len {
+                let mut hasher = Md5::new();
+                hasher.update(&message[..split]);
+                hasher.update(&[]);
+                hasher.update(&message[split..]);
+                assert_eq!(hasher.finalize(), expected, "{} split at {}", len, split);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wiped_on_drop() {
+        let mut hasher = Md5::new();
+        hasher.update(SECRET_MARKER);
+        assert_eq!(freed_secrets(|| drop(Box::new(hasher.clone()))), 0);
+        let mut mac = HmacMd5::new(SECRET_MARKER);
+        mac.update(SECRET_MARKER);
+        assert_eq!(freed_secrets(|| drop(Box::new(mac))), 0);
+    }
+
+    /// Against the `md-5` and `hmac` crates, with `--features vendored-md5` and
+    /// the default `rustcrypto` both enabled
+    #[test]
+    #[cfg(feature = "rustcrypto")]
+    fn test_matches_rustcrypto() {
+        use crate::tests::XorShift;
+        use hmac::{Hmac, Mac};
+        use md5::Digest;
+
+        fn random_bytes(rng: &mut XorShift, max_len: u64) -> Vec<u8> {
+            let len = rng.next() % (max_len + 1);
+            (0..len).map(|_| rng.next() as u8).collect()
+        }
+
+        let mut rng = XorShift(0x510e_527f_ade6_82d1);
+        for _ in 0..2000 {
+            let message = random_bytes(&mut rng, 300);
+            let key = random_bytes(&mut rng, 150);
+            assert_eq!(
+                Md5::digest(&message),
+                <[u8; 16]>::from(md5::Md5::digest(&message))
+            );
+
+            let mut mac = HmacMd5::new(&key);
+            let mut reference = Hmac::<md5::Md5>::new_from_slice(&key).unwrap();
+            let split = message.len() / 3;
+            mac.update(&message[..split]);
+            mac.update(&message[split..]);
+            reference.update(&message);
+            assert_eq!(
+                mac.finalize(),
+                <[u8; 16]>::from(reference.finalize().into_bytes()),
+                "key {:?}",
+                key
+            );
+        }
+    }
+}
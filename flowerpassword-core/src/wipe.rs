@@ -0,0 +1,85 @@
+//! Wiping intermediate secrets
+//!
+//! Plain zeroing of a buffer that is never read again is a dead store the
+//! optimizer may remove, so [`wipe`] and `wipe_words` write through volatile
+//! pointers and fence the compiler. This is the only `unsafe` outside the `simd`
+//! backend; it keeps the core free of a dependency whose newer releases need a
+//! later Rust than the crate's MSRV.
+
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Overwrites `bytes` with zeros in a way the optimizer cannot remove
+///
+/// The whole array is one volatile store, which compiles to a few wide writes
+/// rather than one per byte.
+pub(crate) fn wipe<const N: usize>(bytes: &mut [u8; N]) {
+    // SAFETY: `bytes` is a valid, aligned and exclusive reference
+    unsafe { core::ptr::write_volatile(bytes, [0; N]) };
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrites `words` with zeros in a way the optimizer cannot remove
+#[cfg(all(
+    feature = "scheme-v1-md5",
+    any(
+        feature = "simd",
+        feature = "vendored-md5",
+        not(feature = "rustcrypto")
+    )
+))]
+pub(crate) fn wipe_words<const N: usize>(words: &mut [u32; N]) {
+    // SAFETY: as in `wipe`
+    unsafe { core::ptr::write_volatile(words, [0; N]) };
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// A byte array that is wiped when dropped
+pub(crate) struct Wiped<const N: usize>(pub(crate) [u8; N]);
+
+impl<const N: usize> Deref for Wiped<N> {
+    type Target = [u8; N];
+
+    fn deref(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Wiped<N> {
+    fn deref_mut(&mut self) -> &mut [u8; N] {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> Drop for Wiped<N> {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{freed_secrets, SECRET_MARKER};
+    use alloc::boxed::Box;
+
+    #[test]
+    fn test_wipe() {
+        let mut bytes = *b"secret";
+        wipe(&mut bytes);
+        assert_eq!(bytes, [0; 6]);
+        wipe(&mut []);
+    }
+
+    #[test]
+    fn test_wiped_on_drop() {
+        // The shim the wiping tests rely on does see a secret left in freed memory
+        let marker: [u8; 14] = SECRET_MARKER.try_into().unwrap();
+        assert_eq!(freed_secrets(|| drop(Box::new(marker))), 1);
+        assert_eq!(freed_secrets(|| drop(Box::new(Wiped(marker)))), 0);
+
+        let mut wiped = Wiped([1; 4]);
+        wiped[0] = 2;
+        assert_eq!(*wiped, [2, 1, 1, 1]);
+    }
+}
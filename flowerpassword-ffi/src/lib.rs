@@ -0,0 +1,14 @@
+//! Linkable C library for the `flowerpassword` C ABI
+//!
+//! `flowerpassword` itself is an `rlib` only, since its `no_std` builds cannot link
+//! a `cdylib` or `staticlib`. This crate re-exports `flowerpassword::ffi` and builds
+//! both, as `libflowerpassword_ffi.so` (`.dylib`, `flowerpassword_ffi.dll`) and
+//! `libflowerpassword_ffi.a` (`flowerpassword_ffi.lib`). Building it regenerates
+//! `include/flowerpassword.h`.
+//!
+//! ```sh
+//! cargo build --release -p flowerpassword-ffi
+//! cc app.c -Iinclude -Ltarget/release -lflowerpassword_ffi
+//! ```
+
+pub use flowerpassword::ffi::*;
@@ -0,0 +1,42 @@
+/* Links against the built library and checks the C ABI end to end; see check-ffi.sh. */
+#include <stdio.h>
+#include <string.h>
+
+#include "flowerpassword.h"
+
+static int failures = 0;
+
+static void expect(int condition, const char *what) {
+    if (!condition) {
+        fprintf(stderr, "FAIL: %s\n", what);
+        failures++;
+    }
+}
+
+int main(void) {
+    char out[33];
+
+    expect(fp_code_c("test", "github.com", 16, out, sizeof out) == FP_OK, "fp_code_c succeeds");
+    expect(strcmp(out, "D04175F7A9c7Ab4a") == 0, "known answer for test/github.com");
+    expect(fp_last_error_message() == NULL, "no error after success");
+
+    expect(fp_code_c("password", "key", 32, out, sizeof out) == FP_OK, "length 32 fits");
+    expect(strcmp(out, "K3A2a66Bf88b628c2Cd7cDA9958f6b26") == 0, "known answer at length 32");
+
+    expect(fp_code_c("hunter2-master", "key", 33, out, sizeof out) == FP_ERROR_INVALID_LENGTH,
+           "length 33 is rejected");
+    const char *message = fp_last_error_message();
+    expect(message != NULL && strcmp(message, "Length must be between 2 and 32, got: 33") == 0,
+           "invalid length message");
+    expect(message != NULL && strstr(message, "hunter2") == NULL, "message leaves the master out");
+
+    expect(fp_code_c("password", "key", 16, out, 16) == FP_ERROR_BUFFER_TOO_SMALL,
+           "a buffer without room for NUL is rejected");
+    expect(fp_code_c(NULL, "key", 16, out, sizeof out) == FP_ERROR_NULL_POINTER,
+           "null password is rejected");
+
+    if (failures == 0) {
+        printf("C ABI smoke test passed\n");
+    }
+    return failures == 0 ? 0 : 1;
+}
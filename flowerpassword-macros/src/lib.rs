@@ -0,0 +1,132 @@
+//! Compile-time Flower Password derivation
+//!
+//! [`fp_code!`] runs [`fp_code`](flowerpassword_core::fp_code) while the crate is
+//! being compiled and expands to the derived password as a `&'static str`, so a
+//! device can carry a site password without ever containing the master password.
+//!
+//! The master password is read from an environment variable of the compiler, never
+//! from a literal, to keep it out of the source:
+//!
+//! ```ignore
+//! use flowerpassword_macros::fp_code;
+//!
+//! const PASSWORD: &str = fp_code!(env = "FP_MASTER", key = "device-42.local", length = 16);
+//! ```
+//!
+//! Cargo does not know that the expansion depends on the variable. Add a build
+//! script to the crate that uses the macro so it is rebuilt when the master changes:
+//!
+//! ```ignore
+//! fn main() {
+//!     println!("cargo:rerun-if-env-changed=FP_MASTER");
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitInt, LitStr, Token};
+
+/// Arguments of `fp_code!`
+struct Args {
+    env: LitStr,
+    key: LitStr,
+    length: LitInt,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut env = None;
+        let mut key = None;
+        let mut length = None;
+
+        while !input.is_empty() {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let duplicate = || syn::Error::new(name.span(), format!("duplicate `{}`", name));
+            match name.to_string().as_str() {
+                "env" if env.is_some() => return Err(duplicate()),
+                "env" => env = Some(input.parse()?),
+                "key" if key.is_some() => return Err(duplicate()),
+                "key" => key = Some(input.parse()?),
+                "length" if length.is_some() => return Err(duplicate()),
+                "length" => length = Some(input.parse()?),
+                "master" | "password" => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "the master password cannot be a literal; \
+                         name an environment variable with `env = \"...\"`",
+                    ))
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!(
+                            "unknown argument `{}`, expected `env`, `key` or `length`",
+                            name
+                        ),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let missing = |argument: &str| {
+            syn::Error::new(
+                Span::call_site(),
+                format!("missing `{}` argument", argument),
+            )
+        };
+        Ok(Args {
+            env: env.ok_or_else(|| missing("env"))?,
+            key: key.ok_or_else(|| missing("key"))?,
+            length: length.ok_or_else(|| missing("length"))?,
+        })
+    }
+}
+
+/// Derives a Flower Password at compile time
+///
+/// Takes the name of the environment variable holding the master password, the
+/// key and the length, and expands to the password as a string literal, so it can
+/// initialize a `const` or `static`:
+///
+/// ```ignore
+/// const PASSWORD: &str = fp_code!(env = "FP_MASTER", key = "device-42.local", length = 16);
+/// ```
+///
+/// # Errors
+///
+/// Compilation fails if the variable is not set or not valid UTF-8, if the length
+/// is not between 2 and 32, or if the master password is given as a literal.
+#[proc_macro]
+pub fn fp_code(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as Args);
+    match expand(&args) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(args: &Args) -> syn::Result<proc_macro2::TokenStream> {
+    let name = args.env.value();
+    let master = std::env::var(&name).map_err(|error| {
+        let reason = match error {
+            std::env::VarError::NotPresent => "is not set",
+            std::env::VarError::NotUnicode(_) => "is not valid UTF-8",
+        };
+        syn::Error::new(
+            args.env.span(),
+            format!("environment variable `{}` {} at compile time", name, reason),
+        )
+    })?;
+    let length: usize = args.length.base10_parse()?;
+
+    let password = flowerpassword_core::fp_code(&master, &args.key.value(), length)
+        .map_err(|error| syn::Error::new(args.length.span(), error))?;
+    let password = LitStr::new(&password, Span::call_site());
+    Ok(quote!(#password))
+}
@@ -0,0 +1,15 @@
+//! Expansion and compile-error tests for `fp_code!`
+
+/// Master password the test cases are compiled with
+const MASTER: &str = "test";
+
+#[test]
+fn test_ui() {
+    // trybuild compiles the cases in child processes, which inherit these
+    std::env::set_var("FP_MACROS_TEST_MASTER", MASTER);
+    std::env::remove_var("FP_MACROS_TEST_UNSET");
+
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
@@ -0,0 +1,8 @@
+use flowerpassword_macros::fp_code;
+
+const UNKNOWN: &str = fp_code!(env = "FP_MACROS_TEST_MASTER", site = "github.com", length = 16);
+const DUPLICATE: &str = fp_code!(env = "FP_MACROS_TEST_MASTER", key = "a", key = "b", length = 16);
+const NOT_A_STRING: &str = fp_code!(env = FP_MACROS_TEST_MASTER, key = "github.com", length = 16);
+const NEGATIVE: &str = fp_code!(env = "FP_MACROS_TEST_MASTER", key = "github.com", length = -1);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use flowerpassword_macros::fp_code;
+
+const PASSWORD: &str = fp_code!(env = "FP_MACROS_TEST_UNSET", key = "github.com", length = 16);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use flowerpassword_macros::fp_code;
+
+const PASSWORD: &str = fp_code!(master = "test", key = "github.com", length = 16);
+
+fn main() {}
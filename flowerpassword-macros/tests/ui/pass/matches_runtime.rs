@@ -0,0 +1,23 @@
+use flowerpassword_macros::fp_code;
+
+const GITHUB: &str = fp_code!(env = "FP_MACROS_TEST_MASTER", key = "github.com", length = 16);
+static DEVICE: &str = fp_code!(env = "FP_MACROS_TEST_MASTER", key = "device-42.local", length = 16);
+
+fn main() {
+    assert_eq!(GITHUB, "D04175F7A9c7Ab4a");
+
+    let master = "test";
+    assert_eq!(DEVICE, flowerpassword_core::fp_code(master, "device-42.local", 16).unwrap());
+    assert_eq!(
+        fp_code!(key = "example.com", length = 2, env = "FP_MACROS_TEST_MASTER",),
+        flowerpassword_core::fp_code(master, "example.com", 2).unwrap()
+    );
+    assert_eq!(
+        fp_code!(env = "FP_MACROS_TEST_MASTER", key = "网站.com", length = 32),
+        flowerpassword_core::fp_code(master, "网站.com", 32).unwrap()
+    );
+    assert_eq!(
+        fp_code!(env = "FP_MACROS_TEST_MASTER", key = "", length = 0x10),
+        flowerpassword_core::fp_code(master, "", 16).unwrap()
+    );
+}
@@ -0,0 +1,9 @@
+/* tslint:disable */
+/* eslint-disable */
+
+/* auto-generated by NAPI-RS */
+
+/** Generates a Flower Password on the calling thread */
+export function fpCode(password: string, key: string, length: number): string
+/** Generates a Flower Password off the event loop, resolving to the password */
+export function fpCodeAsync(password: string, key: string, length: number): Promise<string>
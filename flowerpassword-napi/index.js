@@ -0,0 +1,4 @@
+'use strict'
+
+// Loads the native module built by `npm run build` (flowerpassword.node)
+module.exports = require('./flowerpassword.node')
@@ -0,0 +1,56 @@
+//! Node.js bindings for Flower Password
+//!
+//! Exports `fpCode` and `fpCodeAsync` through N-API. Invalid lengths are thrown
+//! (or rejected) as a `RangeError` carrying the library's message, with `code` set
+//! to `"InvalidLength"`.
+
+use flowerpassword::{fp_code, FlowerPasswordError};
+use napi::bindgen_prelude::*;
+use napi::{Env, JsRangeError};
+use napi_derive::napi;
+
+/// Converts a library error into a JavaScript `RangeError`
+fn range_error(env: Env, error: FlowerPasswordError) -> Error {
+    let code = match error {
+        FlowerPasswordError::InvalidLength(_) => "InvalidLength",
+    };
+    let error = Error::new(code, error.to_string());
+    Error::from(JsRangeError::from(error).into_unknown(env))
+}
+
+/// Generates a Flower Password on the calling thread
+#[napi(js_name = "fpCode")]
+pub fn fp_code_sync(env: Env, password: String, key: String, length: u32) -> Result<String> {
+    fp_code(&password, &key, length as usize).map_err(|error| range_error(env, error))
+}
+
+/// Derivation run on the libuv thread pool for `fpCodeAsync`
+pub struct FpCodeTask {
+    password: String,
+    key: String,
+    length: u32,
+}
+
+#[napi]
+impl Task for FpCodeTask {
+    type Output = std::result::Result<String, FlowerPasswordError>;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(fp_code(&self.password, &self.key, self.length as usize))
+    }
+
+    fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        output.map_err(|error| range_error(env, error))
+    }
+}
+
+/// Generates a Flower Password off the event loop, resolving to the password
+#[napi(js_name = "fpCodeAsync", ts_return_type = "Promise<string>")]
+pub fn fp_code_async(password: String, key: String, length: u32) -> AsyncTask<FpCodeTask> {
+    AsyncTask::new(FpCodeTask {
+        password,
+        key,
+        length,
+    })
+}
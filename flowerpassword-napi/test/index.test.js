@@ -0,0 +1,86 @@
+'use strict'
+
+const assert = require('node:assert/strict')
+const { test } = require('node:test')
+
+const { fpCode, fpCodeAsync } = require('..')
+
+// A subset of the known-answer vectors in src/vectors.rs
+const VECTORS = [
+  ['password', 'key', 16, 'K3A2a66Bf88b628c'],
+  ['password', 'key', 2, 'K3'],
+  ['password', 'key', 32, 'K3A2a66Bf88b628c2Cd7cDA9958f6b26'],
+  ['test', 'github.com', 16, 'D04175F7A9c7Ab4a'],
+  ['mypassword', 'example.com', 12, 'K0CA12CecFFB'],
+  ['password', '', 16, 'eB3b1cA3D6B54c00'],
+  ['', '', 16, 'K930B0264e62DDFC'],
+]
+
+const invalidLength = (length) => ({
+  name: 'RangeError',
+  code: 'InvalidLength',
+  message: `Length must be between 2 and 32, got: ${length}`,
+})
+
+test('known vectors', () => {
+  for (const [password, key, length, expected] of VECTORS) {
+    assert.equal(fpCode(password, key, length), expected)
+  }
+})
+
+test('unicode inputs', () => {
+  assert.equal(fpCode('密码', '网站.com', 16), 'KFF7FEa7928bAAAa')
+  assert.equal(fpCode('🌸', 'emoji.example', 8), fpCode('🌸', 'emoji.example', 32).slice(0, 8))
+})
+
+test('async path matches sync', async () => {
+  for (const [password, key, length, expected] of VECTORS) {
+    assert.equal(await fpCodeAsync(password, key, length), expected)
+  }
+})
+
+test('async calls run concurrently', async () => {
+  const keys = Array.from({ length: 64 }, (_, i) => `site${i}.com`)
+  const passwords = await Promise.all(keys.map((key) => fpCodeAsync('test', key, 16)))
+  assert.deepEqual(
+    passwords,
+    keys.map((key) => fpCode('test', key, 16)),
+  )
+})
+
+test('invalid length throws RangeError', () => {
+  for (const length of [0, 1, 33, 100]) {
+    assert.throws(() => fpCode('password', 'key', length), invalidLength(length))
+    assert.throws(() => fpCode('password', 'key', length), RangeError)
+  }
+})
+
+test('errors leave the inputs out', async () => {
+  const sentinel = 'SECRET_SENTINEL'
+  const caught = (f) => {
+    try {
+      f()
+    } catch (error) {
+      return error
+    }
+    assert.fail('did not throw')
+  }
+  const errors = [
+    caught(() => fpCode(sentinel, sentinel, 33)),
+    await fpCodeAsync(sentinel, sentinel, 33).catch((error) => error),
+  ]
+  for (const error of errors) {
+    assert.ok(error instanceof RangeError)
+    assert.ok(!`${error.message} ${error.stack}`.includes(sentinel), error.message)
+  }
+})
+
+test('invalid length rejects with RangeError', async () => {
+  await assert.rejects(fpCodeAsync('password', 'key', 33), invalidLength(33))
+  await assert.rejects(fpCodeAsync('password', 'key', 1), RangeError)
+})
+
+test('non-string arguments throw', () => {
+  assert.throws(() => fpCode(42, 'key', 16))
+  assert.throws(() => fpCode('password', 'key', '16'))
+})
@@ -0,0 +1,79 @@
+//! `no_std` harness for flowerpassword
+//!
+//! This crate is `#![no_std]` and depends on flowerpassword with default features
+//! disabled (and `heapless` enabled), so it only builds if the core API needs
+//! nothing beyond `core` and `alloc`. `check-no-std.sh` checks it for a bare-metal
+//! target and runs its tests on the host; run it on its own
+//! (`cargo test -p flowerpassword-no-std`), since building it with the rest of the
+//! workspace unifies the `std` feature back in.
+
+#![no_std]
+
+extern crate alloc;
+
+use flowerpassword::vectors::self_test;
+
+/// Returns whether every known-answer vector passes, as a boot-time check
+pub fn self_test_passes() -> bool {
+    self_test().iter().all(|result| result.passed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use flowerpassword::policy::{shortest_length_satisfying, Policy, Requirement};
+    use flowerpassword::{fp_code, fp_code_heapless, fp_verify, FlowerPasswordError};
+
+    #[test]
+    fn test_self_test_passes() {
+        assert!(self_test_passes());
+    }
+
+    #[test]
+    fn test_fp_code() {
+        assert_eq!(
+            fp_code("test", "github.com", 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(fp_code("密码", "网站.com", 16).unwrap(), "KFF7FEa7928bAAAa");
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let error = fp_code("test", "github.com", 33).unwrap_err();
+        assert!(matches!(error, FlowerPasswordError::InvalidLength(33)));
+        assert_eq!(
+            error.to_string(),
+            "Length must be between 2 and 32, got: 33"
+        );
+    }
+
+    #[test]
+    fn test_fp_code_heapless() {
+        let password = fp_code_heapless(b"test", b"github.com", 16).unwrap();
+        assert_eq!(
+            password,
+            fp_code("test", "github.com", 16).unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn test_fp_verify() {
+        assert!(fp_verify("test", "github.com", "D04175F7A9c7Ab4a"));
+        assert!(!fp_verify("test", "github.com", "D04175F7A9c7Ab4b"));
+        assert!(!fp_verify("test", "github.com", "D"));
+    }
+
+    #[test]
+    fn test_policy() {
+        let password = fp_code("test", "github.com", 32).unwrap();
+        let policy = Policy::new(&Requirement::ALL);
+        let length = shortest_length_satisfying(&password, 2, &policy).unwrap();
+        assert!(policy.is_satisfied_by(&password[..length]));
+        assert_eq!(
+            policy.unmet("1234"),
+            [Requirement::Upper, Requirement::Lower]
+        );
+    }
+}
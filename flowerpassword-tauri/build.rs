@@ -0,0 +1,10 @@
+//! Build script for the Tauri plugin
+//!
+//! Generates an `allow-` and a `deny-` permission for each command into
+//! `permissions/autogenerated`, and the schema of `permissions/default.toml`.
+
+const COMMANDS: &[&str] = &["set_master", "fp_code", "fp_verify", "fp_fingerprint"];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
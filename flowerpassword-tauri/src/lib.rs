@@ -0,0 +1,272 @@
+//! Tauri v2 plugin for Flower Password
+//!
+//! Registers four commands under `plugin:flowerpassword|…`:
+//!
+//! - `set_master { password }` keeps the master password in the plugin's
+//!   managed state. It can be set once per run.
+//! - `fp_code { key, length }` derives a password with it.
+//! - `fp_verify { key, candidate }` checks a password against it.
+//! - `fp_fingerprint` returns the short fingerprint of it, as `fp fingerprint`
+//!   prints.
+//!
+//! The master password never goes back to the webview, and it is wiped when
+//! the app exits. Until `set_master`, the other commands fail with `Locked`.
+//! Errors reach JavaScript as `{ kind, message }`, where `kind` is `Locked`,
+//! `AlreadySet` or `InvalidLength`.
+//!
+//! ```no_run
+//! tauri::Builder::default()
+//!     .plugin(tauri_plugin_flowerpassword::init())
+//! #   ;
+//! ```
+//!
+//! The `default` permission allows all four commands; see `permissions/` for
+//! one `allow-` and `deny-` permission per command.
+
+use flowerpassword::FlowerPasswordError;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Mutex;
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::{Manager, RunEvent, Runtime};
+use zeroize::Zeroizing;
+
+/// Error type for the commands of the plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// No master password was set yet
+    Locked,
+    /// `set_master` was called a second time
+    AlreadySet,
+    /// `FlowerPasswordError::InvalidLength`: the length is not between 2 and 32
+    InvalidLength(usize),
+}
+
+impl Error {
+    /// Returns the `kind` seen by JavaScript
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Locked => "Locked",
+            Error::AlreadySet => "AlreadySet",
+            Error::InvalidLength(_) => "InvalidLength",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Locked => f.write_str("No master password set, call set_master first"),
+            Error::AlreadySet => f.write_str("The master password is already set"),
+            Error::InvalidLength(length) => {
+                write!(f, "{}", FlowerPasswordError::InvalidLength(*length))
+            }
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl From<FlowerPasswordError> for Error {
+    fn from(error: FlowerPasswordError) -> Self {
+        match error {
+            FlowerPasswordError::InvalidLength(length) => Error::InvalidLength(length),
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("Error", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// The master password, in the plugin's managed state
+#[derive(Default)]
+pub struct Master(Mutex<Option<Zeroizing<String>>>);
+
+impl Master {
+    /// Keeps `password`, unless a master password is already set
+    pub fn set(&self, password: String) -> Result<(), Error> {
+        let password = Zeroizing::new(password);
+        let mut master = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if master.is_some() {
+            return Err(Error::AlreadySet);
+        }
+        *master = Some(password);
+        Ok(())
+    }
+
+    /// Returns whether a master password is set
+    pub fn is_set(&self) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    /// Wipes the master password
+    pub fn wipe(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+
+    /// Calls `f` with the master password
+    fn with<T>(&self, f: impl FnOnce(&str) -> T) -> Result<T, Error> {
+        let master = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        master
+            .as_deref()
+            .map(|master| f(master))
+            .ok_or(Error::Locked)
+    }
+}
+
+impl fmt::Debug for Master {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Master")
+            .field("set", &self.is_set())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The command handlers, taking the [`Master`] state
+pub mod commands {
+    use super::{Error, Master};
+    use tauri::State;
+
+    /// Sets the master password for the rest of the run
+    #[tauri::command]
+    pub fn set_master(master: State<'_, Master>, password: String) -> Result<(), Error> {
+        master.set(password)
+    }
+
+    /// Derives the password for `key`
+    #[tauri::command]
+    pub fn fp_code(master: State<'_, Master>, key: String, length: usize) -> Result<String, Error> {
+        master
+            .with(|master| flowerpassword::fp_code(master, &key, length))?
+            .map_err(Error::from)
+    }
+
+    /// Returns whether `candidate` is the password for `key`
+    #[tauri::command]
+    pub fn fp_verify(
+        master: State<'_, Master>,
+        key: String,
+        candidate: String,
+    ) -> Result<bool, Error> {
+        master.with(|master| flowerpassword::fp_verify(master, &key, &candidate))
+    }
+
+    /// Returns the fingerprint of the master password
+    #[tauri::command]
+    pub fn fp_fingerprint(master: State<'_, Master>) -> Result<String, Error> {
+        master.with(flowerpassword::fp_fingerprint)
+    }
+}
+
+/// Returns the plugin, to pass to `tauri::Builder::plugin`
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("flowerpassword")
+        .invoke_handler(tauri::generate_handler![
+            commands::set_master,
+            commands::fp_code,
+            commands::fp_verify,
+            commands::fp_fingerprint,
+        ])
+        .setup(|app, _api| {
+            app.manage(Master::default());
+            Ok(())
+        })
+        .on_event(|app, event| {
+            if let RunEvent::Exit = event {
+                app.state::<Master>().wipe();
+            }
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::commands::*;
+    use super::*;
+    use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+    use tauri::App;
+
+    fn app() -> App<MockRuntime> {
+        mock_builder()
+            .plugin(init())
+            .build(mock_context(noop_assets()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_locked_until_set() {
+        let app = app();
+        let state = || app.state::<Master>();
+        assert_eq!(
+            fp_code(state(), "github.com".into(), 16),
+            Err(Error::Locked)
+        );
+        assert_eq!(
+            fp_verify(state(), "github.com".into(), "D04175F7A9c7Ab4a".into()),
+            Err(Error::Locked)
+        );
+        assert_eq!(fp_fingerprint(state()), Err(Error::Locked));
+
+        set_master(state(), "test".into()).unwrap();
+        assert_eq!(
+            fp_code(state(), "github.com".into(), 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(
+            fp_verify(state(), "github.com".into(), "D04175F7A9c7Ab4a".into()),
+            Ok(true)
+        );
+        assert_eq!(
+            fp_verify(state(), "github.com".into(), "D04175F7A9c7Ab4b".into()),
+            Ok(false)
+        );
+        assert_eq!(
+            fp_fingerprint(state()).unwrap(),
+            flowerpassword::fp_fingerprint("test")
+        );
+    }
+
+    #[test]
+    fn test_set_once() {
+        let app = app();
+        set_master(app.state(), "test".into()).unwrap();
+        assert_eq!(
+            set_master(app.state(), "other".into()),
+            Err(Error::AlreadySet)
+        );
+        assert_eq!(fp_code(app.state(), "key".into(), 8).unwrap(), "KAC5fEf0");
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let app = app();
+        set_master(app.state(), "test".into()).unwrap();
+        let error = fp_code(app.state(), "key".into(), 40).unwrap_err();
+        assert_eq!(error, Error::InvalidLength(40));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "InvalidLength",
+                "message": "Length must be between 2 and 32, got: 40"
+            })
+        );
+    }
+
+    #[test]
+    fn test_wipe() {
+        let master = Master::default();
+        master.set("test".into()).unwrap();
+        assert!(master.is_set());
+        assert_eq!(format!("{:?}", master), "Master { set: true, .. }");
+        master.wipe();
+        assert!(!master.is_set());
+        assert_eq!(master.with(|_| ()), Err(Error::Locked));
+    }
+}
@@ -0,0 +1,56 @@
+#ifndef FLOWERPASSWORD_H
+#define FLOWERPASSWORD_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The password was written to the output buffer
+#define FP_OK 0
+
+// The requested length is outside the valid range
+#define FP_ERROR_INVALID_LENGTH -1
+
+// The master password or key is not valid UTF-8
+#define FP_ERROR_INVALID_UTF8 -2
+
+// The output buffer cannot hold the password and its NUL terminator
+#define FP_ERROR_BUFFER_TOO_SMALL -3
+
+// A required pointer argument is null
+#define FP_ERROR_NULL_POINTER -4
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Generates a Flower Password into a caller-provided buffer
+//
+// On success the password and a NUL terminator are written to `out`, which must
+// have room for at least `length + 1` bytes. On failure `out` is left untouched
+// and `fp_last_error_message` describes the error.
+//
+// Returns `FP_OK` or one of the negative `FP_ERROR_*` codes.
+//
+// # Safety
+//
+// `password` and `key` must each be null or point to a NUL-terminated string,
+// and `out` must be null or point to at least `out_cap` writable bytes.
+int32_t fp_code_c(const char *password, const char *key, size_t length, char *out, size_t out_cap);
+
+// Returns the message of the calling thread's last failed call, or null
+//
+// The string is owned by the library and stays valid until the next call to
+// `fp_code_c` on the same thread; it must not be freed. A successful call
+// clears the message.
+const char *fp_last_error_message(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* FLOWERPASSWORD_H */
@@ -57,7 +57,8 @@ echo -e "Current version: ${GREEN}$CURRENT_VERSION${NC}"
 
 # Bump version
 echo -e "${BLUE}Bumping version ($BUMP_TYPE)...${NC}"
-cargo set-version --bump $BUMP_TYPE
+# The core and macros are released in lockstep, and requirements on the core follow
+cargo set-version --bump $BUMP_TYPE -p flowerpassword -p flowerpassword-core -p flowerpassword-macros
 
 # Get new version
 NEW_VERSION=$(grep -m 1 '^version = ' Cargo.toml | cut -d'"' -f2)
@@ -87,13 +88,13 @@ echo
 if [[ ! $REPLY =~ ^[Yy]$ ]]; then
   echo -e "${RED}Release cancelled${NC}"
   # Revert version change
-  git checkout Cargo.toml
+  git checkout Cargo.toml flowerpassword-core/Cargo.toml flowerpassword-macros/Cargo.toml
   exit 1
 fi
 
 # Commit and tag
 echo -e "${BLUE}Creating commit and tag...${NC}"
-git add Cargo.toml
+git add Cargo.toml flowerpassword-core/Cargo.toml flowerpassword-macros/Cargo.toml
 git commit -m "chore: bump version to $NEW_VERSION"
 git tag -a "v$NEW_VERSION" -m "Release v$NEW_VERSION"
 
@@ -0,0 +1,1535 @@
+//! Agent protocol
+//!
+//! An agent is a long-running process holding the master password in memory so
+//! that clients can request derived passwords without prompting. Both ends
+//! exchange frames made of a 4-byte big-endian length followed by that many
+//! bytes of JSON:
+//!
+//! ```text
+//! {"op":"derive","key":"github.com","length":16}   ->   {"result":"password","password":"..."}
+//! {"op":"status"}                                  ->   {"result":"status","locked":false}
+//! {"op":"lock"}                                    ->   {"result":"locked"}
+//! {"op":"stop"}                                    ->   {"result":"stopped"}
+//! ```
+//!
+//! [`AgentSession`] implements the agent side of the protocol independently of
+//! the transport, so servers only need to move frames between a socket and
+//! [`AgentSession::handle`], as [`serve_connection`] does. An agent listens with
+//! [`AgentListener`] and answers its clients one at a time with [`serve`].
+//! Clients use [`AgentClient`] over any
+//! [`Transport`]; [`SystemTransport`] connects to the [`Endpoint`] of the
+//! platform, a Unix domain socket or, on Windows, a named pipe per user:
+//!
+//! | Platform | Default endpoint                                   |
+//! |----------|----------------------------------------------------|
+//! | Unix     | `$XDG_RUNTIME_DIR/flowerpassword/agent.sock`       |
+//! | Windows  | `\\.\pipe\flowerpassword-agent-<SID>`               |
+//!
+//! An agent must make its endpoint accessible to its user only: a socket in a
+//! directory of mode 0700, or a pipe created with the security descriptor from
+//! [`pipe_security_descriptor`]. A client turned away by the access check gets
+//! an `AgentError::Io` of kind `PermissionDenied`.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::agent::{read_frame, write_frame, AgentSession, Request, Response};
+//!
+//! let mut session = AgentSession::new("test".to_string());
+//!
+//! // Client side
+//! let mut wire = Vec::new();
+//! let request = Request::Derive { key: "github.com".to_string(), length: 16, scheme: None };
+//! write_frame(&mut wire, &request).unwrap();
+//!
+//! // Agent side
+//! let request: Request = read_frame(&mut wire.as_slice()).unwrap().unwrap();
+//! let response = session.handle(request);
+//! assert_eq!(response, Response::Password { password: "D04175F7A9c7Ab4a".to_string() });
+//! ```
+
+use crate::master::MasterPassword;
+#[cfg(feature = "memlock")]
+use crate::memlock::LockStatus;
+use crate::{Scheme, DEFAULT_SCHEME};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Largest frame body accepted from the other end, in bytes
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// File name of the agent socket inside the runtime directory
+pub const SOCKET_FILE_NAME: &str = "agent.sock";
+
+/// Prefix of Windows named pipe paths
+pub const PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// Start of the agent pipe name, followed by the user's SID
+pub const PIPE_NAME_PREFIX: &str = "flowerpassword-agent-";
+
+/// How long an agent waits for a client to send or take a frame before
+/// dropping the connection
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error type for agent protocol operations
+#[derive(Debug)]
+pub enum AgentError {
+    /// Reading or writing the transport failed
+    Io(io::Error),
+    /// The peer announced a frame larger than [`MAX_FRAME_LEN`]
+    FrameTooLarge(usize),
+    /// The frame body is not a valid protocol message
+    Malformed(String),
+    /// Another agent already answers at the endpoint, named here
+    AlreadyRunning(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AgentError::Io(e) => write!(f, "Agent connection failed: {}", e),
+            AgentError::FrameTooLarge(len) => write!(
+                f,
+                "Frame of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_FRAME_LEN
+            ),
+            AgentError::Malformed(message) => write!(f, "Malformed agent message: {}", message),
+            AgentError::AlreadyRunning(endpoint) => {
+                write!(f, "An agent already answers at {}", endpoint)
+            }
+        }
+    }
+}
+
+impl Error for AgentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AgentError::Io(e) => Some(e),
+            AgentError::FrameTooLarge(_)
+            | AgentError::Malformed(_)
+            | AgentError::AlreadyRunning(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for AgentError {
+    fn from(e: io::Error) -> Self {
+        AgentError::Io(e)
+    }
+}
+
+/// Request sent from a client to the agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Request {
+    /// Derive the password for a key
+    Derive {
+        /// Domain or service identifier
+        key: String,
+        /// Output password length
+        length: usize,
+        /// Derivation scheme, [`DEFAULT_SCHEME`] if not given
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::scheme_name"
+        )]
+        scheme: Option<Scheme>,
+    },
+    /// Forget the master password
+    Lock,
+    /// Report whether the agent holds a master password
+    Status,
+    /// Forget the master password and exit
+    Stop,
+}
+
+/// Response sent from the agent to a client
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum Response {
+    /// Derived password for a `derive` request
+    Password {
+        /// The generated password
+        password: String,
+    },
+    /// The agent no longer holds a master password
+    Locked,
+    /// The agent forgot the master password and is exiting
+    Stopped,
+    /// Current state of the agent
+    Status {
+        /// Whether the agent has been locked
+        locked: bool,
+    },
+    /// The request could not be served
+    Error {
+        /// Description of the problem
+        message: String,
+    },
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Password { .. } => f
+                .debug_struct("Password")
+                .field("password", &"****")
+                .finish(),
+            Response::Locked => f.write_str("Locked"),
+            Response::Stopped => f.write_str("Stopped"),
+            Response::Status { locked } => {
+                f.debug_struct("Status").field("locked", locked).finish()
+            }
+            Response::Error { message } => {
+                f.debug_struct("Error").field("message", message).finish()
+            }
+        }
+    }
+}
+
+/// Agent-side state: the master password, until the session is locked
+///
+/// With the `memlock` feature the master password is kept in locked memory.
+pub struct AgentSession {
+    master: Option<MasterPassword>,
+}
+
+impl AgentSession {
+    /// Starts an unlocked session holding `master`
+    pub fn new(master: String) -> Self {
+        AgentSession {
+            master: Some(MasterPassword::new(master)),
+        }
+    }
+
+    /// Returns `true` once the session has been locked
+    pub fn is_locked(&self) -> bool {
+        self.master.is_none()
+    }
+
+    /// Returns whether the master password is locked in RAM, or `None` once the
+    /// session has been locked
+    #[cfg(feature = "memlock")]
+    pub fn memory_status(&self) -> Option<LockStatus> {
+        self.master.as_ref().map(MasterPassword::status)
+    }
+
+    /// Forgets the master password
+    pub fn lock(&mut self) {
+        self.master = None;
+    }
+
+    /// Serves a single request
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Derive {
+                key,
+                length,
+                scheme,
+            } => match &self.master {
+                None => Response::Error {
+                    message: "Agent is locked".to_string(),
+                },
+                Some(master) => {
+                    match scheme
+                        .unwrap_or(DEFAULT_SCHEME)
+                        .fp_code(master.as_str(), &key, length)
+                    {
+                        Ok(password) => Response::Password { password },
+                        Err(e) => Response::Error {
+                            message: e.to_string(),
+                        },
+                    }
+                }
+            },
+            Request::Lock => {
+                self.lock();
+                Response::Locked
+            }
+            Request::Status => Response::Status {
+                locked: self.is_locked(),
+            },
+            Request::Stop => {
+                self.lock();
+                Response::Stopped
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AgentSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AgentSession")
+            .field("locked", &self.is_locked())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encodes a message as a complete frame
+///
+/// # Errors
+///
+/// Returns `AgentError::FrameTooLarge` if the encoded message exceeds
+/// [`MAX_FRAME_LEN`].
+pub fn encode_frame<T: Serialize>(message: &T) -> Result<Vec<u8>, AgentError> {
+    let body = serde_json::to_vec(message).map_err(|e| AgentError::Malformed(e.to_string()))?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(AgentError::FrameTooLarge(body.len()));
+    }
+
+    let mut frame = Vec::with_capacity(4 + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+/// Writes a message as a single frame
+///
+/// # Errors
+///
+/// Returns `AgentError::Io` if writing fails, or any error from [`encode_frame`].
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> Result<(), AgentError> {
+    writer.write_all(&encode_frame(message)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a single frame and decodes its message
+///
+/// Returns `Ok(None)` if the peer closed the connection between frames.
+///
+/// # Errors
+///
+/// Returns `AgentError::Io` if the connection fails or closes mid-frame,
+/// `AgentError::FrameTooLarge` for oversized frames, and `AgentError::Malformed`
+/// if the body is not a valid message.
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, AgentError> {
+    let mut header = [0u8; 4];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(AgentError::FrameTooLarge(len));
+    }
+
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| AgentError::Malformed(e.to_string()))
+}
+
+/// Returns the default agent socket path, `$XDG_RUNTIME_DIR/flowerpassword/agent.sock`
+///
+/// Returns `None` if `XDG_RUNTIME_DIR` is not set.
+pub fn default_socket_path() -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+    Some(
+        PathBuf::from(runtime_dir)
+            .join("flowerpassword")
+            .join(SOCKET_FILE_NAME),
+    )
+}
+
+/// Where an agent listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A Unix domain socket
+    Unix(PathBuf),
+    /// A Windows named pipe, as its full `\\.\pipe\...` path
+    NamedPipe(String),
+}
+
+impl Endpoint {
+    /// Returns the endpoint of the current user's agent on this platform
+    ///
+    /// Returns `None` if it cannot be found out: without `XDG_RUNTIME_DIR` on
+    /// Unix, if the user's SID cannot be read on Windows, and elsewhere.
+    pub fn default_for_user() -> Option<Self> {
+        #[cfg(unix)]
+        return default_socket_path().map(Endpoint::Unix);
+        #[cfg(windows)]
+        return windows::current_user_sid().map(|sid| Endpoint::NamedPipe(pipe_name(&sid)));
+        #[cfg(not(any(unix, windows)))]
+        return None;
+    }
+
+    /// Returns `endpoint` if given, as for an `--agent-endpoint` option, and
+    /// [`default_for_user`](Self::default_for_user) otherwise
+    pub fn resolve(endpoint: Option<&str>) -> Option<Self> {
+        match endpoint {
+            Some(endpoint) => endpoint.parse().ok(),
+            None => Self::default_for_user(),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = AgentError;
+
+    /// Parses a pipe path starting with [`PIPE_PREFIX`], or a socket path
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(PIPE_PREFIX) {
+            Some("") => Err(AgentError::Malformed("Empty pipe name".to_string())),
+            Some(_) => Ok(Endpoint::NamedPipe(s.to_string())),
+            None if s.is_empty() => Err(AgentError::Malformed("Empty endpoint".to_string())),
+            None => Ok(Endpoint::Unix(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endpoint::Unix(path) => write!(f, "{}", path.display()),
+            Endpoint::NamedPipe(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Returns the path of the agent pipe of the user with the string SID `sid`
+pub fn pipe_name(sid: &str) -> String {
+    format!("{}{}{}", PIPE_PREFIX, PIPE_NAME_PREFIX, sid)
+}
+
+/// Returns the SDDL security descriptor for an agent pipe of the user `sid`
+///
+/// It grants that user full access and nobody else any, with inherited entries
+/// blocked, so other tokens, including other sessions of administrators, are
+/// refused by the pipe's access check.
+pub fn pipe_security_descriptor(sid: &str) -> String {
+    format!("D:P(A;;GA;;;{})", sid)
+}
+
+/// A byte stream to an agent
+pub trait Duplex: Read + Write {}
+
+impl<T: Read + Write> Duplex for T {}
+
+/// A way to connect to an agent
+pub trait Transport {
+    /// The connection this transport makes
+    type Stream: Read + Write;
+
+    /// Connects to the agent at `endpoint`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::Io` if the endpoint cannot be reached, of kind
+    /// `PermissionDenied` if its access check refuses this user, and of kind
+    /// `Unsupported` for endpoints this transport does not handle.
+    fn connect(&mut self, endpoint: &Endpoint) -> Result<Self::Stream, AgentError>;
+}
+
+/// [`Transport`] to Unix domain sockets on Unix and named pipes on Windows
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTransport;
+
+impl Transport for SystemTransport {
+    type Stream = Box<dyn Duplex>;
+
+    fn connect(&mut self, endpoint: &Endpoint) -> Result<Self::Stream, AgentError> {
+        match endpoint {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+            // A pipe client is a file opened for reading and writing
+            #[cfg(windows)]
+            Endpoint::NamedPipe(name) => Ok(Box::new(
+                std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(name)?,
+            )),
+            endpoint => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is not an agent endpoint on this platform", endpoint),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Client side of the protocol, over any connection
+pub struct AgentClient<S> {
+    stream: S,
+}
+
+impl<S: Read + Write> AgentClient<S> {
+    /// Wraps an open connection
+    pub fn new(stream: S) -> Self {
+        AgentClient { stream }
+    }
+
+    /// Connects to `endpoint` with `transport`
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`Transport::connect`].
+    pub fn connect<T: Transport<Stream = S>>(
+        transport: &mut T,
+        endpoint: &Endpoint,
+    ) -> Result<Self, AgentError> {
+        Ok(Self::new(transport.connect(endpoint)?))
+    }
+
+    /// Sends `request` and waits for the response
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::Io` if the agent closes the connection instead of
+    /// answering, or any error from [`write_frame`] and [`read_frame`].
+    pub fn request(&mut self, request: &Request) -> Result<Response, AgentError> {
+        write_frame(&mut self.stream, request)?;
+        read_frame(&mut self.stream)?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof).into())
+    }
+
+    /// Waits for the agent to close the connection, as it does after
+    /// [`Request::Stop`] once it no longer listens
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::Io` if reading fails, and `AgentError::Malformed`
+    /// if the agent sends anything more.
+    pub fn wait_closed(mut self) -> Result<(), AgentError> {
+        match self.stream.read(&mut [0])? {
+            0 => Ok(()),
+            _ => Err(AgentError::Malformed(
+                "Unexpected data after the last response".to_string(),
+            )),
+        }
+    }
+}
+
+impl<S> fmt::Debug for AgentClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AgentClient").finish_non_exhaustive()
+    }
+}
+
+/// Serves the requests of one connection until the client closes it or asks
+/// the agent to stop, returning whether it did
+///
+/// # Errors
+///
+/// Returns any error from [`read_frame`] and [`write_frame`], which ends the
+/// connection; a malformed request is answered with [`Response::Error`] first.
+pub fn serve_connection<S: Read + Write>(
+    session: &mut AgentSession,
+    stream: &mut S,
+) -> Result<bool, AgentError> {
+    loop {
+        let request = match read_frame(stream) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(false),
+            Err(AgentError::Malformed(message)) => {
+                let response = Response::Error {
+                    message: message.clone(),
+                };
+                let _ = write_frame(stream, &response);
+                return Err(AgentError::Malformed(message));
+            }
+            Err(e) => return Err(e),
+        };
+        let stop = request == Request::Stop;
+        write_frame(stream, &session.handle(request))?;
+        if stop {
+            return Ok(true);
+        }
+    }
+}
+
+/// Answers the clients of `listener`, one connection at a time, until one asks
+/// the agent to stop
+///
+/// A client that misbehaves or stalls for [`CLIENT_TIMEOUT`] only loses its
+/// own connection. The listener is dropped before the connection that stopped
+/// the agent is closed, so that client may start another agent right away.
+///
+/// # Errors
+///
+/// Returns any error from [`AgentListener::accept`] other than an interrupted
+/// or aborted connection.
+pub fn serve(listener: AgentListener, session: &mut AgentSession) -> Result<(), AgentError> {
+    loop {
+        let mut stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(AgentError::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::Interrupted | io::ErrorKind::ConnectionAborted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+        if let Ok(true) = serve_connection(session, &mut stream) {
+            drop(listener);
+            return Ok(());
+        }
+    }
+}
+
+/// The agent's end of an [`Endpoint`], accessible to the current user only
+///
+/// A Unix socket is created with mode 0600, in a directory created with mode
+/// 0700 if missing, and removed when the listener is dropped. A named pipe gets
+/// the ACL of [`pipe_security_descriptor`] for the current user and refuses
+/// remote clients.
+pub struct AgentListener {
+    endpoint: Endpoint,
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+    #[cfg(windows)]
+    pipe: windows::PipeListener,
+}
+
+impl AgentListener {
+    /// Listens on `endpoint`, replacing a socket left behind by an agent that
+    /// is gone
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::AlreadyRunning` if an agent answers at `endpoint`,
+    /// and `AgentError::Io` if it cannot be listened on, of kind `Unsupported`
+    /// for endpoints of another platform.
+    pub fn bind(endpoint: &Endpoint) -> Result<Self, AgentError> {
+        match endpoint {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Ok(AgentListener {
+                endpoint: endpoint.clone(),
+                listener: bind_socket(path)?,
+            }),
+            #[cfg(windows)]
+            Endpoint::NamedPipe(name) => Ok(AgentListener {
+                endpoint: endpoint.clone(),
+                pipe: windows::PipeListener::bind(name)?,
+            }),
+            endpoint => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is not an agent endpoint on this platform", endpoint),
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the endpoint listened on
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Waits for the next client, whose connection times out after
+    /// [`CLIENT_TIMEOUT`] without traffic
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::Io` if accepting fails.
+    pub fn accept(&self) -> Result<Box<dyn Duplex>, AgentError> {
+        #[cfg(unix)]
+        {
+            let (stream, _) = self.listener.accept()?;
+            stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+            stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(windows)]
+        return self.pipe.accept();
+        #[cfg(not(any(unix, windows)))]
+        Err(io::Error::from(io::ErrorKind::Unsupported).into())
+    }
+}
+
+impl Drop for AgentListener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Endpoint::Unix(path) = &self.endpoint {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl fmt::Debug for AgentListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AgentListener")
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Binds a socket of mode 0600 at `path`
+#[cfg(unix)]
+fn bind_socket(path: &std::path::Path) -> Result<std::os::unix::net::UnixListener, AgentError> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(AgentError::AlreadyRunning(path.display().to_string()));
+    }
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::{pipe_security_descriptor, AgentError, Duplex, CLIENT_TIMEOUT};
+    use std::cell::Cell;
+    use std::ffi::OsStr;
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr;
+    use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+    use std::thread::JoinHandle;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ACCESS_DENIED, ERROR_NO_DATA, ERROR_PIPE_CONNECTED,
+        HANDLE, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+        SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY,
+        TOKEN_USER,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Memory::LocalFree;
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+    use windows_sys::Win32::System::IO::CancelIoEx;
+
+    /// The buffer size of each pipe instance, enough for any frame in one go
+    const BUFFER_SIZE: u32 = 4096;
+
+    /// The server end of an agent pipe, with an instance waiting for a client
+    pub(super) struct PipeListener {
+        name: Vec<u16>,
+        descriptor: PSECURITY_DESCRIPTOR,
+        next: Cell<HANDLE>,
+    }
+
+    impl PipeListener {
+        /// Creates the first instance of the pipe `name`, accessible to the
+        /// current user only
+        pub(super) fn bind(name: &str) -> Result<Self, AgentError> {
+            let sid = current_user_sid().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    "Cannot read the SID of the current user",
+                )
+            })?;
+            let sddl = wide(&pipe_security_descriptor(&sid));
+            let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+            // SAFETY: sddl is NUL-terminated; the descriptor is freed on drop
+            let ok = unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1,
+                    &mut descriptor,
+                    ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let mut listener = PipeListener {
+                name: wide(name),
+                descriptor,
+                next: Cell::new(INVALID_HANDLE_VALUE),
+            };
+            // Only the first instance may be created while no other exists
+            match listener.instance(FILE_FLAG_FIRST_PIPE_INSTANCE) {
+                Ok(handle) => *listener.next.get_mut() = handle,
+                Err(e) if e.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+                    return Err(AgentError::AlreadyRunning(name.to_string()))
+                }
+                Err(e) => return Err(e.into()),
+            }
+            Ok(listener)
+        }
+
+        /// Creates another instance of the pipe, with `flags` added
+        fn instance(&self, flags: u32) -> io::Result<HANDLE> {
+            let attributes = SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: self.descriptor,
+                bInheritHandle: 0,
+            };
+            // SAFETY: name is NUL-terminated and attributes outlive the call
+            let handle = unsafe {
+                CreateNamedPipeW(
+                    self.name.as_ptr(),
+                    PIPE_ACCESS_DUPLEX | flags,
+                    PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                    PIPE_UNLIMITED_INSTANCES,
+                    BUFFER_SIZE,
+                    BUFFER_SIZE,
+                    0,
+                    &attributes,
+                )
+            };
+            match handle {
+                INVALID_HANDLE_VALUE => Err(io::Error::last_os_error()),
+                handle => Ok(handle),
+            }
+        }
+
+        /// Waits for a client on the waiting instance, and creates the next
+        pub(super) fn accept(&self) -> Result<Box<dyn Duplex>, AgentError> {
+            let handle = self.next.get();
+            // SAFETY: handle is an instance of this pipe, owned by self
+            if unsafe { ConnectNamedPipe(handle, ptr::null_mut()) } == 0 {
+                // SAFETY: reads the error of the call above
+                let error = unsafe { GetLastError() };
+                // A client that connected first is as good; one that left
+                // already leaves an instance to replace
+                if error != ERROR_PIPE_CONNECTED {
+                    self.next.set(self.instance(0)?);
+                    // SAFETY: the failed instance is not used afterwards
+                    unsafe { CloseHandle(handle) };
+                    return Err(match error {
+                        ERROR_NO_DATA => io::Error::from(io::ErrorKind::ConnectionAborted),
+                        error => io::Error::from_raw_os_error(error as i32),
+                    }
+                    .into());
+                }
+            }
+            self.next.set(self.instance(0)?);
+            // SAFETY: the connected instance is handed over to the stream
+            let file = unsafe { File::from_raw_handle(handle as _) };
+            Ok(Box::new(PipeStream::new(file, handle)))
+        }
+    }
+
+    // SAFETY: the handle and the descriptor are owned by the listener alone
+    unsafe impl Send for PipeListener {}
+
+    impl Drop for PipeListener {
+        fn drop(&mut self) {
+            // SAFETY: both were created by bind and are not used afterwards
+            unsafe {
+                CloseHandle(self.next.get());
+                LocalFree(self.descriptor as isize);
+            }
+        }
+    }
+
+    /// A connected pipe instance whose pending reads and writes are cancelled
+    /// after [`CLIENT_TIMEOUT`] without traffic, as socket timeouts do on Unix
+    struct PipeStream {
+        file: File,
+        activity: Option<Sender<()>>,
+        watchdog: Option<JoinHandle<()>>,
+    }
+
+    impl PipeStream {
+        fn new(file: File, handle: HANDLE) -> Self {
+            let (activity, idle) = mpsc::channel();
+            let watchdog = std::thread::spawn(move || loop {
+                match idle.recv_timeout(CLIENT_TIMEOUT) {
+                    Ok(()) => continue,
+                    Err(RecvTimeoutError::Timeout) => {
+                        // SAFETY: the stream joins this thread before closing
+                        // the handle
+                        unsafe { CancelIoEx(handle, ptr::null()) };
+                        return;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            });
+            PipeStream {
+                file,
+                activity: Some(activity),
+                watchdog: Some(watchdog),
+            }
+        }
+
+        fn touch(&self) {
+            if let Some(activity) = &self.activity {
+                let _ = activity.send(());
+            }
+        }
+    }
+
+    impl Read for PipeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.file.read(buf);
+            self.touch();
+            read
+        }
+    }
+
+    impl Write for PipeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = self.file.write(buf);
+            self.touch();
+            written
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.file.flush()
+        }
+    }
+
+    impl Drop for PipeStream {
+        fn drop(&mut self) {
+            drop(self.activity.take());
+            if let Some(watchdog) = self.watchdog.take() {
+                let _ = watchdog.join();
+            }
+        }
+    }
+
+    /// Returns `s` as a NUL-terminated UTF-16 string
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    /// Returns the string SID of the user the process runs as
+    pub(super) fn current_user_sid() -> Option<String> {
+        let mut token: HANDLE = 0;
+        // SAFETY: the pseudo handle of the current process is always valid
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return None;
+        }
+        let sid = token_user_sid(token);
+        // SAFETY: token was opened above and is not used afterwards
+        unsafe { CloseHandle(token) };
+        sid
+    }
+
+    fn token_user_sid(token: HANDLE) -> Option<String> {
+        let mut len = 0u32;
+        // SAFETY: a null buffer of length 0 only asks for the needed length
+        unsafe { GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len) };
+        // u64 elements keep TOKEN_USER and its pointers aligned
+        let mut buffer = vec![0u64; (len as usize + 7) / 8];
+        // SAFETY: buffer holds at least len bytes
+        let ok = unsafe {
+            GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len)
+        };
+        if ok == 0 {
+            return None;
+        }
+        // SAFETY: GetTokenInformation filled the buffer with a TOKEN_USER
+        let user = unsafe { &*buffer.as_ptr().cast::<TOKEN_USER>() };
+        let mut wide = ptr::null_mut();
+        // SAFETY: the SID points into buffer, which outlives the call
+        if unsafe { ConvertSidToStringSidW(user.User.Sid, &mut wide) } == 0 {
+            return None;
+        }
+        // SAFETY: ConvertSidToStringSidW returned a NUL-terminated string
+        let sid = unsafe {
+            let len = (0..).take_while(|&i| *wide.add(i) != 0).count();
+            String::from_utf16_lossy(std::slice::from_raw_parts(wide, len))
+        };
+        // SAFETY: the string was allocated with LocalAlloc by the call above
+        unsafe { LocalFree(wide as isize) };
+        Some(sid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// Reader that hands out at most one byte per call
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn derive(key: &str, length: usize) -> Request {
+        Request::Derive {
+            key: key.to_string(),
+            length,
+            scheme: None,
+        }
+    }
+
+    #[test]
+    fn test_request_wire_format() {
+        let json = serde_json::to_string(&derive("github.com", 16)).unwrap();
+        assert_eq!(json, r#"{"op":"derive","key":"github.com","length":16}"#);
+        assert_eq!(
+            serde_json::to_string(&Request::Lock).unwrap(),
+            r#"{"op":"lock"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::Status).unwrap(),
+            r#"{"op":"status"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::Stop).unwrap(),
+            r#"{"op":"stop"}"#
+        );
+    }
+
+    #[test]
+    fn test_derive_scheme() {
+        let request = Request::Derive {
+            key: "github.com".to_string(),
+            length: 16,
+            scheme: Some(Scheme::V1),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"op":"derive","key":"github.com","length":16,"scheme":"v1"}"#
+        );
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+        assert!(serde_json::from_str::<Request>(
+            r#"{"op":"derive","key":"github.com","length":16,"scheme":"v3"}"#
+        )
+        .is_err());
+
+        let mut session = AgentSession::new("test".to_string());
+        assert_eq!(
+            session.handle(request),
+            Response::Password {
+                password: "D04175F7A9c7Ab4a".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_response_wire_format() {
+        let json = serde_json::to_string(&Response::Status { locked: true }).unwrap();
+        assert_eq!(json, r#"{"result":"status","locked":true}"#);
+        assert_eq!(
+            serde_json::to_string(&Response::Stopped).unwrap(),
+            r#"{"result":"stopped"}"#
+        );
+        let parsed: Response =
+            serde_json::from_str(r#"{"result":"password","password":"K3"}"#).unwrap();
+        assert_eq!(
+            parsed,
+            Response::Password {
+                password: "K3".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        let frame = encode_frame(&Request::Lock).unwrap();
+        assert_eq!(&frame[..4], &[0, 0, 0, 13]);
+        assert_eq!(&frame[4..], br#"{"op":"lock"}"#);
+    }
+
+    #[test]
+    fn test_frames_round_trip_back_to_back() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, &derive("github.com", 16)).unwrap();
+        write_frame(&mut wire, &Request::Status).unwrap();
+
+        let mut reader = wire.as_slice();
+        let first: Option<Request> = read_frame(&mut reader).unwrap();
+        let second: Option<Request> = read_frame(&mut reader).unwrap();
+        let end: Option<Request> = read_frame(&mut reader).unwrap();
+        assert_eq!(first, Some(derive("github.com", 16)));
+        assert_eq!(second, Some(Request::Status));
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn test_frame_split_across_reads() {
+        let wire = encode_frame(&derive("github.com", 16)).unwrap();
+        let request: Option<Request> = read_frame(&mut Trickle(&wire)).unwrap();
+        assert_eq!(request, Some(derive("github.com", 16)));
+    }
+
+    #[test]
+    fn test_truncated_frame() {
+        let wire = encode_frame(&Request::Status).unwrap();
+        let result: Result<Option<Request>, _> = read_frame(&mut &wire[..wire.len() - 1]);
+        assert!(matches!(result, Err(AgentError::Io(_))));
+
+        let result: Result<Option<Request>, _> = read_frame(&mut &wire[..2]);
+        assert!(matches!(result, Err(AgentError::Io(_))));
+    }
+
+    #[test]
+    fn test_oversized_frame_is_rejected() {
+        let header = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+        let result: Result<Option<Request>, _> = read_frame(&mut &header[..]);
+        assert!(matches!(result, Err(AgentError::FrameTooLarge(len)) if len == MAX_FRAME_LEN + 1));
+
+        let huge = derive(&"k".repeat(MAX_FRAME_LEN), 16);
+        assert!(matches!(
+            encode_frame(&huge),
+            Err(AgentError::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_body() {
+        let mut wire = 5u32.to_be_bytes().to_vec();
+        wire.extend_from_slice(b"hello");
+        let result: Result<Option<Request>, _> = read_frame(&mut wire.as_slice());
+        assert!(matches!(result, Err(AgentError::Malformed(_))));
+
+        let mut wire = 12u32.to_be_bytes().to_vec();
+        wire.extend_from_slice(br#"{"op":"fly"}"#);
+        let result: Result<Option<Request>, _> = read_frame(&mut wire.as_slice());
+        assert!(matches!(result, Err(AgentError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_session_derive() {
+        let mut session = AgentSession::new("password".to_string());
+        assert_eq!(
+            session.handle(derive("key", 16)),
+            Response::Password {
+                password: "K3A2a66Bf88b628c".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_session_invalid_length() {
+        let mut session = AgentSession::new("password".to_string());
+        assert_eq!(
+            session.handle(derive("key", 40)),
+            Response::Error {
+                message: "Length must be between 2 and 32, got: 40".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_session_lock() {
+        let mut session = AgentSession::new("password".to_string());
+        assert_eq!(
+            session.handle(Request::Status),
+            Response::Status { locked: false }
+        );
+        assert_eq!(session.handle(Request::Lock), Response::Locked);
+        assert_eq!(
+            session.handle(Request::Status),
+            Response::Status { locked: true }
+        );
+        assert_eq!(
+            session.handle(derive("key", 16)),
+            Response::Error {
+                message: "Agent is locked".to_string()
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "memlock")]
+    fn test_session_memory_status() {
+        let mut session = AgentSession::new("password".to_string());
+        let status = session.memory_status().unwrap();
+        if status != LockStatus::Locked {
+            eprintln!("mlock refused here: {}", status);
+        }
+        assert_eq!(
+            session.handle(derive("github.com", 16)),
+            Response::Password {
+                password: fp_code("password", "github.com", 16).unwrap()
+            }
+        );
+        session.lock();
+        assert_eq!(session.memory_status(), None);
+    }
+
+    /// One connection to an in-process agent, answering the requests sent so far
+    struct MockStream {
+        session: Rc<RefCell<AgentSession>>,
+        sent: Vec<u8>,
+        replies: VecDeque<u8>,
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.replies.is_empty() {
+                let sent = std::mem::take(&mut self.sent);
+                let mut sent = sent.as_slice();
+                while let Some(request) = read_frame(&mut sent)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                {
+                    let response = self.session.borrow_mut().handle(request);
+                    self.replies.extend(encode_frame(&response).unwrap());
+                }
+            }
+            self.replies.read(buf)
+        }
+    }
+
+    /// An agent listening on `endpoint`, with a pipe ACL admitting `allowed`
+    struct MockTransport {
+        session: Rc<RefCell<AgentSession>>,
+        endpoint: Endpoint,
+        allowed: bool,
+    }
+
+    impl MockTransport {
+        fn new(endpoint: &Endpoint, allowed: bool) -> Self {
+            MockTransport {
+                session: Rc::new(RefCell::new(AgentSession::new("password".to_string()))),
+                endpoint: endpoint.clone(),
+                allowed,
+            }
+        }
+    }
+
+    impl Transport for MockTransport {
+        type Stream = MockStream;
+
+        fn connect(&mut self, endpoint: &Endpoint) -> Result<Self::Stream, AgentError> {
+            if *endpoint != self.endpoint {
+                return Err(io::Error::from(io::ErrorKind::NotFound).into());
+            }
+            if !self.allowed {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied).into());
+            }
+            Ok(MockStream {
+                session: Rc::clone(&self.session),
+                sent: Vec::new(),
+                replies: VecDeque::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let sid = "S-1-5-21-1004336348-1177238915-682003330-512";
+        let pipe = pipe_name(sid);
+        assert_eq!(
+            pipe,
+            r"\\.\pipe\flowerpassword-agent-S-1-5-21-1004336348-1177238915-682003330-512"
+        );
+        assert_eq!(
+            pipe.parse::<Endpoint>().unwrap(),
+            Endpoint::NamedPipe(pipe.clone())
+        );
+        assert_eq!(
+            "/run/user/1000/flowerpassword/agent.sock"
+                .parse::<Endpoint>()
+                .unwrap(),
+            Endpoint::Unix(PathBuf::from("/run/user/1000/flowerpassword/agent.sock"))
+        );
+        assert!(PIPE_PREFIX.parse::<Endpoint>().is_err());
+        assert!("".parse::<Endpoint>().is_err());
+        assert_eq!(Endpoint::NamedPipe(pipe.clone()).to_string(), pipe);
+        assert_eq!(
+            Endpoint::resolve(Some("/tmp/agent.sock")),
+            Some(Endpoint::Unix(PathBuf::from("/tmp/agent.sock")))
+        );
+        assert_eq!(
+            pipe_security_descriptor(sid),
+            format!("D:P(A;;GA;;;{})", sid)
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_default_endpoint_is_the_users_pipe() {
+        let Some(Endpoint::NamedPipe(name)) = Endpoint::default_for_user() else {
+            panic!("no pipe endpoint");
+        };
+        assert!(
+            name.starts_with(r"\\.\pipe\flowerpassword-agent-S-1-"),
+            "{}",
+            name
+        );
+    }
+
+    #[test]
+    fn test_client_over_a_transport() {
+        let endpoint = Endpoint::NamedPipe(pipe_name("S-1-5-21-1"));
+        let mut transport = MockTransport::new(&endpoint, true);
+        let mut client = AgentClient::connect(&mut transport, &endpoint).unwrap();
+        assert_eq!(
+            client.request(&derive("key", 16)).unwrap(),
+            Response::Password {
+                password: "K3A2a66Bf88b628c".to_string()
+            }
+        );
+        assert_eq!(client.request(&Request::Lock).unwrap(), Response::Locked);
+        assert_eq!(
+            client.request(&Request::Status).unwrap(),
+            Response::Status { locked: true }
+        );
+    }
+
+    #[test]
+    fn test_access_denied() {
+        let endpoint = Endpoint::NamedPipe(pipe_name("S-1-5-21-1"));
+        let mut transport = MockTransport::new(&endpoint, false);
+        let error = AgentClient::connect(&mut transport, &endpoint).unwrap_err();
+        assert!(matches!(&error, AgentError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied));
+
+        let other = Endpoint::NamedPipe(pipe_name("S-1-5-21-2"));
+        transport.allowed = true;
+        let error = AgentClient::connect(&mut transport, &other).unwrap_err();
+        assert!(matches!(&error, AgentError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+    }
+
+    #[test]
+    fn test_system_transport() {
+        #[cfg(unix)]
+        {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join(SOCKET_FILE_NAME);
+            let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+            let server = std::thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut session = AgentSession::new("password".to_string());
+                while let Some(request) = read_frame(&mut stream).unwrap() {
+                    write_frame(&mut stream, &session.handle(request)).unwrap();
+                }
+            });
+            let endpoint = Endpoint::Unix(path);
+            let mut client = AgentClient::connect(&mut SystemTransport, &endpoint).unwrap();
+            assert_eq!(
+                client.request(&derive("key", 16)).unwrap(),
+                Response::Password {
+                    password: "K3A2a66Bf88b628c".to_string()
+                }
+            );
+            drop(client);
+            server.join().unwrap();
+        }
+        #[cfg(not(windows))]
+        {
+            let pipe = Endpoint::NamedPipe(pipe_name("S-1-5-21-1"));
+            let result = SystemTransport.connect(&pipe);
+            assert!(
+                matches!(result, Err(AgentError::Io(e)) if e.kind() == io::ErrorKind::Unsupported)
+            );
+        }
+    }
+
+    /// A connection whose client sent `input` up front
+    struct Recorded<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl Read for Recorded<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Recorded<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn responses(mut output: &[u8]) -> Vec<Response> {
+        std::iter::from_fn(|| read_frame(&mut output).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_serve_connection_until_stop() {
+        let mut input = Vec::new();
+        for request in [
+            Request::Status,
+            derive("key", 16),
+            Request::Stop,
+            Request::Status,
+        ] {
+            write_frame(&mut input, &request).unwrap();
+        }
+        let mut session = AgentSession::new("password".to_string());
+        let mut stream = Recorded {
+            input: &input,
+            output: Vec::new(),
+        };
+        assert!(serve_connection(&mut session, &mut stream).unwrap());
+        assert_eq!(
+            responses(&stream.output),
+            [
+                Response::Status { locked: false },
+                Response::Password {
+                    password: "K3A2a66Bf88b628c".to_string()
+                },
+                Response::Stopped,
+            ]
+        );
+        assert!(session.is_locked());
+
+        // A client that just closes the connection leaves the agent running
+        let mut session = AgentSession::new("password".to_string());
+        let mut stream = Recorded {
+            input: &[],
+            output: Vec::new(),
+        };
+        assert!(!serve_connection(&mut session, &mut stream).unwrap());
+        assert!(!session.is_locked());
+    }
+
+    #[test]
+    fn test_serve_connection_answers_malformed_requests() {
+        let mut input = 6u32.to_be_bytes().to_vec();
+        input.extend_from_slice(br#"{"op"}"#);
+        let mut session = AgentSession::new("password".to_string());
+        let mut stream = Recorded {
+            input: &input,
+            output: Vec::new(),
+        };
+        let error = serve_connection(&mut session, &mut stream).unwrap_err();
+        assert!(matches!(error, AgentError::Malformed(_)));
+        assert!(matches!(
+            responses(&stream.output).as_slice(),
+            [Response::Error { .. }]
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_listener() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flowerpassword").join(SOCKET_FILE_NAME);
+        let endpoint = Endpoint::Unix(path.clone());
+        // A socket left behind by an agent that is gone is replaced
+        std::fs::create_dir(path.parent().unwrap()).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let listener = AgentListener::bind(&endpoint).unwrap();
+        assert_eq!(listener.endpoint(), &endpoint);
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert!(matches!(
+            AgentListener::bind(&endpoint),
+            Err(AgentError::AlreadyRunning(_))
+        ));
+
+        let server = std::thread::spawn(move || {
+            let mut session = AgentSession::new("password".to_string());
+            serve(listener, &mut session).unwrap();
+        });
+        let mut client = AgentClient::connect(&mut SystemTransport, &endpoint).unwrap();
+        assert_eq!(
+            client.request(&derive("key", 16)).unwrap(),
+            Response::Password {
+                password: "K3A2a66Bf88b628c".to_string()
+            }
+        );
+        // Only one client is served at a time
+        drop(client);
+        let mut client = AgentClient::connect(&mut SystemTransport, &endpoint).unwrap();
+        assert_eq!(client.request(&Request::Stop).unwrap(), Response::Stopped);
+        client.wait_closed().unwrap();
+        assert!(!path.exists());
+        server.join().unwrap();
+
+        let nested = dir.path().join("new").join(SOCKET_FILE_NAME);
+        let _listener = AgentListener::bind(&Endpoint::Unix(nested.clone())).unwrap();
+        assert_eq!(mode(nested.parent().unwrap()), 0o700);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_pipe_listener() {
+        let name = pipe_name(&format!("test-{}", std::process::id()));
+        let endpoint = Endpoint::NamedPipe(name);
+        let listener = AgentListener::bind(&endpoint).unwrap();
+        assert!(matches!(
+            AgentListener::bind(&endpoint),
+            Err(AgentError::AlreadyRunning(_))
+        ));
+
+        let server = std::thread::spawn(move || {
+            let mut session = AgentSession::new("password".to_string());
+            serve(listener, &mut session).unwrap();
+        });
+        let mut client = AgentClient::connect(&mut SystemTransport, &endpoint).unwrap();
+        assert_eq!(
+            client.request(&derive("key", 16)).unwrap(),
+            Response::Password {
+                password: "K3A2a66Bf88b628c".to_string()
+            }
+        );
+        drop(client);
+        let mut client = AgentClient::connect(&mut SystemTransport, &endpoint).unwrap();
+        assert_eq!(client.request(&Request::Stop).unwrap(), Response::Stopped);
+        client.wait_closed().unwrap();
+        server.join().unwrap();
+        // The pipe is gone with the listener, so another agent may start
+        drop(AgentListener::bind(&endpoint).unwrap());
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let session = AgentSession::new("hunter2".to_string());
+        assert!(!format!("{:?}", session).contains("hunter2"));
+
+        let response = Response::Password {
+            password: "K3A2a66Bf88b628c".to_string(),
+        };
+        assert!(!format!("{:?}", response).contains("K3A2a66Bf88b628c"));
+    }
+}
@@ -0,0 +1,689 @@
+//! Batch derivation
+//!
+//! [`fp_code_batch`] derives one password per key for a single master password.
+//! With the `rayon` feature, [`fp_code_batch_par`] spreads the same work over
+//! rayon's thread pool and returns exactly the same passwords, in input order.
+//! With the `simd` feature, both hash several keys at once in vector lanes.
+//!
+//! [`fp_code_batch_into`] and [`fp_code_batch_into_slice`] pack the passwords
+//! back-to-back into one caller buffer instead, with no allocation per entry, and
+//! describe where each one sits with a [`BatchLayout`].
+//!
+//! [`fp_code_stream`] holds nothing at all: it hands each password to a callback
+//! as it is derived, so inputs of any size run in constant memory.
+
+use crate::FlowerPasswordError;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use flowerpassword_core::{fp_code_into, validate_length, MAX_LENGTH};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Derives a password for each key, with the length already validated
+#[cfg(feature = "simd")]
+fn derive(password: &str, keys: &[&str], length: usize) -> Vec<String> {
+    flowerpassword_core::fp_code_batch_simd(password, keys, length).expect("length is validated")
+}
+
+/// Derives a password for each key, with the length already validated
+#[cfg(not(feature = "simd"))]
+fn derive(password: &str, keys: &[&str], length: usize) -> Vec<String> {
+    keys.iter()
+        .map(|key| crate::fp_code(password, key, length).expect("length is validated"))
+        .collect()
+}
+
+/// Keys per rayon task in [`fp_code_batch_par`]
+///
+/// One derivation takes a few microseconds, so tasks of a single key would spend
+/// more time being scheduled than deriving.
+#[cfg(feature = "rayon")]
+const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// Generates a Flower Password for each key, in the order of `keys`
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32,
+/// even when `keys` is empty.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::fp_code_batch;
+///
+/// let passwords = fp_code_batch("test", &["github.com", "example.com"], 16).unwrap();
+/// assert_eq!(passwords[0], "D04175F7A9c7Ab4a");
+/// ```
+pub fn fp_code_batch(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+) -> Result<Vec<String>, FlowerPasswordError> {
+    validate_length(length)?;
+    Ok(derive(password, keys, length))
+}
+
+/// Generates a Flower Password for each key on rayon's thread pool
+///
+/// Returns the same passwords as [`fp_code_batch`], in the order of `keys`. Keys
+/// are handed to threads 256 at a time; use [`fp_code_batch_par_chunked`] to
+/// choose another chunk size.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::fp_code_batch_par;
+///
+/// let keys: Vec<String> = (0..1000).map(|i| format!("site{}.example", i)).collect();
+/// let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+/// let passwords = fp_code_batch_par("test", &keys, 16).unwrap();
+/// assert_eq!(passwords.len(), 1000);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn fp_code_batch_par(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+) -> Result<Vec<String>, FlowerPasswordError> {
+    fp_code_batch_par_chunked(password, keys, length, DEFAULT_CHUNK_SIZE)
+}
+
+/// Like [`fp_code_batch_par`], with `chunk_size` keys per rayon task
+///
+/// A `chunk_size` of 0 is treated as 1.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+#[cfg(feature = "rayon")]
+pub fn fp_code_batch_par_chunked(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+    chunk_size: usize,
+) -> Result<Vec<String>, FlowerPasswordError> {
+    validate_length(length)?;
+    Ok(keys
+        .par_chunks(chunk_size.max(1))
+        .flat_map_iter(|chunk| derive(password, chunk, length))
+        .collect())
+}
+
+/// Where the passwords of a packed batch sit in its buffer
+///
+/// Every password of a batch has the same length, so password `i` occupies
+/// `length` bytes starting at `start + i * length`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLayout {
+    start: usize,
+    length: usize,
+    count: usize,
+}
+
+impl BatchLayout {
+    /// Bytes needed to pack `count` passwords of `length` characters
+    pub fn required_len(count: usize, length: usize) -> usize {
+        count.saturating_mul(length)
+    }
+
+    /// Offset of the first password in the buffer
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Length of every password
+    pub fn password_len(&self) -> usize {
+        self.length
+    }
+
+    /// Number of passwords
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns `true` if the batch has no passwords
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Byte range of all the passwords together
+    pub fn bytes(&self) -> Range<usize> {
+        self.start..self.start + self.count * self.length
+    }
+
+    /// Byte range of password `index`, or `None` if it is out of bounds
+    pub fn range(&self, index: usize) -> Option<Range<usize>> {
+        (index < self.count).then(|| {
+            let start = self.start + index * self.length;
+            start..start + self.length
+        })
+    }
+
+    /// Password `index` in `buffer`, or `None` if it is out of bounds
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is not the one this layout was returned for, or its
+    /// passwords were overwritten with bytes that are not UTF-8.
+    pub fn get<'a>(&self, buffer: &'a [u8], index: usize) -> Option<&'a str> {
+        self.range(index).map(|range| password(&buffer[range]))
+    }
+
+    /// Iterates over the passwords in `buffer`, in the order of the keys
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`BatchLayout::get`].
+    pub fn iter<'a>(&self, buffer: &'a [u8]) -> impl ExactSizeIterator<Item = &'a str> + 'a {
+        // `chunks_exact` panics on a zero size; no valid length is zero
+        buffer[self.bytes()]
+            .chunks_exact(self.length.max(1))
+            .map(password)
+    }
+}
+
+/// Views a packed password as a `str`
+fn password(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes).expect("packed passwords are ASCII")
+}
+
+/// Error type for [`fp_code_batch_into_slice`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    /// Length parameter is outside the valid range
+    InvalidLength(usize),
+    /// The buffer cannot hold every password
+    BufferTooSmall {
+        /// Bytes needed, as given by [`BatchLayout::required_len`]
+        required: usize,
+        /// Bytes the buffer has
+        available: usize,
+    },
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchError::InvalidLength(len) => {
+                write!(f, "{}", FlowerPasswordError::InvalidLength(*len))
+            }
+            BatchError::BufferTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "Buffer of {} bytes is too small for the batch, which needs {}",
+                available, required
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BatchError {}
+
+impl From<FlowerPasswordError> for BatchError {
+    fn from(e: FlowerPasswordError) -> Self {
+        match e {
+            FlowerPasswordError::InvalidLength(len) => BatchError::InvalidLength(len),
+        }
+    }
+}
+
+/// Derives each key's password into consecutive `length`-byte chunks of `out`
+fn pack(password: &str, keys: &[&str], length: usize, out: &mut [u8]) {
+    for (key, chunk) in keys.iter().zip(out.chunks_exact_mut(length)) {
+        fp_code_into(password, key, chunk).expect("length is validated");
+    }
+}
+
+/// Appends a Flower Password for each key to `out`, back-to-back
+///
+/// Grows `out` by [`BatchLayout::required_len`] bytes and returns where each
+/// password went. Apart from that growth nothing is allocated, however many
+/// keys there are.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32,
+/// leaving `out` unchanged.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::fp_code_batch_into;
+///
+/// let mut buffer = Vec::new();
+/// let layout = fp_code_batch_into("test", &["github.com", "example.com"], 16, &mut buffer).unwrap();
+/// assert_eq!(buffer.len(), 32);
+/// assert_eq!(layout.get(&buffer, 0), Some("D04175F7A9c7Ab4a"));
+/// for password in layout.iter(&buffer) {
+///     assert_eq!(password.len(), 16);
+/// }
+/// ```
+pub fn fp_code_batch_into(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+    out: &mut Vec<u8>,
+) -> Result<BatchLayout, FlowerPasswordError> {
+    validate_length(length)?;
+    let start = out.len();
+    out.resize(start + BatchLayout::required_len(keys.len(), length), 0);
+    pack(password, keys, length, &mut out[start..]);
+    Ok(BatchLayout {
+        start,
+        length,
+        count: keys.len(),
+    })
+}
+
+/// Writes a Flower Password for each key to the start of `out`, back-to-back
+///
+/// `out` must hold at least [`BatchLayout::required_len`] bytes; anything after
+/// that is left untouched.
+///
+/// # Errors
+///
+/// Returns `BatchError::InvalidLength` if length is not between 2 and 32, or
+/// `BatchError::BufferTooSmall` if `out` is too short. Nothing is written if
+/// either is returned.
+pub fn fp_code_batch_into_slice(
+    password: &str,
+    keys: &[&str],
+    length: usize,
+    out: &mut [u8],
+) -> Result<BatchLayout, BatchError> {
+    validate_length(length)?;
+    let required = BatchLayout::required_len(keys.len(), length);
+    if out.len() < required {
+        return Err(BatchError::BufferTooSmall {
+            required,
+            available: out.len(),
+        });
+    }
+    pack(password, keys, length, &mut out[..required]);
+    Ok(BatchLayout {
+        start: 0,
+        length,
+        count: keys.len(),
+    })
+}
+
+/// Derives each key into one reused buffer and passes `(key, password)` to `sink`
+///
+/// Stops at the first error from `sink`. Returns the number of keys derived.
+fn stream<K, E>(
+    password: &str,
+    keys: impl Iterator<Item = K>,
+    length: usize,
+    mut sink: impl FnMut(&str, &str) -> Result<(), E>,
+) -> Result<u64, E>
+where
+    K: AsRef<str>,
+{
+    let mut buffer = [0; MAX_LENGTH];
+    let out = &mut buffer[..length];
+    let mut count = 0;
+    for key in keys {
+        let key = key.as_ref();
+        fp_code_into(password, key, out).expect("length is validated");
+        sink(key, core::str::from_utf8(out).expect("passwords are ASCII"))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Derives a Flower Password for each key and hands `(key, password)` to `sink`
+///
+/// Keys are consumed lazily and every password is written to the same internal
+/// buffer, so memory use does not grow with the number of keys. `sink` only
+/// borrows the password for the duration of the call; keep it with `to_owned()`
+/// if needed. Returns the number of keys derived.
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32,
+/// before reading any key.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::fp_code_stream;
+///
+/// let keys = (0..1000).map(|i| format!("host{}.example", i));
+/// let mut upper = 0;
+/// let count = fp_code_stream("test", keys, 16, |_key, password| {
+///     upper += password.bytes().filter(u8::is_ascii_uppercase).count();
+/// })
+/// .unwrap();
+/// assert_eq!(count, 1000);
+/// ```
+pub fn fp_code_stream<K, F>(
+    password: &str,
+    keys: impl Iterator<Item = K>,
+    length: usize,
+    mut sink: F,
+) -> Result<u64, FlowerPasswordError>
+where
+    K: AsRef<str>,
+    F: FnMut(&str, &str),
+{
+    validate_length(length)?;
+    stream(password, keys, length, |key, password| {
+        sink(key, password);
+        Ok(())
+    })
+}
+
+/// Writes `key\tpassword\n` for each key to `writer`, as [`fp_code_stream`] derives them
+///
+/// Keys are written as given, so they should not contain tabs or line breaks if
+/// the output is to be split again. Each line is a separate write; wrap unbuffered
+/// writers such as files in a [`std::io::BufWriter`]. Returns the number of lines
+/// written.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidInput` if length is not between 2 and 32, or
+/// the first error from `writer`, which stops the run.
+#[cfg(feature = "std")]
+pub fn fp_code_stream_to_writer<K, W>(
+    password: &str,
+    keys: impl Iterator<Item = K>,
+    length: usize,
+    mut writer: W,
+) -> io::Result<u64>
+where
+    K: AsRef<str>,
+    W: io::Write,
+{
+    validate_length(length).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    stream(password, keys, length, |key, password| {
+        writeln!(writer, "{}\t{}", key, password)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+    use crate::vectors::KNOWN_ANSWERS;
+    #[cfg(feature = "rayon")]
+    use alloc::format;
+
+    #[test]
+    fn test_batch_matches_fp_code() {
+        let keys: Vec<&str> = KNOWN_ANSWERS.iter().map(|v| v.key).collect();
+        let passwords = fp_code_batch("test", &keys, 16).unwrap();
+        assert_eq!(passwords.len(), keys.len());
+        for (key, password) in keys.iter().zip(&passwords) {
+            assert_eq!(*password, fp_code("test", key, 16).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_batch_known_answers() {
+        for vector in KNOWN_ANSWERS {
+            assert_eq!(
+                fp_code_batch(vector.password, &[vector.key, vector.key], vector.length).unwrap(),
+                [vector.expected, vector.expected],
+                "{:?}",
+                vector
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_invalid_length() {
+        assert!(matches!(
+            fp_code_batch("test", &[], 33),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+        assert!(fp_code_batch("test", &[], 16).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_matches_sequential() {
+        // xorshift64, for a reproducible corpus without a dependency
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let keys: Vec<String> = (0..50_000)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                format!("{:x}-{}.example", state, i)
+            })
+            .collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+        let sequential = fp_code_batch("master", &keys, 16).unwrap();
+        assert_eq!(fp_code_batch_par("master", &keys, 16).unwrap(), sequential);
+        for chunk_size in [0, 1, 7, 100_000] {
+            assert_eq!(
+                fp_code_batch_par_chunked("master", &keys[..2000], 16, chunk_size).unwrap(),
+                sequential[..2000]
+            );
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_invalid_length() {
+        assert!(matches!(
+            fp_code_batch_par("test", &["a"], 1),
+            Err(FlowerPasswordError::InvalidLength(1))
+        ));
+    }
+
+    #[test]
+    fn test_batch_into_layout() {
+        let keys = ["github.com", "example.com", "x"];
+        let mut buffer = b"prefix".to_vec();
+        let layout = fp_code_batch_into("test", &keys, 12, &mut buffer).unwrap();
+        assert_eq!(layout.start(), 6);
+        assert_eq!(layout.password_len(), 12);
+        assert_eq!(layout.len(), 3);
+        assert!(!layout.is_empty());
+        assert_eq!(layout.bytes(), 6..42);
+        assert_eq!(layout.range(0), Some(6..18));
+        assert_eq!(layout.range(2), Some(30..42));
+        assert_eq!(layout.range(3), None);
+        assert_eq!(buffer.len(), 42);
+        assert_eq!(&buffer[..6], b"prefix");
+
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(
+                layout.get(&buffer, i).unwrap(),
+                fp_code("test", key, 12).unwrap()
+            );
+        }
+        assert_eq!(layout.get(&buffer, 3), None);
+        let passwords: Vec<&str> = layout.iter(&buffer).collect();
+        assert_eq!(passwords, fp_code_batch("test", &keys, 12).unwrap());
+        assert_eq!(layout.iter(&buffer).len(), 3);
+
+        // A second batch appends after the first
+        let second = fp_code_batch_into("test", &["y"], 2, &mut buffer).unwrap();
+        assert_eq!(second.bytes(), 42..44);
+        assert_eq!(
+            second.get(&buffer, 0).unwrap(),
+            fp_code("test", "y", 2).unwrap()
+        );
+        assert_eq!(layout.iter(&buffer).count(), 3);
+    }
+
+    #[test]
+    fn test_batch_into_every_length() {
+        let keys: Vec<&str> = KNOWN_ANSWERS.iter().map(|v| v.key).collect();
+        let mut buffer = Vec::new();
+        for length in 2..=32 {
+            buffer.clear();
+            let layout = fp_code_batch_into("master", &keys, length, &mut buffer).unwrap();
+            assert_eq!(buffer.len(), keys.len() * length);
+            for (key, password) in keys.iter().zip(layout.iter(&buffer)) {
+                assert_eq!(password, fp_code("master", key, length).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_into_errors() {
+        let mut buffer = b"kept".to_vec();
+        assert!(matches!(
+            fp_code_batch_into("test", &["a"], 33, &mut buffer),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+        assert_eq!(buffer, b"kept");
+
+        let layout = fp_code_batch_into("test", &[], 16, &mut buffer).unwrap();
+        assert!(layout.is_empty());
+        assert_eq!(layout.iter(&buffer).count(), 0);
+        assert_eq!(buffer, b"kept");
+    }
+
+    #[test]
+    fn test_batch_into_slice() {
+        let keys = ["github.com", "example.com"];
+        let mut out = [b'#'; 40];
+        let layout = fp_code_batch_into_slice("test", &keys, 16, &mut out).unwrap();
+        assert_eq!(layout.bytes(), 0..32);
+        assert_eq!(layout.get(&out, 0), Some("D04175F7A9c7Ab4a"));
+        assert_eq!(
+            layout.get(&out, 1).unwrap(),
+            fp_code("test", "example.com", 16).unwrap()
+        );
+        assert_eq!(&out[32..], b"########");
+
+        // Exactly the required size
+        let mut exact = [0; 32];
+        assert_eq!(
+            fp_code_batch_into_slice("test", &keys, 16, &mut exact).unwrap(),
+            layout
+        );
+        assert_eq!(exact[..], out[..32]);
+    }
+
+    #[test]
+    fn test_batch_into_slice_too_small() {
+        let mut out = [b'#'; 31];
+        let err = fp_code_batch_into_slice("test", &["a", "b"], 16, &mut out).unwrap_err();
+        assert_eq!(
+            err,
+            BatchError::BufferTooSmall {
+                required: 32,
+                available: 31
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Buffer of 31 bytes is too small for the batch, which needs 32"
+        );
+        // Nothing is written, not even the first password that would fit
+        assert!(out.iter().all(|&byte| byte == b'#'));
+
+        assert_eq!(
+            fp_code_batch_into_slice("test", &["a"], 1, &mut out),
+            Err(BatchError::InvalidLength(1))
+        );
+        assert_eq!(
+            BatchError::InvalidLength(1).to_string(),
+            "Length must be between 2 and 32, got: 1"
+        );
+        assert_eq!(BatchLayout::required_len(usize::MAX, 2), usize::MAX);
+    }
+
+    #[test]
+    fn test_stream_pairs() {
+        let mut seen = Vec::new();
+        let keys = KNOWN_ANSWERS.iter().map(|v| v.key);
+        let count = fp_code_stream("test", keys, 16, |key, password| {
+            seen.push((String::from(key), String::from(password)));
+        })
+        .unwrap();
+        assert_eq!(count, KNOWN_ANSWERS.len() as u64);
+        assert_eq!(seen.len(), KNOWN_ANSWERS.len());
+        for (vector, (key, password)) in KNOWN_ANSWERS.iter().zip(&seen) {
+            assert_eq!(key, vector.key);
+            assert_eq!(*password, fp_code("test", key, 16).unwrap());
+        }
+        assert_eq!(
+            seen[0].1,
+            fp_code("test", KNOWN_ANSWERS[0].key, 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stream_reuses_buffer_cleanly() {
+        // Every password overwrites the whole buffer, whatever came before it
+        for length in [2, 17, 32] {
+            let keys = (0..500u32).map(|i| alloc::format!("{}", i * 7919));
+            let count = fp_code_stream("master", keys, length, |key, password| {
+                assert_eq!(password.len(), length);
+                assert_eq!(password, fp_code("master", key, length).unwrap());
+            })
+            .unwrap();
+            assert_eq!(count, 500);
+        }
+    }
+
+    #[test]
+    fn test_stream_counts_and_errors() {
+        let mut calls = 0;
+        assert_eq!(
+            fp_code_stream("test", core::iter::empty::<&str>(), 16, |_, _| calls += 1).unwrap(),
+            0
+        );
+        let keys = core::iter::repeat("a").take(12_345);
+        assert_eq!(
+            fp_code_stream("test", keys, 16, |_, _| calls += 1).unwrap(),
+            12_345
+        );
+        assert_eq!(calls, 12_345);
+
+        // The iterator is not touched when the length is invalid
+        let mut keys = ["a", "b"].into_iter();
+        assert!(matches!(
+            fp_code_stream("test", &mut keys, 1, |_, _| unreachable!()),
+            Err(FlowerPasswordError::InvalidLength(1))
+        ));
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_to_writer() {
+        let mut out = Vec::new();
+        let count =
+            fp_code_stream_to_writer("test", ["github.com", "例え.jp"].iter(), 16, &mut out)
+                .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            alloc::format!(
+                "github.com\tD04175F7A9c7Ab4a\n例え.jp\t{}\n",
+                fp_code("test", "例え.jp", 16).unwrap()
+            )
+        );
+
+        let err = fp_code_stream_to_writer("test", ["a"].iter(), 33, &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Length must be between 2 and 32, got: 33");
+
+        // A failing writer stops the run at its first error
+        let mut room = [0u8; 40];
+        let err = fp_code_stream_to_writer("test", ["a", "b", "c"].iter(), 16, &mut room[..])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+}
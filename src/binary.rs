@@ -0,0 +1,575 @@
+//! Length-prefixed binary protocol for high-throughput embedding
+//!
+//! A parent process that needs millions of derivations can skip JSON and talk
+//! to [`serve`] over a pipe with small fixed-layout frames. Integers are
+//! big-endian. The master password is sent once, in a handshake:
+//!
+//! ```text
+//! Handshake (client -> server)       Handshake reply (server -> client)
+//! +---------+-----------+--------+   +--------+
+//! | version | len (u16) | master |   | status |
+//! +---------+-----------+--------+   +--------+
+//!      1          2        len            1
+//!
+//! Request (client -> server)                Response (server -> client)
+//! +---------+-----------+-----+--------+    +--------+-----+----------+
+//! | version | len (u16) | key | length |    | status | len | password |
+//! +---------+-----------+-----+--------+    +--------+-----+----------+
+//!      1          2       len      1             1      1      len
+//! ```
+//!
+//! `version` is [`VERSION`]. The master password and keys are UTF-8 and at most
+//! [`MAX_FIELD_LEN`] bytes. `status` is a [`Status`]; the password bytes follow
+//! only for [`Status::Ok`], and `len` is 0 otherwise. After a reply with
+//! [`Status::UnsupportedVersion`] the server closes the connection, since it cannot
+//! tell where the next frame starts.
+//!
+//! Requests may be pipelined: the server buffers its responses and flushes
+//! whenever it has consumed all the input that has arrived so far.
+
+use crate::{fp_code, FlowerPasswordError};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+/// Protocol version carried by every client frame
+pub const VERSION: u8 = 1;
+
+/// Largest master password or key accepted, in bytes
+pub const MAX_FIELD_LEN: usize = 4096;
+
+/// Status byte of a response or handshake reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    /// The password follows
+    Ok = 0,
+    /// The requested length is not between 2 and 32
+    InvalidLength = 1,
+    /// The key or master password is not valid UTF-8
+    InvalidUtf8 = 2,
+    /// The frame's version is not [`VERSION`]; the connection is closed
+    UnsupportedVersion = 3,
+}
+
+impl Status {
+    /// Parses a status byte
+    pub fn from_byte(byte: u8) -> Option<Status> {
+        match byte {
+            0 => Some(Status::Ok),
+            1 => Some(Status::InvalidLength),
+            2 => Some(Status::InvalidUtf8),
+            3 => Some(Status::UnsupportedVersion),
+            _ => None,
+        }
+    }
+}
+
+/// Error type for the binary protocol
+#[derive(Debug)]
+pub enum BinaryError {
+    /// Reading or writing the transport failed
+    Io(io::Error),
+    /// The input ended in the middle of a frame
+    Truncated,
+    /// A frame carries a version other than [`VERSION`]
+    UnsupportedVersion(u8),
+    /// A master password or key is longer than [`MAX_FIELD_LEN`]
+    FieldTooLarge(usize),
+    /// A response carries a status byte that is not a [`Status`]
+    UnknownStatus(u8),
+    /// A master password or password is not valid UTF-8
+    InvalidUtf8,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryError::Io(e) => write!(f, "Binary protocol failed: {}", e),
+            BinaryError::Truncated => write!(f, "Input ended in the middle of a frame"),
+            BinaryError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported protocol version {}, expected {}",
+                version, VERSION
+            ),
+            BinaryError::FieldTooLarge(len) => write!(
+                f,
+                "Field of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_FIELD_LEN
+            ),
+            BinaryError::UnknownStatus(status) => write!(f, "Unknown status byte {}", status),
+            BinaryError::InvalidUtf8 => write!(f, "Frame field is not valid UTF-8"),
+        }
+    }
+}
+
+impl Error for BinaryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BinaryError::Io(e) => Some(e),
+            BinaryError::Truncated
+            | BinaryError::UnsupportedVersion(_)
+            | BinaryError::FieldTooLarge(_)
+            | BinaryError::UnknownStatus(_)
+            | BinaryError::InvalidUtf8 => None,
+        }
+    }
+}
+
+impl From<io::Error> for BinaryError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            BinaryError::Truncated
+        } else {
+            BinaryError::Io(e)
+        }
+    }
+}
+
+/// Reads the version byte, returning `None` at a clean end of input
+fn read_version<R: Read>(reader: &mut R) -> Result<Option<u8>, BinaryError> {
+    let mut version = [0];
+    loop {
+        match reader.read(&mut version) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if version[0] != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version[0]));
+    }
+    Ok(Some(version[0]))
+}
+
+/// Reads a `u16` length and that many bytes into `buf`
+fn read_field<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> Result<(), BinaryError> {
+    let mut len = [0; 2];
+    reader.read_exact(&mut len)?;
+    let len = usize::from(u16::from_be_bytes(len));
+    if len > MAX_FIELD_LEN {
+        return Err(BinaryError::FieldTooLarge(len));
+    }
+    buf.resize(len, 0);
+    reader.read_exact(buf)?;
+    Ok(())
+}
+
+fn write_field<W: Write>(writer: &mut W, field: &[u8]) -> Result<(), BinaryError> {
+    if field.len() > MAX_FIELD_LEN {
+        return Err(BinaryError::FieldTooLarge(field.len()));
+    }
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&(field.len() as u16).to_be_bytes())?;
+    writer.write_all(field)?;
+    Ok(())
+}
+
+/// Writes the handshake carrying the master password
+///
+/// # Errors
+///
+/// Returns `BinaryError::FieldTooLarge` if `master` is longer than
+/// [`MAX_FIELD_LEN`], or `BinaryError::Io` if writing fails.
+pub fn write_handshake<W: Write>(writer: &mut W, master: &str) -> Result<(), BinaryError> {
+    write_field(writer, master.as_bytes())
+}
+
+/// Writes a derivation request
+///
+/// # Errors
+///
+/// Returns `BinaryError::FieldTooLarge` if `key` is longer than [`MAX_FIELD_LEN`],
+/// or `BinaryError::Io` if writing fails.
+pub fn write_request<W: Write>(writer: &mut W, key: &str, length: u8) -> Result<(), BinaryError> {
+    write_field(writer, key.as_bytes())?;
+    writer.write_all(&[length])?;
+    Ok(())
+}
+
+/// Reads a response, returning its status and, for [`Status::Ok`], the password
+///
+/// # Errors
+///
+/// Returns `BinaryError::Truncated` if the input ends mid-frame, or
+/// `BinaryError::UnknownStatus` for a status byte that is not a [`Status`].
+pub fn read_response<R: Read>(reader: &mut R) -> Result<(Status, String), BinaryError> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let status = Status::from_byte(header[0]).ok_or(BinaryError::UnknownStatus(header[0]))?;
+    let mut password = vec![0; usize::from(header[1])];
+    reader.read_exact(&mut password)?;
+    let password = String::from_utf8(password).map_err(|_| BinaryError::InvalidUtf8)?;
+    Ok((status, password))
+}
+
+/// Reads a handshake reply
+///
+/// # Errors
+///
+/// Returns `BinaryError::Truncated` if the input ends first, or
+/// `BinaryError::UnknownStatus` for a status byte that is not a [`Status`].
+pub fn read_handshake_reply<R: Read>(reader: &mut R) -> Result<Status, BinaryError> {
+    let mut status = [0];
+    reader.read_exact(&mut status)?;
+    Status::from_byte(status[0]).ok_or(BinaryError::UnknownStatus(status[0]))
+}
+
+/// Server side of a connection
+///
+/// Reuses one buffer for the key of every request, so serving allocates only for
+/// the derivation itself.
+pub struct Server<R> {
+    reader: BufReader<R>,
+    master: String,
+    key: Vec<u8>,
+}
+
+impl<R: Read> Server<R> {
+    /// Reads the handshake from `reader` and answers it on `writer`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handshake is malformed. For a wrong version the
+    /// [`Status::UnsupportedVersion`] reply is written first; for a master password
+    /// that is not UTF-8, [`Status::InvalidUtf8`].
+    pub fn handshake<W: Write>(reader: R, writer: &mut W) -> Result<Self, BinaryError> {
+        let mut reader = BufReader::new(reader);
+        let mut master = Vec::new();
+        let handshake = read_version(&mut reader)
+            .and_then(|version| version.ok_or(BinaryError::Truncated))
+            .and_then(|_| read_field(&mut reader, &mut master));
+        if let Err(BinaryError::UnsupportedVersion(version)) = handshake {
+            writer.write_all(&[Status::UnsupportedVersion as u8])?;
+            writer.flush()?;
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        handshake?;
+
+        let Ok(master) = String::from_utf8(master) else {
+            writer.write_all(&[Status::InvalidUtf8 as u8])?;
+            writer.flush()?;
+            return Err(BinaryError::InvalidUtf8);
+        };
+        writer.write_all(&[Status::Ok as u8])?;
+        writer.flush()?;
+        Ok(Server {
+            reader,
+            master,
+            key: Vec::new(),
+        })
+    }
+
+    /// Serves one request, returning `false` at a clean end of input
+    ///
+    /// The response is written to `writer` but not flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request is malformed. For a wrong version the
+    /// [`Status::UnsupportedVersion`] response is written first.
+    pub fn serve_one<W: Write>(&mut self, writer: &mut W) -> Result<bool, BinaryError> {
+        let request = read_version(&mut self.reader).and_then(|version| match version {
+            Some(_) => {
+                read_field(&mut self.reader, &mut self.key)?;
+                let mut length = [0];
+                self.reader.read_exact(&mut length)?;
+                Ok(Some(length[0]))
+            }
+            None => Ok(None),
+        });
+        let length = match request {
+            Ok(Some(length)) => length,
+            Ok(None) => return Ok(false),
+            Err(BinaryError::UnsupportedVersion(version)) => {
+                writer.write_all(&[Status::UnsupportedVersion as u8, 0])?;
+                return Err(BinaryError::UnsupportedVersion(version));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let derived = match std::str::from_utf8(&self.key) {
+            Ok(key) => fp_code(&self.master, key, usize::from(length)).map_err(|e| match e {
+                FlowerPasswordError::InvalidLength(_) => Status::InvalidLength,
+            }),
+            Err(_) => Err(Status::InvalidUtf8),
+        };
+        match derived {
+            Ok(password) => {
+                writer.write_all(&[Status::Ok as u8, password.len() as u8])?;
+                writer.write_all(password.as_bytes())?;
+            }
+            Err(status) => writer.write_all(&[status as u8, 0])?,
+        }
+        Ok(true)
+    }
+}
+
+impl<R> fmt::Debug for Server<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server").finish_non_exhaustive()
+    }
+}
+
+/// Serves a connection until the input ends, returning the number of requests
+///
+/// Responses are buffered and flushed whenever all input received so far has
+/// been consumed, so both pipelining and one-request-at-a-time clients work.
+///
+/// # Errors
+///
+/// Returns the first transport or framing error. A clean end of input between
+/// frames is not an error.
+pub fn serve<R: Read, W: Write>(reader: R, writer: W) -> Result<u64, BinaryError> {
+    let mut writer = BufWriter::new(writer);
+    let mut server = Server::handshake(reader, &mut writer)?;
+    let mut served = 0;
+    loop {
+        if server.reader.buffer().is_empty() {
+            writer.flush()?;
+            // Wait for more input only after the responses so far are out
+            if server.reader.fill_buf()?.is_empty() {
+                return Ok(served);
+            }
+        }
+        if !server.serve_one(&mut writer)? {
+            writer.flush()?;
+            return Ok(served);
+        }
+        served += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(master: &str) -> Vec<u8> {
+        let mut frame = Vec::new();
+        write_handshake(&mut frame, master).unwrap();
+        frame
+    }
+
+    /// Runs `serve` over `input`, returning the result and everything written
+    fn run(input: &[u8]) -> (Result<u64, BinaryError>, Vec<u8>) {
+        let mut output = Vec::new();
+        let result = serve(input, &mut output);
+        (result, output)
+    }
+
+    /// A reader that returns at most one byte per call
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&byte, rest)), Some(slot)) => {
+                    *slot = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        assert_eq!(handshake("test"), b"\x01\x00\x04test");
+        let mut request = Vec::new();
+        write_request(&mut request, "github.com", 16).unwrap();
+        assert_eq!(request, b"\x01\x00\x0agithub.com\x10");
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut input = handshake("test");
+        write_request(&mut input, "github.com", 16).unwrap();
+        write_request(&mut input, "github.com", 40).unwrap();
+        write_request(&mut input, "", 2).unwrap();
+        let (result, output) = run(&input);
+        assert_eq!(result.unwrap(), 3);
+
+        let mut output = output.as_slice();
+        assert_eq!(read_handshake_reply(&mut output).unwrap(), Status::Ok);
+        assert_eq!(
+            read_response(&mut output).unwrap(),
+            (Status::Ok, "D04175F7A9c7Ab4a".to_string())
+        );
+        assert_eq!(
+            read_response(&mut output).unwrap(),
+            (Status::InvalidLength, String::new())
+        );
+        assert_eq!(
+            read_response(&mut output).unwrap(),
+            (Status::Ok, fp_code("test", "", 2).unwrap())
+        );
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let mut input = handshake("test");
+        write_request(&mut input, "github.com", 16).unwrap();
+        let mut output = Vec::new();
+        assert_eq!(serve(Trickle(&input), &mut output).unwrap(), 1);
+        assert_eq!(&output[..3], b"\x00\x00\x10");
+        assert_eq!(&output[3..], b"D04175F7A9c7Ab4a");
+    }
+
+    #[test]
+    fn test_invalid_utf8_key() {
+        let mut input = handshake("test");
+        input.extend_from_slice(b"\x01\x00\x02\xff\xfe\x10");
+        let (result, output) = run(&input);
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(output, [Status::Ok as u8, Status::InvalidUtf8 as u8, 0]);
+    }
+
+    #[test]
+    fn test_bad_version() {
+        // In the handshake
+        let (result, output) = run(b"\x02\x00\x04test");
+        assert!(matches!(result, Err(BinaryError::UnsupportedVersion(2))));
+        assert_eq!(output, [Status::UnsupportedVersion as u8]);
+
+        // In a request, after which nothing more is read
+        let mut input = handshake("test");
+        input.extend_from_slice(b"\x07\x00\x01a\x10");
+        write_request(&mut input, "github.com", 16).unwrap();
+        let (result, output) = run(&input);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Unsupported protocol version 7, expected 1"
+        );
+        assert_eq!(output, [0, Status::UnsupportedVersion as u8, 0]);
+    }
+
+    #[test]
+    fn test_truncated_frames() {
+        let full = {
+            let mut input = handshake("test");
+            write_request(&mut input, "github.com", 16).unwrap();
+            input
+        };
+        let handshake_len = handshake("test").len();
+        // Every cut inside the request frame
+        for cut in handshake_len + 1..full.len() {
+            let (result, _) = run(&full[..cut]);
+            assert!(
+                matches!(result, Err(BinaryError::Truncated)),
+                "cut at {}",
+                cut
+            );
+        }
+        // And inside the handshake
+        for cut in 0..handshake_len {
+            let (result, _) = run(&full[..cut]);
+            assert!(matches!(result, Err(BinaryError::Truncated)));
+        }
+        // Ending between frames is fine
+        assert_eq!(run(&full[..handshake_len]).0.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_oversized_fields() {
+        let mut input = b"\x01".to_vec();
+        input.extend_from_slice(&((MAX_FIELD_LEN + 1) as u16).to_be_bytes());
+        input.resize(input.len() + MAX_FIELD_LEN + 1, b'x');
+        assert!(matches!(
+            run(&input).0,
+            Err(BinaryError::FieldTooLarge(4097))
+        ));
+
+        let mut input = handshake("test");
+        input.extend_from_slice(b"\x01\xff\xff");
+        assert!(matches!(
+            run(&input).0,
+            Err(BinaryError::FieldTooLarge(65535))
+        ));
+
+        let long = "x".repeat(MAX_FIELD_LEN + 1);
+        assert!(matches!(
+            write_request(&mut Vec::new(), &long, 16),
+            Err(BinaryError::FieldTooLarge(_))
+        ));
+        assert!(matches!(
+            write_handshake(&mut Vec::new(), &long),
+            Err(BinaryError::FieldTooLarge(_))
+        ));
+        // The largest field is accepted
+        let mut input = handshake("test");
+        write_request(&mut input, &long[1..], 16).unwrap();
+        assert_eq!(run(&input).0.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_invalid_utf8_master() {
+        let (result, output) = run(b"\x01\x00\x01\xff");
+        assert!(matches!(result, Err(BinaryError::InvalidUtf8)));
+        assert_eq!(output, [Status::InvalidUtf8 as u8]);
+    }
+
+    #[test]
+    fn test_read_response_errors() {
+        assert!(matches!(
+            read_response(&mut &b"\x09\x00"[..]),
+            Err(BinaryError::UnknownStatus(9))
+        ));
+        assert!(matches!(
+            read_response(&mut &b"\x00\x10D041"[..]),
+            Err(BinaryError::Truncated)
+        ));
+        assert!(matches!(
+            read_handshake_reply(&mut &b""[..]),
+            Err(BinaryError::Truncated)
+        ));
+    }
+
+    /// Pushes 100k requests through a socket pair, as a parent process would
+    #[cfg(unix)]
+    #[test]
+    fn test_pipelined_over_socket() {
+        use std::os::unix::net::UnixStream;
+
+        const REQUESTS: u32 = 100_000;
+        let (client, server) = UnixStream::pair().unwrap();
+        let server_thread = std::thread::spawn(move || {
+            let reader = server.try_clone().unwrap();
+            serve(reader, server).unwrap()
+        });
+
+        let mut sender = client.try_clone().unwrap();
+        let writer_thread = std::thread::spawn(move || {
+            let mut writer = BufWriter::new(&mut sender);
+            write_handshake(&mut writer, "master").unwrap();
+            for i in 0..REQUESTS {
+                let length = (i % 40) as u8;
+                write_request(&mut writer, &format!("site{}.example", i), length).unwrap();
+            }
+            writer.flush().unwrap();
+            drop(writer);
+            sender.shutdown(std::net::Shutdown::Write).unwrap();
+        });
+
+        let mut reader = BufReader::new(client);
+        assert_eq!(read_handshake_reply(&mut reader).unwrap(), Status::Ok);
+        for i in 0..REQUESTS {
+            let (status, password) = read_response(&mut reader).unwrap();
+            let length = (i % 40) as usize;
+            if !(2..=32).contains(&length) {
+                assert_eq!(status, Status::InvalidLength);
+            } else if i % 997 == 0 {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(
+                    password,
+                    fp_code("master", &format!("site{}.example", i), length).unwrap()
+                );
+            }
+        }
+
+        writer_thread.join().unwrap();
+        assert_eq!(server_thread.join().unwrap(), u64::from(REQUESTS));
+    }
+}
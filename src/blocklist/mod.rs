@@ -0,0 +1,170 @@
+//! Offline blocklist of common passwords
+//!
+//! [`Blocklist::builtin`] holds the 30,000 most common passwords of the
+//! [zxcvbn](https://github.com/dropbox/zxcvbn) frequency lists (MIT licensed),
+//! bundled in the crate so the check works without a network. Own lists are
+//! loaded with [`Blocklist::from_file`] and combined with [`Blocklist::merge`].
+//!
+//! Entries and queries are normalized the same way: surrounding whitespace,
+//! including the `\r` of CRLF files, is trimmed and letters are lowercased, so
+//! `"Password "` is found as `password`. Entries are kept sorted and looked up
+//! by binary search; the built-in list borrows from the bundled text rather than
+//! copying it, and there are no false positives.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::blocklist::Blocklist;
+//!
+//! let blocklist = Blocklist::builtin();
+//! assert!(blocklist.contains("letmein"));
+//! assert!(!blocklist.contains(&flowerpassword::fp_code("test", "github.com", 16)?));
+//! # Ok::<(), flowerpassword::FlowerPasswordError>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The bundled list, one lowercase entry per line in byte order
+const BUILTIN: &str = include_str!("common-passwords.txt");
+
+/// A sorted set of passwords to refuse
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blocklist {
+    entries: Vec<Cow<'static, str>>,
+}
+
+impl Blocklist {
+    /// The bundled list of common passwords
+    pub fn builtin() -> Self {
+        Blocklist {
+            entries: BUILTIN.lines().map(Cow::Borrowed).collect(),
+        }
+    }
+
+    /// Parses a list with one entry per line, skipping blank lines
+    pub fn parse(text: &str) -> Self {
+        let mut entries: Vec<Cow<'static, str>> = text
+            .lines()
+            .map(normalize)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| Cow::Owned(entry.into_owned()))
+            .collect();
+        entries.sort_unstable();
+        entries.dedup();
+        Blocklist { entries }
+    }
+
+    /// Reads a list with one entry per line from `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not UTF-8.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Blocklist::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Adds the entries of `other`
+    pub fn merge(&mut self, other: Blocklist) {
+        self.entries.extend(other.entries);
+        self.entries.sort_unstable();
+        self.entries.dedup();
+    }
+
+    /// Returns whether `password`, once normalized, is listed
+    pub fn contains(&self, password: &str) -> bool {
+        let password = normalize(password);
+        self.entries
+            .binary_search_by(|entry| entry.as_ref().cmp(password.as_ref()))
+            .is_ok()
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the list has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Trims surrounding whitespace and lowercases, borrowing when nothing changes
+fn normalize(text: &str) -> Cow<'_, str> {
+    let text = text.trim();
+    if text.chars().any(char::is_uppercase) {
+        Cow::Owned(text.to_lowercase())
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+    use std::io::Write;
+
+    #[test]
+    fn test_builtin_is_sorted_and_normalized() {
+        let blocklist = Blocklist::builtin();
+        assert_eq!(blocklist.len(), 30_000);
+        assert!(blocklist.entries.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(blocklist
+            .entries
+            .iter()
+            .all(|entry| !entry.is_empty() && normalize(entry) == *entry));
+    }
+
+    #[test]
+    fn test_builtin_hits_and_misses() {
+        let blocklist = Blocklist::builtin();
+        for common in [
+            "123456", "password", "qwerty", "dragon", "letmein", "iloveyou",
+        ] {
+            assert!(blocklist.contains(common), "{}", common);
+        }
+        assert!(blocklist.contains("PassWord"));
+        for rare in ["", "vT9#qLm2$Xe8!rWz4&Kp", "correct horse battery staple"] {
+            assert!(!blocklist.contains(rare), "{}", rare);
+        }
+        for key in ["github.com", "example.org", "bank"] {
+            let password = fp_code("test", key, 16).unwrap();
+            assert!(!blocklist.contains(&password), "{}", password);
+        }
+    }
+
+    #[test]
+    fn test_normalization() {
+        let blocklist = Blocklist::parse("Hunter2  \r\n\n\tsesame\r\n  \nhunter2\n");
+        assert_eq!(blocklist.len(), 2);
+        assert!(blocklist.contains("hunter2"));
+        assert!(blocklist.contains("HUNTER2"));
+        assert!(blocklist.contains("hunter2\n"));
+        assert!(blocklist.contains("sesame"));
+        assert!(!blocklist.contains("hunter"));
+        assert!(!blocklist.contains(""));
+        assert!(Blocklist::parse("\n \r\n").is_empty());
+    }
+
+    #[test]
+    fn test_custom_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "companyname2024\r\nOfficeWifi\r\n").unwrap();
+        let custom = Blocklist::from_file(file.path()).unwrap();
+        assert!(custom.contains("officewifi"));
+        assert!(!custom.contains("password"));
+
+        let mut blocklist = Blocklist::builtin();
+        blocklist.merge(custom);
+        assert_eq!(blocklist.len(), 30_002);
+        assert!(blocklist.contains("CompanyName2024"));
+        assert!(blocklist.contains("password"));
+
+        let missing = Blocklist::from_file(file.path().with_extension("missing"));
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
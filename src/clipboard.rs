@@ -0,0 +1,617 @@
+//! Copying secrets to the clipboard for a limited time
+//!
+//! [`copy_with_ttl`] puts a derived password on the clipboard and returns a
+//! [`ClipboardGuard`]. When the guard is dropped, or when the time to live runs
+//! out first, the previous clipboard content is put back, but only if the
+//! clipboard still holds the password: anything the user copied in the meantime
+//! is left alone.
+//!
+//! To tell, the guard keeps a hash of the password under a random per-guard key
+//! rather than the password itself. The previous content is kept until restored,
+//! in a buffer that is zeroed on drop, since it may be a secret too.
+//!
+//! Clipboard access goes through the [`Clipboard`] trait. [`CommandClipboard`]
+//! runs the platform's clipboard programs, passing text on standard input so it
+//! never appears on a command line, and [`MemoryClipboard`] is an in-memory
+//! clipboard for tests.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::clipboard::{copy_with_ttl_using, MemoryClipboard, Outcome};
+//! use std::time::Duration;
+//!
+//! let clipboard = MemoryClipboard::new(Some("shopping list"));
+//! let guard = copy_with_ttl_using(clipboard.clone(), "D04175F7A9c7Ab4a", Duration::from_secs(30))?;
+//! assert_eq!(clipboard.contents().as_deref(), Some("D04175F7A9c7Ab4a"));
+//!
+//! // Pasted; restore right away rather than after 30 seconds
+//! assert_eq!(guard.restore()?, Outcome::Restored);
+//! assert_eq!(clipboard.contents().as_deref(), Some("shopping list"));
+//! # Ok::<(), flowerpassword::clipboard::ClipboardError>(())
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// How long frontends usually leave a password on the clipboard
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Error type for clipboard operations
+#[derive(Debug)]
+pub enum ClipboardError {
+    /// Starting or talking to a clipboard program failed
+    Io(io::Error),
+    /// A clipboard program exited with an error, with its explanation
+    Command {
+        /// The program that failed
+        program: String,
+        /// Its error output, or its exit status
+        message: String,
+    },
+    /// No clipboard program was found for this session
+    Unavailable,
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::Io(e) => write!(f, "Clipboard error: {}", e),
+            ClipboardError::Command { program, message } => {
+                write!(f, "{} failed: {}", program, message)
+            }
+            ClipboardError::Unavailable => write!(
+                f,
+                "No clipboard found, expected wl-copy, xclip, xsel, pbcopy or PowerShell"
+            ),
+        }
+    }
+}
+
+impl Error for ClipboardError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClipboardError::Io(e) => Some(e),
+            ClipboardError::Command { .. } | ClipboardError::Unavailable => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClipboardError {
+    fn from(e: io::Error) -> Self {
+        ClipboardError::Io(e)
+    }
+}
+
+/// Access to a text clipboard
+pub trait Clipboard: Send {
+    /// Reads the clipboard, or `None` if it holds no text
+    fn get(&mut self) -> Result<Option<Zeroizing<String>>, ClipboardError>;
+
+    /// Replaces the clipboard content with `text`
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError>;
+
+    /// Empties the clipboard
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        self.set("")
+    }
+}
+
+impl<T: Clipboard + ?Sized> Clipboard for Box<T> {
+    fn get(&mut self) -> Result<Option<Zeroizing<String>>, ClipboardError> {
+        (**self).get()
+    }
+
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        (**self).set(text)
+    }
+
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        (**self).clear()
+    }
+}
+
+/// A [`Clipboard`] running a copy program and a paste program
+#[derive(Debug, Clone)]
+pub struct CommandClipboard {
+    copy: Vec<String>,
+    paste: Vec<String>,
+}
+
+impl CommandClipboard {
+    /// Uses `copy`, which reads the new content on standard input, and `paste`,
+    /// which writes the content to standard output, each a program and its
+    /// arguments
+    ///
+    /// # Panics
+    ///
+    /// Panics if either command is empty.
+    pub fn new(copy: &[&str], paste: &[&str]) -> Self {
+        assert!(
+            !copy.is_empty() && !paste.is_empty(),
+            "empty clipboard command"
+        );
+        CommandClipboard {
+            copy: copy.iter().map(ToString::to_string).collect(),
+            paste: paste.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Picks the clipboard programs of this platform and session
+    ///
+    /// That is `pbcopy` on macOS and PowerShell on Windows. Elsewhere it is
+    /// `wl-copy` in a Wayland session, then `xclip` or `xsel` in an X11 session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClipboardError::Unavailable` if none of them applies.
+    pub fn detect() -> Result<Self, ClipboardError> {
+        if cfg!(target_os = "macos") {
+            return Ok(CommandClipboard::new(&["pbcopy"], &["pbpaste"]));
+        }
+        if cfg!(windows) {
+            return Ok(CommandClipboard::new(
+                &[
+                    "powershell",
+                    "-NoProfile",
+                    "-Command",
+                    "Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+                ],
+                &["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"],
+            ));
+        }
+        let session = |variable| std::env::var_os(variable).is_some_and(|v| !v.is_empty());
+        if session("WAYLAND_DISPLAY") && on_path("wl-copy") {
+            return Ok(CommandClipboard::new(
+                &["wl-copy"],
+                &["wl-paste", "--no-newline"],
+            ));
+        }
+        if session("DISPLAY") {
+            if on_path("xclip") {
+                return Ok(CommandClipboard::new(
+                    &["xclip", "-selection", "clipboard"],
+                    &["xclip", "-selection", "clipboard", "-o"],
+                ));
+            }
+            if on_path("xsel") {
+                return Ok(CommandClipboard::new(
+                    &["xsel", "--clipboard", "--input"],
+                    &["xsel", "--clipboard", "--output"],
+                ));
+            }
+        }
+        Err(ClipboardError::Unavailable)
+    }
+}
+
+/// Returns whether `name` is a file in a directory of `PATH`
+fn on_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+}
+
+impl Clipboard for CommandClipboard {
+    /// Runs the paste program
+    ///
+    /// Paste programs such as `wl-paste` fail on an empty clipboard, so a failure
+    /// without output is reported as no text.
+    fn get(&mut self) -> Result<Option<Zeroizing<String>>, ClipboardError> {
+        let output = Command::new(&self.paste[0])
+            .args(&self.paste[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() && stdout.is_empty() {
+            return Ok(None);
+        }
+        match std::str::from_utf8(&stdout) {
+            Ok(text) => Ok(Some(Zeroizing::new(text.to_string()))),
+            // Not text, such as an image
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Runs the copy program with `text` on its standard input
+    ///
+    /// Its output is discarded rather than read: `xclip` keeps running in the
+    /// background to serve the selection, holding any pipe open.
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        let mut child = Command::new(&self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // The pipe is closed at the end of the statement, ending the input
+        let written = child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(text.as_bytes());
+        let status = child.wait()?;
+        written?;
+        if !status.success() {
+            return Err(ClipboardError::Command {
+                program: self.copy[0].clone(),
+                message: status.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory [`Clipboard`], for tests
+///
+/// Clones share the same content, so a test can keep one and hand another to
+/// [`copy_with_ttl_using`]. `Debug` does not show the content.
+#[derive(Clone, Default)]
+pub struct MemoryClipboard {
+    contents: Arc<Mutex<Option<Zeroizing<String>>>>,
+}
+
+impl MemoryClipboard {
+    /// Starts with `contents`
+    pub fn new(contents: Option<&str>) -> Self {
+        let clipboard = MemoryClipboard::default();
+        *clipboard.lock() = contents.map(|text| Zeroizing::new(text.to_string()));
+        clipboard
+    }
+
+    /// The current content, as a user pasting it would see it
+    pub fn contents(&self) -> Option<String> {
+        self.lock().as_ref().map(|text| text.to_string())
+    }
+
+    /// Replaces the content, as a user copying something would
+    pub fn copy(&self, text: &str) {
+        *self.lock() = Some(Zeroizing::new(text.to_string()));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Zeroizing<String>>> {
+        self.contents.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&mut self) -> Result<Option<Zeroizing<String>>, ClipboardError> {
+        Ok(self.lock().clone())
+    }
+
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        *self.lock() = Some(Zeroizing::new(text.to_string()));
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        *self.lock() = None;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MemoryClipboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryClipboard").finish_non_exhaustive()
+    }
+}
+
+/// What happened to the clipboard when a [`ClipboardGuard`] ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The previous content was put back
+    Restored,
+    /// There was no previous text, so the clipboard was emptied
+    Cleared,
+    /// The clipboard no longer held the secret and was left alone
+    Kept,
+}
+
+/// Restores the clipboard when dropped or when its time to live runs out
+///
+/// Dropping the guard waits for the restore. `Debug` shows nothing of the
+/// secret or the previous content.
+pub struct ClipboardGuard {
+    shared: Arc<Shared>,
+    timer: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+struct State {
+    clipboard: Box<dyn Clipboard>,
+    previous: Option<Zeroizing<String>>,
+    keys: RandomState,
+    secret_hash: u64,
+    done: bool,
+    outcome: Option<Result<Outcome, ClipboardError>>,
+}
+
+impl State {
+    fn hash(&self, text: &str) -> u64 {
+        let mut hasher = self.keys.build_hasher();
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Restores the clipboard, once
+    fn finish(&mut self) {
+        if !self.done {
+            self.done = true;
+            self.outcome = Some(self.restore());
+        }
+    }
+
+    fn restore(&mut self) -> Result<Outcome, ClipboardError> {
+        let current = self.clipboard.get()?;
+        if current.map(|text| self.hash(&text)) != Some(self.secret_hash) {
+            return Ok(Outcome::Kept);
+        }
+        match self.previous.take() {
+            Some(previous) => {
+                self.clipboard.set(&previous)?;
+                Ok(Outcome::Restored)
+            }
+            None => {
+                self.clipboard.clear()?;
+                Ok(Outcome::Cleared)
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ClipboardGuard {
+    /// Restores the clipboard now, or reports how it was restored at expiry
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the clipboard while reading or restoring it.
+    pub fn restore(mut self) -> Result<Outcome, ClipboardError> {
+        self.stop()
+    }
+
+    /// Returns `true` once the clipboard has been restored
+    pub fn is_expired(&self) -> bool {
+        self.shared.lock().done
+    }
+
+    fn stop(&mut self) -> Result<Outcome, ClipboardError> {
+        let outcome = {
+            let mut state = self.shared.lock();
+            state.finish();
+            state.outcome.take()
+        };
+        self.shared.wakeup.notify_all();
+        if let Some(timer) = self.timer.take() {
+            // The timer only waits and restores; a panic there changes nothing here
+            let _ = timer.join();
+        }
+        // `None` only after a previous `stop`, which `restore` and `drop` rule out
+        outcome.unwrap_or(Ok(Outcome::Kept))
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        if self.timer.is_some() {
+            let _ = self.stop();
+        }
+    }
+}
+
+impl fmt::Debug for ClipboardGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClipboardGuard")
+            .field("expired", &self.is_expired())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Copies `secret` to the system clipboard for `ttl`
+///
+/// # Errors
+///
+/// Returns `ClipboardError::Unavailable` if there is no clipboard program, or
+/// any error from [`copy_with_ttl_using`].
+pub fn copy_with_ttl(secret: &str, ttl: Duration) -> Result<ClipboardGuard, ClipboardError> {
+    copy_with_ttl_using(CommandClipboard::detect()?, secret, ttl)
+}
+
+/// Copies `secret` to `clipboard` for `ttl`
+///
+/// The previous content is read first. A background thread restores it after
+/// `ttl` unless the guard is dropped before.
+///
+/// # Errors
+///
+/// Returns any error from the clipboard while reading or replacing its content;
+/// the clipboard is unchanged if reading fails.
+pub fn copy_with_ttl_using<C: Clipboard + 'static>(
+    mut clipboard: C,
+    secret: &str,
+    ttl: Duration,
+) -> Result<ClipboardGuard, ClipboardError> {
+    let previous = clipboard.get()?;
+    clipboard.set(secret)?;
+
+    let mut state = State {
+        clipboard: Box::new(clipboard),
+        previous,
+        keys: RandomState::new(),
+        secret_hash: 0,
+        done: false,
+        outcome: None,
+    };
+    state.secret_hash = state.hash(secret);
+    let shared = Arc::new(Shared {
+        state: Mutex::new(state),
+        wakeup: Condvar::new(),
+    });
+
+    let deadline = Instant::now() + ttl;
+    let timer_shared = Arc::clone(&shared);
+    let timer = thread::Builder::new()
+        .name("clipboard-ttl".to_string())
+        .spawn(move || {
+            let mut state = timer_shared.lock();
+            while !state.done {
+                let now = Instant::now();
+                if now >= deadline {
+                    state.finish();
+                    break;
+                }
+                state = timer_shared
+                    .wakeup
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+        })?;
+
+    Ok(ClipboardGuard {
+        shared,
+        timer: Some(timer),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "D04175F7A9c7Ab4a";
+    const TTL: Duration = Duration::from_millis(50);
+
+    /// Waits for the timer of `guard` to restore the clipboard
+    fn wait_for_expiry(guard: &ClipboardGuard) {
+        let start = Instant::now();
+        while !guard.is_expired() {
+            assert!(start.elapsed() < Duration::from_secs(10), "never expired");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_restore_on_expiry() {
+        let clipboard = MemoryClipboard::new(Some("previous"));
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, TTL).unwrap();
+        assert_eq!(clipboard.contents().as_deref(), Some(SECRET));
+        wait_for_expiry(&guard);
+        assert_eq!(clipboard.contents().as_deref(), Some("previous"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+    }
+
+    #[test]
+    fn test_clear_on_expiry_without_previous_text() {
+        let clipboard = MemoryClipboard::new(None);
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, TTL).unwrap();
+        wait_for_expiry(&guard);
+        assert_eq!(clipboard.contents(), None);
+        assert_eq!(guard.restore().unwrap(), Outcome::Cleared);
+    }
+
+    #[test]
+    fn test_no_clobber() {
+        let clipboard = MemoryClipboard::new(Some("previous"));
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, TTL).unwrap();
+        clipboard.copy("copied since");
+        wait_for_expiry(&guard);
+        assert_eq!(clipboard.contents().as_deref(), Some("copied since"));
+        assert_eq!(guard.restore().unwrap(), Outcome::Kept);
+
+        // Nor when the guard is dropped early
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, DEFAULT_TTL).unwrap();
+        clipboard.copy("copied again");
+        drop(guard);
+        assert_eq!(clipboard.contents().as_deref(), Some("copied again"));
+    }
+
+    #[test]
+    fn test_early_drop() {
+        let clipboard = MemoryClipboard::new(Some("previous"));
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, DEFAULT_TTL).unwrap();
+        assert!(!guard.is_expired());
+        let start = Instant::now();
+        drop(guard);
+        // Dropping wakes the timer instead of waiting out the TTL
+        assert!(start.elapsed() < DEFAULT_TTL);
+        assert_eq!(clipboard.contents().as_deref(), Some("previous"));
+
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, DEFAULT_TTL).unwrap();
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(clipboard.contents().as_deref(), Some("previous"));
+    }
+
+    #[test]
+    fn test_errors_are_reported() {
+        /// Reads fine but refuses every write after the first
+        struct Flaky(MemoryClipboard, usize);
+
+        impl Clipboard for Flaky {
+            fn get(&mut self) -> Result<Option<Zeroizing<String>>, ClipboardError> {
+                self.0.get()
+            }
+
+            fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+                self.1 += 1;
+                if self.1 > 1 {
+                    return Err(ClipboardError::Unavailable);
+                }
+                self.0.set(text)
+            }
+        }
+
+        let clipboard = MemoryClipboard::new(Some("previous"));
+        let guard = copy_with_ttl_using(Flaky(clipboard.clone(), 0), SECRET, TTL).unwrap();
+        wait_for_expiry(&guard);
+        assert!(matches!(guard.restore(), Err(ClipboardError::Unavailable)));
+        assert_eq!(clipboard.contents().as_deref(), Some(SECRET));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_clipboard() {
+        // `cat` into a file stands in for a copy program, and `cat` of it for paste
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("clipboard");
+        std::fs::write(&file, "previous").unwrap();
+        let path = file.to_str().unwrap();
+        let copy = format!("cat > '{}'", path);
+        let paste = format!("cat '{}'", path);
+        let mut clipboard = CommandClipboard::new(&["sh", "-c", &copy], &["sh", "-c", &paste]);
+
+        assert_eq!(
+            clipboard.get().unwrap().as_deref().map(String::as_str),
+            Some("previous")
+        );
+        let guard = copy_with_ttl_using(clipboard.clone(), SECRET, DEFAULT_TTL).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), SECRET);
+        assert_eq!(guard.restore().unwrap(), Outcome::Restored);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "previous");
+
+        let mut failing = CommandClipboard::new(&["false"], &["false"]);
+        assert!(failing.get().unwrap().is_none());
+        assert!(matches!(
+            failing.set(SECRET),
+            Err(ClipboardError::Command { program, .. }) if program == "false"
+        ));
+        let mut missing = CommandClipboard::new(&["/nonexistent/copy"], &["/nonexistent/paste"]);
+        assert!(matches!(missing.get(), Err(ClipboardError::Io(_))));
+    }
+}
@@ -0,0 +1,56 @@
+//! WebAssembly Component Model guest
+//!
+//! Implements the `flowerpassword:core/derive` interface of the `flowerpassword`
+//! world in `wit/flowerpassword.wit`. Build the core module for
+//! `wasm32-unknown-unknown` with the `component` feature, then wrap it into a
+//! component with `wasm-tools component new`. Errors are returned as the WIT
+//! `error-kind` enum, which has one case per `FlowerPasswordError` variant.
+
+use crate::{fp_code, FlowerPasswordError};
+
+wit_bindgen::generate!({
+    path: "wit",
+    world: "flowerpassword",
+});
+
+use exports::flowerpassword::core::derive::{ErrorKind, Guest};
+
+impl From<FlowerPasswordError> for ErrorKind {
+    fn from(error: FlowerPasswordError) -> Self {
+        match error {
+            FlowerPasswordError::InvalidLength(_) => ErrorKind::InvalidLength,
+        }
+    }
+}
+
+/// The exported component
+struct Component;
+
+impl Guest for Component {
+    fn code(password: String, key: String, length: u32) -> Result<String, ErrorKind> {
+        Ok(fp_code(&password, &key, length as usize)?)
+    }
+}
+
+export!(Component);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code() {
+        assert_eq!(
+            Component::code("test".to_string(), "github.com".to_string(), 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            Component::code("test".to_string(), "github.com".to_string(), 99).unwrap_err(),
+            ErrorKind::InvalidLength
+        );
+    }
+}
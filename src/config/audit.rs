@@ -0,0 +1,166 @@
+//! Password reuse audit
+//!
+//! Short lengths keep only a few characters of the derived hash, so two sites
+//! can end up with the same password. Auditing derives every configured site
+//! and groups the sites that share one, without ever returning the passwords.
+//!
+//! Passwords are not kept around while auditing: each is reduced to a keyed
+//! hash under a key chosen at random for the audit, and only sites whose hashes
+//! agree are derived again and compared in constant time to confirm the match.
+
+use super::Config;
+use crate::FlowerPasswordError;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// Sites that derive the same password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReuseGroup<'a> {
+    /// Names of the sites sharing a password, in name order
+    pub sites: Vec<&'a str>,
+}
+
+impl Config {
+    /// Finds configured sites that derive identical passwords under `master`
+    ///
+    /// Groups are ordered by their first site name. An empty result means every
+    /// site has a distinct password.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if a site has an invalid length.
+    pub fn find_reused(&self, master: &str) -> Result<Vec<ReuseGroup<'_>>, FlowerPasswordError> {
+        let state = RandomState::new();
+        let mut buckets: BTreeMap<u64, Vec<&str>> = BTreeMap::new();
+        for name in self.sites.keys() {
+            let password = self.derive_site(master, name)?;
+            let mut hasher = state.build_hasher();
+            password.hash(&mut hasher);
+            buckets.entry(hasher.finish()).or_default().push(name);
+        }
+
+        let mut groups = Vec::new();
+        for candidates in buckets.into_values().filter(|sites| sites.len() > 1) {
+            // Keyed hashes can collide, so confirm by comparing the passwords
+            let mut unconfirmed = candidates;
+            while let Some(first) = unconfirmed.first().copied() {
+                let reference = self.derive_site(master, first)?;
+                let mut same = Vec::new();
+                let mut different = Vec::new();
+                for site in unconfirmed {
+                    if constant_time_eq(&reference, &self.derive_site(master, site)?) {
+                        same.push(site);
+                    } else {
+                        different.push(site);
+                    }
+                }
+                if same.len() > 1 {
+                    groups.push(ReuseGroup { sites: same });
+                }
+                unconfirmed = different;
+            }
+        }
+
+        groups.sort_by(|a, b| a.sites.cmp(&b.sites));
+        Ok(groups)
+    }
+
+    /// Derives the password of a configured site
+    fn derive_site(&self, master: &str, name: &str) -> Result<String, FlowerPasswordError> {
+        // Site names match before aliases are consulted, so this cannot fail
+        let resolved = self.resolve(name).expect("configured sites always resolve");
+        resolved
+            .scheme
+            .fp_code(master, resolved.key, resolved.length)
+    }
+}
+
+/// Compares two strings without exiting early on the first difference
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::fixtures::config;
+    use crate::fp_code;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("K3A2", "K3A2"));
+        assert!(!constant_time_eq("K3A2", "K3A3"));
+        assert!(!constant_time_eq("K3A2", "K3A"));
+    }
+
+    // "site0.example" and "site3.example" both derive to "K0" at length 2
+    const COLLIDING: &str = r#"[sites.pin-a]
+key = "site0.example"
+length = 2
+
+[sites.pin-b]
+key = "site3.example"
+length = 2
+
+[sites.github]
+key = "github.com"
+
+[sites.long-a]
+key = "site0.example"
+"#;
+
+    const CLEAN: &str = r#"[sites.github]
+key = "github.com"
+
+[sites.gitlab]
+key = "gitlab.com"
+
+[sites.pin]
+key = "site0.example"
+length = 2
+"#;
+
+    #[test]
+    fn test_fixture_collides() {
+        assert_eq!(fp_code("password", "site0.example", 2).unwrap(), "K0");
+        assert_eq!(fp_code("password", "site3.example", 2).unwrap(), "K0");
+    }
+
+    #[test]
+    fn test_reports_colliding_sites() {
+        let config = config(COLLIDING);
+        let groups = config.find_reused("password").unwrap();
+        assert_eq!(
+            groups,
+            vec![ReuseGroup {
+                sites: vec!["pin-a", "pin-b"]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_same_key_at_different_lengths_is_not_reuse() {
+        let config = config(COLLIDING);
+        let groups = config.find_reused("password").unwrap();
+        assert!(groups.iter().all(|group| !group.sites.contains(&"long-a")));
+    }
+
+    #[test]
+    fn test_clean_config() {
+        assert!(config(CLEAN).find_reused("password").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_identical_keys_form_one_group() {
+        let config =
+            config("[sites.a]\nkey = \"x\"\n\n[sites.b]\nkey = \"x\"\n\n[sites.c]\nkey = \"x\"\n");
+        let groups = config.find_reused("password").unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].sites, vec!["a", "b", "c"]);
+    }
+}
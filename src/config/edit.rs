@@ -0,0 +1,782 @@
+//! Comment-preserving configuration edits
+//!
+//! Frontends managing the configuration for the user edit the parsed document
+//! with `toml_edit` rather than re-serializing a [`Config`](super::Config), so
+//! comments, ordering and formatting of everything else in the file survive.
+//!
+//! Files are rewritten with [`write_atomic`], so a crash mid-write leaves either
+//! the old or the new contents, never a mix of both.
+
+use super::{Config, ConfigError, SiteConfig};
+use crate::Scheme;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut, Item, Table};
+
+/// Adds a `[sites.<name>]` table with the settings in `site`
+///
+/// Returns the settings previously stored under `name`, if any.
+///
+/// # Errors
+///
+/// Returns `ConfigError::SiteExists` if the site is already configured and
+/// `force` is `false`, or `ConfigError::Parse` if the document has a `sites` key
+/// that is not a table.
+pub fn add_site(
+    document: &mut DocumentMut,
+    name: &str,
+    site: &SiteConfig,
+    force: bool,
+) -> Result<Option<SiteConfig>, ConfigError> {
+    let sites = document
+        .entry("sites")
+        .or_insert_with(|| {
+            let mut sites = Table::new();
+            sites.set_implicit(true);
+            Item::Table(sites)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table("sites"))?;
+
+    let previous = sites.get(name).map(site_settings);
+    if previous.is_some() && !force {
+        return Err(ConfigError::SiteExists {
+            name: name.to_string(),
+        });
+    }
+
+    let mut table = Table::new();
+    if let Some(key) = &site.key {
+        table.insert("key", value(key.as_str()));
+    }
+    if let Some(length) = site.length {
+        table.insert("length", value(length as i64));
+    }
+    if let Some(scheme) = site.scheme {
+        table.insert("scheme", value(scheme.name()));
+    }
+    if let Some(username) = &site.username {
+        table.insert("username", value(username.as_str()));
+    }
+    if let Some(notes) = &site.notes {
+        table.insert("notes", value(notes.as_str()));
+    }
+    sites.insert(name, Item::Table(table));
+    Ok(previous)
+}
+
+/// Removes the `[sites.<name>]` table, returning the settings it held
+pub fn remove_site(document: &mut DocumentMut, name: &str) -> Option<SiteConfig> {
+    let sites = document.get_mut("sites")?.as_table_like_mut()?;
+    sites.remove(name).as_ref().map(site_settings)
+}
+
+/// Summary of a [`rename_site`] edit
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SiteRename {
+    /// Aliases that pointed at the old name and now point at the new one
+    pub aliases: Vec<String>,
+    /// `true` if the site had no explicit key and the old name was written as
+    /// its key, so that its password does not change
+    pub key_pinned: bool,
+    /// Settings of the site previously configured under the new name, if it was replaced
+    pub replaced: Option<SiteConfig>,
+}
+
+/// Renames the `[sites.<old>]` table to `[sites.<new>]` and rewires aliases
+///
+/// Renaming a site never changes its password: a site without an explicit
+/// `key` derives from its name, so its old name is written as the key. Pass
+/// `new_key` to replace the key as well, which does change the password.
+///
+/// # Errors
+///
+/// Returns `ConfigError::UnknownSite` if `old` is not configured, or
+/// `ConfigError::SiteExists` if `new` is configured and `force` is `false`.
+pub fn rename_site(
+    document: &mut DocumentMut,
+    old: &str,
+    new: &str,
+    new_key: Option<&str>,
+    force: bool,
+) -> Result<SiteRename, ConfigError> {
+    let unknown = || ConfigError::UnknownSite {
+        name: old.to_string(),
+    };
+    let sites = document
+        .get_mut("sites")
+        .and_then(Item::as_table_like_mut)
+        .ok_or_else(unknown)?;
+    if !sites.contains_key(old) {
+        return Err(unknown());
+    }
+
+    let mut rename = SiteRename::default();
+    if old != new {
+        if sites.contains_key(new) && !force {
+            return Err(ConfigError::SiteExists {
+                name: new.to_string(),
+            });
+        }
+        rename.replaced = sites.remove(new).as_ref().map(site_settings);
+        let table = sites.remove(old).ok_or_else(unknown)?;
+        sites.insert(new, table);
+    }
+
+    let site = sites
+        .get_mut(new)
+        .and_then(Item::as_table_like_mut)
+        .ok_or_else(|| not_a_table(new))?;
+    match new_key {
+        Some(key) => {
+            site.insert("key", value(key));
+        }
+        None if !site.contains_key("key") && old != new => {
+            site.insert("key", value(old));
+            rename.key_pinned = true;
+        }
+        None => {}
+    }
+
+    if old != new {
+        if let Some(aliases) = document
+            .get_mut("aliases")
+            .and_then(Item::as_table_like_mut)
+        {
+            for (alias, target) in aliases.iter_mut() {
+                if target.as_str() == Some(old) {
+                    *target = value(new);
+                    rename.aliases.push(alias.get().to_string());
+                }
+            }
+        }
+    }
+
+    Ok(rename)
+}
+
+/// Reads the settings stored in a site table, ignoring anything unrecognised
+fn site_settings(item: &Item) -> SiteConfig {
+    SiteConfig {
+        key: item.get("key").and_then(Item::as_str).map(str::to_string),
+        length: item
+            .get("length")
+            .and_then(Item::as_integer)
+            .and_then(|length| usize::try_from(length).ok()),
+        scheme: item
+            .get("scheme")
+            .and_then(Item::as_str)
+            .and_then(Scheme::from_name),
+        username: item
+            .get("username")
+            .and_then(Item::as_str)
+            .map(str::to_string),
+        notes: item.get("notes").and_then(Item::as_str).map(str::to_string),
+    }
+}
+
+/// Sets the notes of a configured site, returning the previous notes
+///
+/// With `append`, `text` is added on a new line after any existing notes
+/// instead of replacing them. Notes are stored exactly as given.
+///
+/// # Errors
+///
+/// Returns `ConfigError::UnknownSite` if `name` is not configured.
+pub fn set_notes(
+    document: &mut DocumentMut,
+    name: &str,
+    text: &str,
+    append: bool,
+) -> Result<Option<String>, ConfigError> {
+    let site = document
+        .get_mut("sites")
+        .and_then(Item::as_table_like_mut)
+        .and_then(|sites| sites.get_mut(name))
+        .and_then(Item::as_table_like_mut)
+        .ok_or_else(|| ConfigError::UnknownSite {
+            name: name.to_string(),
+        })?;
+
+    let previous = site.get("notes").and_then(Item::as_str).map(str::to_string);
+    let notes = match &previous {
+        Some(previous) if append && !previous.is_empty() => format!("{}\n{}", previous, text),
+        _ => text.to_string(),
+    };
+    site.insert("notes", value(notes));
+    Ok(previous)
+}
+
+/// Points `alias` at `target`, creating the `[aliases]` table if needed
+///
+/// Returns the previous target of the alias, if it already existed.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Parse` if the document has an `aliases` key that is
+/// not a table.
+pub fn set_alias(
+    document: &mut DocumentMut,
+    alias: &str,
+    target: &str,
+) -> Result<Option<String>, ConfigError> {
+    let aliases = document
+        .entry("aliases")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table("aliases"))?;
+
+    let previous = aliases
+        .get(alias)
+        .and_then(Item::as_str)
+        .map(str::to_string);
+    aliases.insert(alias, value(target));
+    Ok(previous)
+}
+
+/// Removes `alias`, returning the target it pointed to
+///
+/// The `[aliases]` table is removed too once it is empty.
+pub fn remove_alias(document: &mut DocumentMut, alias: &str) -> Option<String> {
+    let aliases = document.get_mut("aliases")?.as_table_like_mut()?;
+    let removed = aliases.remove(alias)?.as_str().map(str::to_string);
+    if aliases.is_empty() {
+        document.remove("aliases");
+    }
+    removed
+}
+
+/// Sets the top-level `fingerprint` of the master password, or removes it for
+/// `None`, returning the previous one
+pub fn set_fingerprint(document: &mut DocumentMut, fingerprint: Option<&str>) -> Option<String> {
+    set_root_string(document, "fingerprint", fingerprint)
+}
+
+/// Sets the top-level `fido2_credential`, or removes it for `None`, returning
+/// the previous one
+pub fn set_fido2_credential(
+    document: &mut DocumentMut,
+    credential: Option<&str>,
+) -> Option<String> {
+    set_root_string(document, "fido2_credential", credential)
+}
+
+/// Sets the top-level string `key` to `new`, or removes it for `None`,
+/// returning the previous value
+fn set_root_string(document: &mut DocumentMut, key: &str, new: Option<&str>) -> Option<String> {
+    let previous = document.get(key).and_then(Item::as_str).map(str::to_string);
+    match new {
+        Some(new) => {
+            document.insert(key, value(new));
+        }
+        None => {
+            document.remove(key);
+        }
+    }
+    previous
+}
+
+/// Applies `edit` to a configuration file and writes it back
+///
+/// The edited document must still be a valid configuration; nothing is written
+/// otherwise. The file is replaced with [`write_atomic`].
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` if the file cannot be read or written,
+/// `ConfigError::Parse` if it is not valid TOML, any error returned by `edit`,
+/// or any error from [`Config::from_toml_str`] for the edited document.
+pub fn edit_file<T, F>(path: &Path, edit: F) -> Result<T, ConfigError>
+where
+    F: FnOnce(&mut DocumentMut) -> Result<T, ConfigError>,
+{
+    let origin = path.display().to_string();
+    let io_error = |source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let source = fs::read_to_string(path).map_err(io_error)?;
+    let mut document = source
+        .parse::<DocumentMut>()
+        .map_err(|e| ConfigError::Parse {
+            origin: origin.clone(),
+            message: e.to_string(),
+        })?;
+
+    let result = edit(&mut document)?;
+    let edited = document.to_string();
+    Config::from_toml_str(&edited, &origin)?;
+    write_atomic(path, edited.as_bytes())?;
+    Ok(result)
+}
+
+/// Replaces the contents of `path` without ever leaving it half-written
+///
+/// The data is written and synced to a temporary file in the same directory,
+/// which is then renamed over `path`. Permissions of an existing file are kept.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` if any step fails; the temporary file is removed
+/// and `path` is left untouched.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), ConfigError> {
+    write_atomic_with(path, contents, |temp, path| fs::rename(temp, path))
+}
+
+/// Implements [`write_atomic`], with `commit` moving the temporary file into place
+fn write_atomic_with<F>(path: &Path, contents: &[u8], commit: F) -> Result<(), ConfigError>
+where
+    F: FnOnce(&Path, &Path) -> io::Result<()>,
+{
+    let temp = temp_path(path);
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        commit(&temp, path)
+    })();
+
+    result.map_err(|source| {
+        let _ = fs::remove_file(&temp);
+        ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    })
+}
+
+/// Returns the path of the temporary file used while rewriting `path`
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Error for a configuration key that should hold a table but does not
+fn not_a_table(key: &str) -> ConfigError {
+    ConfigError::Parse {
+        origin: "configuration".to_string(),
+        message: format!("`{}` must be a table", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"# My sites
+[sites.examplebank]
+key = "secure.examplebank.co.uk" # online banking
+"#;
+
+    fn parse(source: &str) -> DocumentMut {
+        source.parse().unwrap()
+    }
+
+    #[test]
+    fn test_set_alias_preserves_comments() {
+        let mut document = parse(SAMPLE);
+        assert_eq!(
+            set_alias(&mut document, "bank", "examplebank").unwrap(),
+            None
+        );
+        assert_eq!(
+            document.to_string(),
+            format!("{}\n[aliases]\nbank = \"examplebank\"\n", SAMPLE)
+        );
+    }
+
+    #[test]
+    fn test_set_alias_replaces_target() {
+        let mut document = parse("[aliases]\nbank = \"old\"\n");
+        let previous = set_alias(&mut document, "bank", "examplebank").unwrap();
+        assert_eq!(previous.as_deref(), Some("old"));
+        assert_eq!(document.to_string(), "[aliases]\nbank = \"examplebank\"\n");
+    }
+
+    #[test]
+    fn test_set_alias_rejects_non_table() {
+        let mut document = parse("aliases = 3\n");
+        assert!(matches!(
+            set_alias(&mut document, "bank", "examplebank"),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_set_fingerprint() {
+        let mut document = parse(SAMPLE);
+        assert_eq!(set_fingerprint(&mut document, Some("K8a2D6")), None);
+        assert_eq!(
+            document.to_string(),
+            format!("fingerprint = \"K8a2D6\"\n{}", SAMPLE)
+        );
+        assert_eq!(
+            set_fingerprint(&mut document, None).as_deref(),
+            Some("K8a2D6")
+        );
+        assert_eq!(document.to_string(), SAMPLE);
+    }
+
+    #[test]
+    fn test_set_fido2_credential() {
+        let mut document = parse(SAMPLE);
+        assert_eq!(set_fido2_credential(&mut document, Some("c001")), None);
+        assert_eq!(
+            document.to_string(),
+            format!("fido2_credential = \"c001\"\n{}", SAMPLE)
+        );
+        let loaded = Config::from_toml_str(&document.to_string(), "fido2.toml").unwrap();
+        assert_eq!(loaded.config.fido2_credential.as_deref(), Some("c001"));
+        assert_eq!(
+            set_fido2_credential(&mut document, Some("c002")).as_deref(),
+            Some("c001")
+        );
+    }
+
+    #[test]
+    fn test_remove_alias() {
+        let mut document = parse("[aliases]\nbank = \"examplebank\"\ngh = \"github\"\n");
+        assert_eq!(
+            remove_alias(&mut document, "bank").as_deref(),
+            Some("examplebank")
+        );
+        assert_eq!(remove_alias(&mut document, "bank"), None);
+        assert_eq!(document.to_string(), "[aliases]\ngh = \"github\"\n");
+
+        remove_alias(&mut document, "gh");
+        assert_eq!(document.to_string(), "");
+    }
+
+    #[test]
+    fn test_add_site() {
+        let mut document = parse(SAMPLE);
+        let site = SiteConfig {
+            key: Some("github.com".to_string()),
+            length: Some(20),
+            scheme: None,
+            username: None,
+            notes: None,
+        };
+        assert_eq!(
+            add_site(&mut document, "github", &site, false).unwrap(),
+            None
+        );
+        assert_eq!(
+            document.to_string(),
+            format!(
+                "{}\n[sites.github]\nkey = \"github.com\"\nlength = 20\n",
+                SAMPLE
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_site_to_empty_document() {
+        let mut document = parse("");
+        add_site(&mut document, "github", &SiteConfig::default(), false).unwrap();
+        assert_eq!(document.to_string(), "[sites.github]\n");
+    }
+
+    #[test]
+    fn test_add_existing_site_requires_force() {
+        let mut document = parse(SAMPLE);
+        let site = SiteConfig {
+            key: Some("examplebank.com".to_string()),
+            length: None,
+            scheme: None,
+            username: None,
+            notes: None,
+        };
+        let error = add_site(&mut document, "examplebank", &site, false).unwrap_err();
+        assert_eq!(error.to_string(), "Site `examplebank` already exists");
+        assert_eq!(document.to_string(), SAMPLE);
+
+        let previous = add_site(&mut document, "examplebank", &site, true).unwrap();
+        assert_eq!(
+            previous.unwrap().key.as_deref(),
+            Some("secure.examplebank.co.uk")
+        );
+        assert_eq!(
+            document["sites"]["examplebank"]["key"].as_str(),
+            Some("examplebank.com")
+        );
+    }
+
+    #[test]
+    fn test_remove_site() {
+        let mut document = parse(SAMPLE);
+        let removed = remove_site(&mut document, "examplebank").unwrap();
+        assert_eq!(removed.key.as_deref(), Some("secure.examplebank.co.uk"));
+        assert_eq!(remove_site(&mut document, "examplebank"), None);
+    }
+
+    #[test]
+    fn test_added_site_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let site = SiteConfig {
+            key: Some("github.com".to_string()),
+            length: Some(20),
+            scheme: Some(Scheme::V1),
+            username: Some("octocat@example.com".to_string()),
+            notes: None,
+        };
+        edit_file(&path, |document| add_site(document, "github", &site, false)).unwrap();
+
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.sites["github"], site);
+        assert_eq!(config.sites.len(), 2);
+    }
+
+    #[test]
+    fn test_add_site_with_invalid_length_is_not_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let site = SiteConfig {
+            key: None,
+            length: Some(64),
+            scheme: None,
+            username: None,
+            notes: None,
+        };
+        let result = edit_file(&path, |document| add_site(document, "github", &site, false));
+        assert!(matches!(result, Err(ConfigError::Invalid { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), SAMPLE);
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_atomic_failure_before_rename_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let result = write_atomic_with(&path, b"half-written", |temp, _| {
+            assert_eq!(fs::read(temp).unwrap(), b"half-written");
+            Err(io::Error::new(io::ErrorKind::Other, "simulated crash"))
+        });
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), SAMPLE);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    const RENAME: &str = r#"[sites.bank]
+key = "secure.examplebank.co.uk"
+
+[sites.github]
+length = 20 # the default is too short
+
+[aliases]
+money = "bank"
+cash = "bank"
+gh = "github"
+"#;
+
+    #[test]
+    fn test_rename_site_rewires_aliases() {
+        let mut document = parse(RENAME);
+        let before = Config::from_toml_str(RENAME, "config.toml").unwrap().config;
+        let rename = rename_site(&mut document, "bank", "examplebank", None, false).unwrap();
+        assert_eq!(rename.aliases, vec!["money", "cash"]);
+        assert!(!rename.key_pinned);
+
+        let after = Config::from_toml_str(&document.to_string(), "config.toml")
+            .unwrap()
+            .config;
+        assert!(!after.sites.contains_key("bank"));
+        assert_eq!(after.resolve("money").unwrap().site, Some("examplebank"));
+        assert_eq!(
+            after.resolve("examplebank").unwrap().key,
+            before.resolve("bank").unwrap().key
+        );
+    }
+
+    #[test]
+    fn test_rename_site_pins_implicit_key() {
+        let mut document = parse(RENAME);
+        let rename = rename_site(&mut document, "github", "gh-work", None, false).unwrap();
+        assert!(rename.key_pinned);
+        assert_eq!(
+            document.to_string(),
+            "[sites.bank]\nkey = \"secure.examplebank.co.uk\"\n\n\
+             [sites.gh-work]\nlength = 20 # the default is too short\nkey = \"github\"\n\n\
+             [aliases]\nmoney = \"bank\"\ncash = \"bank\"\ngh = \"gh-work\"\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_site_with_new_key() {
+        let mut document = parse(RENAME);
+        let rename = rename_site(
+            &mut document,
+            "bank",
+            "examplebank",
+            Some("examplebank.com"),
+            false,
+        )
+        .unwrap();
+        assert!(!rename.key_pinned);
+        assert_eq!(
+            document["sites"]["examplebank"]["key"].as_str(),
+            Some("examplebank.com")
+        );
+    }
+
+    #[test]
+    fn test_rename_site_collision() {
+        let mut document = parse(RENAME);
+        let error = rename_site(&mut document, "bank", "github", None, false).unwrap_err();
+        assert_eq!(error.to_string(), "Site `github` already exists");
+        assert_eq!(document.to_string(), RENAME);
+
+        let rename = rename_site(&mut document, "bank", "github", None, true).unwrap();
+        assert_eq!(rename.replaced.unwrap().length, Some(20));
+        assert_eq!(
+            document["sites"]["github"]["key"].as_str(),
+            Some("secure.examplebank.co.uk")
+        );
+    }
+
+    #[test]
+    fn test_rename_unknown_site() {
+        let mut document = parse(RENAME);
+        let error = rename_site(&mut document, "nope", "other", None, false).unwrap_err();
+        assert_eq!(error.to_string(), "Site `nope` is not configured");
+    }
+
+    #[test]
+    fn test_set_notes() {
+        let mut document = parse(SAMPLE);
+        let previous = set_notes(
+            &mut document,
+            "examplebank",
+            "username is the old email",
+            false,
+        )
+        .unwrap();
+        assert_eq!(previous, None);
+        assert_eq!(
+            document["sites"]["examplebank"]["notes"].as_str(),
+            Some("username is the old email")
+        );
+
+        let previous = set_notes(&mut document, "examplebank", "replaced", false).unwrap();
+        assert_eq!(previous.as_deref(), Some("username is the old email"));
+        assert_eq!(
+            document["sites"]["examplebank"]["notes"].as_str(),
+            Some("replaced")
+        );
+    }
+
+    #[test]
+    fn test_append_notes() {
+        let mut document = parse(SAMPLE);
+        set_notes(&mut document, "examplebank", "first", true).unwrap();
+        set_notes(
+            &mut document,
+            "examplebank",
+            "security answers use label=sq1",
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            document["sites"]["examplebank"]["notes"].as_str(),
+            Some("first\nsecurity answers use label=sq1")
+        );
+    }
+
+    #[test]
+    fn test_unicode_notes_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let text = "用户名是旧邮箱 \"quoted\" {{not}} ${expanded} 🔑";
+        edit_file(&path, |document| {
+            set_notes(document, "examplebank", text, false)
+        })
+        .unwrap();
+
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(config.sites["examplebank"].notes.as_deref(), Some(text));
+        let reparsed = Config::from_toml_str(&config.to_toml_string(), "config.toml")
+            .unwrap()
+            .config;
+        assert_eq!(reparsed, config);
+    }
+
+    #[test]
+    fn test_set_notes_unknown_site() {
+        let mut document = parse(SAMPLE);
+        let error = set_notes(&mut document, "github", "text", false).unwrap_err();
+        assert!(matches!(error, ConfigError::UnknownSite { .. }));
+    }
+
+    #[test]
+    fn test_edit_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        edit_file(&path, |document| set_alias(document, "bank", "examplebank")).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(SAMPLE));
+        let config = Config::load(&path).unwrap().config;
+        assert_eq!(
+            config.resolve("bank").unwrap().key,
+            "secure.examplebank.co.uk"
+        );
+    }
+
+    #[test]
+    fn test_edit_file_rejects_invalid_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let result = edit_file(&path, |document| {
+            document["sites"]["examplebank"]["length"] = value(99);
+            Ok(())
+        });
+        assert!(matches!(result, Err(ConfigError::Invalid { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), SAMPLE);
+    }
+}
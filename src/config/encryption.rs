@@ -0,0 +1,414 @@
+//! Encryption of configuration files at rest
+//!
+//! The file key is derived from a passphrase with scrypt and the document is
+//! sealed with ChaCha20-Poly1305. An encrypted file has this layout, and the
+//! whole header is authenticated along with the ciphertext:
+//!
+//! | Bytes | Content                                 |
+//! |-------|-----------------------------------------|
+//! | 8     | [`ENCRYPTED_MAGIC`](super::ENCRYPTED_MAGIC) |
+//! | 1     | scrypt `log2(N)`                        |
+//! | 4     | scrypt `r`, big endian                  |
+//! | 4     | scrypt `p`, big endian                  |
+//! | 16    | scrypt salt                             |
+//! | 12    | ChaCha20-Poly1305 nonce                 |
+//! | rest  | ciphertext and 16-byte tag              |
+
+use super::{is_encrypted, write_atomic, Config, ConfigError, LoadedConfig, ENCRYPTED_MAGIC};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Default scrypt cost parameter, `N = 2^15`
+const LOG_N: u8 = 15;
+
+/// Default scrypt block size
+const R: u32 = 8;
+
+/// Default scrypt parallelism
+const P: u32 = 1;
+
+/// Most memory accepted for scrypt, `128 * r * N` bytes, so a crafted header
+/// cannot exhaust memory: 256 MiB, eight times the default cost
+const MAX_MEMORY: u64 = 256 << 20;
+
+/// Highest accepted scrypt parallelism, which multiplies the time taken
+const MAX_P: u32 = 16;
+
+/// Length of the scrypt salt in bytes
+const SALT_LEN: usize = 16;
+
+/// Length of the ChaCha20-Poly1305 nonce in bytes
+const NONCE_LEN: usize = 12;
+
+/// Length of the header preceding the ciphertext
+const HEADER_LEN: usize = ENCRYPTED_MAGIC.len() + 1 + 4 + 4 + SALT_LEN + NONCE_LEN;
+
+/// Encrypts a configuration document with a passphrase
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Vec<u8> {
+    encrypt_with_cost(plaintext, passphrase, LOG_N, R, P)
+}
+
+/// Decrypts a configuration document produced by [`encrypt`]
+///
+/// `origin` names the document in errors, usually the file path. The plaintext
+/// is wiped when the returned buffer is dropped.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Decrypt` if the passphrase is wrong or the data is not
+/// a valid encrypted configuration.
+pub fn decrypt(
+    data: &[u8],
+    passphrase: &str,
+    origin: &str,
+) -> Result<Zeroizing<Vec<u8>>, ConfigError> {
+    let error = |message: &str| ConfigError::Decrypt {
+        origin: origin.to_string(),
+        message: message.to_string(),
+    };
+
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return Err(error("not an encrypted configuration"));
+    }
+    let (header, ciphertext) = data.split_at(HEADER_LEN);
+    let mut fields = &header[ENCRYPTED_MAGIC.len()..];
+    let mut take = |len: usize| {
+        let (field, rest) = fields.split_at(len);
+        fields = rest;
+        field
+    };
+
+    let log_n = take(1)[0];
+    let r = u32::from_be_bytes(take(4).try_into().expect("field is 4 bytes"));
+    let p = u32::from_be_bytes(take(4).try_into().expect("field is 4 bytes"));
+    let salt = take(SALT_LEN);
+    let nonce = Nonce::from_slice(take(NONCE_LEN));
+    if !cost_is_accepted(log_n, r, p) {
+        return Err(error("unsupported key derivation parameters"));
+    }
+
+    let key = derive_key(passphrase, salt, log_n, r, p)
+        .ok_or_else(|| error("unsupported key derivation parameters"))?;
+    ChaCha20Poly1305::new(Key::from_slice(&*key))
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| error("wrong passphrase or corrupted file"))
+}
+
+/// Encrypts a plaintext configuration file in place
+///
+/// The file must be a valid configuration, so a typo is not locked away
+/// where [`lint`](super::lint) cannot see it.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Encrypted` if the file is already encrypted, or any
+/// error from [`Config::load`].
+pub fn encrypt_file(path: &Path, passphrase: &str) -> Result<(), ConfigError> {
+    Config::load(path)?;
+    let plaintext = Zeroizing::new(fs::read(path).map_err(|source| io_error(path, source))?);
+    write_atomic(path, &encrypt(&plaintext, passphrase))
+}
+
+/// Decrypts an encrypted configuration file in place
+///
+/// Plaintext files are left unchanged.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` if the file cannot be read or written, or
+/// `ConfigError::Decrypt` if the passphrase is wrong.
+pub fn decrypt_file(path: &Path, passphrase: &str) -> Result<(), ConfigError> {
+    let data = fs::read(path).map_err(|source| io_error(path, source))?;
+    if !is_encrypted(&data) {
+        return Ok(());
+    }
+    let plaintext = decrypt(&data, passphrase, &path.display().to_string())?;
+    write_atomic(path, &plaintext)
+}
+
+impl Config {
+    /// Loads a configuration file that may be encrypted
+    ///
+    /// `passphrase` is only called when the file is encrypted, so a frontend
+    /// can prompt lazily and plaintext files load exactly like [`Config::load`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Decrypt` if the passphrase is wrong, or any error
+    /// from [`Config::load`].
+    pub fn load_with_passphrase<F>(path: &Path, passphrase: F) -> Result<LoadedConfig, ConfigError>
+    where
+        F: FnOnce() -> String,
+    {
+        Self::load_decrypting(path, || Ok(passphrase()))
+    }
+
+    /// Loads a configuration file that may be encrypted, with a passphrase
+    /// source that can fail
+    pub(super) fn load_decrypting<F>(
+        path: &Path,
+        passphrase: F,
+    ) -> Result<LoadedConfig, ConfigError>
+    where
+        F: FnOnce() -> Result<String, ConfigError>,
+    {
+        let origin = path.display().to_string();
+        let data = fs::read(path).map_err(|source| io_error(path, source))?;
+        let data = match is_encrypted(&data) {
+            true => decrypt(&data, &passphrase()?, &origin)?,
+            false => Zeroizing::new(data),
+        };
+        Self::from_toml_bytes(&data, &origin)
+    }
+}
+
+/// Returns `true` if scrypt with these parameters stays within [`MAX_MEMORY`]
+/// and [`MAX_P`]
+fn cost_is_accepted(log_n: u8, r: u32, p: u32) -> bool {
+    let memory = 1u64
+        .checked_shl(u32::from(log_n))
+        .and_then(|n| n.checked_mul(128 * u64::from(r)));
+    p <= MAX_P && memory.is_some_and(|memory| memory <= MAX_MEMORY)
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> ConfigError {
+    ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Derives the file key, wiped on drop, or `None` if the scrypt parameters are
+/// invalid
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Option<Zeroizing<[u8; 32]>> {
+    let params = scrypt::Params::new(log_n, r, p, 32).ok()?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut *key).ok()?;
+    Some(key)
+}
+
+fn encrypt_with_cost(plaintext: &[u8], passphrase: &str, log_n: u8, r: u32, p: u32) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut data = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    data.extend_from_slice(ENCRYPTED_MAGIC);
+    data.push(log_n);
+    data.extend_from_slice(&r.to_be_bytes());
+    data.extend_from_slice(&p.to_be_bytes());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, log_n, r, p).expect("built-in parameters are valid");
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &data,
+            },
+        )
+        .expect("plaintext fits in a single message");
+    data.extend_from_slice(&ciphertext);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "version = 1\n\n[sites.github]\nkey = \"github.com\"\nlength = 16\n";
+
+    /// Cheap scrypt cost so the tests stay fast
+    fn encrypt_fast(plaintext: &[u8], passphrase: &str) -> Vec<u8> {
+        encrypt_with_cost(plaintext, passphrase, 4, R, P)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        assert!(is_encrypted(&data));
+        assert!(!data.windows(6).any(|w| w == b"github"));
+        let plaintext = decrypt(&data, "correct horse", "config.toml").unwrap();
+        assert_eq!(*plaintext, SAMPLE.as_bytes());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        let result = decrypt(&data, "battery staple", "config.toml");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to decrypt config.toml: wrong passphrase or corrupted file"
+        );
+    }
+
+    #[test]
+    fn test_tampered_header_is_rejected() {
+        let mut data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        // Flip a bit of the salt
+        data[ENCRYPTED_MAGIC.len() + 9] ^= 1;
+        assert!(decrypt(&data, "correct horse", "config.toml").is_err());
+    }
+
+    #[test]
+    fn test_truncated_data_is_rejected() {
+        let data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        let result = decrypt(&data[..HEADER_LEN - 1], "correct horse", "config.toml");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to decrypt config.toml: not an encrypted configuration"
+        );
+    }
+
+    #[test]
+    fn test_excessive_cost_is_rejected() {
+        let mut data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        data[ENCRYPTED_MAGIC.len()] = 19;
+        let result = decrypt(&data, "correct horse", "config.toml");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to decrypt config.toml: unsupported key derivation parameters"
+        );
+
+        // N = 2^20 with r = 32 would take 4 GiB
+        let mut data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        data[ENCRYPTED_MAGIC.len()] = 20;
+        data[ENCRYPTED_MAGIC.len() + 1..][..4].copy_from_slice(&32u32.to_be_bytes());
+        assert!(decrypt(&data, "correct horse", "config.toml").is_err());
+
+        let mut data = encrypt_fast(SAMPLE.as_bytes(), "correct horse");
+        data[ENCRYPTED_MAGIC.len() + 1] = 1;
+        assert!(decrypt(&data, "correct horse", "config.toml").is_err());
+    }
+
+    #[test]
+    fn test_cost_limit() {
+        assert!(cost_is_accepted(LOG_N, R, P));
+        // 128 * r * N of exactly 256 MiB
+        assert!(cost_is_accepted(18, 8, 1));
+        assert!(cost_is_accepted(17, 16, MAX_P));
+        assert!(!cost_is_accepted(19, 8, 1));
+        assert!(!cost_is_accepted(17, 17, 1));
+        assert!(!cost_is_accepted(20, 32, 1));
+        assert!(!cost_is_accepted(LOG_N, R, MAX_P + 1));
+        assert!(!cost_is_accepted(63, u32::MAX, 1));
+        assert!(!cost_is_accepted(u8::MAX, 1, 1));
+    }
+
+    #[test]
+    fn test_load_encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, encrypt_fast(SAMPLE.as_bytes(), "correct horse")).unwrap();
+
+        let loaded = Config::load_with_passphrase(&path, || "correct horse".to_string()).unwrap();
+        assert_eq!(loaded.config.resolve("github").unwrap().key, "github.com");
+
+        let wrong = Config::load_with_passphrase(&path, || "battery staple".to_string());
+        assert!(matches!(wrong, Err(ConfigError::Decrypt { .. })));
+    }
+
+    #[test]
+    fn test_load_requires_passphrase_for_encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, encrypt_fast(SAMPLE.as_bytes(), "correct horse")).unwrap();
+
+        let result = Config::load(&path);
+        assert!(matches!(result, Err(ConfigError::Encrypted { .. })));
+    }
+
+    #[test]
+    fn test_plaintext_loads_without_prompting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, SAMPLE).unwrap();
+
+        let loaded = Config::load_with_passphrase(&path, || panic!("must not prompt")).unwrap();
+        assert_eq!(loaded, Config::load(&path).unwrap());
+    }
+
+    #[test]
+    fn test_layered_load_decrypts() {
+        let dir = tempfile::tempdir().unwrap();
+        let encrypted = dir.path().join("config.toml");
+        fs::write(&encrypted, encrypt_fast(SAMPLE.as_bytes(), "correct horse")).unwrap();
+        let plaintext = dir.path().join("work.toml");
+        fs::write(&plaintext, "[sites.mail]\nkey = \"mail.example.org\"\n").unwrap();
+        let env = |name: &str| (name == "FP_CONFIG").then(|| plaintext.display().to_string());
+
+        let mut asked = Vec::new();
+        let loaded = super::super::load_layered_with_passphrase(Some(&encrypted), env, |path| {
+            asked.push(path.to_path_buf());
+            Ok("correct horse".to_string())
+        })
+        .unwrap();
+        assert_eq!(asked, std::slice::from_ref(&encrypted));
+        assert_eq!(loaded.config.resolve("github").unwrap().key, "github.com");
+        assert_eq!(
+            loaded.config.resolve("mail").unwrap().key,
+            "mail.example.org"
+        );
+
+        let failed = super::super::load_layered_with_passphrase(Some(&encrypted), env, |_| {
+            Err(std::io::Error::other("no terminal"))
+        });
+        assert_eq!(
+            failed.unwrap_err().to_string(),
+            format!("Failed to access {}: no terminal", encrypted.display())
+        );
+        let wrong = super::super::load_layered_with_passphrase(Some(&encrypted), env, |_| {
+            Ok("battery staple".to_string())
+        });
+        assert!(matches!(wrong, Err(ConfigError::Decrypt { .. })));
+    }
+
+    #[test]
+    fn test_decrypt_file_restores_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, encrypt_fast(SAMPLE.as_bytes(), "correct horse")).unwrap();
+
+        decrypt_file(&path, "correct horse").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), SAMPLE);
+        // Already plaintext: nothing to do
+        decrypt_file(&path, "ignored").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), SAMPLE);
+    }
+
+    #[test]
+    fn test_encrypt_file_refuses_invalid_or_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[sites.github]\nlength = 99\n").unwrap();
+        assert!(matches!(
+            encrypt_file(&path, "correct horse"),
+            Err(ConfigError::Invalid { .. })
+        ));
+
+        fs::write(&path, encrypt_fast(SAMPLE.as_bytes(), "correct horse")).unwrap();
+        assert!(matches!(
+            encrypt_file(&path, "correct horse"),
+            Err(ConfigError::Encrypted { .. })
+        ));
+    }
+}
@@ -0,0 +1,442 @@
+//! Exporting configured sites with their derived passwords
+//!
+//! Exports are meant for importing into another password manager, so unlike
+//! everything else in this module they contain plaintext passwords. Frontends
+//! should make writing them a deliberate act.
+//!
+//! Supported formats:
+//!
+//! - the `name,url,username,password` CSV accepted by the Chrome and Firefox
+//!   password importers ([`write_browser_csv`]);
+//! - a KeePass CSV with `Group,Title,Username,Password,URL,Notes` columns
+//!   ([`write_keepass_csv`]);
+//! - the KeePass 2.x XML format ([`write_keepass_xml`]).
+
+use super::Config;
+use crate::FlowerPasswordError;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Header row of the browser CSV format
+const BROWSER_CSV_HEADER: [&str; 4] = ["name", "url", "username", "password"];
+
+/// Header row of the KeePass CSV format
+const KEEPASS_CSV_HEADER: [&str; 6] = ["Group", "Title", "Username", "Password", "URL", "Notes"];
+
+/// A configured site together with its derived password
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExportEntry<'a> {
+    /// Site name
+    pub name: &'a str,
+    /// Key the password was derived from
+    pub key: &'a str,
+    /// Username configured for the site
+    pub username: Option<&'a str>,
+    /// Notes configured for the site
+    pub notes: Option<&'a str>,
+    /// Derived password
+    pub password: String,
+}
+
+impl ExportEntry<'_> {
+    /// Returns the URL for the site, synthesized from its key
+    ///
+    /// Keys that already have a scheme are used as they are and keys that look
+    /// like domain names get an `https://` prefix. Anything else has no URL and
+    /// yields an empty string.
+    pub fn url(&self) -> String {
+        if self.key.contains("://") {
+            self.key.to_string()
+        } else if looks_like_domain(self.key) {
+            format!("https://{}", self.key)
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl fmt::Debug for ExportEntry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExportEntry")
+            .field("name", &self.name)
+            .field("key", &self.key)
+            .field("username", &self.username)
+            .field("notes", &self.notes)
+            .field("password", &"****")
+            .finish()
+    }
+}
+
+impl Config {
+    /// Derives the password of every configured site, in name order
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if a site has an invalid length.
+    pub fn export_entries(
+        &self,
+        master: &str,
+    ) -> Result<Vec<ExportEntry<'_>>, FlowerPasswordError> {
+        self.sites
+            .iter()
+            .map(|(name, site)| {
+                // Site names match before aliases are consulted, so this cannot fail
+                let resolved = self.resolve(name).expect("configured sites always resolve");
+                Ok(ExportEntry {
+                    name,
+                    key: resolved.key,
+                    username: resolved.username,
+                    notes: site.notes.as_deref(),
+                    password: resolved
+                        .scheme
+                        .fp_code(master, resolved.key, resolved.length)?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Writes entries in the browser password import format
+///
+/// # Errors
+///
+/// Returns any error from writing to `writer`.
+pub fn write_browser_csv<W: Write>(writer: &mut W, entries: &[ExportEntry]) -> io::Result<()> {
+    write_csv_record(writer, &BROWSER_CSV_HEADER)?;
+    for entry in entries {
+        let url = entry.url();
+        write_csv_record(
+            writer,
+            &[
+                entry.name,
+                &url,
+                entry.username.unwrap_or_default(),
+                &entry.password,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes entries as a KeePass CSV, placing every entry in `group`
+///
+/// # Errors
+///
+/// Returns any error from writing to `writer`.
+pub fn write_keepass_csv<W: Write>(
+    writer: &mut W,
+    entries: &[ExportEntry],
+    group: &str,
+) -> io::Result<()> {
+    write_csv_record(writer, &KEEPASS_CSV_HEADER)?;
+    for entry in entries {
+        let url = entry.url();
+        write_csv_record(
+            writer,
+            &[
+                group,
+                entry.name,
+                entry.username.unwrap_or_default(),
+                &entry.password,
+                &url,
+                entry.notes.unwrap_or_default(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes entries as a KeePass 2.x XML file with a single group named `group`
+///
+/// # Errors
+///
+/// Returns any error from writing to `writer`.
+pub fn write_keepass_xml<W: Write>(
+    writer: &mut W,
+    entries: &[ExportEntry],
+    group: &str,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>"
+    )?;
+    writeln!(writer, "<KeePassFile>")?;
+    writeln!(writer, "\t<Root>")?;
+    writeln!(writer, "\t\t<Group>")?;
+    writeln!(writer, "\t\t\t<Name>{}</Name>", xml_escape(group))?;
+    for entry in entries {
+        let url = entry.url();
+        writeln!(writer, "\t\t\t<Entry>")?;
+        for (key, value, protect) in [
+            ("Title", entry.name, false),
+            ("UserName", entry.username.unwrap_or_default(), false),
+            ("Password", entry.password.as_str(), true),
+            ("URL", url.as_str(), false),
+            ("Notes", entry.notes.unwrap_or_default(), false),
+        ] {
+            let attributes = if protect {
+                " ProtectInMemory=\"True\""
+            } else {
+                ""
+            };
+            writeln!(
+                writer,
+                "\t\t\t\t<String><Key>{}</Key><Value{}>{}</Value></String>",
+                key,
+                attributes,
+                xml_escape(value)
+            )?;
+        }
+        writeln!(writer, "\t\t\t</Entry>")?;
+    }
+    writeln!(writer, "\t\t</Group>")?;
+    writeln!(writer, "\t</Root>")?;
+    writeln!(writer, "</KeePassFile>")
+}
+
+/// Escapes text for XML content and attributes
+///
+/// Control characters that XML 1.0 cannot represent at all are dropped.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes one CSV record, quoting fields as RFC 4180 requires
+pub(super) fn write_csv_record<W: Write>(writer: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        let needs_quotes = field.contains([',', '"', '\n', '\r'])
+            || field.starts_with(' ')
+            || field.ends_with(' ');
+        if needs_quotes {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
+
+/// Returns `true` if `key` looks like a host name such as `github.com`
+fn looks_like_domain(key: &str) -> bool {
+    let labels: Vec<&str> = key.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::fixtures::config;
+    use crate::fp_code;
+    use std::collections::BTreeMap;
+
+    const FIXTURE: &str = r#"[sites.github]
+key = "github.com"
+username = "octocat@example.com"
+
+[sites.intranet]
+key = "https://intranet.example/login"
+length = 12
+
+[sites."Bank, \"Main\""]
+key = "secure.examplebank.co.uk"
+username = "jane doe "
+
+[sites.wifi]
+key = "home router"
+length = 8
+notes = "<admin> & \"guest\" networks\nsecond line"
+"#;
+
+    #[test]
+    fn test_export_entries_derive_passwords() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name).collect();
+        assert_eq!(names, vec!["Bank, \"Main\"", "github", "intranet", "wifi"]);
+
+        let github = &entries[1];
+        assert_eq!(github.password, "D04175F7A9c7Ab4a");
+        assert_eq!(github.username, Some("octocat@example.com"));
+        for entry in &entries {
+            let length = config.resolve(entry.name).unwrap().length;
+            assert_eq!(entry.password, fp_code("test", entry.key, length).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_urls() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        let urls: Vec<String> = entries.iter().map(ExportEntry::url).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://secure.examplebank.co.uk",
+                "https://github.com",
+                "https://intranet.example/login",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_browser_csv() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        let mut out = Vec::new();
+        write_browser_csv(&mut out, &entries).unwrap();
+
+        let expected = format!(
+            "name,url,username,password\r\n\
+             \"Bank, \"\"Main\"\"\",https://secure.examplebank.co.uk,\"jane doe \",{}\r\n\
+             github,https://github.com,octocat@example.com,D04175F7A9c7Ab4a\r\n\
+             intranet,https://intranet.example/login,,{}\r\n\
+             wifi,,,{}\r\n",
+            entries[0].password, entries[2].password, entries[3].password
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_keepass_csv() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        let mut out = Vec::new();
+        write_keepass_csv(&mut out, &entries, "Flower Password").unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let mut lines = text.split("\r\n");
+        assert_eq!(
+            lines.next(),
+            Some("Group,Title,Username,Password,URL,Notes")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                format!(
+                    "Flower Password,\"Bank, \"\"Main\"\"\",\"jane doe \",{},https://secure.examplebank.co.uk,",
+                    entries[0].password
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some("Flower Password,github,octocat@example.com,D04175F7A9c7Ab4a,https://github.com,")
+        );
+        assert!(text.ends_with(&format!(
+            "Flower Password,wifi,,{},,\"<admin> & \"\"guest\"\" networks\nsecond line\"\r\n",
+            entries[3].password
+        )));
+    }
+
+    #[test]
+    fn test_keepass_xml_round_trip() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        let mut out = Vec::new();
+        write_keepass_xml(&mut out, &entries, "Work & Home").unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        let root = document.root_element();
+        assert_eq!(root.tag_name().name(), "KeePassFile");
+        let group = root
+            .children()
+            .find(|node| node.has_tag_name("Root"))
+            .and_then(|node| node.children().find(|node| node.has_tag_name("Group")))
+            .unwrap();
+        let name = group.children().find(|node| node.has_tag_name("Name"));
+        assert_eq!(name.and_then(|node| node.text()), Some("Work & Home"));
+
+        let parsed: Vec<BTreeMap<&str, &str>> = group
+            .children()
+            .filter(|node| node.has_tag_name("Entry"))
+            .map(|entry| {
+                entry
+                    .children()
+                    .filter(|node| node.has_tag_name("String"))
+                    .map(|string| {
+                        let text = |tag: &str| {
+                            string
+                                .children()
+                                .find(|node| node.has_tag_name(tag))
+                                .and_then(|node| node.text())
+                                .unwrap_or_default()
+                        };
+                        (text("Key"), text("Value"))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(parsed.len(), entries.len());
+        assert_eq!(parsed[0]["Title"], "Bank, \"Main\"");
+        assert_eq!(parsed[1]["Password"], "D04175F7A9c7Ab4a");
+        assert_eq!(parsed[1]["UserName"], "octocat@example.com");
+        assert_eq!(parsed[1]["URL"], "https://github.com");
+        assert_eq!(
+            parsed[3]["Notes"],
+            "<admin> & \"guest\" networks\nsecond line"
+        );
+        assert_eq!(parsed[3]["Password"], entries[3].password);
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("a<b>&\"c'\u{1}\td"),
+            "a&lt;b&gt;&amp;&quot;c&apos;\td"
+        );
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let mut out = Vec::new();
+        write_csv_record(&mut out, &["plain", "a,b", "say \"hi\"", "two\nlines", ""]).unwrap();
+        assert_eq!(
+            out,
+            b"plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_looks_like_domain() {
+        assert!(looks_like_domain("github.com"));
+        assert!(looks_like_domain("secure.examplebank.co.uk"));
+        assert!(looks_like_domain("网站.com"));
+        assert!(!looks_like_domain("github"));
+        assert!(!looks_like_domain("home router"));
+        assert!(!looks_like_domain("github..com"));
+        assert!(!looks_like_domain("-bad.com"));
+    }
+
+    #[test]
+    fn test_debug_hides_password() {
+        let config = config(FIXTURE);
+        let entries = config.export_entries("test").unwrap();
+        assert!(!format!("{:?}", entries).contains("D04175F7A9c7Ab4a"));
+    }
+}
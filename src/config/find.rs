@@ -0,0 +1,292 @@
+//! Fuzzy search over configured sites
+//!
+//! Queries match case-insensitively as subsequences, the way fzf's simple
+//! algorithm does: `gh` matches `github`, `exbk` matches `examplebank`. Matches
+//! score higher when they are consecutive or start at a word boundary, and lower
+//! the more characters they skip.
+
+use super::Config;
+
+/// Score for every matched character
+const SCORE_MATCH: i32 = 16;
+
+/// Bonus for a match at the start of the text or right after a separator
+const BONUS_BOUNDARY: i32 = 8;
+
+/// Bonus for a match directly following the previous one
+const BONUS_CONSECUTIVE: i32 = 4;
+
+/// Penalty for every character skipped between two matches
+const PENALTY_GAP: i32 = 1;
+
+/// Matches scoring within this much of the best one are too close to pick
+/// automatically
+pub const TIE_THRESHOLD: i32 = 4;
+
+/// Part of a site entry a query matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchField {
+    /// The site name
+    Name,
+    /// The site's key
+    Key,
+    /// An alias pointing at the site
+    Alias,
+    /// The site's notes
+    Notes,
+}
+
+/// A site matching a fuzzy query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SiteMatch<'a> {
+    /// Name of the matching site
+    pub site: &'a str,
+    /// Field that matched best
+    pub field: MatchField,
+    /// Text of that field
+    pub text: &'a str,
+    /// Match quality, higher is better
+    pub score: i32,
+}
+
+/// Scores how well `query` fuzzy-matches `text`
+///
+/// Returns `None` if the characters of `query` do not all appear in `text` in
+/// order. Comparison ignores case, including for non-ASCII text; an empty query
+/// matches everything with a score of 0.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query = fold(query);
+    let text = fold(text);
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    // Find the first window containing the whole query, then shrink it from the
+    // left so that the match is as tight as possible
+    let mut matched = 0;
+    let mut end = None;
+    for (i, c) in text.iter().enumerate() {
+        if *c == query[matched] {
+            matched += 1;
+            if matched == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut remaining = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if text[i] == query[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut position = start;
+    for q in &query {
+        while text[position] != *q {
+            position += 1;
+        }
+        score += SCORE_MATCH;
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            score += BONUS_BOUNDARY;
+        }
+        match previous {
+            Some(previous) if previous + 1 == position => score += BONUS_CONSECUTIVE,
+            Some(previous) => score -= PENALTY_GAP * (position - previous - 1) as i32,
+            None => {}
+        }
+        previous = Some(position);
+        position += 1;
+    }
+
+    Some(score)
+}
+
+/// Returns `true` if the best two matches score too close to pick one
+///
+/// `matches` must be ranked, as returned by [`Config::find`].
+pub fn is_tie(matches: &[SiteMatch]) -> bool {
+    match matches {
+        [first, second, ..] => first.score - second.score <= TIE_THRESHOLD,
+        _ => false,
+    }
+}
+
+/// Lowercases text for comparison
+fn fold(text: &str) -> Vec<char> {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+impl Config {
+    /// Ranks configured sites by how well their name, key, aliases or notes match `query`
+    ///
+    /// Each site appears at most once, with its best-matching field. Matches are
+    /// ordered by descending score, then by site name.
+    pub fn find<'a>(&'a self, query: &str) -> Vec<SiteMatch<'a>> {
+        let mut candidates: Vec<(&str, MatchField, &str)> = Vec::new();
+        for (name, site) in &self.sites {
+            candidates.push((name, MatchField::Name, name));
+            if let Some(key) = &site.key {
+                candidates.push((name, MatchField::Key, key));
+            }
+            if let Some(notes) = &site.notes {
+                candidates.push((name, MatchField::Notes, notes));
+            }
+        }
+        for alias in self.aliases.keys() {
+            if let Ok(resolved) = self.resolve(alias) {
+                if let (Some(site), Some(_)) = (resolved.site, resolved.alias) {
+                    candidates.push((site, MatchField::Alias, alias));
+                }
+            }
+        }
+
+        let mut matches: Vec<SiteMatch> = Vec::new();
+        for (site, field, text) in candidates {
+            let Some(score) = fuzzy_score(query, text) else {
+                continue;
+            };
+            let candidate = SiteMatch {
+                site,
+                field,
+                text,
+                score,
+            };
+            match matches.iter_mut().find(|m| m.site == site) {
+                Some(best) if best.score < score => *best = candidate,
+                Some(_) => {}
+                None => matches.push(candidate),
+            }
+        }
+
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.site.cmp(b.site)));
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::fixtures::config;
+
+    const SITES: &str = r#"[sites.github]
+key = "github.com"
+
+[sites.gitlab]
+key = "gitlab.com"
+
+[sites.examplebank]
+key = "secure.examplebank.co.uk"
+notes = "username is the old email"
+
+[sites."网站"]
+key = "网站.com"
+
+[sites."Straße"]
+
+[aliases]
+bank = "examplebank"
+"#;
+
+    #[test]
+    fn test_subsequence_matching() {
+        assert!(fuzzy_score("gh", "github").is_some());
+        assert!(fuzzy_score("ghb", "github").is_some());
+        assert!(fuzzy_score("hg", "github").is_none());
+        assert!(fuzzy_score("githubs", "github").is_none());
+        assert_eq!(fuzzy_score("", "github"), Some(0));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert_eq!(
+            fuzzy_score("GitHub", "github"),
+            fuzzy_score("github", "GITHUB")
+        );
+        assert!(fuzzy_score("ÉTÉ", "été").is_some());
+    }
+
+    #[test]
+    fn test_score_prefers_tight_and_boundary_matches() {
+        let consecutive = fuzzy_score("git", "github").unwrap();
+        let scattered = fuzzy_score("git", "gadget-it").unwrap();
+        assert!(consecutive > scattered);
+
+        let boundary = fuzzy_score("bank", "secure.bank.com").unwrap();
+        let inner = fuzzy_score("bank", "sandbankers").unwrap();
+        assert!(boundary > inner);
+    }
+
+    #[test]
+    fn test_window_is_shrunk() {
+        // The leading `g` of "gg-github" must not count as the start of the match
+        assert_eq!(
+            fuzzy_score("github", "gg-github"),
+            fuzzy_score("github", "xx-github")
+        );
+    }
+
+    #[test]
+    fn test_find_ranks_matches() {
+        let config = config(SITES);
+        let matches = config.find("gith");
+        let sites: Vec<&str> = matches.iter().map(|m| m.site).collect();
+        assert_eq!(sites, vec!["github"]);
+
+        let matches = config.find("git");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].site, "github");
+        assert_eq!(matches[1].site, "gitlab");
+    }
+
+    #[test]
+    fn test_find_searches_keys_and_aliases() {
+        let config = config(SITES);
+        let by_key = config.find("secure");
+        assert_eq!(by_key[0].site, "examplebank");
+        assert_eq!(by_key[0].field, MatchField::Key);
+
+        let by_alias = config.find("bank");
+        assert_eq!(by_alias[0].site, "examplebank");
+        assert_eq!(by_alias[0].field, MatchField::Alias);
+        assert_eq!(by_alias.len(), 1);
+
+        let by_notes = config.find("username");
+        assert_eq!(by_notes[0].site, "examplebank");
+        assert_eq!(by_notes[0].field, MatchField::Notes);
+    }
+
+    #[test]
+    fn test_find_unicode_queries() {
+        let config = config(SITES);
+        assert_eq!(config.find("网")[0].site, "网站");
+        assert_eq!(config.find("STRASSE").len(), 0);
+        assert_eq!(config.find("straße")[0].site, "Straße");
+    }
+
+    #[test]
+    fn test_find_no_match() {
+        assert!(config(SITES).find("zzz").is_empty());
+    }
+
+    #[test]
+    fn test_tie_handling() {
+        let config = config(SITES);
+        let matches = config.find("git");
+        assert!(is_tie(&matches));
+        assert_eq!(matches[0].score, matches[1].score);
+
+        let matches = config.find("github");
+        assert!(!is_tie(&matches));
+        assert!(!is_tie(&[]));
+    }
+}
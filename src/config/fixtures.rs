@@ -0,0 +1,8 @@
+//! Helpers shared by the tests of the configuration modules
+
+use super::Config;
+
+/// Parses `source` as the contents of `config.toml`, panicking if it is invalid
+pub(crate) fn config(source: &str) -> Config {
+    Config::from_toml_str(source, "config.toml").unwrap().config
+}
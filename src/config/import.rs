@@ -0,0 +1,496 @@
+//! Bootstrapping the configuration from a browser password export
+//!
+//! Browsers export saved logins as CSV. Importing one creates a site entry per
+//! login, keyed by the registrable domain of its URL and carrying its username.
+//! The passwords in the export are ignored: they stay derived.
+//!
+//! Registrable domains are approximated without the public suffix list: the
+//! last two labels of the host, or the last three when the second-to-last label
+//! is a common second-level suffix such as `co.uk`.
+
+use super::{add_site, Config, ConfigError, SiteConfig};
+use std::collections::BTreeMap;
+use std::fmt;
+use toml_edit::DocumentMut;
+
+/// Second-level labels that are part of the public suffix under a country code
+const SECOND_LEVEL_SUFFIXES: &[&str] = &["ac", "co", "com", "edu", "gov", "net", "org"];
+
+/// Why a row of the export was not imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The row has no URL
+    NoUrl,
+    /// The URL is not a web address, such as an `android://` app login
+    UnsupportedUrl(String),
+    /// A site with the same key and username is already configured, or was
+    /// imported from an earlier row
+    AlreadyPresent(String),
+    /// Another account on the same domain was already imported or configured
+    DuplicateAccount(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::NoUrl => write!(f, "no URL"),
+            SkipReason::UnsupportedUrl(url) => write!(f, "unsupported URL `{}`", url),
+            SkipReason::AlreadyPresent(site) => write!(f, "same account as `{}`", site),
+            SkipReason::DuplicateAccount(domain) => {
+                write!(f, "another account on {} already exists", domain)
+            }
+        }
+    }
+}
+
+/// A row of the export that was not imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRow {
+    /// 1-based line on which the row starts
+    pub line: usize,
+    /// Why it was skipped
+    pub reason: SkipReason,
+}
+
+/// Result of planning an import
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Import {
+    /// Sites to create, in the order their rows appear
+    pub created: Vec<(String, SiteConfig)>,
+    /// Rows that were not imported
+    pub skipped: Vec<SkippedRow>,
+}
+
+impl Import {
+    /// Adds every planned site to a configuration document
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::SiteExists` if a planned name has been configured
+    /// since the import was planned, or any other error from [`add_site`].
+    pub fn apply(&self, document: &mut DocumentMut) -> Result<(), ConfigError> {
+        for (name, site) in &self.created {
+            add_site(document, name, site, false)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses CSV text into records, following RFC 4180
+///
+/// Quoted fields may contain commas, doubled quotes and line breaks. Both `\r\n`
+/// and `\n` end records, and a leading byte order mark is ignored. Each record is
+/// returned with the 1-based line it starts on.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Parse` if a quoted field is never closed.
+pub fn parse_csv(input: &str, origin: &str) -> Result<Vec<(usize, Vec<String>)>, ConfigError> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ConfigError::Parse {
+            origin: origin.to_string(),
+            message: format!(
+                "quoted field starting on line {} is never closed",
+                record_line
+            ),
+        });
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+/// Returns the registrable domain of a web URL, or `None` if it is not one
+///
+/// `https://accounts.google.com/signin` gives `google.com`, and
+/// `https://secure.examplebank.co.uk:8443/` gives `examplebank.co.uk`.
+pub fn registrable_domain(url: &str) -> Option<String> {
+    let rest = match url.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => rest,
+        Some(_) => return None,
+        None => url,
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.iter().any(|label| label.is_empty()) {
+        return None;
+    }
+    let is_ip = labels.len() == 4 && labels.iter().all(|label| label.parse::<u8>().is_ok());
+    if is_ip || labels.len() < 2 {
+        return Some(host);
+    }
+
+    let n = labels.len();
+    let keep =
+        if n >= 3 && labels[n - 1].len() == 2 && SECOND_LEVEL_SUFFIXES.contains(&labels[n - 2]) {
+            3
+        } else {
+            2
+        };
+    Some(labels[n - keep..].join("."))
+}
+
+impl Config {
+    /// Plans sites to create from a browser password export
+    ///
+    /// The export must have a header row with a `url` column; a `username`
+    /// column is used when present. Each new site is named after the first
+    /// label of its domain (`github` for `github.com`).
+    ///
+    /// Rows for a domain that already has an account, in the configuration or
+    /// earlier in the export, are skipped unless `allow_duplicates` is set, in
+    /// which case they get indexed names such as `github-2`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Parse` if the export is not valid CSV or has no
+    /// `url` column.
+    pub fn plan_import(
+        &self,
+        csv: &str,
+        origin: &str,
+        allow_duplicates: bool,
+    ) -> Result<Import, ConfigError> {
+        let mut records = parse_csv(csv, origin)?.into_iter();
+        let header = records.next().map(|(_, header)| header).unwrap_or_default();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|column| column.trim().eq_ignore_ascii_case(name))
+        };
+        let url_column = column("url").ok_or_else(|| ConfigError::Parse {
+            origin: origin.to_string(),
+            message: "the header has no `url` column".to_string(),
+        })?;
+        let username_column = column("username");
+
+        // Names and (key, username) pairs taken so far, including the configuration
+        let mut names: Vec<String> = self.sites.keys().cloned().collect();
+        let mut accounts: BTreeMap<String, Vec<(Option<String>, String)>> = BTreeMap::new();
+        for (name, site) in &self.sites {
+            let key = site.key.clone().unwrap_or_else(|| name.clone());
+            accounts
+                .entry(key)
+                .or_default()
+                .push((site.username.clone(), name.clone()));
+        }
+
+        let mut import = Import::default();
+        for (line, record) in records {
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|index| record.get(index))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+            };
+            let skip = |reason| SkippedRow { line, reason };
+
+            let Some(url) = field(Some(url_column)) else {
+                import.skipped.push(skip(SkipReason::NoUrl));
+                continue;
+            };
+            let Some(domain) = registrable_domain(url) else {
+                import
+                    .skipped
+                    .push(skip(SkipReason::UnsupportedUrl(url.to_string())));
+                continue;
+            };
+            let username = field(username_column).map(str::to_string);
+
+            let existing = accounts.entry(domain.clone()).or_default();
+            if let Some((_, site)) = existing.iter().find(|(user, _)| *user == username) {
+                import
+                    .skipped
+                    .push(skip(SkipReason::AlreadyPresent(site.clone())));
+                continue;
+            }
+            if !existing.is_empty() && !allow_duplicates {
+                import
+                    .skipped
+                    .push(skip(SkipReason::DuplicateAccount(domain)));
+                continue;
+            }
+
+            let base = domain.split('.').next().unwrap_or(&domain).to_string();
+            let name = (1..)
+                .map(|index| match index {
+                    1 => base.clone(),
+                    index => format!("{}-{}", base, index),
+                })
+                .find(|name| !names.contains(name))
+                .expect("an unused name always exists");
+
+            names.push(name.clone());
+            existing.push((username.clone(), name.clone()));
+            import.created.push((
+                name,
+                SiteConfig {
+                    key: Some(domain),
+                    username,
+                    ..SiteConfig::default()
+                },
+            ));
+        }
+
+        Ok(import)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::export::write_browser_csv;
+
+    /// Shaped like real exports: a BOM, Chrome's column order, app logins,
+    /// quoted commas, a multi-line note, empty usernames and repeated domains
+    const MESSY: &str = "\u{feff}name,url,username,password,note\r\n\
+        github.com,https://github.com/login,octocat@example.com,hunter2,\r\n\
+        \"Bank, main\",https://secure.examplebank.co.uk:8443/online?x=1,\"jane,doe\",\"p,w\",\"line one\r\nline two\"\r\n\
+        app,android://hash@com.example.app/,someone,secret,\r\n\
+        nothing,,someone,secret,\r\n\
+        gmail,https://accounts.google.com/,,pw,\r\n\
+        github work,https://www.github.com/,work@example.com,pw2,\r\n\
+        gmail again,https://mail.google.com/,,pw,\r\n";
+
+    fn plan(config: &Config, allow_duplicates: bool) -> Import {
+        config
+            .plan_import(MESSY, "export.csv", allow_duplicates)
+            .unwrap()
+    }
+
+    fn site(key: &str, username: Option<&str>) -> SiteConfig {
+        SiteConfig {
+            key: Some(key.to_string()),
+            username: username.map(str::to_string),
+            ..SiteConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let records = parse_csv("a,\"b,c\",\"say \"\"hi\"\"\"\n\"x\ny\",,z", "t.csv").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (1, vec!["a".into(), "b,c".into(), "say \"hi\"".into()]),
+                (2, vec!["x\ny".into(), "".into(), "z".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_unterminated_quote() {
+        let error = parse_csv("a,b\n\"open,field\n", "t.csv").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse t.csv: quoted field starting on line 2 is never closed"
+        );
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        let cases = [
+            ("https://github.com/login", Some("github.com")),
+            ("https://accounts.google.com/", Some("google.com")),
+            ("http://user@www.Example.ORG:8080/x", Some("example.org")),
+            (
+                "https://secure.examplebank.co.uk/",
+                Some("examplebank.co.uk"),
+            ),
+            ("https://192.168.1.1/admin", Some("192.168.1.1")),
+            ("https://localhost:3000", Some("localhost")),
+            ("example.com", Some("example.com")),
+            ("android://hash@com.example.app/", None),
+            ("https://", None),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(registrable_domain(url).as_deref(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_import_messy_export() {
+        let import = plan(&Config::default(), false);
+        assert_eq!(
+            import.created,
+            vec![
+                (
+                    "github".to_string(),
+                    site("github.com", Some("octocat@example.com"))
+                ),
+                (
+                    "examplebank".to_string(),
+                    site("examplebank.co.uk", Some("jane,doe"))
+                ),
+                ("google".to_string(), site("google.com", None)),
+            ]
+        );
+        assert_eq!(
+            import.skipped,
+            vec![
+                SkippedRow {
+                    line: 5,
+                    reason: SkipReason::UnsupportedUrl("android://hash@com.example.app/".into()),
+                },
+                SkippedRow {
+                    line: 6,
+                    reason: SkipReason::NoUrl,
+                },
+                SkippedRow {
+                    line: 8,
+                    reason: SkipReason::DuplicateAccount("github.com".into()),
+                },
+                SkippedRow {
+                    line: 9,
+                    reason: SkipReason::AlreadyPresent("google".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_allow_duplicates() {
+        let import = plan(&Config::default(), true);
+        let names: Vec<&str> = import
+            .created
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["github", "examplebank", "google", "github-2"]);
+        assert_eq!(
+            import.created[3].1,
+            site("github.com", Some("work@example.com"))
+        );
+    }
+
+    #[test]
+    fn test_import_skips_configured_sites() {
+        let config = Config::from_toml_str(
+            "[sites.gh]\nkey = \"github.com\"\nusername = \"octocat@example.com\"\n\n[sites.google]\nkey = \"g.example\"\n",
+            "config.toml",
+        )
+        .unwrap()
+        .config;
+        let import = plan(&config, true);
+
+        assert_eq!(
+            import.skipped[0],
+            SkippedRow {
+                line: 2,
+                reason: SkipReason::AlreadyPresent("gh".into()),
+            }
+        );
+        let names: Vec<&str> = import
+            .created
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["examplebank", "google-2", "github"]);
+    }
+
+    #[test]
+    fn test_apply_import() {
+        let mut document: DocumentMut = "# mine\n[sites.gh]\nkey = \"github.com\"\n"
+            .parse()
+            .unwrap();
+        let config = Config::from_toml_str(&document.to_string(), "config.toml")
+            .unwrap()
+            .config;
+        plan(&config, false).apply(&mut document).unwrap();
+
+        let applied = Config::from_toml_str(&document.to_string(), "config.toml")
+            .unwrap()
+            .config;
+        assert!(document.to_string().starts_with("# mine\n"));
+        assert_eq!(applied.sites.len(), 3);
+        assert_eq!(applied.sites["google"], site("google.com", None));
+    }
+
+    #[test]
+    fn test_import_requires_url_column() {
+        let error = Config::default()
+            .plan_import("name,password\nx,y\n", "export.csv", false)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse export.csv: the header has no `url` column"
+        );
+    }
+
+    #[test]
+    fn test_import_firefox_columns() {
+        let csv = "\"url\",\"username\",\"password\",\"httpRealm\"\n\"https://github.com\",\"octocat\",\"pw\",\"\"\n";
+        let import = Config::default()
+            .plan_import(csv, "export.csv", false)
+            .unwrap();
+        assert_eq!(
+            import.created,
+            vec![("github".to_string(), site("github.com", Some("octocat")))]
+        );
+    }
+
+    #[test]
+    fn test_export_then_import_round_trip() {
+        let config = Config::from_toml_str(
+            "[sites.github]\nkey = \"github.com\"\nusername = \"octocat\"\n",
+            "config.toml",
+        )
+        .unwrap()
+        .config;
+        let mut csv = Vec::new();
+        write_browser_csv(&mut csv, &config.export_entries("test").unwrap()).unwrap();
+
+        let import = Config::default()
+            .plan_import(std::str::from_utf8(&csv).unwrap(), "export.csv", false)
+            .unwrap();
+        assert_eq!(
+            import.created,
+            vec![("github".to_string(), config.sites["github"].clone())]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Layered configuration loading
+//!
+//! Layers are applied in this order, later layers winning key by key:
+//!
+//! 1. built-in defaults ([`DEFAULT_LENGTH`])
+//! 2. system-wide file (`/etc/flowerpassword/config.toml` on Unix)
+//! 3. user file in the platform config directory
+//! 4. explicit override file (e.g. a `--config` argument)
+//! 5. file named by `FP_CONFIG`
+//! 6. `FP_LENGTH`, overriding the default length
+//!
+//! Missing system and user files are skipped; missing override files are errors.
+
+use super::{Config, ConfigError, Defaults, LoadedConfig, CONFIG_FILE_NAME, DEFAULT_LENGTH};
+use directories::ProjectDirs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the default password length
+pub const ENV_LENGTH: &str = "FP_LENGTH";
+
+/// Environment variable naming an additional configuration file
+pub const ENV_CONFIG: &str = "FP_CONFIG";
+
+/// Source of a file-backed configuration layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    /// System-wide configuration file
+    System,
+    /// Per-user configuration file
+    User,
+    /// Explicitly requested configuration file
+    Override,
+    /// Configuration file named by `FP_CONFIG`
+    Environment,
+}
+
+impl LayerKind {
+    /// Returns `true` if loading fails when the file is missing
+    pub fn is_required(self) -> bool {
+        matches!(self, LayerKind::Override | LayerKind::Environment)
+    }
+}
+
+impl fmt::Display for LayerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LayerKind::System => "system",
+            LayerKind::User => "user",
+            LayerKind::Override => "override",
+            LayerKind::Environment => ENV_CONFIG,
+        };
+        f.write_str(name)
+    }
+}
+
+/// Resolved location of a configuration layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigPath {
+    /// Which layer the file provides
+    pub kind: LayerKind,
+    /// Location of the file
+    pub path: PathBuf,
+    /// Whether the file existed when the paths were resolved
+    pub found: bool,
+}
+
+/// Returns the system-wide configuration file path, where the platform has one
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(unix) {
+        Some(Path::new("/etc/flowerpassword").join(CONFIG_FILE_NAME))
+    } else {
+        None
+    }
+}
+
+/// Returns the per-user configuration file path for the current platform
+///
+/// This is `$XDG_CONFIG_HOME/flowerpassword/config.toml` on Linux,
+/// `~/Library/Application Support/flowerpassword/config.toml` on macOS and
+/// `%APPDATA%\flowerpassword\config\config.toml` on Windows.
+pub fn user_config_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "flowerpassword").map(|dirs| dirs.config_dir().join(CONFIG_FILE_NAME))
+}
+
+/// Resolves the configuration files in layer order
+///
+/// `env` looks up environment variables, so callers and tests can control
+/// `FP_CONFIG` without touching the process environment.
+pub fn config_paths<F>(override_path: Option<&Path>, env: F) -> Vec<ConfigPath>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let candidates = [
+        (LayerKind::System, system_config_path()),
+        (LayerKind::User, user_config_path()),
+        (LayerKind::Override, override_path.map(Path::to_path_buf)),
+        (
+            LayerKind::Environment,
+            env(ENV_CONFIG)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from),
+        ),
+    ];
+
+    candidates
+        .into_iter()
+        .filter_map(|(kind, path)| {
+            path.map(|path| ConfigPath {
+                kind,
+                found: path.is_file(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Loads the configuration from every layer, using the process environment
+///
+/// # Errors
+///
+/// Returns `ConfigError` if an existing file is invalid, a required file is
+/// missing, or an environment variable has an invalid value.
+pub fn load_layered(override_path: Option<&Path>) -> Result<LoadedConfig, ConfigError> {
+    load_layered_with(override_path, |name| std::env::var(name).ok())
+}
+
+/// Loads the configuration from every layer, looking up variables through `env`
+///
+/// # Errors
+///
+/// See [`load_layered`].
+pub fn load_layered_with<F>(
+    override_path: Option<&Path>,
+    env: F,
+) -> Result<LoadedConfig, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    load_layers(override_path, env, Config::load)
+}
+
+/// Loads the configuration from every layer like [`load_layered_with`],
+/// decrypting encrypted files
+///
+/// `passphrase` is called with the path of each encrypted file and only for
+/// those, so plaintext layers load without prompting.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` with the file's path if `passphrase` fails,
+/// `ConfigError::Decrypt` if it returns the wrong passphrase, or any error from
+/// [`load_layered`].
+#[cfg(feature = "config-encryption")]
+pub fn load_layered_with_passphrase<F, P>(
+    override_path: Option<&Path>,
+    env: F,
+    mut passphrase: P,
+) -> Result<LoadedConfig, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+    P: FnMut(&Path) -> std::io::Result<String>,
+{
+    load_layers(override_path, env, |path| {
+        Config::load_decrypting(path, || {
+            passphrase(path).map_err(|source| ConfigError::Io {
+                path: path.to_path_buf(),
+                source,
+            })
+        })
+    })
+}
+
+/// Merges every layer, reading each file that exists or is required with `load`
+fn load_layers<F, L>(
+    override_path: Option<&Path>,
+    env: F,
+    mut load: L,
+) -> Result<LoadedConfig, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+    L: FnMut(&Path) -> Result<LoadedConfig, ConfigError>,
+{
+    let mut config = Config::builtin();
+    let mut warnings = Vec::new();
+
+    for layer in config_paths(override_path, &env) {
+        if !layer.found && !layer.kind.is_required() {
+            continue;
+        }
+        let loaded = load(&layer.path)?;
+        config.merge(loaded.config);
+        warnings.extend(loaded.warnings);
+    }
+
+    config.merge(environment_layer(&env)?);
+
+    Ok(LoadedConfig { config, warnings })
+}
+
+/// Builds the layer provided by environment variables
+fn environment_layer<F>(env: F) -> Result<Config, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let length = match env(ENV_LENGTH).filter(|value| !value.is_empty()) {
+        Some(value) => Some(value.trim().parse().map_err(|_| ConfigError::Environment {
+            variable: ENV_LENGTH.to_string(),
+            message: format!("expected a length, got `{}`", value),
+        })?),
+        None => None,
+    };
+
+    Ok(Config {
+        defaults: Defaults { length },
+        ..Config::default()
+    })
+}
+
+impl Config {
+    /// Returns the built-in defaults that every layered load starts from
+    pub fn builtin() -> Self {
+        Config {
+            defaults: Defaults {
+                length: Some(DEFAULT_LENGTH),
+            },
+            ..Config::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn parse(source: &str) -> Config {
+        Config::from_toml_str(source, "layer.toml").unwrap().config
+    }
+
+    fn env_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_merge_order_later_layers_win() {
+        let system = parse("[defaults]\nlength = 12\n\n[sites.github]\nkey = \"github.com\"\n");
+        let user = parse("[defaults]\nlength = 20\n\n[sites.github]\nlength = 24\n");
+        let explicit = parse("[sites.github]\nkey = \"github.example\"\n");
+
+        let mut config = Config::builtin();
+        for layer in [system, user, explicit] {
+            config.merge(layer);
+        }
+
+        let github = config.resolve("github").unwrap();
+        assert_eq!(github.key, "github.example");
+        assert_eq!(github.length, 24);
+        assert_eq!(config.resolve("other").unwrap().length, 20);
+    }
+
+    #[test]
+    fn test_builtin_defaults() {
+        assert_eq!(
+            Config::builtin().resolve("any").unwrap().length,
+            DEFAULT_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_env_length_overrides_defaults() {
+        let layer = environment_layer(env_from(&[(ENV_LENGTH, "24")])).unwrap();
+        let mut config = parse("[defaults]\nlength = 12\n");
+        config.merge(layer);
+        assert_eq!(config.defaults.length, Some(24));
+    }
+
+    #[test]
+    fn test_env_length_does_not_override_site_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "[sites.github]\nlength = 32\n").unwrap();
+
+        let loaded = load_layered_with(Some(&path), env_from(&[(ENV_LENGTH, "8")])).unwrap();
+        assert_eq!(loaded.config.resolve("github").unwrap().length, 32);
+        assert_eq!(loaded.config.resolve("other").unwrap().length, 8);
+    }
+
+    #[test]
+    fn test_env_length_invalid() {
+        let result = environment_layer(env_from(&[(ENV_LENGTH, "long")]));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid value for FP_LENGTH: expected a length, got `long`"
+        );
+    }
+
+    #[test]
+    fn test_env_unset_is_empty_layer() {
+        let layer = environment_layer(env_from(&[(ENV_LENGTH, "")])).unwrap();
+        assert_eq!(layer, Config::default());
+    }
+
+    #[test]
+    fn test_env_config_file_wins_over_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let explicit = dir.path().join("explicit.toml");
+        let from_env = dir.path().join("env.toml");
+        fs::write(&explicit, "[sites.github]\nkey = \"a\"\nlength = 10\n").unwrap();
+        fs::write(&from_env, "[sites.github]\nkey = \"b\"\n").unwrap();
+
+        let env = env_from(&[(ENV_CONFIG, from_env.to_str().unwrap())]);
+        let loaded = load_layered_with(Some(&explicit), env).unwrap();
+        let github = loaded.config.resolve("github").unwrap();
+        assert_eq!(github.key, "b");
+        assert_eq!(github.length, 10);
+    }
+
+    #[test]
+    fn test_missing_override_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.toml");
+        let result = load_layered_with(Some(&missing), env_from(&[]));
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+    }
+
+    #[test]
+    fn test_missing_env_config_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.toml");
+        let result = load_layered_with(None, env_from(&[(ENV_CONFIG, missing.to_str().unwrap())]));
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+    }
+
+    #[test]
+    fn test_config_paths_reports_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let explicit = dir.path().join("explicit.toml");
+        fs::write(&explicit, "").unwrap();
+        let missing = dir.path().join("missing.toml");
+
+        let env = env_from(&[(ENV_CONFIG, missing.to_str().unwrap())]);
+        let paths = config_paths(Some(&explicit), env);
+        let explicit_layer = paths
+            .iter()
+            .find(|p| p.kind == LayerKind::Override)
+            .unwrap();
+        let env_layer = paths
+            .iter()
+            .find(|p| p.kind == LayerKind::Environment)
+            .unwrap();
+
+        assert!(explicit_layer.found);
+        assert_eq!(env_layer.path, missing);
+        assert!(!env_layer.found);
+        assert_eq!(paths.last().unwrap().kind, LayerKind::Environment);
+    }
+
+    #[test]
+    fn test_user_config_path_file_name() {
+        if let Some(path) = user_config_path() {
+            assert_eq!(path.file_name().unwrap(), CONFIG_FILE_NAME);
+            assert!(path.to_string_lossy().contains("flowerpassword"));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Configuration schema migrations
+//!
+//! Every configuration file carries a top-level `version` key; files without
+//! one are version 0. Each migration is a pure function rewriting a parsed
+//! document from one version to the next, so upgrading chains them until the
+//! document reaches [`CURRENT_VERSION`].
+//!
+//! Documents are edited in place with `toml_edit`, which keeps comments,
+//! ordering and formatting of everything a migration does not touch.
+
+use super::{write_atomic, ConfigError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml_edit::{value, DocumentMut};
+
+/// Schema version written by this version of the library
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Rewrites a document from version `n` to `n + 1`, recording what changed
+type Step = fn(&mut DocumentMut, &mut Vec<String>);
+
+/// Migration steps, indexed by the version they upgrade from
+const STEPS: &[Step] = &[v0_to_v1];
+
+/// Summary of a migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    /// Version of the document before migrating
+    pub from: u32,
+    /// Version of the document after migrating
+    pub to: u32,
+    /// Human-readable description of each change, in order
+    pub changes: Vec<String>,
+}
+
+impl Migration {
+    /// Returns `true` if the document was already at the current version
+    pub fn is_noop(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Result of migrating a configuration file on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMigration {
+    /// What was changed
+    pub migration: Migration,
+    /// Copy of the original file, or `None` if nothing needed to change
+    pub backup: Option<PathBuf>,
+}
+
+/// Reads the schema version of a document
+///
+/// # Errors
+///
+/// Returns `ConfigError::UnsupportedVersion` if the version is newer than
+/// [`CURRENT_VERSION`], or `ConfigError::Parse` if it is not a non-negative integer.
+pub fn schema_version(document: &DocumentMut, origin: &str) -> Result<u32, ConfigError> {
+    let version = match document.get("version") {
+        None => return Ok(0),
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| ConfigError::Parse {
+                origin: origin.to_string(),
+                message: "version must be a non-negative integer".to_string(),
+            })?,
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(ConfigError::UnsupportedVersion {
+            origin: origin.to_string(),
+            version,
+        });
+    }
+    Ok(version)
+}
+
+/// Upgrades a document to [`CURRENT_VERSION`] in place
+///
+/// Migrating a document that is already current changes nothing.
+///
+/// # Errors
+///
+/// See [`schema_version`].
+pub fn migrate(document: &mut DocumentMut, origin: &str) -> Result<Migration, ConfigError> {
+    let from = schema_version(document, origin)?;
+    let mut changes = Vec::new();
+
+    for step in &STEPS[from as usize..] {
+        step(document, &mut changes);
+    }
+
+    Ok(Migration {
+        from,
+        to: CURRENT_VERSION,
+        changes,
+    })
+}
+
+/// Upgrades TOML source to [`CURRENT_VERSION`], returning the rewritten source
+///
+/// # Errors
+///
+/// Returns `ConfigError::Parse` if the source is not valid TOML, or any error
+/// from [`migrate`].
+pub fn migrate_str(source: &str, origin: &str) -> Result<(String, Migration), ConfigError> {
+    let mut document = source
+        .parse::<DocumentMut>()
+        .map_err(|e| ConfigError::Parse {
+            origin: origin.to_string(),
+            message: e.to_string(),
+        })?;
+    let migration = migrate(&mut document, origin)?;
+    Ok((document.to_string(), migration))
+}
+
+/// Upgrades a configuration file to [`CURRENT_VERSION`]
+///
+/// Before rewriting, the original is copied alongside as
+/// `<name>.<unix-timestamp>.bak`. Files that are already current are left
+/// untouched and no backup is made.
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` if the file cannot be read, backed up or written,
+/// or any error from [`migrate_str`].
+pub fn migrate_file(path: &Path) -> Result<FileMigration, ConfigError> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| ConfigError::Io { path, source }
+    };
+
+    let source = fs::read_to_string(path).map_err(io_error(path))?;
+    let (migrated, migration) = migrate_str(&source, &path.display().to_string())?;
+    if migration.is_noop() {
+        return Ok(FileMigration {
+            migration,
+            backup: None,
+        });
+    }
+
+    let backup = backup_path(path);
+    fs::copy(path, &backup).map_err(io_error(&backup))?;
+    write_atomic(path, migrated.as_bytes())?;
+
+    Ok(FileMigration {
+        migration,
+        backup: Some(backup),
+    })
+}
+
+/// Returns the path of a timestamped backup next to `path`
+fn backup_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.bak", timestamp));
+    path.with_file_name(name)
+}
+
+/// Version 1 introduces the `version` key itself
+fn v0_to_v1(document: &mut DocumentMut, changes: &mut Vec<String>) {
+    document.insert("version", value(1));
+    // Keep the version ahead of any other top-level values
+    document
+        .as_table_mut()
+        .sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+    changes.push("added `version = 1`".to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const V0: &str = r#"# Work accounts
+[sites.github]
+key = "github.com" # the public one
+length = 16
+notes = "username is the old email"
+"#;
+
+    const V1: &str = r#"version = 1
+# Work accounts
+[sites.github]
+key = "github.com" # the public one
+length = 16
+notes = "username is the old email"
+"#;
+
+    #[test]
+    fn test_v0_to_v1_preserves_comments() {
+        let (migrated, migration) = migrate_str(V0, "config.toml").unwrap();
+        assert_eq!(migrated, V1);
+        assert_eq!(
+            migration,
+            Migration {
+                from: 0,
+                to: 1,
+                changes: vec!["added `version = 1`".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_migration_is_idempotent() {
+        let (once, _) = migrate_str(V0, "config.toml").unwrap();
+        let (twice, migration) = migrate_str(&once, "config.toml").unwrap();
+        assert_eq!(once, twice);
+        assert!(migration.is_noop());
+        assert!(migration.changes.is_empty());
+    }
+
+    #[test]
+    fn test_refuses_newer_version() {
+        let result = migrate_str("version = 2\n", "config.toml");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "config.toml uses schema version 2, but only versions up to 1 are supported"
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_version() {
+        let result = migrate_str("version = \"one\"\n", "config.toml");
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_migrated_config_loads() {
+        let (migrated, _) = migrate_str(V0, "config.toml").unwrap();
+        let loaded = Config::from_toml_str(&migrated, "config.toml").unwrap();
+        assert!(loaded.warnings.is_empty());
+        assert_eq!(loaded.config.resolve("github").unwrap().key, "github.com");
+        assert_eq!(
+            loaded.config.sites["github"].notes.as_deref(),
+            Some("username is the old email")
+        );
+    }
+
+    #[test]
+    fn test_migrate_file_backs_up_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, V0).unwrap();
+
+        let result = migrate_file(&path).unwrap();
+        let backup = result.backup.unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), V0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), V1);
+        let backup_name = backup.file_name().unwrap().to_str().unwrap();
+        assert!(backup_name.starts_with("config.toml."));
+        assert!(backup_name.ends_with(".bak"));
+    }
+
+    #[test]
+    fn test_migrate_current_file_is_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, V1).unwrap();
+
+        let result = migrate_file(&path).unwrap();
+        assert!(result.migration.is_noop());
+        assert_eq!(result.backup, None);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
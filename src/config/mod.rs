@@ -0,0 +1,794 @@
+//! Per-site configuration
+//!
+//! A configuration file is a TOML document holding one `[sites.<name>]` table per
+//! site, so that frontends can map a short site name to the key and length used
+//! for derivation:
+//!
+//! ```toml
+//! [sites.github]
+//! key = "github.com"
+//! length = 16
+//! ```
+//!
+//! Loading is strict: wrong types and out-of-range lengths are errors naming the
+//! file, table and key. Unknown keys never abort loading; they are reported as
+//! warning [`Diagnostic`]s, with a suggestion when they look like a typo.
+//!
+//! With the `config-encryption` feature, files can be encrypted at rest with a
+//! passphrase; encrypted files start with [`ENCRYPTED_MAGIC`] so they are never
+//! mistaken for malformed TOML.
+//!
+//! An `[aliases]` table maps short names to site names, so that a site can be
+//! reached under more than one name:
+//!
+//! ```toml
+//! [aliases]
+//! bank = "examplebank"
+//! ```
+//!
+//! Configuration is usually assembled from several layers (built-in defaults,
+//! system file, user file, explicit override, environment); see [`load_layered`].
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::config::Config;
+//!
+//! let loaded = Config::from_toml_str("[sites.github]\nkey = \"github.com\"\nlength = 16\n", "config.toml").unwrap();
+//! let resolved = loaded.config.resolve("github").unwrap();
+//! assert_eq!(resolved.key, "github.com");
+//! assert_eq!(resolved.length, 16);
+//! ```
+
+mod audit;
+mod edit;
+#[cfg(feature = "config-encryption")]
+mod encryption;
+mod export;
+mod find;
+#[cfg(test)]
+mod fixtures;
+mod import;
+mod layers;
+mod migrate;
+mod validate;
+
+pub use audit::ReuseGroup;
+pub use edit::{
+    add_site, edit_file, remove_alias, remove_site, rename_site, set_alias, set_fido2_credential,
+    set_fingerprint, set_notes, write_atomic, SiteRename,
+};
+#[cfg(feature = "config-encryption")]
+pub use encryption::{decrypt, decrypt_file, encrypt, encrypt_file};
+pub use export::{write_browser_csv, write_keepass_csv, write_keepass_xml, ExportEntry};
+pub use find::{fuzzy_score, is_tie, MatchField, SiteMatch, TIE_THRESHOLD};
+pub use import::{parse_csv, registrable_domain, Import, SkipReason, SkippedRow};
+#[cfg(feature = "config-encryption")]
+pub use layers::load_layered_with_passphrase;
+pub use layers::{
+    config_paths, load_layered, load_layered_with, system_config_path, user_config_path,
+    ConfigPath, LayerKind, ENV_CONFIG, ENV_LENGTH,
+};
+pub use migrate::{
+    migrate, migrate_file, migrate_str, schema_version, FileMigration, Migration, CURRENT_VERSION,
+};
+pub use validate::{lint, lint_file, Diagnostic, Severity};
+
+use crate::{Scheme, DEFAULT_SCHEME};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml_edit::ImDocument;
+
+/// File name of the configuration file inside the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Header identifying an encrypted configuration file
+pub const ENCRYPTED_MAGIC: &[u8; 8] = b"FPCRYPT\x01";
+
+/// Password length used when neither the site nor any layer sets one
+pub const DEFAULT_LENGTH: usize = 16;
+
+/// Error type for configuration operations
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read or written
+    Io {
+        /// Path of the file being accessed
+        path: PathBuf,
+        /// Underlying IO error
+        source: io::Error,
+    },
+    /// The configuration is not valid TOML
+    Parse {
+        /// File name or other description of where the document came from
+        origin: String,
+        /// Parser message, including the location of the problem
+        message: String,
+    },
+    /// The configuration does not match the schema
+    Invalid {
+        /// File name or other description of where the document came from
+        origin: String,
+        /// Every error found, in document order
+        errors: Vec<Diagnostic>,
+    },
+    /// The configuration uses a schema version newer than this library supports
+    UnsupportedVersion {
+        /// File name or other description of where the document came from
+        origin: String,
+        /// Schema version found in the document
+        version: u32,
+    },
+    /// The configuration file is encrypted and no passphrase was supplied
+    Encrypted {
+        /// File name or other description of where the document came from
+        origin: String,
+    },
+    /// The configuration file could not be decrypted
+    Decrypt {
+        /// File name or other description of where the document came from
+        origin: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// An environment variable has a value that cannot be used
+    Environment {
+        /// Name of the offending variable
+        variable: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// Following an alias leads back to an alias already visited
+    AliasCycle {
+        /// Every alias visited, ending with the one that repeats
+        chain: Vec<String>,
+    },
+    /// A site with this name is already configured
+    SiteExists {
+        /// Name of the existing site
+        name: String,
+    },
+    /// No site with this name is configured
+    UnknownSite {
+        /// Name that was looked up
+        name: String,
+    },
+    /// An alias points to a name that is neither a site nor another alias
+    UnknownAliasTarget {
+        /// The alias being followed
+        alias: String,
+        /// The name it points to
+        target: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "Failed to access {}: {}", path.display(), source)
+            }
+            ConfigError::Parse { origin, message } => {
+                write!(f, "Failed to parse {}: {}", origin, message)
+            }
+            ConfigError::Invalid { origin, errors } => {
+                write!(f, "Invalid configuration in {}", origin)?;
+                for error in errors {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
+            ConfigError::UnsupportedVersion { origin, version } => write!(
+                f,
+                "{} uses schema version {}, but only versions up to {} are supported",
+                origin, version, CURRENT_VERSION
+            ),
+            ConfigError::Encrypted { origin } => {
+                write!(
+                    f,
+                    "{} is encrypted; a passphrase is required to read it",
+                    origin
+                )
+            }
+            ConfigError::Decrypt { origin, message } => {
+                write!(f, "Failed to decrypt {}: {}", origin, message)
+            }
+            ConfigError::Environment { variable, message } => {
+                write!(f, "Invalid value for {}: {}", variable, message)
+            }
+            ConfigError::AliasCycle { chain } => {
+                write!(f, "Alias cycle: {}", chain.join(" -> "))
+            }
+            ConfigError::SiteExists { name } => write!(f, "Site `{}` already exists", name),
+            ConfigError::UnknownSite { name } => write!(f, "Site `{}` is not configured", name),
+            ConfigError::UnknownAliasTarget { alias, target } => {
+                write!(f, "Alias `{}` points to unknown site `{}`", alias, target)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { .. }
+            | ConfigError::Invalid { .. }
+            | ConfigError::UnsupportedVersion { .. }
+            | ConfigError::Encrypted { .. }
+            | ConfigError::Decrypt { .. }
+            | ConfigError::Environment { .. }
+            | ConfigError::AliasCycle { .. }
+            | ConfigError::SiteExists { .. }
+            | ConfigError::UnknownSite { .. }
+            | ConfigError::UnknownAliasTarget { .. } => None,
+        }
+    }
+}
+
+/// Settings applied to every site that does not override them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Defaults {
+    /// Default password length
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+}
+
+impl Defaults {
+    /// Returns `true` if no default is set
+    pub fn is_empty(&self) -> bool {
+        self.length.is_none()
+    }
+}
+
+/// Settings for a single configured site
+///
+/// Every field is optional so that a layer can override a single setting of a
+/// site defined in a lower layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteConfig {
+    /// Key passed to [`fp_code`](crate::fp_code) for this site, defaults to the site name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Preferred password length, if different from the configured default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+    /// Derivation scheme, `v1` or `v2`, if different from [`DEFAULT_SCHEME`]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::scheme_name"
+    )]
+    pub scheme: Option<Scheme>,
+    /// Username or email used on the site, stored only and never derived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Free-form notes about the site, stored verbatim and never interpreted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl SiteConfig {
+    /// Overrides every setting that `other` sets
+    pub fn merge(&mut self, other: SiteConfig) {
+        if other.key.is_some() {
+            self.key = other.key;
+        }
+        if other.length.is_some() {
+            self.length = other.length;
+        }
+        if other.scheme.is_some() {
+            self.scheme = other.scheme;
+        }
+        if other.username.is_some() {
+            self.username = other.username;
+        }
+        if other.notes.is_some() {
+            self.notes = other.notes;
+        }
+    }
+}
+
+/// Parsed configuration file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// Settings applied to every site
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+    /// Configured sites, indexed by site name
+    #[serde(default)]
+    pub sites: BTreeMap<String, SiteConfig>,
+    /// Alternative names for sites, mapping each alias to a site or another alias
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// [`fp_fingerprint`](crate::fp_fingerprint) of the master password, to
+    /// check it against before deriving
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Hex ID of the FIDO2 credential `--master fido2` derives the master
+    /// password from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fido2_credential: Option<String>,
+}
+
+/// Configuration together with the warnings produced while loading it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedConfig {
+    /// The parsed configuration
+    pub config: Config,
+    /// Unknown keys and other non-fatal problems
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Result of looking up a command-line argument in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolved<'a> {
+    /// Name of the matching site, or `None` if the argument is a literal key
+    pub site: Option<&'a str>,
+    /// Alias the argument matched, or `None` if it named a site or key directly
+    pub alias: Option<&'a str>,
+    /// Key to derive the password for
+    pub key: &'a str,
+    /// Length to derive the password with
+    pub length: usize,
+    /// Scheme to derive the password with
+    pub scheme: Scheme,
+    /// Username configured for the site, if any
+    pub username: Option<&'a str>,
+}
+
+impl Config {
+    /// Parses a configuration from TOML source
+    ///
+    /// `origin` names the document in warnings and errors, usually the file path.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Parse` if the source is not valid TOML, or
+    /// `ConfigError::Invalid` if a known key has the wrong type or an invalid value.
+    pub fn from_toml_str(source: &str, origin: &str) -> Result<LoadedConfig, ConfigError> {
+        let parse_error = |message: String| ConfigError::Parse {
+            origin: origin.to_string(),
+            message,
+        };
+
+        let document = ImDocument::parse(source).map_err(|e| parse_error(e.to_string()))?;
+        let (errors, warnings): (Vec<_>, Vec<_>) = validate::check_document(&document, origin)
+            .into_iter()
+            .partition(Diagnostic::is_error);
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid {
+                origin: origin.to_string(),
+                errors,
+            });
+        }
+
+        let config = toml_edit::de::from_str(source).map_err(|e| parse_error(e.to_string()))?;
+        Ok(LoadedConfig { config, warnings })
+    }
+
+    /// Loads a configuration file from disk
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Io` if the file cannot be read, `ConfigError::Encrypted`
+    /// if it is encrypted, or any error from [`Config::from_toml_str`].
+    pub fn load(path: &Path) -> Result<LoadedConfig, ConfigError> {
+        let origin = path.display().to_string();
+        let data = fs::read(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if is_encrypted(&data) {
+            return Err(ConfigError::Encrypted { origin });
+        }
+        Self::from_toml_bytes(&data, &origin)
+    }
+
+    /// Parses a configuration from raw file contents
+    fn from_toml_bytes(data: &[u8], origin: &str) -> Result<LoadedConfig, ConfigError> {
+        let source = std::str::from_utf8(data).map_err(|e| ConfigError::Parse {
+            origin: origin.to_string(),
+            message: e.to_string(),
+        })?;
+        Self::from_toml_str(source, origin)
+    }
+
+    /// Overrides every setting that `other` sets, key by key
+    ///
+    /// Sites present in both configurations are merged field by field, so a
+    /// higher layer can change the length of a site without repeating its key.
+    pub fn merge(&mut self, other: Config) {
+        if other.defaults.length.is_some() {
+            self.defaults.length = other.defaults.length;
+        }
+        for (name, site) in other.sites {
+            self.sites.entry(name).or_default().merge(site);
+        }
+        self.aliases.extend(other.aliases);
+        if other.fingerprint.is_some() {
+            self.fingerprint = other.fingerprint;
+        }
+        if other.fido2_credential.is_some() {
+            self.fido2_credential = other.fido2_credential;
+        }
+    }
+
+    /// Serializes the configuration back to TOML at [`CURRENT_VERSION`]
+    pub fn to_toml_string(&self) -> String {
+        // Serializing maps of strings and integers cannot fail
+        let body = toml_edit::ser::to_string_pretty(self).expect("config is always serializable");
+        format!("version = {}\n\n{}", CURRENT_VERSION, body)
+    }
+
+    /// Resolves a command-line argument to the key, length and scheme to derive with
+    ///
+    /// `name` is looked up in this order:
+    ///
+    /// 1. a configured site with that name, whose settings are used;
+    /// 2. an alias with that name, followed (through other aliases if needed)
+    ///    to the site it points to;
+    /// 3. otherwise `name` itself is treated as a literal key with default settings.
+    ///
+    /// Because sites come first, an alias sharing its name with a site is never
+    /// used. Lengths fall back to `[defaults]` and then to [`DEFAULT_LENGTH`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::AliasCycle` if following aliases loops, or
+    /// `ConfigError::UnknownAliasTarget` if an alias leads to a name that is
+    /// neither a site nor an alias.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> Result<Resolved<'a>, ConfigError> {
+        let default_length = self.defaults.length.unwrap_or(DEFAULT_LENGTH);
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = name;
+
+        loop {
+            if let Some((site, settings)) = self.sites.get_key_value(current) {
+                return Ok(Resolved {
+                    site: Some(site),
+                    alias: chain.first().copied(),
+                    key: settings.key.as_deref().unwrap_or(site),
+                    length: settings.length.unwrap_or(default_length),
+                    scheme: settings.scheme.unwrap_or(DEFAULT_SCHEME),
+                    username: settings.username.as_deref(),
+                });
+            }
+
+            match self.aliases.get_key_value(current) {
+                Some((alias, target)) => {
+                    let repeated = chain.contains(&alias.as_str());
+                    chain.push(alias);
+                    if repeated {
+                        return Err(ConfigError::AliasCycle {
+                            chain: chain.iter().map(|alias| alias.to_string()).collect(),
+                        });
+                    }
+                    current = target;
+                }
+                None => match chain.last() {
+                    None => {
+                        return Ok(Resolved {
+                            site: None,
+                            alias: None,
+                            key: name,
+                            length: default_length,
+                            scheme: DEFAULT_SCHEME,
+                            username: None,
+                        })
+                    }
+                    Some(alias) => {
+                        return Err(ConfigError::UnknownAliasTarget {
+                            alias: alias.to_string(),
+                            target: current.to_string(),
+                        })
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Returns `true` if `data` starts with the encrypted configuration header
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::config;
+
+    const SAMPLE: &str = r#"[sites.github]
+key = "github.com"
+length = 16
+
+[sites.bank]
+key = "secure.examplebank.co.uk"
+"#;
+
+    #[test]
+    fn test_parse_sites() {
+        let loaded = Config::from_toml_str(SAMPLE, "config.toml").unwrap();
+        assert!(loaded.warnings.is_empty());
+        assert_eq!(loaded.config.sites.len(), 2);
+        assert_eq!(
+            loaded.config.sites["github"],
+            SiteConfig {
+                key: Some("github.com".to_string()),
+                length: Some(16),
+                scheme: None,
+                username: None,
+                notes: None,
+            }
+        );
+        assert_eq!(loaded.config.sites["bank"].length, None);
+    }
+
+    #[test]
+    fn test_parse_empty_document() {
+        let loaded = Config::from_toml_str("", "config.toml").unwrap();
+        assert_eq!(loaded.config, Config::default());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config::from_toml_str(SAMPLE, "config.toml").unwrap().config;
+        let serialized = config.to_toml_string();
+        let reparsed = Config::from_toml_str(&serialized, "config.toml").unwrap();
+        assert_eq!(reparsed.config, config);
+        assert!(reparsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_keys_warn_with_line() {
+        let source = "theme = \"dark\"\n\n[sites.github]\nkey = \"github.com\"\nlenght = 16\n";
+        let loaded = Config::from_toml_str(source, "config.toml").unwrap();
+        let messages: Vec<String> = loaded.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "config.toml:1: warning: unknown key `theme` at the top level",
+                "config.toml:5: warning: unknown key `lenght` in [sites.github], did you mean `length`?",
+            ]
+        );
+        assert_eq!(loaded.config.sites["github"].length, None);
+    }
+
+    #[test]
+    fn test_invalid_toml_is_error() {
+        let result = Config::from_toml_str("[sites.github\n", "config.toml");
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_wrong_type_is_error() {
+        let result = Config::from_toml_str("[sites.github]\nkey = 42\n", "config.toml");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid configuration in config.toml\n\
+             config.toml:2: error: [sites.github] key: expected a string, found an integer"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_length_is_error() {
+        let result = Config::from_toml_str("[sites.github]\nlength = 64\n", "config.toml");
+        match result {
+            Err(ConfigError::Invalid { errors, .. }) => assert_eq!(errors.len(), 1),
+            other => panic!("expected invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_configured_site() {
+        let config = Config::from_toml_str(SAMPLE, "config.toml").unwrap().config;
+        assert_eq!(
+            config.resolve("github").unwrap(),
+            Resolved {
+                site: Some("github"),
+                alias: None,
+                key: "github.com",
+                length: 16,
+                scheme: DEFAULT_SCHEME,
+                username: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_literal_key() {
+        let config = Config::from_toml_str(SAMPLE, "config.toml").unwrap().config;
+        assert_eq!(
+            config.resolve("example.com").unwrap(),
+            Resolved {
+                site: None,
+                alias: None,
+                key: "example.com",
+                length: DEFAULT_LENGTH,
+                scheme: DEFAULT_SCHEME,
+                username: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_uses_defaults_and_site_name() {
+        let source = "[defaults]\nlength = 20\n\n[sites.\"example.org\"]\n";
+        let config = Config::from_toml_str(source, "config.toml").unwrap().config;
+        assert_eq!(
+            config.resolve("example.org").unwrap(),
+            Resolved {
+                site: Some("example.org"),
+                alias: None,
+                key: "example.org",
+                length: 20,
+                scheme: DEFAULT_SCHEME,
+                username: None,
+            }
+        );
+        assert_eq!(config.resolve("other").unwrap().length, 20);
+    }
+
+    #[test]
+    fn test_resolve_site_scheme() {
+        let source = "[sites.github]\nscheme = \"v1\"\n\n[aliases]\ngh = \"github\"\n";
+        let config = Config::from_toml_str(source, "config.toml").unwrap().config;
+        assert_eq!(config.sites["github"].scheme, Some(Scheme::V1));
+        assert_eq!(config.resolve("gh").unwrap().scheme, Scheme::V1);
+        assert_eq!(
+            config.resolve("example.com").unwrap().scheme,
+            DEFAULT_SCHEME
+        );
+        assert!(config.to_toml_string().contains("scheme = \"v1\""));
+    }
+
+    const ALIASES: &str = r#"[sites.examplebank]
+key = "secure.examplebank.co.uk"
+length = 20
+
+[sites.github]
+key = "github.com"
+
+[aliases]
+bank = "examplebank"
+money = "bank"
+github = "examplebank"
+loop-a = "loop-b"
+loop-b = "loop-c"
+loop-c = "loop-a"
+dangling = "nowhere"
+"#;
+
+    #[test]
+    fn test_resolve_alias() {
+        assert_eq!(
+            config(ALIASES).resolve("bank").unwrap(),
+            Resolved {
+                site: Some("examplebank"),
+                alias: Some("bank"),
+                key: "secure.examplebank.co.uk",
+                length: 20,
+                scheme: DEFAULT_SCHEME,
+                username: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_alias_chain() {
+        let config = config(ALIASES);
+        let resolved = config.resolve("money").unwrap();
+        assert_eq!(resolved.site, Some("examplebank"));
+        assert_eq!(resolved.alias, Some("money"));
+    }
+
+    #[test]
+    fn test_resolve_site_shadows_alias() {
+        let config = config(ALIASES);
+        let resolved = config.resolve("github").unwrap();
+        assert_eq!(resolved.site, Some("github"));
+        assert_eq!(resolved.alias, None);
+        assert_eq!(resolved.key, "github.com");
+    }
+
+    #[test]
+    fn test_resolve_alias_cycle_is_error() {
+        let config = config(ALIASES);
+        let error = config.resolve("loop-b").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Alias cycle: loop-b -> loop-c -> loop-a -> loop-b"
+        );
+    }
+
+    #[test]
+    fn test_resolve_dangling_alias_is_error() {
+        let config = config(ALIASES);
+        let error = config.resolve("dangling").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Alias `dangling` points to unknown site `nowhere`"
+        );
+    }
+
+    #[test]
+    fn test_resolve_unknown_name_is_literal_key() {
+        let config = config(ALIASES);
+        let resolved = config.resolve("nowhere").unwrap();
+        assert_eq!(resolved.site, None);
+        assert_eq!(resolved.key, "nowhere");
+    }
+
+    #[test]
+    fn test_resolve_username() {
+        let source = "[sites.github]\nkey = \"github.com\"\nusername = \"octocat@example.com\"\n";
+        let config = Config::from_toml_str(source, "config.toml").unwrap().config;
+        assert_eq!(
+            config.resolve("github").unwrap().username,
+            Some("octocat@example.com")
+        );
+
+        let config = Config::from_toml_str(SAMPLE, "config.toml").unwrap().config;
+        assert_eq!(config.resolve("github").unwrap().username, None);
+        assert_eq!(config.resolve("example.com").unwrap().username, None);
+    }
+
+    #[test]
+    fn test_merge_overrides_per_key() {
+        let mut base = Config::from_toml_str(SAMPLE, "base.toml").unwrap().config;
+        let overlay = Config::from_toml_str(
+            "[defaults]\nlength = 24\n\n[sites.github]\nlength = 32\n",
+            "overlay.toml",
+        )
+        .unwrap()
+        .config;
+        base.merge(overlay);
+
+        assert_eq!(base.defaults.length, Some(24));
+        assert_eq!(base.sites["github"].key.as_deref(), Some("github.com"));
+        assert_eq!(base.sites["github"].length, Some(32));
+        assert_eq!(base.resolve("bank").unwrap().length, 24);
+
+        base.merge(parse_fingerprint("fingerprint = \"K8a2D6\"\n"));
+        base.merge(Config::default());
+        assert_eq!(base.fingerprint.as_deref(), Some("K8a2D6"));
+    }
+
+    fn parse_fingerprint(source: &str) -> Config {
+        Config::from_toml_str(source, "fingerprint.toml")
+            .unwrap()
+            .config
+    }
+
+    #[test]
+    fn test_fingerprint_round_trip() {
+        let config = parse_fingerprint("fingerprint = \"K8a2D6\"\n\n[sites.github]\n");
+        assert_eq!(config.fingerprint.as_deref(), Some("K8a2D6"));
+        let reparsed = Config::from_toml_str(&config.to_toml_string(), "config.toml").unwrap();
+        assert_eq!(reparsed.config, config);
+        assert!(reparsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_load_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, SAMPLE).unwrap();
+
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.config.sites.len(), 2);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = Config::load(&dir.path().join(CONFIG_FILE_NAME));
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+    }
+}
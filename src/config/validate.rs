@@ -0,0 +1,581 @@
+//! Configuration validation
+//!
+//! Checks a TOML document against the configuration schema and reports every
+//! problem found as a [`Diagnostic`]. Unknown keys are warnings (with a
+//! suggestion when a valid key is close enough to be a typo); wrong types,
+//! out-of-range lengths, unknown schemes and site or alias names with line breaks are errors. Aliases hidden by a site of the same name
+//! are warnings too, since they can never be used.
+
+use super::{ConfigError, CURRENT_VERSION};
+use crate::{scheme_name, Scheme};
+use flowerpassword_core::{MAX_LENGTH, MIN_LENGTH};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+/// Maximum edit distance for a valid key to be suggested for an unknown one
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Expected shape of a configuration value
+enum Field {
+    /// A string
+    String,
+    /// An integer within the valid password length range
+    Length,
+    /// A schema version this library understands
+    Version,
+    /// The name of a derivation scheme compiled into this build
+    Scheme,
+    /// A table with a fixed set of keys
+    Table(&'static [(&'static str, Field)]),
+    /// A table of arbitrarily named tables sharing one set of keys
+    Map(&'static [(&'static str, Field)]),
+    /// A table of arbitrarily named strings
+    Strings,
+}
+
+/// Keys accepted inside the `[defaults]` table
+const DEFAULT_FIELDS: &[(&str, Field)] = &[("length", Field::Length)];
+
+/// Keys accepted inside a `[sites.<name>]` table
+const SITE_FIELDS: &[(&str, Field)] = &[
+    ("key", Field::String),
+    ("length", Field::Length),
+    ("scheme", Field::Scheme),
+    ("username", Field::String),
+    ("notes", Field::String),
+];
+
+/// Keys accepted at the top level of a configuration file
+const ROOT_FIELDS: &[(&str, Field)] = &[
+    ("version", Field::Version),
+    ("defaults", Field::Table(DEFAULT_FIELDS)),
+    ("sites", Field::Map(SITE_FIELDS)),
+    ("aliases", Field::Strings),
+    ("fingerprint", Field::String),
+    ("fido2_credential", Field::String),
+];
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The configuration loads, but probably not as intended
+    Warning,
+    /// The configuration cannot be loaded
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// Problem found while validating a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// File name or other description of where the document came from
+    pub origin: String,
+    /// 1-based line number the diagnostic refers to
+    pub line: usize,
+    /// Human-readable description naming the table and key involved
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Returns `true` if this diagnostic prevents the configuration from loading
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.origin, self.line, self.severity, self.message
+        )
+    }
+}
+
+/// Runs every check on a configuration document
+///
+/// Unlike [`Config::from_toml_str`](super::Config::from_toml_str), this never
+/// fails: syntax errors are reported as diagnostics too, so a linter can show
+/// everything at once.
+pub fn lint(source: &str, origin: &str) -> Vec<Diagnostic> {
+    match ImDocument::parse(source) {
+        Ok(document) => check_document(&document, origin),
+        Err(e) => vec![Diagnostic {
+            severity: Severity::Error,
+            origin: origin.to_string(),
+            line: e.span().map_or(1, |span| line_of(source, span.start)),
+            message: e.message().to_string(),
+        }],
+    }
+}
+
+/// Runs every check on a configuration file
+///
+/// # Errors
+///
+/// Returns `ConfigError::Io` if the file cannot be read.
+pub fn lint_file(path: &Path) -> Result<Vec<Diagnostic>, ConfigError> {
+    let source = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(lint(&source, &path.display().to_string()))
+}
+
+/// Checks a parsed document against the configuration schema
+pub(super) fn check_document(document: &ImDocument<&str>, origin: &str) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        source: document.raw(),
+        origin,
+        diagnostics: Vec::new(),
+    };
+    checker.check_table(document.as_table(), &[], ROOT_FIELDS);
+    checker.check_shadowed_aliases(document.as_table());
+    checker.diagnostics
+}
+
+/// Converts a byte offset into a 1-based line number
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+/// Accumulates diagnostics while walking a document
+struct Checker<'a> {
+    source: &'a str,
+    origin: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, severity: Severity, table: &dyn TableLike, key: &str, message: String) {
+        let line = table
+            .get_key_value(key)
+            .and_then(|(key, _)| key.span())
+            .map_or(1, |span| line_of(self.source, span.start));
+        self.diagnostics.push(Diagnostic {
+            severity,
+            origin: self.origin.to_string(),
+            line,
+            message,
+        });
+    }
+
+    fn check_table(
+        &mut self,
+        table: &dyn TableLike,
+        path: &[&str],
+        fields: &'static [(&'static str, Field)],
+    ) {
+        for (key, item) in table.iter() {
+            match fields.iter().find(|(name, _)| *name == key) {
+                Some((_, field)) => self.check_field(table, path, key, item, field),
+                None => {
+                    let location = match path {
+                        [] => "at the top level".to_string(),
+                        _ => format!("in [{}]", path.join(".")),
+                    };
+                    let message = match suggest(key, fields) {
+                        Some(suggestion) => format!(
+                            "unknown key `{}` {}, did you mean `{}`?",
+                            key, location, suggestion
+                        ),
+                        None => format!("unknown key `{}` {}", key, location),
+                    };
+                    self.report(Severity::Warning, table, key, message);
+                }
+            }
+        }
+    }
+
+    fn check_field(
+        &mut self,
+        table: &dyn TableLike,
+        path: &[&str],
+        key: &str,
+        item: &Item,
+        field: &Field,
+    ) {
+        let name = match path {
+            [] => key.to_string(),
+            _ => format!("[{}] {}", path.join("."), key),
+        };
+        let mut nested = path.to_vec();
+        nested.push(key);
+
+        match field {
+            Field::String if item.as_str().is_some() => {}
+            Field::String => self.type_error(table, key, &name, "a string", item),
+            Field::Length => match item.as_integer() {
+                Some(length) if (MIN_LENGTH as i64..=MAX_LENGTH as i64).contains(&length) => {}
+                Some(length) => self.report(
+                    Severity::Error,
+                    table,
+                    key,
+                    format!(
+                        "{}: length must be between {} and {}, got {}",
+                        name, MIN_LENGTH, MAX_LENGTH, length
+                    ),
+                ),
+                None => self.type_error(table, key, &name, "an integer", item),
+            },
+            Field::Version => match item.as_integer() {
+                Some(version) if (0..=i64::from(CURRENT_VERSION)).contains(&version) => {}
+                Some(version) => self.report(
+                    Severity::Error,
+                    table,
+                    key,
+                    format!(
+                        "{}: schema version {} is not supported, expected at most {}",
+                        name, version, CURRENT_VERSION
+                    ),
+                ),
+                None => self.type_error(table, key, &name, "an integer", item),
+            },
+            Field::Scheme => match item.as_str() {
+                Some(scheme) if Scheme::from_name(scheme).is_some() => {}
+                Some(scheme) => self.report(
+                    Severity::Error,
+                    table,
+                    key,
+                    format!("{}: {}", name, scheme_name::unavailable(scheme)),
+                ),
+                None => self.type_error(table, key, &name, "a string", item),
+            },
+            Field::Table(fields) => match item.as_table_like() {
+                Some(inner) => self.check_table(inner, &nested, fields),
+                None => self.type_error(table, key, &name, "a table", item),
+            },
+            Field::Map(fields) => match item.as_table_like() {
+                Some(inner) => {
+                    for (entry, entry_item) in inner.iter() {
+                        self.check_name(inner, &nested, entry);
+                        let mut entry_path = nested.clone();
+                        entry_path.push(entry);
+                        match entry_item.as_table_like() {
+                            Some(entry_table) => self.check_table(entry_table, &entry_path, fields),
+                            None => self.type_error(
+                                inner,
+                                entry,
+                                &format!("[{}] {}", nested.join("."), entry),
+                                "a table",
+                                entry_item,
+                            ),
+                        }
+                    }
+                }
+                None => self.type_error(table, key, &name, "a table", item),
+            },
+            Field::Strings => match item.as_table_like() {
+                Some(inner) => {
+                    for (entry, entry_item) in inner.iter() {
+                        self.check_name(inner, &nested, entry);
+                        if entry_item.as_str().is_none() {
+                            self.type_error(
+                                inner,
+                                entry,
+                                &format!("[{}] {}", nested.join("."), entry),
+                                "a string",
+                                entry_item,
+                            );
+                        }
+                    }
+                }
+                None => self.type_error(table, key, &name, "a table", item),
+            },
+        }
+    }
+
+    /// Rejects site and alias names with line breaks, which would break tools that
+    /// list one name per line
+    fn check_name(&mut self, table: &dyn TableLike, path: &[&str], name: &str) {
+        if name.contains(['\n', '\r']) {
+            let message = format!(
+                "[{}] {:?}: name must not contain a line break",
+                path.join("."),
+                name
+            );
+            self.report(Severity::Error, table, name, message);
+        }
+    }
+
+    /// Warns about aliases that can never be used because a site has the same name
+    fn check_shadowed_aliases(&mut self, root: &dyn TableLike) {
+        let (Some(aliases), Some(sites)) = (
+            root.get("aliases").and_then(Item::as_table_like),
+            root.get("sites").and_then(Item::as_table_like),
+        ) else {
+            return;
+        };
+
+        for (alias, _) in aliases.iter() {
+            if sites.contains_key(alias) {
+                let message = format!(
+                    "[aliases] {}: alias is shadowed by [sites.{}] and will never be used",
+                    alias, alias
+                );
+                self.report(Severity::Warning, aliases, alias, message);
+            }
+        }
+    }
+
+    fn type_error(
+        &mut self,
+        table: &dyn TableLike,
+        key: &str,
+        name: &str,
+        expected: &str,
+        item: &Item,
+    ) {
+        let message = format!("{}: expected {}, found {}", name, expected, describe(item));
+        self.report(Severity::Error, table, key, message);
+    }
+}
+
+/// Describes the type of a TOML item for error messages
+fn describe(item: &Item) -> &'static str {
+    match item {
+        Item::None => "nothing",
+        Item::Table(_) => "a table",
+        Item::ArrayOfTables(_) => "an array of tables",
+        Item::Value(value) => match value {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+            Value::Datetime(_) => "a datetime",
+            Value::Array(_) => "an array",
+            Value::InlineTable(_) => "a table",
+        },
+    }
+}
+
+/// Returns the valid key closest to `key`, if it is close enough to be a typo
+fn suggest(key: &str, fields: &[(&'static str, Field)]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|(name, _)| (edit_distance(key, name), *name))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Computes the Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn messages(source: &str) -> Vec<String> {
+        lint(source, "config.toml")
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    const TYPO: &str = "[sites.github]\nkey = \"github.com\"\nlenght = 16\n";
+
+    const UNKNOWN: &str = "theme = \"dark\"\n\n[defaults]\ncolour = true\n";
+
+    const WRONG_TYPES: &str = r#"[defaults]
+length = "16"
+
+[sites.github]
+key = 42
+
+[sites.bank]
+key = "bank.example"
+length = 1.5
+"#;
+
+    const OUT_OF_RANGE: &str = "[defaults]\nlength = 1\n\n[sites.github]\nlength = 40\n";
+
+    const BAD_TABLES: &str = "defaults = 16\n\n[sites]\ngithub = \"github.com\"\n";
+
+    const NEWER_VERSION: &str = "version = 2\n\n[sites.github]\nkey = \"github.com\"\n";
+
+    const ALIASES: &str = r#"[sites.bank]
+key = "secure.examplebank.co.uk"
+
+[aliases]
+money = "bank"
+bank = "money"
+broken = 7
+"#;
+
+    const SYNTAX: &str = "[sites.github]\nkey = \"github.com\n";
+
+    #[test]
+    fn test_valid_config_has_no_diagnostics() {
+        let source =
+            "[defaults]\nlength = 16\n\n[sites.github]\nkey = \"github.com\"\nlength = 32\n";
+        assert!(lint(source, "config.toml").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_key_suggests_nearest() {
+        assert_eq!(
+            messages(TYPO),
+            vec!["config.toml:3: warning: unknown key `lenght` in [sites.github], did you mean `length`?"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_without_suggestion() {
+        assert_eq!(
+            messages(UNKNOWN),
+            vec![
+                "config.toml:1: warning: unknown key `theme` at the top level",
+                "config.toml:4: warning: unknown key `colour` in [defaults]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        assert_eq!(
+            messages(WRONG_TYPES),
+            vec![
+                "config.toml:2: error: [defaults] length: expected an integer, found a string",
+                "config.toml:5: error: [sites.github] key: expected a string, found an integer",
+                "config.toml:9: error: [sites.bank] length: expected an integer, found a float",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_names_with_line_breaks() {
+        let source =
+            "[sites.\"git\\nhub\"]\nkey = \"github.com\"\n\n[aliases]\n\"gh\\r\" = \"github\"\n";
+        assert_eq!(
+            messages(source),
+            vec![
+                "config.toml:1: error: [sites] \"git\\nhub\": name must not contain a line break",
+                "config.toml:5: error: [aliases] \"gh\\r\": name must not contain a line break",
+            ]
+        );
+        assert!(matches!(
+            Config::from_toml_str(source, "config.toml"),
+            Err(ConfigError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_lengths() {
+        assert_eq!(
+            messages(OUT_OF_RANGE),
+            vec![
+                "config.toml:2: error: [defaults] length: length must be between 2 and 32, got 1",
+                "config.toml:5: error: [sites.github] length: length must be between 2 and 32, got 40",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schemes() {
+        let source = "[sites.github]\nscheme = \"v1\"\n\n[sites.bank]\nscheme = \"v3\"\n\n[sites.mail]\nscheme = 2\n";
+        assert_eq!(
+            messages(source),
+            vec![
+                "config.toml:5: error: [sites.bank] scheme: unknown scheme `v3`, expected v1 or v2",
+                "config.toml:8: error: [sites.mail] scheme: expected a string, found an integer",
+            ]
+        );
+        let v2 = "[sites.github]\nscheme = \"v2\"\n";
+        match cfg!(feature = "scheme-v2-sha256") {
+            true => assert!(messages(v2).is_empty()),
+            false => assert_eq!(
+                messages(v2),
+                vec!["config.toml:2: error: [sites.github] scheme: scheme v2 is not compiled into this build"]
+            ),
+        }
+    }
+
+    #[test]
+    fn test_tables_with_wrong_type() {
+        assert_eq!(
+            messages(BAD_TABLES),
+            vec![
+                "config.toml:1: error: defaults: expected a table, found an integer",
+                "config.toml:4: error: [sites] github: expected a table, found a string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_newer_version() {
+        assert_eq!(
+            messages(NEWER_VERSION),
+            vec!["config.toml:1: error: version: schema version 2 is not supported, expected at most 1"]
+        );
+    }
+
+    #[test]
+    fn test_alias_checks() {
+        assert_eq!(
+            messages(ALIASES),
+            vec![
+                "config.toml:7: error: [aliases] broken: expected a string, found an integer",
+                "config.toml:6: warning: [aliases] bank: alias is shadowed by [sites.bank] and will never be used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let diagnostics = lint(SYNTAX, "config.toml");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+        assert_eq!(diagnostics[0].line, 2);
+    }
+
+    #[test]
+    fn test_lint_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, TYPO).unwrap();
+
+        let diagnostics = lint_file(&path).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].origin, path.display().to_string());
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("length", "length"), 0);
+        assert_eq!(edit_distance("lenght", "length"), 2);
+        assert_eq!(edit_distance("ky", "key"), 1);
+        assert_eq!(edit_distance("", "key"), 3);
+        assert_eq!(edit_distance("theme", "key"), 4);
+    }
+}
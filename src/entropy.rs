@@ -0,0 +1,317 @@
+//! Entropy of derived passwords
+//!
+//! [`estimate_entropy`] answers how many bits a password derived with some
+//! [`FpOptions`] is worth against an attacker who knows the algorithm but not the
+//! master password. For the classic scheme the answer is much lower than a random
+//! alphanumeric password of the same length would suggest:
+//!
+//! ```
+//! use flowerpassword::entropy::{estimate_entropy, FpOptions};
+//!
+//! let estimate = estimate_entropy(&FpOptions::classic(16).unwrap());
+//! assert_eq!(estimate.alphabet_size, 23);
+//! assert_eq!(estimate.bits.round(), 68.0); // a random 62-character password: 95.3
+//!
+//! let pin = estimate_entropy(&FpOptions::pin(6).unwrap());
+//! assert_eq!(pin.bits.round(), 20.0);
+//! ```
+//!
+//! Besides [`Mode::Classic`], the estimate covers the shapes frontends turn
+//! derived passwords into: printable characters with symbols, PIN digits and
+//! passphrase words. Those are taken to be drawn uniformly, since a frontend
+//! maps the hash onto them. `FpGenerator::estimate_entropy` estimates for a
+//! generator's default length in the classic scheme.
+
+use alloc::vec::Vec;
+use flowerpassword_core::{validate_length, FlowerPasswordError};
+
+// The classic model: the base, rule and source hashes are HMAC-MD5 outputs,
+// taken to be uniform, and the rule and source hashes are independent because
+// they are keyed with different salts. Character `i` of the password comes from
+// hex character `i` of each, so characters are independent of one another.
+//
+// The source character is one of 16 hex characters, each with probability 1/16.
+// - A digit (10 of 16) is output as it is.
+// - A letter (6 of 16) is uppercased when the rule character is in the magic
+//   string. Of the 16 hex characters, 0 1 2 7 9 a b e are, so that is a coin
+//   flip, and each of a-f and A-F appears with probability 1/32.
+//
+// Any other character carries
+//   H = 10 * (1/16) * log2(16) + 12 * (1/32) * log2(32) = 2.5 + 1.875 = 4.375 bits
+// out of 22 possible characters: 0-9, a-f and A-F.
+//
+// The first character replaces a digit with `K`, so `K` has probability 10/16
+// and each letter still 1/32:
+//   H0 = (5/8) * log2(8/5) + 12 * (1/32) * log2(32) = 0.423795 + 1.875 = 2.298795 bits
+//
+// A password of length n therefore carries H0 + (n - 1) * H bits, from 23
+// possible characters (the 22 plus `K`).
+//
+// The other modes draw every symbol uniformly from their alphabet, so each
+// carries log2 of its size: log2(94) for the printable ASCII characters from `!`
+// to `~`, log2(10) for a digit and log2(7776) for a word of the EFF large
+// wordlist, the one of five-dice diceware.
+//
+// However long a password is, it is a function of the 128-bit base hash, so the
+// total is capped at 128 bits; classic passwords reach the cap from length 30 on.
+
+/// Bits carried by every classic character but the first
+const CHARACTER_BITS: f64 = 4.375;
+
+/// Bits carried by the first classic character, `(5/8) * log2(8/5) + 1.875`
+const FIRST_CHARACTER_BITS: f64 = 2.298_794_940_6;
+
+/// Bits carried by a printable ASCII character, `log2(94)`
+const SYMBOL_BITS: f64 = 6.554_588_851_677_638;
+
+/// Bits carried by a decimal digit, `log2(10)`
+const DIGIT_BITS: f64 = core::f64::consts::LOG2_10;
+
+/// Bits carried by a word of the EFF large wordlist, `log2(7776)`
+const WORD_BITS: f64 = 12.924_812_503_605_78;
+
+/// Bits in the base hash every character is derived from
+const BASE_HASH_BITS: f64 = 128.0;
+
+/// Characters that can appear in a classic password: `0-9`, `a-f`, `A-F` and the
+/// leading `K`
+const ALPHABET_SIZE: usize = 23;
+
+/// Printable ASCII characters, `!` to `~`
+const SYMBOLS: usize = 94;
+
+/// Words in the EFF large wordlist
+pub const EFF_WORDLIST_SIZE: usize = 7776;
+
+/// What derived passwords are made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Flower Password's classic output: hex characters with some letters
+    /// uppercased, the first never a digit
+    Classic,
+    /// Printable ASCII characters, symbols included
+    Symbols,
+    /// Decimal digits
+    Pin,
+    /// Words of the EFF large wordlist
+    Passphrase,
+}
+
+/// How passwords are derived, for [`estimate_entropy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FpOptions {
+    mode: Mode,
+    length: usize,
+}
+
+impl FpOptions {
+    /// Options for classic passwords of `length` characters
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn classic(length: usize) -> Result<Self, FlowerPasswordError> {
+        Self::new(Mode::Classic, length)
+    }
+
+    /// Options for passwords of `length` printable characters
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn symbols(length: usize) -> Result<Self, FlowerPasswordError> {
+        Self::new(Mode::Symbols, length)
+    }
+
+    /// Options for PINs of `digits` digits
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if digits is not between 2 and 32.
+    pub fn pin(digits: usize) -> Result<Self, FlowerPasswordError> {
+        Self::new(Mode::Pin, digits)
+    }
+
+    /// Options for passphrases of `words` words
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if words is not between 2 and 32.
+    pub fn passphrase(words: usize) -> Result<Self, FlowerPasswordError> {
+        Self::new(Mode::Passphrase, words)
+    }
+
+    /// Options for `length` characters, digits or words of `mode`
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn new(mode: Mode, length: usize) -> Result<Self, FlowerPasswordError> {
+        validate_length(length)?;
+        Ok(FpOptions { mode, length })
+    }
+
+    /// What the passwords are made of
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Number of characters, digits or words in a password
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+/// An estimate of the strength of derived passwords
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntropyEstimate {
+    /// Entropy in bits, assuming the master password carries at least as much
+    pub bits: f64,
+    /// Number of distinct characters, or words for a passphrase, a password can
+    /// contain
+    pub alphabet_size: usize,
+    /// Assumptions and caveats behind the estimate, for display
+    pub notes: Vec<&'static str>,
+}
+
+/// Estimates the entropy of passwords derived with `options`
+///
+/// See the module source for the derivation of the numbers.
+pub fn estimate_entropy(options: &FpOptions) -> EntropyEstimate {
+    let length = options.length as f64;
+    let (uncapped, alphabet_size, mut notes) = match options.mode {
+        Mode::Classic => (
+            FIRST_CHARACTER_BITS + (length - 1.0) * CHARACTER_BITS,
+            ALPHABET_SIZE,
+            alloc::vec![
+                "characters are hex digits with some letters uppercased: 22 possibilities, not the 62 of a random alphanumeric password",
+                "the first character is never a digit, so it carries 2.3 bits instead of 4.375",
+            ],
+        ),
+        Mode::Symbols => (
+            length * SYMBOL_BITS,
+            SYMBOLS,
+            alloc::vec!["each character is taken to be any of the 94 printable ASCII characters alike"],
+        ),
+        Mode::Pin => (
+            length * DIGIT_BITS,
+            10,
+            alloc::vec!["each digit is taken to be any of the 10 alike: 3.3 bits"],
+        ),
+        Mode::Passphrase => (
+            length * WORD_BITS,
+            EFF_WORDLIST_SIZE,
+            alloc::vec!["each word is taken to be any of the 7776 of the EFF large wordlist alike: 12.9 bits"],
+        ),
+    };
+    notes.push("a guessable master password caps every derived password at its own strength");
+    if uncapped > BASE_HASH_BITS {
+        notes.push("capped at 128 bits: every password is derived from one 128-bit hash");
+    }
+
+    EntropyEstimate {
+        bits: uncapped.min(BASE_HASH_BITS),
+        alphabet_size,
+        notes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+
+    fn estimate(mode: Mode, length: usize) -> EntropyEstimate {
+        estimate_entropy(&FpOptions::new(mode, length).unwrap())
+    }
+
+    #[test]
+    fn test_constants_match_the_formulas() {
+        let digit: f64 = 10.0 / 16.0;
+        let letter = 1.0 / 32.0;
+        assert_eq!(
+            CHARACTER_BITS,
+            10.0 * (1.0 / 16.0) * 16f64.log2() + 12.0 * letter * 32f64.log2()
+        );
+        let first = digit * (1.0 / digit).log2() + 12.0 * letter * 32f64.log2();
+        assert!((FIRST_CHARACTER_BITS - first).abs() < 1e-10, "{}", first);
+        assert_eq!(SYMBOL_BITS, (SYMBOLS as f64).log2());
+        assert_eq!(DIGIT_BITS, 10f64.log2());
+        assert_eq!(WORD_BITS, (EFF_WORDLIST_SIZE as f64).log2());
+        assert_eq!((b'!'..=b'~').count(), SYMBOLS);
+
+        // Half the hex characters are in the magic string
+        let magic = "0123456789abcdef"
+            .chars()
+            .filter(|&c| "sunlovesnow1990090127xykab".contains(c))
+            .count();
+        assert_eq!(magic, 8);
+    }
+
+    #[test]
+    fn test_hand_computed_values() {
+        // 2.298795 + 15 * 4.375
+        let classic = estimate(Mode::Classic, 16);
+        assert!((classic.bits - 67.923_794_940_6).abs() < 1e-9);
+        assert_eq!(classic.alphabet_size, 23);
+        assert_eq!(classic.notes.len(), 3);
+
+        // 6 * 3.321928, a million PINs
+        let pin = estimate_entropy(&FpOptions::pin(6).unwrap());
+        assert!((pin.bits - 19.931_568_569_3).abs() < 1e-9);
+        assert_eq!(pin.alphabet_size, 10);
+
+        // 5 * 12.924813, 7776^5 passphrases
+        let passphrase = estimate_entropy(&FpOptions::passphrase(5).unwrap());
+        assert!((passphrase.bits - 64.624_062_518_0).abs() < 1e-9);
+        assert_eq!(passphrase.alphabet_size, 7776);
+
+        // 16 * 6.554589
+        let symbols = estimate_entropy(&FpOptions::symbols(16).unwrap());
+        assert!((symbols.bits - 104.873_421_626_8).abs() < 1e-9);
+        assert_eq!(symbols.alphabet_size, 94);
+    }
+
+    #[test]
+    fn test_cap() {
+        // 2.298795 + 4.375
+        assert!((estimate(Mode::Classic, 2).bits - 6.673_794_940_6).abs() < 1e-9);
+        // 2.298795 + 28 * 4.375 = 124.8, and one more character would pass 128
+        assert!((estimate(Mode::Classic, 29).bits - 124.798_794_940_6).abs() < 1e-9);
+        for length in 30..=32 {
+            let estimate = estimate(Mode::Classic, length);
+            assert_eq!(estimate.bits, 128.0);
+            assert!(estimate.notes.iter().any(|note| note.contains("capped")));
+        }
+        // 10 words would be 129.2 bits
+        assert!((estimate(Mode::Passphrase, 9).bits - 116.323_312_532_5).abs() < 1e-9);
+        assert_eq!(estimate(Mode::Passphrase, 10).bits, 128.0);
+        assert_eq!(estimate(Mode::Pin, 32).notes.len(), 2);
+    }
+
+    #[test]
+    fn test_alphabet_matches_derived_passwords() {
+        let mut seen = [false; 128];
+        for i in 0..2000 {
+            let key = alloc::format!("site{}.example", i);
+            for &byte in fp_code("test", &key, 32).unwrap().as_bytes() {
+                seen[usize::from(byte)] = true;
+            }
+        }
+        assert_eq!(seen.iter().filter(|&&s| s).count(), ALPHABET_SIZE);
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        for length in [0, 1, 33] {
+            assert!(matches!(
+                FpOptions::classic(length),
+                Err(FlowerPasswordError::InvalidLength(len)) if len == length
+            ));
+            assert!(FpOptions::pin(length).is_err());
+            assert!(FpOptions::passphrase(length).is_err());
+        }
+        let options = FpOptions::passphrase(5).unwrap();
+        assert_eq!((options.mode(), options.length()), (Mode::Passphrase, 5));
+    }
+}
@@ -0,0 +1,304 @@
+//! C ABI for calling Flower Password from C, Swift and other FFI hosts
+//!
+//! The functions here never allocate memory that the caller has to free: the
+//! password is written into a buffer the caller owns, and the last error message
+//! stays owned by the library. The matching header is generated by cbindgen into
+//! `include/flowerpassword.h` when building with the `ffi` feature.
+//!
+//! # Error codes
+//!
+//! | Code | Constant                   | Meaning                                         |
+//! |------|----------------------------|-------------------------------------------------|
+//! | 0    | `FP_OK`                    | The password was written to `out`               |
+//! | -1   | `FP_ERROR_INVALID_LENGTH`  | `length` is not between 2 and 32                |
+//! | -2   | `FP_ERROR_INVALID_UTF8`    | `password` or `key` is not valid UTF-8          |
+//! | -3   | `FP_ERROR_BUFFER_TOO_SMALL`| `out_cap` is less than `length + 1`             |
+//! | -4   | `FP_ERROR_NULL_POINTER`    | `password`, `key` or `out` is null              |
+
+use crate::{fp_code, FlowerPasswordError};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// The password was written to the output buffer
+pub const FP_OK: i32 = 0;
+
+/// The requested length is outside the valid range
+pub const FP_ERROR_INVALID_LENGTH: i32 = -1;
+
+/// The master password or key is not valid UTF-8
+pub const FP_ERROR_INVALID_UTF8: i32 = -2;
+
+/// The output buffer cannot hold the password and its NUL terminator
+pub const FP_ERROR_BUFFER_TOO_SMALL: i32 = -3;
+
+/// A required pointer argument is null
+pub const FP_ERROR_NULL_POINTER: i32 = -4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records `message` as the calling thread's last error and returns `code`
+fn fail(code: i32, message: String) -> i32 {
+    // Messages are built from Rust strings and contain no NUL bytes
+    let message = CString::new(message).expect("error messages contain no NUL bytes");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// Reads a NUL-terminated argument as UTF-8
+///
+/// # Safety
+///
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, i32> {
+    if value.is_null() {
+        return Err(fail(
+            FP_ERROR_NULL_POINTER,
+            format!("Argument `{}` is a null pointer", name),
+        ));
+    }
+    CStr::from_ptr(value).to_str().map_err(|error| {
+        fail(
+            FP_ERROR_INVALID_UTF8,
+            format!("Argument `{}` is not valid UTF-8: {}", name, error),
+        )
+    })
+}
+
+/// Generates a Flower Password into a caller-provided buffer
+///
+/// On success the password and a NUL terminator are written to `out`, which must
+/// have room for at least `length + 1` bytes. On failure `out` is left untouched
+/// and `fp_last_error_message` describes the error.
+///
+/// Returns `FP_OK` or one of the negative `FP_ERROR_*` codes.
+///
+/// # Safety
+///
+/// `password` and `key` must each be null or point to a NUL-terminated string,
+/// and `out` must be null or point to at least `out_cap` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fp_code_c(
+    password: *const c_char,
+    key: *const c_char,
+    length: usize,
+    out: *mut c_char,
+    out_cap: usize,
+) -> i32 {
+    if out.is_null() {
+        return fail(
+            FP_ERROR_NULL_POINTER,
+            "Argument `out` is a null pointer".to_string(),
+        );
+    }
+    let password = match read_str(password, "password") {
+        Ok(password) => password,
+        Err(code) => return code,
+    };
+    let key = match read_str(key, "key") {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+
+    let generated = match fp_code(password, key, length) {
+        Ok(generated) => generated,
+        Err(error @ FlowerPasswordError::InvalidLength(_)) => {
+            return fail(FP_ERROR_INVALID_LENGTH, error.to_string())
+        }
+    };
+    if out_cap < generated.len() + 1 {
+        return fail(
+            FP_ERROR_BUFFER_TOO_SMALL,
+            format!(
+                "Output buffer holds {} bytes, but {} are needed",
+                out_cap,
+                generated.len() + 1
+            ),
+        );
+    }
+
+    ptr::copy_nonoverlapping(generated.as_ptr().cast::<c_char>(), out, generated.len());
+    *out.add(generated.len()) = 0;
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    FP_OK
+}
+
+/// Returns the message of the calling thread's last failed call, or null
+///
+/// The string is owned by the library and stays valid until the next call to
+/// `fp_code_c` on the same thread; it must not be freed. A successful call
+/// clears the message.
+#[no_mangle]
+pub extern "C" fn fp_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cstring(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    /// Calls `fp_code_c` with Rust strings and returns the code and output
+    fn call(password: &CStr, key: &CStr, length: usize, out_cap: usize) -> (i32, Vec<u8>) {
+        let mut out = vec![0x7fu8; out_cap.max(1)];
+        let code = unsafe {
+            fp_code_c(
+                password.as_ptr(),
+                key.as_ptr(),
+                length,
+                out.as_mut_ptr().cast(),
+                out_cap,
+            )
+        };
+        (code, out)
+    }
+
+    fn last_error() -> Option<String> {
+        let message = fp_last_error_message();
+        if message.is_null() {
+            None
+        } else {
+            Some(
+                unsafe { CStr::from_ptr(message) }
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for length in 2..=32 {
+            let (code, out) = call(&cstring("password"), &cstring("key"), length, 33);
+            assert_eq!(code, FP_OK);
+            let written = CStr::from_bytes_until_nul(&out).unwrap().to_str().unwrap();
+            assert_eq!(written, fp_code("password", "key", length).unwrap());
+        }
+        assert_eq!(last_error(), None);
+    }
+
+    #[test]
+    fn test_unicode_round_trip() {
+        let (code, out) = call(&cstring("密码"), &cstring("网站.com"), 16, 17);
+        assert_eq!(code, FP_OK);
+        assert_eq!(&out[..17], b"KFF7FEa7928bAAAa\0");
+    }
+
+    #[test]
+    fn test_emoji_round_trip() {
+        // U+1F338 is four bytes in UTF-8
+        let (code, out) = call(&cstring("test"), &cstring("🌸.example"), 16, 17);
+        assert_eq!(code, FP_OK);
+        assert_eq!(&out[..17], b"Kb305852652B6264\0");
+        assert_eq!(
+            fp_code("test", "🌸.example", 16).unwrap(),
+            "Kb305852652B6264"
+        );
+    }
+
+    #[test]
+    fn test_buffer_exact_fit() {
+        let (code, out) = call(&cstring("password"), &cstring("key"), 32, 33);
+        assert_eq!(code, FP_OK);
+        assert_eq!(&out[..32], b"K3A2a66Bf88b628c2Cd7cDA9958f6b26");
+        assert_eq!(out[32], 0);
+    }
+
+    #[test]
+    fn test_larger_buffer_is_untouched_past_terminator() {
+        let (code, out) = call(&cstring("password"), &cstring("key"), 4, 16);
+        assert_eq!(code, FP_OK);
+        assert_eq!(&out[..5], b"K3A2\0");
+        assert!(out[5..].iter().all(|&b| b == 0x7f));
+    }
+
+    #[test]
+    fn test_no_last_error_on_fresh_thread() {
+        let message = std::thread::spawn(|| fp_last_error_message() as usize)
+            .join()
+            .unwrap();
+        assert_eq!(message, 0);
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let (code, out) = call(&cstring("password"), &cstring("key"), 33, 64);
+        assert_eq!(code, FP_ERROR_INVALID_LENGTH);
+        assert!(out.iter().all(|&b| b == 0x7f));
+        assert_eq!(
+            last_error().unwrap(),
+            "Length must be between 2 and 32, got: 33"
+        );
+
+        let (code, _) = call(&cstring("password"), &cstring("key"), 1, 64);
+        assert_eq!(code, FP_ERROR_INVALID_LENGTH);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let key = CString::new(vec![0x66, 0xff, 0x6f]).unwrap();
+        let (code, _) = call(&cstring("password"), &key, 16, 17);
+        assert_eq!(code, FP_ERROR_INVALID_UTF8);
+        assert!(last_error()
+            .unwrap()
+            .starts_with("Argument `key` is not valid UTF-8"));
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        // The NUL terminator needs a byte of its own
+        let (code, out) = call(&cstring("password"), &cstring("key"), 16, 16);
+        assert_eq!(code, FP_ERROR_BUFFER_TOO_SMALL);
+        assert!(out.iter().all(|&b| b == 0x7f));
+        assert_eq!(
+            last_error().unwrap(),
+            "Output buffer holds 16 bytes, but 17 are needed"
+        );
+
+        let (code, _) = call(&cstring("password"), &cstring("key"), 16, 0);
+        assert_eq!(code, FP_ERROR_BUFFER_TOO_SMALL);
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        let password = cstring("password");
+        let key = cstring("key");
+        let mut out = [0 as c_char; 17];
+
+        let code = unsafe { fp_code_c(ptr::null(), key.as_ptr(), 16, out.as_mut_ptr(), 17) };
+        assert_eq!(code, FP_ERROR_NULL_POINTER);
+        assert_eq!(
+            last_error().unwrap(),
+            "Argument `password` is a null pointer"
+        );
+
+        let code = unsafe { fp_code_c(password.as_ptr(), ptr::null(), 16, out.as_mut_ptr(), 17) };
+        assert_eq!(code, FP_ERROR_NULL_POINTER);
+        assert_eq!(last_error().unwrap(), "Argument `key` is a null pointer");
+
+        let code = unsafe { fp_code_c(password.as_ptr(), key.as_ptr(), 16, ptr::null_mut(), 17) };
+        assert_eq!(code, FP_ERROR_NULL_POINTER);
+        assert_eq!(last_error().unwrap(), "Argument `out` is a null pointer");
+    }
+
+    #[test]
+    fn test_success_clears_last_error() {
+        let (code, _) = call(&cstring("password"), &cstring("key"), 40, 64);
+        assert_eq!(code, FP_ERROR_INVALID_LENGTH);
+        assert!(last_error().is_some());
+
+        let (code, _) = call(&cstring("password"), &cstring("key"), 16, 17);
+        assert_eq!(code, FP_OK);
+        assert_eq!(last_error(), None);
+    }
+}
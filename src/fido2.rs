@@ -0,0 +1,639 @@
+//! FIDO2 hmac-secret master source
+//!
+//! A FIDO2 authenticator supporting the CTAP2 `hmac-secret` extension creates a
+//! random secret for each credential, which never leaves the authenticator. An
+//! assertion for that credential with a salt returns `HMAC-SHA-256(secret, salt)`,
+//! so asserting a fixed credential with a fixed salt yields the same 32 bytes every
+//! time. Their lowercase hex encoding is used as the master password for the normal
+//! [`fp_code`](crate::fp_code) pipeline:
+//!
+//! ```text
+//! salt   = SHA-256(SALT_PREFIX || label)
+//! master = hex(hmac-secret(credential, salt))
+//! ```
+//!
+//! The label lets one credential hold several independent masters. The credential
+//! is created once by [`enroll`], for the relying party [`RELYING_PARTY`], and its
+//! [`CredentialId`] is stored, for example in the configuration, as hex.
+//!
+//! Authenticators keep two secrets per credential, one for assertions with user
+//! verification (a PIN) and one without. An authenticator with a PIN set is
+//! therefore always asked with the PIN, and [`master_from_fido2`] refuses to go on
+//! without one rather than silently derive a different master.
+//!
+//! Authenticator access goes through the [`HmacSecret`] trait, implemented by a
+//! CTAP2 backend over USB HID or NFC.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::fido2::{master_from_fido2, CredentialId, Fido2Error, HmacSecret};
+//! use std::time::Duration;
+//!
+//! struct Canned;
+//!
+//! impl HmacSecret for Canned {
+//!     fn requires_pin(&mut self) -> Result<bool, Fido2Error> {
+//!         Ok(false)
+//!     }
+//!
+//!     fn make_credential(
+//!         &mut self,
+//!         _rp_id: &str,
+//!         _pin: Option<&str>,
+//!         _timeout: Duration,
+//!     ) -> Result<Vec<u8>, Fido2Error> {
+//!         Ok(vec![1; 16])
+//!     }
+//!
+//!     fn hmac_secret(
+//!         &mut self,
+//!         _rp_id: &str,
+//!         _credential: &[u8],
+//!         _salt: &[u8; 32],
+//!         _pin: Option<&str>,
+//!         _timeout: Duration,
+//!     ) -> Result<[u8; 32], Fido2Error> {
+//!         Ok([0xab; 32])
+//!     }
+//! }
+//!
+//! let credential: CredentialId = "01010101010101010101010101010101".parse()?;
+//! let master = master_from_fido2(&mut Canned, &credential, "personal", None, Duration::from_secs(30), || {})?;
+//! assert_eq!(master.len(), 64);
+//! let password = flowerpassword::fp_code(&master, "github.com", 16)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Relying party every credential is created for and asserted with
+pub const RELYING_PARTY: &str = "flowerpassword";
+
+/// Prefix of the hashed salt, followed by the label
+pub const SALT_PREFIX: &str = "flowerpassword fido2 master v1:";
+
+/// Length of an hmac-secret output, in bytes
+pub const OUTPUT_LEN: usize = 32;
+
+/// How long to wait for the user to touch the authenticator
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error type for FIDO2 operations
+#[derive(Debug)]
+pub enum Fido2Error {
+    /// No authenticator is connected
+    NotFound,
+    /// The authenticator does not support the hmac-secret extension
+    ExtensionUnsupported,
+    /// The authenticator has a PIN set, and none was given
+    PinRequired,
+    /// The PIN was wrong, with the retries the authenticator has left if it said
+    ///
+    /// The PIN itself is not kept.
+    PinInvalid(Option<u8>),
+    /// The authenticator locked its PIN after too many wrong attempts
+    PinBlocked,
+    /// The authenticator was not touched before the timeout
+    UserPresenceTimeout(Duration),
+    /// The authenticator does not hold the enrolled credential
+    NoCredential,
+    /// A stored credential ID that is not an even number of hex digits
+    InvalidCredentialId,
+    /// A `--master` value that is not `fido2` or `fido2:<label>`
+    ///
+    /// The value is not kept, since it may be a master password given by mistake.
+    InvalidSource,
+    /// Talking to the authenticator failed
+    Device(String),
+}
+
+impl fmt::Display for Fido2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fido2Error::NotFound => write!(f, "No FIDO2 authenticator found"),
+            Fido2Error::ExtensionUnsupported => {
+                write!(f, "FIDO2 authenticator does not support hmac-secret")
+            }
+            Fido2Error::PinRequired => write!(f, "FIDO2 authenticator requires its PIN"),
+            Fido2Error::PinInvalid(Some(retries)) => write!(
+                f,
+                "Wrong FIDO2 PIN, {} attempts left before it is blocked",
+                retries
+            ),
+            Fido2Error::PinInvalid(None) => write!(f, "Wrong FIDO2 PIN"),
+            Fido2Error::PinBlocked => write!(
+                f,
+                "FIDO2 PIN is blocked; reset the authenticator to use it again"
+            ),
+            Fido2Error::UserPresenceTimeout(timeout) => write!(
+                f,
+                "FIDO2 authenticator was not touched within {} seconds",
+                timeout.as_secs()
+            ),
+            Fido2Error::NoCredential => write!(
+                f,
+                "FIDO2 authenticator does not hold the enrolled credential"
+            ),
+            Fido2Error::InvalidCredentialId => {
+                write!(f, "Invalid FIDO2 credential ID, expected hex digits")
+            }
+            Fido2Error::InvalidSource => {
+                write!(f, "Invalid master source, expected fido2 or fido2:<label>")
+            }
+            Fido2Error::Device(message) => write!(f, "FIDO2 error: {}", message),
+        }
+    }
+}
+
+impl Error for Fido2Error {}
+
+/// ID of the credential created by [`enroll`]
+///
+/// It identifies the credential to the authenticator and is not secret; it is
+/// formatted and parsed as lowercase hex for storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialId(Vec<u8>);
+
+impl CredentialId {
+    /// Wraps the raw ID returned by the authenticator
+    pub fn new(id: Vec<u8>) -> Self {
+        CredentialId(id)
+    }
+
+    /// Returns the raw ID
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for CredentialId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CredentialId {
+    type Err = Fido2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() % 2 != 0 || !s.is_ascii() {
+            return Err(Fido2Error::InvalidCredentialId);
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map(CredentialId)
+            .map_err(|_| Fido2Error::InvalidCredentialId)
+    }
+}
+
+/// Access to an authenticator's credentials and their hmac-secret extension
+pub trait HmacSecret {
+    /// Returns whether the authenticator has a PIN set
+    fn requires_pin(&mut self) -> Result<bool, Fido2Error>;
+
+    /// Creates a credential with hmac-secret enabled for `rp_id` and returns its ID
+    ///
+    /// Implementations wait for a touch and give up with
+    /// `Fido2Error::UserPresenceTimeout` after `timeout`.
+    fn make_credential(
+        &mut self,
+        rp_id: &str,
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Fido2Error>;
+
+    /// Asserts `credential` for `rp_id` with the hmac-secret extension and `salt`
+    ///
+    /// Implementations wait for a touch and give up with
+    /// `Fido2Error::UserPresenceTimeout` after `timeout`.
+    fn hmac_secret(
+        &mut self,
+        rp_id: &str,
+        credential: &[u8],
+        salt: &[u8; 32],
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<[u8; OUTPUT_LEN], Fido2Error>;
+}
+
+impl<T: HmacSecret + ?Sized> HmacSecret for &mut T {
+    fn requires_pin(&mut self) -> Result<bool, Fido2Error> {
+        (**self).requires_pin()
+    }
+
+    fn make_credential(
+        &mut self,
+        rp_id: &str,
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Fido2Error> {
+        (**self).make_credential(rp_id, pin, timeout)
+    }
+
+    fn hmac_secret(
+        &mut self,
+        rp_id: &str,
+        credential: &[u8],
+        salt: &[u8; 32],
+        pin: Option<&str>,
+        timeout: Duration,
+    ) -> Result<[u8; OUTPUT_LEN], Fido2Error> {
+        (**self).hmac_secret(rp_id, credential, salt, pin, timeout)
+    }
+}
+
+/// Parses a `--master` value of the form `fido2` or `fido2:<label>`, returning the
+/// label if one is given
+///
+/// # Errors
+///
+/// Returns `Fido2Error::InvalidSource` if `spec` does not name the FIDO2 source.
+pub fn parse_source(spec: &str) -> Result<Option<&str>, Fido2Error> {
+    match spec.split_once(':') {
+        None if spec == "fido2" => Ok(None),
+        Some(("fido2", label)) if !label.is_empty() => Ok(Some(label)),
+        _ => Err(Fido2Error::InvalidSource),
+    }
+}
+
+/// Builds the hmac-secret salt for `label`
+pub fn salt(label: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(SALT_PREFIX.as_bytes());
+    hasher.update(label.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Creates the credential masters are derived from
+///
+/// `on_touch` is called before waiting for the user to touch the authenticator.
+///
+/// # Errors
+///
+/// Returns `Fido2Error::PinRequired` without touching the authenticator if it has
+/// a PIN set and `pin` is `None`, or any error from the authenticator.
+pub fn enroll<A: HmacSecret>(
+    mut authenticator: A,
+    pin: Option<&str>,
+    timeout: Duration,
+    on_touch: impl FnOnce(),
+) -> Result<CredentialId, Fido2Error> {
+    if pin.is_none() && authenticator.requires_pin()? {
+        return Err(Fido2Error::PinRequired);
+    }
+    on_touch();
+    authenticator
+        .make_credential(RELYING_PARTY, pin, timeout)
+        .map(CredentialId)
+}
+
+/// Obtains the master password for `label` from `credential` on `authenticator`
+///
+/// `on_touch` is called before waiting for the user to touch the authenticator.
+/// The output is returned as 64 lowercase hex characters in a buffer that is
+/// zeroed on drop.
+///
+/// # Errors
+///
+/// Returns `Fido2Error::PinRequired` without touching the authenticator if it has
+/// a PIN set and `pin` is `None`, or any error from the authenticator, including
+/// `Fido2Error::UserPresenceTimeout` and `Fido2Error::PinInvalid`.
+pub fn master_from_fido2<A: HmacSecret>(
+    mut authenticator: A,
+    credential: &CredentialId,
+    label: &str,
+    pin: Option<&str>,
+    timeout: Duration,
+    on_touch: impl FnOnce(),
+) -> Result<Zeroizing<String>, Fido2Error> {
+    if pin.is_none() && authenticator.requires_pin()? {
+        return Err(Fido2Error::PinRequired);
+    }
+    on_touch();
+    let output = Zeroizing::new(authenticator.hmac_secret(
+        RELYING_PARTY,
+        credential.as_bytes(),
+        &salt(label),
+        pin,
+        timeout,
+    )?);
+
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut master = Zeroizing::new(String::with_capacity(OUTPUT_LEN * 2));
+    for byte in output.iter() {
+        master.push(HEX[(byte >> 4) as usize] as char);
+        master.push(HEX[(byte & 0x0f) as usize] as char);
+    }
+    Ok(master)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+
+    /// HMAC-SHA-256, as authenticators compute hmac-secret outputs
+    fn hmac_sha256(key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+        let mut block = [0u8; 64];
+        block[..32].copy_from_slice(key);
+        let pad = |byte: u8| block.map(|b| b ^ byte);
+        let inner = Sha256::new()
+            .chain_update(pad(0x36))
+            .chain_update(message)
+            .finalize();
+        Sha256::new()
+            .chain_update(pad(0x5c))
+            .chain_update(inner)
+            .finalize()
+            .into()
+    }
+
+    /// A software authenticator following the hmac-secret extension
+    ///
+    /// Credentials are numbered; each has one secret for assertions with a PIN and
+    /// one without, derived from a seed so that tests are reproducible.
+    #[derive(Default)]
+    struct SoftAuthenticator {
+        pin: Option<&'static str>,
+        retries: u8,
+        absent_user: bool,
+        credentials: Vec<u8>,
+        salts: Vec<[u8; 32]>,
+    }
+
+    impl SoftAuthenticator {
+        fn secret(credential: u8, verified: bool) -> [u8; 32] {
+            Sha256::new()
+                .chain_update([credential, u8::from(verified)])
+                .finalize()
+                .into()
+        }
+
+        fn check_pin(&mut self, pin: Option<&str>) -> Result<bool, Fido2Error> {
+            match (self.pin, pin) {
+                (_, None) => Ok(false),
+                (Some(_), Some(_)) if self.retries == 0 => Err(Fido2Error::PinBlocked),
+                (Some(expected), Some(pin)) if expected == pin => Ok(true),
+                (Some(_), Some(_)) => {
+                    self.retries -= 1;
+                    Err(Fido2Error::PinInvalid(Some(self.retries)))
+                }
+                (None, Some(_)) => Err(Fido2Error::Device("no PIN set".to_string())),
+            }
+        }
+    }
+
+    impl HmacSecret for SoftAuthenticator {
+        fn requires_pin(&mut self) -> Result<bool, Fido2Error> {
+            Ok(self.pin.is_some())
+        }
+
+        fn make_credential(
+            &mut self,
+            rp_id: &str,
+            pin: Option<&str>,
+            timeout: Duration,
+        ) -> Result<Vec<u8>, Fido2Error> {
+            assert_eq!(rp_id, RELYING_PARTY);
+            self.check_pin(pin)?;
+            if self.absent_user {
+                return Err(Fido2Error::UserPresenceTimeout(timeout));
+            }
+            let id = self.credentials.len() as u8;
+            self.credentials.push(id);
+            Ok(vec![0xc0, id])
+        }
+
+        fn hmac_secret(
+            &mut self,
+            rp_id: &str,
+            credential: &[u8],
+            salt: &[u8; 32],
+            pin: Option<&str>,
+            timeout: Duration,
+        ) -> Result<[u8; OUTPUT_LEN], Fido2Error> {
+            assert_eq!(rp_id, RELYING_PARTY);
+            let id = match credential {
+                [0xc0, id] if self.credentials.contains(id) => *id,
+                _ => return Err(Fido2Error::NoCredential),
+            };
+            let verified = self.check_pin(pin)?;
+            if self.absent_user {
+                return Err(Fido2Error::UserPresenceTimeout(timeout));
+            }
+            self.salts.push(*salt);
+            Ok(hmac_sha256(&Self::secret(id, verified), salt))
+        }
+    }
+
+    fn enrolled(pin: Option<&'static str>) -> (SoftAuthenticator, CredentialId) {
+        let mut authenticator = SoftAuthenticator {
+            pin,
+            retries: 8,
+            ..SoftAuthenticator::default()
+        };
+        let credential = enroll(&mut authenticator, pin, DEFAULT_TIMEOUT, || {}).unwrap();
+        (authenticator, credential)
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2, with the key zero-padded to 32 bytes as HMAC does
+        let mut key = [0u8; 32];
+        key[..4].copy_from_slice(b"Jefe");
+        let mac = hmac_sha256(&key, b"what do ya want for nothing?");
+        assert_eq!(
+            CredentialId::new(mac.to_vec()).to_string(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(parse_source("fido2").unwrap(), None);
+        assert_eq!(parse_source("fido2:work").unwrap(), Some("work"));
+        for spec in ["fido2:", "yubikey", "fido", "fido2work", ""] {
+            assert!(matches!(parse_source(spec), Err(Fido2Error::InvalidSource)));
+        }
+    }
+
+    #[test]
+    fn test_salt() {
+        let expected: [u8; 32] = Sha256::digest(b"flowerpassword fido2 master v1:personal").into();
+        assert_eq!(salt("personal"), expected);
+        assert_ne!(salt("personal"), salt("work"));
+    }
+
+    #[test]
+    fn test_credential_id() {
+        let id = CredentialId::new(vec![0x00, 0xc0, 0xff]);
+        assert_eq!(id.to_string(), "00c0ff");
+        assert_eq!("00c0ff".parse::<CredentialId>().unwrap(), id);
+        assert_eq!("00C0FF".parse::<CredentialId>().unwrap(), id);
+        for invalid in ["", "0", "00c0f", "zz", "éé"] {
+            assert!(matches!(
+                invalid.parse::<CredentialId>(),
+                Err(Fido2Error::InvalidCredentialId)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_derivation_chain() {
+        let (mut authenticator, credential) = enrolled(None);
+        assert_eq!(credential.to_string(), "c000");
+        let master = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            None,
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap();
+        assert_eq!(authenticator.salts, [salt("personal")]);
+        let expected = hmac_sha256(&SoftAuthenticator::secret(0, false), &salt("personal"));
+        assert_eq!(*master, CredentialId::new(expected.to_vec()).to_string());
+        assert_eq!(
+            *master,
+            "96916c4f823f28b2b2394aea3c0e2da10fc025d6c7b82b7c44c0118fd8f5e13e"
+        );
+        assert_eq!(
+            fp_code(&master, "github.com", 16).unwrap(),
+            "K00453372304c463"
+        );
+
+        // The same credential and label always give the same master
+        let again = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            None,
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap();
+        assert_eq!(again, master);
+        let work = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "work",
+            None,
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap();
+        assert_ne!(work, master);
+    }
+
+    #[test]
+    fn test_pin_protected_authenticator() {
+        let (mut authenticator, credential) = enrolled(Some("1234"));
+        let mut touched = false;
+        let error = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            None,
+            DEFAULT_TIMEOUT,
+            || touched = true,
+        )
+        .unwrap_err();
+        assert!(matches!(error, Fido2Error::PinRequired));
+        assert!(!touched);
+        assert!(authenticator.salts.is_empty());
+
+        let error = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            Some("0000"),
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Wrong FIDO2 PIN, 7 attempts left before it is blocked"
+        );
+
+        let master = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            Some("1234"),
+            DEFAULT_TIMEOUT,
+            || touched = true,
+        )
+        .unwrap();
+        assert!(touched);
+        let verified = hmac_sha256(&SoftAuthenticator::secret(0, true), &salt("personal"));
+        assert_eq!(*master, CredentialId::new(verified.to_vec()).to_string());
+    }
+
+    #[test]
+    fn test_blocked_pin() {
+        let (mut authenticator, credential) = enrolled(Some("1234"));
+        authenticator.retries = 0;
+        let error = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            Some("1234"),
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap_err();
+        assert!(matches!(error, Fido2Error::PinBlocked));
+    }
+
+    #[test]
+    fn test_user_presence_timeout() {
+        let (mut authenticator, credential) = enrolled(None);
+        authenticator.absent_user = true;
+        let error = master_from_fido2(
+            &mut authenticator,
+            &credential,
+            "personal",
+            None,
+            Duration::from_secs(10),
+            || {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "FIDO2 authenticator was not touched within 10 seconds"
+        );
+        assert!(matches!(
+            enroll(&mut authenticator, None, DEFAULT_TIMEOUT, || {}),
+            Err(Fido2Error::UserPresenceTimeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_credential() {
+        let (authenticator, _) = enrolled(None);
+        let other = CredentialId::new(vec![0xc0, 9]);
+        let error = master_from_fido2(
+            authenticator,
+            &other,
+            "personal",
+            None,
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap_err();
+        assert!(matches!(error, Fido2Error::NoCredential));
+    }
+}
@@ -0,0 +1,286 @@
+//! A master password kept for many derivations
+//!
+//! [`FpGenerator`] holds the master password once, in locked memory with the
+//! `memlock` feature, so frontends deriving for many keys do not pass it around.
+//! Its `Debug` output leaves the master password out.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::FpGenerator;
+//!
+//! let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+//! assert_eq!(generator.generate("github.com"), "D04175F7A9c7Ab4a");
+//! assert!(generator.verify("github.com", "D04175F7A9c7Ab4a"));
+//! ```
+
+use crate::entropy::{estimate_entropy, EntropyEstimate, FpOptions};
+use crate::master::MasterPassword;
+#[cfg(feature = "memlock")]
+use crate::memlock::LockStatus;
+#[cfg(feature = "strength")]
+use crate::strength::{require_strength, CheckedError};
+use crate::{fp_code, fp_verify, FlowerPasswordError, Scheme};
+use flowerpassword_core::validate_length;
+#[cfg(feature = "secrecy")]
+use secrecy::SecretString;
+use std::fmt;
+
+/// Derives passwords for many keys under one master password
+pub struct FpGenerator {
+    master: MasterPassword,
+    length: usize,
+}
+
+impl FpGenerator {
+    /// Password length used by frontends that do not ask for one
+    pub const DEFAULT_LENGTH: usize = 16;
+
+    /// Takes ownership of `master` and derives `length` characters by default
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn new(master: String, length: usize) -> Result<Self, FlowerPasswordError> {
+        validate_length(length)?;
+        Ok(FpGenerator {
+            master: MasterPassword::new(master),
+            length,
+        })
+    }
+
+    /// Like [`new`](Self::new), but rejects master passwords that zxcvbn scores
+    /// below `min_score` (0 to 4)
+    ///
+    /// The length is checked first, so an invalid one never has the master rated.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CheckedError::InvalidLength` if length is not between 2 and 32, or
+    /// `CheckedError::WeakMaster` with zxcvbn's warnings and suggestions if the
+    /// score is below `min_score`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use flowerpassword::strength::{CheckedError, DEFAULT_MIN_SCORE};
+    /// use flowerpassword::FpGenerator;
+    ///
+    /// let error = FpGenerator::new_checked("password1".to_string(), 16, DEFAULT_MIN_SCORE).unwrap_err();
+    /// assert!(matches!(error, CheckedError::WeakMaster(weak) if !weak.strength.suggestions.is_empty()));
+    /// ```
+    #[cfg(feature = "strength")]
+    pub fn new_checked(master: String, length: usize, min_score: u8) -> Result<Self, CheckedError> {
+        validate_length(length).map_err(|_| CheckedError::InvalidLength(length))?;
+        require_strength(&master, min_score)?;
+        Ok(FpGenerator {
+            master: MasterPassword::new(master),
+            length,
+        })
+    }
+
+    /// Takes ownership of a master password held in a [`SecretString`]
+    ///
+    /// With `memlock` the secret is copied into locked memory and dropped, which
+    /// wipes it; otherwise the generator keeps the `SecretString` itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use flowerpassword::FpGenerator;
+    /// use secrecy::SecretString;
+    ///
+    /// let generator = FpGenerator::from_secret(SecretString::from("test"), 16).unwrap();
+    /// assert_eq!(generator.generate("github.com"), "D04175F7A9c7Ab4a");
+    /// ```
+    #[cfg(feature = "secrecy")]
+    pub fn from_secret(master: SecretString, length: usize) -> Result<Self, FlowerPasswordError> {
+        validate_length(length)?;
+        Ok(FpGenerator {
+            master: MasterPassword::from_secret(master),
+            length,
+        })
+    }
+
+    /// Default length of generated passwords
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Generates the password for `key` at the default length
+    pub fn generate(&self, key: &str) -> String {
+        fp_code(self.master.as_str(), key, self.length).expect("length validated in new")
+    }
+
+    /// Generates the password for `key` at `length`
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn generate_with_length(
+        &self,
+        key: &str,
+        length: usize,
+    ) -> Result<String, FlowerPasswordError> {
+        fp_code(self.master.as_str(), key, length)
+    }
+
+    /// Generates the password for `key` at `length` under `scheme`
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+    pub fn generate_with_scheme(
+        &self,
+        scheme: Scheme,
+        key: &str,
+        length: usize,
+    ) -> Result<String, FlowerPasswordError> {
+        scheme.fp_code(self.master.as_str(), key, length)
+    }
+
+    /// Generates the password for each key at the default length, in order
+    pub fn generate_many<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        keys.into_iter().map(|key| self.generate(key)).collect()
+    }
+
+    /// Checks whether `candidate` is the password for `key`, at any valid length
+    pub fn verify(&self, key: &str, candidate: &str) -> bool {
+        fp_verify(self.master.as_str(), key, candidate)
+    }
+
+    /// Estimates the entropy of classic passwords at the default length
+    ///
+    /// See [`estimate_entropy`].
+    pub fn estimate_entropy(&self) -> EntropyEstimate {
+        estimate_entropy(&FpOptions::classic(self.length).expect("length validated in new"))
+    }
+
+    /// Whether the master password's pages are locked in RAM
+    #[cfg(feature = "memlock")]
+    pub fn memory_status(&self) -> LockStatus {
+        self.master.status()
+    }
+}
+
+impl fmt::Debug for FpGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FpGenerator")
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::KNOWN_ANSWERS;
+
+    #[test]
+    fn test_matches_fp_code() {
+        for vector in KNOWN_ANSWERS {
+            let generator = FpGenerator::new(vector.password.to_string(), vector.length).unwrap();
+            assert_eq!(generator.generate(vector.key), vector.expected);
+            assert_eq!(
+                generator
+                    .generate_with_length(vector.key, vector.length)
+                    .unwrap(),
+                vector.expected
+            );
+            assert_eq!(
+                generator
+                    .generate_with_scheme(Scheme::V1, vector.key, vector.length)
+                    .unwrap(),
+                vector.expected
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "secrecy")]
+    fn test_from_secret_matches_new() {
+        for vector in KNOWN_ANSWERS {
+            let master = SecretString::from(vector.password);
+            let generator = FpGenerator::from_secret(master, vector.length).unwrap();
+            assert_eq!(generator.generate(vector.key), vector.expected);
+        }
+        assert!(matches!(
+            FpGenerator::from_secret(SecretString::from("test"), 40),
+            Err(FlowerPasswordError::InvalidLength(40))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "strength")]
+    fn test_new_checked() {
+        use crate::strength::DEFAULT_MIN_SCORE;
+
+        let error =
+            FpGenerator::new_checked("password1".to_string(), 16, DEFAULT_MIN_SCORE).unwrap_err();
+        let weak = match error {
+            CheckedError::WeakMaster(weak) => weak,
+            other => panic!("{:?}", other),
+        };
+        assert!(weak.strength.score < DEFAULT_MIN_SCORE);
+        assert!(!weak.strength.suggestions.is_empty());
+        assert_eq!(weak.min_score, DEFAULT_MIN_SCORE);
+
+        let strong = "vT9#qLm2$Xe8!rWz4&Kp";
+        let generator = FpGenerator::new_checked(strong.to_string(), 12, 4).unwrap();
+        assert_eq!(
+            generator.generate("github.com"),
+            fp_code(strong, "github.com", 12).unwrap()
+        );
+        assert_eq!(
+            FpGenerator::new_checked(strong.to_string(), 40, 4).unwrap_err(),
+            CheckedError::InvalidLength(40)
+        );
+        assert_eq!(
+            CheckedError::InvalidLength(40).to_string(),
+            "Length must be between 2 and 32, got: 40"
+        );
+    }
+
+    #[test]
+    fn test_validates_lengths() {
+        assert!(matches!(
+            FpGenerator::new("test".to_string(), 33),
+            Err(FlowerPasswordError::InvalidLength(33))
+        ));
+        let generator = FpGenerator::new("test".to_string(), 12).unwrap();
+        assert_eq!(generator.length(), 12);
+        assert!(matches!(
+            generator.generate_with_length("github.com", 1),
+            Err(FlowerPasswordError::InvalidLength(1))
+        ));
+    }
+
+    #[test]
+    fn test_generate_many_and_verify() {
+        let generator = FpGenerator::new("test".to_string(), 16).unwrap();
+        let passwords = generator.generate_many(["github.com", "gitlab.com"]);
+        assert_eq!(passwords[0], "D04175F7A9c7Ab4a");
+        assert_eq!(passwords[1], fp_code("test", "gitlab.com", 16).unwrap());
+        assert!(generator.verify("github.com", &passwords[0][..8]));
+        assert!(!generator.verify("gitlab.com", &passwords[0]));
+    }
+
+    #[test]
+    fn test_estimate_entropy() {
+        let generator = FpGenerator::new("test".to_string(), 14).unwrap();
+        assert_eq!(
+            generator.estimate_entropy(),
+            estimate_entropy(&FpOptions::classic(14).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_debug_leaves_master_out() {
+        let generator = FpGenerator::new("hunter2-master".to_string(), 16).unwrap();
+        assert_eq!(format!("{:?}", generator), "FpGenerator { length: 16, .. }");
+    }
+}
@@ -0,0 +1,251 @@
+//! Keeping secrets out of core dumps
+//!
+//! A process that crashes while holding the master password writes it to its
+//! core file. Frontends and agent servers call [`harden`] at startup, before
+//! reading the master password, to prevent that:
+//!
+//! - on Linux and Android, `prctl(PR_SET_DUMPABLE, 0)` marks the process as not
+//!   dumpable, which also keeps other processes of the same user from attaching
+//!   to it with `ptrace`;
+//! - on every Unix, `setrlimit(RLIMIT_CORE, 0)` limits core files to nothing, as
+//!   a fallback for kernels, or core patterns piping to a handler, that ignore
+//!   the first measure.
+//!
+//! With the `memlock` feature, `LockedBuf` pages are additionally marked
+//! `MADV_DONTDUMP` on Linux, so they are left out of any dump that still happens.
+//! Elsewhere these steps are no-ops and [`Hardening`] reports them unsupported.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::harden::{harden, Hardening};
+//!
+//! let hardening = harden();
+//! println!("{}", hardening); // "not dumpable: applied, core file limit 0: applied"
+//! if cfg!(unix) {
+//!     assert!(Hardening::current().no_core_files.is_applied());
+//! }
+//! ```
+
+use std::fmt;
+use std::io;
+
+/// The outcome of one hardening measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// The measure is in effect
+    Applied,
+    /// The measure is available but not in effect
+    NotApplied,
+    /// The OS refused it, with this raw OS error code
+    Failed(i32),
+    /// This platform has no such measure
+    Unsupported,
+}
+
+impl Measure {
+    /// Returns `true` if the measure is in effect
+    pub fn is_applied(self) -> bool {
+        self == Measure::Applied
+    }
+}
+
+impl fmt::Display for Measure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Measure::Applied => f.write_str("applied"),
+            Measure::NotApplied => f.write_str("not applied"),
+            Measure::Failed(code) => {
+                write!(f, "failed: {}", io::Error::from_raw_os_error(*code))
+            }
+            Measure::Unsupported => f.write_str("unsupported on this platform"),
+        }
+    }
+}
+
+/// The state of the process-wide core dump measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hardening {
+    /// `prctl(PR_SET_DUMPABLE, 0)`, on Linux and Android
+    pub not_dumpable: Measure,
+    /// `setrlimit(RLIMIT_CORE, 0)`, on Unix
+    pub no_core_files: Measure,
+}
+
+impl Hardening {
+    /// Reads which measures are in effect, without changing anything
+    ///
+    /// Diagnostics such as `fp doctor` report this; a failure to read a state is
+    /// reported as `Measure::Failed`.
+    pub fn current() -> Self {
+        Hardening {
+            not_dumpable: sys::is_not_dumpable(),
+            no_core_files: sys::has_no_core_files(),
+        }
+    }
+}
+
+impl fmt::Display for Hardening {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "not dumpable: {}, core file limit 0: {}",
+            self.not_dumpable, self.no_core_files
+        )
+    }
+}
+
+/// Applies every measure this platform supports and reports the outcome
+///
+/// Each measure is best effort: a refusal is reported, not returned as an error,
+/// and the others are still applied. Calling it again is harmless.
+pub fn harden() -> Hardening {
+    Hardening {
+        not_dumpable: sys::set_not_dumpable(),
+        no_core_files: sys::set_no_core_files(),
+    }
+}
+
+/// Outcome of a libc call returning 0 on success
+#[cfg(unix)]
+fn check(result: libc::c_int) -> Measure {
+    if result == 0 {
+        Measure::Applied
+    } else {
+        Measure::Failed(io::Error::last_os_error().raw_os_error().unwrap_or(0))
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::{check, Measure};
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn set_not_dumpable() -> Measure {
+        // SAFETY: PR_SET_DUMPABLE takes an integer argument and touches no memory
+        check(unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0 as libc::c_ulong) })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn is_not_dumpable() -> Measure {
+        // SAFETY: PR_GET_DUMPABLE takes no arguments
+        match unsafe { libc::prctl(libc::PR_GET_DUMPABLE) } {
+            0 => Measure::Applied,
+            -1 => check(-1),
+            _ => Measure::NotApplied,
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(super) fn set_not_dumpable() -> Measure {
+        Measure::Unsupported
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(super) fn is_not_dumpable() -> Measure {
+        Measure::Unsupported
+    }
+
+    pub(super) fn set_no_core_files() -> Measure {
+        let limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid rlimit for the duration of the call
+        check(unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) })
+    }
+
+    pub(super) fn has_no_core_files() -> Measure {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid, writable rlimit for the duration of the call
+        match check(unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) }) {
+            Measure::Applied if limit.rlim_cur != 0 => Measure::NotApplied,
+            measure => measure,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use super::Measure;
+
+    pub(super) fn set_not_dumpable() -> Measure {
+        Measure::Unsupported
+    }
+
+    pub(super) fn is_not_dumpable() -> Measure {
+        Measure::Unsupported
+    }
+
+    pub(super) fn set_no_core_files() -> Measure {
+        Measure::Unsupported
+    }
+
+    pub(super) fn has_no_core_files() -> Measure {
+        Measure::Unsupported
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harden() {
+        let hardening = harden();
+
+        // Containers with seccomp filters may refuse prctl
+        match hardening.not_dumpable {
+            Measure::Failed(code) => eprintln!("skipping, prctl refused here: {}", code),
+            measure if cfg!(any(target_os = "linux", target_os = "android")) => {
+                assert_eq!(measure, Measure::Applied);
+                assert_eq!(Hardening::current().not_dumpable, Measure::Applied);
+            }
+            measure => assert_eq!(measure, Measure::Unsupported),
+        }
+
+        if cfg!(unix) {
+            assert_eq!(hardening.no_core_files, Measure::Applied);
+        } else {
+            assert_eq!(hardening.no_core_files, Measure::Unsupported);
+        }
+        assert_eq!(harden(), hardening);
+        assert_eq!(Hardening::current().no_core_files, hardening.no_core_files);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_core_limit_is_zero() {
+        harden();
+        let mut limit = libc::rlimit {
+            rlim_cur: 1,
+            rlim_max: 1,
+        };
+        assert_eq!(unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) }, 0);
+        assert_eq!((limit.rlim_cur, limit.rlim_max), (0, 0));
+    }
+
+    #[test]
+    fn test_status_display() {
+        let hardening = Hardening {
+            not_dumpable: Measure::Applied,
+            no_core_files: Measure::NotApplied,
+        };
+        assert_eq!(
+            hardening.to_string(),
+            "not dumpable: applied, core file limit 0: not applied"
+        );
+        assert!(hardening.not_dumpable.is_applied());
+        assert!(!hardening.no_core_files.is_applied());
+        assert_eq!(
+            Measure::Unsupported.to_string(),
+            "unsupported on this platform"
+        );
+        // What Linux reports for an unknown prctl option
+        const EINVAL: i32 = 22;
+        assert!(Measure::Failed(EINVAL).to_string().starts_with("failed: "));
+    }
+}
@@ -0,0 +1,338 @@
+//! Have I Been Pwned breach check
+//!
+//! Asks the [Pwned Passwords](https://haveibeenpwned.com/API/v3#PwnedPasswords)
+//! range API whether a password appears in known breaches, without sending the
+//! password or its hash. Only the first [`PREFIX_LEN`] hex characters of its
+//! SHA-1 hash leave the process; the service answers with the suffix of every
+//! breached hash sharing that prefix, and the suffix is matched locally:
+//!
+//! ```text
+//! SHA-1("password") = 5BAA6 1E4C9B93F3F0682250B6CF8331B7EE68FD8
+//! GET /range/5BAA6                   ->
+//!                                    <-   1E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004
+//!                                    <-   ...
+//! ```
+//!
+//! Requests go through the [`RangeClient`] trait. [`check_pwned`] uses [`Curl`],
+//! which runs the `curl` program, so the crate needs no HTTP or TLS stack of its
+//! own; [`check_pwned_with`] takes any other client.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::hibp::{check_pwned_with, HibpError, RangeClient};
+//!
+//! struct Canned;
+//!
+//! impl RangeClient for Canned {
+//!     fn get_range(&mut self, prefix: &str) -> Result<String, HibpError> {
+//!         assert_eq!(prefix, "5BAA6");
+//!         Ok("1E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004\r\n".to_string())
+//!     }
+//! }
+//!
+//! assert_eq!(check_pwned_with(Canned, "password")?, Some(10434004));
+//! # Ok::<(), HibpError>(())
+//! ```
+
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Address of the range API, to which the hash prefix is appended
+pub const RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Number of hex characters of the SHA-1 hash sent to the service
+pub const PREFIX_LEN: usize = 5;
+
+/// Number of hex characters of the SHA-1 hash matched locally
+const SUFFIX_LEN: usize = 40 - PREFIX_LEN;
+
+/// Error type for breach checks
+#[derive(Debug)]
+pub enum HibpError {
+    /// Starting the HTTP client failed
+    Io(io::Error),
+    /// The request failed, with the client's explanation
+    Http(String),
+    /// Line `line` (from 1) of the response is not `SUFFIX:COUNT`
+    Malformed {
+        /// The offending line number
+        line: usize,
+    },
+}
+
+impl fmt::Display for HibpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HibpError::Io(e) => write!(f, "Could not run the HTTP client: {}", e),
+            HibpError::Http(message) => write!(f, "Pwned Passwords request failed: {}", message),
+            HibpError::Malformed { line } => write!(
+                f,
+                "Malformed Pwned Passwords response at line {}, expected SUFFIX:COUNT",
+                line
+            ),
+        }
+    }
+}
+
+impl Error for HibpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HibpError::Io(e) => Some(e),
+            HibpError::Http(_) | HibpError::Malformed { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for HibpError {
+    fn from(e: io::Error) -> Self {
+        HibpError::Io(e)
+    }
+}
+
+/// An HTTP client for the range API
+pub trait RangeClient {
+    /// Fetches the body of `RANGE_URL` followed by `prefix`
+    ///
+    /// `prefix` is always [`PREFIX_LEN`] uppercase hex characters.
+    fn get_range(&mut self, prefix: &str) -> Result<String, HibpError>;
+}
+
+impl<T: RangeClient + ?Sized> RangeClient for &mut T {
+    fn get_range(&mut self, prefix: &str) -> Result<String, HibpError> {
+        (**self).get_range(prefix)
+    }
+}
+
+/// A [`RangeClient`] running the `curl` program
+///
+/// It requests padded responses, so the response size does not reveal the
+/// prefix either. Only the prefix appears on curl's command line.
+#[derive(Debug, Clone)]
+pub struct Curl {
+    program: PathBuf,
+}
+
+impl Curl {
+    /// Uses `program` instead of `curl` from `PATH`
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Curl {
+            program: program.into(),
+        }
+    }
+}
+
+impl Default for Curl {
+    fn default() -> Self {
+        Curl::new("curl")
+    }
+}
+
+impl RangeClient for Curl {
+    fn get_range(&mut self, prefix: &str) -> Result<String, HibpError> {
+        let output = Command::new(&self.program)
+            .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+            .args(["--header", "Add-Padding: true"])
+            .arg(format!("{}{}", RANGE_URL, prefix))
+            .stdin(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(HibpError::Http(match stderr.trim() {
+                "" => output.status.to_string(),
+                message => message.to_string(),
+            }));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|_| HibpError::Http("response is not UTF-8".into()))
+    }
+}
+
+/// Checks `password` against Pwned Passwords with [`Curl`]
+///
+/// Returns how many times the password appears in breaches, or `None` if it
+/// does not.
+///
+/// # Errors
+///
+/// Returns `HibpError::Io` if curl cannot be started, `HibpError::Http` if the
+/// request fails, or `HibpError::Malformed` for a response that is not a list of
+/// `SUFFIX:COUNT` lines.
+pub fn check_pwned(password: &str) -> Result<Option<u64>, HibpError> {
+    check_pwned_with(Curl::default(), password)
+}
+
+/// Checks `password` against Pwned Passwords with `client`
+///
+/// # Errors
+///
+/// Returns any error from `client`, or `HibpError::Malformed` for a response
+/// that is not a list of `SUFFIX:COUNT` lines.
+pub fn check_pwned_with<C: RangeClient>(
+    mut client: C,
+    password: &str,
+) -> Result<Option<u64>, HibpError> {
+    let hash = sha1_hex(password);
+    let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+    let body = client.get_range(prefix)?;
+    find_suffix(&body, suffix)
+}
+
+/// Uppercase hex SHA-1 of `password`, as the service lists hashes
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+/// Looks `suffix` up in a range response, validating every line
+///
+/// Padding entries have a count of 0 and are reported as misses.
+fn find_suffix(body: &str, suffix: &str) -> Result<Option<u64>, HibpError> {
+    let mut found = None;
+    for (index, line) in body.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        let malformed = || HibpError::Malformed { line: index + 1 };
+        let (candidate, count) = line.split_once(':').ok_or_else(malformed)?;
+        if candidate.len() != SUFFIX_LEN || !candidate.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(malformed());
+        }
+        let count: u64 = count.parse().map_err(|_| malformed())?;
+        if count > 0 && candidate.eq_ignore_ascii_case(suffix) {
+            found = Some(count);
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-1 of "password", split at the prefix
+    const PREFIX: &str = "5BAA6";
+    const SUFFIX: &str = "1E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    /// Serves a canned body and records every prefix it was asked for
+    struct Fake {
+        body: &'static str,
+        requests: Vec<String>,
+    }
+
+    impl Fake {
+        fn new(body: &'static str) -> Self {
+            Fake {
+                body,
+                requests: Vec::new(),
+            }
+        }
+    }
+
+    impl RangeClient for Fake {
+        fn get_range(&mut self, prefix: &str) -> Result<String, HibpError> {
+            self.requests.push(prefix.to_string());
+            Ok(self.body.to_string())
+        }
+    }
+
+    #[test]
+    fn test_sha1_hex() {
+        assert_eq!(sha1_hex("password"), format!("{}{}", PREFIX, SUFFIX));
+        assert_eq!(sha1_hex(""), "DA39A3EE5E6B4B0D3255BFEF95601890AFD80709");
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut fake = Fake::new(
+            "003D68EB55068C33ACE09247EE4C639306B:3\r\n\
+             1E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004\r\n\
+             FFFFE3F1A2EE1C8CDB1C3E8FC0E4C0C1E50:0\r\n",
+        );
+        assert_eq!(
+            check_pwned_with(&mut fake, "password").unwrap(),
+            Some(10434004)
+        );
+
+        // Suffixes are matched regardless of case
+        let mut fake = Fake::new("1e4c9b93f3f0682250b6cf8331b7ee68fd8:2");
+        assert_eq!(check_pwned_with(&mut fake, "password").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_miss() {
+        let mut fake = Fake::new("003D68EB55068C33ACE09247EE4C639306B:3\r\n");
+        assert_eq!(check_pwned_with(&mut fake, "password").unwrap(), None);
+        let mut fake = Fake::new("");
+        assert_eq!(check_pwned_with(&mut fake, "password").unwrap(), None);
+
+        // Padding entries carry a count of 0
+        let mut fake = Fake::new("1E4C9B93F3F0682250B6CF8331B7EE68FD8:0\r\n");
+        assert_eq!(check_pwned_with(&mut fake, "password").unwrap(), None);
+    }
+
+    #[test]
+    fn test_malformed_responses() {
+        for (body, line) in [
+            ("<html>rate limited</html>", 1),
+            ("003D68EB55068C33ACE09247EE4C639306B:3\r\nno colon", 2),
+            ("1E4C9B93F3F0682250B6CF8331B7EE68FD8:many", 1),
+            ("1E4C9B93F3F0682250B6CF8331B7EE68FD8:-1", 1),
+            ("1E4C9B93F3F0682250B6CF8331B7EE68FD:3", 1),
+            ("1E4C9B93F3F0682250B6CF8331B7EE68FDZ:3", 1),
+            ("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:3", 1),
+        ] {
+            let error = check_pwned_with(Fake::new(body), "password").unwrap_err();
+            assert!(
+                matches!(error, HibpError::Malformed { line: l } if l == line),
+                "{:?}: {}",
+                body,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_only_the_prefix_is_sent() {
+        for password in ["password", "K3b2aF19c0dE5a7b", ""] {
+            let hash = sha1_hex(password);
+            let mut fake = Fake::new("");
+            check_pwned_with(&mut fake, password).unwrap();
+            assert_eq!(fake.requests, [&hash[..PREFIX_LEN]]);
+            let request = &fake.requests[0];
+            assert_eq!(request.len(), PREFIX_LEN);
+            assert!(request.bytes().all(|b| b.is_ascii_hexdigit()));
+            assert!(!request.contains(&hash[PREFIX_LEN..]));
+        }
+    }
+
+    #[test]
+    fn test_client_errors_pass_through() {
+        struct Down;
+
+        impl RangeClient for Down {
+            fn get_range(&mut self, _prefix: &str) -> Result<String, HibpError> {
+                Err(HibpError::Http("HTTP 503".into()))
+            }
+        }
+
+        let error = check_pwned_with(Down, "password").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Pwned Passwords request failed: HTTP 503"
+        );
+    }
+
+    #[test]
+    fn test_missing_curl() {
+        let mut curl = Curl::new("/nonexistent/curl");
+        assert!(matches!(curl.get_range(PREFIX), Err(HibpError::Io(_))));
+    }
+}
@@ -0,0 +1,306 @@
+//! JNI entry points for Android and other JVM hosts
+//!
+//! These back the native methods of `com.flowerpassword.Native`, whose source is in
+//! `bindings/android`. Java strings are read as UTF-16 rather than through
+//! `GetStringUTFChars`: the latter yields modified UTF-8, which encodes characters
+//! outside the Basic Multilingual Plane as two 3-byte surrogates and NUL as
+//! `C0 80`, neither of which is valid UTF-8.
+//!
+//! Errors are thrown as Java exceptions instead of returning null: an invalid
+//! length or an unpaired surrogate throws `IllegalArgumentException`, and a null
+//! argument throws `NullPointerException`.
+
+use crate::{fp_code, fp_verify};
+use flowerpassword_core::{MAX_LENGTH, MIN_LENGTH};
+use jni::objects::{JClass, JString};
+use jni::sys::{jboolean, jint, jstring, JNI_FALSE, JNI_TRUE};
+use jni::JNIEnv;
+use std::ptr;
+
+/// A Java exception to throw back to the caller
+#[derive(Debug, PartialEq, Eq)]
+struct Throw {
+    class: &'static str,
+    message: String,
+}
+
+impl Throw {
+    fn illegal_argument(message: String) -> Self {
+        Throw {
+            class: "java/lang/IllegalArgumentException",
+            message,
+        }
+    }
+
+    fn null_pointer(name: &str) -> Self {
+        Throw {
+            class: "java/lang/NullPointerException",
+            message: format!("Argument `{}` is null", name),
+        }
+    }
+
+    /// Throws this exception in `env`
+    fn raise(self, env: &mut JNIEnv) {
+        // If even throwing fails, the JVM already has an exception pending
+        let _ = env.throw_new(self.class, self.message);
+    }
+}
+
+/// Decodes UTF-16 code units from Java, pairing surrogates
+fn decode_utf16(units: &[u16], name: &str) -> Result<String, Throw> {
+    let mut decoded = String::with_capacity(units.len());
+    let mut index = 0;
+    for result in char::decode_utf16(units.iter().copied()) {
+        let ch = result.map_err(|_| {
+            Throw::illegal_argument(format!(
+                "Argument `{}` contains an unpaired surrogate at index {}",
+                name, index
+            ))
+        })?;
+        decoded.push(ch);
+        index += ch.len_utf16();
+    }
+    Ok(decoded)
+}
+
+/// Converts a Java `int` length, rejecting negative values like out-of-range ones
+fn length_from_jint(length: jint) -> Result<usize, Throw> {
+    usize::try_from(length).map_err(|_| {
+        Throw::illegal_argument(format!(
+            "Length must be between {} and {}, got: {}",
+            MIN_LENGTH, MAX_LENGTH, length
+        ))
+    })
+}
+
+/// Reads a Java string argument as UTF-16
+fn read_string(env: &JNIEnv, value: &JString, name: &str) -> Result<String, Throw> {
+    if value.is_null() {
+        return Err(Throw::null_pointer(name));
+    }
+
+    let raw_env = env.get_raw();
+    let raw_value = value.as_raw();
+    // SAFETY: `raw_env` is the live environment of this native call and `raw_value` a
+    // non-null local reference to a string, so its length and region can be read
+    let units = unsafe {
+        let functions = &**raw_env;
+        let length = (functions
+            .GetStringLength
+            .expect("JNI provides GetStringLength"))(raw_env, raw_value);
+        let mut units = vec![0u16; usize::try_from(length).unwrap_or(0)];
+        (functions
+            .GetStringRegion
+            .expect("JNI provides GetStringRegion"))(
+            raw_env,
+            raw_value,
+            0,
+            length,
+            units.as_mut_ptr(),
+        );
+        units
+    };
+    decode_utf16(&units, name)
+}
+
+/// Reads the arguments of `fpCode` and generates the password
+fn code(env: &JNIEnv, password: &JString, key: &JString, length: jint) -> Result<String, Throw> {
+    let password = read_string(env, password, "password")?;
+    let key = read_string(env, key, "key")?;
+    let length = length_from_jint(length)?;
+    fp_code(&password, &key, length).map_err(|error| Throw::illegal_argument(error.to_string()))
+}
+
+/// Reads the arguments of `fpVerify` and checks the candidate
+fn verify(
+    env: &JNIEnv,
+    password: &JString,
+    key: &JString,
+    candidate: &JString,
+) -> Result<bool, Throw> {
+    let password = read_string(env, password, "password")?;
+    let key = read_string(env, key, "key")?;
+    let candidate = read_string(env, candidate, "candidate")?;
+    Ok(fp_verify(&password, &key, &candidate))
+}
+
+/// `static native String fpCode(String password, String key, int length)`
+#[no_mangle]
+pub extern "system" fn Java_com_flowerpassword_Native_fpCode<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+    key: JString<'local>,
+    length: jint,
+) -> jstring {
+    match code(&env, &password, &key, length) {
+        Ok(generated) => match env.new_string(generated) {
+            Ok(generated) => generated.into_raw(),
+            // An OutOfMemoryError is already pending
+            Err(_) => ptr::null_mut(),
+        },
+        Err(throw) => {
+            throw.raise(&mut env);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// `static native boolean fpVerify(String password, String key, String candidate)`
+#[no_mangle]
+pub extern "system" fn Java_com_flowerpassword_Native_fpVerify<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+    key: JString<'local>,
+    candidate: JString<'local>,
+) -> jboolean {
+    match verify(&env, &password, &key, &candidate) {
+        Ok(true) => JNI_TRUE,
+        Ok(false) => JNI_FALSE,
+        Err(throw) => {
+            throw.raise(&mut env);
+            JNI_FALSE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_decode_bmp() {
+        assert_eq!(decode_utf16(&utf16("网站.com"), "key").unwrap(), "网站.com");
+        assert_eq!(decode_utf16(&[], "key").unwrap(), "");
+    }
+
+    #[test]
+    fn test_decode_surrogate_pair() {
+        // U+1F338 CHERRY BLOSSOM is the pair D83C DF38
+        let units = [0xd83c, 0xdf38, 0x2e, 0x78];
+        let key = decode_utf16(&units, "key").unwrap();
+        assert_eq!(key, "🌸.x");
+        assert_eq!(key.len(), 6);
+    }
+
+    #[test]
+    fn test_modified_utf8_is_not_utf8() {
+        // What GetStringUTFChars would return for U+1F338: each surrogate encoded separately
+        let modified = [0xed, 0xa0, 0xbc, 0xed, 0xbc, 0xb8];
+        assert_eq!(String::from_utf8_lossy(&modified), "\u{fffd}".repeat(6));
+        // ...and for NUL, which Java strings may contain
+        assert_eq!(String::from_utf8_lossy(&[0xc0, 0x80]), "\u{fffd}\u{fffd}");
+        assert_eq!(decode_utf16(&[0x61, 0, 0x62], "key").unwrap(), "a\0b");
+        assert_eq!(fp_code("test", "a\0b", 16).unwrap(), "K8071E9AE472F0d5");
+    }
+
+    #[test]
+    fn test_decode_unpaired_surrogates() {
+        assert_eq!(
+            decode_utf16(&[0xd83c], "key").unwrap_err(),
+            Throw::illegal_argument(
+                "Argument `key` contains an unpaired surrogate at index 0".to_string()
+            )
+        );
+        // A low surrogate first, after a character that itself takes two units
+        let error = decode_utf16(&[0xd83c, 0xdf38, 0xdf38], "password").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Argument `password` contains an unpaired surrogate at index 2"
+        );
+    }
+
+    #[test]
+    fn test_length_from_jint() {
+        assert_eq!(length_from_jint(16).unwrap(), 16);
+        let error = length_from_jint(-1).unwrap_err();
+        assert_eq!(error.class, "java/lang/IllegalArgumentException");
+        assert_eq!(error.message, "Length must be between 2 and 32, got: -1");
+    }
+
+    #[test]
+    fn test_throws_leave_inputs_out() {
+        use crate::redaction::{assert_redacted, SENTINEL};
+
+        // The sentinel master password followed by an unpaired surrogate
+        let mut units = utf16(SENTINEL);
+        units.push(0xd800);
+        let throws = [
+            decode_utf16(&units, "password").unwrap_err(),
+            length_from_jint(-1).unwrap_err(),
+            Throw::null_pointer("password"),
+        ];
+        let debug: Vec<String> = throws.iter().map(|throw| format!("{:?}", throw)).collect();
+        assert_redacted(throws.iter().map(|throw| throw.message.as_str()));
+        assert_redacted(debug.iter().map(String::as_str));
+    }
+
+    #[test]
+    fn test_null_pointer_throw() {
+        let throw = Throw::null_pointer("key");
+        assert_eq!(throw.class, "java/lang/NullPointerException");
+        assert_eq!(throw.message, "Argument `key` is null");
+    }
+
+    /// Runs `bindings/android`'s `NativeTest` against the built library
+    ///
+    /// Needs a JDK on `PATH`, `FP_JNI_TEST=1`, and the library built first with
+    /// `cargo rustc --lib --features jni --crate-type cdylib`.
+    #[test]
+    fn test_jvm_integration() {
+        if std::env::var_os("FP_JNI_TEST").map_or(true, |flag| flag != "1") {
+            return;
+        }
+
+        let library_dir = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let library = format!(
+            "{}flowerpassword{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        );
+        assert!(
+            library_dir.join(&library).exists(),
+            "{} not found in {}; run `cargo rustc --lib --features jni --crate-type cdylib` first",
+            library,
+            library_dir.display()
+        );
+
+        let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("bindings/android/src");
+        let classes = tempfile::tempdir().unwrap();
+        let status = Command::new("javac")
+            .arg("-encoding")
+            .arg("UTF-8")
+            .arg("-d")
+            .arg(classes.path())
+            .arg(sources.join("main/java/com/flowerpassword/Native.java"))
+            .arg(sources.join("test/java/com/flowerpassword/NativeTest.java"))
+            .status()
+            .expect("javac is installed");
+        assert!(status.success());
+
+        let output = Command::new("java")
+            .arg(format!("-Djava.library.path={}", library_dir.display()))
+            .arg("-cp")
+            .arg(classes.path())
+            .arg("com.flowerpassword.NativeTest")
+            .output()
+            .expect("java is installed");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ok");
+    }
+}
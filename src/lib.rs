@@ -12,187 +12,143 @@
 //! let password = fp_code("test", "github.com", 16).unwrap();
 //! assert_eq!(password, "D04175F7A9c7Ab4a");
 //! ```
+//!
+//! The algorithm itself lives in the `flowerpassword-core` crate, which this crate
+//! re-exports; depend on it directly to compile only the algorithm.
+//!
+//! # `no_std`
+//!
+//! The core API (`fp_code`, `fp_verify`, `entropy`, `policy` and `vectors`) needs
+//! only `alloc`; `FpGenerator` needs `std`.
+//! Disable the default `std` feature to build for targets without the standard
+//! library; every other feature requires `std`.
+//!
+//! # Schemes
+//!
+//! This crate always has the classic v1 scheme: `fp_code` and everything built
+//! on it, from `FpGenerator` to the configuration and the bindings. The
+//! `scheme-v2-sha256` feature adds `fp_code_v2` and `fp_verify_v2`, and
+//! `default-scheme-v2` makes `fp_code_default` and `DEFAULT_SCHEME` use v2.
+//! `fp_code_default`, `Scheme` and `DEFAULT_SCHEME` are always there.
+//!
+//! To compile MD5 out, depend on `flowerpassword-core` with only
+//! `scheme-v2-sha256`; its documentation lists the items of each combination.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "agent")]
+pub mod agent;
+mod batch;
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "blocklist")]
+pub mod blocklist;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+#[cfg(feature = "component")]
+mod component;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod entropy;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fido2")]
+pub mod fido2;
+#[cfg(feature = "std")]
+mod generator;
+#[cfg(feature = "hardening")]
+pub mod harden;
+#[cfg(feature = "hibp")]
+pub mod hibp;
+#[cfg(feature = "jni")]
+mod jvm;
+#[cfg(feature = "std")]
+mod master;
+#[cfg(feature = "memlock")]
+pub mod memlock;
+#[cfg(feature = "uniffi")]
+mod mobile;
+#[cfg(feature = "native-host")]
+pub mod native_host;
+#[cfg(feature = "pinentry")]
+pub mod pinentry;
+pub mod policy;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(test)]
+mod redaction;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(any(feature = "agent", feature = "config"))]
+mod scheme_name;
+#[cfg(feature = "secrecy")]
+mod secret;
+#[cfg(feature = "ssh-agent")]
+pub mod ssh_agent;
+#[cfg(feature = "strength")]
+pub mod strength;
+pub mod uri;
+pub mod vectors;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "yubikey")]
+pub mod yubikey;
+
+#[cfg(feature = "std")]
+pub use batch::fp_code_stream_to_writer;
+pub use batch::{
+    fp_code_batch, fp_code_batch_into, fp_code_batch_into_slice, fp_code_stream, BatchError,
+    BatchLayout,
+};
+#[cfg(feature = "rayon")]
+pub use batch::{fp_code_batch_par, fp_code_batch_par_chunked};
+#[cfg(feature = "heapless")]
+pub use flowerpassword_core::fp_code_heapless;
+pub use flowerpassword_core::{
+    fp_code, fp_code_default, fp_code_into, fp_fingerprint, fp_verify, FlowerPasswordError,
+    KeyStream, Scheme, DEFAULT_SCHEME,
+};
+#[cfg(feature = "tiny")]
+pub use flowerpassword_core::{fp_code_tiny, FpErrorCode};
+#[cfg(feature = "scheme-v2-sha256")]
+pub use flowerpassword_core::{fp_code_v2, fp_verify_v2};
+#[cfg(feature = "std")]
+pub use generator::FpGenerator;
+#[cfg(feature = "std")]
+pub use reader::{fp_code_from_reader, FpIoError};
+#[cfg(feature = "secrecy")]
+pub use secret::fp_code_secret_input;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
 
-use std::error::Error;
-use std::fmt;
-
-/// Minimum valid password length
-const MIN_LENGTH: usize = 2;
-
-/// Maximum valid password length
-const MAX_LENGTH: usize = 32;
-
-/// Magic string used for character transformation rules
-/// This is part of the Flower Password algorithm specification
-const MAGIC_STRING: &str = "sunlovesnow1990090127xykab";
-
-/// MD5 hash length in hexadecimal characters
-const MD5_HEX_LENGTH: usize = 32;
-
-/// Error type for Flower Password operations
-#[derive(Debug, Clone)]
-pub enum FlowerPasswordError {
-    /// Length parameter is outside the valid range
-    InvalidLength(usize),
-}
-
-impl fmt::Display for FlowerPasswordError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            FlowerPasswordError::InvalidLength(len) => {
-                write!(
-                    f,
-                    "Length must be between {} and {}, got: {}",
-                    MIN_LENGTH, MAX_LENGTH, len
-                )
-            }
-        }
-    }
-}
-
-impl Error for FlowerPasswordError {}
-
-/// Computes HMAC-MD5 hash
-///
-/// This function implements HMAC-MD5 to match the behavior of blueimp-md5's
-/// two-parameter mode used in the JavaScript implementation.
-///
-/// Special case: When key is empty, returns regular MD5 hash (not HMAC)
-/// to match blueimp-md5 behavior.
-fn hmac_md5(message: &str, key: &str) -> String {
-    // Special case: empty key returns regular MD5 (matching blueimp-md5 behavior)
-    if key.is_empty() {
-        let digest = md5::compute(message.as_bytes());
-        return format!("{:x}", digest);
-    }
-
-    let key_bytes = key.as_bytes();
-    let message_bytes = message.as_bytes();
-
-    const BLOCK_SIZE: usize = 64;
-
-    // Prepare the key
-    let mut key_block = [0u8; BLOCK_SIZE];
-    if key_bytes.len() > BLOCK_SIZE {
-        // If key is longer than block size, hash it
-        let digest = md5::compute(key_bytes);
-        key_block[..16].copy_from_slice(&digest.0);
-    } else {
-        // Otherwise, use key as-is (padded with zeros)
-        key_block[..key_bytes.len()].copy_from_slice(key_bytes);
-    }
-
-    // Create inner and outer padded keys
-    let mut ipad = [0x36u8; BLOCK_SIZE];
-    let mut opad = [0x5cu8; BLOCK_SIZE];
-
-    for i in 0..BLOCK_SIZE {
-        ipad[i] ^= key_block[i];
-        opad[i] ^= key_block[i];
-    }
-
-    // Compute inner hash: H(K XOR ipad, message)
-    let mut inner_data = Vec::with_capacity(BLOCK_SIZE + message_bytes.len());
-    inner_data.extend_from_slice(&ipad);
-    inner_data.extend_from_slice(message_bytes);
-    let inner_hash = md5::compute(&inner_data);
-
-    // Compute outer hash: H(K XOR opad, inner_hash)
-    let mut outer_data = Vec::with_capacity(BLOCK_SIZE + 16);
-    outer_data.extend_from_slice(&opad);
-    outer_data.extend_from_slice(&inner_hash.0);
-    let outer_hash = md5::compute(&outer_data);
-
-    // Return as hex string
-    format!("{:x}", outer_hash)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-/// Validates the length parameter
-fn validate_length(length: usize) -> Result<(), FlowerPasswordError> {
-    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
-        Err(FlowerPasswordError::InvalidLength(length))
-    } else {
-        Ok(())
+    #[test]
+    fn test_reexports_core() {
+        let derived: Result<String, FlowerPasswordError> =
+            flowerpassword_core::fp_code("test", "github.com", 16);
+        assert_eq!(derived.unwrap(), fp_code("test", "github.com", 16).unwrap());
     }
-}
 
-/// Core algorithm to generate Flower Password from MD5 hashes
-fn generate_password(rule_hash: &str, source_hash: &str, length: usize) -> String {
-    let rule_chars: Vec<char> = rule_hash.chars().collect();
-    let mut source_chars: Vec<char> = source_hash.chars().collect();
-
-    // Apply transformation rules: uppercase letters based on magic string pattern
-    for i in 0..MD5_HEX_LENGTH {
-        let ch = source_chars[i];
-        // Check if character is a letter (not a digit)
-        if !ch.is_ascii_digit() {
-            // Check if rule character exists in magic string
-            if MAGIC_STRING.contains(rule_chars[i]) {
-                source_chars[i] = ch.to_ascii_uppercase();
-            }
-        }
+    #[test]
+    #[cfg(feature = "scheme-v2-sha256")]
+    fn test_reexports_v2() {
+        let derived = fp_code_v2("test", "github.com", 16).unwrap();
+        assert_eq!(
+            derived,
+            flowerpassword_core::fp_code_v2("test", "github.com", 16).unwrap()
+        );
+        assert!(fp_verify_v2("test", "github.com", &derived));
+        assert_ne!(derived, fp_code("test", "github.com", 16).unwrap());
     }
 
-    let transformed_hash: String = source_chars.iter().collect();
-    let first_char = transformed_hash.chars().next().unwrap();
-
-    // Ensure first character is always a letter (replace with 'K' if it's a digit)
-    let first = if first_char.is_ascii_digit() {
-        'K'
-    } else {
-        first_char
-    };
-
-    // Build the final password
-    let mut result = String::with_capacity(length);
-    result.push(first);
-    result.push_str(&transformed_hash[1..length]);
-
-    result
-}
-
-/// Generates a Flower Password based on master password and key
-///
-/// # Arguments
-///
-/// * `password` - Master password
-/// * `key` - Domain or service identifier
-/// * `length` - Output password length (2-32 characters)
-///
-/// # Returns
-///
-/// Returns `Ok(String)` with the generated password, or `Err(FlowerPasswordError)`
-/// if the length is invalid.
-///
-/// # Errors
-///
-/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
-///
-/// # Example
-///
-/// ```
-/// use flowerpassword::fp_code;
-///
-/// let password = fp_code("test", "github.com", 16).unwrap();
-/// assert_eq!(password, "D04175F7A9c7Ab4a");
-/// ```
-pub fn fp_code(password: &str, key: &str, length: usize) -> Result<String, FlowerPasswordError> {
-    validate_length(length)?;
-
-    // Generate base MD5 hash from password and key using HMAC
-    let base_hash = hmac_md5(password, key);
-
-    // Generate rule and source hashes using fixed salts
-    let rule_hash = hmac_md5(&base_hash, "kise");
-    let source_hash = hmac_md5(&base_hash, "snow");
-
-    Ok(generate_password(&rule_hash, &source_hash, length))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
     // Basic functionality tests
     #[test]
     fn test_generate_password_with_length_16() {
@@ -464,6 +420,32 @@ mod tests {
         );
     }
 
+    // Verification
+    #[test]
+    fn test_verify_matching_candidate() {
+        assert!(fp_verify("password", "key", "K3A2a66Bf88b628c"));
+        assert!(fp_verify("password", "key", "K3"));
+        assert!(fp_verify(
+            "password",
+            "key",
+            "K3A2a66Bf88b628c2Cd7cDA9958f6b26"
+        ));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_candidate() {
+        assert!(!fp_verify("password", "key", "K3A2a66Bf88b628C"));
+        assert!(!fp_verify("password", "other", "K3A2a66Bf88b628c"));
+        assert!(!fp_verify("Password", "key", "K3A2a66Bf88b628c"));
+    }
+
+    #[test]
+    fn test_verify_rejects_invalid_lengths() {
+        assert!(!fp_verify("password", "key", ""));
+        assert!(!fp_verify("password", "key", "K"));
+        assert!(!fp_verify("password", "key", &"K".repeat(33)));
+    }
+
     // Additional verification tests
     #[test]
     fn test_first_char_is_always_letter() {
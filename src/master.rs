@@ -0,0 +1,88 @@
+//! The master password held by long-lived sessions
+//!
+//! With the `memlock` feature the password lives in a [`LockedBuf`], and the
+//! `String` it came from is wiped. Without it, it is kept in a `SecretString`,
+//! wiped on drop, with the `secrecy` feature, and as given otherwise.
+
+#[cfg(feature = "memlock")]
+use crate::memlock::{LockStatus, LockedBuf};
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, SecretString};
+
+/// A master password kept for the lifetime of a session or generator
+pub(crate) struct MasterPassword {
+    #[cfg(feature = "memlock")]
+    bytes: LockedBuf,
+    #[cfg(all(not(feature = "memlock"), feature = "secrecy"))]
+    password: SecretString,
+    #[cfg(not(any(feature = "memlock", feature = "secrecy")))]
+    password: String,
+}
+
+impl MasterPassword {
+    /// Takes ownership of `password`
+    #[cfg(feature = "memlock")]
+    pub(crate) fn new(mut password: String) -> Self {
+        let bytes = LockedBuf::from_slice(password.as_bytes());
+        zeroize::Zeroize::zeroize(&mut password);
+        MasterPassword { bytes }
+    }
+
+    /// Takes ownership of `password`
+    #[cfg(not(feature = "memlock"))]
+    pub(crate) fn new(password: String) -> Self {
+        #[cfg(feature = "secrecy")]
+        let password = SecretString::from(password);
+        MasterPassword { password }
+    }
+
+    /// Takes ownership of `password`, which is dropped and so wiped once copied
+    #[cfg(all(feature = "secrecy", feature = "memlock"))]
+    pub(crate) fn from_secret(password: SecretString) -> Self {
+        MasterPassword {
+            bytes: LockedBuf::from_slice(password.expose_secret().as_bytes()),
+        }
+    }
+
+    /// Takes ownership of `password`
+    #[cfg(all(feature = "secrecy", not(feature = "memlock")))]
+    pub(crate) fn from_secret(password: SecretString) -> Self {
+        MasterPassword { password }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        #[cfg(feature = "memlock")]
+        return std::str::from_utf8(&self.bytes).expect("copied from a String");
+        #[cfg(all(not(feature = "memlock"), feature = "secrecy"))]
+        return self.password.expose_secret();
+        #[cfg(not(any(feature = "memlock", feature = "secrecy")))]
+        return &self.password;
+    }
+
+    /// Whether the password's pages are locked in RAM
+    #[cfg(feature = "memlock")]
+    pub(crate) fn status(&self) -> LockStatus {
+        self.bytes.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_str() {
+        for password in ["", "test", "例え 🌸"] {
+            assert_eq!(MasterPassword::new(password.to_string()).as_str(), password);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "secrecy")]
+    fn test_from_secret() {
+        for password in ["", "test", "例え 🌸"] {
+            let master = MasterPassword::from_secret(SecretString::from(password));
+            assert_eq!(master.as_str(), password);
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//! Secrets in memory that is never swapped out
+//!
+//! A [`LockedBuf`] maps its own pages and asks the OS to keep them in RAM:
+//! `mlock` on Unix, `VirtualLock` on Windows. Locking is best effort. When the OS
+//! refuses, for instance because `RLIMIT_MEMLOCK` is exhausted, the buffer still
+//! works, [`LockedBuf::status`] says why it is not locked, and the bytes are wiped
+//! on drop either way. On Linux the pages are also marked `MADV_DONTDUMP`, so they
+//! are left out of core dumps (the `harden` module covers the process-wide measures).
+//!
+//! With the `memlock` feature, the agent's `AgentSession` and the
+//! Python `FpGenerator` keep their master password in a `LockedBuf`.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::memlock::LockedBuf;
+//!
+//! let master = LockedBuf::from_slice(b"test");
+//! assert_eq!(&*master, b"test");
+//! println!("master password memory: {}", master.status());
+//! ```
+
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use zeroize::Zeroize;
+
+/// Whether the pages of a [`LockedBuf`] are locked in RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockStatus {
+    /// The pages are locked and will not be written to swap
+    Locked,
+    /// The OS refused to lock them, with this raw OS error code
+    ///
+    /// On Linux this is usually `ENOMEM` or `EPERM` when `RLIMIT_MEMLOCK` is too low.
+    Refused(i32),
+    /// This platform has no way to lock memory
+    Unsupported,
+}
+
+impl fmt::Display for LockStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockStatus::Locked => f.write_str("locked in RAM"),
+            LockStatus::Refused(code) => {
+                write!(f, "not locked: {}", io::Error::from_raw_os_error(*code))
+            }
+            LockStatus::Unsupported => f.write_str("not locked: unsupported on this platform"),
+        }
+    }
+}
+
+/// A fixed-size byte buffer in its own locked pages, wiped on drop
+///
+/// The buffer is zero-initialised and cannot grow, so the secret is never copied
+/// to an unlocked reallocation. `Debug` shows only the length and lock status.
+pub struct LockedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    status: LockStatus,
+    excluded_from_dumps: bool,
+}
+
+// SAFETY: `LockedBuf` owns its mapping exclusively, like a `Box<[u8]>`
+unsafe impl Send for LockedBuf {}
+unsafe impl Sync for LockedBuf {}
+
+impl LockedBuf {
+    /// Allocates `len` zero bytes and tries to lock them in RAM
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pages cannot be mapped at all, as an allocation failure would.
+    pub fn new(len: usize) -> Self {
+        Self::with_locker(len, sys::lock)
+    }
+
+    /// Copies `bytes` into a new locked buffer
+    ///
+    /// The caller remains responsible for wiping its own copy.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut buf = Self::new(bytes.len());
+        buf.copy_from_slice(bytes);
+        buf
+    }
+
+    /// Maps the pages, then locks them with `lock`
+    fn with_locker(len: usize, lock: fn(NonNull<u8>, usize) -> LockStatus) -> Self {
+        // Always map at least one byte so that every buffer owns a real mapping
+        let ptr = sys::map(len.max(1)).unwrap_or_else(|| {
+            std::alloc::handle_alloc_error(std::alloc::Layout::array::<u8>(len.max(1)).unwrap())
+        });
+        LockedBuf {
+            ptr,
+            len,
+            status: lock(ptr, len.max(1)),
+            excluded_from_dumps: sys::exclude_from_dumps(ptr, len.max(1)),
+        }
+    }
+
+    /// Returns `true` if the pages are locked in RAM
+    pub fn is_locked(&self) -> bool {
+        self.status == LockStatus::Locked
+    }
+
+    /// Returns whether the pages are locked, and why not if they are not
+    pub fn status(&self) -> LockStatus {
+        self.status
+    }
+
+    /// Returns `true` if the pages are left out of core dumps
+    ///
+    /// Only Linux and Android support this, with `MADV_DONTDUMP`.
+    pub fn is_excluded_from_dumps(&self) -> bool {
+        self.excluded_from_dumps
+    }
+}
+
+impl Deref for LockedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping holds `len` initialised bytes for as long as `self`
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for LockedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: as in `deref`, and `&mut self` makes the access exclusive
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for LockedBuf {
+    /// Wipes the bytes, then unlocks and unmaps the pages
+    fn drop(&mut self) {
+        self.zeroize();
+        let len = self.len.max(1);
+        if self.is_locked() {
+            sys::unlock(self.ptr, len);
+        }
+        #[cfg(test)]
+        if tests::KEEP_MAPPED.with(|keep| keep.get()) {
+            return;
+        }
+        // SAFETY: `ptr` came from `sys::map(len)` and is not used again
+        unsafe { sys::unmap(self.ptr, len) };
+    }
+}
+
+impl fmt::Debug for LockedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LockedBuf")
+            .field("len", &self.len)
+            .field("status", &self.status)
+            .field("excluded_from_dumps", &self.excluded_from_dumps)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::LockStatus;
+    use std::io;
+    use std::ptr::{self, NonNull};
+
+    /// Maps `len` bytes of fresh zeroed pages
+    pub(super) fn map(len: usize) -> Option<NonNull<u8>> {
+        // SAFETY: an anonymous private mapping has no preconditions
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        NonNull::new(ptr.cast())
+    }
+
+    pub(super) fn lock(ptr: NonNull<u8>, len: usize) -> LockStatus {
+        // SAFETY: `ptr..ptr + len` lies in a mapping we own
+        if unsafe { libc::mlock(ptr.as_ptr().cast(), len) } == 0 {
+            LockStatus::Locked
+        } else {
+            LockStatus::Refused(io::Error::last_os_error().raw_os_error().unwrap_or(0))
+        }
+    }
+
+    pub(super) fn unlock(ptr: NonNull<u8>, len: usize) {
+        // SAFETY: as in `lock`
+        unsafe { libc::munlock(ptr.as_ptr().cast(), len) };
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn exclude_from_dumps(ptr: NonNull<u8>, len: usize) -> bool {
+        // SAFETY: as in `lock`; the advice changes only what dumps contain
+        unsafe { libc::madvise(ptr.as_ptr().cast(), len, libc::MADV_DONTDUMP) == 0 }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(super) fn exclude_from_dumps(_ptr: NonNull<u8>, _len: usize) -> bool {
+        false
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must come from `map(len)` and not be used afterwards.
+    pub(super) unsafe fn unmap(ptr: NonNull<u8>, len: usize) {
+        libc::munmap(ptr.as_ptr().cast(), len);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::LockStatus;
+    use std::io;
+    use std::ptr::{self, NonNull};
+    use windows_sys::Win32::System::Memory::{
+        VirtualAlloc, VirtualFree, VirtualLock, VirtualUnlock, MEM_COMMIT, MEM_RELEASE,
+        MEM_RESERVE, PAGE_READWRITE,
+    };
+
+    /// Commits `len` bytes of fresh zeroed pages
+    pub(super) fn map(len: usize) -> Option<NonNull<u8>> {
+        // SAFETY: a new allocation at an address of the system's choosing
+        let ptr =
+            unsafe { VirtualAlloc(ptr::null(), len, MEM_COMMIT | MEM_RESERVE, PAGE_READWRITE) };
+        NonNull::new(ptr.cast())
+    }
+
+    pub(super) fn lock(ptr: NonNull<u8>, len: usize) -> LockStatus {
+        // SAFETY: `ptr..ptr + len` lies in an allocation we own
+        if unsafe { VirtualLock(ptr.as_ptr().cast(), len) } != 0 {
+            LockStatus::Locked
+        } else {
+            LockStatus::Refused(io::Error::last_os_error().raw_os_error().unwrap_or(0))
+        }
+    }
+
+    pub(super) fn unlock(ptr: NonNull<u8>, len: usize) {
+        // SAFETY: as in `lock`
+        unsafe { VirtualUnlock(ptr.as_ptr().cast(), len) };
+    }
+
+    pub(super) fn exclude_from_dumps(_ptr: NonNull<u8>, _len: usize) -> bool {
+        false
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must come from `map(len)` and not be used afterwards.
+    pub(super) unsafe fn unmap(ptr: NonNull<u8>, _len: usize) {
+        VirtualFree(ptr.as_ptr().cast(), 0, MEM_RELEASE);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use super::LockStatus;
+    use std::alloc::{alloc_zeroed, dealloc, Layout};
+    use std::ptr::NonNull;
+
+    pub(super) fn map(len: usize) -> Option<NonNull<u8>> {
+        // SAFETY: `len` is never zero
+        NonNull::new(unsafe { alloc_zeroed(Layout::array::<u8>(len).ok()?) })
+    }
+
+    pub(super) fn lock(_ptr: NonNull<u8>, _len: usize) -> LockStatus {
+        LockStatus::Unsupported
+    }
+
+    pub(super) fn unlock(_ptr: NonNull<u8>, _len: usize) {}
+
+    pub(super) fn exclude_from_dumps(_ptr: NonNull<u8>, _len: usize) -> bool {
+        false
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must come from `map(len)` and not be used afterwards.
+    pub(super) unsafe fn unmap(ptr: NonNull<u8>, len: usize) {
+        dealloc(ptr.as_ptr(), Layout::array::<u8>(len).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Leaks the mapping on drop, so a test can check what was left in it
+        pub(super) static KEEP_MAPPED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[test]
+    fn test_allocates_and_locks() {
+        let mut buf = LockedBuf::new(100);
+        assert_eq!(&*buf, &[0; 100][..]);
+        buf[..4].copy_from_slice(b"test");
+        assert_eq!(&buf[..5], b"test\0");
+
+        match buf.status() {
+            LockStatus::Locked => assert!(buf.is_locked()),
+            // CI runners with a small RLIMIT_MEMLOCK refuse; the buffer still works
+            LockStatus::Refused(code) => {
+                eprintln!("mlock refused here: {}", buf.status());
+                assert!(!buf.is_locked());
+                assert_ne!(code, 0);
+            }
+            LockStatus::Unsupported => assert!(!buf.is_locked()),
+        }
+
+        let buf = LockedBuf::from_slice(b"hunter2");
+        assert_eq!(&*buf, b"hunter2");
+        assert!(LockedBuf::new(0).is_empty());
+        assert!(LockedBuf::from_slice(&[7; 10_000]).iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_locked_pages_are_counted() {
+        fn locked_kib() -> u64 {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            let line = status.lines().find(|l| l.starts_with("VmLck:")).unwrap();
+            line.split_whitespace().nth(1).unwrap().parse().unwrap()
+        }
+
+        let buf = LockedBuf::new(1 << 20);
+        if !buf.is_locked() {
+            eprintln!("skipping, mlock refused here: {}", buf.status());
+            return;
+        }
+        // Other tests may hold locked buffers too, so only a lower bound holds
+        assert!(locked_kib() >= 1024);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_excluded_from_dumps() {
+        fn dont_dump_flag(address: usize) -> bool {
+            // Find the mapping in smaps and look for the `dd` VmFlag
+            let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap();
+            let mut inside = false;
+            for line in smaps.lines() {
+                if let Some((range, _)) = line.split_once(' ') {
+                    if let Some((start, end)) = range.split_once('-') {
+                        if let (Ok(start), Ok(end)) = (
+                            usize::from_str_radix(start, 16),
+                            usize::from_str_radix(end, 16),
+                        ) {
+                            inside = (start..end).contains(&address);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(flags) = line.strip_prefix("VmFlags:") {
+                    if inside {
+                        return flags.split_whitespace().any(|flag| flag == "dd");
+                    }
+                }
+            }
+            panic!("no mapping at {:#x}", address);
+        }
+
+        let buf = LockedBuf::from_slice(b"hunter2");
+        assert!(buf.is_excluded_from_dumps());
+        assert!(dont_dump_flag(buf.ptr.as_ptr() as usize));
+    }
+
+    #[test]
+    fn test_refused_lock_degrades() {
+        // What Linux reports when RLIMIT_MEMLOCK is exhausted
+        const ENOMEM: i32 = 12;
+        let mut buf = LockedBuf::with_locker(32, |_, _| LockStatus::Refused(ENOMEM));
+        assert!(!buf.is_locked());
+        assert_eq!(buf.status(), LockStatus::Refused(ENOMEM));
+        assert!(buf.status().to_string().starts_with("not locked: "));
+        buf.copy_from_slice(&[1; 32]);
+        assert_eq!(&*buf, &[1; 32][..]);
+        // Dropping must not unlock pages that were never locked
+        drop(buf);
+
+        assert_eq!(
+            LockStatus::Unsupported.to_string(),
+            "not locked: unsupported on this platform"
+        );
+        assert_eq!(LockStatus::Locked.to_string(), "locked in RAM");
+    }
+
+    #[test]
+    fn test_wiped_on_drop() {
+        let buf = LockedBuf::from_slice(b"hunter2-master");
+        let (ptr, len) = (buf.ptr, buf.len);
+        KEEP_MAPPED.with(|keep| keep.set(true));
+        drop(buf);
+        KEEP_MAPPED.with(|keep| keep.set(false));
+
+        // SAFETY: the mapping was deliberately leaked by the drop above
+        let left = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), len) };
+        assert_eq!(left, &[0; 14][..]);
+        unsafe { sys::unmap(ptr, len) };
+    }
+
+    #[test]
+    fn test_debug_hides_contents() {
+        let buf = LockedBuf::from_slice(b"hunter2");
+        let debug = format!("{:?}", buf);
+        assert!(debug.starts_with("LockedBuf { len: 7, status: "));
+        assert!(debug.contains("excluded_from_dumps: "));
+        assert!(!debug.contains("hunter2"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Kotlin and Swift bindings via UniFFI
+//!
+//! With the `uniffi` feature the library exports `fp_code` and `fp_verify` through
+//! UniFFI's proc-macro interface. Bindings are generated from the built library by
+//! the workspace's `uniffi-bindgen` crate, where `FpError` becomes a sealed
+//! exception class in Kotlin and an `Error` enum in Swift:
+//!
+//! ```kotlin
+//! try {
+//!     fpCode("test", "github.com", 40u)
+//! } catch (e: FpException.InvalidLength) {
+//!     e.length // 40
+//! }
+//! ```
+
+use crate::FlowerPasswordError;
+use flowerpassword_core::{MAX_LENGTH, MIN_LENGTH};
+use std::error::Error;
+use std::fmt;
+
+/// Error type exposed to foreign languages, mirroring `FlowerPasswordError`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum FpError {
+    /// Length parameter is outside the valid range
+    InvalidLength {
+        /// The rejected length
+        length: u64,
+    },
+}
+
+impl fmt::Display for FpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FpError::InvalidLength { length } => write!(
+                f,
+                "Length must be between {} and {}, got: {}",
+                MIN_LENGTH, MAX_LENGTH, length
+            ),
+        }
+    }
+}
+
+impl Error for FpError {}
+
+impl From<FlowerPasswordError> for FpError {
+    fn from(error: FlowerPasswordError) -> Self {
+        match error {
+            FlowerPasswordError::InvalidLength(length) => FpError::InvalidLength {
+                length: length as u64,
+            },
+        }
+    }
+}
+
+/// Generates a Flower Password based on master password and key
+///
+/// # Errors
+///
+/// Returns `FpError::InvalidLength` if length is not between 2 and 32.
+#[uniffi::export]
+pub fn fp_code(password: String, key: String, length: u32) -> Result<String, FpError> {
+    Ok(crate::fp_code(&password, &key, length as usize)?)
+}
+
+/// Checks whether `candidate` is the Flower Password for master password and key
+#[uniffi::export]
+pub fn fp_verify(password: String, key: String, candidate: String) -> bool {
+    crate::fp_verify(&password, &key, &candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fp_code() {
+        assert_eq!(
+            fp_code("test".into(), "github.com".into(), 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+        assert_eq!(
+            fp_code("密码".into(), "网站.com".into(), 16).unwrap(),
+            "KFF7FEa7928bAAAa"
+        );
+    }
+
+    #[test]
+    fn test_fp_code_invalid_length() {
+        assert_eq!(
+            fp_code("test".into(), "github.com".into(), 33).unwrap_err(),
+            FpError::InvalidLength { length: 33 }
+        );
+        assert_eq!(
+            fp_code("test".into(), "github.com".into(), 1).unwrap_err(),
+            FpError::InvalidLength { length: 1 }
+        );
+    }
+
+    #[test]
+    fn test_fp_verify() {
+        let candidate = "D04175F7A9c7Ab4a".to_string();
+        assert!(fp_verify(
+            "test".into(),
+            "github.com".into(),
+            candidate.clone()
+        ));
+        assert!(!fp_verify("test".into(), "gitlab.com".into(), candidate));
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        for length in [0, 1, 33, usize::from(u16::MAX)] {
+            let error = FlowerPasswordError::InvalidLength(length);
+            let converted = FpError::from(error.clone());
+            assert_eq!(
+                converted,
+                FpError::InvalidLength {
+                    length: length as u64
+                }
+            );
+            assert_eq!(converted.to_string(), error.to_string());
+        }
+    }
+}
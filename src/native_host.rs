@@ -0,0 +1,651 @@
+//! Browser native messaging host
+//!
+//! Chrome and Firefox extensions talk to native programs over stdio using the
+//! [native messaging] protocol: each message is a 4-byte length in native byte
+//! order (little-endian on every platform the browsers support) followed by that
+//! many bytes of JSON.
+//!
+//! ```text
+//! {"cmd":"derive","origin":"https://github.com","length":16}   ->   {"result":"password","password":"..."}
+//! ```
+//!
+//! The origin is mapped to a key with [`origin_to_key`], so the extension never
+//! chooses the key directly. The master password never comes from the extension
+//! either: [`NativeHost`] is created with one that the host obtained itself, and
+//! requests that carry any other field are rejected.
+//!
+//! [native messaging]: https://developer.chrome.com/docs/extensions/develop/concepts/native-messaging
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::native_host::{read_message, write_message, NativeHost, Request, Response};
+//!
+//! let mut host = NativeHost::new("test".to_string());
+//!
+//! // Extension side
+//! let mut wire = Vec::new();
+//! let request = Request::Derive { origin: "https://github.com".to_string(), length: 16 };
+//! write_message(&mut wire, &request).unwrap();
+//!
+//! // Host side
+//! let request: Request = read_message(&mut wire.as_slice()).unwrap().unwrap();
+//! let response = host.handle(request);
+//! assert_eq!(response, Response::Password { password: "D04175F7A9c7Ab4a".to_string() });
+//! ```
+
+use crate::config::registrable_domain;
+use crate::fp_code;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Largest message accepted or sent, in bytes
+///
+/// This is the limit browsers apply to messages from a native host.
+pub const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+/// Name of the native messaging host, as extensions pass it to `connectNative`
+pub const HOST_NAME: &str = "com.flowerpassword.native_host";
+
+/// Error type for native messaging operations
+#[derive(Debug)]
+pub enum NativeHostError {
+    /// Reading or writing the transport failed
+    Io(io::Error),
+    /// A message is larger than [`MAX_MESSAGE_LEN`]
+    MessageTooLarge(usize),
+    /// The message body is not a valid request
+    Malformed(String),
+    /// The origin is not an `http` or `https` web origin
+    UnsupportedOrigin(String),
+}
+
+impl fmt::Display for NativeHostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NativeHostError::Io(e) => write!(f, "Native messaging failed: {}", e),
+            NativeHostError::MessageTooLarge(len) => write!(
+                f,
+                "Message of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_MESSAGE_LEN
+            ),
+            NativeHostError::Malformed(message) => {
+                write!(f, "Malformed native message: {}", message)
+            }
+            NativeHostError::UnsupportedOrigin(origin) => {
+                write!(
+                    f,
+                    "Unsupported origin `{}`, expected an http(s) origin",
+                    origin
+                )
+            }
+        }
+    }
+}
+
+impl Error for NativeHostError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NativeHostError::Io(e) => Some(e),
+            NativeHostError::MessageTooLarge(_)
+            | NativeHostError::Malformed(_)
+            | NativeHostError::UnsupportedOrigin(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for NativeHostError {
+    fn from(e: io::Error) -> Self {
+        NativeHostError::Io(e)
+    }
+}
+
+/// Request sent from the extension
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Request {
+    /// Derive the password for the site at `origin`
+    Derive {
+        /// Web origin of the page, such as `https://github.com`
+        origin: String,
+        /// Output password length
+        length: usize,
+    },
+}
+
+/// Response sent back to the extension
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum Response {
+    /// Derived password for a `derive` request
+    Password {
+        /// The generated password
+        password: String,
+    },
+    /// The request could not be served
+    Error {
+        /// Description of the problem
+        message: String,
+    },
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Password { .. } => f
+                .debug_struct("Password")
+                .field("password", &"****")
+                .finish(),
+            Response::Error { message } => {
+                f.debug_struct("Error").field("message", message).finish()
+            }
+        }
+    }
+}
+
+/// Maps a web origin to the key passwords are derived for
+///
+/// The key is the registrable domain, so `https://accounts.google.com` and
+/// `https://mail.google.com` both give `google.com`, like imported browser
+/// passwords (see [`registrable_domain`]).
+///
+/// # Errors
+///
+/// Returns `NativeHostError::UnsupportedOrigin` unless `origin` is an `http` or
+/// `https` origin with a host, such as for `chrome-extension://` or `file://`
+/// origins.
+pub fn origin_to_key(origin: &str) -> Result<String, NativeHostError> {
+    let unsupported = || NativeHostError::UnsupportedOrigin(origin.to_string());
+    let (scheme, rest) = origin.split_once("://").ok_or_else(unsupported)?;
+    if !scheme.eq_ignore_ascii_case("https") && !scheme.eq_ignore_ascii_case("http") {
+        return Err(unsupported());
+    }
+    // An origin has no path; allow only the trailing slash some callers add
+    if rest.trim_end_matches('/').contains(['/', '?', '#', '@']) {
+        return Err(unsupported());
+    }
+    registrable_domain(origin).ok_or_else(unsupported)
+}
+
+/// Host-side state: the master password obtained by the host itself
+pub struct NativeHost {
+    master: String,
+}
+
+impl NativeHost {
+    /// Creates a host deriving with `master`
+    ///
+    /// Obtain `master` from the keyring or an agent, never from the extension.
+    pub fn new(master: String) -> Self {
+        NativeHost { master }
+    }
+
+    /// Serves a single request
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Derive { origin, length } => {
+                let derived = origin_to_key(&origin)
+                    .map_err(|e| e.to_string())
+                    .and_then(|key| fp_code(&self.master, &key, length).map_err(|e| e.to_string()));
+                match derived {
+                    Ok(password) => Response::Password { password },
+                    Err(message) => Response::Error { message },
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for NativeHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeHost").finish_non_exhaustive()
+    }
+}
+
+/// Writes a message with its length prefix
+///
+/// # Errors
+///
+/// Returns `NativeHostError::MessageTooLarge` if the encoded message exceeds
+/// [`MAX_MESSAGE_LEN`], or `NativeHostError::Io` if writing fails.
+pub fn write_message<W: Write, T: Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> Result<(), NativeHostError> {
+    let body =
+        serde_json::to_vec(message).map_err(|e| NativeHostError::Malformed(e.to_string()))?;
+    if body.len() > MAX_MESSAGE_LEN {
+        return Err(NativeHostError::MessageTooLarge(body.len()));
+    }
+
+    writer.write_all(&(body.len() as u32).to_ne_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a single message and decodes it
+///
+/// Returns `Ok(None)` if the browser closed stdin between messages, which is how
+/// it asks the host to exit.
+///
+/// # Errors
+///
+/// Returns `NativeHostError::Io` if reading fails or the stream ends mid-message,
+/// `NativeHostError::MessageTooLarge` without reading the body of oversized
+/// messages, and `NativeHostError::Malformed` if the body is not a valid message.
+pub fn read_message<R: Read, T: DeserializeOwned>(
+    reader: &mut R,
+) -> Result<Option<T>, NativeHostError> {
+    let mut header = [0u8; 4];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let len = u32::from_ne_bytes(header) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(NativeHostError::MessageTooLarge(len));
+    }
+
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| NativeHostError::Malformed(e.to_string()))
+}
+
+/// A browser that can launch the native host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    /// Google Chrome
+    Chrome,
+    /// Chromium
+    Chromium,
+    /// Mozilla Firefox
+    Firefox,
+}
+
+impl Browser {
+    /// Returns the per-user directory the browser reads host manifests from
+    ///
+    /// Returns `None` on Windows, where manifests are registered in the registry
+    /// instead of found by directory.
+    pub fn manifest_dir(self, home: &Path) -> Option<PathBuf> {
+        let relative = if cfg!(target_os = "macos") {
+            match self {
+                Browser::Chrome => "Library/Application Support/Google/Chrome/NativeMessagingHosts",
+                Browser::Chromium => "Library/Application Support/Chromium/NativeMessagingHosts",
+                Browser::Firefox => "Library/Application Support/Mozilla/NativeMessagingHosts",
+            }
+        } else if cfg!(unix) {
+            match self {
+                Browser::Chrome => ".config/google-chrome/NativeMessagingHosts",
+                Browser::Chromium => ".config/chromium/NativeMessagingHosts",
+                Browser::Firefox => ".mozilla/native-messaging-hosts",
+            }
+        } else {
+            return None;
+        };
+        Some(home.join(relative))
+    }
+
+    /// Builds the host manifest allowing `extension_ids` to launch `binary`
+    ///
+    /// Chromium-based browsers take extension IDs, which become
+    /// `chrome-extension://<id>/` origins; Firefox takes add-on IDs as they are.
+    pub fn manifest(self, binary: &Path, extension_ids: &[&str]) -> serde_json::Value {
+        let mut manifest = serde_json::json!({
+            "name": HOST_NAME,
+            "description": "Flower Password",
+            "path": binary,
+            "type": "stdio",
+        });
+        match self {
+            Browser::Chrome | Browser::Chromium => {
+                let origins: Vec<String> = extension_ids
+                    .iter()
+                    .map(|id| format!("chrome-extension://{}/", id))
+                    .collect();
+                manifest["allowed_origins"] = origins.into();
+            }
+            Browser::Firefox => manifest["allowed_extensions"] = extension_ids.into(),
+        }
+        manifest
+    }
+}
+
+/// Writes the host manifest for `browser` under the user's home directory
+///
+/// Returns the path of the manifest, `<HOST_NAME>.json` in
+/// [`Browser::manifest_dir`]. `binary` must be absolute, since browsers launch the
+/// host without a search path.
+///
+/// # Errors
+///
+/// Returns an `InvalidInput` error if `binary` is relative, an `Unsupported` error
+/// on platforms without a manifest directory, or any error from writing the file.
+pub fn install_manifest(
+    browser: Browser,
+    home: &Path,
+    binary: &Path,
+    extension_ids: &[&str],
+) -> io::Result<PathBuf> {
+    if !binary.is_absolute() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Host binary path must be absolute: {}", binary.display()),
+        ));
+    }
+    let dir = browser.manifest_dir(home).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Native messaging hosts are registered in the Windows registry",
+        )
+    })?;
+
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", HOST_NAME));
+    let manifest = browser.manifest(binary, extension_ids);
+    let mut json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    json.push('\n');
+    fs::write(&path, json)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader that hands out at most one byte per call
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn derive(origin: &str, length: usize) -> Request {
+        Request::Derive {
+            origin: origin.to_string(),
+            length,
+        }
+    }
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = (body.len() as u32).to_ne_bytes().to_vec();
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let json = serde_json::to_string(&derive("https://github.com", 16)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"cmd":"derive","origin":"https://github.com","length":16}"#
+        );
+        let json = serde_json::to_string(&Response::Error {
+            message: "nope".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"result":"error","message":"nope"}"#);
+    }
+
+    #[test]
+    fn test_message_layout() {
+        let mut wire = Vec::new();
+        write_message(&mut wire, &derive("https://a.io", 2)).unwrap();
+        let body = br#"{"cmd":"derive","origin":"https://a.io","length":2}"#;
+        assert_eq!(wire, frame(body));
+        if cfg!(target_endian = "little") {
+            assert_eq!(&wire[..4], &[body.len() as u8, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn test_messages_back_to_back() {
+        let mut wire = Vec::new();
+        write_message(&mut wire, &derive("https://github.com", 16)).unwrap();
+        write_message(&mut wire, &derive("https://gitlab.com", 12)).unwrap();
+
+        let mut reader = wire.as_slice();
+        let first: Option<Request> = read_message(&mut reader).unwrap();
+        let second: Option<Request> = read_message(&mut reader).unwrap();
+        let end: Option<Request> = read_message(&mut reader).unwrap();
+        assert_eq!(first, Some(derive("https://github.com", 16)));
+        assert_eq!(second, Some(derive("https://gitlab.com", 12)));
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn test_message_split_across_reads() {
+        let mut wire = Vec::new();
+        write_message(&mut wire, &derive("https://github.com", 16)).unwrap();
+        let request: Option<Request> = read_message(&mut Trickle(&wire)).unwrap();
+        assert_eq!(request, Some(derive("https://github.com", 16)));
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        let wire = frame(br#"{"cmd":"derive"}"#);
+        for cut in [2, 4, wire.len() - 1] {
+            let result: Result<Option<Request>, _> = read_message(&mut Trickle(&wire[..cut]));
+            assert!(
+                matches!(result, Err(NativeHostError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof),
+                "cut at {}",
+                cut
+            );
+        }
+    }
+
+    #[test]
+    fn test_oversized_message_is_rejected_before_reading() {
+        // Only the header is present: the body must not be read or allocated
+        let header = ((MAX_MESSAGE_LEN + 1) as u32).to_ne_bytes();
+        let result: Result<Option<Request>, _> = read_message(&mut header.as_slice());
+        assert!(matches!(
+            result,
+            Err(NativeHostError::MessageTooLarge(len)) if len == MAX_MESSAGE_LEN + 1
+        ));
+
+        let result: Result<Option<Request>, _> = read_message(&mut [0xff; 4].as_slice());
+        assert!(matches!(result, Err(NativeHostError::MessageTooLarge(_))));
+
+        let huge = "x".repeat(MAX_MESSAGE_LEN);
+        let error = write_message(&mut Vec::new(), &huge).unwrap_err();
+        assert!(matches!(error, NativeHostError::MessageTooLarge(_)));
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        for body in [
+            &b"not json"[..],
+            br#"{"cmd":"unlock"}"#,
+            br#"{"cmd":"derive","origin":"https://github.com"}"#,
+            // The extension cannot supply the master password or the key
+            br#"{"cmd":"derive","origin":"https://github.com","length":16,"master":"x"}"#,
+            br#"{"cmd":"derive","origin":"https://github.com","length":16,"key":"x"}"#,
+        ] {
+            let result: Result<Option<Request>, _> = read_message(&mut frame(body).as_slice());
+            assert!(
+                matches!(result, Err(NativeHostError::Malformed(_))),
+                "{}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn test_origin_to_key() {
+        for (origin, key) in [
+            ("https://github.com", "github.com"),
+            ("https://github.com/", "github.com"),
+            ("https://accounts.google.com", "google.com"),
+            ("HTTPS://WWW.GitHub.COM", "github.com"),
+            ("https://secure.examplebank.co.uk:8443", "examplebank.co.uk"),
+            ("http://localhost:8080", "localhost"),
+            ("http://192.168.1.1", "192.168.1.1"),
+        ] {
+            assert_eq!(origin_to_key(origin).unwrap(), key, "{}", origin);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_origins() {
+        for origin in [
+            "",
+            "github.com",
+            "chrome-extension://abcdefghijklmnop",
+            "moz-extension://0f1e2d3c",
+            "file:///home/user/page.html",
+            "https://",
+            "https://github.com/login",
+            "https://github.com?q=1",
+            "https://user@github.com",
+            "null",
+        ] {
+            let error = origin_to_key(origin).unwrap_err();
+            assert!(
+                matches!(&error, NativeHostError::UnsupportedOrigin(o) if o == origin),
+                "{}",
+                origin
+            );
+        }
+        assert_eq!(
+            origin_to_key("file:///x").unwrap_err().to_string(),
+            "Unsupported origin `file:///x`, expected an http(s) origin"
+        );
+    }
+
+    #[test]
+    fn test_handle_derive() {
+        let mut host = NativeHost::new("test".to_string());
+        assert_eq!(
+            host.handle(derive("https://www.github.com", 16)),
+            Response::Password {
+                password: fp_code("test", "github.com", 16).unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_errors() {
+        let mut host = NativeHost::new("test".to_string());
+        assert_eq!(
+            host.handle(derive("https://github.com", 40)),
+            Response::Error {
+                message: "Length must be between 2 and 32, got: 40".to_string()
+            }
+        );
+        assert_eq!(
+            host.handle(derive("chrome-extension://abc", 16)),
+            Response::Error {
+                message: "Unsupported origin `chrome-extension://abc`, expected an http(s) origin"
+                    .to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let host = NativeHost::new("test".to_string());
+        assert_eq!(format!("{:?}", host), "NativeHost { .. }");
+        let response = Response::Password {
+            password: "secret".to_string(),
+        };
+        assert_eq!(
+            format!("{:?}", response),
+            r#"Password { password: "****" }"#
+        );
+    }
+
+    #[test]
+    fn test_chrome_manifest() {
+        let manifest = Browser::Chrome.manifest(Path::new("/usr/bin/fp"), &["abcdefg"]);
+        assert_eq!(
+            manifest,
+            serde_json::json!({
+                "name": "com.flowerpassword.native_host",
+                "description": "Flower Password",
+                "path": "/usr/bin/fp",
+                "type": "stdio",
+                "allowed_origins": ["chrome-extension://abcdefg/"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_firefox_manifest() {
+        let manifest = Browser::Firefox.manifest(Path::new("/usr/bin/fp"), &["fp@example.org"]);
+        assert_eq!(
+            manifest["allowed_extensions"],
+            serde_json::json!(["fp@example.org"])
+        );
+        assert!(manifest.get("allowed_origins").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_manifest() {
+        let home = tempfile::tempdir().unwrap();
+        let path = install_manifest(
+            Browser::Firefox,
+            home.path(),
+            Path::new("/usr/bin/fp"),
+            &["fp@example.org"],
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            Browser::Firefox
+                .manifest_dir(home.path())
+                .unwrap()
+                .join("com.flowerpassword.native_host.json")
+        );
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            Browser::Firefox.manifest(Path::new("/usr/bin/fp"), &["fp@example.org"])
+        );
+
+        let error =
+            install_manifest(Browser::Chrome, home.path(), Path::new("fp"), &[]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_manifest_dirs() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            Browser::Chrome.manifest_dir(home).unwrap(),
+            Path::new("/home/user/.config/google-chrome/NativeMessagingHosts")
+        );
+        assert_eq!(
+            Browser::Firefox.manifest_dir(home).unwrap(),
+            Path::new("/home/user/.mozilla/native-messaging-hosts")
+        );
+    }
+}
@@ -0,0 +1,534 @@
+//! Assuan pinentry client
+//!
+//! Asks an existing [pinentry] program, such as the one GnuPG uses, for the
+//! master password instead of prompting on the terminal. The conversation is a
+//! few lines of the Assuan protocol:
+//!
+//! ```text
+//!                               <-   OK Pleased to meet you
+//! SETDESC Enter your master password   ->
+//!                               <-   OK
+//! SETPROMPT Master password:    ->
+//!                               <-   OK
+//! GETPIN                        ->
+//!                               <-   D secret
+//!                               <-   OK
+//! BYE                           ->
+//! ```
+//!
+//! Text is percent-escaped in both directions. The PIN is returned in a buffer
+//! that is zeroed when dropped, and so is every line that held it. A cancelled
+//! dialog is reported as [`PinentryError::Cancelled`], so callers can tell it
+//! apart from a failure and stop, rather than fall back to another prompt.
+//!
+//! [pinentry]: https://www.gnupg.org/related_software/pinentry/
+//!
+//! # Example
+//!
+//! ```no_run
+//! use flowerpassword::pinentry::{resolve_program, Pinentry, PinentryError};
+//!
+//! let program = resolve_program("auto").expect("a pinentry on PATH");
+//! let (mut pinentry, _child) = Pinentry::spawn(&program)?;
+//! match pinentry.get_pin("Enter your master password", "Master password:") {
+//!     Ok(master) => println!("{}", flowerpassword::fp_code(&master, "github.com", 16)?),
+//!     Err(PinentryError::Cancelled) => eprintln!("cancelled"),
+//!     Err(e) => eprintln!("pinentry failed: {}", e),
+//! }
+//! pinentry.bye()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Largest line accepted from the pinentry, in bytes
+///
+/// The Assuan line limit is 1000 bytes; allow some slack for sloppy servers.
+pub const MAX_LINE_LEN: usize = 4096;
+
+/// `GPG_ERR_CANCELED`, in the low 16 bits of the error code of a cancelled dialog
+const GPG_ERR_CANCELED: u32 = 99;
+
+/// Error type for pinentry conversations
+#[derive(Debug)]
+pub enum PinentryError {
+    /// Starting or talking to the pinentry failed
+    Io(io::Error),
+    /// The user closed or cancelled the dialog
+    Cancelled,
+    /// The pinentry answered a command with an error
+    Failed {
+        /// Assuan error code
+        code: u32,
+        /// Description sent with the error
+        message: String,
+    },
+    /// The pinentry sent something that is not valid Assuan
+    Protocol(String),
+}
+
+impl fmt::Display for PinentryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinentryError::Io(e) => write!(f, "Pinentry failed: {}", e),
+            PinentryError::Cancelled => write!(f, "Pinentry was cancelled"),
+            PinentryError::Failed { code, message } => {
+                write!(f, "Pinentry error {}: {}", code, message)
+            }
+            PinentryError::Protocol(message) => write!(f, "Pinentry protocol error: {}", message),
+        }
+    }
+}
+
+impl Error for PinentryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PinentryError::Io(e) => Some(e),
+            PinentryError::Cancelled
+            | PinentryError::Failed { .. }
+            | PinentryError::Protocol(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PinentryError {
+    fn from(e: io::Error) -> Self {
+        PinentryError::Io(e)
+    }
+}
+
+/// Escapes `%`, CR and LF for an Assuan command argument
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '\r' => escaped.push_str("%0D"),
+            '\n' => escaped.push_str("%0A"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Decodes `%XX` escapes, appending the bytes to `out`
+fn unescape_into(text: &[u8], out: &mut Vec<u8>) -> Result<(), PinentryError> {
+    let mut bytes = text.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            out.push(byte);
+            continue;
+        }
+        let high = bytes.next().and_then(|&b| (b as char).to_digit(16));
+        let low = bytes.next().and_then(|&b| (b as char).to_digit(16));
+        match (high, low) {
+            (Some(high), Some(low)) => out.push((high * 16 + low) as u8),
+            _ => {
+                return Err(PinentryError::Protocol(
+                    "invalid percent escape".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One line received from the pinentry
+enum Reply {
+    Ok,
+    Err { code: u32, message: String },
+    Data(Zeroizing<Vec<u8>>),
+}
+
+/// A connection to a pinentry
+pub struct Pinentry<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl Pinentry<BufReader<ChildStdout>, ChildStdin> {
+    /// Starts `program` and waits for its greeting
+    ///
+    /// The child is returned so the caller can wait for it after [`Pinentry::bye`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `PinentryError::Io` if the program cannot be started, or any error
+    /// from [`Pinentry::new`].
+    pub fn spawn(program: &Path) -> Result<(Self, Child), PinentryError> {
+        let mut child = Command::new(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok((Pinentry::new(BufReader::new(stdout), stdin)?, child))
+    }
+}
+
+impl<R: BufRead, W: Write> Pinentry<R, W> {
+    /// Wraps an established connection and reads the pinentry's greeting
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the greeting is not `OK`.
+    pub fn new(reader: R, writer: W) -> Result<Self, PinentryError> {
+        let mut pinentry = Pinentry { reader, writer };
+        pinentry.expect_ok()?;
+        Ok(pinentry)
+    }
+
+    /// Shows `description` and `prompt` and asks for the PIN
+    ///
+    /// # Errors
+    ///
+    /// Returns `PinentryError::Cancelled` if the user cancels, `PinentryError::Failed`
+    /// for other errors reported by the pinentry, and `PinentryError::Protocol` if
+    /// the PIN is not valid UTF-8.
+    pub fn get_pin(
+        &mut self,
+        description: &str,
+        prompt: &str,
+    ) -> Result<Zeroizing<String>, PinentryError> {
+        self.command(&format!("SETDESC {}", escape(description)))?;
+        self.command(&format!("SETPROMPT {}", escape(prompt)))?;
+
+        self.send("GETPIN")?;
+        let mut pin = Zeroizing::new(Vec::new());
+        loop {
+            match self.read_reply()? {
+                Reply::Data(data) => pin.extend_from_slice(&data),
+                Reply::Ok => break,
+                Reply::Err { code, message } => return Err(error(code, message)),
+            }
+        }
+
+        let pin = std::mem::take(&mut *pin);
+        String::from_utf8(pin).map(Zeroizing::new).map_err(|e| {
+            // Zero the bytes that did not make it into a string
+            drop(Zeroizing::new(e.into_bytes()));
+            PinentryError::Protocol("PIN is not valid UTF-8".to_string())
+        })
+    }
+
+    /// Ends the conversation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pinentry does not acknowledge `BYE`.
+    pub fn bye(mut self) -> Result<(), PinentryError> {
+        self.command("BYE")
+    }
+
+    /// Sends a command that is answered with `OK`
+    fn command(&mut self, line: &str) -> Result<(), PinentryError> {
+        self.send(line)?;
+        self.expect_ok()
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), PinentryError> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn expect_ok(&mut self) -> Result<(), PinentryError> {
+        match self.read_reply()? {
+            Reply::Ok => Ok(()),
+            Reply::Err { code, message } => Err(error(code, message)),
+            Reply::Data(_) => Err(PinentryError::Protocol("unexpected data line".to_string())),
+        }
+    }
+
+    /// Reads the next `OK`, `ERR` or `D` line, skipping status and comment lines
+    fn read_reply(&mut self) -> Result<Reply, PinentryError> {
+        loop {
+            let mut line = Zeroizing::new(Vec::new());
+            let read = Read::take(&mut self.reader, MAX_LINE_LEN as u64 + 1)
+                .read_until(b'\n', &mut line)?;
+            if read == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            } else if line.len() > MAX_LINE_LEN {
+                return Err(PinentryError::Protocol("line too long".to_string()));
+            }
+
+            let (keyword, rest) = match line.iter().position(|&b| b == b' ') {
+                Some(space) => (&line[..space], &line[space + 1..]),
+                None => (&line[..], &[][..]),
+            };
+            match keyword {
+                b"OK" => return Ok(Reply::Ok),
+                b"D" => {
+                    let mut data = Zeroizing::new(Vec::with_capacity(rest.len()));
+                    unescape_into(rest, &mut data)?;
+                    return Ok(Reply::Data(data));
+                }
+                b"ERR" => {
+                    let rest = String::from_utf8_lossy(rest);
+                    let (code, message) = rest.split_once(' ').unwrap_or((&rest, ""));
+                    let code = code.parse().map_err(|_| {
+                        PinentryError::Protocol(format!("invalid error code `{}`", code))
+                    })?;
+                    return Ok(Reply::Err {
+                        code,
+                        message: message.to_string(),
+                    });
+                }
+                // Status and comment lines carry nothing the client needs
+                b"S" | b"#" => {}
+                _ => {
+                    return Err(PinentryError::Protocol(format!(
+                        "unexpected line `{}`",
+                        String::from_utf8_lossy(keyword)
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl<R, W> fmt::Debug for Pinentry<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pinentry").finish_non_exhaustive()
+    }
+}
+
+/// Maps an `ERR` reply to `Cancelled` or `Failed`
+fn error(code: u32, message: String) -> PinentryError {
+    if code & 0xffff == GPG_ERR_CANCELED {
+        PinentryError::Cancelled
+    } else {
+        PinentryError::Failed { code, message }
+    }
+}
+
+/// Resolves a `--pinentry` value: `auto` searches `PATH` for `pinentry`, anything
+/// else is a path to the program
+///
+/// Returns `None` if no such program exists, so callers can fall back to their own
+/// prompt.
+pub fn resolve_program(spec: &str) -> Option<PathBuf> {
+    if spec != "auto" {
+        let path = PathBuf::from(spec);
+        return path.is_file().then_some(path);
+    }
+
+    let name = if cfg!(windows) {
+        "pinentry.exe"
+    } else {
+        "pinentry"
+    };
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a conversation against canned replies, returning the result and the
+    /// commands that were sent
+    fn converse(
+        replies: &str,
+        run: impl FnOnce(&mut Pinentry<&[u8], &mut Vec<u8>>) -> Result<String, PinentryError>,
+    ) -> (Result<String, PinentryError>, String) {
+        let mut sent = Vec::new();
+        let result = Pinentry::new(replies.as_bytes(), &mut sent).and_then(|mut p| run(&mut p));
+        (result, String::from_utf8(sent).unwrap())
+    }
+
+    fn get_pin(replies: &str) -> (Result<String, PinentryError>, String) {
+        converse(replies, |p| {
+            p.get_pin("Enter your master password", "Master password:")
+                .map(|pin| pin.to_string())
+        })
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain text"), "plain text");
+        assert_eq!(escape("100%\r\nnext"), "100%25%0D%0Anext");
+        assert_eq!(escape("密码"), "密码");
+    }
+
+    #[test]
+    fn test_unescape() {
+        let mut out = Vec::new();
+        unescape_into(b"a%25b%0a%0D%e5%AF%86", &mut out).unwrap();
+        assert_eq!(out, "a%b\n\r密".as_bytes());
+        for bad in [&b"%"[..], b"%2", b"%zz"] {
+            assert!(matches!(
+                unescape_into(bad, &mut Vec::new()),
+                Err(PinentryError::Protocol(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_get_pin() {
+        let (pin, sent) = get_pin("OK Pleased to meet you\nOK\nOK\nD secret%25pin\nOK\n");
+        assert_eq!(pin.unwrap(), "secret%pin");
+        assert_eq!(
+            sent,
+            "SETDESC Enter your master password\nSETPROMPT Master password:\nGETPIN\n"
+        );
+    }
+
+    #[test]
+    fn test_commands_are_escaped() {
+        let (_, sent) = converse("OK\nOK\nOK\nD x\nOK\n", |p| {
+            p.get_pin("Line one\nLine two: 100%", "PIN")
+                .map(|pin| pin.to_string())
+        });
+        assert!(sent.starts_with("SETDESC Line one%0ALine two: 100%25\n"));
+    }
+
+    #[test]
+    fn test_pin_split_across_data_lines() {
+        let (pin, _) = get_pin("OK\nOK\nOK\nD first\nD %20second\nOK\n");
+        assert_eq!(pin.unwrap(), "first second");
+    }
+
+    #[test]
+    fn test_empty_pin() {
+        let (pin, _) = get_pin("OK\nOK\nOK\nOK\n");
+        assert_eq!(pin.unwrap(), "");
+    }
+
+    #[test]
+    fn test_status_and_comment_lines_are_skipped() {
+        let replies = "# pinentry 1.2\nOK Pleased to meet you\nOK\nS PROGRESS 1\nOK\n\
+                       S PASSWORD_FROM_CACHE\n# comment\nD pin\nOK\n";
+        let (pin, _) = get_pin(replies);
+        assert_eq!(pin.unwrap(), "pin");
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (pin, _) = get_pin("OK\nOK\nOK\nERR 83886179 Operation cancelled <Pinentry>\n");
+        assert!(matches!(pin, Err(PinentryError::Cancelled)));
+        // The source bits do not matter, only the error code
+        let (pin, _) = get_pin("OK\nOK\nOK\nERR 99 cancelled\n");
+        assert!(matches!(pin, Err(PinentryError::Cancelled)));
+    }
+
+    #[test]
+    fn test_error_after_status_lines() {
+        let replies = "OK\nOK\nOK\nS ERROR pinentry.getpin 83886254\n\
+                       # no display\nERR 83886254 No such device <Pinentry>\n";
+        let (pin, _) = get_pin(replies);
+        match pin {
+            Err(PinentryError::Failed { code, message }) => {
+                assert_eq!(code, 83886254);
+                assert_eq!(message, "No such device <Pinentry>");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_from_setdesc() {
+        let (pin, sent) = get_pin("OK\nERR 536871187 Unknown IPC command\n");
+        assert_eq!(
+            pin.unwrap_err().to_string(),
+            "Pinentry error 536871187: Unknown IPC command"
+        );
+        assert_eq!(sent, "SETDESC Enter your master password\n");
+    }
+
+    #[test]
+    fn test_bad_greeting() {
+        let mut sent = Vec::new();
+        let error = Pinentry::new(&b"ERR 1 no\n"[..], &mut sent).unwrap_err();
+        assert!(matches!(error, PinentryError::Failed { code: 1, .. }));
+        let error = Pinentry::new(&b""[..], &mut sent).unwrap_err();
+        assert!(matches!(error, PinentryError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let (pin, _) = get_pin("OK\nOK\nOK\nHELLO there\n");
+        assert_eq!(
+            pin.unwrap_err().to_string(),
+            "Pinentry protocol error: unexpected line `HELLO`"
+        );
+        let (pin, _) = get_pin("OK\nOK\nOK\nERR abc oops\n");
+        assert!(matches!(pin, Err(PinentryError::Protocol(_))));
+        let (pin, _) = get_pin("OK\nOK\nOK\nD %ff\nOK\n");
+        assert_eq!(
+            pin.unwrap_err().to_string(),
+            "Pinentry protocol error: PIN is not valid UTF-8"
+        );
+        let long = format!("OK\nOK\nOK\nD {}\nOK\n", "x".repeat(MAX_LINE_LEN));
+        let (pin, _) = get_pin(&long);
+        assert!(matches!(pin, Err(PinentryError::Protocol(_))));
+        // Ending mid-conversation
+        let (pin, _) = get_pin("OK\nOK\nOK\nD pin\n");
+        assert!(matches!(pin, Err(PinentryError::Io(_))));
+    }
+
+    #[test]
+    fn test_bye() {
+        let mut sent = Vec::new();
+        let pinentry = Pinentry::new(&b"OK\nOK closing connection\n"[..], &mut sent).unwrap();
+        pinentry.bye().unwrap();
+        assert_eq!(sent, b"BYE\n");
+    }
+
+    #[test]
+    fn test_resolve_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("my-pinentry");
+        std::fs::write(&program, "").unwrap();
+        assert_eq!(
+            resolve_program(program.to_str().unwrap()),
+            Some(program.clone())
+        );
+        assert_eq!(
+            resolve_program(dir.path().join("missing").to_str().unwrap()),
+            None
+        );
+        assert_eq!(resolve_program(dir.path().to_str().unwrap()), None);
+    }
+
+    /// Runs a shell script that plays the pinentry side
+    #[cfg(unix)]
+    #[test]
+    fn test_spawned_fake_pinentry() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("pinentry");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\n\
+             echo 'OK Pleased to meet you'\n\
+             while read -r command rest; do\n\
+               case \"$command\" in\n\
+                 GETPIN) echo 'D fake%25pin'; echo OK ;;\n\
+                 BYE) echo 'OK closing connection'; exit 0 ;;\n\
+                 *) echo OK ;;\n\
+               esac\n\
+             done\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (mut pinentry, mut child) = Pinentry::spawn(&script).unwrap();
+        let pin = pinentry.get_pin("Description", "Prompt:").unwrap();
+        assert_eq!(*pin, "fake%pin");
+        pinentry.bye().unwrap();
+        assert!(child.wait().unwrap().success());
+    }
+}
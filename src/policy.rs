@@ -0,0 +1,334 @@
+//! Password composition policies
+//!
+//! Some sites insist on a digit or an uppercase letter. Since every derived
+//! password is a prefix of the length-32 derivation, the shortest length that
+//! satisfies a policy can be found by deriving once and checking prefixes.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::fp_code;
+//! use flowerpassword::policy::{shortest_length_satisfying, Policy};
+//!
+//! let policy: Policy = "require-digit,require-upper,require-lower".parse().unwrap();
+//! let full = fp_code("test", "site33.example", 32).unwrap();
+//! assert_eq!(shortest_length_satisfying(&full, 12, &policy).unwrap(), 13);
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+use flowerpassword_core::{MAX_LENGTH, MIN_LENGTH};
+
+/// A single character-class requirement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Requirement {
+    /// At least one ASCII digit
+    Digit,
+    /// At least one uppercase ASCII letter
+    Upper,
+    /// At least one lowercase ASCII letter
+    Lower,
+}
+
+impl Requirement {
+    /// Every requirement, in the order they are listed in messages
+    pub const ALL: [Requirement; 3] = [Requirement::Digit, Requirement::Upper, Requirement::Lower];
+
+    /// Returns the name used for this requirement in policy strings
+    pub fn name(self) -> &'static str {
+        match self {
+            Requirement::Digit => "require-digit",
+            Requirement::Upper => "require-upper",
+            Requirement::Lower => "require-lower",
+        }
+    }
+
+    /// Returns `true` if `password` satisfies this requirement
+    pub fn is_met(self, password: &str) -> bool {
+        match self {
+            Requirement::Digit => password.bytes().any(|b| b.is_ascii_digit()),
+            Requirement::Upper => password.bytes().any(|b| b.is_ascii_uppercase()),
+            Requirement::Lower => password.bytes().any(|b| b.is_ascii_lowercase()),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = PolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Requirement::ALL
+            .into_iter()
+            .find(|requirement| requirement.name() == s)
+            .ok_or_else(|| PolicyError::UnknownRequirement(s.to_string()))
+    }
+}
+
+/// Error type for policy parsing and checking
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// A policy string names a requirement that does not exist
+    UnknownRequirement(String),
+    /// A policy string has an empty entry, such as a trailing comma
+    EmptyRequirement,
+    /// The minimum length is outside the valid range
+    InvalidLength(usize),
+    /// No length up to the full password satisfies the policy
+    Unsatisfiable(Vec<Requirement>),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::UnknownRequirement(name) => {
+                let known: Vec<&str> = Requirement::ALL.iter().map(|r| r.name()).collect();
+                write!(
+                    f,
+                    "Unknown requirement `{}`, expected one of: {}",
+                    name,
+                    known.join(", ")
+                )
+            }
+            PolicyError::EmptyRequirement => {
+                write!(
+                    f,
+                    "Policy has an empty requirement; separate names with single commas"
+                )
+            }
+            PolicyError::InvalidLength(len) => write!(
+                f,
+                "Length must be between {} and {}, got: {}",
+                MIN_LENGTH, MAX_LENGTH, len
+            ),
+            PolicyError::Unsatisfiable(unmet) => {
+                let names: Vec<&str> = unmet.iter().map(|r| r.name()).collect();
+                write!(
+                    f,
+                    "No length satisfies the policy; unmet: {}",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PolicyError {}
+
+/// A set of requirements a password must meet
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    requirements: Vec<Requirement>,
+}
+
+impl Policy {
+    /// Creates a policy from a list of requirements
+    pub fn new(requirements: &[Requirement]) -> Self {
+        let mut requirements = requirements.to_vec();
+        requirements.sort();
+        requirements.dedup();
+        Policy { requirements }
+    }
+
+    /// Returns the requirements of this policy
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    /// Returns the requirements `password` does not meet
+    pub fn unmet(&self, password: &str) -> Vec<Requirement> {
+        self.requirements
+            .iter()
+            .copied()
+            .filter(|requirement| !requirement.is_met(password))
+            .collect()
+    }
+
+    /// Returns `true` if `password` meets every requirement
+    pub fn is_satisfied_by(&self, password: &str) -> bool {
+        self.unmet(password).is_empty()
+    }
+}
+
+impl FromStr for Policy {
+    type Err = PolicyError;
+
+    /// Parses a comma-separated list of requirement names, such as
+    /// `require-digit,require-upper`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let requirements = s
+            .split(',')
+            .map(str::trim)
+            .map(|name| match name {
+                "" => Err(PolicyError::EmptyRequirement),
+                name => name.parse(),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Policy::new(&requirements))
+    }
+}
+
+/// Finds the shortest length of at least `min` whose prefix of `password` satisfies `policy`
+///
+/// `password` is usually the length-32 derivation, since shorter derivations
+/// are its prefixes. Lengths count characters, so a `password` that is not
+/// ASCII is only ever cut between characters.
+///
+/// # Errors
+///
+/// Returns `PolicyError::InvalidLength` if `min` is outside the valid range or
+/// longer than `password`, or `PolicyError::Unsatisfiable` listing the
+/// requirements even the whole `password` does not meet.
+pub fn shortest_length_satisfying(
+    password: &str,
+    min: usize,
+    policy: &Policy,
+) -> Result<usize, PolicyError> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&min) || min > password.chars().count() {
+        return Err(PolicyError::InvalidLength(min));
+    }
+
+    // The byte offset just past each character, paired with the length it ends
+    password
+        .char_indices()
+        .map(|(start, c)| start + c.len_utf8())
+        .zip(1..)
+        .skip(min - 1)
+        .find(|&(end, _)| policy.is_satisfied_by(&password[..end]))
+        .map(|(_, length)| length)
+        .ok_or_else(|| PolicyError::Unsatisfiable(policy.unmet(password)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+
+    fn policy(s: &str) -> Policy {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            policy("require-digit,require-upper").requirements(),
+            &[Requirement::Digit, Requirement::Upper]
+        );
+        assert_eq!(
+            policy(" require-lower , require-digit,require-lower").requirements(),
+            &[Requirement::Digit, Requirement::Lower]
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_requirement() {
+        let error = "require-digit,require-symbol"
+            .parse::<Policy>()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PolicyError::UnknownRequirement("require-symbol".to_string())
+        );
+        assert_eq!(
+            error.to_string(),
+            "Unknown requirement `require-symbol`, expected one of: \
+             require-digit, require-upper, require-lower"
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_requirement() {
+        assert_eq!(
+            "require-digit,".parse::<Policy>().unwrap_err(),
+            PolicyError::EmptyRequirement
+        );
+        assert_eq!(
+            "".parse::<Policy>().unwrap_err(),
+            PolicyError::EmptyRequirement
+        );
+    }
+
+    #[test]
+    fn test_requirement_checks() {
+        assert!(Requirement::Digit.is_met("K3"));
+        assert!(!Requirement::Digit.is_met("Kab"));
+        assert!(Requirement::Upper.is_met("a3K"));
+        assert!(!Requirement::Lower.is_met("K60F9E0F24C5"));
+    }
+
+    #[test]
+    fn test_shortest_length_skips_failing_prefix() {
+        // The first 12 characters are "K60F9E0F24C5", which has no lowercase letter
+        let full = fp_code("test", "site33.example", 32).unwrap();
+        let policy = policy("require-digit,require-upper,require-lower");
+        assert!(!policy.is_satisfied_by(&full[..12]));
+        assert_eq!(shortest_length_satisfying(&full, 12, &policy).unwrap(), 13);
+        assert_eq!(fp_code("test", "site33.example", 13).unwrap(), full[..13]);
+    }
+
+    #[test]
+    fn test_shortest_length_at_minimum() {
+        let full = fp_code("password", "key", 32).unwrap();
+        let policy = policy("require-digit,require-upper");
+        assert_eq!(shortest_length_satisfying(&full, 2, &policy).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_unsatisfiable_policy() {
+        let policy = policy("require-digit,require-lower");
+        let error = shortest_length_satisfying("KABCDEFABCDEF", 4, &policy).unwrap_err();
+        assert_eq!(
+            error,
+            PolicyError::Unsatisfiable(vec![Requirement::Digit, Requirement::Lower])
+        );
+        assert_eq!(
+            error.to_string(),
+            "No length satisfies the policy; unmet: require-digit, require-lower"
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_password() {
+        // Cutting "éa" after one byte would split the "é"
+        let policy = policy("require-lower");
+        assert_eq!(shortest_length_satisfying("ÉéaB", 2, &policy).unwrap(), 3);
+        assert_eq!(
+            shortest_length_satisfying("éé", 2, &policy).unwrap_err(),
+            PolicyError::Unsatisfiable(vec![Requirement::Lower])
+        );
+    }
+
+    #[test]
+    fn test_minimum_longer_than_password() {
+        let policy = policy("require-digit");
+        assert_eq!(
+            shortest_length_satisfying("K3", 4, &policy).unwrap_err(),
+            PolicyError::InvalidLength(4)
+        );
+        assert_eq!(
+            shortest_length_satisfying("éé", 3, &policy).unwrap_err(),
+            PolicyError::InvalidLength(3)
+        );
+    }
+
+    #[test]
+    fn test_invalid_minimum() {
+        let policy = policy("require-digit");
+        assert_eq!(
+            shortest_length_satisfying("K3", 1, &policy).unwrap_err(),
+            PolicyError::InvalidLength(1)
+        );
+        assert_eq!(
+            shortest_length_satisfying("K3", 33, &policy).unwrap_err(),
+            PolicyError::InvalidLength(33)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Python bindings
+//!
+//! Built as the `flowerpassword` extension module with maturin and the `python`
+//! feature (see `pyproject.toml`). Invalid lengths raise `ValueError` with the same
+//! message as `FlowerPasswordError`, and non-`str` arguments such as `bytes` raise
+//! `TypeError`. The GIL is released while passwords are derived, so threads can
+//! generate in parallel.
+//!
+//! ```python
+//! from flowerpassword import FpGenerator, fp_code, fp_verify
+//!
+//! fp_code("test", "github.com")  # "D04175F7A9c7Ab4a"
+//! fp_verify("test", "github.com", "D04175F7A9c7Ab4a")  # True
+//!
+//! generator = FpGenerator("test", length=12)
+//! generator.generate_many(["github.com", "gitlab.com"])
+//! ```
+
+#[cfg(feature = "strength")]
+use crate::strength::DEFAULT_MIN_SCORE;
+use crate::{fp_code, fp_verify, FlowerPasswordError};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Password length used when Python callers do not pass one
+const DEFAULT_LENGTH: usize = crate::FpGenerator::DEFAULT_LENGTH;
+
+/// Raises library errors as `ValueError`
+fn value_error(error: FlowerPasswordError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Generates a Flower Password, exposed to Python as `fp_code`
+#[pyfunction(name = "fp_code")]
+#[pyo3(signature = (password, key, length = DEFAULT_LENGTH))]
+fn py_fp_code(py: Python<'_>, password: &str, key: &str, length: usize) -> PyResult<String> {
+    py.allow_threads(|| fp_code(password, key, length))
+        .map_err(value_error)
+}
+
+/// Checks a candidate password, exposed to Python as `fp_verify`
+#[pyfunction(name = "fp_verify")]
+fn py_fp_verify(py: Python<'_>, password: &str, key: &str, candidate: &str) -> bool {
+    py.allow_threads(|| fp_verify(password, key, candidate))
+}
+
+/// Generates passwords for many keys under one master password
+///
+/// Wraps the Rust [`FpGenerator`](crate::FpGenerator): the master password
+/// stays on the Rust side, in locked memory with the `memlock` feature, and is
+/// left out of `repr()`.
+#[pyclass(name = "FpGenerator", module = "flowerpassword", frozen)]
+struct FpGenerator(crate::FpGenerator);
+
+#[pymethods]
+impl FpGenerator {
+    #[new]
+    #[pyo3(signature = (master, length = DEFAULT_LENGTH))]
+    fn new(master: String, length: usize) -> PyResult<Self> {
+        crate::FpGenerator::new(master, length)
+            .map(FpGenerator)
+            .map_err(value_error)
+    }
+
+    /// Default length of generated passwords
+    #[getter]
+    fn length(&self) -> usize {
+        self.0.length()
+    }
+
+    /// Like the constructor, but raises `ValueError` if zxcvbn scores the master
+    /// password below `min_score` (0 to 4), with its warnings and suggestions
+    #[cfg(feature = "strength")]
+    #[staticmethod]
+    #[pyo3(signature = (master, length = DEFAULT_LENGTH, min_score = DEFAULT_MIN_SCORE))]
+    fn new_checked(master: String, length: usize, min_score: u8) -> PyResult<Self> {
+        crate::FpGenerator::new_checked(master, length, min_score)
+            .map(FpGenerator)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Whether the master password is locked in RAM, with the `memlock` feature
+    #[cfg(feature = "memlock")]
+    #[getter]
+    fn memory_locked(&self) -> bool {
+        self.0.memory_status() == crate::memlock::LockStatus::Locked
+    }
+
+    /// Generates the password for `key`, at `length` or the default length
+    #[pyo3(signature = (key, length = None))]
+    fn generate(&self, py: Python<'_>, key: &str, length: Option<usize>) -> PyResult<String> {
+        let length = length.unwrap_or(self.0.length());
+        py.allow_threads(|| self.0.generate_with_length(key, length))
+            .map_err(value_error)
+    }
+
+    /// Generates the password for each key, in order
+    fn generate_many(&self, py: Python<'_>, keys: Vec<String>) -> Vec<String> {
+        py.allow_threads(|| self.0.generate_many(keys.iter().map(String::as_str)))
+    }
+
+    /// Checks whether `candidate` is the password for `key`
+    fn verify(&self, py: Python<'_>, key: &str, candidate: &str) -> bool {
+        py.allow_threads(|| self.0.verify(key, candidate))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FpGenerator(length={})", self.0.length())
+    }
+}
+
+/// The `flowerpassword` Python module
+#[pymodule(name = "flowerpassword")]
+fn py_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_fp_code, m)?)?;
+    m.add_function(wrap_pyfunction!(py_fp_verify, m)?)?;
+    m.add_class::<FpGenerator>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "strength")]
+    use crate::strength::require_strength;
+
+    #[test]
+    fn test_invalid_length_is_value_error() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let error = value_error(FlowerPasswordError::InvalidLength(33));
+            assert!(error.is_instance_of::<PyValueError>(py));
+            assert_eq!(
+                error.value(py).to_string(),
+                "Length must be between 2 and 32, got: 33"
+            );
+        });
+    }
+
+    #[test]
+    fn test_errors_leave_master_out() {
+        use crate::redaction::{assert_redacted, SENTINEL};
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let generator = FpGenerator::new(SENTINEL.to_string(), 16).unwrap();
+            let errors = [
+                py_fp_code(py, SENTINEL, SENTINEL, 40).unwrap_err(),
+                FpGenerator::new(SENTINEL.to_string(), 40).err().unwrap(),
+                generator.generate(py, SENTINEL, Some(40)).unwrap_err(),
+                #[cfg(feature = "strength")]
+                FpGenerator::new_checked("SECRET_SENTINEL1".to_string(), 16, 4)
+                    .err()
+                    .unwrap(),
+            ];
+            let mut messages: Vec<String> = errors
+                .iter()
+                .map(|error| error.value(py).to_string())
+                .collect();
+            messages.push(generator.__repr__());
+            assert_redacted(messages.iter().map(String::as_str));
+        });
+    }
+
+    #[test]
+    fn test_generator_validates_length() {
+        assert!(FpGenerator::new("test".to_string(), 1).is_err());
+        let generator = FpGenerator::new("test".to_string(), 12).unwrap();
+        assert_eq!(generator.__repr__(), "FpGenerator(length=12)");
+    }
+
+    #[test]
+    #[cfg(feature = "strength")]
+    fn test_new_checked_rejects_weak_masters() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let error = FpGenerator::new_checked("password1".to_string(), 16, 3)
+                .err()
+                .unwrap();
+            assert!(error.is_instance_of::<PyValueError>(py));
+            let weak = require_strength("password1", 3).unwrap_err();
+            assert!(!weak.strength.suggestions.is_empty());
+            assert_eq!(error.value(py).to_string(), weak.to_string());
+        });
+        assert!(FpGenerator::new_checked("vT9#qLm2$Xe8!rWz4&Kp".to_string(), 16, 3).is_ok());
+        assert!(FpGenerator::new_checked("vT9#qLm2$Xe8!rWz4&Kp".to_string(), 40, 3).is_err());
+    }
+}
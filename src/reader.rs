@@ -0,0 +1,276 @@
+//! Keys read from an `io::Read`
+//!
+//! [`fp_code_from_reader`] streams the key through a fixed-size buffer into a
+//! [`KeyStream`], so a key file of any size derives in constant memory.
+
+use crate::FlowerPasswordError;
+use flowerpassword_core::{validate_length, KeyStream};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Bytes read from the key reader at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Error type for [`fp_code_from_reader`]
+#[derive(Debug)]
+pub enum FpIoError {
+    /// Length parameter is outside the valid range
+    InvalidLength(usize),
+    /// Reading the key failed after `offset` bytes had been read
+    Read {
+        /// Bytes of the key read successfully before the error
+        offset: u64,
+        /// The underlying error
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for FpIoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FpIoError::InvalidLength(len) => {
+                write!(f, "{}", FlowerPasswordError::InvalidLength(*len))
+            }
+            FpIoError::Read { offset, source } => {
+                write!(f, "Failed to read key at byte {}: {}", offset, source)
+            }
+        }
+    }
+}
+
+impl Error for FpIoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FpIoError::InvalidLength(_) => None,
+            FpIoError::Read { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<FlowerPasswordError> for FpIoError {
+    fn from(e: FlowerPasswordError) -> Self {
+        match e {
+            FlowerPasswordError::InvalidLength(len) => FpIoError::InvalidLength(len),
+        }
+    }
+}
+
+/// Generates a Flower Password with the key read from `key_reader`
+///
+/// The key is read to the end in chunks of 64 KiB, which is all the memory used
+/// however large it is. Its bytes are taken as they are: for a reader yielding the
+/// UTF-8 encoding of `key`, the result is exactly `fp_code(password, key, length)`.
+/// Reads interrupted by a signal are retried.
+///
+/// # Errors
+///
+/// Returns `FpIoError::InvalidLength` if length is not between 2 and 32, before
+/// reading anything, or `FpIoError::Read` with the offset reached if a read fails.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::{fp_code, fp_code_from_reader};
+///
+/// let password = fp_code_from_reader("test", &b"github.com"[..], 16).unwrap();
+/// assert_eq!(password, fp_code("test", "github.com", 16).unwrap());
+/// ```
+pub fn fp_code_from_reader<R: Read>(
+    password: &str,
+    mut key_reader: R,
+    length: usize,
+) -> Result<String, FpIoError> {
+    validate_length(length)?;
+    let mut key = KeyStream::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut offset = 0u64;
+    loop {
+        match key_reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                key.update(&chunk[..n]);
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(source) => return Err(FpIoError::Read { offset, source }),
+        }
+    }
+    Ok(key.fp_code(password, length)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+
+    /// A reader of `remaining` bytes repeating `PATTERN`, checking every buffer it is given
+    struct Pattern {
+        remaining: u64,
+        position: usize,
+        largest_buffer: usize,
+    }
+
+    const PATTERN: &[u8] = b"flower password key material 0123456789\n";
+
+    impl Pattern {
+        fn new(len: u64) -> Self {
+            Pattern {
+                remaining: len,
+                position: 0,
+                largest_buffer: 0,
+            }
+        }
+    }
+
+    impl Read for Pattern {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.largest_buffer = self.largest_buffer.max(buf.len());
+            let n = buf
+                .len()
+                .min(self.remaining.try_into().unwrap_or(usize::MAX));
+            let mut filled = 0;
+            while filled < n {
+                let piece = &PATTERN[self.position..];
+                let take = piece.len().min(n - filled);
+                buf[filled..filled + take].copy_from_slice(&piece[..take]);
+                filled += take;
+                self.position = (self.position + take) % PATTERN.len();
+            }
+            self.remaining -= n as u64;
+            Ok(n)
+        }
+    }
+
+    /// Fails with `kind` after yielding `good` bytes, one per read
+    struct Failing {
+        good: usize,
+        kind: io::ErrorKind,
+    }
+
+    impl Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.good == 0 {
+                return Err(io::Error::new(self.kind, "device gone"));
+            }
+            self.good -= 1;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_matches_fp_code() {
+        for key in [
+            "",
+            "github.com",
+            "例え.jp",
+            &"k".repeat(64),
+            &"k".repeat(65),
+        ] {
+            for length in [2, 16, 32] {
+                assert_eq!(
+                    fp_code_from_reader("test", key.as_bytes(), length).unwrap(),
+                    fp_code("test", key, length).unwrap(),
+                    "{} {}",
+                    key,
+                    length
+                );
+            }
+        }
+
+        // Larger than one chunk, in reads of many sizes
+        let len = 3 * CHUNK_SIZE as u64 + 7;
+        let key: String = PATTERN
+            .iter()
+            .cycle()
+            .take(len as usize)
+            .map(|&b| char::from(b))
+            .collect();
+        assert_eq!(
+            fp_code_from_reader("test", Pattern::new(len), 16).unwrap(),
+            fp_code("test", &key, 16).unwrap()
+        );
+        assert_eq!(
+            fp_code_from_reader(
+                "test",
+                io::BufReader::with_capacity(100, key.as_bytes()),
+                16
+            )
+            .unwrap(),
+            fp_code("test", &key, 16).unwrap()
+        );
+    }
+
+    /// Streams `len` bytes of `PATTERN` as the key, checking the buffer stays fixed
+    fn stream_pattern(len: u64) -> String {
+        let mut reader = Pattern::new(len);
+        let password = fp_code_from_reader("test", &mut reader, 16).unwrap();
+        assert_eq!(reader.remaining, 0);
+        assert_eq!(reader.largest_buffer, CHUNK_SIZE);
+        password
+    }
+
+    // Expected passwords computed independently with Python's hashlib and hmac
+
+    #[test]
+    fn test_large_key_in_constant_memory() {
+        assert_eq!(stream_pattern(256 << 20), "K63567a18DE97E1a");
+    }
+
+    #[test]
+    #[ignore = "hashes 3 GiB; run with `cargo test --release -- --ignored`"]
+    fn test_multi_gigabyte_key_in_constant_memory() {
+        assert_eq!(stream_pattern(3 << 30), "E67d88e83F646368");
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = fp_code_from_reader(
+            "test",
+            Failing {
+                good: 5,
+                kind: io::ErrorKind::BrokenPipe,
+            },
+            16,
+        )
+        .unwrap_err();
+        assert!(matches!(err, FpIoError::Read { offset: 5, .. }));
+        assert_eq!(err.to_string(), "Failed to read key at byte 5: device gone");
+        assert_eq!(err.source().unwrap().to_string(), "device gone".to_string());
+
+        assert!(matches!(
+            fp_code_from_reader(
+                "test",
+                Failing {
+                    good: 0,
+                    kind: io::ErrorKind::Other
+                },
+                1
+            ),
+            Err(FpIoError::InvalidLength(1))
+        ));
+        assert_eq!(
+            FpIoError::InvalidLength(33).to_string(),
+            "Length must be between 2 and 32, got: 33"
+        );
+    }
+
+    #[test]
+    fn test_retries_interrupted_reads() {
+        struct Interrupting(bool, &'static [u8]);
+        impl Read for Interrupting {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0 = !self.0;
+                if self.0 {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                self.1.read(buf)
+            }
+        }
+        assert_eq!(
+            fp_code_from_reader("test", Interrupting(false, b"github.com"), 16).unwrap(),
+            "D04175F7A9c7Ab4a"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Redaction tests
+//!
+//! Every type that can hold a master password, a derived password or a PIN is
+//! formatted here with `Debug` and, where it has one, `Display`, after being
+//! given [`SENTINEL`] as that secret. The sentinel must never appear. A new type
+//! holding secrets belongs in this module, with a manual `Debug` that prints
+//! `****` or leaves the field out.
+//!
+//! The error paths of the language bindings are audited the same way, since their
+//! messages end up in foreign exceptions, logs and crash reports:
+//!
+//! - the C ABI's `fp_last_error_message` and UniFFI's `FpError`, here;
+//! - the Python, JNI and WebAssembly wrappers, whose helpers are private, in
+//!   their own modules' tests with [`SENTINEL`] and [`assert_redacted`];
+//! - the Node.js addon in `flowerpassword-napi/test` and the Dart package in
+//!   `bindings/dart/test`, which pass a sentinel master password and check the
+//!   thrown error.
+//!
+//! The .NET wrapper has no test project. Its errors were checked by reading them:
+//! they carry argument names and the length, never a password or key.
+
+use crate::fp_code;
+use flowerpassword_core::KeyStream;
+
+/// Stands in for a master password, derived password or PIN
+pub(crate) const SENTINEL: &str = "SECRET_SENTINEL";
+
+/// Asserts that none of `outputs` contains the sentinel
+pub(crate) fn assert_redacted<'a>(outputs: impl IntoIterator<Item = &'a str>) {
+    for output in outputs {
+        assert!(!output.contains(SENTINEL), "leaked: {}", output);
+    }
+}
+
+#[test]
+fn test_core_types() {
+    let mut key = KeyStream::new();
+    key.update(SENTINEL.as_bytes());
+    let error = fp_code(SENTINEL, SENTINEL, 40).unwrap_err();
+    assert_redacted([
+        format!("{:?}", key).as_str(),
+        &format!("{:?}", error),
+        &error.to_string(),
+    ]);
+}
+
+#[test]
+fn test_policy_errors() {
+    use crate::policy::{shortest_length_satisfying, Policy};
+
+    let policy: Policy = "require-digit".parse().unwrap();
+    let error = shortest_length_satisfying("SECRET_SENTINEL", 2, &policy).unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_generator() {
+    use crate::FpGenerator;
+
+    let generator = FpGenerator::new(SENTINEL.to_string(), 16).unwrap();
+    let error = generator.generate_with_length(SENTINEL, 40).unwrap_err();
+    assert_redacted([
+        format!("{:?}", generator).as_str(),
+        &format!("{:?}", error),
+        &error.to_string(),
+    ]);
+}
+
+#[test]
+#[cfg(feature = "secrecy")]
+fn test_secret_inputs() {
+    use crate::fp_code_secret_input;
+    use secrecy::SecretString;
+
+    let master = SecretString::from(SENTINEL);
+    let error = fp_code_secret_input(&master, "github.com", 40).unwrap_err();
+    assert_redacted([
+        format!("{:?}", master).as_str(),
+        &format!("{:?}", error),
+        &error.to_string(),
+    ]);
+    #[cfg(feature = "std")]
+    {
+        let generator = crate::FpGenerator::from_secret(master, 16).unwrap();
+        assert_redacted([format!("{:?}", generator).as_str()]);
+    }
+}
+
+#[test]
+#[cfg(feature = "ffi")]
+fn test_ffi_errors() {
+    use crate::ffi::{fp_code_c, fp_last_error_message};
+    use std::ffi::{CStr, CString};
+
+    let last_error = || {
+        let message = fp_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    let sentinel = CString::new(SENTINEL).unwrap();
+    // The sentinel followed by a byte that is not UTF-8
+    let invalid = CString::new([SENTINEL.as_bytes(), b"\xff"].concat()).unwrap();
+    let mut out = [0; 33];
+    let mut messages = Vec::new();
+    for (password, key, length) in [
+        (&sentinel, &sentinel, 40),
+        (&invalid, &sentinel, 16),
+        (&sentinel, &invalid, 16),
+    ] {
+        let code = unsafe {
+            fp_code_c(
+                password.as_ptr(),
+                key.as_ptr(),
+                length,
+                out.as_mut_ptr(),
+                out.len(),
+            )
+        };
+        assert_ne!(code, crate::ffi::FP_OK);
+        messages.push(last_error());
+    }
+    let code = unsafe {
+        fp_code_c(
+            sentinel.as_ptr(),
+            sentinel.as_ptr(),
+            16,
+            out.as_mut_ptr(),
+            4,
+        )
+    };
+    assert_eq!(code, crate::ffi::FP_ERROR_BUFFER_TOO_SMALL);
+    messages.push(last_error());
+    assert_redacted(messages.iter().map(String::as_str));
+}
+
+#[test]
+#[cfg(feature = "uniffi")]
+fn test_uniffi_errors() {
+    let error = crate::mobile::fp_code(SENTINEL.into(), SENTINEL.into(), 40).unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "agent")]
+fn test_agent() {
+    use crate::agent::{AgentSession, Response};
+
+    let session = AgentSession::new(SENTINEL.to_string());
+    let response = Response::Password {
+        password: SENTINEL.to_string(),
+    };
+    assert_redacted([
+        format!("{:?}", session).as_str(),
+        &format!("{:?}", response),
+    ]);
+}
+
+#[test]
+#[cfg(feature = "rpc")]
+fn test_rpc() {
+    use crate::rpc::RpcSession;
+
+    let mut session = RpcSession::new(true);
+    let set = format!(
+        r#"{{"jsonrpc":"2.0","id":1,"method":"setMaster","params":{{"password":"{}"}}}}"#,
+        SENTINEL
+    );
+    let response = session.handle(set.as_bytes()).unwrap();
+    assert_redacted([format!("{:?}", session).as_str(), &response]);
+
+    // serde quotes values of the wrong type in its errors
+    let numeric =
+        br#"{"jsonrpc":"2.0","id":2,"method":"setMaster","params":{"password":73577357}}"#;
+    let response = session.handle(numeric).unwrap();
+    assert!(response.contains("Invalid params"), "{}", response);
+    assert!(!response.contains("73577357"), "leaked: {}", response);
+}
+
+#[test]
+#[cfg(feature = "native-host")]
+fn test_native_host() {
+    use crate::native_host::{NativeHost, Response};
+
+    let host = NativeHost::new(SENTINEL.to_string());
+    let response = Response::Password {
+        password: SENTINEL.to_string(),
+    };
+    assert_redacted([format!("{:?}", host).as_str(), &format!("{:?}", response)]);
+}
+
+#[test]
+#[cfg(feature = "config")]
+fn test_export_entries() {
+    use crate::config::ExportEntry;
+
+    let entry = ExportEntry {
+        name: "github",
+        key: "github.com",
+        username: None,
+        notes: None,
+        password: SENTINEL.to_string(),
+    };
+    assert_redacted([format!("{:?}", entry).as_str()]);
+}
+
+#[test]
+#[cfg(feature = "memlock")]
+fn test_locked_buffers() {
+    use crate::memlock::LockedBuf;
+
+    let buf = LockedBuf::from_slice(SENTINEL.as_bytes());
+    assert_redacted([format!("{:?}", buf).as_str(), &buf.status().to_string()]);
+}
+
+#[test]
+#[cfg(feature = "pinentry")]
+fn test_pinentry_errors() {
+    use crate::pinentry::Pinentry;
+
+    let replies = format!("OK\nOK\nOK\nD {}\nXX {}\n", SENTINEL, SENTINEL);
+    let mut sent = Vec::new();
+    let mut pinentry = Pinentry::new(replies.as_bytes(), &mut sent).unwrap();
+    let error = pinentry.get_pin("Master password", "PIN").unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "ssh-agent")]
+fn test_ssh_agent_source() {
+    // A master password typed where the source was expected
+    let error = crate::ssh_agent::parse_source(SENTINEL).unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "yubikey")]
+fn test_yubikey_source() {
+    let error = crate::yubikey::parse_source(SENTINEL).unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "fido2")]
+fn test_fido2_source() {
+    let error = crate::fido2::parse_source(SENTINEL).unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+    let error = SENTINEL.parse::<crate::fido2::CredentialId>().unwrap_err();
+    assert_redacted([format!("{:?}", error).as_str(), &error.to_string()]);
+}
+
+#[test]
+#[cfg(feature = "strength")]
+fn test_strength() {
+    use crate::strength::{check_master_strength, require_strength};
+
+    // Weak enough for zxcvbn to explain why
+    let master = "SECRET_SENTINEL1";
+    let strength = check_master_strength(master);
+    let error = require_strength(master, 4).unwrap_err();
+    let checked = crate::FpGenerator::new_checked(master.to_string(), 16, 4).unwrap_err();
+    assert_redacted([
+        format!("{:?}", strength).as_str(),
+        &format!("{:?}", error),
+        &error.to_string(),
+        &format!("{:?}", checked),
+        &checked.to_string(),
+    ]);
+}
+
+#[test]
+#[cfg(feature = "clipboard")]
+fn test_clipboard() {
+    use crate::clipboard::{copy_with_ttl_using, MemoryClipboard, DEFAULT_TTL};
+
+    let clipboard = MemoryClipboard::new(Some(SENTINEL));
+    let guard = copy_with_ttl_using(clipboard.clone(), SENTINEL, DEFAULT_TTL).unwrap();
+    assert_redacted([format!("{:?}", clipboard).as_str(), &format!("{:?}", guard)]);
+}
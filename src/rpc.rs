@@ -0,0 +1,1236 @@
+//! JSON-RPC 2.0 protocol
+//!
+//! Editor plugins and GUI shells can keep a child process around and send it
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) requests over stdio.
+//! [`RpcSession`] implements the methods independently of the transport, and
+//! [`serve`] moves messages between a reader and a writer:
+//!
+//! ```text
+//! {"jsonrpc":"2.0","id":1,"method":"setMaster","params":{"password":"test"}}
+//! {"jsonrpc":"2.0","id":2,"method":"derive","params":{"key":"github.com","length":16}}
+//! {"jsonrpc":"2.0","id":3,"method":"deriveBatch","params":{"keys":["a.com","b.com"],"length":16}}
+//! {"jsonrpc":"2.0","id":4,"method":"lock"}
+//! {"jsonrpc":"2.0","id":5,"method":"status"}
+//! ```
+//!
+//! `derive` and `deriveBatch` accept `options.policy`, a composition policy such
+//! as `require-digit,require-upper`: the password is then the shortest prefix of
+//! at least `length` characters that satisfies it. `setMaster`, `lock` and
+//! `status` return `{"state": "unset" | "unlocked" | "locked", "allowRekey": bool}`.
+//! `setMaster` is accepted once per session unless the session allows rekeying.
+//!
+//! Messages are either one per line or framed with `Content-Length` headers as in
+//! the Language Server Protocol; the first message decides, and responses use the
+//! same framing.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::rpc::{serve, RpcSession};
+//! use serde_json::Value;
+//!
+//! let input = concat!(
+//!     r#"{"jsonrpc":"2.0","id":1,"method":"setMaster","params":{"password":"test"}}"#, "\n",
+//!     r#"{"jsonrpc":"2.0","id":2,"method":"derive","params":{"key":"github.com","length":16}}"#, "\n",
+//! );
+//! let mut output = Vec::new();
+//! serve(input.as_bytes(), &mut output, &mut RpcSession::new(false)).unwrap();
+//!
+//! let output = String::from_utf8(output).unwrap();
+//! let response: Value = serde_json::from_str(output.lines().last().unwrap()).unwrap();
+//! assert_eq!(response["result"]["password"], "D04175F7A9c7Ab4a");
+//! ```
+
+use crate::policy::{shortest_length_satisfying, Policy, PolicyError};
+use crate::{fp_code, FlowerPasswordError};
+use flowerpassword_core::MAX_LENGTH;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+/// Largest message accepted from the client, in bytes
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// The message is not valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// The message is not a valid JSON-RPC request
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The parameters are missing, mistyped or name an unknown policy requirement
+pub const INVALID_PARAMS: i64 = -32602;
+/// A derivation was requested before `setMaster`
+pub const MASTER_NOT_SET: i64 = -32001;
+/// `setMaster` was called again in a session that does not allow rekeying
+pub const MASTER_ALREADY_SET: i64 = -32002;
+/// A derivation was requested after `lock`
+pub const SESSION_LOCKED: i64 = -32003;
+/// `FlowerPasswordError::InvalidLength`: the length is not between 2 and 32
+pub const INVALID_LENGTH: i64 = -32010;
+/// No length up to 32 satisfies the requested policy
+pub const UNSATISFIABLE_POLICY: i64 = -32011;
+
+/// Error type for the transport: reading and writing framed messages
+#[derive(Debug)]
+pub enum RpcError {
+    /// Reading or writing the transport failed
+    Io(io::Error),
+    /// The client sent a message larger than [`MAX_MESSAGE_LEN`]
+    MessageTooLarge(usize),
+    /// A `Content-Length` header block is invalid
+    MalformedHeader(String),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpcError::Io(e) => write!(f, "RPC transport failed: {}", e),
+            RpcError::MessageTooLarge(len) => write!(
+                f,
+                "Message of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_MESSAGE_LEN
+            ),
+            RpcError::MalformedHeader(message) => write!(f, "Malformed RPC header: {}", message),
+        }
+    }
+}
+
+impl Error for RpcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RpcError::Io(e) => Some(e),
+            RpcError::MessageTooLarge(_) | RpcError::MalformedHeader(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for RpcError {
+    fn from(e: io::Error) -> Self {
+        RpcError::Io(e)
+    }
+}
+
+/// How messages are delimited on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// One message per line
+    Newline,
+    /// `Content-Length: N` headers, a blank line, then N bytes of message
+    ContentLength,
+}
+
+/// Reads messages, detecting the framing from the first one
+pub struct MessageReader<R> {
+    reader: R,
+    framing: Option<Framing>,
+}
+
+impl<R: BufRead> MessageReader<R> {
+    /// Wraps `reader`; the framing is decided by the first message
+    pub fn new(reader: R) -> Self {
+        MessageReader {
+            reader,
+            framing: None,
+        }
+    }
+
+    /// Returns the framing in use, once the first message has been read
+    pub fn framing(&self) -> Option<Framing> {
+        self.framing
+    }
+
+    /// Reads the next message body
+    ///
+    /// Blank lines between messages are skipped. Returns `Ok(None)` once the
+    /// client closes the stream between messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RpcError::Io` if reading fails or the stream ends inside a message,
+    /// `RpcError::MessageTooLarge` for oversized messages, and
+    /// `RpcError::MalformedHeader` for invalid `Content-Length` headers.
+    pub fn read_message(&mut self) -> Result<Option<Vec<u8>>, RpcError> {
+        loop {
+            let line = match self.read_line()? {
+                Some(line) => line,
+                None => return Ok(None),
+            };
+            let line = trim(&line);
+            if line.is_empty() {
+                continue;
+            }
+
+            let framing = *self.framing.get_or_insert_with(|| {
+                if is_content_length(line) {
+                    Framing::ContentLength
+                } else {
+                    Framing::Newline
+                }
+            });
+            return match framing {
+                Framing::Newline => Ok(Some(line.to_vec())),
+                Framing::ContentLength => self.read_body(line).map(Some),
+            };
+        }
+    }
+
+    /// Reads one line of at most [`MAX_MESSAGE_LEN`] bytes, newline included
+    fn read_line(&mut self) -> Result<Option<Vec<u8>>, RpcError> {
+        let mut line = Vec::new();
+        let read = (&mut self.reader)
+            .take(MAX_MESSAGE_LEN as u64 + 1)
+            .read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let content_len = line.len() - usize::from(line.ends_with(b"\n"));
+        if content_len > MAX_MESSAGE_LEN {
+            return Err(RpcError::MessageTooLarge(content_len));
+        }
+        Ok(Some(line))
+    }
+
+    /// Reads the rest of a header block starting with `first`, then the body
+    fn read_body(&mut self, first: &[u8]) -> Result<Vec<u8>, RpcError> {
+        let mut length = parse_header(first)?;
+        loop {
+            let line = self
+                .read_line()?
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            let line = trim(&line);
+            if line.is_empty() {
+                break;
+            }
+            length = parse_header(line)?.or(length);
+        }
+
+        let length = length
+            .ok_or_else(|| RpcError::MalformedHeader("missing Content-Length".to_string()))?;
+        if length > MAX_MESSAGE_LEN {
+            return Err(RpcError::MessageTooLarge(length));
+        }
+
+        let mut body = vec![0u8; length];
+        self.reader.read_exact(&mut body)?;
+        Ok(body)
+    }
+}
+
+/// Strips ASCII whitespace from both ends
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |end| end + 1);
+    &bytes[start..end]
+}
+
+/// Splits a header line into its name and value
+///
+/// Names are letters, digits and dashes, so a JSON message is never a header.
+fn split_header(line: &[u8]) -> Option<(&[u8], &[u8])> {
+    let colon = line.iter().position(|&b| b == b':')?;
+    let name = trim(&line[..colon]);
+    let is_token = |b: &u8| b.is_ascii_alphanumeric() || *b == b'-';
+    (!name.is_empty() && name.iter().all(is_token)).then(|| (name, trim(&line[colon + 1..])))
+}
+
+/// Returns whether `line` is a `Content-Length` header, which starts header framing
+fn is_content_length(line: &[u8]) -> bool {
+    split_header(line).is_some_and(|(name, _)| name.eq_ignore_ascii_case(b"content-length"))
+}
+
+/// Parses a header line, returning the length if it is `Content-Length`
+fn parse_header(line: &[u8]) -> Result<Option<usize>, RpcError> {
+    let (name, value) = split_header(line).ok_or_else(|| {
+        RpcError::MalformedHeader(format!(
+            "expected `Name: value`, got `{}`",
+            String::from_utf8_lossy(line)
+        ))
+    })?;
+    if !name.eq_ignore_ascii_case(b"content-length") {
+        return Ok(None);
+    }
+
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            RpcError::MalformedHeader(format!(
+                "invalid Content-Length `{}`",
+                String::from_utf8_lossy(value)
+            ))
+        })
+}
+
+/// Writes one message with `framing`
+///
+/// # Errors
+///
+/// Returns any error from writing or flushing `writer`.
+pub fn write_message<W: Write>(writer: &mut W, framing: Framing, body: &[u8]) -> io::Result<()> {
+    match framing {
+        Framing::Newline => {
+            writer.write_all(body)?;
+            writer.write_all(b"\n")?;
+        }
+        Framing::ContentLength => {
+            write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
+            writer.write_all(body)?;
+        }
+    }
+    writer.flush()
+}
+
+/// A JSON-RPC error object
+#[derive(Debug, Clone, PartialEq)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl ErrorObject {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        ErrorObject {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    fn invalid_request(message: &str) -> Self {
+        ErrorObject::new(INVALID_REQUEST, format!("Invalid request: {}", message))
+    }
+
+    fn to_value(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+
+    /// The `InvalidLength` error shared by the library and policy errors
+    fn invalid_length(length: usize, message: String) -> Self {
+        ErrorObject::new(INVALID_LENGTH, message)
+            .with_data(json!({ "kind": "InvalidLength", "length": length }))
+    }
+}
+
+impl From<FlowerPasswordError> for ErrorObject {
+    fn from(error: FlowerPasswordError) -> Self {
+        match error {
+            FlowerPasswordError::InvalidLength(length) => {
+                ErrorObject::invalid_length(length, error.to_string())
+            }
+        }
+    }
+}
+
+impl From<PolicyError> for ErrorObject {
+    fn from(error: PolicyError) -> Self {
+        match &error {
+            PolicyError::InvalidLength(length) => {
+                ErrorObject::invalid_length(*length, error.to_string())
+            }
+            PolicyError::Unsatisfiable(unmet) => {
+                let unmet: Vec<&str> = unmet.iter().map(|r| r.name()).collect();
+                ErrorObject::new(UNSATISFIABLE_POLICY, error.to_string())
+                    .with_data(json!({ "unmet": unmet }))
+            }
+            PolicyError::UnknownRequirement(_) | PolicyError::EmptyRequirement => {
+                ErrorObject::new(INVALID_PARAMS, error.to_string())
+            }
+        }
+    }
+}
+
+/// Builds an error response
+fn error_response(id: Value, error: &ErrorObject) -> Value {
+    json!({ "jsonrpc": "2.0", "error": error.to_value(), "id": id })
+}
+
+/// Options shared by `derive` and `deriveBatch`
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeriveOptions {
+    /// Composition policy the password must satisfy
+    policy: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeriveParams {
+    key: String,
+    length: usize,
+    options: Option<DeriveOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeriveBatchParams {
+    keys: Vec<String>,
+    length: usize,
+    options: Option<DeriveOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetMasterParams {
+    password: String,
+}
+
+/// Decodes named parameters
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, ErrorObject> {
+    serde_json::from_value(params)
+        .map_err(|e| ErrorObject::new(INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+/// Derives one password, honouring `options.policy`
+fn derive(
+    master: &str,
+    key: &str,
+    length: usize,
+    options: &DeriveOptions,
+) -> Result<String, ErrorObject> {
+    let policy = match &options.policy {
+        None => return Ok(fp_code(master, key, length)?),
+        Some(policy) => policy.parse::<Policy>()?,
+    };
+
+    let full = fp_code(master, key, MAX_LENGTH)?;
+    let length = shortest_length_satisfying(&full, length, &policy)?;
+    Ok(full[..length].to_string())
+}
+
+/// Server-side state: the master password and whether it may be replaced
+pub struct RpcSession {
+    master: Option<String>,
+    master_was_set: bool,
+    allow_rekey: bool,
+}
+
+impl RpcSession {
+    /// Starts a session without a master password
+    ///
+    /// With `allow_rekey`, `setMaster` may be called again, including after `lock`.
+    pub fn new(allow_rekey: bool) -> Self {
+        RpcSession {
+            master: None,
+            master_was_set: false,
+            allow_rekey,
+        }
+    }
+
+    /// Returns `true` once a master password has been set and then locked
+    pub fn is_locked(&self) -> bool {
+        self.master_was_set && self.master.is_none()
+    }
+
+    /// Forgets the master password
+    pub fn lock(&mut self) {
+        self.master = None;
+    }
+
+    /// Handles one message, a single request or a batch
+    ///
+    /// Returns the response to send, or `None` if the message held only
+    /// notifications.
+    pub fn handle(&mut self, message: &[u8]) -> Option<String> {
+        let message: Value = match serde_json::from_slice(message) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = ErrorObject::new(PARSE_ERROR, format!("Parse error: {}", e));
+                return Some(error_response(Value::Null, &error).to_string());
+            }
+        };
+
+        let response = match message {
+            Value::Array(batch) if batch.is_empty() => Some(error_response(
+                Value::Null,
+                &ErrorObject::invalid_request("empty batch"),
+            )),
+            Value::Array(batch) => {
+                let responses: Vec<Value> = batch
+                    .into_iter()
+                    .filter_map(|call| self.handle_call(call))
+                    .collect();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            call => self.handle_call(call),
+        };
+        response.map(|response| response.to_string())
+    }
+
+    /// Handles one request object, returning `None` for notifications
+    fn handle_call(&mut self, call: Value) -> Option<Value> {
+        let mut call = match call {
+            Value::Object(call) => call,
+            _ => {
+                let error = ErrorObject::invalid_request("expected an object");
+                return Some(error_response(Value::Null, &error));
+            }
+        };
+
+        // Without an `id` the request is a notification and gets no response
+        let id = call.remove("id");
+        let response_id = match &id {
+            None => Value::Null,
+            Some(id @ (Value::Null | Value::String(_) | Value::Number(_))) => id.clone(),
+            Some(_) => {
+                let error = ErrorObject::invalid_request("`id` must be a string, number or null");
+                return Some(error_response(Value::Null, &error));
+            }
+        };
+
+        let checked = self.check_call(&mut call);
+        let result = match checked {
+            Ok((method, params)) => self.call(&method, params),
+            // Malformed requests are answered even without an `id`
+            Err(error) => return Some(error_response(response_id, &error)),
+        };
+
+        id.map(|id| match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err(error) => error_response(id, &error),
+        })
+    }
+
+    /// Validates the envelope, returning the method and named parameters
+    fn check_call(&self, call: &mut Map<String, Value>) -> Result<(String, Value), ErrorObject> {
+        if call.get("jsonrpc") != Some(&json!("2.0")) {
+            return Err(ErrorObject::invalid_request("`jsonrpc` must be \"2.0\""));
+        }
+        let method = match call.remove("method") {
+            Some(Value::String(method)) => method,
+            _ => return Err(ErrorObject::invalid_request("`method` must be a string")),
+        };
+        let params = match call.remove("params") {
+            None => Value::Object(Map::new()),
+            Some(params @ Value::Object(_)) => params,
+            Some(Value::Array(_)) => {
+                return Err(ErrorObject::new(
+                    INVALID_PARAMS,
+                    "Invalid params: parameters must be named",
+                ))
+            }
+            Some(_) => {
+                return Err(ErrorObject::invalid_request(
+                    "`params` must be an object or an array",
+                ))
+            }
+        };
+        Ok((method, params))
+    }
+
+    /// Runs a method
+    fn call(&mut self, method: &str, params_value: Value) -> Result<Value, ErrorObject> {
+        match method {
+            "derive" => {
+                let p: DeriveParams = params(params_value)?;
+                let options = p.options.unwrap_or_default();
+                let password = derive(self.master()?, &p.key, p.length, &options)?;
+                Ok(json!({ "password": password }))
+            }
+            "deriveBatch" => {
+                let p: DeriveBatchParams = params(params_value)?;
+                let options = p.options.unwrap_or_default();
+                let master = self.master()?;
+                let passwords = p
+                    .keys
+                    .iter()
+                    .map(|key| derive(master, key, p.length, &options))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(json!({ "passwords": passwords }))
+            }
+            "setMaster" => {
+                // serde's message can quote the value, which here is the master password
+                let p: SetMasterParams = serde_json::from_value(params_value).map_err(|_| {
+                    ErrorObject::new(
+                        INVALID_PARAMS,
+                        "Invalid params: expected a string `password` and nothing else",
+                    )
+                })?;
+                if self.master_was_set && !self.allow_rekey {
+                    return Err(ErrorObject::new(
+                        MASTER_ALREADY_SET,
+                        "Master password is already set",
+                    ));
+                }
+                self.master = Some(p.password);
+                self.master_was_set = true;
+                Ok(self.status())
+            }
+            "lock" => {
+                self.lock();
+                Ok(self.status())
+            }
+            "status" => Ok(self.status()),
+            _ => Err(ErrorObject::new(
+                METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        }
+    }
+
+    /// Returns the master password, or why there is none
+    fn master(&self) -> Result<&str, ErrorObject> {
+        match &self.master {
+            Some(master) => Ok(master),
+            None if self.master_was_set => {
+                Err(ErrorObject::new(SESSION_LOCKED, "Session is locked"))
+            }
+            None => Err(ErrorObject::new(
+                MASTER_NOT_SET,
+                "Master password is not set; call setMaster first",
+            )),
+        }
+    }
+
+    /// `unset`, `unlocked` or `locked`
+    fn state(&self) -> &'static str {
+        if self.master.is_some() {
+            "unlocked"
+        } else if self.master_was_set {
+            "locked"
+        } else {
+            "unset"
+        }
+    }
+
+    /// The result of `status`
+    fn status(&self) -> Value {
+        json!({ "state": self.state(), "allowRekey": self.allow_rekey })
+    }
+}
+
+impl fmt::Debug for RpcSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RpcSession")
+            .field("state", &self.state())
+            .field("allow_rekey", &self.allow_rekey)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serves requests from `reader` until it is closed, writing responses to `writer`
+///
+/// # Errors
+///
+/// Returns any transport error; protocol errors are answered with JSON-RPC error
+/// responses instead.
+pub fn serve<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    session: &mut RpcSession,
+) -> Result<(), RpcError> {
+    let mut messages = MessageReader::new(reader);
+    while let Some(message) = messages.read_message()? {
+        if let Some(response) = session.handle(&message) {
+            let framing = messages.framing().unwrap_or(Framing::Newline);
+            write_message(writer, framing, response.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    /// Reader that hands out at most one byte per call
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn read_all(input: &[u8]) -> (Vec<Vec<u8>>, Option<Framing>) {
+        let mut reader = MessageReader::new(BufReader::new(Trickle(input)));
+        let mut messages = Vec::new();
+        while let Some(message) = reader.read_message().unwrap() {
+            messages.push(message);
+        }
+        (messages, reader.framing())
+    }
+
+    fn read_error(input: &[u8]) -> RpcError {
+        let mut reader = MessageReader::new(input);
+        loop {
+            match reader.read_message() {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("no error in {:?}", String::from_utf8_lossy(input)),
+                Err(e) => return e,
+            }
+        }
+    }
+
+    /// Sends one message and parses the response
+    fn send(session: &mut RpcSession, message: &str) -> Value {
+        let response = session.handle(message.as_bytes()).expect("a response");
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string()
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    fn unlocked() -> RpcSession {
+        let mut session = RpcSession::new(false);
+        send(
+            &mut session,
+            &request(0, "setMaster", json!({ "password": "test" })),
+        );
+        session
+    }
+
+    // Framing
+
+    #[test]
+    fn test_newline_framing() {
+        let (messages, framing) = read_all(b"{\"a\":1}\n\n  \r\n{\"b\":2}\r\n{\"c\":3}");
+        assert_eq!(framing, Some(Framing::Newline));
+        assert_eq!(
+            messages,
+            [&b"{\"a\":1}"[..], &b"{\"b\":2}"[..], &b"{\"c\":3}"[..]]
+        );
+    }
+
+    #[test]
+    fn test_content_length_framing() {
+        let input = b"Content-Length: 7\r\n\r\n{\"a\":1}\
+            content-length:7\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{\"b\":2}\
+            \r\nContent-Length: 2\r\n\r\n[]";
+        let (messages, framing) = read_all(input);
+        assert_eq!(framing, Some(Framing::ContentLength));
+        assert_eq!(messages, [&b"{\"a\":1}"[..], &b"{\"b\":2}"[..], &b"[]"[..]]);
+    }
+
+    #[test]
+    fn test_content_length_body_may_contain_newlines() {
+        let (messages, _) = read_all(b"Content-Length: 9\n\n{\n\"a\":1\n}");
+        assert_eq!(messages, [b"{\n\"a\":1\n}"]);
+    }
+
+    #[test]
+    fn test_framing_is_decided_once() {
+        // After header framing is chosen, a bare JSON line is a malformed header
+        let error = read_error(b"Content-Length: 2\r\n\r\n{}{\"a\":1}\n");
+        assert!(matches!(error, RpcError::MalformedHeader(_)));
+
+        // ...and after newline framing, a header line is just a message
+        let (messages, framing) = read_all(b"{}\nContent-Length: 2\n");
+        assert_eq!(framing, Some(Framing::Newline));
+        assert_eq!(messages[1], b"Content-Length: 2");
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        assert_eq!(read_all(b""), (Vec::new(), None));
+        assert_eq!(read_all(b"\n\r\n"), (Vec::new(), None));
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let error = read_error(b"Content-Length: 2\r\nno colon\r\n\r\n{}");
+        assert_eq!(
+            error.to_string(),
+            "Malformed RPC header: expected `Name: value`, got `no colon`"
+        );
+        let error = read_error(b"Content-Length: two\r\n\r\n{}");
+        assert_eq!(
+            error.to_string(),
+            "Malformed RPC header: invalid Content-Length `two`"
+        );
+        let error = read_error(b"Content-Length: 2\r\n\r\n{}Content-Type: json\r\n\r\n{}");
+        assert_eq!(
+            error.to_string(),
+            "Malformed RPC header: missing Content-Length"
+        );
+    }
+
+    #[test]
+    fn test_truncated_content_length_message() {
+        assert!(matches!(
+            read_error(b"Content-Length: 10\r\n\r\n{}"),
+            RpcError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            read_error(b"Content-Length: 10\r\n"),
+            RpcError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let header = format!("Content-Length: {}\r\n\r\n", MAX_MESSAGE_LEN + 1);
+        assert!(matches!(
+            read_error(header.as_bytes()),
+            RpcError::MessageTooLarge(len) if len == MAX_MESSAGE_LEN + 1
+        ));
+
+        let line = vec![b'x'; MAX_MESSAGE_LEN + 10];
+        assert!(matches!(
+            read_error(&line),
+            RpcError::MessageTooLarge(len) if len == MAX_MESSAGE_LEN + 1
+        ));
+
+        let mut line = vec![b'x'; MAX_MESSAGE_LEN];
+        line.push(b'\n');
+        assert_eq!(read_all(&line).0[0].len(), MAX_MESSAGE_LEN);
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut output = Vec::new();
+        write_message(&mut output, Framing::Newline, b"{}").unwrap();
+        write_message(
+            &mut output,
+            Framing::ContentLength,
+            "{\"k\":\"密\"}".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{}\nContent-Length: 11\r\n\r\n{\"k\":\"密\"}"
+        );
+    }
+
+    // Envelope
+
+    #[test]
+    fn test_parse_error() {
+        let response = send(
+            &mut RpcSession::new(false),
+            "{\"jsonrpc\": \"2.0\", \"method\"",
+        );
+        assert_eq!(error_code(&response), PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = send(&mut RpcSession::new(false), "\u{fffd}");
+        assert_eq!(error_code(&response), PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let cases = [
+            ("1", Value::Null),
+            (r#"{"id":1,"method":"status"}"#, json!(1)),
+            (r#"{"jsonrpc":"1.0","id":1,"method":"status"}"#, json!(1)),
+            (r#"{"jsonrpc":"2.0","id":"a","method":7}"#, json!("a")),
+            (r#"{"jsonrpc":"2.0","id":1}"#, json!(1)),
+            (
+                r#"{"jsonrpc":"2.0","id":[1],"method":"status"}"#,
+                Value::Null,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","id":1,"method":"status","params":3}"#,
+                json!(1),
+            ),
+            // Answered even though it has no `id`
+            (r#"{"jsonrpc":"2.0","method":1}"#, Value::Null),
+        ];
+        for (message, id) in cases {
+            let response = send(&mut RpcSession::new(false), message);
+            assert_eq!(error_code(&response), INVALID_REQUEST, "{}", message);
+            assert_eq!(response["id"], id, "{}", message);
+            assert_eq!(response["jsonrpc"], "2.0");
+        }
+    }
+
+    #[test]
+    fn test_positional_params_are_rejected() {
+        let message = r#"{"jsonrpc":"2.0","id":1,"method":"derive","params":["github.com",16]}"#;
+        let response = send(&mut unlocked(), message);
+        assert_eq!(error_code(&response), INVALID_PARAMS);
+        assert_eq!(
+            response["error"]["message"],
+            "Invalid params: parameters must be named"
+        );
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let response = send(
+            &mut RpcSession::new(false),
+            &request(3, "shutdown", json!({})),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "error": { "code": METHOD_NOT_FOUND, "message": "Method not found: shutdown" },
+                "id": 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_notifications_get_no_response() {
+        let mut session = RpcSession::new(false);
+        let notification = r#"{"jsonrpc":"2.0","method":"setMaster","params":{"password":"test"}}"#;
+        assert_eq!(session.handle(notification.as_bytes()), None);
+        // Errors from notifications are not reported either
+        let unknown = r#"{"jsonrpc":"2.0","method":"shutdown"}"#;
+        assert_eq!(session.handle(unknown.as_bytes()), None);
+        // ...but the notification still took effect
+        assert_eq!(
+            send(&mut session, &request(1, "status", json!({})))["result"]["state"],
+            "unlocked"
+        );
+    }
+
+    #[test]
+    fn test_null_and_string_ids() {
+        let message = r#"{"jsonrpc":"2.0","id":null,"method":"status"}"#;
+        let response = send(&mut RpcSession::new(false), message);
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["result"]["state"], "unset");
+
+        let message = r#"{"jsonrpc":"2.0","id":"req-1","method":"status"}"#;
+        assert_eq!(send(&mut RpcSession::new(false), message)["id"], "req-1");
+    }
+
+    // Batches
+
+    #[test]
+    fn test_batch() {
+        let mut session = unlocked();
+        let batch = format!(
+            "[{}, {}, {}, 7, {}]",
+            request(1, "derive", json!({ "key": "github.com", "length": 16 })),
+            r#"{"jsonrpc":"2.0","method":"status"}"#,
+            request(2, "derive", json!({ "key": "github.com", "length": 99 })),
+            request(3, "nope", json!({})),
+        );
+        let responses = send(&mut session, &batch);
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"]["password"], "D04175F7A9c7Ab4a");
+        assert_eq!(error_code(&responses[1]), INVALID_LENGTH);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(error_code(&responses[2]), INVALID_REQUEST);
+        assert_eq!(responses[2]["id"], Value::Null);
+        assert_eq!(error_code(&responses[3]), METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        let response = send(&mut RpcSession::new(false), "[]");
+        assert_eq!(error_code(&response), INVALID_REQUEST);
+        assert_eq!(response["error"]["message"], "Invalid request: empty batch");
+    }
+
+    #[test]
+    fn test_batch_of_notifications() {
+        let batch = r#"[{"jsonrpc":"2.0","method":"status"},{"jsonrpc":"2.0","method":"lock"}]"#;
+        assert_eq!(RpcSession::new(false).handle(batch.as_bytes()), None);
+    }
+
+    // Methods
+
+    #[test]
+    fn test_derive_before_set_master() {
+        let mut session = RpcSession::new(false);
+        for message in [
+            request(1, "derive", json!({ "key": "github.com", "length": 16 })),
+            request(
+                2,
+                "deriveBatch",
+                json!({ "keys": ["github.com"], "length": 16 }),
+            ),
+        ] {
+            let response = send(&mut session, &message);
+            assert_eq!(error_code(&response), MASTER_NOT_SET);
+            assert_eq!(
+                response["error"]["message"],
+                "Master password is not set; call setMaster first"
+            );
+        }
+    }
+
+    #[test]
+    fn test_derive() {
+        let response = send(
+            &mut unlocked(),
+            &request(1, "derive", json!({ "key": "github.com", "length": 16 })),
+        );
+        assert_eq!(
+            response,
+            json!({ "jsonrpc": "2.0", "result": { "password": "D04175F7A9c7Ab4a" }, "id": 1 })
+        );
+    }
+
+    #[test]
+    fn test_derive_batch() {
+        let response = send(
+            &mut unlocked(),
+            &request(
+                1,
+                "deriveBatch",
+                json!({ "keys": ["github.com", "example.com"], "length": 16 }),
+            ),
+        );
+        assert_eq!(
+            response["result"]["passwords"],
+            json!([
+                "D04175F7A9c7Ab4a",
+                fp_code("test", "example.com", 16).unwrap()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_length_error() {
+        let response = send(
+            &mut unlocked(),
+            &request(1, "derive", json!({ "key": "github.com", "length": 33 })),
+        );
+        assert_eq!(
+            response["error"],
+            json!({
+                "code": INVALID_LENGTH,
+                "message": "Length must be between 2 and 32, got: 33",
+                "data": { "kind": "InvalidLength", "length": 33 },
+            })
+        );
+
+        let response = send(
+            &mut unlocked(),
+            &request(1, "deriveBatch", json!({ "keys": ["a", "b"], "length": 1 })),
+        );
+        assert_eq!(error_code(&response), INVALID_LENGTH);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let cases = [
+            json!({ "key": "github.com" }),
+            json!({ "key": 7, "length": 16 }),
+            json!({ "key": "github.com", "length": -1 }),
+            json!({ "key": "github.com", "length": 16, "extra": true }),
+            json!({ "key": "github.com", "length": 16, "options": { "symbols": true } }),
+        ];
+        for params in cases {
+            let response = send(&mut unlocked(), &request(1, "derive", params.clone()));
+            assert_eq!(error_code(&response), INVALID_PARAMS, "{}", params);
+        }
+
+        let response = send(
+            &mut RpcSession::new(false),
+            &request(1, "setMaster", json!({})),
+        );
+        assert_eq!(error_code(&response), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_derive_with_policy() {
+        // As in the `policy` example, the policy needs one character more than 12
+        let full = fp_code("test", "site33.example", 32).unwrap();
+        let mut session = unlocked();
+        let params = json!({
+            "key": "site33.example",
+            "length": 12,
+            "options": { "policy": "require-digit,require-upper,require-lower" },
+        });
+        let response = send(&mut session, &request(1, "derive", params));
+        assert_eq!(response["result"]["password"], full[..13]);
+
+        let params = json!({ "key": "a", "length": 16, "options": { "policy": "require-symbol" } });
+        let response = send(&mut session, &request(2, "derive", params));
+        assert_eq!(error_code(&response), INVALID_PARAMS);
+
+        let params = json!({ "key": "a", "length": 16, "options": null });
+        let response = send(&mut session, &request(3, "derive", params));
+        assert_eq!(
+            response["result"]["password"],
+            fp_code("test", "a", 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_policy_errors() {
+        assert_eq!(
+            ErrorObject::from(PolicyError::InvalidLength(40)),
+            ErrorObject::from(FlowerPasswordError::InvalidLength(40))
+        );
+        let error = ErrorObject::from(PolicyError::Unsatisfiable(vec![
+            crate::policy::Requirement::Digit,
+        ]));
+        assert_eq!(error.code, UNSATISFIABLE_POLICY);
+        assert_eq!(error.data, Some(json!({ "unmet": ["require-digit"] })));
+        assert_eq!(
+            ErrorObject::from(PolicyError::EmptyRequirement).code,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn test_set_master_once() {
+        let mut session = RpcSession::new(false);
+        let response = send(
+            &mut session,
+            &request(1, "setMaster", json!({ "password": "test" })),
+        );
+        assert_eq!(
+            response["result"],
+            json!({ "state": "unlocked", "allowRekey": false })
+        );
+
+        for id in [2, 3] {
+            let response = send(
+                &mut session,
+                &request(id, "setMaster", json!({ "password": "other" })),
+            );
+            assert_eq!(error_code(&response), MASTER_ALREADY_SET);
+            // Locking does not allow a new master password either
+            send(&mut session, &request(9, "lock", json!({})));
+        }
+    }
+
+    #[test]
+    fn test_rekey() {
+        let mut session = RpcSession::new(true);
+        send(
+            &mut session,
+            &request(1, "setMaster", json!({ "password": "other" })),
+        );
+        send(
+            &mut session,
+            &request(2, "setMaster", json!({ "password": "test" })),
+        );
+        let derive = request(3, "derive", json!({ "key": "github.com", "length": 16 }));
+        assert_eq!(
+            send(&mut session, &derive)["result"]["password"],
+            "D04175F7A9c7Ab4a"
+        );
+
+        // Rekeying also unlocks a locked session
+        send(&mut session, &request(4, "lock", json!({})));
+        send(
+            &mut session,
+            &request(5, "setMaster", json!({ "password": "test" })),
+        );
+        assert_eq!(
+            send(&mut session, &derive)["result"]["password"],
+            "D04175F7A9c7Ab4a"
+        );
+    }
+
+    #[test]
+    fn test_lock_and_status() {
+        let mut session = RpcSession::new(false);
+        let status = request(1, "status", json!({}));
+        assert_eq!(
+            send(&mut session, &status)["result"],
+            json!({ "state": "unset", "allowRekey": false })
+        );
+        assert!(!session.is_locked());
+
+        send(
+            &mut session,
+            &request(2, "setMaster", json!({ "password": "test" })),
+        );
+        assert_eq!(send(&mut session, &status)["result"]["state"], "unlocked");
+
+        let response = send(&mut session, &request(3, "lock", json!({})));
+        assert_eq!(response["result"]["state"], "locked");
+        assert!(session.is_locked());
+
+        let response = send(
+            &mut session,
+            &request(4, "derive", json!({ "key": "github.com", "length": 16 })),
+        );
+        assert_eq!(error_code(&response), SESSION_LOCKED);
+        assert_eq!(response["error"]["message"], "Session is locked");
+    }
+
+    #[test]
+    fn test_session_debug_hides_master() {
+        let debug = format!("{:?}", unlocked());
+        assert_eq!(
+            debug,
+            r#"RpcSession { state: "unlocked", allow_rekey: false, .. }"#
+        );
+        assert!(!debug.contains("test"));
+    }
+
+    // Serving
+
+    #[test]
+    fn test_serve_newline() {
+        let input = format!(
+            "{}\n{}\n{}\n",
+            request(1, "setMaster", json!({ "password": "test" })),
+            r#"{"jsonrpc":"2.0","method":"status"}"#,
+            request(2, "derive", json!({ "key": "github.com", "length": 16 })),
+        );
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, &mut RpcSession::new(false)).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let response: Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(response["result"]["password"], "D04175F7A9c7Ab4a");
+    }
+
+    #[test]
+    fn test_serve_content_length() {
+        let mut input = Vec::new();
+        for message in [
+            request(1, "setMaster", json!({ "password": "test" })),
+            request(2, "derive", json!({ "key": "github.com", "length": 16 })),
+        ] {
+            write_message(&mut input, Framing::ContentLength, message.as_bytes()).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(input.as_slice(), &mut output, &mut RpcSession::new(false)).unwrap();
+
+        let mut responses = MessageReader::new(output.as_slice());
+        responses.read_message().unwrap().unwrap();
+        let response: Value =
+            serde_json::from_slice(&responses.read_message().unwrap().unwrap()).unwrap();
+        assert_eq!(responses.framing(), Some(Framing::ContentLength));
+        assert_eq!(response["result"]["password"], "D04175F7A9c7Ab4a");
+        assert!(responses.read_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_serve_stops_on_transport_errors() {
+        let input = b"Content-Length: x\r\n\r\n{}";
+        let mut output = Vec::new();
+        let error = serve(&input[..], &mut output, &mut RpcSession::new(false)).unwrap_err();
+        assert!(matches!(error, RpcError::MalformedHeader(_)));
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+//! Serde support for an optional [`Scheme`] written by its name, `v1` or `v2`
+//!
+//! Used with `#[serde(with = "crate::scheme_name")]` by the configuration and
+//! the agent protocol. A name this build has no scheme for fails to
+//! deserialize rather than falling back to another scheme, since that would
+//! derive a different password.
+
+use crate::Scheme;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(
+    scheme: &Option<Scheme>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match scheme {
+        Some(scheme) => serializer.serialize_str(scheme.name()),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Scheme>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match Scheme::from_name(&name) {
+        Some(scheme) => Ok(Some(scheme)),
+        None => Err(D::Error::custom(unavailable(&name))),
+    }
+}
+
+/// Explains why there is no scheme named `name`
+pub(crate) fn unavailable(name: &str) -> String {
+    match name {
+        "v1" | "v2" => format!("scheme {} is not compiled into this build", name),
+        _ => format!("unknown scheme `{}`, expected v1 or v2", name),
+    }
+}
@@ -0,0 +1,61 @@
+//! `secrecy` inputs
+//!
+//! Applications that keep the master password in a [`SecretString`] can derive
+//! without calling `expose_secret` themselves: the secret is only exposed for the
+//! duration of the derivation, and errors never carry it.
+
+use crate::{fp_code, FlowerPasswordError};
+use alloc::string::String;
+use secrecy::{ExposeSecret, SecretString};
+
+/// Generates a Flower Password from a master password held in a [`SecretString`]
+///
+/// Produces the same passwords as [`fp_code`].
+///
+/// # Errors
+///
+/// Returns `FlowerPasswordError::InvalidLength` if length is not between 2 and 32.
+///
+/// # Example
+///
+/// ```
+/// use flowerpassword::fp_code_secret_input;
+/// use secrecy::SecretString;
+///
+/// let master = SecretString::from("test");
+/// assert_eq!(fp_code_secret_input(&master, "github.com", 16).unwrap(), "D04175F7A9c7Ab4a");
+/// ```
+pub fn fp_code_secret_input(
+    password: &SecretString,
+    key: &str,
+    length: usize,
+) -> Result<String, FlowerPasswordError> {
+    fp_code(password.expose_secret(), key, length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::KNOWN_ANSWERS;
+
+    #[test]
+    fn test_matches_fp_code() {
+        for vector in KNOWN_ANSWERS {
+            let master = SecretString::from(vector.password);
+            assert_eq!(
+                fp_code_secret_input(&master, vector.key, vector.length).unwrap(),
+                fp_code(vector.password, vector.key, vector.length).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let master = SecretString::from("hunter2-master");
+        assert!(!format!("{:?}", master).contains("hunter2"));
+
+        let error = fp_code_secret_input(&master, "github.com", 40).unwrap_err();
+        assert!(!format!("{:?}", error).contains("hunter2"));
+        assert!(!error.to_string().contains("hunter2"));
+    }
+}
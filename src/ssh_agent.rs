@@ -0,0 +1,759 @@
+//! ssh-agent master source
+//!
+//! Derives the master password from a signature made by a key held in
+//! `ssh-agent`, so machines that share an SSH key share the master without it
+//! ever being typed. The agent is asked to sign [`CHALLENGE`], and the
+//! SHA-256 of the signature blob, in lowercase hex, is the master:
+//!
+//! ```text
+//! master = hex(SHA-256(signature blob of CHALLENGE))
+//! ```
+//!
+//! This only works if signing the same message twice gives the same signature.
+//! Ed25519 signatures are deterministic. ECDSA signatures use a random nonce, RSA
+//! signatures depend on the scheme the agent picks, and security-key (`sk-*`)
+//! signatures include a counter, so only `ssh-ed25519` keys are accepted and
+//! everything else is rejected with [`SshAgentError::NondeterministicKey`].
+//!
+//! The client speaks the [agent protocol] over any `Read + Write` stream; on Unix,
+//! [`AgentClient::connect_env`] connects to `$SSH_AUTH_SOCK`.
+//!
+//! [agent protocol]: https://datatracker.ietf.org/doc/html/draft-miller-ssh-agent
+//!
+//! # Example
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use flowerpassword::ssh_agent::{master_from_agent, AgentClient};
+//!
+//! let mut agent = AgentClient::connect_env()?;
+//! let master = master_from_agent(&mut agent, None)?;
+//! let password = flowerpassword::fp_code(&master, "github.com", 16)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+
+/// Message signed by the agent key, fixed so the master never changes
+pub const CHALLENGE: &[u8] = b"flowerpassword ssh-agent master v1";
+
+/// Largest agent message accepted, in bytes, as in OpenSSH
+pub const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// The only key type whose signatures are deterministic
+pub const ED25519: &str = "ssh-ed25519";
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Error type for ssh-agent operations
+#[derive(Debug)]
+pub enum SshAgentError {
+    /// Reading or writing the agent connection failed
+    Io(io::Error),
+    /// `SSH_AUTH_SOCK` is not set
+    NoAgent,
+    /// A message is larger than [`MAX_MESSAGE_LEN`]
+    MessageTooLarge(usize),
+    /// The agent sent something that is not a valid reply
+    Malformed(String),
+    /// The agent refused the request
+    Refused,
+    /// No key in the agent has the requested fingerprint
+    KeyNotFound(String),
+    /// The agent holds no Ed25519 key
+    NoEligibleKey,
+    /// The agent holds several Ed25519 keys and none was chosen
+    AmbiguousKey(usize),
+    /// The chosen key's signatures are not deterministic
+    NondeterministicKey(String),
+    /// A `--master` value that is not `ssh-agent` or `ssh-agent:<fingerprint>`
+    ///
+    /// The value is not kept, since it may be a master password given by mistake.
+    InvalidSource,
+}
+
+impl fmt::Display for SshAgentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SshAgentError::Io(e) => write!(f, "ssh-agent connection failed: {}", e),
+            SshAgentError::NoAgent => write!(f, "SSH_AUTH_SOCK is not set"),
+            SshAgentError::MessageTooLarge(len) => write!(
+                f,
+                "Message of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_MESSAGE_LEN
+            ),
+            SshAgentError::Malformed(message) => {
+                write!(f, "Malformed ssh-agent reply: {}", message)
+            }
+            SshAgentError::Refused => write!(f, "ssh-agent refused the request"),
+            SshAgentError::KeyNotFound(fingerprint) => {
+                write!(f, "No key with fingerprint {} in ssh-agent", fingerprint)
+            }
+            SshAgentError::NoEligibleKey => write!(f, "No Ed25519 key in ssh-agent"),
+            SshAgentError::AmbiguousKey(count) => write!(
+                f,
+                "ssh-agent holds {} Ed25519 keys, choose one by fingerprint",
+                count
+            ),
+            SshAgentError::NondeterministicKey(key_type) => write!(
+                f,
+                "{} keys do not sign deterministically, use an Ed25519 key",
+                key_type
+            ),
+            SshAgentError::InvalidSource => {
+                write!(
+                    f,
+                    "Invalid master source, expected ssh-agent or ssh-agent:<fingerprint>"
+                )
+            }
+        }
+    }
+}
+
+impl Error for SshAgentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SshAgentError::Io(e) => Some(e),
+            SshAgentError::NoAgent
+            | SshAgentError::MessageTooLarge(_)
+            | SshAgentError::Malformed(_)
+            | SshAgentError::Refused
+            | SshAgentError::KeyNotFound(_)
+            | SshAgentError::NoEligibleKey
+            | SshAgentError::AmbiguousKey(_)
+            | SshAgentError::NondeterministicKey(_)
+            | SshAgentError::InvalidSource => None,
+        }
+    }
+}
+
+impl From<io::Error> for SshAgentError {
+    fn from(e: io::Error) -> Self {
+        SshAgentError::Io(e)
+    }
+}
+
+/// A public key held by the agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Public key in SSH wire format
+    pub key_blob: Vec<u8>,
+    /// Comment the key was added with, usually its file name
+    pub comment: String,
+}
+
+impl Identity {
+    /// Returns the key type, such as `ssh-ed25519`, read from the key blob
+    pub fn key_type(&self) -> Option<&str> {
+        let mut blob = self.key_blob.as_slice();
+        let key_type = read_string(&mut blob).ok()?;
+        std::str::from_utf8(key_type).ok()
+    }
+
+    /// Returns whether this key signs deterministically
+    pub fn is_deterministic(&self) -> bool {
+        self.key_type() == Some(ED25519)
+    }
+
+    /// Returns the OpenSSH fingerprint: `SHA256:` and the unpadded base64 SHA-256
+    /// of the key blob, as `ssh-add -l` prints it
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(&self.key_blob);
+        format!("SHA256:{}", base64_unpadded(&digest))
+    }
+}
+
+/// Encodes `bytes` as standard base64 without `=` padding
+fn base64_unpadded(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((bytes.len() * 4 + 2) / 3);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Reads an SSH `string`: a big-endian `u32` length and that many bytes
+fn read_string<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], SshAgentError> {
+    let len = read_u32(data)? as usize;
+    if data.len() < len {
+        return Err(SshAgentError::Malformed(
+            "string runs past the end".to_string(),
+        ));
+    }
+    let (string, rest) = data.split_at(len);
+    *data = rest;
+    Ok(string)
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32, SshAgentError> {
+    if data.len() < 4 {
+        return Err(SshAgentError::Malformed("truncated integer".to_string()));
+    }
+    let (int, rest) = data.split_at(4);
+    *data = rest;
+    Ok(u32::from_be_bytes([int[0], int[1], int[2], int[3]]))
+}
+
+fn write_string(out: &mut Vec<u8>, string: &[u8]) {
+    out.extend_from_slice(&(string.len() as u32).to_be_bytes());
+    out.extend_from_slice(string);
+}
+
+/// Client side of an agent connection
+pub struct AgentClient<S> {
+    stream: S,
+}
+
+#[cfg(unix)]
+impl AgentClient<std::os::unix::net::UnixStream> {
+    /// Connects to the agent at `$SSH_AUTH_SOCK`
+    ///
+    /// # Errors
+    ///
+    /// Returns `SshAgentError::NoAgent` if the variable is unset, or
+    /// `SshAgentError::Io` if the socket cannot be reached.
+    pub fn connect_env() -> Result<Self, SshAgentError> {
+        let path = std::env::var_os("SSH_AUTH_SOCK").ok_or(SshAgentError::NoAgent)?;
+        Ok(AgentClient::new(std::os::unix::net::UnixStream::connect(
+            path,
+        )?))
+    }
+}
+
+impl<S: Read + Write> AgentClient<S> {
+    /// Wraps an established connection to an agent
+    pub fn new(stream: S) -> Self {
+        AgentClient { stream }
+    }
+
+    /// Lists the keys the agent holds
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the agent refuses or sends a malformed reply.
+    pub fn list_identities(&mut self) -> Result<Vec<Identity>, SshAgentError> {
+        let reply = self.request(&[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let mut body = expect_reply(&reply, SSH_AGENT_IDENTITIES_ANSWER)?;
+        let count = read_u32(&mut body)?;
+        let mut identities = Vec::new();
+        for _ in 0..count {
+            let key_blob = read_string(&mut body)?.to_vec();
+            let comment = String::from_utf8_lossy(read_string(&mut body)?).into_owned();
+            identities.push(Identity { key_blob, comment });
+        }
+        Ok(identities)
+    }
+
+    /// Asks the agent to sign `data` with `identity`, returning the signature blob
+    ///
+    /// # Errors
+    ///
+    /// Returns `SshAgentError::Refused` if the agent does not hold the key or the
+    /// user declines a confirmation prompt.
+    pub fn sign(&mut self, identity: &Identity, data: &[u8]) -> Result<Vec<u8>, SshAgentError> {
+        let mut message = vec![SSH_AGENTC_SIGN_REQUEST];
+        write_string(&mut message, &identity.key_blob);
+        write_string(&mut message, data);
+        message.extend_from_slice(&0u32.to_be_bytes());
+
+        let reply = self.request(&message)?;
+        let mut body = expect_reply(&reply, SSH_AGENT_SIGN_RESPONSE)?;
+        Ok(read_string(&mut body)?.to_vec())
+    }
+
+    /// Sends one message and reads the reply
+    fn request(&mut self, message: &[u8]) -> Result<Vec<u8>, SshAgentError> {
+        self.stream
+            .write_all(&(message.len() as u32).to_be_bytes())?;
+        self.stream.write_all(message)?;
+        self.stream.flush()?;
+
+        let mut len = [0; 4];
+        self.stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(SshAgentError::MessageTooLarge(len));
+        }
+        let mut reply = vec![0; len];
+        self.stream.read_exact(&mut reply)?;
+        Ok(reply)
+    }
+}
+
+impl<S> fmt::Debug for AgentClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AgentClient").finish_non_exhaustive()
+    }
+}
+
+/// Checks the reply type and returns the rest of the reply
+fn expect_reply(reply: &[u8], expected: u8) -> Result<&[u8], SshAgentError> {
+    match reply.split_first() {
+        Some((&kind, body)) if kind == expected => Ok(body),
+        Some((&SSH_AGENT_FAILURE, _)) => Err(SshAgentError::Refused),
+        Some((&kind, _)) => Err(SshAgentError::Malformed(format!(
+            "unexpected reply type {}",
+            kind
+        ))),
+        None => Err(SshAgentError::Malformed("empty reply".to_string())),
+    }
+}
+
+/// Chooses the key to derive the master from
+///
+/// With a `fingerprint`, the key with that fingerprint is chosen and must be Ed25519.
+/// Without one, the agent must hold exactly one Ed25519 key.
+///
+/// # Errors
+///
+/// Returns `KeyNotFound`, `NondeterministicKey`, `NoEligibleKey` or `AmbiguousKey`.
+pub fn select_identity<'a>(
+    identities: &'a [Identity],
+    fingerprint: Option<&str>,
+) -> Result<&'a Identity, SshAgentError> {
+    if let Some(fingerprint) = fingerprint {
+        let identity = identities
+            .iter()
+            .find(|identity| identity.fingerprint() == fingerprint)
+            .ok_or_else(|| SshAgentError::KeyNotFound(fingerprint.to_string()))?;
+        if !identity.is_deterministic() {
+            let key_type = identity.key_type().unwrap_or("Unknown");
+            return Err(SshAgentError::NondeterministicKey(key_type.to_string()));
+        }
+        return Ok(identity);
+    }
+
+    let mut eligible = identities
+        .iter()
+        .filter(|identity| identity.is_deterministic());
+    match (eligible.next(), eligible.count()) {
+        (None, _) => Err(SshAgentError::NoEligibleKey),
+        (Some(identity), 0) => Ok(identity),
+        (Some(_), others) => Err(SshAgentError::AmbiguousKey(others + 1)),
+    }
+}
+
+/// Derives the master password from the agent key chosen by `fingerprint`
+///
+/// The master is 64 lowercase hex characters in a buffer that is zeroed on drop.
+///
+/// # Errors
+///
+/// Returns any error from [`select_identity`] or the agent.
+pub fn master_from_agent<S: Read + Write>(
+    agent: &mut AgentClient<S>,
+    fingerprint: Option<&str>,
+) -> Result<Zeroizing<String>, SshAgentError> {
+    let identities = agent.list_identities()?;
+    let identity = select_identity(&identities, fingerprint)?;
+    let signature = Zeroizing::new(agent.sign(identity, CHALLENGE)?);
+    let digest = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(&*signature)));
+
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut master = Zeroizing::new(String::with_capacity(64));
+    for byte in digest.iter() {
+        master.push(HEX[(byte >> 4) as usize] as char);
+        master.push(HEX[(byte & 0x0f) as usize] as char);
+    }
+    Ok(master)
+}
+
+/// Parses a `--master` value of the form `ssh-agent` or `ssh-agent:<fingerprint>`,
+/// returning the fingerprint if one is given
+///
+/// # Errors
+///
+/// Returns `SshAgentError::InvalidSource` if `spec` does not name the ssh-agent
+/// source.
+pub fn parse_source(spec: &str) -> Result<Option<&str>, SshAgentError> {
+    match spec.split_once(':') {
+        None if spec == "ssh-agent" => Ok(None),
+        Some(("ssh-agent", fingerprint)) if !fingerprint.is_empty() => Ok(Some(fingerprint)),
+        _ => Err(SshAgentError::InvalidSource),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_blob(key_type: &str, public: &[u8]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        write_string(&mut blob, key_type.as_bytes());
+        write_string(&mut blob, public);
+        blob
+    }
+
+    fn identity(key_type: &str, public: &[u8], comment: &str) -> Identity {
+        Identity {
+            key_blob: key_blob(key_type, public),
+            comment: comment.to_string(),
+        }
+    }
+
+    /// An in-memory agent that answers each request as it is written
+    ///
+    /// Ed25519 signatures are a hash of the key and data, so they repeat; other
+    /// keys mix in a counter, like a randomized signature scheme.
+    struct FakeAgent {
+        identities: Vec<Identity>,
+        refuse_signing: bool,
+        signatures: u32,
+        requests: Vec<u8>,
+        pending: Vec<u8>,
+        replies: io::Cursor<Vec<u8>>,
+    }
+
+    impl FakeAgent {
+        fn new(identities: Vec<Identity>) -> Self {
+            FakeAgent {
+                identities,
+                refuse_signing: false,
+                signatures: 0,
+                requests: Vec::new(),
+                pending: Vec::new(),
+                replies: io::Cursor::new(Vec::new()),
+            }
+        }
+
+        fn answer(&mut self, message: &[u8]) -> Vec<u8> {
+            self.requests.push(message[0]);
+            match message[0] {
+                SSH_AGENTC_REQUEST_IDENTITIES => {
+                    let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+                    reply.extend_from_slice(&(self.identities.len() as u32).to_be_bytes());
+                    for identity in &self.identities {
+                        write_string(&mut reply, &identity.key_blob);
+                        write_string(&mut reply, identity.comment.as_bytes());
+                    }
+                    reply
+                }
+                SSH_AGENTC_SIGN_REQUEST => {
+                    let mut body = &message[1..];
+                    let blob = read_string(&mut body).unwrap().to_vec();
+                    let data = read_string(&mut body).unwrap().to_vec();
+                    assert_eq!(read_u32(&mut body).unwrap(), 0);
+                    let Some(identity) = self
+                        .identities
+                        .iter()
+                        .find(|identity| identity.key_blob == blob)
+                    else {
+                        return vec![SSH_AGENT_FAILURE];
+                    };
+                    if self.refuse_signing {
+                        return vec![SSH_AGENT_FAILURE];
+                    }
+                    let key_type = identity.key_type().unwrap().to_string();
+                    let mut hasher = Sha256::new();
+                    hasher.update(&blob);
+                    hasher.update(&data);
+                    if key_type != ED25519 {
+                        self.signatures += 1;
+                        hasher.update(self.signatures.to_be_bytes());
+                    }
+                    let mut signature = Vec::new();
+                    write_string(&mut signature, key_type.as_bytes());
+                    write_string(&mut signature, &hasher.finalize());
+
+                    let mut reply = vec![SSH_AGENT_SIGN_RESPONSE];
+                    write_string(&mut reply, &signature);
+                    reply
+                }
+                _ => vec![SSH_AGENT_FAILURE],
+            }
+        }
+    }
+
+    impl Write for FakeAgent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend_from_slice(buf);
+            while self.pending.len() >= 4 {
+                let len = u32::from_be_bytes(self.pending[..4].try_into().unwrap()) as usize;
+                if self.pending.len() < 4 + len {
+                    break;
+                }
+                let message: Vec<u8> = self.pending.drain(..4 + len).skip(4).collect();
+                let reply = self.answer(&message);
+                let replies = self.replies.get_mut();
+                replies.extend_from_slice(&(reply.len() as u32).to_be_bytes());
+                replies.extend_from_slice(&reply);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for FakeAgent {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    fn keys() -> Vec<Identity> {
+        vec![
+            identity("ssh-rsa", b"rsa public key", "id_rsa"),
+            identity(ED25519, &[7; 32], "id_ed25519"),
+            identity("ecdsa-sha2-nistp256", b"ecdsa public key", "id_ecdsa"),
+        ]
+    }
+
+    #[test]
+    fn test_base64_unpadded() {
+        assert_eq!(base64_unpadded(b""), "");
+        assert_eq!(base64_unpadded(b"f"), "Zg");
+        assert_eq!(base64_unpadded(b"fo"), "Zm8");
+        assert_eq!(base64_unpadded(b"foo"), "Zm9v");
+        assert_eq!(base64_unpadded(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_unpadded(&[0xfb, 0xff]), "+/8");
+    }
+
+    #[test]
+    fn test_identity() {
+        let key = identity(ED25519, &[7; 32], "me@host");
+        assert_eq!(key.key_type(), Some(ED25519));
+        assert!(key.is_deterministic());
+        let fingerprint = key.fingerprint();
+        assert!(fingerprint.starts_with("SHA256:"));
+        // 32 digest bytes are 43 base64 characters without padding
+        assert_eq!(fingerprint.len(), "SHA256:".len() + 43);
+
+        assert!(!identity("ssh-rsa", b"n", "").is_deterministic());
+        assert!(!identity("sk-ssh-ed25519@openssh.com", b"k", "").is_deterministic());
+        let garbage = Identity {
+            key_blob: vec![0, 0, 0, 9, b'x'],
+            comment: String::new(),
+        };
+        assert_eq!(garbage.key_type(), None);
+    }
+
+    #[test]
+    fn test_list_identities() {
+        let mut agent = AgentClient::new(FakeAgent::new(keys()));
+        let identities = agent.list_identities().unwrap();
+        assert_eq!(identities, keys());
+        assert_eq!(identities[1].comment, "id_ed25519");
+    }
+
+    #[test]
+    fn test_select_identity() {
+        let keys = keys();
+        assert_eq!(select_identity(&keys, None).unwrap().comment, "id_ed25519");
+        let fingerprint = keys[1].fingerprint();
+        assert_eq!(
+            select_identity(&keys, Some(&fingerprint)).unwrap().comment,
+            "id_ed25519"
+        );
+
+        assert!(matches!(
+            select_identity(&keys, Some("SHA256:missing")),
+            Err(SshAgentError::KeyNotFound(_))
+        ));
+        let error = select_identity(&keys, Some(&keys[0].fingerprint())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ssh-rsa keys do not sign deterministically, use an Ed25519 key"
+        );
+        assert!(matches!(
+            select_identity(&keys[2..], None),
+            Err(SshAgentError::NoEligibleKey)
+        ));
+
+        let mut two = keys.clone();
+        two.push(identity(ED25519, &[8; 32], "second"));
+        assert!(matches!(
+            select_identity(&two, None),
+            Err(SshAgentError::AmbiguousKey(2))
+        ));
+        assert_eq!(
+            select_identity(&two, Some(&two[3].fingerprint()))
+                .unwrap()
+                .comment,
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_master_from_agent() {
+        let mut agent = AgentClient::new(FakeAgent::new(keys()));
+        let master = master_from_agent(&mut agent, None).unwrap();
+        assert_eq!(master.len(), 64);
+        assert!(master.bytes().all(|b| b.is_ascii_hexdigit()));
+
+        // The same key gives the same master every time
+        let again = master_from_agent(&mut agent, None).unwrap();
+        assert_eq!(master, again);
+        assert_eq!(
+            agent.stream.requests,
+            [
+                SSH_AGENTC_REQUEST_IDENTITIES,
+                SSH_AGENTC_SIGN_REQUEST,
+                SSH_AGENTC_REQUEST_IDENTITIES,
+                SSH_AGENTC_SIGN_REQUEST
+            ]
+        );
+
+        // A different key gives a different master
+        let mut other = AgentClient::new(FakeAgent::new(vec![identity(ED25519, &[9; 32], "")]));
+        assert_ne!(master, master_from_agent(&mut other, None).unwrap());
+    }
+
+    #[test]
+    fn test_master_is_hash_of_signature() {
+        let keys = keys();
+        let mut agent = AgentClient::new(FakeAgent::new(keys.clone()));
+        let signature = agent.sign(&keys[1], CHALLENGE).unwrap();
+        let expected: String = Sha256::digest(&signature)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(*master_from_agent(&mut agent, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_randomized_signatures_differ() {
+        // Why RSA and ECDSA keys are refused: their signatures need not repeat
+        let keys = keys();
+        let mut agent = AgentClient::new(FakeAgent::new(keys.clone()));
+        assert_ne!(
+            agent.sign(&keys[2], CHALLENGE).unwrap(),
+            agent.sign(&keys[2], CHALLENGE).unwrap()
+        );
+        let error = master_from_agent(&mut agent, Some(&keys[2].fingerprint())).unwrap_err();
+        assert!(
+            matches!(error, SshAgentError::NondeterministicKey(t) if t == "ecdsa-sha2-nistp256")
+        );
+        // Refused before asking for a signature
+        assert_eq!(
+            agent.stream.requests.last(),
+            Some(&SSH_AGENTC_REQUEST_IDENTITIES)
+        );
+    }
+
+    #[test]
+    fn test_agent_refuses() {
+        let mut fake = FakeAgent::new(keys());
+        fake.refuse_signing = true;
+        let mut agent = AgentClient::new(fake);
+        assert!(matches!(
+            master_from_agent(&mut agent, None),
+            Err(SshAgentError::Refused)
+        ));
+    }
+
+    #[test]
+    fn test_malformed_replies() {
+        assert!(matches!(
+            expect_reply(&[], SSH_AGENT_SIGN_RESPONSE),
+            Err(SshAgentError::Malformed(_))
+        ));
+        assert!(matches!(
+            expect_reply(&[99], SSH_AGENT_SIGN_RESPONSE),
+            Err(SshAgentError::Malformed(_))
+        ));
+
+        // An identity count larger than the reply
+        let mut reply = 5u32.to_be_bytes().to_vec();
+        reply.extend_from_slice(&[SSH_AGENT_IDENTITIES_ANSWER, 0, 0, 0, 5]);
+        let mut agent = AgentClient::new(ReadWrite(io::Cursor::new(reply)));
+        assert!(matches!(
+            agent.list_identities(),
+            Err(SshAgentError::Malformed(_))
+        ));
+
+        let reply = ((MAX_MESSAGE_LEN + 1) as u32).to_be_bytes().to_vec();
+        let mut agent = AgentClient::new(ReadWrite(io::Cursor::new(reply)));
+        assert!(matches!(
+            agent.list_identities(),
+            Err(SshAgentError::MessageTooLarge(_))
+        ));
+    }
+
+    /// Canned replies, ignoring what is written
+    struct ReadWrite(io::Cursor<Vec<u8>>);
+
+    impl Read for ReadWrite {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for ReadWrite {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(parse_source("ssh-agent").unwrap(), None);
+        assert_eq!(
+            parse_source("ssh-agent:SHA256:abc").unwrap(),
+            Some("SHA256:abc")
+        );
+        for spec in ["ssh-agent:", "agent", "yubikey:2", ""] {
+            assert!(matches!(
+                parse_source(spec),
+                Err(SshAgentError::InvalidSource)
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut fake = FakeAgent::new(keys());
+            let mut len = [0; 4];
+            while stream.read_exact(&mut len).is_ok() {
+                let mut message = vec![0; u32::from_be_bytes(len) as usize];
+                stream.read_exact(&mut message).unwrap();
+                let reply = fake.answer(&message);
+                stream
+                    .write_all(&(reply.len() as u32).to_be_bytes())
+                    .unwrap();
+                stream.write_all(&reply).unwrap();
+            }
+        });
+
+        let mut agent = AgentClient::new(std::os::unix::net::UnixStream::connect(&path).unwrap());
+        let expected = {
+            let mut fake = AgentClient::new(FakeAgent::new(keys()));
+            master_from_agent(&mut fake, None).unwrap()
+        };
+        assert_eq!(master_from_agent(&mut agent, None).unwrap(), expected);
+        drop(agent);
+        server.join().unwrap();
+    }
+}
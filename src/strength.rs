@@ -0,0 +1,243 @@
+//! Master password strength
+//!
+//! Every derived password is only as strong as the master password: one leaked
+//! password and its key are enough to test master password guesses offline at
+//! the speed of HMAC-MD5. [`check_master_strength`] rates a master password with
+//! [zxcvbn](https://github.com/dropbox/zxcvbn), and [`require_strength`] turns a
+//! low rating into a [`WeakMaster`] error for frontends that refuse weak ones.
+//! [`FpGenerator::new_checked`](crate::FpGenerator::new_checked) applies the same
+//! guard when a generator is created.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::strength::{check_master_strength, require_strength, DEFAULT_MIN_SCORE};
+//!
+//! assert!(check_master_strength("password1").score < DEFAULT_MIN_SCORE);
+//! let error = require_strength("password1", DEFAULT_MIN_SCORE).unwrap_err();
+//! assert!(!error.strength.warnings.is_empty());
+//! ```
+
+#[cfg(feature = "blocklist")]
+use crate::blocklist::Blocklist;
+use crate::FlowerPasswordError;
+use std::error::Error;
+use std::fmt;
+
+/// Lowest [`MasterStrength::score`] accepted by default, as zxcvbn recommends
+pub const DEFAULT_MIN_SCORE: u8 = 3;
+
+/// zxcvbn's rating of a master password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterStrength {
+    /// Score from 0 (guessable in under 10^3 tries) to 4 (over 10^10)
+    pub score: u8,
+    /// Time to find the master with ten billion guesses a second, as fast hashes
+    /// like HMAC-MD5 allow, such as "3 hours" or "centuries"
+    pub crack_time_display: String,
+    /// What makes the master password weak, if anything
+    pub warnings: Vec<String>,
+    /// How to choose a stronger one
+    pub suggestions: Vec<String>,
+}
+
+/// Error returned by [`require_strength`] for a master password scoring too low
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeakMaster {
+    /// The rating that fell short, with its warnings and suggestions
+    pub strength: MasterStrength,
+    /// The score that was required
+    pub min_score: u8,
+}
+
+impl fmt::Display for WeakMaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Master password is too weak: score {} of 4, at least {} required (cracked in {})",
+            self.strength.score, self.min_score, self.strength.crack_time_display
+        )?;
+        for note in self
+            .strength
+            .warnings
+            .iter()
+            .chain(&self.strength.suggestions)
+        {
+            write!(f, ". {}", note.trim_end_matches('.'))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for WeakMaster {}
+
+/// Error returned by [`FpGenerator::new_checked`](crate::FpGenerator::new_checked)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckedError {
+    /// The default length is not between 2 and 32
+    InvalidLength(usize),
+    /// The master password scored below the minimum
+    WeakMaster(WeakMaster),
+}
+
+impl fmt::Display for CheckedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckedError::InvalidLength(len) => {
+                write!(f, "{}", FlowerPasswordError::InvalidLength(*len))
+            }
+            CheckedError::WeakMaster(weak) => write!(f, "{}", weak),
+        }
+    }
+}
+
+impl Error for CheckedError {}
+
+impl From<WeakMaster> for CheckedError {
+    fn from(weak: WeakMaster) -> Self {
+        CheckedError::WeakMaster(weak)
+    }
+}
+
+/// Rates `master` with zxcvbn
+pub fn check_master_strength(master: &str) -> MasterStrength {
+    let entropy = zxcvbn::zxcvbn(master, &[]);
+    let (warnings, suggestions) = match entropy.feedback() {
+        Some(feedback) => (
+            feedback.warning().iter().map(ToString::to_string).collect(),
+            feedback
+                .suggestions()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+    MasterStrength {
+        score: entropy.score().into(),
+        crack_time_display: entropy
+            .crack_times()
+            .offline_fast_hashing_1e10_per_second()
+            .to_string(),
+        warnings,
+        suggestions,
+    }
+}
+
+/// Rates `master` and rejects it if it scores below `min_score`
+///
+/// # Errors
+///
+/// Returns `WeakMaster`, carrying zxcvbn's warnings and suggestions, if the score
+/// is below `min_score`.
+pub fn require_strength(master: &str, min_score: u8) -> Result<MasterStrength, WeakMaster> {
+    require(check_master_strength(master), min_score)
+}
+
+/// Rates `master` with zxcvbn, scoring it 0 if `blocklist` lists it
+///
+/// zxcvbn already knows the passwords of [`Blocklist::builtin`]; this is for
+/// lists of your own, such as company or product names.
+#[cfg(feature = "blocklist")]
+pub fn check_master_strength_against(master: &str, blocklist: &Blocklist) -> MasterStrength {
+    let mut strength = check_master_strength(master);
+    if blocklist.contains(master) {
+        strength.score = 0;
+        strength.crack_time_display = "less than a second".to_string();
+        strength
+            .warnings
+            .insert(0, "This password is on the blocklist".to_string());
+    }
+    strength
+}
+
+/// Rates `master` against `blocklist` and rejects it if it scores below `min_score`
+///
+/// # Errors
+///
+/// Returns `WeakMaster` if the score is below `min_score`, which a listed master
+/// always is for a `min_score` above 0.
+#[cfg(feature = "blocklist")]
+pub fn require_strength_against(
+    master: &str,
+    min_score: u8,
+    blocklist: &Blocklist,
+) -> Result<MasterStrength, WeakMaster> {
+    require(check_master_strength_against(master, blocklist), min_score)
+}
+
+fn require(strength: MasterStrength, min_score: u8) -> Result<MasterStrength, WeakMaster> {
+    if strength.score < min_score {
+        return Err(WeakMaster {
+            strength,
+            min_score,
+        });
+    }
+    Ok(strength)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_master() {
+        let strength = check_master_strength("password");
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.crack_time_display, "less than a second");
+        assert!(!strength.warnings.is_empty());
+        assert!(!strength.suggestions.is_empty());
+        assert_eq!(check_master_strength("").score, 0);
+    }
+
+    #[test]
+    fn test_random_master_passes() {
+        let master = "vT9#qLm2$Xe8!rWz4&Kp";
+        let strength = require_strength(master, DEFAULT_MIN_SCORE).unwrap();
+        assert_eq!(strength.score, 4);
+        let time = &strength.crack_time_display;
+        assert!(time.ends_with("years") || time == "centuries", "{}", time);
+        assert!(strength.warnings.is_empty());
+        assert!(require_strength(master, 4).is_ok());
+    }
+
+    #[test]
+    fn test_guard_carries_feedback() {
+        let error = require_strength("qwerty123", DEFAULT_MIN_SCORE).unwrap_err();
+        assert_eq!(error.min_score, 3);
+        assert!(error.strength.score < 3);
+        assert_eq!(error.strength, check_master_strength("qwerty123"));
+
+        let message = error.to_string();
+        assert!(message.starts_with("Master password is too weak: score "));
+        for note in error
+            .strength
+            .warnings
+            .iter()
+            .chain(&error.strength.suggestions)
+        {
+            assert!(message.contains(note.trim_end_matches('.')), "{}", note);
+        }
+
+        // A threshold of 0 accepts anything
+        assert!(require_strength("qwerty123", 0).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "blocklist")]
+    fn test_blocklisted_master() {
+        // Strong on its own, but listed
+        let master = "vT9#qLm2$Xe8!rWz4&Kp";
+        let blocklist = Blocklist::parse(master);
+        let strength = check_master_strength_against(master, &blocklist);
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.warnings[0], "This password is on the blocklist");
+
+        let error = require_strength_against(master, DEFAULT_MIN_SCORE, &blocklist).unwrap_err();
+        assert_eq!(error.strength, strength);
+        assert!(error
+            .to_string()
+            .contains("This password is on the blocklist"));
+        assert!(require_strength_against(master, DEFAULT_MIN_SCORE, &Blocklist::builtin()).is_ok());
+    }
+}
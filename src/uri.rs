@@ -0,0 +1,339 @@
+//! `flowerpassword://` URIs for triggering derivations from bookmarks and other apps
+//!
+//! A derivation is requested with `flowerpassword://derive?key=<key>&len=<n>&copy=<0|1>`.
+//! `key` is required and percent-decoded as UTF-8; `len` and `copy` are optional.
+//! Parameters may appear in any order but only once, and unknown parameters are
+//! rejected so that a typo cannot silently change the derived password. `+` is a
+//! literal plus sign, not a space. A fragment, if any, is ignored.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::uri::parse_fp_uri;
+//!
+//! let request = parse_fp_uri("flowerpassword://derive?key=github.com&len=16&copy=1").unwrap();
+//! assert_eq!(request.key, "github.com");
+//! assert_eq!(request.length, Some(16));
+//! assert!(request.copy);
+//! assert_eq!(request.to_string(), "flowerpassword://derive?key=github.com&len=16&copy=1");
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use flowerpassword_core::{validate_length, MAX_LENGTH, MIN_LENGTH};
+
+/// URI scheme handled by [`parse_fp_uri`]
+pub const SCHEME: &str = "flowerpassword";
+
+/// A derivation requested through a `flowerpassword://` URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpUriRequest {
+    /// Site key, percent-decoded
+    pub key: String,
+    /// Requested length, or `None` to use the configured default
+    pub length: Option<usize>,
+    /// Whether the password should go to the clipboard rather than stdout
+    pub copy: bool,
+}
+
+impl fmt::Display for FpUriRequest {
+    /// Formats the request as a URI that [`parse_fp_uri`] reads back
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://derive?key=", SCHEME)?;
+        for byte in self.key.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                write!(f, "{}", byte as char)?;
+            } else {
+                write!(f, "%{:02X}", byte)?;
+            }
+        }
+        if let Some(length) = self.length {
+            write!(f, "&len={}", length)?;
+        }
+        if self.copy {
+            f.write_str("&copy=1")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error type for [`parse_fp_uri`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FpUriError {
+    /// The URI does not start with `flowerpassword://`
+    InvalidScheme,
+    /// The URI names an action other than `derive`
+    UnknownAction(String),
+    /// A required parameter is absent or empty
+    MissingParameter(&'static str),
+    /// A parameter appears more than once
+    DuplicateParameter(String),
+    /// A parameter is not one of `key`, `len` and `copy`
+    UnknownParameter(String),
+    /// A `%` escape is malformed or decodes to invalid UTF-8
+    InvalidEncoding(String),
+    /// `len` is not a number between 2 and 32
+    InvalidLength(String),
+    /// `copy` is not `0`, `1`, `false` or `true`
+    InvalidFlag(String),
+}
+
+impl fmt::Display for FpUriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FpUriError::InvalidScheme => write!(f, "URI must start with {}://", SCHEME),
+            FpUriError::UnknownAction(action) => {
+                write!(f, "Unknown action `{}`, expected `derive`", action)
+            }
+            FpUriError::MissingParameter(name) => write!(f, "Missing parameter `{}`", name),
+            FpUriError::DuplicateParameter(name) => {
+                write!(f, "Parameter `{}` is given more than once", name)
+            }
+            FpUriError::UnknownParameter(name) => write!(
+                f,
+                "Unknown parameter `{}`, expected one of: key, len, copy",
+                name
+            ),
+            FpUriError::InvalidEncoding(value) => {
+                write!(f, "Invalid percent-encoding in `{}`", value)
+            }
+            FpUriError::InvalidLength(value) => write!(
+                f,
+                "Length must be between {} and {}, got: {}",
+                MIN_LENGTH, MAX_LENGTH, value
+            ),
+            FpUriError::InvalidFlag(value) => {
+                write!(f, "`copy` must be 0, 1, false or true, got: {}", value)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FpUriError {}
+
+/// Decodes `%XX` escapes, requiring the result to be UTF-8
+fn percent_decode(value: &str) -> Result<String, FpUriError> {
+    let invalid = || FpUriError::InvalidEncoding(value.to_string());
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or_else(invalid)?;
+            let hex = core::str::from_utf8(hex).map_err(|_| invalid())?;
+            // from_str_radix accepts a sign, which is not a hex digit here
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+/// Parses a `flowerpassword://derive?...` URI
+///
+/// # Errors
+///
+/// Returns an [`FpUriError`] for a wrong scheme or action, a missing, duplicate
+/// or unknown parameter, a malformed escape, or an out-of-range length.
+pub fn parse_fp_uri(uri: &str) -> Result<FpUriRequest, FpUriError> {
+    // Compared as bytes, since a character may straddle the end of the scheme
+    let rest = uri
+        .as_bytes()
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME.as_bytes()))
+        .and_then(|_| uri[SCHEME.len()..].strip_prefix("://"))
+        .ok_or(FpUriError::InvalidScheme)?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    // Some platforms hand over `flowerpassword://derive/?...`
+    let action = action.strip_suffix('/').unwrap_or(action);
+    if action != "derive" {
+        return Err(FpUriError::UnknownAction(percent_decode(action)?));
+    }
+
+    let mut key = None;
+    let mut length = None;
+    let mut copy = None;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let name = percent_decode(name)?;
+        let value = percent_decode(value)?;
+        let slot = match name.as_str() {
+            "key" => &mut key,
+            "len" => &mut length,
+            "copy" => &mut copy,
+            _ => return Err(FpUriError::UnknownParameter(name)),
+        };
+        if slot.replace(value).is_some() {
+            return Err(FpUriError::DuplicateParameter(name));
+        }
+    }
+
+    let key = key
+        .filter(|key| !key.is_empty())
+        .ok_or(FpUriError::MissingParameter("key"))?;
+    let length = length
+        .map(|value| {
+            value
+                .parse()
+                .ok()
+                .filter(|&length| validate_length(length).is_ok())
+                .ok_or(FpUriError::InvalidLength(value))
+        })
+        .transpose()?;
+    let copy = match copy.as_deref() {
+        None | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(value) => return Err(FpUriError::InvalidFlag(value.to_string())),
+    };
+    Ok(FpUriRequest { key, length, copy })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fp_uri() {
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=github.com&len=16&copy=1").unwrap(),
+            FpUriRequest {
+                key: "github.com".into(),
+                length: Some(16),
+                copy: true,
+            }
+        );
+        assert_eq!(
+            parse_fp_uri("FlowerPassword://derive/?copy=false&key=a+b#ignored").unwrap(),
+            FpUriRequest {
+                key: "a+b".into(),
+                length: None,
+                copy: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_encoded_unicode_key() {
+        let request =
+            parse_fp_uri("flowerpassword://derive?key=%E4%BE%8B%E3%81%88.jp&len=2").unwrap();
+        assert_eq!(request.key, "例え.jp");
+        assert_eq!(request.length, Some(2));
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=%61%2Fb")
+                .unwrap()
+                .key,
+            "a/b"
+        );
+        // Lone continuation byte
+        assert!(matches!(
+            parse_fp_uri("flowerpassword://derive?key=%BE"),
+            Err(FpUriError::InvalidEncoding(_))
+        ));
+        for bad in ["%", "%4", "%zz", "%+1", "a%-1"] {
+            let uri = alloc::format!("flowerpassword://derive?key={}", bad);
+            assert_eq!(
+                parse_fp_uri(&uri),
+                Err(FpUriError::InvalidEncoding(bad.into())),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_key() {
+        for uri in [
+            "flowerpassword://derive",
+            "flowerpassword://derive?len=16",
+            "flowerpassword://derive?key=&len=16",
+            "flowerpassword://derive?key",
+        ] {
+            assert_eq!(
+                parse_fp_uri(uri),
+                Err(FpUriError::MissingParameter("key")),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn test_length_out_of_range() {
+        for len in ["1", "33", "0", "-1", "sixteen", "", "99999999999999999999"] {
+            let uri = alloc::format!("flowerpassword://derive?key=a&len={}", len);
+            assert_eq!(
+                parse_fp_uri(&uri),
+                Err(FpUriError::InvalidLength(len.into())),
+                "{}",
+                len
+            );
+        }
+        assert_eq!(
+            FpUriError::InvalidLength("33".into()).to_string(),
+            "Length must be between 2 and 32, got: 33"
+        );
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=a&len=32")
+                .unwrap()
+                .length,
+            Some(32)
+        );
+    }
+
+    #[test]
+    fn test_rejected_parameters() {
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=a&length=16"),
+            Err(FpUriError::UnknownParameter("length".into()))
+        );
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=a&key=b"),
+            Err(FpUriError::DuplicateParameter("key".into()))
+        );
+        assert_eq!(
+            parse_fp_uri("flowerpassword://derive?key=a&copy=yes"),
+            Err(FpUriError::InvalidFlag("yes".into()))
+        );
+        assert_eq!(
+            parse_fp_uri("flowerpassword://verify?key=a"),
+            Err(FpUriError::UnknownAction("verify".into()))
+        );
+        for uri in [
+            "https://derive?key=a",
+            "flowerpassword:derive?key=a",
+            "flower",
+            // A character across byte 14, the end of the scheme
+            "flowerpassworé://derive?key=a",
+            "flowerpassword\u{e9}//derive?key=a",
+        ] {
+            assert_eq!(parse_fp_uri(uri), Err(FpUriError::InvalidScheme), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for key in ["github.com", "例え.jp", "a b&c=d%e#f+g", "x?y/z"] {
+            let request = FpUriRequest {
+                key: key.into(),
+                length: Some(12),
+                copy: true,
+            };
+            assert_eq!(parse_fp_uri(&request.to_string()).unwrap(), request);
+        }
+        let request = FpUriRequest {
+            key: "a b".into(),
+            length: None,
+            copy: false,
+        };
+        assert_eq!(request.to_string(), "flowerpassword://derive?key=a%20b");
+    }
+}
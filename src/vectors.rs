@@ -0,0 +1,182 @@
+//! Known-answer test vectors
+//!
+//! These vectors pin the output of [`fp_code`] to the JavaScript implementation.
+//! The library's own tests check every entry, and frontends can run
+//! [`self_test`] to confirm a build reproduces them before trusting it.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::vectors::self_test;
+//!
+//! assert!(self_test().iter().all(|result| result.passed()));
+//! ```
+
+use crate::{fp_code, FlowerPasswordError};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A single known-answer vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownAnswer {
+    /// Master password
+    pub password: &'static str,
+    /// Domain or service identifier
+    pub key: &'static str,
+    /// Output password length
+    pub length: usize,
+    /// Expected generated password
+    pub expected: &'static str,
+}
+
+/// Outcome of checking one vector
+#[derive(Debug, Clone)]
+pub struct VectorResult {
+    /// The vector that was checked
+    pub vector: &'static KnownAnswer,
+    /// What this build produced for it
+    pub actual: Result<String, FlowerPasswordError>,
+}
+
+impl VectorResult {
+    /// Returns `true` if the build reproduced the expected password
+    pub fn passed(&self) -> bool {
+        matches!(&self.actual, Ok(actual) if actual == self.vector.expected)
+    }
+}
+
+macro_rules! vector {
+    ($password:expr, $key:expr, $length:expr, $expected:expr) => {
+        KnownAnswer {
+            password: $password,
+            key: $key,
+            length: $length,
+            expected: $expected,
+        }
+    };
+}
+
+/// Canonical vectors covering every valid length, unicode and empty inputs
+pub const KNOWN_ANSWERS: &[KnownAnswer] = &[
+    // Every valid length
+    vector!("password", "key", 2, "K3"),
+    vector!("password", "key", 3, "K3A"),
+    vector!("password", "key", 4, "K3A2"),
+    vector!("password", "key", 5, "K3A2a"),
+    vector!("password", "key", 6, "K3A2a6"),
+    vector!("password", "key", 7, "K3A2a66"),
+    vector!("password", "key", 8, "K3A2a66B"),
+    vector!("password", "key", 9, "K3A2a66Bf"),
+    vector!("password", "key", 10, "K3A2a66Bf8"),
+    vector!("password", "key", 11, "K3A2a66Bf88"),
+    vector!("password", "key", 12, "K3A2a66Bf88b"),
+    vector!("password", "key", 13, "K3A2a66Bf88b6"),
+    vector!("password", "key", 14, "K3A2a66Bf88b62"),
+    vector!("password", "key", 15, "K3A2a66Bf88b628"),
+    vector!("password", "key", 16, "K3A2a66Bf88b628c"),
+    vector!("password", "key", 17, "K3A2a66Bf88b628c2"),
+    vector!("password", "key", 18, "K3A2a66Bf88b628c2C"),
+    vector!("password", "key", 19, "K3A2a66Bf88b628c2Cd"),
+    vector!("password", "key", 20, "K3A2a66Bf88b628c2Cd7"),
+    vector!("password", "key", 21, "K3A2a66Bf88b628c2Cd7c"),
+    vector!("password", "key", 22, "K3A2a66Bf88b628c2Cd7cD"),
+    vector!("password", "key", 23, "K3A2a66Bf88b628c2Cd7cDA"),
+    vector!("password", "key", 24, "K3A2a66Bf88b628c2Cd7cDA9"),
+    vector!("password", "key", 25, "K3A2a66Bf88b628c2Cd7cDA99"),
+    vector!("password", "key", 26, "K3A2a66Bf88b628c2Cd7cDA995"),
+    vector!("password", "key", 27, "K3A2a66Bf88b628c2Cd7cDA9958"),
+    vector!("password", "key", 28, "K3A2a66Bf88b628c2Cd7cDA9958f"),
+    vector!("password", "key", 29, "K3A2a66Bf88b628c2Cd7cDA9958f6"),
+    vector!("password", "key", 30, "K3A2a66Bf88b628c2Cd7cDA9958f6b"),
+    vector!("password", "key", 31, "K3A2a66Bf88b628c2Cd7cDA9958f6b2"),
+    vector!("password", "key", 32, "K3A2a66Bf88b628c2Cd7cDA9958f6b26"),
+    // Real-world examples
+    vector!("test", "github.com", 16, "D04175F7A9c7Ab4a"),
+    vector!("test", "example.com", 16, "B0399e643E07a2EA"),
+    vector!("mypassword", "example.com", 12, "K0CA12CecFFB"),
+    vector!("mypass", "github.com", 16, "K5817EB58CE4512F"),
+    vector!("secret", "google.com", 16, "Kc6813f75AAa6Bd1"),
+    vector!("12345", "site", 16, "K05a62bfea0C1553"),
+    // Special characters and unicode
+    vector!("p@ssw0rd!#$%", "key", 16, "D4e5c2BE16F71498"),
+    vector!("password", "user@example.com", 16, "K98076292B62A974"),
+    vector!("密码", "网站.com", 16, "KFF7FEa7928bAAAa"),
+    // Empty strings
+    vector!("", "key", 16, "K46eB52c968caeAa"),
+    vector!("password", "", 16, "eB3b1cA3D6B54c00"),
+    vector!("", "", 16, "K930B0264e62DDFC"),
+];
+
+/// Checks every vector in [`KNOWN_ANSWERS`] against this build
+pub fn self_test() -> Vec<VectorResult> {
+    KNOWN_ANSWERS
+        .iter()
+        .map(|vector| VectorResult {
+            vector,
+            actual: fp_code(vector.password, vector.key, vector.length),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_known_answers_pass() {
+        for result in self_test() {
+            assert!(
+                result.passed(),
+                "Vector {:?} produced {:?}",
+                result.vector,
+                result.actual
+            );
+        }
+    }
+
+    #[test]
+    fn test_covers_every_valid_length() {
+        for length in 2..=32 {
+            assert!(
+                KNOWN_ANSWERS.iter().any(|v| v.length == length),
+                "No vector for length {}",
+                length
+            );
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_heapless_matches_known_answers() {
+        for vector in KNOWN_ANSWERS {
+            let password = crate::fp_code_heapless(
+                vector.password.as_bytes(),
+                vector.key.as_bytes(),
+                vector.length,
+            )
+            .unwrap();
+            assert_eq!(password, vector.expected);
+        }
+    }
+
+    #[cfg(feature = "tiny")]
+    #[test]
+    fn test_tiny_matches_known_answers() {
+        for vector in KNOWN_ANSWERS {
+            let mut password = [0; flowerpassword_core::MAX_LENGTH];
+            let out = &mut password[..vector.length];
+            crate::fp_code_tiny(vector.password.as_bytes(), vector.key.as_bytes(), out).unwrap();
+            assert_eq!(out, vector.expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_mismatch_is_reported() {
+        static WRONG: KnownAnswer = vector!("password", "key", 16, "K3A2a66Bf88b628d");
+        let result = VectorResult {
+            vector: &WRONG,
+            actual: fp_code(WRONG.password, WRONG.key, WRONG.length),
+        };
+        assert!(!result.passed());
+    }
+}
@@ -0,0 +1,168 @@
+//! JavaScript bindings for WebAssembly
+//!
+//! Built for `wasm32-unknown-unknown` with the `wasm` feature, these functions are
+//! exported to JavaScript under camelCase names. Errors are thrown as plain objects
+//! with a `kind` and a `message`, so callers can branch on the kind without parsing
+//! the message:
+//!
+//! ```js
+//! import { fpCode, fpCodeMany } from "flowerpassword";
+//!
+//! fpCode("test", "github.com", 16); // "D04175F7A9c7Ab4a"
+//! fpCodeMany("test", ["github.com", "gitlab.com"], 16);
+//!
+//! try {
+//!     fpCode("test", "github.com", 40);
+//! } catch (error) {
+//!     error.kind; // "InvalidLength"
+//! }
+//! ```
+
+use crate::{fp_code, FlowerPasswordError};
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// Error kind for arguments that are not of the expected JavaScript type
+const INVALID_INPUT: &str = "InvalidInput";
+
+/// Returns the `kind` reported to JavaScript for `error`
+fn error_kind(error: &FlowerPasswordError) -> &'static str {
+    match error {
+        FlowerPasswordError::InvalidLength(_) => "InvalidLength",
+    }
+}
+
+/// Builds the `{ kind, message }` object thrown to JavaScript
+fn js_error(kind: &str, message: &str) -> JsValue {
+    let error = Object::new();
+    // Setting properties on a fresh plain object cannot fail
+    let _ = Reflect::set(&error, &"kind".into(), &kind.into());
+    let _ = Reflect::set(&error, &"message".into(), &message.into());
+    error.into()
+}
+
+/// Converts a library error into the object thrown to JavaScript
+fn throw(error: FlowerPasswordError) -> JsValue {
+    js_error(error_kind(&error), &error.to_string())
+}
+
+/// Generates a Flower Password, exported to JavaScript as `fpCode`
+///
+/// # Errors
+///
+/// Throws `{ kind: "InvalidLength", message }` if length is not between 2 and 32.
+#[wasm_bindgen(js_name = fpCode)]
+pub fn fp_code_js(password: &str, key: &str, length: u32) -> Result<String, JsValue> {
+    fp_code(password, key, length as usize).map_err(throw)
+}
+
+/// Generates a password for each key in `keys`, exported to JavaScript as `fpCodeMany`
+///
+/// Returns an array of passwords in the order of `keys`.
+///
+/// # Errors
+///
+/// Throws `{ kind: "InvalidLength", message }` if length is not between 2 and 32,
+/// or `{ kind: "InvalidInput", message }` if an element of `keys` is not a string.
+#[wasm_bindgen(js_name = fpCodeMany)]
+pub fn fp_code_many_js(password: &str, keys: Array, length: u32) -> Result<Array, JsValue> {
+    let passwords = Array::new();
+    for (index, key) in keys.iter().enumerate() {
+        let key = key.as_string().ok_or_else(|| {
+            js_error(
+                INVALID_INPUT,
+                &format!("Key at index {} is not a string", index),
+            )
+        })?;
+        passwords.push(
+            &fp_code(password, &key, length as usize)
+                .map_err(throw)?
+                .into(),
+        );
+    }
+    Ok(passwords)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            error_kind(&FlowerPasswordError::InvalidLength(1)),
+            "InvalidLength"
+        );
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use crate::vectors::KNOWN_ANSWERS;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn field(value: &JsValue, name: &str) -> String {
+        Reflect::get(value, &name.into())
+            .unwrap()
+            .as_string()
+            .unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_known_vectors() {
+        for vector in KNOWN_ANSWERS {
+            assert_eq!(
+                fp_code_js(vector.password, vector.key, vector.length as u32).unwrap(),
+                vector.expected
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_invalid_length_error() {
+        let error = fp_code_js("password", "key", 33).unwrap_err();
+        assert_eq!(field(&error, "kind"), "InvalidLength");
+        assert_eq!(
+            field(&error, "message"),
+            "Length must be between 2 and 32, got: 33"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_errors_leave_inputs_out() {
+        use crate::redaction::{assert_redacted, SENTINEL};
+
+        let single = fp_code_js(SENTINEL, SENTINEL, 33).unwrap_err();
+        let keys = Array::of2(&SENTINEL.into(), &JsValue::from(7));
+        let many = fp_code_many_js(SENTINEL, keys, 16).unwrap_err();
+        assert_redacted([field(&single, "message").as_str(), &field(&many, "message")]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_many() {
+        let keys = Array::of2(&"github.com".into(), &"key".into());
+        let passwords = fp_code_many_js("test", keys, 16).unwrap();
+        assert_eq!(passwords.length(), 2);
+        assert_eq!(passwords.get(0).as_string().unwrap(), "D04175F7A9c7Ab4a");
+        assert_eq!(
+            passwords.get(1).as_string().unwrap(),
+            fp_code("test", "key", 16).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_many_rejects_non_string_keys() {
+        let keys = Array::of2(&"github.com".into(), &JsValue::from(7));
+        let error = fp_code_many_js("test", keys, 16).unwrap_err();
+        assert_eq!(field(&error, "kind"), "InvalidInput");
+        assert_eq!(field(&error, "message"), "Key at index 1 is not a string");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_many_invalid_length() {
+        let keys = Array::of1(&"github.com".into());
+        let error = fp_code_many_js("test", keys, 1).unwrap_err();
+        assert_eq!(field(&error, "kind"), "InvalidLength");
+    }
+}
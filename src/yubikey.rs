@@ -0,0 +1,417 @@
+//! YubiKey HMAC-SHA1 challenge-response master source
+//!
+//! A YubiKey slot configured for HMAC-SHA1 challenge-response (as KeePassXC uses
+//! it) answers a challenge with a 20-byte HMAC under a secret that never leaves the
+//! key. Sending a fixed challenge therefore yields the same response every time,
+//! and its lowercase hex encoding is used as the master password for the normal
+//! [`fp_code`](crate::fp_code) pipeline.
+//!
+//! The challenge is the UTF-8 bytes of [`CHALLENGE_PREFIX`] followed by a
+//! user-chosen label, so one key can hold several independent masters:
+//!
+//! ```text
+//! label "personal"  ->  challenge b"flowerpassword:personal"
+//! ```
+//!
+//! Challenges are at most [`MAX_CHALLENGE_LEN`] bytes, which keeps keys
+//! configured for fixed 64-byte and variable-length challenges in agreement.
+//!
+//! Device access goes through the [`ChallengeResponse`] trait, implemented by a
+//! USB HID or PC/SC backend.
+//!
+//! # Example
+//!
+//! ```
+//! use flowerpassword::yubikey::{master_from_yubikey, ChallengeResponse, Slot, YubiKeyError};
+//! use std::time::Duration;
+//!
+//! struct Canned;
+//!
+//! impl ChallengeResponse for Canned {
+//!     fn requires_touch(&mut self, _slot: Slot) -> Result<bool, YubiKeyError> {
+//!         Ok(false)
+//!     }
+//!
+//!     fn challenge_response(
+//!         &mut self,
+//!         _slot: Slot,
+//!         _challenge: &[u8],
+//!         _timeout: Duration,
+//!     ) -> Result<[u8; 20], YubiKeyError> {
+//!         Ok([0xab; 20])
+//!     }
+//! }
+//!
+//! let master = master_from_yubikey(&mut Canned, Slot::Two, "personal", Duration::from_secs(15), || {})?;
+//! assert_eq!(master.len(), 40);
+//! let password = flowerpassword::fp_code(&master, "github.com", 16)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Prefix of every challenge, followed by the label
+pub const CHALLENGE_PREFIX: &str = "flowerpassword:";
+
+/// Longest challenge sent to the key, in bytes
+pub const MAX_CHALLENGE_LEN: usize = 63;
+
+/// Length of an HMAC-SHA1 response, in bytes
+pub const RESPONSE_LEN: usize = 20;
+
+/// How long to wait for a touch on slots that require one
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// One of the two configuration slots of a YubiKey
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Slot {
+    /// Slot 1, the short touch
+    One,
+    /// Slot 2, the long touch, and the usual slot for challenge-response
+    #[default]
+    Two,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::One => write!(f, "1"),
+            Slot::Two => write!(f, "2"),
+        }
+    }
+}
+
+impl FromStr for Slot {
+    type Err = YubiKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(Slot::One),
+            "2" => Ok(Slot::Two),
+            _ => Err(YubiKeyError::InvalidSlot(s.to_string())),
+        }
+    }
+}
+
+/// Error type for YubiKey operations
+#[derive(Debug)]
+pub enum YubiKeyError {
+    /// No YubiKey is connected
+    NotFound,
+    /// The slot is not configured for HMAC-SHA1 challenge-response
+    SlotNotConfigured(Slot),
+    /// The key was not touched before the timeout
+    TouchTimeout(Duration),
+    /// The label makes the challenge longer than [`MAX_CHALLENGE_LEN`]
+    LabelTooLong(usize),
+    /// A slot other than `1` or `2`
+    InvalidSlot(String),
+    /// A `--master` value that is not `yubikey` or `yubikey:<slot>`
+    ///
+    /// The value is not kept, since it may be a master password given by mistake.
+    InvalidSource,
+    /// Talking to the device failed
+    Device(String),
+}
+
+impl fmt::Display for YubiKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            YubiKeyError::NotFound => write!(f, "No YubiKey found"),
+            YubiKeyError::SlotNotConfigured(slot) => write!(
+                f,
+                "YubiKey slot {} is not configured for challenge-response",
+                slot
+            ),
+            YubiKeyError::TouchTimeout(timeout) => write!(
+                f,
+                "YubiKey was not touched within {} seconds",
+                timeout.as_secs()
+            ),
+            YubiKeyError::LabelTooLong(len) => write!(
+                f,
+                "Challenge of {} bytes exceeds the maximum of {} bytes",
+                len, MAX_CHALLENGE_LEN
+            ),
+            YubiKeyError::InvalidSlot(slot) => {
+                write!(f, "Invalid YubiKey slot `{}`, expected 1 or 2", slot)
+            }
+            YubiKeyError::InvalidSource => {
+                write!(
+                    f,
+                    "Invalid master source, expected yubikey or yubikey:<slot>"
+                )
+            }
+            YubiKeyError::Device(message) => write!(f, "YubiKey error: {}", message),
+        }
+    }
+}
+
+impl Error for YubiKeyError {}
+
+/// Access to a key's HMAC-SHA1 challenge-response slots
+pub trait ChallengeResponse {
+    /// Returns whether `slot` waits for a touch before answering
+    fn requires_touch(&mut self, slot: Slot) -> Result<bool, YubiKeyError>;
+
+    /// Sends `challenge` to `slot` and returns the HMAC-SHA1 response
+    ///
+    /// Implementations give up with `YubiKeyError::TouchTimeout` after `timeout`.
+    fn challenge_response(
+        &mut self,
+        slot: Slot,
+        challenge: &[u8],
+        timeout: Duration,
+    ) -> Result<[u8; RESPONSE_LEN], YubiKeyError>;
+}
+
+impl<T: ChallengeResponse + ?Sized> ChallengeResponse for &mut T {
+    fn requires_touch(&mut self, slot: Slot) -> Result<bool, YubiKeyError> {
+        (**self).requires_touch(slot)
+    }
+
+    fn challenge_response(
+        &mut self,
+        slot: Slot,
+        challenge: &[u8],
+        timeout: Duration,
+    ) -> Result<[u8; RESPONSE_LEN], YubiKeyError> {
+        (**self).challenge_response(slot, challenge, timeout)
+    }
+}
+
+/// Parses a `--master` value of the form `yubikey` or `yubikey:<slot>`
+///
+/// Without a slot, [`Slot::Two`] is used.
+///
+/// # Errors
+///
+/// Returns `YubiKeyError::InvalidSource` if `spec` does not name the YubiKey source,
+/// or `YubiKeyError::InvalidSlot` if the slot is not `1` or `2`.
+pub fn parse_source(spec: &str) -> Result<Slot, YubiKeyError> {
+    match spec.split_once(':') {
+        None if spec == "yubikey" => Ok(Slot::default()),
+        Some(("yubikey", slot)) => slot.parse(),
+        _ => Err(YubiKeyError::InvalidSource),
+    }
+}
+
+/// Builds the challenge for `label`
+///
+/// # Errors
+///
+/// Returns `YubiKeyError::LabelTooLong` if the challenge would exceed
+/// [`MAX_CHALLENGE_LEN`] bytes.
+pub fn challenge(label: &str) -> Result<Vec<u8>, YubiKeyError> {
+    let len = CHALLENGE_PREFIX.len() + label.len();
+    if len > MAX_CHALLENGE_LEN {
+        return Err(YubiKeyError::LabelTooLong(len));
+    }
+    let mut challenge = Vec::with_capacity(len);
+    challenge.extend_from_slice(CHALLENGE_PREFIX.as_bytes());
+    challenge.extend_from_slice(label.as_bytes());
+    Ok(challenge)
+}
+
+/// Obtains the master password for `label` from `slot` of `device`
+///
+/// `on_touch` is called before waiting on a slot that requires a touch, so the
+/// caller can tell the user to touch the key. The response is returned as 40
+/// lowercase hex characters in a buffer that is zeroed on drop.
+///
+/// # Errors
+///
+/// Returns `YubiKeyError::LabelTooLong` for an oversized label, or any error from
+/// the device, including `YubiKeyError::TouchTimeout`.
+pub fn master_from_yubikey<D: ChallengeResponse>(
+    mut device: D,
+    slot: Slot,
+    label: &str,
+    timeout: Duration,
+    on_touch: impl FnOnce(),
+) -> Result<Zeroizing<String>, YubiKeyError> {
+    let challenge = challenge(label)?;
+    if device.requires_touch(slot)? {
+        on_touch();
+    }
+    let response = Zeroizing::new(device.challenge_response(slot, &challenge, timeout)?);
+
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut master = Zeroizing::new(String::with_capacity(RESPONSE_LEN * 2));
+    for byte in response.iter() {
+        master.push(HEX[(byte >> 4) as usize] as char);
+        master.push(HEX[(byte & 0x0f) as usize] as char);
+    }
+    Ok(master)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp_code;
+
+    /// Canned response, as from a slot programmed with a known secret
+    const RESPONSE: [u8; RESPONSE_LEN] = [
+        0x3f, 0x8a, 0x01, 0xc2, 0x5e, 0x77, 0x90, 0x0b, 0xd4, 0x16, 0xa9, 0xee, 0x42, 0x00, 0x5c,
+        0x81, 0x6d, 0xf3, 0x28, 0xb7,
+    ];
+
+    /// A fake key that records the challenges it receives
+    #[derive(Default)]
+    struct FakeKey {
+        touch: bool,
+        touched: bool,
+        configured: Option<Slot>,
+        challenges: Vec<(Slot, Vec<u8>)>,
+    }
+
+    impl ChallengeResponse for FakeKey {
+        fn requires_touch(&mut self, _slot: Slot) -> Result<bool, YubiKeyError> {
+            Ok(self.touch)
+        }
+
+        fn challenge_response(
+            &mut self,
+            slot: Slot,
+            challenge: &[u8],
+            timeout: Duration,
+        ) -> Result<[u8; RESPONSE_LEN], YubiKeyError> {
+            self.challenges.push((slot, challenge.to_vec()));
+            if self.configured != Some(slot) {
+                return Err(YubiKeyError::SlotNotConfigured(slot));
+            }
+            if self.touch && !self.touched {
+                return Err(YubiKeyError::TouchTimeout(timeout));
+            }
+            Ok(RESPONSE)
+        }
+    }
+
+    fn key_in_slot_two() -> FakeKey {
+        FakeKey {
+            configured: Some(Slot::Two),
+            ..FakeKey::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(parse_source("yubikey").unwrap(), Slot::Two);
+        assert_eq!(parse_source("yubikey:1").unwrap(), Slot::One);
+        assert_eq!(parse_source("yubikey:2").unwrap(), Slot::Two);
+        assert!(matches!(
+            parse_source("yubikey:3"),
+            Err(YubiKeyError::InvalidSlot(slot)) if slot == "3"
+        ));
+        for spec in ["keyring", "yubikey2", "fido2:1", ""] {
+            assert!(matches!(
+                parse_source(spec),
+                Err(YubiKeyError::InvalidSource)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_challenge() {
+        assert_eq!(challenge("personal").unwrap(), b"flowerpassword:personal");
+        assert_eq!(challenge("").unwrap(), b"flowerpassword:");
+        let longest = "x".repeat(MAX_CHALLENGE_LEN - CHALLENGE_PREFIX.len());
+        assert_eq!(challenge(&longest).unwrap().len(), MAX_CHALLENGE_LEN);
+        assert!(matches!(
+            challenge(&format!("{}x", longest)),
+            Err(YubiKeyError::LabelTooLong(64))
+        ));
+    }
+
+    #[test]
+    fn test_master_from_yubikey() {
+        let mut key = key_in_slot_two();
+        let master =
+            master_from_yubikey(&mut key, Slot::Two, "personal", DEFAULT_TIMEOUT, || {}).unwrap();
+        assert_eq!(*master, "3f8a01c25e77900bd416a9ee42005c816df328b7");
+        assert_eq!(
+            key.challenges,
+            vec![(Slot::Two, b"flowerpassword:personal".to_vec())]
+        );
+        assert_eq!(
+            fp_code(&master, "github.com", 16).unwrap(),
+            "KfB14790b8B6DF93"
+        );
+    }
+
+    #[test]
+    fn test_labels_give_different_challenges() {
+        let mut key = key_in_slot_two();
+        for label in ["personal", "work"] {
+            master_from_yubikey(&mut key, Slot::Two, label, DEFAULT_TIMEOUT, || {}).unwrap();
+        }
+        assert_ne!(key.challenges[0].1, key.challenges[1].1);
+    }
+
+    #[test]
+    fn test_touch_notice() {
+        let mut key = FakeKey {
+            touch: true,
+            touched: true,
+            ..key_in_slot_two()
+        };
+        let mut noticed = false;
+        master_from_yubikey(&mut key, Slot::Two, "personal", DEFAULT_TIMEOUT, || {
+            noticed = true
+        })
+        .unwrap();
+        assert!(noticed);
+
+        let mut noticed = false;
+        master_from_yubikey(key_in_slot_two(), Slot::Two, "a", DEFAULT_TIMEOUT, || {
+            noticed = true
+        })
+        .unwrap();
+        assert!(!noticed);
+    }
+
+    #[test]
+    fn test_touch_timeout() {
+        let key = FakeKey {
+            touch: true,
+            ..key_in_slot_two()
+        };
+        let error = master_from_yubikey(key, Slot::Two, "personal", Duration::from_secs(5), || {})
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YubiKey was not touched within 5 seconds"
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_slot() {
+        let error = master_from_yubikey(
+            key_in_slot_two(),
+            Slot::One,
+            "personal",
+            DEFAULT_TIMEOUT,
+            || {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YubiKey slot 1 is not configured for challenge-response"
+        );
+    }
+
+    #[test]
+    fn test_label_checked_before_device() {
+        let mut key = key_in_slot_two();
+        let label = "x".repeat(MAX_CHALLENGE_LEN);
+        assert!(matches!(
+            master_from_yubikey(&mut key, Slot::Two, &label, DEFAULT_TIMEOUT, || {}),
+            Err(YubiKeyError::LabelTooLong(_))
+        ));
+        assert!(key.challenges.is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+//! Builds the `component` guest, wraps it into a component and calls it through
+//! wasmtime's component API
+
+use flowerpassword::vectors::KNOWN_ANSWERS;
+use std::path::Path;
+use std::process::Command;
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Config, Engine, Store};
+
+wasmtime::component::bindgen!({
+    path: "../../wit",
+    world: "flowerpassword",
+});
+
+use exports::flowerpassword::core::derive::ErrorKind;
+
+/// Builds the guest core module and encodes it as a component
+fn build_component() -> Vec<u8> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(&root)
+        .args([
+            "rustc",
+            "--lib",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--features",
+            "component",
+            "--crate-type",
+            "cdylib",
+        ])
+        .status()
+        .expect("cargo runs");
+    assert!(status.success(), "building the guest failed");
+
+    let module =
+        std::fs::read(root.join("target/wasm32-unknown-unknown/release/flowerpassword.wasm"))
+            .unwrap();
+    wit_component::ComponentEncoder::default()
+        .module(&module)
+        .unwrap()
+        .validate(true)
+        .encode()
+        .unwrap()
+}
+
+fn instantiate() -> (Store<()>, Flowerpassword) {
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config).unwrap();
+    let component = Component::new(&engine, build_component()).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance =
+        Flowerpassword::instantiate(&mut store, &component, &Linker::new(&engine)).unwrap();
+    (store, instance)
+}
+
+#[test]
+fn test_component() {
+    let (mut store, instance) = instantiate();
+    let derive = instance.flowerpassword_core_derive();
+
+    for vector in KNOWN_ANSWERS {
+        let password = derive
+            .call_code(
+                &mut store,
+                vector.password,
+                vector.key,
+                vector.length as u32,
+            )
+            .unwrap();
+        assert_eq!(password.as_deref(), Ok(vector.expected));
+    }
+
+    let error = derive
+        .call_code(&mut store, "test", "github.com", 99)
+        .unwrap();
+    assert_eq!(error, Err(ErrorKind::InvalidLength));
+}
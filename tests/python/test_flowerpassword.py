@@ -0,0 +1,109 @@
+"""Tests for the Python bindings.
+
+Run from the repository root after building the extension module:
+
+    maturin develop --extras test
+    pytest
+"""
+
+import threading
+
+import pytest
+
+from flowerpassword import FpGenerator, fp_code, fp_verify
+
+# A subset of the known-answer vectors in src/vectors.rs
+VECTORS = [
+    ("password", "key", 16, "K3A2a66Bf88b628c"),
+    ("password", "key", 2, "K3"),
+    ("password", "key", 32, "K3A2a66Bf88b628c2Cd7cDA9958f6b26"),
+    ("test", "github.com", 16, "D04175F7A9c7Ab4a"),
+    ("mypassword", "example.com", 12, "K0CA12CecFFB"),
+    ("password", "", 16, "eB3b1cA3D6B54c00"),
+    ("", "", 16, "K930B0264e62DDFC"),
+    ("密码", "网站.com", 16, "KFF7FEa7928bAAAa"),
+]
+
+
+@pytest.mark.parametrize("password,key,length,expected", VECTORS)
+def test_known_vectors(password, key, length, expected):
+    assert fp_code(password, key, length) == expected
+
+
+def test_default_length_is_16():
+    assert fp_code("test", "github.com") == "D04175F7A9c7Ab4a"
+
+
+@pytest.mark.parametrize("length", [0, 1, 33, 100])
+def test_invalid_length_raises_value_error(length):
+    with pytest.raises(ValueError) as excinfo:
+        fp_code("password", "key", length)
+    assert str(excinfo.value) == f"Length must be between 2 and 32, got: {length}"
+
+
+def test_negative_length_is_rejected():
+    with pytest.raises(OverflowError):
+        fp_code("password", "key", -1)
+
+
+@pytest.mark.parametrize(
+    "args",
+    [
+        (b"password", "key", 16),
+        ("password", b"key", 16),
+    ],
+)
+def test_bytes_inputs_raise_type_error(args):
+    with pytest.raises(TypeError, match="'bytes' object cannot be converted to 'PyString'"):
+        fp_code(*args)
+
+
+def test_verify():
+    assert fp_verify("test", "github.com", "D04175F7A9c7Ab4a")
+    assert fp_verify("password", "key", "K3")
+    assert not fp_verify("test", "gitlab.com", "D04175F7A9c7Ab4a")
+    assert not fp_verify("password", "key", "K")
+
+
+def test_generator():
+    generator = FpGenerator("password")
+    assert generator.length == 16
+    assert generator.generate("key") == "K3A2a66Bf88b628c"
+    assert generator.generate("key", 8) == "K3A2a66B"
+    assert generator.verify("key", "K3A2a66Bf88b628c")
+    assert not generator.verify("other", "K3A2a66Bf88b628c")
+
+
+def test_generator_many():
+    generator = FpGenerator("test", length=12)
+    keys = ["github.com", "example.com", "key"]
+    assert generator.generate_many(keys) == [fp_code("test", key, 12) for key in keys]
+
+
+def test_generator_rejects_invalid_length():
+    with pytest.raises(ValueError, match="got: 40"):
+        FpGenerator("password", 40)
+    with pytest.raises(ValueError, match="got: 1"):
+        FpGenerator("password").generate("key", 1)
+
+
+def test_generator_repr_hides_master():
+    generator = FpGenerator("hunter2", 20)
+    assert repr(generator) == "FpGenerator(length=20)"
+    assert "hunter2" not in repr(generator)
+
+
+def test_threads_generate_in_parallel():
+    generator = FpGenerator("password")
+    results = {}
+
+    def worker(index):
+        results[index] = generator.generate_many([f"site{i}.com" for i in range(200)])
+
+    threads = [threading.Thread(target=worker, args=(index,)) for index in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert all(result == results[0] for result in results.values())
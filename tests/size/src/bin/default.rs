@@ -0,0 +1,110 @@
+//! `fp_code` as a default-featured caller uses it: a heap for the `String`, and
+//! the error rendered with `Display`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::hint::black_box;
+use core::panic::PanicInfo;
+use flowerpassword_core::fp_code;
+
+#[panic_handler]
+fn panic(_: &PanicInfo) -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// The smallest heap that works: a bump allocator that never frees
+struct Bump(UnsafeCell<([u8; 1024], usize)>);
+
+unsafe impl Sync for Bump {}
+
+unsafe impl GlobalAlloc for Bump {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (heap, next) = &mut *self.0.get();
+        let start = (*next + layout.align() - 1) & !(layout.align() - 1);
+        match heap.get_mut(start..start + layout.size()) {
+            Some(block) => {
+                *next = start + layout.size();
+                block.as_mut_ptr()
+            }
+            None => core::ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, _: *mut u8, _: Layout) {}
+}
+
+#[global_allocator]
+static HEAP: Bump = Bump(UnsafeCell::new(([0; 1024], 0)));
+
+/// Writes `bytes` into `out`, truncating
+struct Buffer<'a>(&'a mut [u8], usize);
+
+impl Write for Buffer<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            if let Some(slot) = self.0.get_mut(self.1) {
+                *slot = byte;
+                self.1 += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Derives into `out`, returning the bytes written: the password, or the error
+/// message
+///
+/// # Safety
+///
+/// Each pointer must be valid for its length, and `password` and `key` must be
+/// UTF-8.
+unsafe fn derive(
+    password: *const u8,
+    password_len: usize,
+    key: *const u8,
+    key_len: usize,
+    length: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> usize {
+    let password =
+        core::str::from_utf8_unchecked(core::slice::from_raw_parts(password, password_len));
+    let key = core::str::from_utf8_unchecked(core::slice::from_raw_parts(key, key_len));
+    let mut out = Buffer(core::slice::from_raw_parts_mut(out, out_len), 0);
+    let _ = match fp_code(password, key, length) {
+        Ok(password) => out.write_str(&password),
+        Err(e) => write!(out, "{}", e),
+    };
+    out.1
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    // Opaque inputs, so the derivation is neither folded away nor discarded
+    let input = black_box(*b"input");
+    let mut out = [0; 64];
+    let length = black_box(16);
+    let written = unsafe {
+        derive(
+            input.as_ptr(),
+            5,
+            input.as_ptr(),
+            5,
+            length,
+            out.as_mut_ptr(),
+            64,
+        )
+    };
+    black_box((written, out));
+    loop {
+        core::hint::spin_loop();
+    }
+}
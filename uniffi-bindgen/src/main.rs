@@ -0,0 +1,11 @@
+//! Bindings generator for the `uniffi` feature
+//!
+//! ```sh
+//! cargo rustc --lib --features uniffi --crate-type cdylib
+//! cargo run -p uniffi-bindgen -- generate --library target/debug/libflowerpassword.so \
+//!     --language kotlin --language swift --out-dir target/bindings
+//! ```
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}